@@ -2,7 +2,7 @@
 //! Battery API endpoints
 
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
@@ -10,10 +10,12 @@ use axum::{
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
+use std::sync::Arc;
+
 use crate::{
     auth::AuthBearer,
     controller::{AppState, BatteryStateSample},
-    domain::HealthStatus,
+    domain::{Battery, BatteryCapabilities, BatteryState, HealthStatus},
 };
 
 /// Get current battery state
@@ -142,7 +144,80 @@ pub async fn get_battery_statistics(
     }
 }
 
+/// List every battery pack plus the aggregate the controller dispatches
+pub async fn list_battery_packs(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+) -> impl IntoResponse {
+    let packs = futures::future::join_all(
+        st.controller
+            .battery_packs()
+            .into_iter()
+            .map(|(id, battery)| pack_status(id, battery)),
+    )
+    .await;
+
+    let aggregate = pack_status("aggregate".to_string(), Arc::clone(&st.controller.battery)).await;
+    (StatusCode::OK, Json(BatteryPacksResponse { aggregate, packs })).into_response()
+}
+
+/// Get one battery pack by id
+pub async fn get_battery_pack(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match st
+        .controller
+        .battery_packs()
+        .into_iter()
+        .find(|(pack_id, _)| *pack_id == id)
+    {
+        Some((id, battery)) => (StatusCode::OK, Json(pack_status(id, battery).await)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("No battery pack '{}'", id),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Read a pack; a pack that fails to answer is reported, not hidden
+async fn pack_status(id: String, battery: Arc<dyn Battery>) -> BatteryPackStatus {
+    let (state, health) = tokio::join!(battery.read_state(), battery.health_check());
+    let error = match (&state, &health) {
+        (Err(e), _) | (_, Err(e)) => Some(e.to_string()),
+        _ => None,
+    };
+    BatteryPackStatus {
+        id,
+        state: state.ok(),
+        health: health.ok(),
+        capabilities: battery.capabilities(),
+        error,
+    }
+}
+
 // Response types
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+pub struct BatteryPackStatus {
+    pub id: String,
+    pub state: Option<BatteryState>,
+    pub health: Option<HealthStatus>,
+    pub capabilities: BatteryCapabilities,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+pub struct BatteryPacksResponse {
+    pub aggregate: BatteryPackStatus,
+    pub packs: Vec<BatteryPackStatus>,
+}
+
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
 pub struct HealthResponse {
@@ -1,16 +1,21 @@
 #![allow(dead_code)]
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 use crate::{
     auth::AuthBearer,
     controller::AppState,
-    domain::{ChargerState, ChargerStatus},
+    domain::{
+        ChargePoint, ChargerCapabilities, ChargerState, ChargerStatus, EvCharger, VehiclePlan,
+    },
+    power_flow::EvAllocationPolicy,
 };
 
 #[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
@@ -33,6 +38,180 @@ pub struct StartChargingRequest {
     pub max_current_amps: Option<f64>,
 }
 
+/// One charger of the site with its allocation and vehicle plan
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Serialize)]
+pub struct EvChargerStatus {
+    pub id: String,
+    pub state: Option<ChargerState>,
+    pub capabilities: ChargerCapabilities,
+    pub priority: u8,
+    /// Current granted by the last control tick (A)
+    pub allocated_current_a: Option<f64>,
+    pub plan: Option<VehiclePlan>,
+    pub next_departure: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Serialize)]
+pub struct EvChargersResponse {
+    pub policy: EvAllocationPolicy,
+    pub chargers: Vec<EvChargerStatus>,
+}
+
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Deserialize)]
+pub struct SetDepartureRequest {
+    /// One-off departure; `null` falls back to the daily departure
+    pub departure_time: Option<DateTime<Utc>>,
+    pub target_soc_percent: Option<f64>,
+    pub vehicle_capacity_kwh: Option<f64>,
+}
+
+/// List every charger sharing the fuse
+///
+/// Sites without `[[ev_chargers]]` report their single charger as "main".
+pub async fn list_ev_chargers(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+) -> impl IntoResponse {
+    let response = match st.controller.ev_charger_group {
+        Some(ref group) => EvChargersResponse {
+            policy: group.policy(),
+            chargers: futures::future::join_all(
+                group
+                    .chargers()
+                    .iter()
+                    .map(|c| charge_point_status(c, group.timezone())),
+            )
+            .await,
+        },
+        None => EvChargersResponse {
+            policy: EvAllocationPolicy::default(),
+            chargers: vec![single_charger_status(Arc::clone(&st.controller.ev_charger)).await],
+        },
+    };
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+/// Get one charger by id
+pub async fn get_ev_charger(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let status = match st.controller.ev_charger_group {
+        Some(ref group) => match group.charger(&id) {
+            Some(charge_point) => Some(charge_point_status(charge_point, group.timezone()).await),
+            None => None,
+        },
+        None if id == "main" => {
+            Some(single_charger_status(Arc::clone(&st.controller.ev_charger)).await)
+        }
+        None => None,
+    };
+
+    match status {
+        Some(status) => (StatusCode::OK, Json(status)).into_response(),
+        None => charger_not_found(&id),
+    }
+}
+
+/// Set the departure and target of the vehicle on a charger
+///
+/// Used by the `deadline` allocation policy and by each vehicle's urgency.
+pub async fn set_ev_charger_departure(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+    Path(id): Path<String>,
+    Json(req): Json<SetDepartureRequest>,
+) -> impl IntoResponse {
+    let Some((charge_point, timezone)) = st
+        .controller
+        .ev_charger_group
+        .as_ref()
+        .and_then(|g| g.charger(&id).map(|c| (c, g.timezone())))
+    else {
+        return charger_not_found(&id);
+    };
+
+    if let Some(target) = req.target_soc_percent {
+        if !(10.0..=100.0).contains(&target) {
+            return bad_request("target_soc_percent must be between 10 and 100");
+        }
+    }
+    if let Some(capacity) = req.vehicle_capacity_kwh {
+        if !(1.0..=250.0).contains(&capacity) {
+            return bad_request("vehicle_capacity_kwh must be between 1 and 250");
+        }
+    }
+    if req.departure_time.is_some_and(|d| d <= Utc::now()) {
+        return bad_request("departure_time must be in the future");
+    }
+
+    let mut plan = charge_point.plan().await;
+    plan.departure_time = req.departure_time;
+    if let Some(target) = req.target_soc_percent {
+        plan.target_soc_percent = target;
+    }
+    if let Some(capacity) = req.vehicle_capacity_kwh {
+        plan.capacity_kwh = capacity;
+    }
+    charge_point.set_plan(plan).await;
+
+    (StatusCode::OK, Json(charge_point_status(charge_point, timezone).await)).into_response()
+}
+
+async fn charge_point_status(charge_point: &ChargePoint, timezone: chrono_tz::Tz) -> EvChargerStatus {
+    let plan = charge_point.plan().await;
+    let mut status = single_charger_status(Arc::clone(&charge_point.charger)).await;
+    status.id = charge_point.id.clone();
+    status.priority = charge_point.priority;
+    status.allocated_current_a = Some(charge_point.allocated_current_a().await);
+    status.next_departure = plan.next_departure(Utc::now(), timezone);
+    status.plan = Some(plan);
+    status
+}
+
+/// Read a charger; one that fails to answer is reported, not hidden
+async fn single_charger_status(charger: Arc<dyn EvCharger>) -> EvChargerStatus {
+    let (state, error) = match charger.read_state().await {
+        Ok(state) => (Some(state), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    EvChargerStatus {
+        id: "main".to_string(),
+        state,
+        capabilities: charger.capabilities(),
+        priority: 0,
+        allocated_current_a: None,
+        plan: None,
+        next_departure: None,
+        error,
+    }
+}
+
+fn charger_not_found(id: &str) -> axum::response::Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({
+            "error": format!("No EV charger '{}'", id)
+        })),
+    )
+        .into_response()
+}
+
+fn bad_request(message: &str) -> axum::response::Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "error": message
+        })),
+    )
+        .into_response()
+}
+
 /// Get EV charger current state
 pub async fn get_charger_state(
     State(_st): State<AppState>,
@@ -57,6 +57,57 @@ pub async fn get_grid_statistics(
     }
 }
 
+/// Get the committed frequency reserve and its activation log
+pub async fn get_reserve_status(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+) -> impl IntoResponse {
+    match st.controller.get_reserve_status().await {
+        Some(status) => (StatusCode::OK, Json(status)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Frequency reserve mode is not enabled".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Get the backup mode, outage reserve and outage log
+pub async fn get_backup_status(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+) -> impl IntoResponse {
+    match st.controller.get_backup_status().await {
+        Some(status) => (StatusCode::OK, Json(status)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Backup operation is not enabled".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Get the export limit and the export limit loop's last step
+pub async fn get_export_limit_status(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+) -> impl IntoResponse {
+    match st.controller.get_export_limit_status().await {
+        Some(status) => (StatusCode::OK, Json(status)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Export limitation is not enabled".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
 pub struct ErrorResponse {
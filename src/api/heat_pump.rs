@@ -0,0 +1,30 @@
+#![allow(dead_code)]
+//! Heat pump API endpoints
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{api::grid::ErrorResponse, auth::AuthBearer, controller::AppState};
+
+/// Get the heat pump's latest reading and pre-heating plan
+pub async fn get_heat_pump_status(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+) -> impl IntoResponse {
+    match st.controller.get_heat_pump_status().await {
+        Some(Ok(status)) => (StatusCode::OK, Json(status)).into_response(),
+        Some(Err(e)) => (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Heat pump control is not enabled".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
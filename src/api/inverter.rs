@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     auth::AuthBearer,
     controller::AppState,
-    domain::{InverterMode, InverterState, InverterStatus},
+    domain::{InverterError, InverterMode, InverterState, InverterStatus},
 };
 
 #[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
@@ -35,23 +35,12 @@ pub struct SetExportLimitRequest {
 
 /// Get inverter current state
 pub async fn get_inverter_state(
-    State(_st): State<AppState>,
+    State(st): State<AppState>,
     AuthBearer: AuthBearer,
 ) -> impl IntoResponse {
-    // TODO: Get from actual inverter device when implemented
-    let state = InverterState {
-        mode: InverterMode::GridTied,
-        pv_power_w: 0.0,
-        ac_output_power_w: 0.0,
-        dc_input_power_w: 0.0,
-        grid_frequency_hz: 50.0,
-        ac_voltage_v: 230.0,
-        dc_voltage_v: 400.0,
-        temperature_c: 35.0,
-        efficiency_percent: 97.0,
-        status: InverterStatus::Normal,
-        daily_energy_kwh: 0.0,
-        total_energy_kwh: 0.0,
+    let state = match st.controller.inverter.read_state().await {
+        Ok(state) => state,
+        Err(e) => return inverter_error(e, "Failed to read inverter state"),
     };
 
     let response = InverterResponse {
@@ -65,11 +54,14 @@ pub async fn get_inverter_state(
 
 /// Set inverter operating mode
 pub async fn set_inverter_mode(
-    State(_st): State<AppState>,
+    State(st): State<AppState>,
     AuthBearer: AuthBearer,
     Json(req): Json<SetModeRequest>,
 ) -> impl IntoResponse {
-    // TODO: Actually set mode on real inverter
+    if let Err(e) = st.controller.inverter.set_mode(req.mode).await {
+        return inverter_error(e, "Failed to set inverter mode");
+    }
+
     (
         StatusCode::OK,
         Json(serde_json::json!({
@@ -82,7 +74,7 @@ pub async fn set_inverter_mode(
 
 /// Set export power limit
 pub async fn set_export_limit(
-    State(_st): State<AppState>,
+    State(st): State<AppState>,
     AuthBearer: AuthBearer,
     Json(req): Json<SetExportLimitRequest>,
 ) -> impl IntoResponse {
@@ -97,7 +89,10 @@ pub async fn set_export_limit(
             .into_response();
     }
 
-    // TODO: Actually set limit on real inverter
+    if let Err(e) = st.controller.inverter.set_export_limit(req.limit_watts).await {
+        return inverter_error(e, "Failed to set export limit");
+    }
+
     (
         StatusCode::OK,
         Json(serde_json::json!({
@@ -108,6 +103,27 @@ pub async fn set_export_limit(
         .into_response()
 }
 
+/// Map an inverter failure to a response
+///
+/// Requests the device refuses are the caller's fault (400/409); anything
+/// else means we could not talk to the inverter (502).
+fn inverter_error(e: anyhow::Error, context: &str) -> axum::response::Response {
+    let status = match e.downcast_ref::<InverterError>() {
+        Some(InverterError::InvalidModeTransition(..)) => StatusCode::CONFLICT,
+        Some(InverterError::ExportLimitNotSupported) | Some(InverterError::InvalidExportLimit(_)) => {
+            StatusCode::BAD_REQUEST
+        }
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    (
+        status,
+        Json(serde_json::json!({
+            "error": format!("{}: {}", context, e)
+        })),
+    )
+        .into_response()
+}
+
 /// Get inverter production history
 pub async fn get_production_history(
     State(_st): State<AppState>,
@@ -0,0 +1,139 @@
+#![allow(dead_code)]
+//! Controllable load API endpoints
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::grid::ErrorResponse,
+    auth::AuthBearer,
+    controller::AppState,
+    domain::{LoadConstraints, LoadOverride, LoadSchedule, LoadState, ManagedLoad},
+};
+
+/// One switchable load with its plan
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Serialize)]
+pub struct LoadStatus {
+    pub id: String,
+    pub state: Option<LoadState>,
+    pub constraints: LoadConstraints,
+    pub schedule: Option<LoadSchedule>,
+    /// Manual on/off replacing the schedule, while in force
+    #[serde(rename = "override")]
+    pub override_: Option<LoadOverride>,
+    /// Runtime reached in the window under way
+    pub runtime_done_minutes: u32,
+    pub error: Option<String>,
+}
+
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Deserialize)]
+pub struct SetLoadOverrideRequest {
+    pub on: bool,
+    pub until: DateTime<Utc>,
+}
+
+/// List every controllable load
+pub async fn list_loads(State(st): State<AppState>, AuthBearer: AuthBearer) -> impl IntoResponse {
+    let loads = match st.controller.controllable_loads {
+        Some(ref group) => futures::future::join_all(group.loads().iter().map(load_status)).await,
+        None => Vec::new(),
+    };
+    (StatusCode::OK, Json(loads)).into_response()
+}
+
+/// Get one load by id
+pub async fn get_load(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match find_load(&st, &id) {
+        Some(load) => (StatusCode::OK, Json(load_status(load).await)).into_response(),
+        None => load_not_found(&id),
+    }
+}
+
+/// Force a load on or off until a given time
+///
+/// The control tick still holds back a start the main fuse has no room for.
+pub async fn set_load_override(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+    Path(id): Path<String>,
+    Json(req): Json<SetLoadOverrideRequest>,
+) -> impl IntoResponse {
+    let Some(load) = find_load(&st, &id) else {
+        return load_not_found(&id);
+    };
+    if req.until <= Utc::now() {
+        return error_response(StatusCode::BAD_REQUEST, "until must be in the future");
+    }
+    load.set_override(Some(LoadOverride {
+        on: req.on,
+        until: req.until,
+    }))
+    .await;
+
+    (StatusCode::OK, Json(load_status(load).await)).into_response()
+}
+
+/// Hand a load back to its schedule
+pub async fn clear_load_override(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Some(load) = find_load(&st, &id) else {
+        return load_not_found(&id);
+    };
+    load.set_override(None).await;
+
+    (StatusCode::OK, Json(load_status(load).await)).into_response()
+}
+
+fn find_load<'a>(st: &'a AppState, id: &str) -> Option<&'a ManagedLoad> {
+    st.controller.controllable_loads.as_ref()?.load(id)
+}
+
+/// Read a load; one that fails to answer is reported, not hidden
+async fn load_status(load: &ManagedLoad) -> LoadStatus {
+    let now = Utc::now();
+    let (state, error) = match load.load.read_state().await {
+        Ok(state) => (Some(state), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    LoadStatus {
+        id: load.id.clone(),
+        state,
+        constraints: load.constraints.clone(),
+        schedule: load.schedule().await,
+        override_: load.override_at(now).await,
+        runtime_done_minutes: load.runtime_done_minutes(now).await,
+        error,
+    }
+}
+
+fn load_not_found(id: &str) -> axum::response::Response {
+    error_response(
+        StatusCode::NOT_FOUND,
+        &format!("No controllable load '{}'", id),
+    )
+}
+
+fn error_response(status: StatusCode, message: &str) -> axum::response::Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.to_string(),
+        }),
+    )
+        .into_response()
+}
@@ -4,7 +4,9 @@ pub mod v1;
 pub mod battery;
 pub mod ev_charger;
 pub mod grid;
+pub mod heat_pump;
 pub mod inverter;
+pub mod loads;
 pub mod weather;
 pub mod error;
 pub mod response;
@@ -23,9 +25,16 @@ use tower::ServiceBuilder;
 use crate::{config::Config, controller::AppState};
 
 pub fn router(state: AppState, cfg: &Config) -> Router {
+    let central_system = state.ocpp.clone();
     let mut router = Router::new()
         .nest("/api/v1", v1::router(state, cfg));
 
+    // Chargers authenticate with per-charge-point Basic auth instead of the
+    // API bearer token, so the OCPP endpoint sits next to the API rather than inside it
+    if let (Some(central_system), Some(ocpp)) = (central_system, cfg.hardware.ocpp.as_ref()) {
+        router = router.merge(central_system.router(&ocpp.central_system_path));
+    }
+
     if cfg.server.enable_cors {
         use tower_http::cors::AllowOrigin;
         let cors = CorsLayer::new()
@@ -1,11 +1,16 @@
 #![allow(dead_code)]
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::{
     api::{error::ApiError, response::ApiResponse},
-    controller::AppState,
+    controller::{AppState, OptimizationRunRecord},
+    optimizer::explain::{Baseline, ScheduleExplanation},
 };
 
 /// Optimization status response
@@ -45,11 +50,50 @@ pub struct OptimizationHistoryResponse {
 /// Optimization run
 #[derive(Debug, Serialize)]
 pub struct OptimizationRun {
-    id: i64,
+    id: Uuid,
     created_at: DateTime<Utc>,
     duration_ms: i64,
     objective: String,
+    optimizer_version: Option<String>,
     success: bool,
+    error_message: Option<String>,
+    /// Expected cost of the schedule (SEK)
+    cost_estimate_sek: Option<f64>,
+    savings_vs_no_battery_sek: Option<f64>,
+    savings_vs_self_consumption_sek: Option<f64>,
+}
+
+impl From<OptimizationRunRecord> for OptimizationRun {
+    fn from(run: OptimizationRunRecord) -> Self {
+        let explanation = run.explanation.as_ref();
+        Self {
+            id: run.id,
+            created_at: run.created_at,
+            duration_ms: run.duration_ms,
+            objective: run.objective,
+            optimizer_version: run.optimizer_version,
+            success: run.success,
+            error_message: run.error_message,
+            cost_estimate_sek: explanation.map(|e| e.total.total_sek),
+            savings_vs_no_battery_sek: explanation.and_then(|e| e.savings_sek(Baseline::NoBattery)),
+            savings_vs_self_consumption_sek: explanation
+                .and_then(|e| e.savings_sek(Baseline::SelfConsumption)),
+        }
+    }
+}
+
+/// Query for the optimization history
+#[derive(Debug, Deserialize)]
+pub struct OptimizationHistoryQuery {
+    /// Number of runs, newest first (default 20, at most 200)
+    limit: Option<usize>,
+}
+
+/// Query for a run's explanation
+#[derive(Debug, Deserialize)]
+pub struct ExplainQuery {
+    /// Run to explain; the latest successful run when omitted
+    run_id: Option<Uuid>,
 }
 
 /// POST /api/v1/optimize/trigger - Force trigger optimization
@@ -90,29 +134,46 @@ pub async fn get_optimization_status(
 }
 
 /// GET /api/v1/optimize/history - Get optimization history
-#[cfg(feature = "db")]
 pub async fn get_optimization_history(
-    State(_state): State<AppState>,
-) -> Result<Json<ApiResponse<OptimizationHistoryResponse>>, ApiError> {
-    // TODO: Fetch from database
-    let response = OptimizationHistoryResponse {
-        runs: vec![],
-        total: 0,
-    };
-
-    Ok(Json(ApiResponse::success(response)))
-}
-
-#[cfg(not(feature = "db"))]
-pub async fn get_optimization_history(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    Query(query): Query<OptimizationHistoryQuery>,
 ) -> Result<Json<ApiResponse<OptimizationHistoryResponse>>, ApiError> {
+    let limit = query.limit.unwrap_or(20).clamp(1, 200);
+    let runs: Vec<OptimizationRun> = state
+        .controller
+        .get_optimization_history(limit)
+        .await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .into_iter()
+        .map(OptimizationRun::from)
+        .collect();
+
+    let total = runs.len();
     Ok(Json(ApiResponse::success(OptimizationHistoryResponse {
-        runs: vec![],
-        total: 0,
+        runs,
+        total,
     })))
 }
 
+/// GET /api/v1/optimize/explain - Per-interval cost breakdown of a run and
+/// its comparison with the no-battery and self-consumption baselines
+pub async fn explain_optimization(
+    State(state): State<AppState>,
+    Query(query): Query<ExplainQuery>,
+) -> Result<Json<ApiResponse<ScheduleExplanation>>, ApiError> {
+    let explanation = state
+        .controller
+        .get_explanation(query.run_id)
+        .await
+        .map_err(|e| ApiError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| match query.run_id {
+            Some(id) => ApiError::NotFound(format!("No explanation for optimization run {id}")),
+            None => ApiError::NotFound("No successful optimization run yet".to_string()),
+        })?;
+
+    Ok(Json(ApiResponse::success(explanation)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
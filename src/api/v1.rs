@@ -17,13 +17,16 @@ use crate::{
 
 pub fn router(state: AppState, cfg: &Config) -> Router {
     #[allow(unused_imports)]
-    use crate::api::{battery, ev_charger, grid, inverter, weather};
+    use crate::api::{battery, ev_charger, grid, heat_pump, inverter, loads, optimize, weather};
 
     Router::new()
         .route("/status", get(get_status))
+        .route("/status/loops", get(get_control_loops))
         .route("/forecast", get(get_forecast))
         .route("/schedule", get(get_schedule).post(set_schedule))
         .route("/optimize", post(trigger_optimization))
+        .route("/optimize/history", get(optimize::get_optimization_history))
+        .route("/optimize/explain", get(optimize::explain_optimization))
         .route("/devices", get(list_devices))
         .route("/simulation/step", post(simulation_step))
         .route("/healthz", get(healthz))
@@ -37,6 +40,8 @@ pub fn router(state: AppState, cfg: &Config) -> Router {
         .route("/battery/power", post(battery::set_battery_power))
         .route("/battery/history", get(battery::get_battery_history))
         .route("/battery/statistics", get(battery::get_battery_statistics))
+        .route("/batteries", get(battery::list_battery_packs))
+        .route("/batteries/:id", get(battery::get_battery_pack))
         // EV Charger routes
         .route("/ev-charger/state", get(ev_charger::get_charger_state))
         .route(
@@ -49,6 +54,12 @@ pub fn router(state: AppState, cfg: &Config) -> Router {
             "/ev-charger/sessions",
             get(ev_charger::get_charging_sessions),
         )
+        .route("/ev-chargers", get(ev_charger::list_ev_chargers))
+        .route("/ev-chargers/:id", get(ev_charger::get_ev_charger))
+        .route(
+            "/ev-chargers/:id/departure",
+            post(ev_charger::set_ev_charger_departure),
+        )
         // Inverter routes
         .route("/inverter/state", get(inverter::get_inverter_state))
         .route("/inverter/mode", post(inverter::set_inverter_mode))
@@ -62,6 +73,18 @@ pub fn router(state: AppState, cfg: &Config) -> Router {
         .route("/grid/status", get(grid::get_grid_status))
         .route("/grid/limits", get(grid::get_grid_limits))
         .route("/grid/statistics", get(grid::get_grid_statistics))
+        .route("/grid/reserve", get(grid::get_reserve_status))
+        .route("/grid/backup", get(grid::get_backup_status))
+        .route("/grid/export-limit", get(grid::get_export_limit_status))
+        // Heat pump routes
+        .route("/heat-pump", get(heat_pump::get_heat_pump_status))
+        // Controllable load routes
+        .route("/loads", get(loads::list_loads))
+        .route("/loads/:id", get(loads::get_load))
+        .route(
+            "/loads/:id/override",
+            post(loads::set_load_override).delete(loads::clear_load_override),
+        )
         // Weather routes
         .route("/weather/forecast", get(weather::get_weather_forecast))
         .with_state(state)
@@ -125,6 +148,14 @@ pub async fn get_status(
     }
 }
 
+/// Timing of the fast, medium and slow control loops
+pub async fn get_control_loops(
+    State(st): State<AppState>,
+    AuthBearer: AuthBearer,
+) -> impl IntoResponse {
+    Json(st.controller.get_control_loop_status().await)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ForecastQuery {
     pub horizon_hours: Option<u32>,
@@ -108,7 +108,7 @@ where
 /// - Always iterates over fixed 64-byte buffer
 /// - XORs length difference into result
 /// - No early returns = no timing side channel
-fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
+pub(crate) fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
     const MAX_TOKEN_LEN: usize = 64;
 
     // XOR the length difference into result (contributes to failure if lengths differ)
@@ -5,10 +5,13 @@ use figment::{
     Figment,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use validator::Validate;
 
+use crate::domain::BatteryChemistry;
+
 /// Top-level application configuration
 #[derive(Debug, Clone, Deserialize, Serialize, Validate)]
 pub struct AppConfig {
@@ -27,6 +30,69 @@ pub struct AppConfig {
     #[validate(nested)]
     pub battery: BatteryConfig,
 
+    /// Individual packs when the site has more than one battery
+    ///
+    /// When set, capacity and power ratings come from the packs; `battery`
+    /// still sets the system-wide SoC window and replacement cost.
+    #[serde(default)]
+    #[validate(nested)]
+    pub batteries: Vec<BatteryPackConfig>,
+
+    /// How a setpoint is split across `batteries`
+    #[serde(default)]
+    pub battery_dispatch: BatteryDispatch,
+
+    /// EV chargers sharing the main fuse
+    ///
+    /// When empty, a single charger is created from `hardware`.
+    #[serde(default)]
+    #[validate(nested)]
+    pub ev_chargers: Vec<EvChargerConfig>,
+
+    /// How fuse headroom is shared between `ev_chargers`
+    #[serde(default)]
+    pub ev_allocation: EvAllocation,
+
+    /// Grid connection: main fuse and import/export limits
+    #[serde(default)]
+    #[validate(nested)]
+    pub grid: GridConfig,
+
+    /// Peak-power tariff ("effekttariff") rules and the monthly peak ledger
+    #[serde(default)]
+    #[validate(nested)]
+    pub peak_tariff: PeakTariffConfig,
+
+    /// Frequency reserve (FCR-D/FFR) capacity sold through an aggregator
+    #[serde(default)]
+    #[validate(nested)]
+    pub reserve: ReserveConfig,
+
+    /// Heat pump steered as a flexible load
+    #[serde(default)]
+    #[validate(nested)]
+    pub heat_pump: HeatPumpConfig,
+
+    /// Switchable loads run in the cheapest periods of their daily window
+    #[serde(default)]
+    #[validate(nested)]
+    pub controllable_loads: Vec<ControllableLoadConfig>,
+
+    /// Backup operation during grid outages and the outage reserve
+    #[serde(default)]
+    #[validate(nested)]
+    pub backup: BackupConfig,
+
+    /// Closed-loop export limitation for capped (or zero) export connections
+    #[serde(default)]
+    #[validate(nested)]
+    pub export_limit: ExportLimitConfig,
+
+    /// Sub-second loop tracking the grid meter between control ticks
+    #[serde(default)]
+    #[validate(nested)]
+    pub fast_loop: FastLoopConfig,
+
     #[validate(nested)]
     pub hardware: HardwareConfig,
 
@@ -180,6 +246,169 @@ pub struct BatteryConfig {
     #[serde(default = "default_ambient_temp_c")]
     #[validate(range(min = -40.0, max = 50.0))]
     pub ambient_temp_c: f64,
+
+    /// Cell chemistry, selects the degradation curve used to price wear
+    /// when the battery does not report it
+    #[serde(default = "default_battery_chemistry")]
+    pub chemistry: BatteryChemistry,
+}
+
+/// One pack of a multi-battery site
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct BatteryPackConfig {
+    /// Identifier used in the API, e.g. "garage"
+    #[validate(length(min = 1, max = 64))]
+    pub id: String,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub battery: BatteryConfig,
+
+    /// Modbus unit id of this pack, defaults to `hardware.modbus.default_unit_id`
+    #[serde(default)]
+    #[validate(range(min = 1, max = 247))]
+    pub unit_id: Option<u8>,
+
+    /// Device profile of this pack, defaults to `hardware.modbus.battery_profile`
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    /// Dispatch order under `priority`, lowest first
+    #[serde(default)]
+    pub priority: u8,
+
+    /// Below this the pack is neither charged nor discharged
+    #[serde(default = "default_pack_min_temp_c")]
+    #[validate(range(min = -40.0, max = 20.0))]
+    pub min_temp_c: f64,
+
+    /// Above this the pack is neither charged nor discharged
+    #[serde(default = "default_pack_max_temp_c")]
+    #[validate(range(min = 30.0, max = 70.0))]
+    pub max_temp_c: f64,
+}
+
+/// Multi-battery dispatch strategy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryDispatch {
+    /// Proportional to each pack's remaining energy
+    #[default]
+    Headroom,
+    /// Drive the packs' SoCs towards each other
+    SocBalancing,
+    /// Fill packs in `priority` order
+    Priority,
+}
+
+/// One charger of a multi-charger site
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct EvChargerConfig {
+    /// Identifier used in the API, e.g. "driveway"
+    #[validate(length(min = 1, max = 64))]
+    pub id: String,
+
+    /// Charge point ID the wallbox uses towards the OCPP central system, defaults to `id`
+    #[serde(default)]
+    pub charge_point_id: Option<String>,
+
+    /// Allocation order under `priority`, lowest first
+    #[serde(default)]
+    pub priority: u8,
+
+    /// 1 for a single-phase charger, 3 for a three-phase one
+    #[serde(default = "default_charger_phases")]
+    #[validate(range(min = 1, max = 3))]
+    pub phases: u8,
+
+    /// Site phase (1-3) a single-phase charger is wired to
+    #[serde(default = "default_charger_phase")]
+    #[validate(range(min = 1, max = 3))]
+    pub phase: u8,
+
+    /// Rating of the charger's circuit breaker
+    #[serde(default = "default_charger_max_current_a")]
+    #[validate(range(min = 6.0, max = 80.0))]
+    pub max_current_a: f64,
+
+    /// Battery size of the vehicle that usually charges here
+    #[serde(default = "default_vehicle_capacity_kwh")]
+    #[validate(range(min = 1.0, max = 250.0))]
+    pub vehicle_capacity_kwh: f64,
+
+    #[serde(default = "default_vehicle_target_soc")]
+    #[validate(range(min = 10.0, max = 100.0))]
+    pub target_soc_percent: f64,
+
+    /// Usual departure in household local time, e.g. "07:30"
+    #[serde(default)]
+    pub departure: Option<chrono::NaiveTime>,
+}
+
+/// Multi-charger allocation policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvAllocation {
+    /// Equal current to every charging vehicle
+    #[default]
+    FairShare,
+    /// Serve chargers in `priority` order
+    Priority,
+    /// Earliest departure first
+    Deadline,
+}
+
+/// Grid connection of the site
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_grid_config"))]
+pub struct GridConfig {
+    /// Main fuse rating per phase, e.g. 16, 20 or 25 A
+    #[serde(default = "default_fuse_rating_amps")]
+    #[validate(range(min = 6.0, max = 200.0))]
+    pub fuse_rating_amps: f64,
+
+    /// 1 for a single-phase connection, 3 for a three-phase one
+    #[serde(default = "default_grid_phases")]
+    #[validate(range(min = 1, max = 3))]
+    pub phases: u8,
+
+    /// Nominal phase-to-neutral voltage
+    #[serde(default = "default_grid_voltage_v")]
+    #[validate(range(min = 100.0, max = 260.0))]
+    pub voltage_v: f64,
+
+    /// Total import limit, may be below what the fuse allows
+    #[serde(default = "default_max_grid_kw")]
+    #[validate(range(min = 0.1, max = 1000.0))]
+    pub max_import_kw: f64,
+
+    #[serde(default = "default_max_grid_kw")]
+    #[validate(range(min = 0.0, max = 1000.0))]
+    pub max_export_kw: f64,
+
+    /// Phase (1-3) a single-phase PV inverter feeds; unset for three-phase
+    #[serde(default)]
+    #[validate(range(min = 1, max = 3))]
+    pub pv_phase: Option<u8>,
+
+    /// Phase (1-3) a single-phase battery inverter is on; unset for three-phase
+    #[serde(default)]
+    #[validate(range(min = 1, max = 3))]
+    pub battery_phase: Option<u8>,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            fuse_rating_amps: default_fuse_rating_amps(),
+            phases: default_grid_phases(),
+            voltage_v: default_grid_voltage_v(),
+            max_import_kw: default_max_grid_kw(),
+            max_export_kw: default_max_grid_kw(),
+            pv_phase: None,
+            battery_phase: None,
+        }
+    }
 }
 
 /// Hardware sensor fallback configuration
@@ -215,6 +444,600 @@ fn validate_battery_config(config: &BatteryConfig) -> Result<(), validator::Vali
     Ok(())
 }
 
+/// Custom validation for GridConfig
+fn validate_grid_config(config: &GridConfig) -> Result<(), validator::ValidationError> {
+    if config.phases != 1 && config.phases != 3 {
+        return Err(validator::ValidationError::new("grid phases must be 1 or 3"));
+    }
+
+    Ok(())
+}
+
+/// Peak-power tariff ("effekttariff") of the grid operator
+///
+/// Hours inside the window count at full weight, all others at
+/// `off_window_weight`. Window hours are in the household's timezone.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_peak_tariff_config"))]
+pub struct PeakTariffConfig {
+    /// Monthly charge per kW of billed peak, 0 disables peak shaving
+    #[serde(default = "default_peak_tariff_price")]
+    #[validate(range(min = 0.0, max = 1000.0))]
+    pub price_sek_per_kw_month: f64,
+
+    /// Number of monthly peaks averaged into the billed peak (Ellevio: 3)
+    #[serde(default = "default_peak_top_n")]
+    #[validate(range(min = 1, max = 31))]
+    pub top_n: usize,
+
+    /// Count at most one peak per day
+    #[serde(default)]
+    pub one_peak_per_day: bool,
+
+    /// Weekend hours count at `off_window_weight`
+    #[serde(default)]
+    pub weekdays_only: bool,
+
+    #[serde(default)]
+    #[validate(range(min = 0, max = 23))]
+    pub window_start_hour: Option<u32>,
+
+    /// End of the window (exclusive); may be before the start to wrap midnight
+    #[serde(default)]
+    #[validate(range(min = 0, max = 23))]
+    pub window_end_hour: Option<u32>,
+
+    /// Weight of hours outside the window, e.g. 0.5 for half-rate nights
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub off_window_weight: f64,
+
+    /// Where the measured hourly peaks of the month are kept between runs
+    #[serde(default = "default_peak_ledger_path")]
+    pub ledger_path: PathBuf,
+}
+
+impl Default for PeakTariffConfig {
+    fn default() -> Self {
+        Self {
+            price_sek_per_kw_month: default_peak_tariff_price(),
+            top_n: default_peak_top_n(),
+            one_peak_per_day: false,
+            weekdays_only: false,
+            window_start_hour: None,
+            window_end_hour: None,
+            off_window_weight: 0.0,
+            ledger_path: default_peak_ledger_path(),
+        }
+    }
+}
+
+/// Custom validation for PeakTariffConfig
+fn validate_peak_tariff_config(
+    config: &PeakTariffConfig,
+) -> Result<(), validator::ValidationError> {
+    if config.window_start_hour.is_some() != config.window_end_hour.is_some() {
+        return Err(validator::ValidationError::new(
+            "peak tariff window needs both window_start_hour and window_end_hour",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Frequency reserve participation
+///
+/// The same capacity is committed every day for `hours` (local time). In
+/// those hours the optimizer leaves the power and energy headroom the
+/// capacity needs and a fast loop follows the grid frequency.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_reserve_config"))]
+pub struct ReserveConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Committed local hours (0-23)
+    #[serde(default)]
+    pub hours: Vec<u32>,
+
+    /// FCR-D up capacity sold (kW)
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1000.0))]
+    pub fcr_d_up_kw: f64,
+
+    /// FCR-D down capacity sold (kW)
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1000.0))]
+    pub fcr_d_down_kw: f64,
+
+    /// FFR capacity sold (kW)
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1000.0))]
+    pub ffr_kw: f64,
+
+    /// FFR activates at or below this frequency (49.5, 49.6 or 49.7 Hz)
+    #[serde(default = "default_ffr_trigger_hz")]
+    #[validate(range(min = 49.5, max = 49.7))]
+    pub ffr_trigger_hz: f64,
+
+    /// How long FFR is held at full power once activated
+    #[serde(default = "default_ffr_support_seconds")]
+    #[validate(range(min = 5.0, max = 30.0))]
+    pub ffr_support_seconds: f64,
+
+    /// Period of the frequency response loop
+    #[serde(default = "default_reserve_loop_interval_ms")]
+    #[validate(range(min = 50, max = 1000))]
+    pub loop_interval_ms: u64,
+}
+
+impl Default for ReserveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hours: Vec::new(),
+            fcr_d_up_kw: 0.0,
+            fcr_d_down_kw: 0.0,
+            ffr_kw: 0.0,
+            ffr_trigger_hz: default_ffr_trigger_hz(),
+            ffr_support_seconds: default_ffr_support_seconds(),
+            loop_interval_ms: default_reserve_loop_interval_ms(),
+        }
+    }
+}
+
+fn validate_reserve_config(config: &ReserveConfig) -> Result<(), validator::ValidationError> {
+    if config.hours.iter().any(|h| *h > 23) {
+        return Err(validator::ValidationError::new(
+            "reserve hours must be between 0 and 23",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Heat pump pre-heating
+///
+/// The optimizer plans an SG-Ready operating state per price period so the
+/// house and hot-water tank are heated ahead of expensive hours, keeping
+/// the room between `comfort_min_c` and `comfort_max_c`.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_heat_pump_config"))]
+pub struct HeatPumpConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// How the heat pump is reached; real units need `hardware.mode = "modbus"`
+    #[serde(default)]
+    pub kind: HeatPumpKind,
+
+    /// Unit id of the heat pump or relay module when it differs from `default_unit_id`
+    #[serde(default)]
+    #[validate(range(min = 1, max = 247))]
+    pub unit_id: Option<u8>,
+
+    /// Coils driving SG-Ready relay 1 and relay 2
+    #[serde(default = "default_sg_ready_coils")]
+    pub sg_ready_coils: [u16; 2],
+
+    #[serde(default = "default_comfort_min_c")]
+    #[validate(range(min = 10.0, max = 30.0))]
+    pub comfort_min_c: f64,
+
+    #[serde(default = "default_comfort_max_c")]
+    #[validate(range(min = 10.0, max = 30.0))]
+    pub comfort_max_c: f64,
+
+    /// Room temperature the heat pump holds on its own
+    #[serde(default = "default_heat_pump_setpoint_c")]
+    #[validate(range(min = 10.0, max = 30.0))]
+    pub setpoint_c: f64,
+
+    #[serde(default = "default_dhw_min_c")]
+    #[validate(range(min = 35.0, max = 70.0))]
+    pub dhw_min_c: f64,
+
+    #[serde(default = "default_dhw_max_c")]
+    #[validate(range(min = 35.0, max = 70.0))]
+    pub dhw_max_c: f64,
+
+    /// Electrical input at full output
+    #[serde(default = "default_heat_pump_rated_power_kw")]
+    #[validate(range(min = 0.5, max = 30.0))]
+    pub rated_power_kw: f64,
+
+    #[serde(default = "default_heat_pump_cop")]
+    #[validate(range(min = 1.0, max = 8.0))]
+    pub cop: f64,
+
+    #[serde(default = "default_heat_pump_dhw_cop")]
+    #[validate(range(min = 1.0, max = 8.0))]
+    pub dhw_cop: f64,
+
+    /// House heat loss per kelvin indoor-outdoor; defaults to the
+    /// simulator's standard 150 m² house
+    #[serde(default = "default_house_heat_loss_w_per_k")]
+    #[validate(range(min = 10.0, max = 2000.0))]
+    pub house_heat_loss_w_per_k: f64,
+
+    /// Heat the house stores per kelvin
+    #[serde(default = "default_house_thermal_mass_kwh_per_k")]
+    #[validate(range(min = 0.5, max = 100.0))]
+    pub house_thermal_mass_kwh_per_k: f64,
+
+    #[serde(default = "default_dhw_tank_liters")]
+    #[validate(range(min = 0.0, max = 1000.0))]
+    pub dhw_tank_liters: f64,
+
+    #[serde(default = "default_dhw_draw_liters_per_day")]
+    #[validate(range(min = 0.0, max = 2000.0))]
+    pub dhw_draw_liters_per_day: f64,
+
+    /// Outdoor temperature assumed when the heat pump does not report one
+    #[serde(default)]
+    #[validate(range(min = -50.0, max = 40.0))]
+    pub fallback_outdoor_temp_c: f64,
+}
+
+/// Interface to the heat pump
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatPumpKind {
+    /// Simulated ground source heat pump heating a simulated house
+    #[default]
+    Simulated,
+    /// Two relays on a Modbus relay module wired to the SG-Ready inputs
+    SgReady,
+    /// NIBE over Modbus (MODBUS 40 or S-series)
+    Nibe,
+    /// Thermia Genesis over Modbus
+    Thermia,
+}
+
+impl Default for HeatPumpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kind: HeatPumpKind::default(),
+            unit_id: None,
+            sg_ready_coils: default_sg_ready_coils(),
+            comfort_min_c: default_comfort_min_c(),
+            comfort_max_c: default_comfort_max_c(),
+            setpoint_c: default_heat_pump_setpoint_c(),
+            dhw_min_c: default_dhw_min_c(),
+            dhw_max_c: default_dhw_max_c(),
+            rated_power_kw: default_heat_pump_rated_power_kw(),
+            cop: default_heat_pump_cop(),
+            dhw_cop: default_heat_pump_dhw_cop(),
+            house_heat_loss_w_per_k: default_house_heat_loss_w_per_k(),
+            house_thermal_mass_kwh_per_k: default_house_thermal_mass_kwh_per_k(),
+            dhw_tank_liters: default_dhw_tank_liters(),
+            dhw_draw_liters_per_day: default_dhw_draw_liters_per_day(),
+            fallback_outdoor_temp_c: 0.0,
+        }
+    }
+}
+
+fn validate_heat_pump_config(config: &HeatPumpConfig) -> Result<(), validator::ValidationError> {
+    if !(config.comfort_min_c <= config.setpoint_c && config.setpoint_c <= config.comfort_max_c) {
+        return Err(validator::ValidationError::new(
+            "heat pump setpoint must lie between comfort_min_c and comfort_max_c",
+        ));
+    }
+    if config.dhw_min_c >= config.dhw_max_c {
+        return Err(validator::ValidationError::new(
+            "heat pump dhw_min_c must be below dhw_max_c",
+        ));
+    }
+
+    Ok(())
+}
+
+/// A switchable load such as a pool pump, dryer or immersion heater
+///
+/// The load runs `runtime_minutes_per_day` within the daily window from
+/// `earliest_start` to `latest_finish` (household local time). A window
+/// whose finish is at or before its start runs past midnight; the default
+/// 00:00 to 00:00 is the whole day.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_controllable_load_config"))]
+pub struct ControllableLoadConfig {
+    /// Identifier used in the API, e.g. "pool-pump"
+    #[validate(length(min = 1, max = 64))]
+    pub id: String,
+
+    #[serde(default)]
+    pub kind: ControllableLoadKind,
+
+    /// Host name or IP address of a Shelly switch
+    #[serde(default)]
+    pub address: Option<String>,
+
+    /// Output of a multi-channel Shelly switch
+    #[serde(default)]
+    pub switch_id: u8,
+
+    /// Unit id of the relay module when it differs from `default_unit_id`
+    #[serde(default)]
+    #[validate(range(min = 1, max = 247))]
+    pub unit_id: Option<u8>,
+
+    /// Coil of the relay module driving the load
+    #[serde(default)]
+    pub coil: u16,
+
+    #[validate(range(min = 0, max = 1440))]
+    pub runtime_minutes_per_day: u32,
+
+    #[serde(default)]
+    pub earliest_start: chrono::NaiveTime,
+
+    #[serde(default)]
+    pub latest_finish: chrono::NaiveTime,
+
+    #[serde(default = "default_load_min_on_minutes")]
+    #[validate(range(min = 0, max = 1440))]
+    pub min_on_minutes: u32,
+
+    #[serde(default = "default_load_min_off_minutes")]
+    #[validate(range(min = 0, max = 1440))]
+    pub min_off_minutes: u32,
+
+    /// Power drawn while on
+    #[validate(range(min = 0.01, max = 50.0))]
+    pub power_kw: f64,
+
+    /// Site phase (1-3) a single-phase load is wired to; unset for three-phase
+    #[serde(default)]
+    #[validate(range(min = 1, max = 3))]
+    pub phase: Option<u8>,
+}
+
+/// Interface to a switchable load
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllableLoadKind {
+    /// Relay with a fixed load behind it
+    #[default]
+    Simulated,
+    /// Shelly Gen2+ switch over its HTTP RPC API
+    Shelly,
+    /// Coil of a Modbus relay module; needs `hardware.mode = "modbus"`
+    ModbusCoil,
+}
+
+impl ControllableLoadConfig {
+    /// Length of the daily window in minutes
+    pub fn window_minutes(&self) -> u32 {
+        let minutes = (self.latest_finish - self.earliest_start).num_minutes();
+        if minutes <= 0 {
+            (minutes + 24 * 60) as u32
+        } else {
+            minutes as u32
+        }
+    }
+}
+
+fn validate_controllable_load_config(
+    config: &ControllableLoadConfig,
+) -> Result<(), validator::ValidationError> {
+    if config.kind == ControllableLoadKind::Shelly && config.address.is_none() {
+        return Err(validator::ValidationError::new(
+            "a shelly controllable load needs an address",
+        ));
+    }
+    if config.runtime_minutes_per_day > config.window_minutes() {
+        return Err(validator::ValidationError::new(
+            "controllable load runtime_minutes_per_day does not fit its window",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Backup (island) operation for a hybrid inverter with a backup output
+///
+/// While the grid is out the battery carries the house alone: export and
+/// EV charging are stopped and only `critical_loads` among the
+/// controllable loads keep running. The optimizer keeps
+/// `reserve_soc_percent` in the battery for the next outage, raised to
+/// `storm_reserve_soc_percent` while the weather forecast shows storm
+/// winds.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_backup_config"))]
+pub struct BackupConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// SoC the schedule keeps for an outage
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub reserve_soc_percent: f64,
+
+    /// Reserve held while storm winds are forecast; unset leaves the
+    /// reserve alone
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub storm_reserve_soc_percent: Option<f64>,
+
+    /// Forecast mean wind speed counted as a storm
+    #[serde(default = "default_storm_wind_speed_ms")]
+    #[validate(range(min = 5.0, max = 60.0))]
+    pub storm_wind_speed_ms: f64,
+
+    /// How far ahead the forecast is searched for storm winds
+    #[serde(default = "default_storm_lookahead_hours")]
+    #[validate(range(min = 1, max = 72))]
+    pub storm_lookahead_hours: u32,
+
+    /// Grid voltage below which the grid counts as lost
+    #[serde(default = "default_outage_voltage_v")]
+    #[validate(range(min = 10.0, max = 200.0))]
+    pub outage_voltage_v: f64,
+
+    /// How long the grid must stay back before normal operation resumes
+    #[serde(default = "default_reconnect_delay_seconds")]
+    #[validate(range(min = 0, max = 3600))]
+    pub reconnect_delay_seconds: u64,
+
+    /// Controllable loads kept running during an outage, highest priority first
+    #[serde(default)]
+    pub critical_loads: Vec<String>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reserve_soc_percent: 0.0,
+            storm_reserve_soc_percent: None,
+            storm_wind_speed_ms: default_storm_wind_speed_ms(),
+            storm_lookahead_hours: default_storm_lookahead_hours(),
+            outage_voltage_v: default_outage_voltage_v(),
+            reconnect_delay_seconds: default_reconnect_delay_seconds(),
+            critical_loads: Vec::new(),
+        }
+    }
+}
+
+fn validate_backup_config(config: &BackupConfig) -> Result<(), validator::ValidationError> {
+    if config
+        .storm_reserve_soc_percent
+        .is_some_and(|storm| storm < config.reserve_soc_percent)
+    {
+        return Err(validator::ValidationError::new(
+            "backup storm_reserve_soc_percent must not be below reserve_soc_percent",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Closed-loop export limitation
+///
+/// A fast loop holds the measured export at `limit_w - margin_w`. Surplus
+/// is soaked up by charging the battery first, then by raising the EV
+/// charging current, and only then by curtailing the inverter. Needs a
+/// grid meter.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct ExportLimitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Export the connection is approved for, 0 for zero-export
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1_000_000.0))]
+    pub limit_w: f64,
+
+    /// Distance kept below the limit
+    #[serde(default = "default_export_margin_w")]
+    #[validate(range(min = 0.0, max = 10_000.0))]
+    pub margin_w: f64,
+
+    /// Period of the control loop
+    #[serde(default = "default_export_loop_interval_ms")]
+    #[validate(range(min = 100, max = 1000))]
+    pub loop_interval_ms: u64,
+
+    /// Proportional gain (W absorbed per W of excess export)
+    #[serde(default = "default_export_kp")]
+    #[validate(range(min = 0.0, max = 2.0))]
+    pub kp: f64,
+
+    /// Integral gain (1/s)
+    #[serde(default = "default_export_ki")]
+    #[validate(range(min = 0.0, max = 5.0))]
+    pub ki: f64,
+
+    /// Derivative gain (s)
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub kd: f64,
+}
+
+impl Default for ExportLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            limit_w: 0.0,
+            margin_w: default_export_margin_w(),
+            loop_interval_ms: default_export_loop_interval_ms(),
+            kp: default_export_kp(),
+            ki: default_export_ki(),
+            kd: 0.0,
+        }
+    }
+}
+
+impl ExportLimitConfig {
+    /// Export the planners may schedule (kW), the grid's own limit when
+    /// export limitation is off
+    pub fn max_export_kw(&self, grid_max_export_kw: f64) -> f64 {
+        if self.enabled {
+            grid_max_export_kw.min(((self.limit_w - self.margin_w) / 1000.0).max(0.0))
+        } else {
+            grid_max_export_kw
+        }
+    }
+}
+
+/// Fast control loop
+///
+/// Runs beneath the control tick, which keeps following the schedule, and
+/// the optimizer. While the tick's plan leaves the grid exchange within
+/// `tracking_band_w` of zero, the battery follows the measured grid flow
+/// to hold it at `target_grid_w`; otherwise it holds the tick's setpoint.
+/// Either way setpoint changes are slew-limited. Needs a grid meter.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct FastLoopConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Period of the loop
+    #[serde(default = "default_fast_loop_interval_ms")]
+    #[validate(range(min = 100, max = 2000))]
+    pub interval_ms: u64,
+
+    /// Grid flow held during self-consumption (W, positive = import)
+    #[serde(default)]
+    #[validate(range(min = -5000.0, max = 5000.0))]
+    pub target_grid_w: f64,
+
+    /// Planned grid exchange still counted as self-consumption
+    #[serde(default = "default_fast_loop_tracking_band_w")]
+    #[validate(range(min = 0.0, max = 10_000.0))]
+    pub tracking_band_w: f64,
+
+    /// Share of the measured deviation corrected per step
+    #[serde(default = "default_fast_loop_gain")]
+    #[validate(range(min = 0.05, max = 1.0))]
+    pub gain: f64,
+
+    /// Slew limit of the battery setpoint
+    #[serde(default = "default_fast_loop_ramp_rate_w_per_s")]
+    #[validate(range(min = 10.0, max = 100_000.0))]
+    pub max_ramp_rate_w_per_s: f64,
+
+    /// Setpoint changes below this are not sent to the battery
+    #[serde(default = "default_fast_loop_deadband_w")]
+    #[validate(range(min = 0.0, max = 1000.0))]
+    pub deadband_w: f64,
+}
+
+impl Default for FastLoopConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_fast_loop_interval_ms(),
+            target_grid_w: 0.0,
+            tracking_band_w: default_fast_loop_tracking_band_w(),
+            gain: default_fast_loop_gain(),
+            max_ramp_rate_w_per_s: default_fast_loop_ramp_rate_w_per_s(),
+            deadband_w: default_fast_loop_deadband_w(),
+        }
+    }
+}
+
 /// Hardware abstraction configuration
 #[derive(Debug, Clone, Deserialize, Serialize, Validate)]
 pub struct HardwareConfig {
@@ -227,6 +1050,11 @@ pub struct HardwareConfig {
     #[serde(default)]
     pub ocpp: Option<OcppConfig>,
 
+    /// Smart meter HAN/P1 port, preferred over a Modbus grid meter
+    #[serde(default)]
+    #[validate(nested)]
+    pub han: Option<HanConfig>,
+
     #[serde(default = "default_scan_interval_secs")]
     pub scan_interval_secs: u64,
 
@@ -278,10 +1106,107 @@ pub struct ModbusConfig {
 
     #[serde(default)]
     pub scan_ranges: Vec<String>,
+
+    /// Device profile describing the battery's register layout, e.g.
+    /// "huawei_luna2000". Looked up as `<profiles_dir>/<name>.toml`, then
+    /// among the built-in profiles. Uses the generic map when unset.
+    #[serde(default)]
+    pub battery_profile: Option<String>,
+
+    #[serde(default = "default_device_profiles_dir")]
+    pub profiles_dir: String,
+
+    /// Wire transport: "tcp", "rtu" (local RS-485 adapter) or
+    /// "rtu_over_tcp" (serial gateway forwarding raw RTU frames)
+    #[serde(default)]
+    pub transport: ModbusTransport,
+
+    /// Serial device for `transport = "rtu"`, e.g. "/dev/ttyUSB0"
+    #[serde(default)]
+    pub serial_port: Option<String>,
+
+    #[serde(default = "default_modbus_baud_rate")]
+    #[validate(range(min = 1200, max = 115200))]
+    pub baud_rate: u32,
+
+    #[serde(default)]
+    pub parity: SerialParity,
+
+    #[serde(default = "default_modbus_stop_bits")]
+    #[validate(range(min = 1, max = 2))]
+    pub stop_bits: u8,
+
+    /// Silence between RTU frames. Derived from the baud rate (3.5
+    /// characters) when unset; slow gateways sometimes need more.
+    #[serde(default)]
+    pub inter_frame_delay_ms: Option<u64>,
+
+    /// Register layout of the PV/hybrid inverter
+    #[serde(default)]
+    pub inverter_registers: InverterRegisterLayout,
+
+    /// Unit id of the inverter when it differs from `default_unit_id`
+    #[serde(default)]
+    #[validate(range(min = 1, max = 247))]
+    pub inverter_unit_id: Option<u8>,
+
+    /// Utility meter at the grid connection, read over the same transport
+    #[serde(default)]
+    pub grid_meter: Option<GridMeterModel>,
+
+    /// Unit id of the grid meter when it differs from `default_unit_id`
+    #[serde(default)]
+    #[validate(range(min = 1, max = 247))]
+    pub grid_meter_unit_id: Option<u8>,
+}
+
+/// Supported Modbus grid meters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridMeterModel {
+    /// Eastron SDM630
+    Sdm630,
+    /// Carlo Gavazzi EM24
+    Em24,
+    /// Any meter exposing SunSpec models 201-204
+    Sunspec,
+}
+
+/// Register layout used to talk to the inverter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InverterRegisterLayout {
+    /// Probe for SunSpec, fall back to the generic map
+    #[default]
+    Auto,
+    Sunspec,
+    Generic,
+    HuaweiSun2000,
+}
+
+/// How Modbus frames reach the device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModbusTransport {
+    #[default]
+    Tcp,
+    Rtu,
+    RtuOverTcp,
+}
+
+/// Parity of an RS-485 line (8 data bits are always used)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialParity {
+    #[default]
+    None,
+    Even,
+    Odd,
 }
 
 /// OCPP configuration
 #[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_ocpp_config"))]
 pub struct OcppConfig {
     #[validate(url)]
     pub server_url: String,
@@ -294,6 +1219,61 @@ pub struct OcppConfig {
 
     #[serde(default)]
     pub enable_tls: bool,
+
+    /// Host an OCPP central system endpoint that chargers dial into
+    #[serde(default)]
+    pub central_system_enabled: bool,
+
+    /// Mount path of the central system; chargers connect to `{path}/{charge_point_id}`
+    #[serde(default = "default_central_system_path")]
+    #[validate(length(min = 2))]
+    pub central_system_path: String,
+
+    /// HTTP Basic auth password per charge point ID (OCPP security profile 1)
+    ///
+    /// Charge points without a password are refused by the central system.
+    #[serde(default)]
+    pub charge_point_passwords: HashMap<String, String>,
+}
+
+/// OCPP 1.6 security profile 1 passwords are 16 to 40 characters
+fn validate_ocpp_config(config: &OcppConfig) -> Result<(), validator::ValidationError> {
+    if config
+        .charge_point_passwords
+        .values()
+        .any(|password| !(16..=40).contains(&password.len()))
+    {
+        return Err(validator::ValidationError::new(
+            "ocpp charge_point_passwords must be 16 to 40 characters",
+        ));
+    }
+
+    Ok(())
+}
+
+/// HAN/P1 smart meter port configuration
+///
+/// Set either `serial_port` for a P1 cable or `tcp_address` for a
+/// P1-to-TCP bridge.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct HanConfig {
+    /// Serial device of the P1 cable, e.g. /dev/ttyUSB0
+    #[serde(default)]
+    pub serial_port: Option<String>,
+
+    /// host:port of a bridge streaming raw telegrams
+    #[serde(default)]
+    pub tcp_address: Option<String>,
+
+    /// DSMR 5 and Swedish HAN use 115200; DSMR 2/3 meters use 9600
+    #[serde(default = "default_han_baud_rate")]
+    #[validate(range(min = 9600, max = 115200))]
+    pub baud_rate: u32,
+
+    /// Readings older than this are treated as unavailable
+    #[serde(default = "default_han_max_age_secs")]
+    #[validate(range(min = 2, max = 300))]
+    pub max_age_secs: u64,
 }
 
 /// Database configuration
@@ -322,13 +1302,18 @@ pub struct DatabaseConfig {
 
 /// Optimization engine configuration
 #[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[validate(schema(function = "validate_optimization_config"))]
 pub struct OptimizationConfig {
     #[serde(default = "default_optimization_strategy")]
     pub strategy: OptimizationStrategy,
 
-    #[validate(range(min = 1, max = 168))]
+    /// How far ahead schedules are planned, as far as prices are published
+    #[serde(default = "default_optimization_horizon_hours")]
+    #[validate(range(min = 1, max = 48))]
     pub horizon_hours: u32,
 
+    /// Schedule period length; prices, load and PV are resampled to it
+    #[serde(default = "default_time_step_minutes")]
     #[validate(range(min = 1, max = 60))]
     pub time_step_minutes: u32,
 
@@ -351,6 +1336,141 @@ pub struct OptimizationConfig {
     #[serde(default = "default_low_price_charge_rate")]
     #[validate(range(min = 0.1, max = 1.0))]
     pub low_price_charge_rate: f64,
+
+    /// Deducted from the spot price to get the export price (SEK/kWh)
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 10.0))]
+    pub export_fee_sek_per_kwh: Option<f64>,
+
+    /// Allow schedules that curtail PV; needs an inverter that accepts a limit
+    #[serde(default)]
+    pub allow_pv_curtailment: bool,
+
+    /// Allow schedules that discharge a vehicle (V2G); needs a bidirectional
+    /// charger driven by the V2X controller
+    #[serde(default)]
+    pub allow_v2g_discharge: bool,
+
+    /// Store surplus PV rather than export it when both are worth the same
+    #[serde(default = "default_prefer_self_consumption")]
+    pub prefer_self_consumption: bool,
+
+    /// Forecast uncertainty for the `stochastic` strategy
+    #[serde(default)]
+    #[validate(nested)]
+    pub scenarios: ScenarioConfig,
+
+    /// Per-tick re-solve for the `mpc` strategy
+    #[serde(default)]
+    #[validate(nested)]
+    pub mpc: MpcConfig,
+}
+
+/// Custom validation for OptimizationConfig
+fn validate_optimization_config(
+    config: &OptimizationConfig,
+) -> Result<(), validator::ValidationError> {
+    // Periods must tile the hour, or they straddle the tariff's hourly peaks
+    if 60 % config.time_step_minutes.max(1) != 0 {
+        return Err(validator::ValidationError::new(
+            "time_step_minutes must divide 60, e.g. 15, 30 or 60",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Receding-horizon re-solve for the `mpc` strategy
+///
+/// Every control tick the next `horizon_periods` schedule periods are
+/// re-solved from the measured SoC, PV and load. A solve that takes longer
+/// than `budget_ms` is dropped and the schedule is followed instead.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct MpcConfig {
+    #[serde(default = "default_mpc_horizon_periods")]
+    #[validate(range(min = 1, max = 48))]
+    pub horizon_periods: usize,
+
+    #[serde(default = "default_mpc_budget_ms")]
+    #[validate(range(min = 10, max = 10_000))]
+    pub budget_ms: u64,
+
+    /// SoC grid resolution (percentage points)
+    #[serde(default = "default_mpc_soc_step_percent")]
+    #[validate(range(min = 0.1, max = 10.0))]
+    pub soc_step_percent: f64,
+
+    /// Battery power levels tried between full discharge and full charge
+    #[serde(default = "default_mpc_power_levels")]
+    #[validate(range(min = 2, max = 101))]
+    pub power_levels: usize,
+}
+
+impl Default for MpcConfig {
+    fn default() -> Self {
+        Self {
+            horizon_periods: default_mpc_horizon_periods(),
+            budget_ms: default_mpc_budget_ms(),
+            soc_step_percent: default_mpc_soc_step_percent(),
+            power_levels: default_mpc_power_levels(),
+        }
+    }
+}
+
+/// Scenario sampling and risk weighting for the `stochastic` strategy
+///
+/// Spreads are relative standard deviations of the forecast error.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct ScenarioConfig {
+    #[serde(default = "default_num_scenarios")]
+    #[validate(range(min = 1, max = 500))]
+    pub num_scenarios: usize,
+
+    /// Schedules planned by the base strategy and scored on the scenarios
+    #[serde(default = "default_num_candidates")]
+    #[validate(range(min = 1, max = 20))]
+    pub num_candidates: usize,
+
+    #[serde(default = "default_pv_spread")]
+    #[validate(range(min = 0.0, max = 2.0))]
+    pub pv_spread: f64,
+
+    #[serde(default = "default_load_spread")]
+    #[validate(range(min = 0.0, max = 2.0))]
+    pub load_spread: f64,
+
+    /// Only applied to hours whose day-ahead prices are not yet published
+    #[serde(default = "default_price_spread")]
+    #[validate(range(min = 0.0, max = 2.0))]
+    pub price_spread: f64,
+
+    /// CVaR level; 0.9 scores the mean of the worst 10% of scenarios
+    #[serde(default = "default_cvar_alpha")]
+    #[validate(range(min = 0.0, max = 0.99))]
+    pub cvar_alpha: f64,
+
+    /// Weight of the CVaR term against the expected cost, 0 = risk neutral
+    #[serde(default = "default_risk_weight")]
+    #[validate(range(min = 0.0, max = 10.0))]
+    pub risk_weight: f64,
+
+    #[serde(default = "default_scenario_seed")]
+    pub seed: u64,
+}
+
+impl Default for ScenarioConfig {
+    fn default() -> Self {
+        Self {
+            num_scenarios: default_num_scenarios(),
+            num_candidates: default_num_candidates(),
+            pv_spread: default_pv_spread(),
+            load_spread: default_load_spread(),
+            price_spread: default_price_spread(),
+            cvar_alpha: default_cvar_alpha(),
+            risk_weight: default_risk_weight(),
+            seed: default_scenario_seed(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -359,8 +1479,11 @@ pub enum OptimizationStrategy {
     Greedy,
     DynamicProgramming,
     Milp,
+    /// Base strategy plus a receding-horizon re-solve every control tick
     Mpc,
     ReinforcementLearning,
+    /// Base strategy scored over forecast scenarios with a CVaR risk term
+    Stochastic,
 }
 
 /// Forecasting configuration
@@ -512,19 +1635,90 @@ fn default_min_soc() -> f64 { 10.0 }
 fn default_max_soc() -> f64 { 95.0 }
 fn default_battery_replacement_cost() -> f64 { 50000.0 } // 50k SEK typical for home battery
 fn default_ambient_temp_c() -> f64 { 15.0 } // Typical Nordic garage/outdoor installation
+fn default_battery_chemistry() -> BatteryChemistry { BatteryChemistry::LiFePO4 }
+fn default_pack_min_temp_c() -> f64 { -10.0 }
+fn default_pack_max_temp_c() -> f64 { 55.0 }
+fn default_ffr_trigger_hz() -> f64 { 49.7 }
+fn default_ffr_support_seconds() -> f64 { 30.0 }
+fn default_reserve_loop_interval_ms() -> u64 { 200 }
+fn default_sg_ready_coils() -> [u16; 2] { [0, 1] }
+fn default_comfort_min_c() -> f64 { 20.0 }
+fn default_comfort_max_c() -> f64 { 23.0 }
+fn default_heat_pump_setpoint_c() -> f64 { 21.0 }
+fn default_dhw_min_c() -> f64 { 45.0 }
+fn default_dhw_max_c() -> f64 { 60.0 }
+fn default_heat_pump_rated_power_kw() -> f64 { 3.0 }
+fn default_heat_pump_cop() -> f64 { 3.5 }
+fn default_heat_pump_dhw_cop() -> f64 { 2.5 }
+fn default_house_heat_loss_w_per_k() -> f64 {
+    crate::simulation::thermal::ThermalZoneConfig::default().heat_loss_coefficient_w_per_k()
+}
+fn default_house_thermal_mass_kwh_per_k() -> f64 {
+    crate::simulation::thermal::ThermalZoneConfig::default().thermal_mass_j_per_k() / 3.6e6
+}
+fn default_dhw_tank_liters() -> f64 { 200.0 }
+fn default_dhw_draw_liters_per_day() -> f64 { 150.0 }
+fn default_load_min_on_minutes() -> u32 { 30 }
+fn default_load_min_off_minutes() -> u32 { 30 }
+fn default_storm_wind_speed_ms() -> f64 { 17.0 }
+fn default_storm_lookahead_hours() -> u32 { 24 }
+fn default_outage_voltage_v() -> f64 { 100.0 }
+fn default_reconnect_delay_seconds() -> u64 { 60 }
+fn default_export_margin_w() -> f64 { 100.0 }
+fn default_export_loop_interval_ms() -> u64 { 500 }
+fn default_export_kp() -> f64 { 0.5 }
+fn default_export_ki() -> f64 { 0.8 }
+fn default_fast_loop_interval_ms() -> u64 { 500 }
+fn default_fast_loop_tracking_band_w() -> f64 { 300.0 }
+fn default_fast_loop_gain() -> f64 { 0.7 }
+fn default_fast_loop_ramp_rate_w_per_s() -> f64 { 2000.0 }
+fn default_fast_loop_deadband_w() -> f64 { 30.0 }
+fn default_charger_phases() -> u8 { 3 }
+fn default_charger_phase() -> u8 { 1 }
+fn default_charger_max_current_a() -> f64 { 16.0 }
+fn default_vehicle_capacity_kwh() -> f64 { 60.0 }
+fn default_vehicle_target_soc() -> f64 { 80.0 }
+fn default_fuse_rating_amps() -> f64 { 25.0 }
+fn default_grid_phases() -> u8 { 3 } // Swedish homes are typically 3x16/20/25 A
+fn default_grid_voltage_v() -> f64 { 230.0 }
+fn default_max_grid_kw() -> f64 { 11.0 }
 fn default_pv_production_kw() -> f64 { 0.0 } // Conservative: assume no PV if sensor unavailable
 fn default_house_load_kw() -> f64 { 2.0 } // Typical household base load
 fn default_hardware_mode() -> HardwareMode { HardwareMode::Simulated }
 fn default_scan_interval_secs() -> u64 { 300 }
+fn default_central_system_path() -> String { "/ocpp".to_string() }
+fn default_peak_tariff_price() -> f64 { 100.0 }
+fn default_peak_top_n() -> usize { 1 }
+fn default_peak_ledger_path() -> PathBuf { PathBuf::from("data/peak_ledger.json") }
+fn default_device_profiles_dir() -> String { "config/device_profiles".to_string() }
+fn default_modbus_baud_rate() -> u32 { 9600 }
+fn default_modbus_stop_bits() -> u8 { 1 }
+fn default_han_baud_rate() -> u32 { 115_200 }
+fn default_han_max_age_secs() -> u64 { 30 }
 fn default_db_max_connections() -> u32 { 10 }
 fn default_db_min_connections() -> u32 { 2 }
 fn default_db_timeout_secs() -> u64 { 30 }
 fn default_db_idle_timeout_secs() -> u64 { 600 }
 fn default_optimization_strategy() -> OptimizationStrategy { OptimizationStrategy::DynamicProgramming }
+fn default_optimization_horizon_hours() -> u32 { 24 }
+fn default_time_step_minutes() -> u32 { 60 }
 fn default_max_iterations() -> u32 { 1000 }
 fn default_convergence_threshold() -> f64 { 0.001 }
 fn default_timeout_secs() -> u64 { 300 }
 fn default_low_price_charge_rate() -> f64 { 0.5 }
+fn default_prefer_self_consumption() -> bool { true }
+fn default_num_scenarios() -> usize { 30 }
+fn default_num_candidates() -> usize { 5 }
+fn default_pv_spread() -> f64 { 0.35 }
+fn default_load_spread() -> f64 { 0.15 }
+fn default_price_spread() -> f64 { 0.25 }
+fn default_cvar_alpha() -> f64 { 0.9 }
+fn default_risk_weight() -> f64 { 0.5 }
+fn default_scenario_seed() -> u64 { 42 }
+fn default_mpc_horizon_periods() -> usize { 6 }
+fn default_mpc_budget_ms() -> u64 { 200 }
+fn default_mpc_soc_step_percent() -> f64 { 0.5 }
+fn default_mpc_power_levels() -> usize { 11 }
 fn default_update_interval_hours() -> u32 { 1 }
 fn default_cache_ttl_secs() -> u64 { 3600 }
 fn default_cache_ttl_seconds() -> u64 { 3600 }
@@ -633,6 +1827,7 @@ mod tests {
             max_soc_percent: 95.0,
             replacement_cost_sek: 50000.0,
             ambient_temp_c: 15.0,
+            chemistry: BatteryChemistry::LiFePO4,
         };
 
         assert!(config.validate().is_ok());
@@ -651,11 +1846,223 @@ mod tests {
             max_soc_percent: 95.0,
             replacement_cost_sek: 50000.0,
             ambient_temp_c: 15.0,
+            chemistry: BatteryChemistry::LiFePO4,
         };
 
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_battery_pack_config_flattens_battery() {
+        let json = r#"{
+            "id": "garage",
+            "capacity_kwh": 5.0,
+            "initial_soc_percent": 40.0,
+            "max_charge_kw": 2.5,
+            "max_discharge_kw": 2.5,
+            "efficiency": 0.95,
+            "degradation_per_cycle": 0.0001,
+            "unit_id": 3,
+            "priority": 1
+        }"#;
+        let pack: BatteryPackConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(pack.battery.capacity_kwh, 5.0);
+        assert_eq!(pack.battery.min_soc_percent, 10.0);
+        assert_eq!(pack.unit_id, Some(3));
+        assert_eq!(pack.max_temp_c, 55.0);
+        assert!(pack.validate().is_ok());
+    }
+
+    #[test]
+    fn test_ev_charger_config_defaults() {
+        let json = r#"{"id": "driveway", "departure": "07:30"}"#;
+        let charger: EvChargerConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(charger.phases, 3);
+        assert_eq!(charger.max_current_a, 16.0);
+        assert_eq!(charger.departure, chrono::NaiveTime::from_hms_opt(7, 30, 0));
+        assert!(charger.validate().is_ok());
+    }
+
+    #[test]
+    fn test_controllable_load_window() {
+        let json = r#"{"id": "pool-pump", "runtime_minutes_per_day": 360,
+            "earliest_start": "22:00", "latest_finish": "06:00", "power_kw": 1.2}"#;
+        let load: ControllableLoadConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(load.kind, ControllableLoadKind::Simulated);
+        assert_eq!(load.window_minutes(), 8 * 60);
+        assert!(load.validate().is_ok());
+
+        let load = ControllableLoadConfig {
+            runtime_minutes_per_day: 9 * 60,
+            ..load
+        };
+        assert!(load.validate().is_err(), "runtime longer than the window");
+
+        let load = ControllableLoadConfig {
+            runtime_minutes_per_day: 60,
+            kind: ControllableLoadKind::Shelly,
+            ..load
+        };
+        assert!(load.validate().is_err(), "shelly without an address");
+    }
+
+    #[test]
+    fn test_backup_storm_reserve() {
+        let json = r#"{"enabled": true, "reserve_soc_percent": 30.0,
+            "storm_reserve_soc_percent": 80.0, "critical_loads": ["freezer"]}"#;
+        let backup: BackupConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(backup.reconnect_delay_seconds, 60);
+        assert_eq!(backup.storm_wind_speed_ms, 17.0);
+        assert!(backup.validate().is_ok());
+
+        let backup = BackupConfig {
+            storm_reserve_soc_percent: Some(20.0),
+            ..backup
+        };
+        assert!(backup.validate().is_err(), "storm reserve below the reserve");
+    }
+
+    #[test]
+    fn test_export_limit_caps_planned_export() {
+        let json = r#"{"enabled": true, "limit_w": 3000.0}"#;
+        let export_limit: ExportLimitConfig = serde_json::from_str(json).unwrap();
+
+        assert!(export_limit.validate().is_ok());
+        assert_eq!(export_limit.max_export_kw(11.0), 2.9);
+        assert_eq!(ExportLimitConfig::default().max_export_kw(11.0), 11.0);
+
+        let zero_export = ExportLimitConfig {
+            limit_w: 0.0,
+            ..export_limit
+        };
+        assert_eq!(zero_export.max_export_kw(11.0), 0.0);
+    }
+
+    #[test]
+    fn test_fast_loop_defaults_and_limits() {
+        let fast_loop: FastLoopConfig = serde_json::from_str(r#"{"enabled": true}"#).unwrap();
+
+        assert!(fast_loop.validate().is_ok());
+        assert_eq!(fast_loop.interval_ms, 500);
+        assert_eq!(fast_loop.target_grid_w, 0.0);
+
+        let too_slow = FastLoopConfig {
+            interval_ms: 5000,
+            ..fast_loop.clone()
+        };
+        assert!(too_slow.validate().is_err());
+
+        let no_gain = FastLoopConfig {
+            gain: 0.0,
+            ..fast_loop
+        };
+        assert!(no_gain.validate().is_err());
+    }
+
+    #[test]
+    fn test_ocpp_charge_point_passwords() {
+        let json = r#"{"server_url": "ws://localhost:9000/ocpp", "charge_point_id": "CP001",
+            "heartbeat_interval_secs": 300, "central_system_enabled": true,
+            "charge_point_passwords": {"CP001": "correct-horse-battery"}}"#;
+        let ocpp: OcppConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(ocpp.central_system_path, "/ocpp");
+        assert!(ocpp.validate().is_ok());
+
+        let mut short = ocpp;
+        short
+            .charge_point_passwords
+            .insert("CP002".to_string(), "secret".to_string());
+        assert!(short.validate().is_err(), "password shorter than 16 characters");
+    }
+
+    #[test]
+    fn test_grid_config_single_phase_devices() {
+        let json = r#"{"fuse_rating_amps": 20.0, "pv_phase": 2}"#;
+        let grid: GridConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(grid.phases, 3);
+        assert_eq!(grid.pv_phase, Some(2));
+        assert_eq!(grid.battery_phase, None);
+        assert!(grid.validate().is_ok());
+
+        let grid = GridConfig {
+            battery_phase: Some(4),
+            ..grid
+        };
+        assert!(grid.validate().is_err());
+    }
+
+    #[test]
+    fn test_peak_tariff_config() {
+        let json = r#"{"top_n": 3, "one_peak_per_day": true, "window_start_hour": 7}"#;
+        let tariff: PeakTariffConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(tariff.price_sek_per_kw_month, 100.0);
+        assert_eq!(tariff.top_n, 3);
+        assert!(tariff.validate().is_err(), "window without an end");
+
+        let tariff = PeakTariffConfig {
+            window_end_hour: Some(19),
+            ..tariff
+        };
+        assert!(tariff.validate().is_ok());
+    }
+
+    #[test]
+    fn test_scenario_config_defaults() {
+        let scenarios: ScenarioConfig = serde_json::from_str(r#"{"risk_weight": 1.0}"#).unwrap();
+
+        assert_eq!(scenarios.num_scenarios, 30);
+        assert_eq!(scenarios.risk_weight, 1.0);
+        assert!(scenarios.validate().is_ok());
+
+        let scenarios = ScenarioConfig {
+            cvar_alpha: 1.0,
+            ..scenarios
+        };
+        assert!(scenarios.validate().is_err(), "an empty CVaR tail");
+    }
+
+    #[test]
+    fn test_time_step_must_tile_the_hour() {
+        let optimization: OptimizationConfig =
+            serde_json::from_str(r#"{"time_step_minutes": 15, "horizon_hours": 48}"#).unwrap();
+        assert!(optimization.validate().is_ok());
+
+        let optimization = OptimizationConfig {
+            time_step_minutes: 45,
+            ..optimization
+        };
+        assert!(optimization.validate().is_err());
+
+        let optimization = OptimizationConfig {
+            time_step_minutes: 60,
+            horizon_hours: 72,
+            ..optimization
+        };
+        assert!(optimization.validate().is_err(), "beyond the 48 h horizon");
+    }
+
+    #[test]
+    fn test_mpc_config_defaults() {
+        let mpc: MpcConfig = serde_json::from_str(r#"{"budget_ms": 500}"#).unwrap();
+
+        assert_eq!(mpc.horizon_periods, 6);
+        assert_eq!(mpc.budget_ms, 500);
+        assert!(mpc.validate().is_ok());
+
+        let mpc = MpcConfig {
+            power_levels: 1,
+            ..mpc
+        };
+        assert!(mpc.validate().is_err(), "a single power level");
+    }
+
     #[test]
     fn test_hardware_mode_deserialization() {
         let json = r#"{"mode": "simulated"}"#;
@@ -672,6 +2079,7 @@ mod tests {
             ("dynamic_programming", OptimizationStrategy::DynamicProgramming),
             ("milp", OptimizationStrategy::Milp),
             ("mpc", OptimizationStrategy::Mpc),
+            ("stochastic", OptimizationStrategy::Stochastic),
         ];
 
         for (name, expected) in strategies {
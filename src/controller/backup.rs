@@ -0,0 +1,430 @@
+//! # Backup Operation
+//!
+//! Follows the grid connection and switches the controller into backup
+//! operation while the grid is out. The hybrid inverter then carries the
+//! house from the battery and PV alone, so export and EV charging stop and
+//! only the critical controllable loads keep running.
+//!
+//! The grid has to stay back for the reconnect delay before normal
+//! operation resumes; a flickering grid keeps the site in backup.
+//!
+//! Between outages the battery holds a reserve, raised while the weather
+//! forecast shows storm winds.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use tracing::{info, warn};
+
+use crate::config::BackupConfig;
+use crate::forecast::WeatherForecast;
+use crate::power_flow::{LoadSwitch, PowerFlowInputs};
+
+/// Outages kept in the log
+const MAX_OUTAGES: usize = 20;
+
+/// Grid connection as the controller sees it
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupMode {
+    /// Normal operation
+    GridConnected,
+    /// Grid lost, the house runs from the backup output
+    Outage,
+    /// Grid back, waiting out the reconnect delay
+    Reconnecting,
+}
+
+/// One grid outage
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize)]
+pub struct OutageRecord {
+    pub started_at: DateTime<Utc>,
+    /// `None` while the outage is ongoing
+    pub ended_at: Option<DateTime<Utc>>,
+    pub start_soc_percent: f64,
+    pub min_soc_percent: f64,
+}
+
+/// Storm winds found in the weather forecast
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StormWarning {
+    /// Strongest forecast mean wind
+    pub max_wind_speed_ms: f64,
+    pub first_storm_at: DateTime<Utc>,
+    /// End of the last stormy forecast hour; the storm reserve lapses here
+    pub until: DateTime<Utc>,
+}
+
+impl StormWarning {
+    /// Storm winds within `lookahead_hours` of `now`, if any
+    pub fn from_forecast(
+        forecast: &WeatherForecast,
+        now: DateTime<Utc>,
+        wind_speed_ms: f64,
+        lookahead_hours: u32,
+    ) -> Option<Self> {
+        let horizon = now + Duration::hours(lookahead_hours as i64);
+        let stormy: Vec<_> = forecast
+            .points
+            .iter()
+            .map(|p| (p.timestamp.with_timezone(&Utc), p.wind_speed_ms))
+            .filter(|(t, wind)| {
+                *t + Duration::hours(1) > now && *t <= horizon && *wind >= wind_speed_ms
+            })
+            .collect();
+        Some(Self {
+            max_wind_speed_ms: stormy.iter().map(|(_, w)| *w).fold(f64::MIN, f64::max),
+            first_storm_at: stormy.iter().map(|(t, _)| *t).min()?,
+            until: stormy.iter().map(|(t, _)| *t).max()? + Duration::hours(1),
+        })
+    }
+}
+
+/// Backup mode, outage reserve and outage log
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupStatus {
+    pub mode: BackupMode,
+    pub since: DateTime<Utc>,
+    /// SoC the schedule currently keeps for an outage
+    pub reserve_soc_percent: f64,
+    pub storm: Option<StormWarning>,
+    pub critical_loads: Vec<String>,
+    /// Most recent last
+    pub outages: Vec<OutageRecord>,
+}
+
+/// Battery power and load decisions while islanded
+#[derive(Debug, Clone, PartialEq)]
+pub struct IslandDispatch {
+    /// Battery power (W, positive = charge)
+    pub battery_w: f64,
+    pub load_switches: Vec<LoadSwitch>,
+}
+
+/// State machine following the grid connection
+#[derive(Debug)]
+pub struct BackupSupervisor {
+    config: BackupConfig,
+    mode: BackupMode,
+    since: DateTime<Utc>,
+    storm: Option<StormWarning>,
+    outages: VecDeque<OutageRecord>,
+}
+
+impl BackupSupervisor {
+    pub fn new(config: BackupConfig, now: DateTime<Utc>) -> Self {
+        Self {
+            config,
+            mode: BackupMode::GridConnected,
+            since: now,
+            storm: None,
+            outages: VecDeque::with_capacity(MAX_OUTAGES),
+        }
+    }
+
+    pub fn mode(&self) -> BackupMode {
+        self.mode
+    }
+
+    /// Whether backup operation is in force, including the reconnect delay
+    pub fn is_islanded(&self) -> bool {
+        self.mode != BackupMode::GridConnected
+    }
+
+    /// Whether a measured grid voltage means the grid is there
+    pub fn grid_present(&self, voltage_v: f64) -> bool {
+        voltage_v >= self.config.outage_voltage_v
+    }
+
+    /// Advance the state machine with this tick's observation
+    ///
+    /// `grid_present` is `None` when neither meter nor inverter could be
+    /// read, which leaves the mode as it is. Returns the new mode on a
+    /// change.
+    pub fn observe(
+        &mut self,
+        now: DateTime<Utc>,
+        grid_present: Option<bool>,
+        soc_percent: f64,
+    ) -> Option<BackupMode> {
+        if let Some(outage) = self.outages.back_mut().filter(|o| o.ended_at.is_none()) {
+            outage.min_soc_percent = outage.min_soc_percent.min(soc_percent);
+        }
+
+        let next = match (self.mode, grid_present?) {
+            (BackupMode::GridConnected, false) => {
+                if self.outages.len() == MAX_OUTAGES {
+                    self.outages.pop_front();
+                }
+                self.outages.push_back(OutageRecord {
+                    started_at: now,
+                    ended_at: None,
+                    start_soc_percent: soc_percent,
+                    min_soc_percent: soc_percent,
+                });
+                warn!(
+                    soc_percent,
+                    "Grid outage detected, entering backup operation"
+                );
+                BackupMode::Outage
+            }
+            (BackupMode::Outage, true) => {
+                info!(
+                    delay_seconds = self.config.reconnect_delay_seconds,
+                    "Grid back, waiting before leaving backup operation"
+                );
+                BackupMode::Reconnecting
+            }
+            (BackupMode::Reconnecting, false) => {
+                warn!("Grid lost again while reconnecting");
+                BackupMode::Outage
+            }
+            (BackupMode::Reconnecting, true)
+                if now - self.since
+                    >= Duration::seconds(self.config.reconnect_delay_seconds as i64) =>
+            {
+                if let Some(outage) = self.outages.back_mut() {
+                    outage.ended_at = Some(self.since);
+                    info!(
+                        minutes = (self.since - outage.started_at).num_minutes(),
+                        min_soc_percent = outage.min_soc_percent,
+                        "Grid stable, leaving backup operation"
+                    );
+                }
+                BackupMode::GridConnected
+            }
+            _ => return None,
+        };
+        self.mode = next;
+        self.since = now;
+        Some(next)
+    }
+
+    /// Replace the storm warning; returns whether the reserve changed
+    pub fn set_storm(&mut self, storm: Option<StormWarning>, now: DateTime<Utc>) -> bool {
+        let before = self.reserve_soc_percent(now);
+        self.storm = storm;
+        self.reserve_soc_percent(now) != before
+    }
+
+    /// SoC to keep for an outage, the storm reserve while a storm is forecast
+    pub fn reserve_soc_percent(&self, now: DateTime<Utc>) -> f64 {
+        match (&self.storm, self.config.storm_reserve_soc_percent) {
+            (Some(storm), Some(storm_reserve)) if storm.until > now => {
+                storm_reserve.max(self.config.reserve_soc_percent)
+            }
+            _ => self.config.reserve_soc_percent,
+        }
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> BackupStatus {
+        BackupStatus {
+            mode: self.mode,
+            since: self.since,
+            reserve_soc_percent: self.reserve_soc_percent(now),
+            storm: self.storm.clone().filter(|s| s.until > now),
+            critical_loads: self.config.critical_loads.clone(),
+            outages: self.outages.iter().cloned().collect(),
+        }
+    }
+
+    /// Battery power and load switching while islanded
+    ///
+    /// The house comes first. Critical loads that want to run are started
+    /// in priority order while PV and the battery can carry them; every
+    /// other controllable load is shed. The battery takes whatever PV is
+    /// left over, the inverter curtails the rest.
+    pub fn island_dispatch(
+        &self,
+        inputs: &PowerFlowInputs,
+        min_soc_percent: f64,
+        max_charge_w: f64,
+        max_discharge_w: f64,
+    ) -> IslandDispatch {
+        // Running loads are part of the measured house load
+        let running_kw: f64 = inputs
+            .controllable_loads
+            .iter()
+            .filter(|l| l.running)
+            .map(|l| l.power_kw)
+            .sum();
+        let mut demand_kw = (inputs.house_load_kw - running_kw).max(0.0);
+        let battery_kw = if inputs.battery_soc_percent > min_soc_percent {
+            max_discharge_w / 1000.0
+        } else {
+            0.0
+        };
+        let supply_kw = inputs.pv_production_kw + battery_kw;
+
+        let mut load_switches: Vec<LoadSwitch> = inputs
+            .controllable_loads
+            .iter()
+            .map(|l| LoadSwitch {
+                id: l.id.clone(),
+                on: false,
+            })
+            .collect();
+        for id in &self.config.critical_loads {
+            let Some(load) = inputs.controllable_loads.iter().find(|l| &l.id == id) else {
+                continue;
+            };
+            if load.requested && demand_kw + load.power_kw <= supply_kw {
+                demand_kw += load.power_kw;
+                if let Some(switch) = load_switches.iter_mut().find(|s| &s.id == id) {
+                    switch.on = true;
+                }
+            }
+        }
+
+        IslandDispatch {
+            battery_w: ((inputs.pv_production_kw - demand_kw) * 1000.0)
+                .clamp(-max_discharge_w, max_charge_w),
+            load_switches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::{GeoLocation, WeatherPoint};
+    use crate::power_flow::inputs::ControllableLoadInput;
+    use chrono::TimeZone;
+
+    fn config() -> BackupConfig {
+        BackupConfig {
+            enabled: true,
+            reserve_soc_percent: 30.0,
+            storm_reserve_soc_percent: Some(80.0),
+            critical_loads: vec!["freezer".to_string(), "well-pump".to_string()],
+            ..BackupConfig::default()
+        }
+    }
+
+    fn load(id: &str, power_kw: f64, running: bool) -> ControllableLoadInput {
+        ControllableLoadInput {
+            id: id.to_string(),
+            power_kw,
+            phase: None,
+            running,
+            requested: true,
+        }
+    }
+
+    #[test]
+    fn test_outage_ends_after_reconnect_delay() {
+        let t0 = Utc.with_ymd_and_hms(2025, 1, 6, 12, 0, 0).unwrap();
+        let mut backup = BackupSupervisor::new(config(), t0);
+
+        assert_eq!(backup.observe(t0, Some(true), 60.0), None);
+        assert_eq!(
+            backup.observe(t0 + Duration::seconds(1), Some(false), 60.0),
+            Some(BackupMode::Outage)
+        );
+        assert_eq!(backup.observe(t0 + Duration::seconds(2), None, 59.0), None);
+        assert_eq!(
+            backup.observe(t0 + Duration::minutes(30), Some(true), 45.0),
+            Some(BackupMode::Reconnecting)
+        );
+        // The grid flickers and the delay starts over
+        backup.observe(t0 + Duration::minutes(31), Some(false), 45.0);
+        backup.observe(t0 + Duration::minutes(32), Some(true), 45.0);
+        assert_eq!(
+            backup.observe(
+                t0 + Duration::minutes(32) + Duration::seconds(59),
+                Some(true),
+                45.0
+            ),
+            None
+        );
+        assert!(backup.is_islanded());
+        assert_eq!(
+            backup.observe(t0 + Duration::minutes(33), Some(true), 45.0),
+            Some(BackupMode::GridConnected)
+        );
+
+        let outage = &backup.status(t0).outages[0];
+        assert_eq!(outage.started_at, t0 + Duration::seconds(1));
+        assert_eq!(outage.ended_at, Some(t0 + Duration::minutes(32)));
+        assert_eq!(outage.min_soc_percent, 45.0);
+    }
+
+    #[test]
+    fn test_storm_raises_reserve_until_it_passes() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 6, 12, 0, 0).unwrap();
+        let forecast = WeatherForecast {
+            location: GeoLocation {
+                latitude: 57.7,
+                longitude: 11.9,
+                name: None,
+            },
+            generated_at: now.fixed_offset(),
+            points: (0..48)
+                .map(|h| WeatherPoint {
+                    timestamp: (now + Duration::hours(h)).fixed_offset(),
+                    temperature_c: 2.0,
+                    cloud_cover_percent: 100.0,
+                    wind_speed_ms: if (18..22).contains(&h) { 24.0 } else { 8.0 },
+                    precipitation_mm: 1.0,
+                    humidity_percent: 90.0,
+                })
+                .collect(),
+        };
+        let mut backup = BackupSupervisor::new(config(), now);
+
+        assert_eq!(
+            StormWarning::from_forecast(&forecast, now, 17.0, 12),
+            None,
+            "storm beyond the lookahead"
+        );
+        let storm = StormWarning::from_forecast(&forecast, now, 17.0, 24).unwrap();
+        assert_eq!(storm.first_storm_at, now + Duration::hours(18));
+        assert_eq!(storm.until, now + Duration::hours(22));
+
+        assert!(backup.set_storm(Some(storm), now));
+        assert_eq!(backup.reserve_soc_percent(now), 80.0);
+        assert_eq!(backup.reserve_soc_percent(now + Duration::hours(22)), 30.0);
+    }
+
+    #[test]
+    fn test_island_dispatch_sheds_all_but_critical_loads() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 6, 12, 0, 0).unwrap();
+        let backup = BackupSupervisor::new(config(), now);
+        // 1.5 kW house including the running pool pump, 1 kW PV
+        let inputs = PowerFlowInputs::new(1.0, 1.5, 60.0, 20.0, 1.0, now)
+            .with_controllable_load(load("pool-pump", 0.5, true))
+            .with_controllable_load(load("well-pump", 2.0, false))
+            .with_controllable_load(load("freezer", 0.2, false));
+
+        let dispatch = backup.island_dispatch(&inputs, 10.0, 5000.0, 2000.0);
+
+        // The freezer fits, the well pump would exceed the 2 kW discharge limit
+        assert_eq!(
+            dispatch.load_switches,
+            vec![
+                LoadSwitch {
+                    id: "pool-pump".to_string(),
+                    on: false
+                },
+                LoadSwitch {
+                    id: "well-pump".to_string(),
+                    on: false
+                },
+                LoadSwitch {
+                    id: "freezer".to_string(),
+                    on: true
+                },
+            ]
+        );
+        assert!((dispatch.battery_w + 200.0).abs() < 1e-6);
+
+        // An empty battery leaves only PV for the critical loads
+        let dispatch = backup.island_dispatch(&inputs, 60.0, 5000.0, 2000.0);
+        assert!(!dispatch.load_switches[2].on);
+        assert_eq!(dispatch.battery_w, 0.0);
+    }
+}
@@ -0,0 +1,338 @@
+//! # Export Limitation
+//!
+//! Holds the measured grid export under the limit the connection is
+//! approved for, 0 W on a zero-export connection. The planners already
+//! keep their schedules within the limit; this loop corrects what the
+//! forecasts and the power flow model miss, second by second.
+//!
+//! A PI loop on the grid meter's export decides how much power has to be
+//! absorbed. That power is taken by charging the battery harder first,
+//! then by raising the current of connected EVs, and only what neither can
+//! take is curtailed at the inverter. Less absorption releases the stages
+//! in reverse order, curtailment first.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::pid::PowerPidController;
+use crate::config::ExportLimitConfig;
+use crate::power_flow::EvAllocation;
+
+/// Charger id used for a site's only charger, outside a charger group
+pub const SINGLE_CHARGER_ID: &str = "ev";
+
+/// Room one connected charger has for extra current
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvHeadroom {
+    pub id: String,
+    /// Current granted by the control tick
+    pub base_current_a: f64,
+    /// Below this the charger cannot run
+    pub min_current_a: f64,
+    pub max_current_a: f64,
+    /// Charging power per amp, voltage times phases
+    pub watts_per_amp: f64,
+}
+
+/// What each stage could absorb on top of the control tick's commands
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportLimitHeadroom {
+    /// Extra battery charging power (W)
+    pub battery_w: f64,
+    /// Connected chargers, in the order they are raised
+    pub evs: Vec<EvHeadroom>,
+    /// AC output the inverter could give up (W)
+    pub curtailable_w: f64,
+}
+
+/// Extra current for one charger
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvBoost {
+    pub id: String,
+    pub current_a: f64,
+}
+
+/// One step of the export limit loop
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportLimitStep {
+    pub timestamp: DateTime<Utc>,
+    /// Measured export (W, negative = import)
+    pub export_w: f64,
+    /// Power the loop asked to absorb (W)
+    pub absorb_w: f64,
+    /// Extra battery charging power (W)
+    pub battery_w: f64,
+    pub ev_boosts: Vec<EvBoost>,
+    /// Inverter output given up (W)
+    pub curtail_w: f64,
+}
+
+/// PI loop holding the export at `limit_w - margin_w`
+#[derive(Debug)]
+pub struct ExportLimiter {
+    limit_w: f64,
+    target_w: f64,
+    pid: PowerPidController,
+    last: Option<ExportLimitStep>,
+}
+
+impl ExportLimiter {
+    /// `max_absorb_w` bounds the loop output: battery, EV and inverter
+    /// power together
+    pub fn new(config: &ExportLimitConfig, max_absorb_w: f64) -> Self {
+        // The loop output is the negated absorption, so export above the
+        // target drives it negative
+        let mut pid = PowerPidController::with_output_range(
+            config.kp,
+            config.ki,
+            config.kd,
+            -max_absorb_w.max(0.0),
+            0.0,
+        );
+        let target_w = config.limit_w - config.margin_w;
+        pid.set_target(target_w);
+        Self {
+            limit_w: config.limit_w,
+            target_w,
+            pid,
+            last: None,
+        }
+    }
+
+    pub fn limit_w(&self) -> f64 {
+        self.limit_w
+    }
+
+    /// Export the loop settles at
+    pub fn target_export_w(&self) -> f64 {
+        self.target_w
+    }
+
+    /// Take a measured export and split the absorption it calls for
+    pub fn step(
+        &mut self,
+        now: DateTime<Utc>,
+        export_w: f64,
+        headroom: &ExportLimitHeadroom,
+        dt: f64,
+    ) -> &ExportLimitStep {
+        let absorb_w = -self.pid.calculate(export_w, dt);
+
+        let battery_w = absorb_w.min(headroom.battery_w.max(0.0));
+        let mut rest_w = absorb_w - battery_w;
+
+        let mut ev_boosts = Vec::new();
+        for ev in &headroom.evs {
+            let room_a = ev.max_current_a - ev.base_current_a;
+            if rest_w <= 0.0 || room_a <= 0.0 || ev.watts_per_amp <= 0.0 {
+                continue;
+            }
+            // A paused charger only starts at its minimum current
+            let start_a = (ev.min_current_a - ev.base_current_a).max(0.0);
+            let wanted_a = rest_w / ev.watts_per_amp;
+            if wanted_a < start_a {
+                continue;
+            }
+            let current_a = wanted_a.min(room_a);
+            rest_w -= current_a * ev.watts_per_amp;
+            ev_boosts.push(EvBoost {
+                id: ev.id.clone(),
+                current_a,
+            });
+        }
+
+        let curtail_w = rest_w.clamp(0.0, headroom.curtailable_w.max(0.0));
+
+        self.last.insert(ExportLimitStep {
+            timestamp: now,
+            export_w,
+            absorb_w,
+            battery_w,
+            ev_boosts,
+            curtail_w,
+        })
+    }
+
+    pub fn last_step(&self) -> Option<&ExportLimitStep> {
+        self.last.as_ref()
+    }
+
+    /// Battery power added to the control tick's setpoint (W)
+    pub fn battery_offset_w(&self) -> f64 {
+        self.last.as_ref().map(|s| s.battery_w).unwrap_or(0.0)
+    }
+
+    /// Current added to a charger's grant (A)
+    pub fn ev_boost_a(&self, id: &str) -> f64 {
+        self.last
+            .as_ref()
+            .and_then(|s| s.ev_boosts.iter().find(|b| b.id == id))
+            .map(|b| b.current_a)
+            .unwrap_or(0.0)
+    }
+
+    /// Drop every offset, e.g. during an emergency stop or an outage
+    pub fn reset(&mut self) {
+        self.pid.reset();
+        self.last = None;
+    }
+}
+
+/// The tick's EV grants with the loop's extra current added
+pub fn with_ev_boosts(
+    grants: &[EvAllocation],
+    boosts: &[EvBoost],
+    watts_per_amp: impl Fn(&str) -> f64,
+) -> Vec<EvAllocation> {
+    let mut allocations = grants.to_vec();
+    for boost in boosts {
+        let allocation = match allocations.iter().position(|a| a.id == boost.id) {
+            Some(i) => &mut allocations[i],
+            None => {
+                allocations.push(EvAllocation {
+                    id: boost.id.clone(),
+                    current_a: 0.0,
+                    power_kw: 0.0,
+                });
+                allocations.last_mut().expect("allocation was just pushed")
+            }
+        };
+        allocation.current_a += boost.current_a;
+        allocation.power_kw += boost.current_a * watts_per_amp(&boost.id) / 1000.0;
+    }
+    allocations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Inverter, SimulatedInverter};
+    use crate::simulation::{HouseSimulator, HouseSimulatorConfig};
+    use chrono::{Duration, TimeZone};
+
+    const DT: f64 = 0.5;
+
+    fn config() -> ExportLimitConfig {
+        ExportLimitConfig {
+            enabled: true,
+            limit_w: 0.0,
+            margin_w: 100.0,
+            ..ExportLimitConfig::default()
+        }
+    }
+
+    fn ev() -> EvHeadroom {
+        EvHeadroom {
+            id: SINGLE_CHARGER_ID.to_string(),
+            base_current_a: 0.0,
+            min_current_a: 6.0,
+            max_current_a: 16.0,
+            watts_per_amp: 230.0 * 3.0,
+        }
+    }
+
+    /// Run the loop against the simulated inverter at midday and a
+    /// simulated house; returns the last step and the export it left
+    async fn run_closed_loop(evs: Vec<EvHeadroom>, steps: usize) -> (ExportLimitStep, f64) {
+        let start = Utc.with_ymd_and_hms(2025, 6, 16, 13, 0, 0).unwrap();
+        let inverter = SimulatedInverter::default_inverter();
+        let mut house = HouseSimulator::new(
+            HouseSimulatorConfig {
+                enable_appliance_events: false,
+                noise_std_dev: 0.0,
+                random_seed: Some(7),
+                ..HouseSimulatorConfig::default()
+            },
+            start.naive_utc(),
+        );
+        let mut limiter = ExportLimiter::new(&config(), 25_000.0);
+
+        let mut step: Option<ExportLimitStep> = None;
+        let mut export_w = 0.0;
+        for k in 0..steps {
+            let now = start + Duration::milliseconds((k as f64 * DT * 1000.0) as i64);
+            house.tick(now.naive_utc());
+            inverter.simulate_pv_production(13).await;
+            let (battery_w, ev_w, curtail_w) = match &step {
+                Some(s) => (
+                    s.battery_w,
+                    s.ev_boosts.iter().map(|b| b.current_a * 690.0).sum(),
+                    s.curtail_w,
+                ),
+                None => (0.0, 0.0, 0.0),
+            };
+            let available_w = inverter.read_state().await.unwrap().ac_output_power_w;
+            inverter
+                .set_export_limit(available_w - curtail_w)
+                .await
+                .unwrap();
+            let ac_w = inverter.read_state().await.unwrap().ac_output_power_w;
+            export_w = ac_w - house.load_kw() * 1000.0 - battery_w - ev_w;
+
+            let headroom = ExportLimitHeadroom {
+                battery_w: 3000.0,
+                evs: evs.clone(),
+                curtailable_w: available_w,
+            };
+            step = Some(limiter.step(now, export_w, &headroom, DT).clone());
+        }
+        (step.unwrap(), export_w)
+    }
+
+    #[tokio::test]
+    async fn test_surplus_goes_to_battery_then_ev() {
+        let (step, export_w) = run_closed_loop(vec![ev()], 60).await;
+
+        assert!((export_w + 100.0).abs() < 20.0, "export {export_w:.0} W");
+        assert_eq!(step.battery_w, 3000.0);
+        assert_eq!(step.ev_boosts.len(), 1);
+        assert!(step.ev_boosts[0].current_a > 6.0 && step.ev_boosts[0].current_a < 16.0);
+        assert!(step.curtail_w < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_inverter_curtailed_without_ev() {
+        let (step, export_w) = run_closed_loop(Vec::new(), 60).await;
+
+        assert!((export_w + 100.0).abs() < 20.0, "export {export_w:.0} W");
+        assert_eq!(step.battery_w, 3000.0);
+        assert!(step.curtail_w > 4000.0);
+    }
+
+    #[test]
+    fn test_paused_charger_needs_room_for_minimum_current() {
+        let now = Utc.with_ymd_and_hms(2025, 6, 16, 13, 0, 0).unwrap();
+        let mut limiter = ExportLimiter::new(
+            &ExportLimitConfig {
+                kp: 1.0,
+                ki: 0.0,
+                ..config()
+            },
+            25_000.0,
+        );
+        let headroom = ExportLimitHeadroom {
+            battery_w: 1000.0,
+            evs: vec![ev()],
+            curtailable_w: 8000.0,
+        };
+
+        // 3.9 kW to absorb: the battery takes 1 kW, 2.9 kW cannot start the EV
+        let step = limiter.step(now, 3800.0, &headroom, DT);
+        assert_eq!(step.battery_w, 1000.0);
+        assert!(step.ev_boosts.is_empty());
+        assert!((step.curtail_w - 2900.0).abs() < 1e-6);
+
+        let allocations = with_ev_boosts(
+            &[],
+            &[EvBoost {
+                id: SINGLE_CHARGER_ID.to_string(),
+                current_a: 8.0,
+            }],
+            |_| 690.0,
+        );
+        assert_eq!(allocations[0].current_a, 8.0);
+        assert!((allocations[0].power_kw - 5.52).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,406 @@
+//! # Control Loop Tiers
+//!
+//! The controller runs three loops at different rates:
+//! - **Fast** (sub-second): tracks the grid meter with the battery while
+//!   the plan calls for self-consumption, slew-limited by a `PowerRamp`
+//! - **Medium** (the control tick): follows the schedule through the power
+//!   flow model and publishes the setpoint the fast loop works around
+//! - **Slow**: re-optimizes the schedule
+//!
+//! Each loop runs on a task of its own and only exchanges the latest
+//! values with the others, so a stalled optimizer or a slow tick never
+//! holds up the fast loop. Every loop reports its iteration times to a
+//! `LoopMonitor`, which counts missed deadlines and notices stalls.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::power_transition::{PowerRamp, PowerRampConfig};
+use crate::config::{Config, FastLoopConfig};
+
+/// A loop counts as stalled after this many periods without an iteration
+const STALL_PERIODS: u32 = 3;
+
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlLoop {
+    Fast,
+    Medium,
+    Slow,
+}
+
+/// Timing of one loop
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize)]
+pub struct LoopStats {
+    pub control_loop: ControlLoop,
+    /// Period, also the deadline of each iteration (ms)
+    pub period_ms: u64,
+    pub iterations: u64,
+    /// Iterations that took longer than the period
+    pub deadline_misses: u64,
+    pub last_duration_ms: f64,
+    pub mean_duration_ms: f64,
+    pub max_duration_ms: f64,
+    pub last_completed_at: Option<DateTime<Utc>>,
+    /// No iteration completed for several periods
+    pub stalled: bool,
+}
+
+#[derive(Debug)]
+struct LoopTiming {
+    iterations: u64,
+    deadline_misses: u64,
+    last_duration: Duration,
+    total_duration: Duration,
+    max_duration: Duration,
+    last_completed: Option<(Instant, DateTime<Utc>)>,
+}
+
+/// Deadline and stall monitoring of one loop
+#[derive(Debug)]
+pub struct LoopMonitor {
+    control_loop: ControlLoop,
+    period: Duration,
+    started: Instant,
+    timing: Mutex<LoopTiming>,
+}
+
+/// Records the iteration's duration when dropped, so every `continue`
+/// and early return of a loop body is counted
+pub struct LoopIteration<'a> {
+    monitor: &'a LoopMonitor,
+    started: Instant,
+}
+
+impl Drop for LoopIteration<'_> {
+    fn drop(&mut self) {
+        self.monitor.record(self.started.elapsed());
+    }
+}
+
+impl LoopMonitor {
+    pub fn new(control_loop: ControlLoop, period: Duration) -> Self {
+        Self {
+            control_loop,
+            period,
+            started: Instant::now(),
+            timing: Mutex::new(LoopTiming {
+                iterations: 0,
+                deadline_misses: 0,
+                last_duration: Duration::ZERO,
+                total_duration: Duration::ZERO,
+                max_duration: Duration::ZERO,
+                last_completed: None,
+            }),
+        }
+    }
+
+    /// Start timing an iteration
+    pub fn start(&self) -> LoopIteration<'_> {
+        LoopIteration {
+            monitor: self,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record a finished iteration; returns whether it missed its deadline
+    pub fn record(&self, duration: Duration) -> bool {
+        let mut timing = self.timing.lock().expect("loop timing lock poisoned");
+        let missed = duration > self.period;
+        // Only the first of a run of misses is logged
+        if missed && timing.last_duration <= self.period {
+            warn!(
+                control_loop = ?self.control_loop,
+                duration_ms = duration.as_millis() as u64,
+                period_ms = self.period.as_millis() as u64,
+                "Control loop missed its deadline"
+            );
+        }
+        timing.iterations += 1;
+        timing.deadline_misses += u64::from(missed);
+        timing.last_duration = duration;
+        timing.total_duration += duration;
+        timing.max_duration = timing.max_duration.max(duration);
+        timing.last_completed = Some((Instant::now(), Utc::now()));
+        missed
+    }
+
+    /// Whether no iteration has completed for `STALL_PERIODS` periods
+    pub fn is_stalled(&self) -> bool {
+        self.is_stalled_at(Instant::now())
+    }
+
+    pub fn is_stalled_at(&self, now: Instant) -> bool {
+        let timing = self.timing.lock().expect("loop timing lock poisoned");
+        let since = timing
+            .last_completed
+            .map(|(at, _)| at)
+            .unwrap_or(self.started);
+        now.saturating_duration_since(since) > self.period * STALL_PERIODS
+    }
+
+    pub fn stats(&self) -> LoopStats {
+        let stalled = self.is_stalled();
+        let timing = self.timing.lock().expect("loop timing lock poisoned");
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        LoopStats {
+            control_loop: self.control_loop,
+            period_ms: self.period.as_millis() as u64,
+            iterations: timing.iterations,
+            deadline_misses: timing.deadline_misses,
+            last_duration_ms: ms(timing.last_duration),
+            mean_duration_ms: ms(timing.total_duration) / timing.iterations.max(1) as f64,
+            max_duration_ms: ms(timing.max_duration),
+            last_completed_at: timing.last_completed.map(|(_, at)| at),
+            stalled,
+        }
+    }
+}
+
+/// Monitors of the controller's loops; `fast` is `None` when the fast
+/// loop is off
+#[derive(Debug)]
+pub struct LoopMonitors {
+    pub fast: Option<LoopMonitor>,
+    pub medium: LoopMonitor,
+    pub slow: LoopMonitor,
+}
+
+impl LoopMonitors {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            fast: config.fast_loop.enabled.then(|| {
+                LoopMonitor::new(
+                    ControlLoop::Fast,
+                    Duration::from_millis(config.fast_loop.interval_ms),
+                )
+            }),
+            medium: LoopMonitor::new(
+                ControlLoop::Medium,
+                Duration::from_secs(config.controller.tick_seconds.max(1)),
+            ),
+            slow: LoopMonitor::new(
+                ControlLoop::Slow,
+                Duration::from_secs(config.controller.reoptimize_every_minutes.max(1) * 60),
+            ),
+        }
+    }
+
+    pub fn stats(&self) -> Vec<LoopStats> {
+        self.fast
+            .iter()
+            .chain([&self.medium, &self.slow])
+            .map(LoopMonitor::stats)
+            .collect()
+    }
+}
+
+/// What the control tick hands the fast loop
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FastLoopReference {
+    /// Battery setpoint of the tick, before reserve and export limit
+    /// offsets (W, positive = charge)
+    pub setpoint_w: f64,
+    /// Grid flow the tick planned at that setpoint (W, positive = import),
+    /// `None` when the power flow model failed
+    pub planned_grid_w: Option<f64>,
+}
+
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackingMode {
+    /// The battery follows the grid meter to hold the grid target
+    SelfConsumption,
+    /// The battery holds the tick's setpoint
+    Schedule,
+}
+
+/// Battery setpoint of the fast loop
+///
+/// Corrects a share of the measured grid deviation each step and slews
+/// towards the result, so a kettle or a passing cloud is covered within a
+/// second or two without the battery jumping.
+#[derive(Debug)]
+pub struct GridTracker {
+    target_grid_w: f64,
+    tracking_band_w: f64,
+    gain: f64,
+    ramp: PowerRamp,
+    mode: TrackingMode,
+}
+
+impl GridTracker {
+    pub fn new(config: &FastLoopConfig, initial_w: f64) -> Self {
+        let ramp = PowerRamp::with_initial_power(
+            PowerRampConfig {
+                max_ramp_rate_w_per_s: config.max_ramp_rate_w_per_s,
+                min_ramp_threshold_w: config.deadband_w,
+                emergency_mode: false,
+            },
+            initial_w,
+        );
+        Self {
+            target_grid_w: config.target_grid_w,
+            tracking_band_w: config.tracking_band_w,
+            gain: config.gain,
+            ramp,
+            mode: TrackingMode::Schedule,
+        }
+    }
+
+    pub fn mode(&self) -> TrackingMode {
+        self.mode
+    }
+
+    /// Battery setpoint the loop is at (W)
+    pub fn power_w(&self) -> f64 {
+        self.ramp.current_power_w()
+    }
+
+    /// Take a grid measurement and return the battery setpoint
+    ///
+    /// `grid_w` is the measured grid flow less the offsets other loops add
+    /// on top of the setpoint, so the tracker does not cancel them. Without
+    /// a live tick the plan is stale and the battery falls back to
+    /// self-consumption. A new target is slewed towards from the next step.
+    pub fn step(
+        &mut self,
+        reference: &FastLoopReference,
+        medium_stalled: bool,
+        grid_w: f64,
+        min_w: f64,
+        max_w: f64,
+    ) -> f64 {
+        let self_consumption = medium_stalled
+            || reference
+                .planned_grid_w
+                .is_some_and(|g| g.abs() <= self.tracking_band_w);
+        self.mode = if self_consumption {
+            TrackingMode::SelfConsumption
+        } else {
+            TrackingMode::Schedule
+        };
+
+        // The measurement reflects the setpoint of the previous step
+        let target_w = match self.mode {
+            TrackingMode::SelfConsumption => {
+                self.ramp.current_power_w() - self.gain * (grid_w - self.target_grid_w)
+            }
+            TrackingMode::Schedule => reference.setpoint_w,
+        };
+        self.ramp.retarget(target_w.clamp(min_w, max_w.max(min_w)))
+    }
+
+    /// Restart from a known battery power, e.g. after an emergency stop
+    pub fn reset(&mut self, power_w: f64) {
+        self.ramp = PowerRamp::with_initial_power(self.ramp.config().clone(), power_w);
+        self.mode = TrackingMode::Schedule;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FastLoopConfig {
+        FastLoopConfig {
+            enabled: true,
+            max_ramp_rate_w_per_s: 1000.0,
+            ..FastLoopConfig::default()
+        }
+    }
+
+    fn self_consumption(setpoint_w: f64) -> FastLoopReference {
+        FastLoopReference {
+            setpoint_w,
+            planned_grid_w: Some(0.0),
+        }
+    }
+
+    #[test]
+    fn test_monitor_counts_deadline_misses_and_stalls() {
+        let monitor = LoopMonitor::new(ControlLoop::Fast, Duration::from_millis(500));
+
+        assert!(!monitor.record(Duration::from_millis(20)));
+        assert!(monitor.record(Duration::from_millis(700)));
+        {
+            let _iteration = monitor.start();
+        }
+
+        let stats = monitor.stats();
+        assert_eq!(stats.iterations, 3);
+        assert_eq!(stats.deadline_misses, 1);
+        assert!((stats.max_duration_ms - 700.0).abs() < 1e-9);
+        assert!(!stats.stalled);
+
+        assert!(!monitor.is_stalled_at(Instant::now() + Duration::from_millis(1400)));
+        assert!(monitor.is_stalled_at(Instant::now() + Duration::from_millis(1600)));
+    }
+
+    #[tokio::test]
+    async fn test_tracker_holds_grid_at_zero_under_fluctuating_load() {
+        let mut tracker = GridTracker::new(
+            &FastLoopConfig {
+                max_ramp_rate_w_per_s: 100_000.0,
+                ..config()
+            },
+            0.0,
+        );
+        let pv_w = 4000.0;
+        let mut grid_w = 0.0;
+        for k in 0..30 {
+            // A 2 kW kettle switches on halfway through
+            let load_w = if k < 15 { 1500.0 } else { 3500.0 };
+            let battery_w = tracker.step(&self_consumption(0.0), false, grid_w, -5000.0, 5000.0);
+            grid_w = load_w - pv_w + battery_w;
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(tracker.mode(), TrackingMode::SelfConsumption);
+        assert!(grid_w.abs() < 50.0, "grid {grid_w:.0} W");
+        assert!((tracker.power_w() - 500.0).abs() < 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_tracker_slews_to_schedule_setpoint() {
+        let mut tracker = GridTracker::new(&config(), 0.0);
+        let charge = FastLoopReference {
+            setpoint_w: 5000.0,
+            planned_grid_w: Some(6500.0),
+        };
+
+        assert!(tracker.step(&charge, false, 1500.0, -5000.0, 5000.0) < 50.0);
+        assert_eq!(tracker.mode(), TrackingMode::Schedule);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let power_w = tracker.step(&charge, false, 1500.0, -5000.0, 5000.0);
+        assert!(power_w > 100.0 && power_w < 600.0, "power {power_w:.0} W");
+
+        // A stalled tick leaves the plan stale: back to self-consumption
+        tracker.step(&charge, true, 1500.0, -5000.0, 5000.0);
+        assert_eq!(tracker.mode(), TrackingMode::SelfConsumption);
+    }
+
+    #[test]
+    fn test_tracker_respects_battery_limits() {
+        let mut tracker = GridTracker::new(
+            &FastLoopConfig {
+                gain: 1.0,
+                deadband_w: 10_000.0,
+                ..config()
+            },
+            0.0,
+        );
+
+        // A full battery cannot take the 3 kW export
+        let power_w = tracker.step(&self_consumption(0.0), false, -3000.0, -5000.0, 0.0);
+        assert_eq!(power_w, 0.0);
+        let power_w = tracker.step(&self_consumption(0.0), false, 2000.0, -5000.0, 0.0);
+        assert_eq!(power_w, -2000.0);
+    }
+}
@@ -1,7 +1,11 @@
 #![allow(dead_code)]
+pub mod backup;
+pub mod export_limit;
+pub mod loops;
 pub mod maintenance;
 pub mod pid;
 pub mod power_transition;
+pub mod reserve;
 pub mod safety;
 pub mod safety_monitor;
 pub mod scheduler;
@@ -16,22 +20,36 @@ use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 use crate::config::Config;
+use crate::ocpp::central_system::{CentralSystem, CentralSystemConfig};
 use crate::power_flow::{
     constraints::{EconomicObjectives, PhysicalConstraints, SafetyConstraints},
     model::PowerFlowModel,
-    AllConstraints, PowerFlowInputs,
+    AllConstraints, EvAllocation, EvAllocationPolicy, LoadSwitch, PowerFlowInputs,
 };
+use crate::simulation::three_phase::{LoadDistribution, ThreePhasePower};
 use crate::simulation::{Environment, EnvironmentConfig};
 
 use crate::domain::{
-    Battery, BatteryCapabilities, BatteryState, Forecast24h, GridConnection, GridLimits,
-    GridStatistics, GridStatus, HealthStatus, PriceArea, Schedule,
+    degradation_model, AggregateBattery, Battery, BatteryCapabilities, BatteryChemistry,
+    BatteryPack, BatteryState, ChargePoint, ControllableLoadGroup, CycleTracker,
+    DegradationModel, DispatchStrategy, EvChargerGroup, FlexibleThermalLoad, Forecast24h, GridConnection, GridLimits, GridStatistics,
+    GridStatus, HealthStatus, LoadConstraints, ManagedLoad, PackLimits, PeakLedger, PeakTariffRules, PriceArea, ReserveCommitment,
+    ReserveOffer, Schedule, ThermalLoadMode, ThermalLoadState, VehiclePlan,
 };
 use crate::forecast::{
     ConsumptionForecaster, ElprisetJustNuPriceForecaster, ForecastEngine, GeoLocation,
     SimpleConsumptionForecaster, SimpleProductionForecaster, SmhiClient, WeatherForecast,
 };
-use crate::optimizer::{BatteryOptimizer, Constraints, DynamicProgrammingOptimizer, SystemState};
+use crate::optimizer::explain::{explain_schedule, Baseline, ScheduleExplanation};
+use crate::optimizer::loads::{LoadScheduler, LoadStart};
+use crate::optimizer::mpc::{MpcMeasurement, MpcPlanner, MpcSettings};
+use crate::optimizer::thermal::{LumpedThermalModel, ThermalPlan, ThermalPlanSettings, ThermalPlanner};
+use crate::optimizer::{
+    BatteryOptimizer, Constraints, DynamicProgrammingOptimizer, EvChargeRequest,
+    OptimizationStrategy, ScenarioOptimizer, ScenarioSettings, SystemState,
+};
+#[cfg(feature = "db")]
+use crate::repo::optimization_runs::OptimizationRunRow;
 use crate::repo::Repositories;
 
 #[derive(Clone)]
@@ -40,6 +58,8 @@ pub struct AppState {
     pub controller: Arc<BatteryController>,
     pub repos: Arc<Repositories>,
     pub safety_monitor: Arc<safety_monitor::SafetyMonitor>,
+    /// OCPP central system chargers connect to, if enabled
+    pub ocpp: Option<CentralSystem>,
 }
 
 impl AppState {
@@ -53,40 +73,11 @@ impl AppState {
             max_discharge_kw: cfg.battery.max_discharge_kw,
             efficiency: cfg.battery.efficiency,
             degradation_per_cycle: cfg.battery.degradation_per_cycle,
-            chemistry: crate::domain::BatteryChemistry::LiFePO4,
+            chemistry: cfg.battery.chemistry,
         };
 
-        // Validate capabilities
-        if !caps.capacity_kwh.is_finite() || caps.capacity_kwh <= 0.0 {
-            bail!(
-                "Battery capacity_kwh must be positive and finite, got: {}",
-                caps.capacity_kwh
-            );
-        }
-        if !caps.max_charge_kw.is_finite() || caps.max_charge_kw <= 0.0 {
-            bail!(
-                "Battery max_charge_kw must be positive and finite, got: {}",
-                caps.max_charge_kw
-            );
-        }
-        if !caps.max_discharge_kw.is_finite() || caps.max_discharge_kw <= 0.0 {
-            bail!(
-                "Battery max_discharge_kw must be positive and finite, got: {}",
-                caps.max_discharge_kw
-            );
-        }
-        if !caps.efficiency.is_finite() || caps.efficiency <= 0.0 || caps.efficiency > 1.0 {
-            bail!(
-                "Battery efficiency must be between 0 and 1, got: {}",
-                caps.efficiency
-            );
-        }
-        if !caps.degradation_per_cycle.is_finite() || caps.degradation_per_cycle < 0.0 {
-            bail!(
-                "Battery degradation_per_cycle must be non-negative and finite, got: {}",
-                caps.degradation_per_cycle
-            );
-        }
+        validate_battery_capabilities(&caps)?;
+
         // CRITICAL FIX: Use DeviceFactory to respect hardware configuration
         // Previously, this was hardcoded to use SimulatedBattery regardless of config
         use crate::hardware::factory::{DeviceFactory, HardwareMode};
@@ -99,12 +90,36 @@ impl AppState {
         };
 
         let factory = DeviceFactory::with_config(hardware_mode, cfg.clone());
-        let battery = factory
-            .create_battery(
-                caps.clone(),
-                cfg.battery.initial_soc_percent,
-                cfg.battery.ambient_temp_c,
-            )
+        let (battery, battery_bank) = if cfg.batteries.is_empty() {
+            let battery = factory
+                .create_battery(
+                    caps.clone(),
+                    cfg.battery.initial_soc_percent,
+                    cfg.battery.ambient_temp_c,
+                )
+                .await;
+            (battery, None)
+        } else {
+            let bank = Arc::new(Self::create_battery_bank(&cfg, &factory).await?);
+            (Arc::clone(&bank) as Arc<dyn Battery>, Some(bank))
+        };
+        // A bank's ratings are the sum of its packs
+        let caps = match battery_bank {
+            Some(ref bank) => bank.capabilities(),
+            None => caps,
+        };
+
+        // Simulated inverter uses these; Modbus inverters report their own ratings
+        let inverter = factory
+            .create_inverter(crate::domain::InverterCapabilities {
+                rated_power_w: 10000.0,
+                max_dc_input_w: 15000.0,
+                max_ac_output_w: 10000.0,
+                max_efficiency_percent: 97.5,
+                mppt_channels: 2,
+                supports_export_limit: true,
+                supports_frequency_regulation: true,
+            })
             .await;
 
         // AUDIT FIX #7: Handle price forecaster initialization gracefully
@@ -136,11 +151,17 @@ impl AppState {
         let consumption_forecaster: Box<dyn ConsumptionForecaster> =
             Box::new(SimpleConsumptionForecaster);
 
-        let forecast_engine = Arc::new(ForecastEngine::new(
-            price,
-            consumption_forecaster,
-            Box::new(SimpleProductionForecaster::default()),
-        ));
+        let forecast_engine = Arc::new(
+            ForecastEngine::new(
+                price,
+                consumption_forecaster,
+                Box::new(SimpleProductionForecaster::default()),
+            )
+            .with_resolution(
+                cfg.optimization.time_step_minutes,
+                cfg.optimization.horizon_hours,
+            ),
+        );
 
         // Use MILP optimizer if optimization feature is enabled, otherwise use DP
         #[cfg(feature = "optimization")]
@@ -149,37 +170,174 @@ impl AppState {
         #[cfg(not(feature = "optimization"))]
         let strategy = Box::new(DynamicProgrammingOptimizer);
 
+        // The stochastic strategy scores the base strategy's plans over forecast scenarios
+        let strategy: Box<dyn OptimizationStrategy> = match cfg.optimization.strategy {
+            crate::config::OptimizationStrategy::Stochastic => {
+                let scenarios = &cfg.optimization.scenarios;
+                Box::new(ScenarioOptimizer::new(
+                    strategy,
+                    ScenarioSettings {
+                        num_scenarios: scenarios.num_scenarios,
+                        num_candidates: scenarios.num_candidates,
+                        pv_spread: scenarios.pv_spread,
+                        load_spread: scenarios.load_spread,
+                        price_spread: scenarios.price_spread,
+                        cvar_alpha: scenarios.cvar_alpha,
+                        risk_weight: scenarios.risk_weight,
+                        seed: scenarios.seed,
+                    },
+                ))
+            }
+            _ => strategy,
+        };
+
         let optimizer = Arc::new(BatteryOptimizer { strategy });
+
+        let mpc = match cfg.optimization.strategy {
+            crate::config::OptimizationStrategy::Mpc => {
+                let mpc = &cfg.optimization.mpc;
+                info!(
+                    horizon_periods = mpc.horizon_periods,
+                    budget_ms = mpc.budget_ms,
+                    "MPC re-solve enabled"
+                );
+                Some(Arc::new(MpcPlanner::new(MpcSettings {
+                    horizon_periods: mpc.horizon_periods,
+                    soc_step_percent: mpc.soc_step_percent,
+                    power_levels: mpc.power_levels,
+                    budget: std::time::Duration::from_millis(mpc.budget_ms),
+                })))
+            }
+            _ => None,
+        };
         let schedule = Arc::new(RwLock::new(None::<Schedule>));
 
+        let peak_tariff = PeakTariffRules {
+            top_n: cfg.peak_tariff.top_n,
+            one_peak_per_day: cfg.peak_tariff.one_peak_per_day,
+            weekdays_only: cfg.peak_tariff.weekdays_only,
+            window_start_hour: cfg.peak_tariff.window_start_hour,
+            window_end_hour: cfg.peak_tariff.window_end_hour,
+            off_window_weight: cfg.peak_tariff.off_window_weight,
+            timezone: cfg.household.timezone.clone(),
+        };
+        let peak_ledger = if cfg.peak_tariff.ledger_path.exists() {
+            PeakLedger::load(&cfg.peak_tariff.ledger_path)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!(error = %e, "Starting a new peak ledger");
+                    PeakLedger::new()
+                })
+        } else {
+            PeakLedger::new()
+        };
+
+        // Wear is priced from the chemistry's degradation curve, the
+        // configured one when the device does not report its own
+        let degradation = degradation_model(match battery.capabilities().chemistry {
+            BatteryChemistry::Unknown => caps.chemistry,
+            chemistry => chemistry,
+        });
+
+        // Stored history is keyed by the configured household
+        let household_id = Uuid::parse_str(&cfg.household.id).unwrap_or_else(|_| {
+            warn!(
+                "Household id '{}' is not a UUID, stored history will not carry over restarts",
+                cfg.household.id
+            );
+            Uuid::new_v4()
+        });
+
+        // Cycles are counted per local day; the health estimate continues
+        // from the days stored before a restart
+        let mut cycle_tracker = CycleTracker::new(
+            Arc::clone(&degradation),
+            caps.capacity_kwh,
+            cfg.household.timezone.parse().unwrap_or(chrono_tz::UTC),
+        );
+        #[cfg(feature = "db")]
+        let stored_days =
+            Self::load_battery_cycles(&repos, household_id, degradation.as_ref()).await;
+        #[cfg(not(feature = "db"))]
+        let stored_days: Vec<crate::domain::DailyCycles> = Vec::new();
+        cycle_tracker.resume(&stored_days);
+        if !stored_days.is_empty() {
+            info!(
+                days = stored_days.len(),
+                health_percent = ?cycle_tracker.health_percent(),
+                "Resumed battery health estimate"
+            );
+        }
+
+        // Reserve capacity sold through the aggregator; the optimizer keeps
+        // headroom for it and the fast loop delivers it
+        let reserve_offer = if cfg.reserve.enabled {
+            ReserveOffer {
+                hours: cfg.reserve.hours.clone(),
+                fcr_d_up_kw: cfg.reserve.fcr_d_up_kw,
+                fcr_d_down_kw: cfg.reserve.fcr_d_down_kw,
+                ffr_kw: cfg.reserve.ffr_kw,
+                ffr_support_seconds: cfg.reserve.ffr_support_seconds,
+                timezone: cfg.household.timezone.clone(),
+            }
+        } else {
+            ReserveOffer::default()
+        };
+        let reserve = cfg.reserve.enabled.then(|| {
+            Arc::new(RwLock::new(reserve::FrequencyReserve::new(
+                reserve_offer.clone(),
+                cfg.reserve.ffr_trigger_hz,
+            )))
+        });
+
         // Initialize constraints with actual battery capabilities
         let constraints = Constraints {
             min_soc_percent: cfg.battery.min_soc_percent,
             max_soc_percent: cfg.battery.max_soc_percent,
             max_cycles_per_day: 1.0,
-            max_power_grid_kw: 11.0,
-            v2g_enabled: false,
+            max_power_grid_kw: cfg.grid.max_import_kw,
+            v2g_enabled: cfg.optimization.allow_v2g_discharge,
             battery_capacity_kwh: caps.capacity_kwh,
             battery_max_charge_kw: caps.max_charge_kw,
             battery_max_discharge_kw: caps.max_discharge_kw,
             battery_efficiency: caps.efficiency,
             battery_degradation_per_cycle: caps.degradation_per_cycle,
             battery_replacement_cost_sek: cfg.battery.replacement_cost_sek,
-            peak_power_tariff_sek_per_kw: 100.0, // Swedish "Effekttariff" (typical value)
+            battery_wear: Some(degradation.linearized(
+                caps.capacity_kwh,
+                caps.max_charge_kw.max(caps.max_discharge_kw),
+                cfg.battery.replacement_cost_sek,
+                cfg.battery.ambient_temp_c,
+            )),
+            peak_power_tariff_sek_per_kw: cfg.peak_tariff.price_sek_per_kw_month,
+            peak_power_incurred_kw: 0.0, // Taken from the peak ledger on each run
+            peak_tariff,
+            max_export_grid_kw: cfg.export_limit.max_export_kw(cfg.grid.max_export_kw),
+            export_fee_sek_per_kwh: cfg.optimization.export_fee_sek_per_kwh,
+            allow_pv_curtailment: cfg.optimization.allow_pv_curtailment,
+            prefer_self_consumption: cfg.optimization.prefer_self_consumption,
+            reserve: reserve_offer,
+            backup_reserve_soc_percent: if cfg.backup.enabled {
+                cfg.backup.reserve_soc_percent
+            } else {
+                0.0
+            },
         };
 
         // Initialize power flow constraints for real-time safety checks
         let power_flow_constraints = Arc::new(AllConstraints {
             physical: PhysicalConstraints {
-                max_grid_import_kw: 11.0,
-                max_grid_export_kw: 11.0,
+                max_grid_import_kw: cfg.grid.max_import_kw,
+                max_grid_export_kw: cfg.export_limit.max_export_kw(cfg.grid.max_export_kw),
                 max_battery_charge_kw: caps.max_charge_kw,
                 max_battery_discharge_kw: caps.max_discharge_kw,
                 evse_min_current_a: 6.0,
                 evse_max_current_a: 32.0,
-                phases: 1,
-                max_current_per_phase_a: Some(32.0),
-                grid_voltage_v: 230.0,
+                phases: cfg.grid.phases,
+                max_current_per_phase_a: Some(cfg.grid.fuse_rating_amps),
+                grid_voltage_v: cfg.grid.voltage_v,
+                pv_phase: cfg.grid.pv_phase,
+                battery_phase: cfg.grid.battery_phase,
             },
             safety: SafetyConstraints {
                 battery_min_soc_percent: cfg.battery.min_soc_percent,
@@ -196,12 +354,36 @@ impl AppState {
                 ev_departure_time: None,
                 ev_target_soc_percent: None,
                 low_price_charge_rate: cfg.optimization.low_price_charge_rate,
+                ev_allocation: match cfg.ev_allocation {
+                    crate::config::EvAllocation::FairShare => EvAllocationPolicy::FairShare,
+                    crate::config::EvAllocation::Priority => EvAllocationPolicy::Priority,
+                    crate::config::EvAllocation::Deadline => EvAllocationPolicy::Deadline,
+                },
             },
         });
 
         // CRITICAL FIX: Initialize V2X Controller if EV charger is available
         // This enables vehicle-to-grid/home functionality
-        let ev_charger = factory.create_ev_charger();
+        let ocpp = cfg
+            .hardware
+            .ocpp
+            .as_ref()
+            .filter(|o| o.central_system_enabled)
+            .map(|o| CentralSystem::new(CentralSystemConfig::from(o)));
+        let ev_charger_group = if cfg.ev_chargers.is_empty() {
+            None
+        } else {
+            Some(Arc::new(
+                Self::create_ev_charger_group(&cfg, &factory, ocpp.as_ref(), power_flow_constraints.economic.ev_allocation)
+                    .await?,
+            ))
+        };
+        // On a multi-charger site V2X and emergency stops address the first charger
+        let ev_charger = match (&ev_charger_group, &ocpp) {
+            (Some(group), _) => Arc::clone(&group.chargers()[0].charger),
+            (None, Some(central_system)) => factory.create_ocpp_ev_charger(central_system).await,
+            (None, None) => factory.create_ev_charger(),
+        };
         let ev_charger_clone = Arc::clone(&ev_charger);
         let v2x_controller = if ev_charger.v2x_capabilities().is_some() {
             let v2x_config = v2x_controller::V2XConfig::default();
@@ -216,7 +398,7 @@ impl AppState {
         // This provides defense-in-depth against hardware failures
         let safety_config = safety_monitor::SafetyMonitorConfig {
             check_interval_s: 1,   // Fast 1-second monitoring
-            fuse_rating_a: 25.0,   // Default 25A fuse
+            fuse_rating_a: cfg.grid.fuse_rating_amps,
             fuse_trip_margin: 0.1, // Trip at 90% of rating
             grid_voltage_min_v: 207.0,
             grid_voltage_max_v: 253.0,
@@ -229,7 +411,8 @@ impl AppState {
             control_loop_timeout_s: 30, // 30s timeout for control loop
             enable_emergency_stop: true,
         };
-        let (safety_monitor, _safety_rx) = safety_monitor::SafetyMonitor::new(safety_config);
+        let (mut safety_monitor, _safety_rx) = safety_monitor::SafetyMonitor::new(safety_config);
+        safety_monitor.set_hardware(Some(Arc::clone(&battery)), Some(Arc::clone(&inverter)));
         let safety_monitor_arc = Arc::new(safety_monitor);
 
         // CRITICAL FIX: Initialize simulation environment if hardware mode is Simulated
@@ -252,6 +435,44 @@ impl AppState {
             None
         };
 
+        // Measured grid flows; without a meter the controller estimates them
+        let grid_meter = factory.create_grid_meter(environment.clone()).await;
+        if grid_meter.is_none() {
+            warn!("No grid meter available, house load and grid status will be estimated");
+        }
+
+        // Heat pump pre-heated in cheap hours, when enabled and reachable
+        let thermal_load = factory.create_thermal_load().await;
+        let thermal_planner = thermal_load
+            .as_ref()
+            .map(|heat_pump| Arc::new(Self::create_thermal_planner(&cfg, heat_pump.as_ref())));
+
+        // Switchable loads run in the cheapest periods of their windows
+        let controllable_loads = if cfg.controllable_loads.is_empty() {
+            None
+        } else {
+            Some(Arc::new(Self::create_controllable_load_group(&cfg, &factory).await?))
+        };
+
+        // The export limit loop can at most charge the battery, run every
+        // charger at full current and switch the inverter off
+        let export_limiter = cfg.export_limit.enabled.then(|| {
+            let ev_max_w: f64 = match ev_charger_group {
+                Some(ref group) => group
+                    .chargers()
+                    .iter()
+                    .map(|cp| cp.charger.capabilities().power_max_kw * 1000.0)
+                    .sum(),
+                None => ev_charger_clone.capabilities().power_max_kw * 1000.0,
+            };
+            let max_absorb_w =
+                caps.max_charge_kw * 1000.0 + ev_max_w + inverter.capabilities().max_ac_output_w;
+            Arc::new(RwLock::new(export_limit::ExportLimiter::new(
+                &cfg.export_limit,
+                max_absorb_w,
+            )))
+        });
+
         // CRITICAL FIX: Create bounded channel for state recording to prevent resource leak
         // Limits pending database writes to 100 to prevent OOM during long simulations
         #[cfg(feature = "db")]
@@ -267,9 +488,9 @@ impl AppState {
         > = None;
 
         let history_capacity = ((24 * 60 * 60) / cfg.controller.tick_seconds.max(1)) as usize;
-        let household_id = Uuid::new_v4();
         let controller = Arc::new(BatteryController {
             battery,
+            inverter,
             optimizer,
             forecast_engine,
             schedule,
@@ -287,6 +508,38 @@ impl AppState {
             ev_charger: ev_charger_clone,
             safety_monitor: Some(Arc::clone(&safety_monitor_arc)),
             environment,
+            grid_meter,
+            battery_bank,
+            ev_charger_group,
+            peak_ledger: Arc::new(RwLock::new(peak_ledger)),
+            mpc,
+            last_forecast: Arc::new(RwLock::new(None)),
+            cycle_tracker: Arc::new(RwLock::new(cycle_tracker)),
+            degradation,
+            reserve,
+            battery_setpoint_w: Arc::new(RwLock::new(0.0)),
+            optimization_runs: Arc::new(RwLock::new(VecDeque::with_capacity(
+                MAX_OPTIMIZATION_RUNS,
+            ))),
+            thermal_load,
+            thermal_planner,
+            thermal_plan: Arc::new(RwLock::new(None)),
+            thermal_mode: Arc::new(RwLock::new(None)),
+            controllable_loads,
+            backup: cfg.backup.enabled.then(|| {
+                Arc::new(RwLock::new(backup::BackupSupervisor::new(
+                    cfg.backup.clone(),
+                    Utc::now(),
+                )))
+            }),
+            export_limiter,
+            ev_grants: Arc::new(RwLock::new(Vec::new())),
+            loops: Arc::new(loops::LoopMonitors::new(&cfg)),
+            fast_tracker: cfg
+                .fast_loop
+                .enabled
+                .then(|| Arc::new(RwLock::new(loops::GridTracker::new(&cfg.fast_loop, 0.0)))),
+            fast_reference: Arc::new(RwLock::new(loops::FastLoopReference::default())),
             #[cfg(feature = "db")]
             state_write_tx: state_write_tx_opt,
         });
@@ -329,8 +582,191 @@ impl AppState {
             controller,
             repos,
             safety_monitor: safety_monitor_arc,
+            ocpp,
         })
     }
+
+    /// Build the aggregate battery for a `[[batteries]]` site
+    async fn create_battery_bank(
+        cfg: &Config,
+        factory: &crate::hardware::factory::DeviceFactory,
+    ) -> Result<AggregateBattery> {
+        let mut packs = Vec::with_capacity(cfg.batteries.len());
+        for pack in &cfg.batteries {
+            let caps = BatteryCapabilities {
+                capacity_kwh: pack.battery.capacity_kwh,
+                max_charge_kw: pack.battery.max_charge_kw,
+                max_discharge_kw: pack.battery.max_discharge_kw,
+                efficiency: pack.battery.efficiency,
+                degradation_per_cycle: pack.battery.degradation_per_cycle,
+                chemistry: pack.battery.chemistry,
+            };
+            validate_battery_capabilities(&caps)
+                .with_context(|| format!("Invalid battery pack '{}'", pack.id))?;
+
+            packs.push(BatteryPack {
+                id: pack.id.clone(),
+                battery: factory.create_battery_pack(caps, pack).await,
+                priority: pack.priority,
+                limits: PackLimits {
+                    min_soc_percent: pack.battery.min_soc_percent,
+                    max_soc_percent: pack.battery.max_soc_percent,
+                    min_temp_c: pack.min_temp_c,
+                    max_temp_c: pack.max_temp_c,
+                },
+            });
+        }
+
+        let strategy = match cfg.battery_dispatch {
+            crate::config::BatteryDispatch::Headroom => DispatchStrategy::Headroom,
+            crate::config::BatteryDispatch::SocBalancing => DispatchStrategy::SocBalancing,
+            crate::config::BatteryDispatch::Priority => DispatchStrategy::Priority,
+        };
+        info!(
+            "Dispatching {} battery packs with {:?} strategy",
+            packs.len(),
+            strategy
+        );
+        AggregateBattery::new(packs, strategy)
+    }
+
+    /// Thermal models of the house and tank from `[heat_pump]`
+    fn create_thermal_planner(cfg: &Config, heat_pump: &dyn FlexibleThermalLoad) -> ThermalPlanner {
+        use crate::simulation::hvac::DhwTankState;
+
+        let hp = &cfg.heat_pump;
+        let house = LumpedThermalModel {
+            heat_loss_w_per_k: hp.house_heat_loss_w_per_k,
+            thermal_mass_j_per_k: hp.house_thermal_mass_kwh_per_k * 3.6e6,
+            internal_gains_w: 0.0,
+        };
+        let tank = (heat_pump.capabilities().has_dhw_tank && hp.dhw_tank_liters > 0.0).then(|| {
+            let tank = DhwTankState {
+                capacity_liters: hp.dhw_tank_liters,
+                ..Default::default()
+            };
+            LumpedThermalModel::dhw_tank(&tank, hp.dhw_draw_liters_per_day)
+        });
+        ThermalPlanner::new(
+            ThermalPlanSettings {
+                comfort_min_c: hp.comfort_min_c,
+                comfort_max_c: hp.comfort_max_c,
+                setpoint_c: hp.setpoint_c,
+                dhw_min_c: hp.dhw_min_c,
+                dhw_max_c: hp.dhw_max_c,
+                rated_power_kw: hp.rated_power_kw,
+                cop: hp.cop,
+                dhw_cop: hp.dhw_cop,
+            },
+            house,
+            tank,
+        )
+    }
+
+    /// Build the charger group for an `[[ev_chargers]]` site
+    async fn create_ev_charger_group(
+        cfg: &Config,
+        factory: &crate::hardware::factory::DeviceFactory,
+        central_system: Option<&CentralSystem>,
+        policy: EvAllocationPolicy,
+    ) -> Result<EvChargerGroup> {
+        let timezone = cfg.household.timezone.parse().unwrap_or_else(|_| {
+            warn!(
+                "Unknown household timezone '{}', reading departures as UTC",
+                cfg.household.timezone
+            );
+            chrono_tz::UTC
+        });
+
+        let mut chargers = Vec::with_capacity(cfg.ev_chargers.len());
+        for charger in &cfg.ev_chargers {
+            chargers.push(ChargePoint::new(
+                charger.id.clone(),
+                factory.create_site_ev_charger(charger, central_system).await,
+                charger.priority,
+                charger.phase,
+                charger.max_current_a,
+                VehiclePlan {
+                    capacity_kwh: charger.vehicle_capacity_kwh,
+                    target_soc_percent: charger.target_soc_percent,
+                    departure_time: None,
+                    daily_departure: charger.departure,
+                },
+            ));
+        }
+
+        info!(
+            "Sharing the fuse between {} EV chargers with {:?} allocation",
+            chargers.len(),
+            policy
+        );
+        EvChargerGroup::new(chargers, policy, timezone)
+    }
+
+    /// Days of battery cycling stored before this start, oldest first
+    #[cfg(feature = "db")]
+    async fn load_battery_cycles(
+        repos: &Repositories,
+        household_id: Uuid,
+        model: &dyn DegradationModel,
+    ) -> Vec<crate::domain::DailyCycles> {
+        // The whole history: every stored day counts towards the fade
+        match repos
+            .db
+            .battery_cycles()
+            .find_since(household_id, chrono::NaiveDate::default())
+            .await
+        {
+            Ok(rows) => rows
+                .into_iter()
+                .map(|row| {
+                    crate::domain::DailyCycles::replayed(
+                        row.cycle_date,
+                        row.equivalent_full_cycles,
+                        row.avg_depth_of_discharge,
+                        row.max_temperature_c,
+                        row.total_energy_throughput_kwh,
+                        model,
+                    )
+                })
+                .collect(),
+            Err(e) => {
+                warn!(error = %e, "Failed to load battery cycle history");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Build the group of `[[controllable_loads]]`; unreachable loads are left out
+    async fn create_controllable_load_group(
+        cfg: &Config,
+        factory: &crate::hardware::factory::DeviceFactory,
+    ) -> Result<ControllableLoadGroup> {
+        let timezone = cfg.household.timezone.parse().unwrap_or(chrono_tz::UTC);
+
+        let mut loads = Vec::with_capacity(cfg.controllable_loads.len());
+        for load in &cfg.controllable_loads {
+            let Some(device) = factory.create_controllable_load(load).await else {
+                continue;
+            };
+            loads.push(ManagedLoad::new(
+                load.id.clone(),
+                device,
+                LoadConstraints {
+                    runtime_minutes_per_day: load.runtime_minutes_per_day,
+                    earliest_start: load.earliest_start,
+                    latest_finish: load.latest_finish,
+                    min_on_minutes: load.min_on_minutes,
+                    min_off_minutes: load.min_off_minutes,
+                    power_kw: load.power_kw,
+                    phase: load.phase,
+                },
+            ));
+        }
+
+        info!("Scheduling {} controllable loads", loads.len());
+        ControllableLoadGroup::new(loads, timezone)
+    }
 }
 
 pub fn spawn_controller_tasks(state: AppState, cfg: Config) {
@@ -355,6 +791,53 @@ pub fn spawn_controller_tasks(state: AppState, cfg: Config) {
         }
     });
 
+    if cfg.reserve.enabled {
+        let controller3 = Arc::clone(&state_arc.controller);
+        let interval_ms = cfg.reserve.loop_interval_ms;
+        tokio::spawn(async move {
+            if let Err(e) = controller3.reserve_loop(interval_ms).await {
+                warn!(error=%e, "frequency reserve loop stopped");
+            }
+        });
+        info!(
+            hours = ?cfg.reserve.hours,
+            fcr_d_up_kw = cfg.reserve.fcr_d_up_kw,
+            fcr_d_down_kw = cfg.reserve.fcr_d_down_kw,
+            ffr_kw = cfg.reserve.ffr_kw,
+            "Frequency reserve loop started"
+        );
+    }
+
+    if cfg.fast_loop.enabled {
+        let controller5 = Arc::clone(&state_arc.controller);
+        let interval_ms = cfg.fast_loop.interval_ms;
+        tokio::spawn(async move {
+            if let Err(e) = controller5.fast_loop(interval_ms).await {
+                warn!(error=%e, "fast control loop stopped");
+            }
+        });
+        info!(
+            interval_ms = cfg.fast_loop.interval_ms,
+            target_grid_w = cfg.fast_loop.target_grid_w,
+            "Fast control loop started"
+        );
+    }
+
+    if cfg.export_limit.enabled {
+        let controller4 = Arc::clone(&state_arc.controller);
+        let interval_ms = cfg.export_limit.loop_interval_ms;
+        tokio::spawn(async move {
+            if let Err(e) = controller4.export_limit_loop(interval_ms).await {
+                warn!(error=%e, "export limit loop stopped");
+            }
+        });
+        info!(
+            limit_w = cfg.export_limit.limit_w,
+            margin_w = cfg.export_limit.margin_w,
+            "Export limit loop started"
+        );
+    }
+
     // Start periodic task scheduler (includes ML training)
     if cfg.forecast.use_ml_models {
         let scheduler = Arc::new(scheduler::TaskScheduler::new(state_arc.clone()));
@@ -389,8 +872,16 @@ pub fn spawn_controller_tasks(state: AppState, cfg: Config) {
                         error!(error=%e, "Failed to stop battery during emergency");
                     }
 
-                    if let Err(e) = controller_for_emergency.ev_charger.set_current(0.0).await {
-                        error!(error=%e, "Failed to stop EV charger during emergency");
+                    match controller_for_emergency.ev_charger_group {
+                        Some(ref group) => group.stop_all().await,
+                        None => {
+                            if let Err(e) = controller_for_emergency.ev_charger.set_current(0.0).await {
+                                error!(error=%e, "Failed to stop EV charger during emergency");
+                            }
+                        }
+                    }
+                    if let Some(ref loads) = controller_for_emergency.controllable_loads {
+                        loads.stop_all(Utc::now()).await;
                     }
 
                     info!("Emergency stop completed - all power flows halted");
@@ -421,24 +912,48 @@ pub fn spawn_controller_tasks(state: AppState, cfg: Config) {
 
             // Get current battery state for safety checks
             if let Ok(battery_state) = controller_for_safety.get_current_state().await {
+                // Prefer the grid meter: per-phase values catch an overloaded phase
+                // that the total power hides
+                let meter_reading = match controller_for_safety.grid_meter {
+                    Some(ref meter) => match meter.read().await {
+                        Ok(reading) => Some(reading),
+                        Err(e) => {
+                            warn!(error=%e, "Grid meter read failed in safety loop");
+                            None
+                        }
+                    },
+                    None => None,
+                };
+
                 // Get real grid status from controller (use safe defaults if unavailable)
-                let grid_status =
-                    controller_for_safety
-                        .get_grid_status()
-                        .await
-                        .unwrap_or(GridConnection {
-                            status: GridStatus::Normal,
-                            import_power_w: 0.0,
-                            export_power_w: 0.0,
-                            frequency_hz: 50.0,
-                            voltage_v: 230.0,
-                            current_a: 0.0,
-                        });
+                let grid_status = match meter_reading {
+                    Some(ref reading) => Ok(reading.to_connection()),
+                    None => controller_for_safety.get_grid_status().await,
+                }
+                .unwrap_or(GridConnection {
+                    status: GridStatus::Normal,
+                    import_power_w: 0.0,
+                    export_power_w: 0.0,
+                    frequency_hz: None,
+                    voltage_v: 230.0,
+                    current_a: 0.0,
+                });
+                // HAN meters do not measure frequency; the inverter does
+                let grid_frequency_hz = match grid_status.frequency_hz {
+                    Some(frequency_hz) => Some(frequency_hz),
+                    None => controller_for_safety.inverter_frequency_hz().await,
+                };
+                let grid_voltage_v = meter_reading
+                    .as_ref()
+                    .map(|r| r.worst_voltage_v(230.0))
+                    .unwrap_or(grid_status.voltage_v);
 
                 let measurements = safety_monitor::SafetyMeasurements {
                     grid_import_kw: grid_status.import_power_w / 1000.0, // Convert W to kW
-                    grid_voltage_v: grid_status.voltage_v,
-                    grid_frequency_hz: grid_status.frequency_hz,
+                    grid_phase_currents_a: meter_reading.as_ref().map(|r| r.phase_currents_a()),
+                    grid_phase_power_w: meter_reading.as_ref().map(|r| r.phase_power()),
+                    grid_voltage_v,
+                    grid_frequency_hz,
                     battery_soc_percent: battery_state.soc_percent,
                     battery_temperature_c: battery_state.temperature_c,
                     grid_nominal_voltage_v: 230.0,
@@ -461,8 +976,47 @@ pub fn spawn_controller_tasks(state: AppState, cfg: Config) {
     maintenance::spawn_maintenance_tasks(Arc::clone(&state_arc));
 }
 
+/// Reject battery ratings that would break the optimizer (division by zero, NaN)
+fn validate_battery_capabilities(caps: &BatteryCapabilities) -> Result<()> {
+    if !caps.capacity_kwh.is_finite() || caps.capacity_kwh <= 0.0 {
+        bail!(
+            "Battery capacity_kwh must be positive and finite, got: {}",
+            caps.capacity_kwh
+        );
+    }
+    if !caps.max_charge_kw.is_finite() || caps.max_charge_kw <= 0.0 {
+        bail!(
+            "Battery max_charge_kw must be positive and finite, got: {}",
+            caps.max_charge_kw
+        );
+    }
+    if !caps.max_discharge_kw.is_finite() || caps.max_discharge_kw <= 0.0 {
+        bail!(
+            "Battery max_discharge_kw must be positive and finite, got: {}",
+            caps.max_discharge_kw
+        );
+    }
+    if !caps.efficiency.is_finite() || caps.efficiency <= 0.0 || caps.efficiency > 1.0 {
+        bail!(
+            "Battery efficiency must be between 0 and 1, got: {}",
+            caps.efficiency
+        );
+    }
+    if !caps.degradation_per_cycle.is_finite() || caps.degradation_per_cycle < 0.0 {
+        bail!(
+            "Battery degradation_per_cycle must be non-negative and finite, got: {}",
+            caps.degradation_per_cycle
+        );
+    }
+    Ok(())
+}
+
+/// Optimization runs kept in memory
+const MAX_OPTIMIZATION_RUNS: usize = 48;
+
 pub struct BatteryController {
     pub battery: Arc<dyn Battery>,
+    pub inverter: Arc<dyn crate::domain::Inverter>,
     pub optimizer: Arc<BatteryOptimizer>,
     pub forecast_engine: Arc<ForecastEngine>,
     pub schedule: Arc<RwLock<Option<Schedule>>>,
@@ -481,12 +1035,55 @@ pub struct BatteryController {
     // V2X controller for vehicle-to-grid/home coordination
     v2x: Option<Arc<v2x_controller::V2XController>>,
     // EV charger for direct actuation
-    ev_charger: Arc<dyn crate::domain::EvCharger>,
+    pub ev_charger: Arc<dyn crate::domain::EvCharger>,
     // Safety monitor for watchdog heartbeat updates
     safety_monitor: Option<Arc<safety_monitor::SafetyMonitor>>,
     // CRITICAL FIX: Simulation environment for realistic PV/load data
     // This replaces the static fallback values with dynamic simulation
     environment: Option<Arc<RwLock<Environment>>>,
+    // Utility meter at the connection point, source of measured grid flows
+    pub grid_meter: Option<Arc<dyn crate::domain::GridMeter>>,
+    // Individual packs when `battery` is an aggregate of several
+    pub battery_bank: Option<Arc<AggregateBattery>>,
+    // Chargers sharing the fuse on a multi-charger site
+    pub ev_charger_group: Option<Arc<EvChargerGroup>>,
+    // Hourly grid imports of the month, so peak tariff costs carry across runs
+    peak_ledger: Arc<RwLock<PeakLedger>>,
+    // Per-tick receding-horizon re-solve when the `mpc` strategy is selected
+    mpc: Option<Arc<MpcPlanner>>,
+    // Forecast behind the current schedule, for the MPC re-solve
+    last_forecast: Arc<RwLock<Option<Forecast24h>>>,
+    // Aging curve of the battery's chemistry, for the optimizers' wear costs
+    degradation: Arc<dyn DegradationModel>,
+    // Daily cycle count and health estimate from measured throughput
+    cycle_tracker: Arc<RwLock<CycleTracker>>,
+    // Frequency response for committed reserve hours, when enabled
+    reserve: Option<Arc<RwLock<reserve::FrequencyReserve>>>,
+    // Last battery setpoint of the control tick, before the reserve offset
+    battery_setpoint_w: Arc<RwLock<f64>>,
+    // Recent optimization runs with their cost breakdowns, newest last
+    optimization_runs: Arc<RwLock<VecDeque<OptimizationRunRecord>>>,
+    // Heat pump steered as a flexible load, when enabled
+    pub thermal_load: Option<Arc<dyn FlexibleThermalLoad>>,
+    thermal_planner: Option<Arc<ThermalPlanner>>,
+    // Operating state per price period, replanned with the schedule
+    thermal_plan: Arc<RwLock<Option<ThermalPlan>>>,
+    // Operating state last sent to the heat pump
+    thermal_mode: Arc<RwLock<Option<ThermalLoadMode>>>,
+    // Pool pumps, water heaters and other loads on a switch, with their plans
+    pub controllable_loads: Option<Arc<ControllableLoadGroup>>,
+    // Grid outage detection and backup operation, when enabled
+    backup: Option<Arc<RwLock<backup::BackupSupervisor>>>,
+    // Closed-loop export limitation, when enabled
+    export_limiter: Option<Arc<RwLock<export_limit::ExportLimiter>>>,
+    // EV currents of the last control tick, before the export limit's boosts
+    ev_grants: Arc<RwLock<Vec<EvAllocation>>>,
+    // Deadline and stall monitoring of the fast, medium and slow loops
+    loops: Arc<loops::LoopMonitors>,
+    // Grid tracking of the fast loop, when enabled
+    fast_tracker: Option<Arc<RwLock<loops::GridTracker>>>,
+    // Setpoint and planned grid flow the tick hands the fast loop
+    fast_reference: Arc<RwLock<loops::FastLoopReference>>,
     // CRITICAL FIX: Bounded channel for state recording to prevent resource leak
     // Limits pending database writes to prevent OOM during long simulations
     #[cfg(feature = "db")]
@@ -501,6 +1098,7 @@ impl BatteryController {
             tokio::time::interval(std::time::Duration::from_secs(tick_seconds.max(1)));
         loop {
             interval.tick().await;
+            let _iteration = self.loops.medium.start();
 
             // CRITICAL FIX: Capture timestamp BEFORE sensor polling to ensure
             // accurate time-based calculations. Modbus polling can take 2-3s.
@@ -534,7 +1132,7 @@ impl BatteryController {
 
             // CRITICAL FIX: Wrap sensor read in error handler to prevent loop termination
             // If sensor read fails, log error, wait, and continue (don't crash)
-            let state = match self.battery.read_state().await {
+            let mut state = match self.battery.read_state().await {
                 Ok(s) => {
                     // Update last successful sensor read timestamp
                     *self.last_sensor_read.write().await = now_utc;
@@ -580,6 +1178,9 @@ impl BatteryController {
                 }
             };
 
+            self.record_battery_cycles(now_utc, &mut state, tick_seconds)
+                .await;
+
             // CRITICAL FIX: Offload telemetry recording to background task
             // to prevent DB write latency from blocking the critical control path.
             // If record_state takes >1000ms (e.g., SD card stall), queueing up
@@ -592,7 +1193,8 @@ impl BatteryController {
 
             // Get scheduled target power from optimizer
             let schedule_snapshot = self.schedule.read().await.clone();
-            let schedule_target_w = schedule_snapshot.as_ref().and_then(|s| s.power_at(now_utc));
+            let mut schedule_target_w =
+                schedule_snapshot.as_ref().and_then(|s| s.power_at(now_utc));
 
             // CRITICAL FIX: Evaluate V2X discharge decision and feed target into PowerFlowModel
             // EV commands are issued only after PowerFlowModel computes a safe snapshot
@@ -639,15 +1241,55 @@ impl BatteryController {
                         warn!(error=%e, "V2X decision evaluation failed");
                     }
                 }
+
+                // Without a decision of its own, V2G discharge planned by the
+                // schedule for the V2X charger is carried out
+                if ev_target_w.is_none() {
+                    if let (Some(group), Some(schedule)) =
+                        (&self.ev_charger_group, &schedule_snapshot)
+                    {
+                        ev_target_w = schedule
+                            .ev_power_at(&group.chargers()[0].id, now_utc)
+                            .filter(|w| *w < 0.0);
+                    }
+                }
             }
 
             // Build PowerFlowInputs from current state
-            // TODO: Integrate real sensors when available:
-            // - PV production: Read from inverter or PV sensor via Modbus/OCPP
-            // - House load: Calculate from grid meter (grid_import + battery_power - pv_production)
-            // For now, use sensor fallback values as conservative estimates
+            // PV comes from the inverter; house load is derived from the grid meter
             let pv_production_kw = self.get_pv_production_kw().await;
-            let house_load_kw = self.get_house_load_kw().await;
+            let (house_load_kw, house_load_phases) =
+                self.get_house_load(pv_production_kw, state.power_w).await;
+
+            // MPC: re-solve the next periods from this tick's measurements,
+            // following the schedule when the solve misses its budget
+            if let (Some(mpc), Some(schedule)) = (&self.mpc, &schedule_snapshot) {
+                let mut constraints = self.constraints.read().await.clone();
+                constraints.peak_power_incurred_kw = self
+                    .peak_ledger
+                    .read()
+                    .await
+                    .incurred_peak_kw(&constraints.peak_tariff, now_utc);
+                let measurement = MpcMeasurement {
+                    timestamp: now_utc,
+                    soc_percent: state.soc_percent,
+                    pv_kw: pv_production_kw,
+                    house_load_kw,
+                };
+                let forecast = self.last_forecast.read().await.clone();
+                if let Some(solution) = mpc
+                    .solve_within_budget(measurement, schedule.clone(), forecast, constraints)
+                    .await
+                {
+                    debug!(
+                        schedule_target_w = schedule_target_w.unwrap_or(0.0),
+                        mpc_target_w = solution.target_power_w,
+                        expected_cost_sek = solution.expected_cost_sek,
+                        "MPC re-solve"
+                    );
+                    schedule_target_w = Some(solution.target_power_w);
+                }
+            }
 
             // Get grid price from current schedule or use fallback
             let grid_price_sek_kwh = schedule_snapshot
@@ -669,6 +1311,10 @@ impl BatteryController {
                 now_utc, // Use timestamp captured before sensor polling
             );
 
+            if let Some(phases) = house_load_phases {
+                inputs = inputs.with_house_load_phases(phases);
+            }
+
             // Pass schedule target to PowerFlowModel if available
             if let Some(target_w) = schedule_target_w {
                 inputs = inputs.with_target_power_w(target_w);
@@ -679,6 +1325,24 @@ impl BatteryController {
                 inputs = inputs.with_ev_target_power_w(ev_w);
             }
 
+            if let Some(ref group) = self.ev_charger_group {
+                for mut charger in group.inputs(now_utc).await {
+                    charger.scheduled_power_kw = schedule_snapshot
+                        .as_ref()
+                        .and_then(|s| s.ev_power_at(&charger.id, now_utc))
+                        .map(|w| w / 1000.0);
+                    inputs = inputs.with_ev_charger(charger);
+                }
+            }
+
+            if let Some(ref loads) = self.controllable_loads {
+                for load in loads.inputs(now_utc).await {
+                    inputs = inputs.with_controllable_load(load);
+                }
+            }
+
+            let islanded = self.update_backup_mode(now_utc, state.soc_percent).await;
+
             // Validate inputs before passing to PowerFlowModel
             if let Err(e) = inputs.validate() {
                 warn!(error=%e, "Invalid PowerFlowInputs, using fallback");
@@ -694,17 +1358,25 @@ impl BatteryController {
             let model = PowerFlowModel::new((*self.power_flow_constraints).clone());
 
             // Compute power flows with safety checks
-            let (target_power_w, ev_current_a, ev_discharge_w) = match model.compute_flows(&inputs)
-            {
+            let mut modeled_import_kw = None;
+            let mut modeled_grid_kw = None;
+            let (target_power_w, ev_current_a, ev_discharge_w, ev_allocations, load_switches) =
+                match model.compute_flows(&inputs) {
                 Ok(snapshot) => {
+                    modeled_import_kw = Some(snapshot.grid_kw.max(0.0));
+                    modeled_grid_kw = Some(snapshot.grid_kw);
+
                     // Use the battery power from PowerFlowModel
                     // Convert kW to W
                     let battery_target_w = snapshot.battery_kw * 1000.0;
 
                     // CRITICAL FIX: Extract EV charging current from PowerFlowModel
-                    // Convert kW to current: I = P / V (assuming 230V single-phase)
+                    // Convert kW to current: I = P / (V * phases), the model
+                    // spreads a single charger over all site phases
+                    let physical = &self.power_flow_constraints.physical;
                     let ev_current_a = if snapshot.ev_kw > 0.0 {
-                        snapshot.ev_kw * 1000.0 / 230.0 // kW -> A
+                        snapshot.ev_kw * 1000.0
+                            / (physical.grid_voltage_v * physical.phases.max(1) as f64)
                     } else {
                         0.0
                     };
@@ -729,29 +1401,45 @@ impl BatteryController {
                         "PowerFlowModel decision"
                     );
 
-                    (battery_target_w, ev_current_a, ev_discharge_w)
+                    (
+                        battery_target_w,
+                        ev_current_a,
+                        ev_discharge_w,
+                        snapshot.ev_allocations,
+                        Some(snapshot.load_switches),
+                    )
                 }
                 Err(e) => {
                     // If PowerFlowModel fails due to constraint violations,
-                    // the safest fallback is to idle the battery and stop EV charging
+                    // the safest fallback is to idle the battery, stop EV
+                    // charging and switch off the controllable loads
                     warn!(error=%e, "PowerFlowModel failed, entering safe fallback mode (Idle)");
-                    (0.0, 0.0, 0.0)
+                    (0.0, 0.0, 0.0, Vec::new(), None)
                 }
             };
 
+            self.record_grid_import(now_utc, modeled_import_kw, tick_seconds)
+                .await;
+
             // CRITICAL FIX: Check safety monitor BEFORE sending any power commands
             // This prevents race condition where main loop and safety monitor fight over setpoints
             let mut commanded_power_w = target_power_w;
             let mut commanded_ev_current_a = ev_current_a;
             let mut commanded_ev_discharge_w = ev_discharge_w;
+            let mut commanded_ev_allocations = ev_allocations;
+            let mut commanded_load_switches: Option<Vec<LoadSwitch>> = load_switches;
 
+            let mut emergency_stop_active = false;
             if let Some(ref safety_monitor) = self.safety_monitor {
                 let safety_state = safety_monitor.state().await;
                 if safety_state.emergency_stop_active {
                     warn!("Safety monitor emergency stop active - overriding commands to 0");
+                    emergency_stop_active = true;
                     commanded_power_w = 0.0;
                     commanded_ev_current_a = 0.0;
                     commanded_ev_discharge_w = 0.0;
+                    commanded_ev_allocations.clear();
+                    commanded_load_switches = None;
                 }
             }
 
@@ -761,6 +1449,47 @@ impl BatteryController {
             let max_discharge_w = caps.max_discharge_kw * 1000.0;
             commanded_power_w = commanded_power_w.clamp(-max_discharge_w, max_charge_w);
 
+            // Islanded, the battery carries the house and the critical loads;
+            // nothing is exported and the EVs wait for the grid
+            if let (Some(backup), true, false) = (&self.backup, islanded, emergency_stop_active) {
+                let dispatch = backup.read().await.island_dispatch(
+                    &inputs,
+                    self.power_flow_constraints.safety.battery_min_soc_percent,
+                    max_charge_w,
+                    max_discharge_w,
+                );
+                commanded_power_w = dispatch.battery_w;
+                commanded_ev_current_a = 0.0;
+                commanded_ev_discharge_w = 0.0;
+                commanded_ev_allocations.clear();
+                commanded_load_switches = Some(dispatch.load_switches);
+            } else if commanded_power_w < 0.0 {
+                // On the grid, the outage reserve is not spent
+                let reserve_soc_percent = self.constraints.read().await.backup_reserve_soc_percent;
+                if state.soc_percent <= reserve_soc_percent {
+                    commanded_power_w = 0.0;
+                }
+            }
+
+            // The frequency response and the export limit's extra charging
+            // ride on top of the setpoint; their fast loops keep them current
+            // between ticks
+            *self.battery_setpoint_w.write().await = commanded_power_w;
+            *self.fast_reference.write().await = loops::FastLoopReference {
+                setpoint_w: commanded_power_w,
+                planned_grid_w: modeled_grid_kw.map(|kw| kw * 1000.0),
+            };
+            if let (Some(reserve), false) = (&self.reserve, emergency_stop_active || islanded) {
+                commanded_power_w = (commanded_power_w + reserve.read().await.last_response_w())
+                    .clamp(-max_discharge_w, max_charge_w);
+            }
+            if let (Some(limiter), false) =
+                (&self.export_limiter, emergency_stop_active || islanded)
+            {
+                commanded_power_w = (commanded_power_w + limiter.read().await.battery_offset_w())
+                    .clamp(-max_discharge_w, max_charge_w);
+            }
+
             if let Some(ref _v2x) = self.v2x {
                 if commanded_ev_discharge_w > 0.0 {
                     if let Err(e) = self.ev_charger.start_discharging().await {
@@ -793,11 +1522,44 @@ impl BatteryController {
                 }
             }
 
+            // The grants are the base the export limit loop adds its extra
+            // current to
+            *self.ev_grants.write().await = match self.ev_charger_group {
+                Some(_) => commanded_ev_allocations.clone(),
+                None => vec![EvAllocation {
+                    id: export_limit::SINGLE_CHARGER_ID.to_string(),
+                    current_a: commanded_ev_current_a,
+                    power_kw: commanded_ev_current_a
+                        * self.ev_watts_per_amp(export_limit::SINGLE_CHARGER_ID)
+                        / 1000.0,
+                }],
+            };
+            if let (Some(limiter), false) = (
+                &self.export_limiter,
+                emergency_stop_active || islanded || commanded_ev_discharge_w > 0.0,
+            ) {
+                let limiter = limiter.read().await;
+                if self.ev_charger_group.is_some() {
+                    if let Some(step) = limiter.last_step() {
+                        commanded_ev_allocations = export_limit::with_ev_boosts(
+                            &commanded_ev_allocations,
+                            &step.ev_boosts,
+                            |id| self.ev_watts_per_amp(id),
+                        );
+                    }
+                } else {
+                    commanded_ev_current_a += limiter.ev_boost_a(export_limit::SINGLE_CHARGER_ID);
+                }
+            }
+
+            // CRITICAL FIX: Actuate EV charger to prevent fuse overload
+            // The PowerFlowModel calculated the safe EV charging current.
+            // We MUST apply it, or the main fuse will blow during high house load.
             if commanded_ev_discharge_w <= 0.0 {
-                // CRITICAL FIX: Actuate EV charger to prevent fuse overload
-                // The PowerFlowModel calculated the safe EV charging current.
-                // We MUST apply it, or the main fuse will blow during high house load.
-                if let Err(e) = self.ev_charger.set_current(commanded_ev_current_a).await {
+                if let Some(ref group) = self.ev_charger_group {
+                    // Chargers left out of the allocation are paused
+                    group.apply(&commanded_ev_allocations).await;
+                } else if let Err(e) = self.ev_charger.set_current(commanded_ev_current_a).await {
                     error!(
                         error = %e,
                         commanded_current_a = commanded_ev_current_a,
@@ -811,7 +1573,26 @@ impl BatteryController {
                 }
             }
 
-            self.battery.set_power(commanded_power_w).await?;
+            if let Some(ref loads) = self.controllable_loads {
+                match commanded_load_switches {
+                    Some(ref switches) => loads.apply(switches, now_utc).await,
+                    None => loads.stop_all(now_utc).await,
+                }
+            }
+
+            self.apply_heating_mode(now_utc, emergency_stop_active, islanded)
+                .await;
+
+            // A live fast loop owns the battery and works around the
+            // published setpoint; emergencies and the island are the tick's
+            let fast_loop_in_control = self.fast_tracker.is_some()
+                && self.grid_meter.is_some()
+                && !emergency_stop_active
+                && !islanded
+                && self.loops.fast.as_ref().is_some_and(|m| !m.is_stalled());
+            if !fast_loop_in_control {
+                self.battery.set_power(commanded_power_w).await?;
+            }
             info!(
                 soc_percent = state.soc_percent,
                 power_w = state.power_w,
@@ -824,47 +1605,741 @@ impl BatteryController {
         }
     }
 
-    pub async fn reoptimize_loop(self: Arc<Self>, every_minutes: u64) -> Result<()> {
+    /// Fast loop tracking the grid meter between control ticks
+    ///
+    /// Each step takes the latest setpoint the tick published and, while the
+    /// plan calls for self-consumption, moves the battery to hold the grid
+    /// at its target. It only reads values the other loops publish, so it
+    /// keeps going when the tick or the optimizer stall; without a live
+    /// tick it falls back to self-consumption.
+    pub async fn fast_loop(self: Arc<Self>, interval_ms: u64) -> Result<()> {
+        let (Some(tracker), Some(monitor)) = (self.fast_tracker.clone(), self.loops.fast.as_ref())
+        else {
+            return Ok(());
+        };
+        let Some(meter) = self.grid_meter.clone() else {
+            warn!("Fast control loop needs a grid meter, loop not started");
+            return Ok(());
+        };
+        let deadband_w = self.config.fast_loop.deadband_w;
         let mut interval =
-            tokio::time::interval(std::time::Duration::from_secs(every_minutes.max(1) * 60));
+            tokio::time::interval(std::time::Duration::from_millis(interval_ms.max(50)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut applied_w: Option<f64> = None;
         loop {
             interval.tick().await;
-            if let Err(e) = self.reoptimize_schedule().await {
-                warn!(error=%e, "reoptimize failed");
+            let _iteration = monitor.start();
+
+            // Emergency stops and backup operation leave the battery to the
+            // tick; tracking restarts from its setpoint
+            let emergency_stop_active = match self.safety_monitor {
+                Some(ref safety_monitor) => safety_monitor.state().await.emergency_stop_active,
+                None => false,
+            };
+            let islanded = match self.backup {
+                Some(ref backup) => backup.read().await.is_islanded(),
+                None => false,
+            };
+            let reference = self.fast_reference.read().await.clone();
+            if emergency_stop_active || islanded {
+                tracker.write().await.reset(reference.setpoint_w);
+                applied_w = None;
+                continue;
+            }
+
+            let reading = match meter.read().await {
+                Ok(reading) => reading,
+                Err(e) => {
+                    warn!(error=%e, "Failed to read grid meter for the fast loop");
+                    continue;
+                }
+            };
+
+            // Reserve response and export limit charging stay on top
+            let response_w = match self.reserve {
+                Some(ref reserve) => reserve.read().await.last_response_w(),
+                None => 0.0,
+            };
+            let offset_w = response_w + self.export_limit_battery_w().await;
+            let (min_w, max_w) = self.fast_loop_battery_range().await;
+            let base_w = tracker.write().await.step(
+                &reference,
+                self.loops.medium.is_stalled(),
+                reading.power_w - offset_w,
+                min_w,
+                max_w,
+            );
+
+            let caps = self.battery.capabilities();
+            let command_w = (base_w + offset_w)
+                .clamp(-caps.max_discharge_kw * 1000.0, caps.max_charge_kw * 1000.0);
+            if applied_w.is_some_and(|w| (command_w - w).abs() < deadband_w) {
+                continue;
+            }
+            match self.battery.set_power(command_w).await {
+                Ok(()) => {
+                    applied_w = Some(command_w);
+                    // The fast loop is the one commanding the battery now
+                    if let Some(ref safety_monitor) = self.safety_monitor {
+                        safety_monitor.heartbeat().await;
+                    }
+                }
+                Err(e) => warn!(error=%e, command_w, "Fast loop failed to set battery power"),
             }
         }
     }
 
-    pub async fn reoptimize_schedule(&self) -> Result<()> {
-        let area: PriceArea = "SE3".parse().unwrap_or(PriceArea::SE3);
-        let forecast: Forecast24h = self
-            .forecast_engine
-            .get_forecast_24h(area, self.household_id)
-            .await?;
-        let battery_state = self.battery.read_state().await?;
-        let constraints = self.constraints.read().await.clone();
-        let state = SystemState {
-            battery: battery_state,
+    /// Battery power the fast loop may use (W): no charging when full, no
+    /// discharging at the minimum SoC or the outage reserve
+    async fn fast_loop_battery_range(&self) -> (f64, f64) {
+        let caps = self.battery.capabilities();
+        let (mut min_w, mut max_w) = (-caps.max_discharge_kw * 1000.0, caps.max_charge_kw * 1000.0);
+        let soc_percent = match self.state_history.read().await.back() {
+            Some(sample) => sample.state.soc_percent,
+            None => return (min_w, max_w),
         };
-        let schedule = self
-            .optimizer
-            .optimize(&state, &forecast, &constraints)
-            .await?;
-        schedule.validate().map_err(|err| anyhow::anyhow!(err))?;
-        *self.schedule.write().await = Some(schedule);
-        Ok(())
+        let safety = &self.power_flow_constraints.safety;
+        let floor_percent = safety
+            .battery_min_soc_percent
+            .max(self.constraints.read().await.backup_reserve_soc_percent);
+        if soc_percent >= safety.battery_max_soc_percent {
+            max_w = 0.0;
+        }
+        if soc_percent <= floor_percent {
+            min_w = 0.0;
+        }
+        (min_w, max_w)
     }
 
-    pub async fn get_schedule(&self) -> Option<Schedule> {
-        self.schedule.read().await.clone()
-    }
-    pub async fn set_schedule(&self, schedule: Schedule) -> Result<()> {
-        schedule.validate().map_err(|err| anyhow::anyhow!(err))?;
-        *self.schedule.write().await = Some(schedule);
-        Ok(())
+    /// Battery setpoint the reserve response and the export limit charging
+    /// ride on: the fast loop's while it runs, else the control tick's
+    async fn battery_base_w(&self) -> f64 {
+        if let (Some(tracker), Some(monitor)) = (&self.fast_tracker, &self.loops.fast) {
+            if !monitor.is_stalled() {
+                return tracker.read().await.power_w();
+            }
+        }
+        *self.battery_setpoint_w.read().await
+    }
+
+    /// Timing of the fast, medium and slow loops and the fast loop's mode
+    pub async fn get_control_loop_status(&self) -> ControlLoopStatus {
+        let fast_loop = match self.fast_tracker {
+            Some(ref tracker) => {
+                let tracker = tracker.read().await;
+                Some(FastLoopStatus {
+                    mode: tracker.mode(),
+                    battery_setpoint_w: tracker.power_w(),
+                    target_grid_w: self.config.fast_loop.target_grid_w,
+                })
+            }
+            None => None,
+        };
+        ControlLoopStatus {
+            loops: self.loops.stats(),
+            fast_loop,
+        }
+    }
+
+    /// Fast loop delivering the committed frequency reserve
+    ///
+    /// Each step turns the measured frequency into a power offset and
+    /// re-commands the battery at the tick's setpoint plus that offset. Small
+    /// changes are left for the next step to avoid flooding the inverter.
+    /// Emergency stops and islanded backup operation pause the response.
+    pub async fn reserve_loop(self: Arc<Self>, interval_ms: u64) -> Result<()> {
+        const DEADBAND_W: f64 = 50.0;
+
+        let Some(reserve) = self.reserve.clone() else {
+            return Ok(());
+        };
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_millis(interval_ms.max(50)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut applied_w = 0.0;
+        loop {
+            interval.tick().await;
+
+            if let Some(ref safety_monitor) = self.safety_monitor {
+                if safety_monitor.state().await.emergency_stop_active {
+                    applied_w = 0.0;
+                    continue;
+                }
+            }
+            // An island runs at the inverter's own frequency, which it may
+            // shift to curtail PV; backup operation owns the battery then
+            let islanded = match self.backup {
+                Some(ref backup) => backup.read().await.is_islanded(),
+                None => false,
+            };
+            if islanded {
+                if reserve.read().await.last_response_w() != 0.0 {
+                    reserve.write().await.stand_down(Utc::now());
+                }
+                applied_w = 0.0;
+                continue;
+            }
+            let Some(frequency_hz) = self.grid_frequency_hz().await else {
+                continue;
+            };
+
+            let response_w = reserve.write().await.response(Utc::now(), frequency_hz);
+            let settled = response_w == applied_w
+                || (response_w != 0.0 && (response_w - applied_w).abs() < DEADBAND_W);
+            if settled {
+                continue;
+            }
+
+            let caps = self.battery.capabilities();
+            let command_w =
+                (self.battery_base_w().await + response_w + self.export_limit_battery_w().await)
+                    .clamp(-caps.max_discharge_kw * 1000.0, caps.max_charge_kw * 1000.0);
+            match self.battery.set_power(command_w).await {
+                Ok(()) => applied_w = response_w,
+                Err(e) => warn!(error=%e, frequency_hz, "Failed to apply frequency response"),
+            }
+        }
+    }
+
+    /// Measured grid frequency, from the grid meter if it measures one or else the inverter
+    ///
+    /// `None` while the grid is down, when there is nothing to respond to.
+    async fn grid_frequency_hz(&self) -> Option<f64> {
+        let meter_hz = match self.grid_meter {
+            Some(ref meter) => meter.read().await.ok().and_then(|r| r.frequency_hz),
+            None => None,
+        };
+        let inverter_hz = match meter_hz {
+            Some(_) => None,
+            None => self.inverter_frequency_hz().await,
+        };
+        reserve::measured_frequency_hz(meter_hz, inverter_hz)
+    }
+
+    /// Grid frequency measured by the inverter, `None` if it reports none
+    async fn inverter_frequency_hz(&self) -> Option<f64> {
+        let frequency_hz = self.inverter.read_state().await.ok()?.grid_frequency_hz;
+        (frequency_hz > 0.0).then_some(frequency_hz)
+    }
+
+    /// Committed reserve and logged activations, `None` when reserve mode
+    /// is off
+    pub async fn get_reserve_status(&self) -> Option<ReserveStatus> {
+        let reserve = self.reserve.as_ref()?.read().await;
+        Some(ReserveStatus {
+            commitment: reserve.offer().commitment_at(Utc::now()),
+            response_w: reserve.last_response_w(),
+            activations: reserve.activations(),
+        })
+    }
+
+    /// Fast loop holding the grid export under the connection's limit
+    ///
+    /// Each step reads the grid meter, lets the limiter split the power to
+    /// absorb between battery, EVs and inverter, and re-commands whatever
+    /// moved by more than its deadband. The battery and the EVs get the
+    /// control tick's commands plus the limiter's extra; the inverter's
+    /// output is capped only while something has to be curtailed.
+    pub async fn export_limit_loop(self: Arc<Self>, interval_ms: u64) -> Result<()> {
+        const BATTERY_DEADBAND_W: f64 = 50.0;
+        const EV_DEADBAND_A: f64 = 0.5;
+        const CURTAIL_DEADBAND_W: f64 = 50.0;
+
+        let Some(limiter) = self.export_limiter.clone() else {
+            return Ok(());
+        };
+        let Some(meter) = self.grid_meter.clone() else {
+            warn!("Export limitation needs a grid meter, loop not started");
+            return Ok(());
+        };
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_millis(interval_ms.max(50)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut last_step_at: Option<std::time::Instant> = None;
+        let mut applied = export_limit::ExportLimitStep {
+            timestamp: Utc::now(),
+            export_w: 0.0,
+            absorb_w: 0.0,
+            battery_w: 0.0,
+            ev_boosts: Vec::new(),
+            curtail_w: 0.0,
+        };
+        loop {
+            interval.tick().await;
+
+            // Emergency stops and backup operation own the setpoints; the
+            // next control tick re-commands the battery and the EVs
+            let emergency_stop_active = match self.safety_monitor {
+                Some(ref safety_monitor) => safety_monitor.state().await.emergency_stop_active,
+                None => false,
+            };
+            let islanded = match self.backup {
+                Some(ref backup) => backup.read().await.is_islanded(),
+                None => false,
+            };
+            if emergency_stop_active || islanded {
+                if limiter.read().await.last_step().is_some() {
+                    limiter.write().await.reset();
+                    if applied.curtail_w > 0.0 {
+                        self.release_curtailment().await;
+                    }
+                    applied.battery_w = 0.0;
+                    applied.ev_boosts.clear();
+                    applied.curtail_w = 0.0;
+                }
+                last_step_at = None;
+                continue;
+            }
+
+            let reading = match meter.read().await {
+                Ok(reading) => reading,
+                Err(e) => {
+                    warn!(error=%e, "Failed to read grid meter for export limitation");
+                    continue;
+                }
+            };
+            let now = std::time::Instant::now();
+            let dt = match last_step_at {
+                Some(at) => now.duration_since(at).as_secs_f64(),
+                None => interval_ms as f64 / 1000.0,
+            };
+            last_step_at = Some(now);
+
+            let chargers = self.export_limit_chargers();
+            let headroom = self.export_limit_headroom(&chargers).await;
+            let step = limiter
+                .write()
+                .await
+                .step(Utc::now(), -reading.power_w, &headroom, dt)
+                .clone();
+
+            if (step.battery_w - applied.battery_w).abs() >= BATTERY_DEADBAND_W
+                || (step.battery_w == 0.0 && applied.battery_w != 0.0)
+            {
+                let caps = self.battery.capabilities();
+                let response_w = match self.reserve {
+                    Some(ref reserve) => reserve.read().await.last_response_w(),
+                    None => 0.0,
+                };
+                let command_w = (self.battery_base_w().await + response_w + step.battery_w)
+                    .clamp(-caps.max_discharge_kw * 1000.0, caps.max_charge_kw * 1000.0);
+                match self.battery.set_power(command_w).await {
+                    Ok(()) => applied.battery_w = step.battery_w,
+                    Err(e) => warn!(error=%e, "Failed to apply export limit charging"),
+                }
+            }
+
+            let grants = self.ev_grants.read().await.clone();
+            for (id, charger, _) in &chargers {
+                let boost_of = |boosts: &[export_limit::EvBoost]| {
+                    boosts
+                        .iter()
+                        .find(|b| &b.id == id)
+                        .map(|b| b.current_a)
+                        .unwrap_or(0.0)
+                };
+                let boost_a = boost_of(&step.ev_boosts);
+                let applied_a = boost_of(&applied.ev_boosts);
+                if (boost_a - applied_a).abs() < EV_DEADBAND_A
+                    && !(boost_a == 0.0 && applied_a != 0.0)
+                {
+                    continue;
+                }
+                let base_a = grants
+                    .iter()
+                    .find(|g| &g.id == id)
+                    .map(|g| g.current_a)
+                    .unwrap_or(0.0);
+                match charger.set_current(base_a + boost_a).await {
+                    Ok(()) => {
+                        applied.ev_boosts.retain(|b| &b.id != id);
+                        if boost_a > 0.0 {
+                            applied.ev_boosts.push(export_limit::EvBoost {
+                                id: id.clone(),
+                                current_a: boost_a,
+                            });
+                        }
+                    }
+                    Err(e) => {
+                        warn!(error=%e, charger = %id, "Failed to apply export limit current")
+                    }
+                }
+            }
+
+            if (step.curtail_w - applied.curtail_w).abs() >= CURTAIL_DEADBAND_W
+                || (step.curtail_w == 0.0 && applied.curtail_w != 0.0)
+            {
+                if step.curtail_w > 0.0 {
+                    let cap_w = (headroom.curtailable_w - step.curtail_w).max(0.0);
+                    match self.inverter.set_export_limit(cap_w).await {
+                        Ok(()) => applied.curtail_w = step.curtail_w,
+                        Err(e) => warn!(error=%e, cap_w, "Failed to curtail inverter output"),
+                    }
+                } else {
+                    self.release_curtailment().await;
+                    applied.curtail_w = 0.0;
+                }
+            }
+        }
+    }
+
+    /// Lift the inverter's output cap set by the export limit loop
+    async fn release_curtailment(&self) {
+        let max_w = self.inverter.capabilities().max_ac_output_w;
+        if let Err(e) = self.inverter.set_export_limit(max_w).await {
+            warn!(error=%e, "Failed to release inverter curtailment");
+        }
+    }
+
+    /// Extra battery charging of the export limit loop (W)
+    async fn export_limit_battery_w(&self) -> f64 {
+        match self.export_limiter {
+            Some(ref limiter) => limiter.read().await.battery_offset_w(),
+            None => 0.0,
+        }
+    }
+
+    /// Chargers the export limit loop can raise, with the highest current
+    /// their circuits allow, in priority order
+    fn export_limit_chargers(&self) -> Vec<(String, Arc<dyn crate::domain::EvCharger>, f64)> {
+        match self.ev_charger_group {
+            Some(ref group) => {
+                let mut chargers: Vec<_> = group.chargers().iter().collect();
+                chargers.sort_by_key(|cp| cp.priority);
+                chargers
+                    .into_iter()
+                    .map(|cp| {
+                        let max_a = cp
+                            .max_current_a
+                            .min(cp.charger.capabilities().max_current_amps);
+                        (cp.id.clone(), Arc::clone(&cp.charger), max_a)
+                    })
+                    .collect()
+            }
+            None => vec![(
+                export_limit::SINGLE_CHARGER_ID.to_string(),
+                Arc::clone(&self.ev_charger),
+                self.ev_charger.capabilities().max_current_amps,
+            )],
+        }
+    }
+
+    /// Charging power per amp of a charger (W/A)
+    fn ev_watts_per_amp(&self, id: &str) -> f64 {
+        let charger = match self.ev_charger_group {
+            Some(ref group) => match group.charger(id) {
+                Some(cp) => &cp.charger,
+                None => return 0.0,
+            },
+            None => &self.ev_charger,
+        };
+        let caps = charger.capabilities();
+        caps.voltage_v * caps.phases as f64
+    }
+
+    /// What battery, EVs and inverter could absorb beyond the tick's commands
+    async fn export_limit_headroom(
+        &self,
+        chargers: &[(String, Arc<dyn crate::domain::EvCharger>, f64)],
+    ) -> export_limit::ExportLimitHeadroom {
+        let max_soc_percent = self.power_flow_constraints.safety.battery_max_soc_percent;
+        let battery_w = match self.battery.read_state().await {
+            Ok(state) if state.soc_percent < max_soc_percent => {
+                let response_w = match self.reserve {
+                    Some(ref reserve) => reserve.read().await.last_response_w(),
+                    None => 0.0,
+                };
+                let committed_w = self.battery_base_w().await + response_w;
+                (self.battery.capabilities().max_charge_kw * 1000.0 - committed_w).max(0.0)
+            }
+            Ok(_) => 0.0,
+            Err(e) => {
+                warn!(error=%e, "Failed to read battery for export limitation");
+                0.0
+            }
+        };
+
+        let grants = self.ev_grants.read().await;
+        let mut evs = Vec::new();
+        for (id, charger, max_current_a) in chargers {
+            match charger.read_state().await {
+                Ok(state) if state.connected && !state.discharging => {}
+                _ => continue,
+            }
+            let caps = charger.capabilities();
+            evs.push(export_limit::EvHeadroom {
+                id: id.clone(),
+                base_current_a: grants
+                    .iter()
+                    .find(|g| &g.id == id)
+                    .map(|g| g.current_a)
+                    .unwrap_or(0.0),
+                min_current_a: caps.min_current_amps,
+                max_current_a: *max_current_a,
+                watts_per_amp: caps.voltage_v * caps.phases as f64,
+            });
+        }
+
+        let curtailable_w = match self.inverter.read_state().await {
+            Ok(state) => state.pv_power_w * state.efficiency_percent / 100.0,
+            Err(e) => {
+                warn!(error=%e, "Failed to read inverter for export limitation");
+                0.0
+            }
+        };
+
+        export_limit::ExportLimitHeadroom {
+            battery_w,
+            evs,
+            curtailable_w,
+        }
+    }
+
+    /// Limit, target and the loop's last step, `None` when export
+    /// limitation is off
+    pub async fn get_export_limit_status(&self) -> Option<ExportLimitStatus> {
+        let limiter = self.export_limiter.as_ref()?.read().await;
+        Some(ExportLimitStatus {
+            limit_w: limiter.limit_w(),
+            target_export_w: limiter.target_export_w(),
+            last_step: limiter.last_step().cloned(),
+        })
+    }
+
+    pub async fn reoptimize_loop(self: Arc<Self>, every_minutes: u64) -> Result<()> {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(every_minutes.max(1) * 60));
+        loop {
+            interval.tick().await;
+            let _iteration = self.loops.slow.start();
+            if let Err(e) = self.reoptimize_schedule().await {
+                warn!(error=%e, "reoptimize failed");
+            }
+        }
+    }
+
+    pub async fn reoptimize_schedule(&self) -> Result<()> {
+        let area: PriceArea = "SE3".parse().unwrap_or(PriceArea::SE3);
+        let forecast: Forecast24h = self
+            .forecast_engine
+            .get_forecast_24h(area, self.household_id)
+            .await?;
+        let now = Utc::now();
+        let battery_state = self.battery.read_state().await?;
+        self.update_storm_reserve(now).await;
+        let mut constraints = self.constraints.read().await.clone();
+        constraints.peak_power_incurred_kw = self
+            .peak_ledger
+            .read()
+            .await
+            .incurred_peak_kw(&constraints.peak_tariff, now);
+        // Wear is priced at the pack's current temperature
+        if constraints.battery_wear.is_some() {
+            constraints.battery_wear = Some(self.degradation.linearized(
+                constraints.battery_capacity_kwh,
+                constraints
+                    .battery_max_charge_kw
+                    .max(constraints.battery_max_discharge_kw),
+                constraints.battery_replacement_cost_sek,
+                battery_state.temperature_c,
+            ));
+        }
+        // Vehicles on the charger group are scheduled with the battery; only
+        // the first charger, which the V2X controller drives, can discharge
+        let evs = match self.ev_charger_group {
+            Some(ref group) => {
+                let voltage_v = self.power_flow_constraints.physical.grid_voltage_v;
+                let v2x_charger = self.v2x.as_ref().map(|_| group.chargers()[0].id.as_str());
+                group
+                    .inputs(now)
+                    .await
+                    .iter()
+                    .filter(|charger| charger.ev.connected)
+                    .map(|charger| {
+                        let v2x = v2x_charger == Some(charger.id.as_str());
+                        EvChargeRequest::from_input(charger, voltage_v, v2x)
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let soc_percent = battery_state.soc_percent;
+        let state = SystemState {
+            battery: battery_state,
+            evs,
+        };
+        let started = std::time::Instant::now();
+        let result = self
+            .optimizer
+            .optimize(&state, &forecast, &constraints)
+            .await
+            .and_then(|schedule| {
+                schedule.validate().map_err(|err| anyhow::anyhow!(err))?;
+                Ok(schedule)
+            });
+        let run = OptimizationRunRecord {
+            id: Uuid::new_v4(),
+            created_at: now,
+            duration_ms: started.elapsed().as_millis() as i64,
+            objective: format!("{:?}", self.config.optimization.strategy),
+            optimizer_version: result.as_ref().ok().map(|s| s.optimizer_version.clone()),
+            success: result.is_ok(),
+            error_message: result.as_ref().err().map(|e| e.to_string()),
+            explanation: result
+                .as_ref()
+                .ok()
+                .map(|schedule| explain_schedule(schedule, &forecast, &constraints, soc_percent)),
+        };
+        self.record_optimization_run(run, &constraints).await;
+        let schedule = result?;
+        if let Some(ref group) = self.ev_charger_group {
+            group.push_plans(&schedule, now).await;
+        }
+        *self.schedule.write().await = Some(schedule);
+        if let Err(e) = self.plan_heating(&forecast).await {
+            warn!(error=%e, "Failed to plan heat pump pre-heating, keeping the previous plan");
+        }
+        self.plan_controllable_loads(&forecast, now).await;
+        *self.last_forecast.write().await = Some(forecast);
+        Ok(())
+    }
+
+    /// Plan the heat pump's operating states over the forecast horizon
+    async fn plan_heating(&self, forecast: &Forecast24h) -> Result<()> {
+        let (Some(heat_pump), Some(planner)) = (&self.thermal_load, &self.thermal_planner) else {
+            return Ok(());
+        };
+        let state = heat_pump.read_state().await?;
+        // SG-Ready units report nothing; plan from the setpoint then
+        let plan = planner.plan(
+            forecast,
+            state.indoor_temp_c.unwrap_or(planner.settings().setpoint_c),
+            state.dhw_temp_c,
+            state
+                .outdoor_temp_c
+                .unwrap_or(self.config.heat_pump.fallback_outdoor_temp_c),
+        );
+        info!(
+            cost_sek = plan.cost_sek,
+            savings_sek = plan.savings_sek(),
+            boost_periods = plan.entries.iter().filter(|e| e.mode == ThermalLoadMode::Boost).count(),
+            blocked_periods = plan.entries.iter().filter(|e| e.mode == ThermalLoadMode::Blocked).count(),
+            "Heat pump plan updated"
+        );
+        *self.thermal_plan.write().await = Some(plan);
+        Ok(())
+    }
+
+    /// Send the planned operating state to the heat pump
+    ///
+    /// Outside the plan, during an emergency stop, or once the room has
+    /// fallen below the comfort band, the heat pump is handed back its own
+    /// schedule. Only changes are sent.
+    ///
+    /// Islanded, planned pre-heating is dropped and the heat pump is blocked
+    /// until the house falls below the comfort band.
+    async fn apply_heating_mode(
+        &self,
+        now: DateTime<Utc>,
+        emergency_stop_active: bool,
+        islanded: bool,
+    ) {
+        let (Some(heat_pump), Some(planner)) = (&self.thermal_load, &self.thermal_planner) else {
+            return;
+        };
+        let planned = self
+            .thermal_plan
+            .read()
+            .await
+            .as_ref()
+            .and_then(|plan| plan.mode_at(now));
+        let too_cold = match heat_pump.read_state().await {
+            Ok(state) => state
+                .indoor_temp_c
+                .is_some_and(|t| t < planner.settings().comfort_min_c),
+            Err(e) => {
+                warn!(error=%e, "Failed to read heat pump");
+                false
+            }
+        };
+        let planned = if islanded {
+            Some(ThermalLoadMode::Blocked)
+        } else {
+            planned
+        };
+        let mode = match planned {
+            Some(ThermalLoadMode::Blocked) if too_cold => ThermalLoadMode::Normal,
+            Some(mode) if !emergency_stop_active => mode,
+            _ => ThermalLoadMode::Normal,
+        };
+
+        let mut applied = self.thermal_mode.write().await;
+        if *applied == Some(mode) {
+            return;
+        }
+        match heat_pump.set_mode(mode).await {
+            Ok(()) => *applied = Some(mode),
+            Err(e) => warn!(error=%e, ?mode, "Failed to set heat pump operating state"),
+        }
+    }
+
+    /// Place the runs of the controllable loads in the forecast's cheapest periods
+    async fn plan_controllable_loads(&self, forecast: &Forecast24h, now: DateTime<Utc>) {
+        let Some(ref group) = self.controllable_loads else {
+            return;
+        };
+        let mut starts = Vec::with_capacity(group.loads().len());
+        for load in group.loads() {
+            let (on, minutes_in_state) = load.current_run(now).await;
+            starts.push(LoadStart {
+                on,
+                minutes_in_state,
+                runtime_done_minutes: load.runtime_done_minutes(now).await,
+            });
+        }
+        let loads: Vec<(&LoadConstraints, LoadStart)> = group
+            .loads()
+            .iter()
+            .map(|l| &l.constraints)
+            .zip(starts)
+            .collect();
+
+        let scheduler =
+            LoadScheduler::new(self.power_flow_constraints.physical.max_grid_import_kw);
+        let schedules = scheduler.plan_group(&loads, forecast, now, group.timezone());
+        for (load, schedule) in group.loads().iter().zip(schedules) {
+            info!(
+                load = %load.id,
+                runs = schedule.runs.len(),
+                cost_sek = schedule.cost_sek,
+                shortfall_minutes = schedule.shortfall_minutes,
+                "Controllable load schedule updated"
+            );
+            load.set_schedule(schedule).await;
+        }
+    }
+
+    /// Latest heat pump reading and plan, `None` without a heat pump
+    pub async fn get_heat_pump_status(&self) -> Option<Result<HeatPumpStatus>> {
+        let state = self.thermal_load.as_ref()?.read_state().await;
+        let plan = self.thermal_plan.read().await.clone();
+        Some(state.map(|state| HeatPumpStatus { state, plan }))
+    }
+
+    pub async fn get_schedule(&self) -> Option<Schedule> {
+        self.schedule.read().await.clone()
+    }
+    pub async fn set_schedule(&self, schedule: Schedule) -> Result<()> {
+        schedule.validate().map_err(|err| anyhow::anyhow!(err))?;
+        *self.schedule.write().await = Some(schedule);
+        Ok(())
     }
     pub async fn get_current_state(&self) -> Result<BatteryState> {
-        self.battery.read_state().await
+        let mut state = self.battery.read_state().await?;
+        if let Some(health) = self.cycle_tracker.read().await.health_percent() {
+            state.health_percent = state.health_percent.min(health);
+        }
+        Ok(state)
     }
     pub async fn get_battery_capabilities(&self) -> BatteryCapabilities {
         self.battery.capabilities()
@@ -872,6 +2347,17 @@ impl BatteryController {
     pub async fn get_battery_health(&self) -> Result<HealthStatus> {
         self.battery.health_check().await
     }
+    /// Individual battery packs by id; a single battery is reported as pack "main"
+    pub fn battery_packs(&self) -> Vec<(String, Arc<dyn Battery>)> {
+        match self.battery_bank {
+            Some(ref bank) => bank
+                .packs()
+                .iter()
+                .map(|p| (p.id.clone(), Arc::clone(&p.battery)))
+                .collect(),
+            None => vec![("main".to_string(), Arc::clone(&self.battery))],
+        }
+    }
     pub async fn set_battery_power(&self, power_w: f64) -> Result<()> {
         if !power_w.is_finite() {
             bail!("power must be finite");
@@ -970,6 +2456,29 @@ impl BatteryController {
     }
     /// Return the latest grid connection status.
     pub async fn get_grid_status(&self) -> Result<GridConnection> {
+        if let Some(ref meter) = self.grid_meter {
+            match meter.read().await {
+                Ok(reading) => {
+                    let mut connection = reading.to_connection();
+                    if connection.frequency_hz.is_none() {
+                        connection.frequency_hz = self.inverter_frequency_hz().await;
+                    }
+                    // In backup operation the house runs on the inverter's island
+                    if let Some(ref backup) = self.backup {
+                        match backup.read().await.mode() {
+                            backup::BackupMode::Outage => connection.status = GridStatus::Islanded,
+                            backup::BackupMode::Reconnecting => {
+                                connection.status = GridStatus::Reconnecting
+                            }
+                            backup::BackupMode::GridConnected => {}
+                        }
+                    }
+                    return Ok(connection);
+                }
+                Err(e) => warn!("Failed to read grid meter: {}", e),
+            }
+        }
+
         let limits = self.get_grid_limits().await?;
         let voltage_v = (limits.voltage_min_v + limits.voltage_max_v) / 2.0;
 
@@ -977,11 +2486,97 @@ impl BatteryController {
             status: GridStatus::Normal,
             import_power_w: 0.0,
             export_power_w: 0.0,
-            frequency_hz: 50.0,
+            frequency_hz: self.inverter_frequency_hz().await,
             voltage_v,
             current_a: 0.0,
         })
     }
+    /// Backup mode, outage reserve and outage log, `None` when backup
+    /// operation is not enabled
+    pub async fn get_backup_status(&self) -> Option<backup::BackupStatus> {
+        Some(self.backup.as_ref()?.read().await.status(Utc::now()))
+    }
+
+    /// Follow the grid connection; returns whether backup operation is in force
+    ///
+    /// The grid meter's voltage decides. Without a meter reading, a hybrid
+    /// inverter reporting off-grid or backup operation counts as an outage.
+    async fn update_backup_mode(&self, now: DateTime<Utc>, soc_percent: f64) -> bool {
+        let Some(ref backup) = self.backup else {
+            return false;
+        };
+        let voltage_v = match self.grid_meter {
+            Some(ref meter) => match meter.read().await {
+                Ok(reading) => Some(reading.average_voltage_v()),
+                Err(e) => {
+                    warn!("Failed to read grid meter: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let grid_present = match voltage_v {
+            Some(voltage_v) => Some(backup.read().await.grid_present(voltage_v)),
+            None => match self.inverter.read_state().await {
+                Ok(inverter) => Some(!matches!(
+                    inverter.mode,
+                    crate::domain::InverterMode::OffGrid | crate::domain::InverterMode::Backup
+                )),
+                Err(e) => {
+                    warn!(error=%e, "Failed to read inverter, grid status unknown");
+                    None
+                }
+            },
+        };
+
+        let mut backup = backup.write().await;
+        backup.observe(now, grid_present, soc_percent);
+        backup.is_islanded()
+    }
+
+    /// Raise the outage reserve while the weather forecast shows storm winds
+    async fn update_storm_reserve(&self, now: DateTime<Utc>) {
+        let Some(ref backup) = self.backup else {
+            return;
+        };
+        let settings = &self.config.backup;
+        if settings.storm_reserve_soc_percent.is_none() {
+            return;
+        }
+        let location = GeoLocation {
+            latitude: self.config.household.latitude,
+            longitude: self.config.household.longitude,
+            name: None,
+        };
+        let storm = match self.get_weather_forecast(location).await {
+            Ok(forecast) => backup::StormWarning::from_forecast(
+                &forecast,
+                now,
+                settings.storm_wind_speed_ms,
+                settings.storm_lookahead_hours,
+            ),
+            Err(e) => {
+                warn!(error=%e, "Failed to fetch weather forecast, keeping the storm reserve");
+                return;
+            }
+        };
+
+        let mut backup = backup.write().await;
+        if backup.set_storm(storm.clone(), now) {
+            let reserve_soc_percent = backup.reserve_soc_percent(now);
+            match storm {
+                Some(storm) => info!(
+                    reserve_soc_percent,
+                    max_wind_speed_ms = storm.max_wind_speed_ms,
+                    first_storm_at = %storm.first_storm_at,
+                    "Storm forecast, outage reserve raised"
+                ),
+                None => info!(reserve_soc_percent, "Storm passed, outage reserve lowered"),
+            }
+        }
+        self.constraints.write().await.backup_reserve_soc_percent = backup.reserve_soc_percent(now);
+    }
+
     /// Return grid limits derived from controller constraints.
     pub async fn get_grid_limits(&self) -> Result<GridLimits> {
         let constraints = self.constraints.read().await.clone();
@@ -1026,19 +2621,17 @@ impl BatteryController {
     /// Get current PV production (kW)
     ///
     /// CRITICAL FIX: Now queries the simulation environment for realistic data
-    /// Falls back to config value only if simulation is not available
+    /// Otherwise reads the inverter, falling back to the config value if that fails
     async fn get_pv_production_kw(&self) -> f64 {
         // If simulation environment is available, query it
         if let Some(ref env) = self.environment {
             return env.read().await.solar_production_kw();
         }
 
-        // Future implementation: Read from inverter via Modbus
-        // if let Some(inverter) = &self.inverter {
-        //     if let Ok(state) = inverter.read_state().await {
-        //         return state.pv_power_w / 1000.0;
-        //     }
-        // }
+        match self.inverter.read_state().await {
+            Ok(state) => return state.pv_power_w / 1000.0,
+            Err(e) => warn!("Failed to read PV power from inverter: {}", e),
+        }
 
         // Fallback to config (only for real hardware mode)
         self.config
@@ -1047,26 +2640,260 @@ impl BatteryController {
             .default_pv_production_kw
     }
 
-    /// Get current house load (kW)
+    /// Get current house load (kW) and, when the meter reports phases, its
+    /// split over the phases (W)
     ///
     /// CRITICAL FIX: Now queries the simulation environment for realistic data
-    /// Falls back to config value only if simulation is not available
-    async fn get_house_load_kw(&self) -> f64 {
+    /// Otherwise derives it from the grid meter energy balance, falling back to
+    /// the config value if no meter is available
+    async fn get_house_load(
+        &self,
+        pv_production_kw: f64,
+        battery_power_w: f64,
+    ) -> (f64, Option<ThreePhasePower>) {
         // If simulation environment is available, query it
         if let Some(ref env) = self.environment {
-            return env.read().await.house_load_kw();
+            return (env.read().await.house_load_kw(), None);
         }
 
-        // Future implementation: Calculate from energy balance
-        // house_load = grid_import + battery_discharge + pv_production - grid_export
-        // if let Ok(grid) = self.get_grid_status().await {
-        //     let battery_state = self.battery.read_state().await.ok()?;
-        //     let pv_kw = self.get_pv_production_kw().await;
-        //     return (grid.import_power_w / 1000.0) + (battery_state.power_w / 1000.0) + pv_kw;
-        // }
+        if let Some(ref meter) = self.grid_meter {
+            match meter.read().await {
+                Ok(reading) => {
+                    // Everything not accounted for by PV, battery or EV is house load:
+                    // house = grid_net + pv - battery_charge - ev_charge
+                    let physical = &self.power_flow_constraints.physical;
+                    let ev_phases = match self.ev_charger_group {
+                        Some(ref group) => group.phase_power_w().await,
+                        None => match self.ev_charger.read_state().await {
+                            Ok(ev) => {
+                                let distribution = if self.ev_charger.capabilities().phases >= 3 {
+                                    LoadDistribution::Balanced
+                                } else {
+                                    LoadDistribution::SinglePhase(1)
+                                };
+                                distribution.distribute(ev.power_w)
+                            }
+                            Err(e) => {
+                                warn!("Failed to read EV charger power: {}", e);
+                                ThreePhasePower::default()
+                            }
+                        },
+                    };
+                    let load_w = reading.power_w + pv_production_kw * 1000.0
+                        - battery_power_w
+                        - ev_phases.total();
+
+                    // Meters without per-phase power registers report zeros there,
+                    // so only trust phases that add up to the total (within 100 W)
+                    let grid_phases = reading.phase_power();
+                    let house_phases = ((grid_phases.total() - reading.power_w).abs() < 100.0)
+                        .then(|| {
+                            let pv = physical
+                                .distribution(physical.pv_phase)
+                                .distribute(pv_production_kw * 1000.0);
+                            let battery = physical
+                                .distribution(physical.battery_phase)
+                                .distribute(battery_power_w);
+                            let [l1, l2, l3] = grid_phases
+                                .add(&pv)
+                                .sub(&battery)
+                                .sub(&ev_phases)
+                                .as_array()
+                                .map(|w| w.max(0.0));
+                            ThreePhasePower::new(l1, l2, l3)
+                        });
+                    return ((load_w / 1000.0).max(0.0), house_phases);
+                }
+                Err(e) => warn!("Failed to read grid meter: {}", e),
+            }
+        }
 
         // Fallback to config (only for real hardware mode)
-        self.config.hardware.sensor_fallback.default_house_load_kw
+        (self.config.hardware.sensor_fallback.default_house_load_kw, None)
+    }
+
+    /// Add this tick's grid import to the peak ledger, saving it when an hour closes
+    ///
+    /// Uses the grid meter when there is one, otherwise the power flow model's
+    /// grid import for the tick.
+    async fn record_grid_import(
+        &self,
+        now: DateTime<Utc>,
+        modeled_import_kw: Option<f64>,
+        tick_seconds: u64,
+    ) {
+        let measured_import_kw = match self.grid_meter {
+            Some(ref meter) => match meter.read().await {
+                Ok(reading) => Some(reading.import_power_w() / 1000.0),
+                Err(e) => {
+                    warn!("Failed to read grid meter: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let Some(import_kw) = measured_import_kw.or(modeled_import_kw) else {
+            return;
+        };
+
+        let rules = self.constraints.read().await.peak_tariff.clone();
+        let dt = chrono::Duration::seconds(tick_seconds.max(1) as i64);
+        let ledger = {
+            let mut ledger = self.peak_ledger.write().await;
+            match ledger.record(&rules, now, import_kw, dt) {
+                Some(hour) => {
+                    debug!(
+                        hour_start = %hour.hour_start,
+                        import_kw = hour.import_kw,
+                        "Hourly grid import recorded"
+                    );
+                    ledger.clone()
+                }
+                None => return,
+            }
+        };
+
+        if let Err(e) = ledger.save(&self.config.peak_tariff.ledger_path).await {
+            warn!(error = %e, "Failed to save peak ledger");
+        }
+    }
+
+    /// Count this tick's battery use towards the day's cycles, storing each
+    /// finished day
+    ///
+    /// The estimated health replaces the reported one when lower, as many
+    /// BMSes report full health for years.
+    async fn record_battery_cycles(
+        &self,
+        now: DateTime<Utc>,
+        state: &mut BatteryState,
+        tick_seconds: u64,
+    ) {
+        let dt_h = tick_seconds.max(1) as f64 / 3600.0;
+        let (day, health_percent) = {
+            let mut tracker = self.cycle_tracker.write().await;
+            let day = tracker.record(now, dt_h, state);
+            (day, tracker.health_percent())
+        };
+        if let Some(health) = health_percent {
+            state.health_percent = state.health_percent.min(health);
+        }
+        let Some(day) = day else {
+            return;
+        };
+
+        info!(
+            date = %day.date,
+            equivalent_full_cycles = day.equivalent_full_cycles,
+            fade_percent = day.fade_percent,
+            health_percent = state.health_percent,
+            "Battery cycles recorded"
+        );
+
+        #[cfg(feature = "db")]
+        {
+            use crate::repo::battery_cycles::BatteryCycleRow;
+
+            let row = BatteryCycleRow {
+                device_id: Some(self.household_id),
+                cycle_date: day.date,
+                equivalent_full_cycles: day.equivalent_full_cycles,
+                avg_depth_of_discharge: day.avg_depth_of_discharge,
+                max_temperature_c: day.max_temperature_c,
+                total_energy_throughput_kwh: day.total_energy_throughput_kwh,
+            };
+            let repos = Arc::clone(&self.repos);
+            tokio::spawn(async move {
+                if let Err(e) = repos.db.battery_cycles().upsert(&row).await {
+                    warn!(error = %e, "Failed to persist battery cycles");
+                }
+            });
+        }
+    }
+
+    /// Keep a run in the recent history and persist it to `optimization_runs`
+    async fn record_optimization_run(&self, run: OptimizationRunRecord, constraints: &Constraints) {
+        if let Some(ref explanation) = run.explanation {
+            info!(
+                run_id = %run.id,
+                cost_sek = explanation.total.total_sek,
+                savings_vs_no_battery_sek = explanation.savings_sek(Baseline::NoBattery),
+                savings_vs_self_consumption_sek = explanation.savings_sek(Baseline::SelfConsumption),
+                "Optimization run"
+            );
+        }
+
+        #[cfg(feature = "db")]
+        {
+            let row = OptimizationRunRow {
+                id: run.id,
+                household_id: Some(self.household_id),
+                created_at: run.created_at,
+                duration_ms: run.duration_ms,
+                objective: run.objective.clone(),
+                optimizer_version: run.optimizer_version.clone().unwrap_or_default(),
+                constraints_json: serde_json::to_value(constraints).unwrap_or_default(),
+                result_json: serde_json::to_value(&run.explanation).unwrap_or_default(),
+                cost_estimate_sek: run.explanation.as_ref().map(|e| e.total.total_sek),
+                success: run.success,
+                error_message: run.error_message.clone(),
+            };
+            let repos = Arc::clone(&self.repos);
+            tokio::spawn(async move {
+                if let Err(e) = repos.db.optimization_runs().insert(&row).await {
+                    warn!(error = %e, "Failed to persist optimization run");
+                }
+            });
+        }
+        #[cfg(not(feature = "db"))]
+        let _ = constraints;
+
+        let mut runs = self.optimization_runs.write().await;
+        while runs.len() >= MAX_OPTIMIZATION_RUNS {
+            runs.pop_front();
+        }
+        runs.push_back(run);
+    }
+
+    /// The latest `limit` optimization runs, newest first
+    #[cfg(feature = "db")]
+    pub async fn get_optimization_history(&self, limit: usize) -> Result<Vec<OptimizationRunRecord>> {
+        let rows = self
+            .repos
+            .db
+            .optimization_runs()
+            .find_recent(limit as i64)
+            .await?;
+        Ok(rows.into_iter().map(OptimizationRunRecord::from).collect())
+    }
+
+    /// The latest `limit` optimization runs since startup, newest first
+    #[cfg(not(feature = "db"))]
+    pub async fn get_optimization_history(&self, limit: usize) -> Result<Vec<OptimizationRunRecord>> {
+        let runs = self.optimization_runs.read().await;
+        Ok(runs.iter().rev().take(limit).cloned().collect())
+    }
+
+    /// Cost breakdown of run `run_id`, or of the latest successful run
+    pub async fn get_explanation(&self, run_id: Option<Uuid>) -> Result<Option<ScheduleExplanation>> {
+        let recent = self
+            .optimization_runs
+            .read()
+            .await
+            .iter()
+            .rev()
+            .filter(|run| run_id.is_none_or(|id| run.id == id))
+            .find_map(|run| run.explanation.clone());
+        if recent.is_some() {
+            return Ok(recent);
+        }
+
+        #[cfg(feature = "db")]
+        if let Some(id) = run_id {
+            let row = self.repos.db.optimization_runs().find_by_id(id).await?;
+            return Ok(row.and_then(|row| OptimizationRunRecord::from(row).explanation));
+        }
+        Ok(None)
     }
 
     async fn record_state(&self, timestamp: DateTime<Utc>, state: BatteryState) {
@@ -1138,6 +2965,78 @@ pub struct BatteryStateSample {
     pub state: BatteryState,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct HeatPumpStatus {
+    pub state: ThermalLoadState,
+    pub plan: Option<ThermalPlan>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FastLoopStatus {
+    pub mode: loops::TrackingMode,
+    /// Setpoint of the fast loop, before reserve and export limit
+    /// offsets (W, positive = charge)
+    pub battery_setpoint_w: f64,
+    /// Grid flow held during self-consumption (W, positive = import)
+    pub target_grid_w: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ControlLoopStatus {
+    pub loops: Vec<loops::LoopStats>,
+    /// `None` when the fast loop is off
+    pub fast_loop: Option<FastLoopStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportLimitStatus {
+    /// Export the connection is approved for (W)
+    pub limit_w: f64,
+    /// Export the loop holds, the limit less the margin (W)
+    pub target_export_w: f64,
+    pub last_step: Option<export_limit::ExportLimitStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReserveStatus {
+    /// Capacity committed in the current hour
+    pub commitment: Option<ReserveCommitment>,
+    /// Current power offset (W, positive = charge)
+    pub response_w: f64,
+    pub activations: Vec<reserve::ReserveActivation>,
+}
+
+/// An optimization run and the cost breakdown of its schedule
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizationRunRecord {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    /// Configured strategy
+    pub objective: String,
+    pub optimizer_version: Option<String>,
+    pub success: bool,
+    pub error_message: Option<String>,
+    /// `None` when the run failed
+    pub explanation: Option<ScheduleExplanation>,
+}
+
+#[cfg(feature = "db")]
+impl From<OptimizationRunRow> for OptimizationRunRecord {
+    fn from(row: OptimizationRunRow) -> Self {
+        Self {
+            id: row.id,
+            created_at: row.created_at,
+            duration_ms: row.duration_ms,
+            objective: row.objective,
+            optimizer_version: Some(row.optimizer_version).filter(|v| !v.is_empty()),
+            success: row.success,
+            error_message: row.error_message,
+            explanation: serde_json::from_value(row.result_json).ok(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct BatteryStatistics {
     pub average_soc_percent: f64,
@@ -1257,9 +3156,11 @@ mod tests {
         };
         let ev_charger =
             Arc::new(crate::domain::SimulatedEvCharger::new(initial_state, caps)) as Arc<dyn crate::domain::EvCharger>;
+        let loop_monitors = Arc::new(loops::LoopMonitors::new(&config));
 
         BatteryController {
             battery,
+            inverter: Arc::new(crate::domain::SimulatedInverter::default_inverter()),
             optimizer: Arc::new(BatteryOptimizer {
                 strategy: Box::new(DynamicProgrammingOptimizer),
             }),
@@ -1277,6 +3178,32 @@ mod tests {
             ev_charger,
             safety_monitor: None, // No safety monitor in tests by default
             environment: None,    // No environment in tests by default
+            grid_meter: None,
+            battery_bank: None,
+            ev_charger_group: None,
+            peak_ledger: Arc::new(RwLock::new(PeakLedger::new())),
+            mpc: None,
+            last_forecast: Arc::new(RwLock::new(None)),
+            degradation: degradation_model(BatteryChemistry::LiFePO4),
+            cycle_tracker: Arc::new(RwLock::new(CycleTracker::new(
+                degradation_model(BatteryChemistry::LiFePO4),
+                10.0,
+                chrono_tz::UTC,
+            ))),
+            reserve: None,
+            battery_setpoint_w: Arc::new(RwLock::new(0.0)),
+            optimization_runs: Arc::new(RwLock::new(VecDeque::new())),
+            thermal_load: None,
+            thermal_planner: None,
+            thermal_plan: Arc::new(RwLock::new(None)),
+            thermal_mode: Arc::new(RwLock::new(None)),
+            controllable_loads: None,
+            backup: None,
+            export_limiter: None,
+            ev_grants: Arc::new(RwLock::new(Vec::new())),
+            loops: loop_monitors,
+            fast_tracker: None,
+            fast_reference: Arc::new(RwLock::new(loops::FastLoopReference::default())),
             #[cfg(feature = "db")]
             state_write_tx: None, // No DB writes in tests by default
         }
@@ -179,6 +179,7 @@ impl PidController {
 
 /// Power-specific PID controller for battery management
 /// Pre-configured with reasonable defaults for power control
+#[derive(Debug)]
 pub struct PowerPidController {
     pid: PidController,
 }
@@ -221,6 +222,21 @@ impl PowerPidController {
         Self { pid }
     }
 
+    /// Create with custom gains and an asymmetric output range
+    ///
+    /// The integral is bounded so that the I term alone can just span the
+    /// output range, which is what a loop holding a steady offset needs.
+    pub fn with_output_range(kp: f64, ki: f64, kd: f64, min_w: f64, max_w: f64) -> Self {
+        let (integral_min, integral_max) = if ki > 0.0 {
+            (min_w / ki, max_w / ki)
+        } else {
+            (0.0, 0.0)
+        };
+        let pid = PidController::with_limits(kp, ki, kd, integral_min, integral_max, min_w, max_w);
+
+        Self { pid }
+    }
+
     /// Set target power
     pub fn set_target(&mut self, target_power_w: f64) {
         self.pid.set_setpoint(target_power_w);
@@ -98,6 +98,7 @@ impl Default for PowerRampState {
 }
 
 /// Power ramper - manages smooth power transitions
+#[derive(Debug)]
 pub struct PowerRamp {
     config: PowerRampConfig,
     state: PowerRampState,
@@ -232,6 +233,24 @@ impl PowerRamp {
         self.state.current_power_w
     }
 
+    /// Set a new target and advance towards it in one go
+    ///
+    /// Unlike `set_target` followed by `update`, the time since the last
+    /// update counts towards the new target, which suits a control loop
+    /// that retargets on every iteration.
+    pub fn retarget(&mut self, target_power_w: f64) -> f64 {
+        let power_delta = (target_power_w - self.state.current_power_w).abs();
+        self.state.target_power_w = target_power_w;
+
+        if self.config.emergency_mode || power_delta < self.config.min_ramp_threshold_w {
+            self.force_instant();
+            return self.state.current_power_w;
+        }
+
+        self.state.is_ramping = true;
+        self.update()
+    }
+
     /// Force instant change to target (override ramping)
     pub fn force_instant(&mut self) {
         self.state.current_power_w = self.state.target_power_w;
@@ -447,6 +466,25 @@ mod tests {
         assert!((eta - 1.0).abs() < 0.1); // 1000W / 1000W/s = 1s
     }
 
+    #[test]
+    fn test_retarget_counts_time_since_last_update() {
+        let config = PowerRampConfig {
+            max_ramp_rate_w_per_s: 1000.0,
+            min_ramp_threshold_w: 50.0,
+            emergency_mode: false,
+        };
+        let mut ramp = PowerRamp::with_initial_power(config, 0.0);
+
+        sleep(StdDuration::from_millis(200));
+        let power = ramp.retarget(3000.0);
+        assert!(power > 150.0 && power < 500.0);
+
+        // Small steps are taken at once
+        let power = ramp.retarget(power + 20.0);
+        assert!(!ramp.is_ramping());
+        assert_eq!(ramp.current_power_w(), power);
+    }
+
     #[test]
     fn test_multiple_target_changes() {
         let config = PowerRampConfig {
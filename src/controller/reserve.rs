@@ -0,0 +1,456 @@
+//! # Frequency Reserve Response
+//!
+//! Turns the measured grid frequency into a battery power offset during
+//! committed reserve hours (see `domain::reserve`). The controller runs it
+//! on a fast loop of its own and adds the offset to the scheduled setpoint.
+//!
+//! Every activation is logged with its frequency samples and delivered
+//! energy, which the aggregator needs for prequalification and for
+//! verifying delivery after the fact.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use tracing::info;
+
+use crate::domain::{
+    fcr_d_down_share, fcr_d_up_share, ReserveCommitment, ReserveOffer, FCR_D_UP_START_HZ,
+};
+
+/// Activations kept in the log
+const MAX_ACTIVATIONS: usize = 50;
+/// Samples kept per activation, 20 minutes at the default 200 ms loop
+const MAX_SAMPLES_PER_ACTIVATION: usize = 6000;
+/// FFR is released at no more than 20% of its capacity per second
+const FFR_RELEASE_PER_SECOND: f64 = 0.2;
+
+/// Direction of an activation
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReserveDirection {
+    /// Under-frequency: FCR-D up and FFR discharge the battery
+    Up,
+    /// Over-frequency: FCR-D down charges the battery
+    Down,
+}
+
+/// One step of the response loop
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize)]
+pub struct ReserveSample {
+    pub timestamp: DateTime<Utc>,
+    pub frequency_hz: f64,
+    /// FCR-D power (W, positive = charge)
+    pub fcr_d_w: f64,
+    /// FFR power (W, positive = discharge)
+    pub ffr_w: f64,
+    /// Total battery power offset (W, positive = charge)
+    pub response_w: f64,
+}
+
+/// A continuous period of reserve response
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize)]
+pub struct ReserveActivation {
+    pub direction: ReserveDirection,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the activation is ongoing
+    pub ended_at: Option<DateTime<Utc>>,
+    pub min_frequency_hz: f64,
+    pub max_frequency_hz: f64,
+    /// Largest power offset (W, absolute)
+    pub peak_response_w: f64,
+    /// Energy delivered as FCR-D (kWh, absolute)
+    pub fcr_d_energy_kwh: f64,
+    /// Energy delivered as FFR (kWh)
+    pub ffr_energy_kwh: f64,
+    pub samples: Vec<ReserveSample>,
+}
+
+impl ReserveActivation {
+    fn new(direction: ReserveDirection, sample: &ReserveSample) -> Self {
+        Self {
+            direction,
+            started_at: sample.timestamp,
+            ended_at: None,
+            min_frequency_hz: sample.frequency_hz,
+            max_frequency_hz: sample.frequency_hz,
+            peak_response_w: 0.0,
+            fcr_d_energy_kwh: 0.0,
+            ffr_energy_kwh: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, sample: ReserveSample) {
+        // Energy is held at the previous sample's power until this one
+        if let Some(last) = self.samples.last() {
+            let hours = (sample.timestamp - last.timestamp)
+                .num_milliseconds()
+                .max(0) as f64
+                / 3_600_000.0;
+            self.fcr_d_energy_kwh += last.fcr_d_w.abs() / 1000.0 * hours;
+            self.ffr_energy_kwh += last.ffr_w / 1000.0 * hours;
+        }
+        self.min_frequency_hz = self.min_frequency_hz.min(sample.frequency_hz);
+        self.max_frequency_hz = self.max_frequency_hz.max(sample.frequency_hz);
+        self.peak_response_w = self.peak_response_w.max(sample.response_w.abs());
+        if self.samples.len() < MAX_SAMPLES_PER_ACTIVATION {
+            self.samples.push(sample);
+        } else if let Some(last) = self.samples.last_mut() {
+            // Past the cap only the latest sample is kept, for the energy sum
+            *last = sample;
+        }
+    }
+}
+
+/// FFR activation state
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FfrState {
+    Armed,
+    /// Full power since the trigger
+    Supporting {
+        since: DateTime<Utc>,
+    },
+    /// Ramping down after the support duration
+    Releasing {
+        since: DateTime<Utc>,
+    },
+    /// Released; re-armed once the frequency has recovered
+    Spent,
+}
+
+/// Frequency response for the committed reserve capacity
+pub struct FrequencyReserve {
+    offer: ReserveOffer,
+    ffr_trigger_hz: f64,
+    ffr: FfrState,
+    last_response_w: f64,
+    active: Option<ReserveActivation>,
+    log: VecDeque<ReserveActivation>,
+}
+
+impl FrequencyReserve {
+    pub fn new(offer: ReserveOffer, ffr_trigger_hz: f64) -> Self {
+        Self {
+            offer,
+            ffr_trigger_hz,
+            ffr: FfrState::Armed,
+            last_response_w: 0.0,
+            active: None,
+            log: VecDeque::with_capacity(MAX_ACTIVATIONS),
+        }
+    }
+
+    pub fn offer(&self) -> &ReserveOffer {
+        &self.offer
+    }
+
+    /// Offset from the latest `response` (W, positive = charge)
+    pub fn last_response_w(&self) -> f64 {
+        self.last_response_w
+    }
+
+    /// Logged activations, oldest first, including an ongoing one
+    pub fn activations(&self) -> Vec<ReserveActivation> {
+        self.log.iter().chain(self.active.iter()).cloned().collect()
+    }
+
+    /// Battery power offset for `frequency_hz` measured at `now`
+    /// (W, positive = charge)
+    ///
+    /// Zero outside the committed hours.
+    pub fn response(&mut self, now: DateTime<Utc>, frequency_hz: f64) -> f64 {
+        let Some(commitment) = self.offer.commitment_at(now) else {
+            self.stand_down(now);
+            return 0.0;
+        };
+
+        let fcr_d_w = (fcr_d_down_share(frequency_hz) * commitment.fcr_d_down_kw
+            - fcr_d_up_share(frequency_hz) * commitment.fcr_d_up_kw)
+            * 1000.0;
+        let ffr_w = self.ffr_power_w(now, frequency_hz, &commitment);
+        let response_w = fcr_d_w - ffr_w;
+
+        let direction = if response_w < 0.0 {
+            Some(ReserveDirection::Up)
+        } else if response_w > 0.0 {
+            Some(ReserveDirection::Down)
+        } else {
+            None
+        };
+        if self.active.as_ref().map(|a| a.direction) != direction {
+            self.finish_activation(now);
+        }
+
+        let sample = ReserveSample {
+            timestamp: now,
+            frequency_hz,
+            fcr_d_w,
+            ffr_w,
+            response_w,
+        };
+        if let Some(direction) = direction {
+            self.active
+                .get_or_insert_with(|| {
+                    info!(
+                        frequency_hz,
+                        direction = ?direction,
+                        "Frequency reserve activated"
+                    );
+                    ReserveActivation::new(direction, &sample)
+                })
+                .record(sample);
+        }
+
+        self.last_response_w = response_w;
+        response_w
+    }
+
+    /// End any response at `now` and re-arm FFR
+    ///
+    /// Used outside the committed hours and while the site is islanded, when
+    /// the frequency is the inverter's own and not the grid's.
+    pub fn stand_down(&mut self, now: DateTime<Utc>) {
+        self.ffr = FfrState::Armed;
+        self.finish_activation(now);
+        self.last_response_w = 0.0;
+    }
+
+    /// FFR discharge power (W): full power for the support duration after
+    /// the trigger, then a ramp down at the permitted release rate
+    fn ffr_power_w(
+        &mut self,
+        now: DateTime<Utc>,
+        frequency_hz: f64,
+        commitment: &ReserveCommitment,
+    ) -> f64 {
+        let capacity_w = commitment.ffr_kw * 1000.0;
+        if capacity_w <= 0.0 {
+            return 0.0;
+        }
+        let seconds_since =
+            |since: DateTime<Utc>| (now - since).num_milliseconds().max(0) as f64 / 1000.0;
+
+        if self.ffr == FfrState::Armed && frequency_hz <= self.ffr_trigger_hz {
+            self.ffr = FfrState::Supporting { since: now };
+        }
+        if let FfrState::Supporting { since } = self.ffr {
+            if seconds_since(since) >= commitment.ffr_support_seconds {
+                let support_ms = (commitment.ffr_support_seconds * 1000.0) as i64;
+                self.ffr = FfrState::Releasing {
+                    since: since + Duration::milliseconds(support_ms),
+                };
+            }
+        }
+        match self.ffr {
+            FfrState::Supporting { .. } => capacity_w,
+            FfrState::Releasing { since } => {
+                let share = 1.0 - FFR_RELEASE_PER_SECOND * seconds_since(since);
+                if share > 0.0 {
+                    capacity_w * share
+                } else {
+                    self.ffr = FfrState::Spent;
+                    0.0
+                }
+            }
+            FfrState::Spent => {
+                if frequency_hz >= FCR_D_UP_START_HZ {
+                    self.ffr = FfrState::Armed;
+                }
+                0.0
+            }
+            FfrState::Armed => 0.0,
+        }
+    }
+
+    fn finish_activation(&mut self, now: DateTime<Utc>) {
+        let Some(mut activation) = self.active.take() else {
+            return;
+        };
+        // Close the energy sum at the end of the activation
+        if let Some(last) = activation.samples.last().cloned() {
+            activation.record(ReserveSample {
+                timestamp: now,
+                fcr_d_w: 0.0,
+                ffr_w: 0.0,
+                response_w: 0.0,
+                ..last
+            });
+        }
+        activation.ended_at = Some(now);
+        info!(
+            direction = ?activation.direction,
+            duration_s = (now - activation.started_at).num_seconds(),
+            min_frequency_hz = activation.min_frequency_hz,
+            max_frequency_hz = activation.max_frequency_hz,
+            fcr_d_energy_kwh = activation.fcr_d_energy_kwh,
+            ffr_energy_kwh = activation.ffr_energy_kwh,
+            "Frequency reserve activation ended"
+        );
+        if self.log.len() == MAX_ACTIVATIONS {
+            self.log.pop_front();
+        }
+        self.log.push_back(activation);
+    }
+}
+
+/// Grid frequency to respond to, from measured values only
+///
+/// The grid meter's reading wins; meters that do not measure frequency, such
+/// as HAN/P1 ports, defer to the inverter. Zero means the grid is down.
+pub fn measured_frequency_hz(meter_hz: Option<f64>, inverter_hz: Option<f64>) -> Option<f64> {
+    meter_hz.or(inverter_hz).filter(|&f| f > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::grid::{GridFaultType, GridSimulator, GridSimulatorConfig};
+    use chrono::TimeZone;
+
+    fn reserve(hours: Vec<u32>) -> FrequencyReserve {
+        FrequencyReserve::new(
+            ReserveOffer {
+                hours,
+                fcr_d_up_kw: 4.0,
+                fcr_d_down_kw: 2.0,
+                ffr_kw: 1.0,
+                ffr_support_seconds: 5.0,
+                timezone: "UTC".to_string(),
+            },
+            49.7,
+        )
+    }
+
+    fn grid(start: DateTime<Utc>) -> GridSimulator {
+        let config = GridSimulatorConfig {
+            random_seed: Some(7),
+            enable_faults: false,
+            ..Default::default()
+        };
+        GridSimulator::new(config, start.naive_utc())
+    }
+
+    #[test]
+    fn test_follows_simulated_frequency_events() {
+        let start = Utc.with_ymd_and_hms(2025, 3, 10, 12, 0, 0).unwrap();
+        let mut sim = grid(start);
+        let mut reserve = reserve(vec![12]);
+
+        // Normal operation stays inside the FCR-D dead band
+        for minute in 1..5 {
+            let now = start + Duration::minutes(minute);
+            sim.tick(now.naive_utc(), 1.0, 0.0);
+            assert_eq!(reserve.response(now, sim.frequency_hz()), 0.0);
+        }
+
+        sim.trigger_fault(GridFaultType::UnderFrequency, 3);
+        let now = start + Duration::minutes(5);
+        sim.tick(now.naive_utc(), 1.0, 0.0);
+        let f = sim.frequency_hz();
+        let response = reserve.response(now, f);
+        let expected_fcr_d = -fcr_d_up_share(f) * 4000.0;
+        let expected_ffr = if f <= 49.7 { 1000.0 } else { 0.0 };
+        assert!(f < 49.9);
+        assert!((response - (expected_fcr_d - expected_ffr)).abs() < 1e-6);
+
+        // Cleared: the response ends and the activation is logged
+        let cleared = start + Duration::minutes(9);
+        sim.tick(cleared.naive_utc(), 1.0, 0.0);
+        assert_eq!(reserve.response(cleared, sim.frequency_hz()), 0.0);
+
+        sim.trigger_fault(GridFaultType::OverFrequency, 2);
+        let now = start + Duration::minutes(10);
+        sim.tick(now.naive_utc(), 1.0, 0.0);
+        let f = sim.frequency_hz();
+        let response = reserve.response(now, f);
+        assert!(f > 50.1);
+        assert!((response - fcr_d_down_share(f) * 2000.0).abs() < 1e-6);
+
+        let log = reserve.activations();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].direction, ReserveDirection::Up);
+        assert_eq!(log[0].ended_at, Some(cleared));
+        assert!(log[0].fcr_d_energy_kwh > 0.0);
+        assert_eq!(log[1].direction, ReserveDirection::Down);
+        assert!(log[1].ended_at.is_none());
+
+        // Outside the committed hours nothing is delivered
+        let later = start + Duration::hours(1);
+        assert_eq!(reserve.response(later, 49.6), 0.0);
+    }
+
+    #[test]
+    fn test_meter_without_frequency_defers_to_inverter() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 10, 12, 0, 0).unwrap();
+        let mut reserve = reserve(vec![12]);
+        let han_reading = crate::domain::GridMeterReading {
+            phases: Default::default(),
+            power_w: 1200.0,
+            frequency_hz: None,
+            import_energy_kwh: 0.0,
+            export_energy_kwh: 0.0,
+            timestamp: now,
+        };
+
+        let f = measured_frequency_hz(han_reading.frequency_hz, Some(49.8)).unwrap();
+        assert_eq!(f, 49.8);
+        assert!(
+            reserve.response(now, f) < 0.0,
+            "FCR-D up discharges the battery"
+        );
+
+        assert_eq!(measured_frequency_hz(Some(50.02), Some(49.8)), Some(50.02));
+        assert_eq!(measured_frequency_hz(Some(0.0), Some(49.8)), None);
+        assert_eq!(measured_frequency_hz(None, None), None);
+    }
+
+    #[test]
+    fn test_islanding_silences_the_response() {
+        let start = Utc.with_ymd_and_hms(2025, 3, 10, 12, 0, 0).unwrap();
+        let mut reserve = reserve(vec![12]);
+        assert!(reserve.response(start, 49.6) < 0.0);
+
+        // The grid fails and the inverter forms an island
+        let islanded = start + Duration::seconds(1);
+        reserve.stand_down(islanded);
+        assert_eq!(reserve.last_response_w(), 0.0);
+        let log = reserve.activations();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].ended_at, Some(islanded));
+
+        // Back on the grid, FFR triggers afresh
+        let restored = start + Duration::minutes(10);
+        let fcr_d_w = -fcr_d_up_share(49.6) * 4000.0;
+        assert!((reserve.response(restored, 49.6) - (fcr_d_w - 1000.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ffr_holds_then_releases() {
+        let start = Utc.with_ymd_and_hms(2025, 3, 10, 12, 0, 0).unwrap();
+        let mut reserve = reserve(vec![12]);
+        let at = |ms: i64| start + Duration::milliseconds(ms);
+        let fcr_d_w = -fcr_d_up_share(49.6) * 4000.0;
+
+        // Support for 5 s, then released at 20%/s over another 5 s
+        let mut ffr_w = Vec::new();
+        for ms in (0..=11_000).step_by(100) {
+            ffr_w.push(fcr_d_w - reserve.response(at(ms), 49.6));
+        }
+        assert!((ffr_w[0] - 1000.0).abs() < 1e-6);
+        assert!((ffr_w[49] - 1000.0).abs() < 1e-6);
+        assert!((ffr_w[75] - 500.0).abs() < 1e-6);
+        assert!(ffr_w[101].abs() < 1e-6);
+
+        // Not re-triggered until the frequency has recovered
+        reserve.response(at(12_000), 50.0);
+        assert!((reserve.response(at(13_000), 49.6) - (fcr_d_w - 1000.0)).abs() < 1e-6);
+
+        let log = reserve.activations();
+        assert_eq!(log.len(), 2);
+        // 1 kW for 5 s plus the 5 s ramp, within one 100 ms step
+        assert!((log[0].ffr_energy_kwh * 3600.0 - 7.5).abs() < 0.1);
+    }
+}
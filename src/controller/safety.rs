@@ -198,25 +198,25 @@ impl SafetyMonitor {
             return Err(anyhow::anyhow!("Grid blackout detected"));
         }
 
-        // Check grid frequency
-        if grid.frequency_hz < self.config.min_grid_frequency_hz
-            || grid.frequency_hz > self.config.max_grid_frequency_hz
-        {
+        // Check grid frequency, when something measures it
+        if let Some(frequency_hz) = grid.frequency_hz.filter(|&f| {
+            f < self.config.min_grid_frequency_hz || f > self.config.max_grid_frequency_hz
+        }) {
             let event = SafetyEvent::GridFrequencyFault {
-                frequency_hz: grid.frequency_hz,
+                frequency_hz,
                 min_hz: self.config.min_grid_frequency_hz,
                 max_hz: self.config.max_grid_frequency_hz,
             };
             self.record_event(now, event.clone());
             error!(
-                frequency_hz = grid.frequency_hz,
+                frequency_hz,
                 min_hz = self.config.min_grid_frequency_hz,
                 max_hz = self.config.max_grid_frequency_hz,
                 "SAFETY VIOLATION: Grid frequency out of range"
             );
             return Err(anyhow::anyhow!(
                 "Grid frequency {:.2}Hz out of acceptable range ({:.2}-{:.2}Hz)",
-                grid.frequency_hz,
+                frequency_hz,
                 self.config.min_grid_frequency_hz,
                 self.config.max_grid_frequency_hz
             ));
@@ -347,7 +347,7 @@ mod tests {
             status: GridStatus::Normal,
             import_power_w: 0.0,
             export_power_w: 0.0,
-            frequency_hz: 50.0,
+            frequency_hz: Some(50.0),
             voltage_v: 230.0,
             current_a: 0.0,
         }
@@ -404,7 +404,7 @@ mod tests {
     fn test_grid_frequency_fault() {
         let mut monitor = SafetyMonitor::new();
         let mut grid = make_safe_grid_connection();
-        grid.frequency_hz = 49.0; // Below 49.5Hz limit
+        grid.frequency_hz = Some(49.0); // Below 49.5Hz limit
         assert!(monitor.check_grid_safety(&grid).is_err());
     }
 
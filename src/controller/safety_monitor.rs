@@ -20,6 +20,7 @@ use tokio::sync::{broadcast, RwLock};
 use tracing::{debug, error, info, warn};
 
 use crate::domain::{Battery, Inverter};
+use crate::simulation::three_phase::ThreePhasePower;
 
 /// Safety monitor configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -212,10 +213,16 @@ impl Default for SafetyMonitorState {
 pub struct SafetyMeasurements {
     /// Grid import power (kW)
     pub grid_import_kw: f64,
+    /// Measured current per phase (A), when a grid meter is available
+    /// Takes precedence over the current estimated from `grid_import_kw`
+    pub grid_phase_currents_a: Option<[f64; 3]>,
+    /// Grid power per phase (W, positive = import), e.g. from the power flow model
+    /// Used to estimate phase currents when none are measured
+    pub grid_phase_power_w: Option<ThreePhasePower>,
     /// Grid voltage (V)
     pub grid_voltage_v: f64,
-    /// Grid frequency (Hz)
-    pub grid_frequency_hz: f64,
+    /// Grid frequency (Hz), `None` when neither meter nor inverter measures it
+    pub grid_frequency_hz: Option<f64>,
     /// Battery SoC (%)
     pub battery_soc_percent: f64,
     /// Battery temperature (°C)
@@ -231,8 +238,10 @@ impl Default for SafetyMeasurements {
     fn default() -> Self {
         Self {
             grid_import_kw: 0.0,
+            grid_phase_currents_a: None,
+            grid_phase_power_w: None,
             grid_voltage_v: 230.0,
-            grid_frequency_hz: 50.0,
+            grid_frequency_hz: Some(50.0),
             battery_soc_percent: 50.0,
             battery_temperature_c: 25.0,
             grid_nominal_voltage_v: 230.0,
@@ -322,27 +331,38 @@ impl SafetyMonitor {
         }
 
         // Check fuse overcurrent
+        // The fuse protects each phase, so a measured phase current is authoritative,
+        // per-phase power is the next best estimate and the total is the last resort
         // CRITICAL FIX: Protect against division by zero/near-zero voltage
-        let grid_current_a = if measurements.grid_nominal_voltage_v < 1.0 {
+        let (grid_current_a, phase) = if let Some(currents) = measurements.grid_phase_currents_a {
+            worst_phase(currents)
+        } else if measurements.grid_nominal_voltage_v < 1.0 {
             // Voltage sensor fault or blackout - assume maximum current to trigger safety
             warn!(
                 "Grid voltage sensor fault or blackout ({}V < 1V) - assuming max current",
                 measurements.grid_nominal_voltage_v
             );
-            self.config.fuse_rating_a * 2.0 // Trigger overcurrent fault
+            (self.config.fuse_rating_a * 2.0, None) // Trigger overcurrent fault
+        } else if let Some(powers) = measurements.grid_phase_power_w {
+            let currents = powers.currents(measurements.grid_nominal_voltage_v);
+            worst_phase([currents.l1_a, currents.l2_a, currents.l3_a])
         } else {
-            measurements.grid_import_kw * 1000.0 / measurements.grid_nominal_voltage_v
+            (
+                measurements.grid_import_kw * 1000.0 / measurements.grid_nominal_voltage_v,
+                None,
+            )
         };
         let fuse_trip_threshold = self.config.fuse_rating_a * (1.0 - self.config.fuse_trip_margin);
 
         if grid_current_a > fuse_trip_threshold {
+            let location = phase.map(|p| format!(" on L{}", p)).unwrap_or_default();
             violations.push(SafetyViolation::new(
                 SafetyViolationType::FuseOvercurrent,
                 grid_current_a,
                 fuse_trip_threshold,
                 format!(
-                    "Grid current {:.1}A exceeds fuse trip threshold {:.1}A",
-                    grid_current_a, fuse_trip_threshold
+                    "Grid current {:.1}A{} exceeds fuse trip threshold {:.1}A",
+                    grid_current_a, location, fuse_trip_threshold
                 ),
             ));
         }
@@ -372,29 +392,31 @@ impl SafetyMonitor {
             ));
         }
 
-        // Check grid frequency
-        if measurements.grid_frequency_hz < self.config.grid_frequency_min_hz {
-            violations.push(SafetyViolation::new(
-                SafetyViolationType::GridFrequencyViolation,
-                measurements.grid_frequency_hz,
-                self.config.grid_frequency_min_hz,
-                format!(
-                    "Grid frequency {:.2}Hz below minimum {:.2}Hz",
-                    measurements.grid_frequency_hz, self.config.grid_frequency_min_hz
-                ),
-            ));
-        }
+        // Check grid frequency, unless nothing measures it
+        if let Some(frequency_hz) = measurements.grid_frequency_hz {
+            if frequency_hz < self.config.grid_frequency_min_hz {
+                violations.push(SafetyViolation::new(
+                    SafetyViolationType::GridFrequencyViolation,
+                    frequency_hz,
+                    self.config.grid_frequency_min_hz,
+                    format!(
+                        "Grid frequency {:.2}Hz below minimum {:.2}Hz",
+                        frequency_hz, self.config.grid_frequency_min_hz
+                    ),
+                ));
+            }
 
-        if measurements.grid_frequency_hz > self.config.grid_frequency_max_hz {
-            violations.push(SafetyViolation::new(
-                SafetyViolationType::GridFrequencyViolation,
-                measurements.grid_frequency_hz,
-                self.config.grid_frequency_max_hz,
-                format!(
-                    "Grid frequency {:.2}Hz above maximum {:.2}Hz",
-                    measurements.grid_frequency_hz, self.config.grid_frequency_max_hz
-                ),
-            ));
+            if frequency_hz > self.config.grid_frequency_max_hz {
+                violations.push(SafetyViolation::new(
+                    SafetyViolationType::GridFrequencyViolation,
+                    frequency_hz,
+                    self.config.grid_frequency_max_hz,
+                    format!(
+                        "Grid frequency {:.2}Hz above maximum {:.2}Hz",
+                        frequency_hz, self.config.grid_frequency_max_hz
+                    ),
+                ));
+            }
         }
 
         // Check battery temperature
@@ -650,6 +672,15 @@ impl SafetyMonitor {
     }
 }
 
+/// Highest phase current magnitude and its phase (1-3)
+fn worst_phase(currents_a: [f64; 3]) -> (f64, Option<usize>) {
+    currents_a
+        .iter()
+        .enumerate()
+        .map(|(i, a)| (a.abs(), Some(i + 1)))
+        .fold((0.0, None), |worst, phase| if phase.0 > worst.0 { phase } else { worst })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -689,6 +720,58 @@ mod tests {
         assert_eq!(violations[0].violation_type, SafetyViolationType::FuseOvercurrent);
     }
 
+    #[tokio::test]
+    async fn test_fuse_overcurrent_on_single_phase() {
+        let config = SafetyMonitorConfig {
+            fuse_rating_a: 25.0,
+            fuse_trip_margin: 0.1,
+            enable_emergency_stop: false,
+            ..Default::default()
+        };
+        let (monitor, _rx) = SafetyMonitor::new(config);
+
+        monitor.start_monitoring().await;
+
+        // 5.5 kW total is fine spread over three phases, but L1 carries 24 A
+        let measurements = SafetyMeasurements {
+            grid_import_kw: 5.5,
+            grid_phase_currents_a: Some([24.0, 0.0, 0.0]),
+            grid_nominal_voltage_v: 230.0,
+            ..Default::default()
+        };
+
+        let violations = monitor.check_safety(&measurements).await;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].violation_type, SafetyViolationType::FuseOvercurrent);
+        assert_eq!(violations[0].value, 24.0);
+    }
+
+    #[tokio::test]
+    async fn test_fuse_overcurrent_from_phase_power() {
+        let config = SafetyMonitorConfig {
+            fuse_rating_a: 25.0,
+            fuse_trip_margin: 0.1,
+            enable_emergency_stop: false,
+            ..Default::default()
+        };
+        let (monitor, _rx) = SafetyMonitor::new(config);
+
+        monitor.start_monitoring().await;
+
+        // No current registers: 5.52 kW on L2 alone is 24 A on that phase
+        let measurements = SafetyMeasurements {
+            grid_import_kw: 5.52,
+            grid_phase_power_w: Some(ThreePhasePower::new(0.0, 5520.0, 0.0)),
+            grid_nominal_voltage_v: 230.0,
+            ..Default::default()
+        };
+
+        let violations = monitor.check_safety(&measurements).await;
+        assert_eq!(violations.len(), 1);
+        assert!((violations[0].value - 24.0).abs() < 1e-6);
+        assert!(violations[0].message.contains("on L2"), "{}", violations[0].message);
+    }
+
     #[tokio::test]
     async fn test_grid_voltage_violation() {
         let config = SafetyMonitorConfig {
@@ -726,6 +809,32 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_grid_frequency_violation() {
+        let config = SafetyMonitorConfig {
+            enable_emergency_stop: false,
+            ..Default::default()
+        };
+        let (monitor, _rx) = SafetyMonitor::new(config);
+
+        let measurements = SafetyMeasurements {
+            grid_frequency_hz: Some(49.2), // Below 49.5Hz minimum
+            ..Default::default()
+        };
+        let violations = monitor.check_safety(&measurements).await;
+        assert_eq!(
+            violations[0].violation_type,
+            SafetyViolationType::GridFrequencyViolation
+        );
+
+        // A HAN meter without an inverter reading leaves nothing to check
+        let measurements = SafetyMeasurements {
+            grid_frequency_hz: None,
+            ..Default::default()
+        };
+        assert!(monitor.check_safety(&measurements).await.is_empty());
+    }
+
     #[tokio::test]
     async fn test_battery_temperature_violation() {
         let config = SafetyMonitorConfig {
@@ -902,8 +1011,10 @@ mod tests {
         // Normal measurements within all limits
         let measurements = SafetyMeasurements {
             grid_import_kw: 2.0,          // Well below fuse limit
+            grid_phase_currents_a: None,
+            grid_phase_power_w: None,
             grid_voltage_v: 230.0,        // Nominal
-            grid_frequency_hz: 50.0,      // Nominal
+            grid_frequency_hz: Some(50.0), // Nominal
             battery_soc_percent: 50.0,    // Mid-range
             battery_temperature_c: 25.0,  // Room temperature
             grid_nominal_voltage_v: 230.0,
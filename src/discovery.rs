@@ -15,6 +15,8 @@ pub struct DiscoveredDevice {
     pub manufacturer: Option<String>,
     pub model: Option<String>,
     pub serial: Option<String>,
+    /// SunSpec model IDs, empty for devices without SunSpec
+    pub sunspec_models: Vec<u16>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,6 +24,7 @@ pub enum DeviceType {
     ModbusBattery,
     ModbusInverter,
     ModbusEvCharger,
+    ModbusGridMeter,
     Unknown,
 }
 
@@ -199,6 +202,11 @@ impl ModbusIdentifier {
         // Try different unit IDs
         for unit_id in [1, 2, 3, 126, 247] {
             if let Ok(client) = ModbusClient::connect(&addr, unit_id).await {
+                // SunSpec devices describe themselves, so prefer that over probing
+                if let Ok(device) = Self::identify_sunspec(&client, ip, port).await {
+                    return Ok(device);
+                }
+
                 // Try to identify the device by reading identification registers
                 if let Ok(device_type) = Self::detect_device_type(&client).await {
                     return Ok(DiscoveredDevice {
@@ -208,6 +216,7 @@ impl ModbusIdentifier {
                         manufacturer: None, // Could be read from device-specific registers
                         model: None,
                         serial: None,
+                        sunspec_models: Vec::new(),
                     });
                 }
             }
@@ -221,6 +230,44 @@ impl ModbusIdentifier {
             manufacturer: None,
             model: None,
             serial: None,
+            sunspec_models: Vec::new(),
+        })
+    }
+
+    /// Identify a device from its SunSpec model chain and common model
+    #[cfg(feature = "modbus")]
+    async fn identify_sunspec(
+        client: &crate::modbus::client::ModbusClient,
+        ip: IpAddr,
+        port: u16,
+    ) -> Result<DiscoveredDevice> {
+        use crate::modbus::sunspec::SunSpecReader;
+
+        let reader = SunSpecReader::new(client.clone()).await?;
+        let common = reader.common().await?;
+        let map = reader.map();
+
+        // A hybrid inverter also carries storage and meter models; classify
+        // by its primary function
+        let device_type = if map.is_inverter() {
+            DeviceType::ModbusInverter
+        } else if map.is_storage() {
+            DeviceType::ModbusBattery
+        } else if map.is_meter() {
+            DeviceType::ModbusGridMeter
+        } else {
+            DeviceType::Unknown
+        };
+
+        let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+        Ok(DiscoveredDevice {
+            ip,
+            port,
+            device_type,
+            manufacturer: non_empty(common.manufacturer),
+            model: non_empty(common.model),
+            serial: non_empty(common.serial_number),
+            sunspec_models: map.models.iter().map(|m| m.id).collect(),
         })
     }
 
@@ -253,6 +300,7 @@ impl ModbusIdentifier {
             manufacturer: None,
             model: None,
             serial: None,
+            sunspec_models: Vec::new(),
         })
     }
 }
@@ -398,6 +446,7 @@ pub mod mdns {
                 manufacturer: self.txt_properties.get("manufacturer").cloned(),
                 model: self.txt_properties.get("model").cloned(),
                 serial: self.txt_properties.get("serial").cloned(),
+                sunspec_models: Vec::new(),
             })
         }
     }
@@ -0,0 +1,488 @@
+#![allow(dead_code)]
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use super::battery::{
+    Battery, BatteryCapabilities, BatteryChemistry, BatteryState, BatteryStatus, HealthStatus,
+};
+
+/// How an aggregate setpoint is split across packs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DispatchStrategy {
+    /// Proportional to each pack's remaining energy in the requested direction
+    #[default]
+    Headroom,
+    /// Favour the emptiest pack when charging and the fullest when
+    /// discharging, so the packs' SoCs converge
+    SocBalancing,
+    /// Fill packs in priority order (lowest value first)
+    Priority,
+}
+
+/// Operating window of one pack, enforced before any power is assigned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackLimits {
+    pub min_soc_percent: f64,
+    pub max_soc_percent: f64,
+    pub min_temp_c: f64,
+    pub max_temp_c: f64,
+}
+
+impl Default for PackLimits {
+    fn default() -> Self {
+        Self {
+            min_soc_percent: 10.0,
+            max_soc_percent: 95.0,
+            min_temp_c: -10.0,
+            max_temp_c: 55.0,
+        }
+    }
+}
+
+/// One physical battery in an `AggregateBattery`
+pub struct BatteryPack {
+    pub id: String,
+    pub battery: Arc<dyn Battery>,
+    pub priority: u8,
+    pub limits: PackLimits,
+}
+
+/// What a pack can absorb or deliver right now, in one direction
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct PackHeadroom {
+    pub power_w: f64,
+    pub energy_kwh: f64,
+    /// Usable capacity after health derating
+    pub capacity_kwh: f64,
+    pub priority: u8,
+}
+
+impl PackHeadroom {
+    fn new(pack: &BatteryPack, state: &BatteryState, charging: bool) -> Self {
+        let limits = &pack.limits;
+        let usable = !matches!(state.status, BatteryStatus::Fault | BatteryStatus::Offline)
+            && state.temperature_c >= limits.min_temp_c
+            && state.temperature_c <= limits.max_temp_c;
+        if !usable {
+            return Self {
+                priority: pack.priority,
+                ..Self::default()
+            };
+        }
+
+        let caps = pack.battery.capabilities();
+        let capacity_kwh = caps.capacity_kwh * (state.health_percent / 100.0).clamp(0.0, 1.0);
+        let soc_room = if charging {
+            limits.max_soc_percent - state.soc_percent
+        } else {
+            state.soc_percent - limits.min_soc_percent
+        };
+        let energy_kwh = capacity_kwh * soc_room.max(0.0) / 100.0;
+        let max_kw = if charging {
+            caps.max_charge_kw
+        } else {
+            caps.max_discharge_kw
+        };
+
+        Self {
+            power_w: if energy_kwh > 0.0 { max_kw * 1000.0 } else { 0.0 },
+            energy_kwh,
+            capacity_kwh,
+            priority: pack.priority,
+        }
+    }
+}
+
+/// Several batteries dispatched as one
+///
+/// Each pack keeps its own SoC, temperature and power limits: a pack that
+/// is full, empty, faulted or outside its temperature window gets no power,
+/// and no pack is asked for more than its own rating.
+pub struct AggregateBattery {
+    packs: Vec<BatteryPack>,
+    strategy: DispatchStrategy,
+}
+
+impl AggregateBattery {
+    pub fn new(packs: Vec<BatteryPack>, strategy: DispatchStrategy) -> Result<Self> {
+        if packs.is_empty() {
+            bail!("Aggregate battery needs at least one pack");
+        }
+        for (i, pack) in packs.iter().enumerate() {
+            if packs[..i].iter().any(|p| p.id == pack.id) {
+                bail!("Duplicate battery pack id '{}'", pack.id);
+            }
+        }
+        Ok(Self { packs, strategy })
+    }
+
+    pub fn packs(&self) -> &[BatteryPack] {
+        &self.packs
+    }
+
+    pub fn pack(&self, id: &str) -> Option<&BatteryPack> {
+        self.packs.iter().find(|p| p.id == id)
+    }
+
+    pub fn strategy(&self) -> DispatchStrategy {
+        self.strategy
+    }
+
+    /// Read every pack concurrently, in pack order
+    pub async fn read_pack_states(&self) -> Vec<Result<BatteryState>> {
+        join_all(self.packs.iter().map(|p| p.battery.read_state())).await
+    }
+
+    /// Split `total_w` (positive = charge) according to `strategy`
+    pub(crate) fn split(strategy: DispatchStrategy, headroom: &[PackHeadroom], total_w: f64) -> Vec<f64> {
+        let caps: Vec<f64> = headroom.iter().map(|h| h.power_w).collect();
+        let magnitude = total_w.abs();
+
+        let shares = match strategy {
+            DispatchStrategy::Headroom => {
+                let weights: Vec<f64> = headroom.iter().map(|h| h.energy_kwh).collect();
+                water_fill(&weights, &caps, magnitude)
+            }
+            DispatchStrategy::SocBalancing => {
+                // Weight = capacity x (relative headroom)^2: SoC moves fastest
+                // on the pack furthest from its limit
+                let weights: Vec<f64> = headroom
+                    .iter()
+                    .map(|h| {
+                        if h.capacity_kwh > 0.0 {
+                            h.energy_kwh * h.energy_kwh / h.capacity_kwh
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+                water_fill(&weights, &caps, magnitude)
+            }
+            DispatchStrategy::Priority => {
+                let mut priorities: Vec<u8> = headroom.iter().map(|h| h.priority).collect();
+                priorities.sort_unstable();
+                priorities.dedup();
+
+                let mut shares = vec![0.0; headroom.len()];
+                let mut remaining = magnitude;
+                for priority in priorities {
+                    let weights: Vec<f64> = headroom
+                        .iter()
+                        .map(|h| if h.priority == priority { h.energy_kwh } else { 0.0 })
+                        .collect();
+                    for (share, tier) in shares.iter_mut().zip(water_fill(&weights, &caps, remaining)) {
+                        *share += tier;
+                        remaining -= tier;
+                    }
+                }
+                shares
+            }
+        };
+
+        shares.into_iter().map(|s| s.copysign(total_w)).collect()
+    }
+}
+
+/// Distribute `total` proportionally to `weights`, capping each share at
+/// `caps` and handing the excess to the others
+fn water_fill(weights: &[f64], caps: &[f64], total: f64) -> Vec<f64> {
+    let mut alloc = vec![0.0; weights.len()];
+    let mut active: Vec<usize> = (0..weights.len())
+        .filter(|&i| weights[i] > 0.0 && caps[i] > 0.0)
+        .collect();
+    let mut remaining = total;
+
+    while remaining > 1e-9 && !active.is_empty() {
+        let weight_sum: f64 = active.iter().map(|&i| weights[i]).sum();
+        let saturated: Vec<usize> = active
+            .iter()
+            .copied()
+            .filter(|&i| remaining * weights[i] / weight_sum >= caps[i] - alloc[i])
+            .collect();
+
+        if saturated.is_empty() {
+            for &i in &active {
+                alloc[i] += remaining * weights[i] / weight_sum;
+            }
+            break;
+        }
+        for i in saturated {
+            remaining -= caps[i] - alloc[i];
+            alloc[i] = caps[i];
+            active.retain(|&a| a != i);
+        }
+    }
+    alloc
+}
+
+fn health_rank(health: HealthStatus) -> u8 {
+    match health {
+        HealthStatus::Healthy => 0,
+        HealthStatus::Degraded => 1,
+        HealthStatus::Warning => 2,
+        HealthStatus::Critical => 3,
+        HealthStatus::Offline => 4,
+    }
+}
+
+#[async_trait]
+impl Battery for AggregateBattery {
+    async fn read_state(&self) -> Result<BatteryState> {
+        let states = self.read_pack_states().await;
+
+        let mut online = Vec::with_capacity(states.len());
+        for (pack, state) in self.packs.iter().zip(states) {
+            match state {
+                Ok(state) => online.push((pack.battery.capabilities().capacity_kwh, state)),
+                Err(e) => warn!(pack = %pack.id, error = %e, "Battery pack unreadable"),
+            }
+        }
+        if online.is_empty() {
+            bail!("All {} battery packs are unreadable", self.packs.len());
+        }
+
+        // Capacity-weighted so the aggregate SoC tracks stored energy
+        let capacity: f64 = online.iter().map(|(c, _)| c).sum::<f64>().max(f64::EPSILON);
+        let weighted = |f: fn(&BatteryState) -> f64| {
+            online.iter().map(|(c, s)| c * f(s)).sum::<f64>() / capacity
+        };
+        let power_w: f64 = online.iter().map(|(_, s)| s.power_w).sum();
+        let status = if power_w > 10.0 {
+            BatteryStatus::Charging
+        } else if power_w < -10.0 {
+            BatteryStatus::Discharging
+        } else if online.iter().all(|(_, s)| s.status == BatteryStatus::Fault) {
+            BatteryStatus::Fault
+        } else {
+            BatteryStatus::Idle
+        };
+
+        Ok(BatteryState {
+            soc_percent: weighted(|s| s.soc_percent),
+            power_w,
+            voltage_v: online.iter().map(|(_, s)| s.voltage_v).sum::<f64>() / online.len() as f64,
+            // Hottest pack is the one safety limits care about
+            temperature_c: online
+                .iter()
+                .map(|(_, s)| s.temperature_c)
+                .fold(f64::NEG_INFINITY, f64::max),
+            health_percent: weighted(|s| s.health_percent),
+            status,
+        })
+    }
+
+    async fn set_power(&self, watts: f64) -> Result<()> {
+        let charging = watts >= 0.0;
+        let headroom: Vec<PackHeadroom> = self
+            .read_pack_states()
+            .await
+            .iter()
+            .zip(&self.packs)
+            .map(|(state, pack)| match state {
+                Ok(state) => PackHeadroom::new(pack, state, charging),
+                Err(_) => PackHeadroom::default(),
+            })
+            .collect();
+
+        let shares = Self::split(self.strategy, &headroom, watts);
+        let delivered: f64 = shares.iter().sum();
+        if (delivered - watts).abs() > 1.0 {
+            debug!(
+                requested_w = watts,
+                delivered_w = delivered,
+                "Battery packs cannot absorb the full setpoint"
+            );
+        }
+
+        // Packs with no share get 0 W so they stop rather than hold an old setpoint
+        let results = join_all(
+            self.packs
+                .iter()
+                .zip(&shares)
+                .map(|(pack, &share)| pack.battery.set_power(share)),
+        )
+        .await;
+
+        let failed: Vec<String> = self
+            .packs
+            .iter()
+            .zip(results)
+            .filter_map(|(pack, r)| r.err().map(|e| format!("{}: {}", pack.id, e)))
+            .collect();
+        if !failed.is_empty() {
+            bail!("Failed to set power on battery packs: {}", failed.join("; "));
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> BatteryCapabilities {
+        let caps: Vec<BatteryCapabilities> = self.packs.iter().map(|p| p.battery.capabilities()).collect();
+        let capacity_kwh: f64 = caps.iter().map(|c| c.capacity_kwh).sum();
+        let weighted = |f: fn(&BatteryCapabilities) -> f64| {
+            caps.iter().map(|c| c.capacity_kwh * f(c)).sum::<f64>() / capacity_kwh.max(f64::EPSILON)
+        };
+        let chemistry = if caps.iter().all(|c| c.chemistry == caps[0].chemistry) {
+            caps[0].chemistry
+        } else {
+            BatteryChemistry::Unknown
+        };
+
+        BatteryCapabilities {
+            capacity_kwh,
+            max_charge_kw: caps.iter().map(|c| c.max_charge_kw).sum(),
+            max_discharge_kw: caps.iter().map(|c| c.max_discharge_kw).sum(),
+            efficiency: weighted(|c| c.efficiency),
+            degradation_per_cycle: weighted(|c| c.degradation_per_cycle),
+            chemistry,
+        }
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        let checks = join_all(self.packs.iter().map(|p| p.battery.health_check())).await;
+        let online: Vec<HealthStatus> = checks
+            .into_iter()
+            .map(|c| c.unwrap_or(HealthStatus::Offline))
+            .collect();
+
+        let worst_online = online
+            .iter()
+            .copied()
+            .filter(|&h| h != HealthStatus::Offline)
+            .max_by_key(|&h| health_rank(h));
+        Ok(match worst_online {
+            None => HealthStatus::Offline,
+            // Losing a pack costs capacity but the bank still works
+            Some(h) if online.contains(&HealthStatus::Offline) => {
+                if health_rank(h) < health_rank(HealthStatus::Degraded) {
+                    HealthStatus::Degraded
+                } else {
+                    h
+                }
+            }
+            Some(h) => h,
+        })
+    }
+
+    async fn reconnect(&self) -> Result<()> {
+        let results = join_all(self.packs.iter().map(|p| p.battery.reconnect())).await;
+        results.into_iter().collect()
+    }
+
+    async fn emergency_shutdown(&self) -> Result<()> {
+        // Every pack gets the command even if an earlier one fails
+        let results = join_all(self.packs.iter().map(|p| p.battery.emergency_shutdown())).await;
+        let failed: Vec<String> = self
+            .packs
+            .iter()
+            .zip(results)
+            .filter_map(|(pack, r)| r.err().map(|e| format!("{}: {}", pack.id, e)))
+            .collect();
+        if !failed.is_empty() {
+            bail!("Emergency shutdown failed on battery packs: {}", failed.join("; "));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::SimulatedBattery;
+
+    fn headroom(power_kw: f64, energy_kwh: f64, capacity_kwh: f64, priority: u8) -> PackHeadroom {
+        PackHeadroom {
+            power_w: power_kw * 1000.0,
+            energy_kwh,
+            capacity_kwh,
+            priority,
+        }
+    }
+
+    fn pack(id: &str, soc: f64, capacity_kwh: f64, max_kw: f64, priority: u8) -> BatteryPack {
+        let state = BatteryState {
+            soc_percent: soc,
+            power_w: 0.0,
+            voltage_v: 48.0,
+            temperature_c: 25.0,
+            health_percent: 100.0,
+            status: BatteryStatus::Idle,
+        };
+        let caps = BatteryCapabilities {
+            capacity_kwh,
+            max_charge_kw: max_kw,
+            max_discharge_kw: max_kw,
+            efficiency: 0.95,
+            degradation_per_cycle: 0.0001,
+            chemistry: BatteryChemistry::LiFePO4,
+        };
+        BatteryPack {
+            id: id.to_string(),
+            battery: Arc::new(SimulatedBattery::new(state, caps)),
+            priority,
+            limits: PackLimits::default(),
+        }
+    }
+
+    #[test]
+    fn test_headroom_split_respects_pack_power() {
+        // 10 kWh vs 5 kWh of room, but the first pack is limited to 3 kW
+        let packs = [headroom(3.0, 10.0, 10.0, 0), headroom(5.0, 5.0, 10.0, 0)];
+        let shares = AggregateBattery::split(DispatchStrategy::Headroom, &packs, 6000.0);
+        assert!((shares[0] - 3000.0).abs() < 1e-6);
+        assert!((shares[1] - 3000.0).abs() < 1e-6);
+
+        // More than both can take: each at its limit
+        let shares = AggregateBattery::split(DispatchStrategy::Headroom, &packs, -20_000.0);
+        assert_eq!(shares, vec![-3000.0, -5000.0]);
+    }
+
+    #[test]
+    fn test_soc_balancing_favours_emptier_pack() {
+        let packs = [headroom(5.0, 8.0, 10.0, 0), headroom(5.0, 2.0, 10.0, 0)];
+        let balanced = AggregateBattery::split(DispatchStrategy::SocBalancing, &packs, 5000.0);
+        let proportional = AggregateBattery::split(DispatchStrategy::Headroom, &packs, 5000.0);
+        assert!(balanced[0] > proportional[0]);
+        assert!((balanced.iter().sum::<f64>() - 5000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_priority_fills_first_pack_before_next() {
+        let packs = [headroom(5.0, 3.0, 10.0, 1), headroom(4.0, 9.0, 10.0, 0)];
+        let shares = AggregateBattery::split(DispatchStrategy::Priority, &packs, 3000.0);
+        assert_eq!(shares, vec![0.0, 3000.0]);
+        let shares = AggregateBattery::split(DispatchStrategy::Priority, &packs, 6000.0);
+        assert!((shares[0] - 2000.0).abs() < 1e-6);
+        assert_eq!(shares[1], 4000.0);
+    }
+
+    #[tokio::test]
+    async fn test_full_pack_gets_no_charge() {
+        let aggregate = AggregateBattery::new(
+            vec![pack("house", 95.0, 10.0, 5.0, 0), pack("garage", 40.0, 5.0, 2.5, 0)],
+            DispatchStrategy::Headroom,
+        )
+        .unwrap();
+        assert_eq!(aggregate.capabilities().max_charge_kw, 7.5);
+
+        aggregate.set_power(4000.0).await.unwrap();
+        let states = aggregate.read_pack_states().await;
+        assert_eq!(states[0].as_ref().unwrap().power_w, 0.0);
+        assert_eq!(states[1].as_ref().unwrap().power_w, 2500.0);
+
+        let state = aggregate.read_state().await.unwrap();
+        assert_eq!(state.power_w, 2500.0);
+        assert_eq!(state.status, BatteryStatus::Charging);
+        assert!(state.soc_percent > 76.0 && state.soc_percent < 77.0);
+    }
+
+    #[test]
+    fn test_duplicate_pack_ids_rejected() {
+        let packs = vec![pack("a", 50.0, 5.0, 2.0, 0), pack("a", 50.0, 5.0, 2.0, 0)];
+        assert!(AggregateBattery::new(packs, DispatchStrategy::Headroom).is_err());
+    }
+}
@@ -97,29 +97,6 @@ pub enum BatteryCommand {
     Standby,         // Enter standby mode
 }
 
-/// Battery degradation model
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
-pub struct DegradationModel {
-    pub cycle_count: u32,
-    pub health_percent: f64,
-    pub degradation_rate_per_cycle: f64,
-    pub calendar_age_years: f64,
-    pub temperature_impact: f64,
-}
-
-impl Default for DegradationModel {
-    fn default() -> Self {
-        Self {
-            cycle_count: 0,
-            health_percent: 100.0,
-            degradation_rate_per_cycle: 0.01, // 0.01% per cycle
-            calendar_age_years: 0.0,
-            temperature_impact: 1.0,
-        }
-    }
-}
-
 #[async_trait]
 pub trait Battery: Send + Sync {
     async fn read_state(&self) -> Result<BatteryState>;
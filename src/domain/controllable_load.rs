@@ -0,0 +1,472 @@
+//! Switchable loads: pool pumps, dryers, immersion heaters on a relay
+//!
+//! These loads only know on and off. Each needs a certain runtime per day
+//! inside a daily window, so the controller can move that runtime into the
+//! cheapest periods of the window. The relay is switched by the control
+//! tick, which lets the power flow model hold back a start the main fuse
+//! has no room for.
+#![allow(dead_code)]
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info, warn};
+
+use super::HealthStatus;
+use crate::power_flow::{inputs::ControllableLoadInput, LoadSwitch};
+
+/// What a switchable load needs from its day
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadConstraints {
+    /// Runtime to reach within every daily window
+    pub runtime_minutes_per_day: u32,
+    /// Start of the window in the household's local time
+    #[cfg_attr(feature = "swagger", schema(value_type = String))]
+    pub earliest_start: NaiveTime,
+    /// End of the window; at or before `earliest_start` it is on the next day
+    #[cfg_attr(feature = "swagger", schema(value_type = String))]
+    pub latest_finish: NaiveTime,
+    /// Shortest run once started
+    pub min_on_minutes: u32,
+    /// Shortest pause between two runs
+    pub min_off_minutes: u32,
+    /// Power drawn while on
+    pub power_kw: f64,
+    /// Site phase (1-3) a single-phase load is wired to; `None` for three-phase
+    pub phase: Option<u8>,
+}
+
+impl LoadConstraints {
+    /// Daily windows overlapping `from..until`, in order
+    pub fn windows(
+        &self,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        tz: chrono_tz::Tz,
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let overnight = self.latest_finish <= self.earliest_start;
+        let first_day = from.with_timezone(&tz).date_naive() - Duration::days(1);
+        let days = (until - from).num_days() + 2;
+
+        (0..=days)
+            .filter_map(|offset| {
+                let day = first_day + Duration::days(offset);
+                let end_day = if overnight {
+                    day + Duration::days(1)
+                } else {
+                    day
+                };
+                let start = day
+                    .and_time(self.earliest_start)
+                    .and_local_timezone(tz)
+                    .earliest()?;
+                let end = end_day
+                    .and_time(self.latest_finish)
+                    .and_local_timezone(tz)
+                    .earliest()?;
+                Some((start.with_timezone(&Utc), end.with_timezone(&Utc)))
+            })
+            .filter(|(start, end)| *end > from && *start < until)
+            .collect()
+    }
+}
+
+/// One reading of a switchable load
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadState {
+    pub on: bool,
+    /// Measured power, when the switch meters it
+    pub power_w: Option<f64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Manual on/off that replaces the schedule until `until`
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct LoadOverride {
+    pub on: bool,
+    pub until: DateTime<Utc>,
+}
+
+/// One planned run
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct LoadRun {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Planned runs of one load over the forecast horizon
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadSchedule {
+    pub generated_at: DateTime<Utc>,
+    pub runs: Vec<LoadRun>,
+    /// Expected energy cost of the runs (SEK)
+    pub cost_sek: f64,
+    /// Runtime the windows in the horizon could not fit
+    pub shortfall_minutes: u32,
+}
+
+impl LoadSchedule {
+    pub fn is_on_at(&self, t: DateTime<Utc>) -> bool {
+        self.runs.iter().any(|run| run.start <= t && t < run.end)
+    }
+}
+
+/// Relay or smart plug the controller can switch
+#[async_trait]
+pub trait ControllableLoad: Send + Sync {
+    async fn read_state(&self) -> Result<LoadState>;
+    async fn switch(&self, on: bool) -> Result<()>;
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        Ok(match self.read_state().await {
+            Ok(_) => HealthStatus::Healthy,
+            Err(_) => HealthStatus::Offline,
+        })
+    }
+}
+
+/// Relay with a fixed load behind it
+pub struct SimulatedControllableLoad {
+    power_kw: f64,
+    on: Mutex<bool>,
+}
+
+impl SimulatedControllableLoad {
+    pub fn new(power_kw: f64) -> Self {
+        Self {
+            power_kw,
+            on: Mutex::new(false),
+        }
+    }
+}
+
+#[async_trait]
+impl ControllableLoad for SimulatedControllableLoad {
+    async fn read_state(&self) -> Result<LoadState> {
+        let on = *self.on.lock().await;
+        Ok(LoadState {
+            on,
+            power_w: Some(if on { self.power_kw * 1000.0 } else { 0.0 }),
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn switch(&self, on: bool) -> Result<()> {
+        *self.on.lock().await = on;
+        Ok(())
+    }
+}
+
+/// Runtime bookkeeping of one load, from the control tick's readings
+#[derive(Debug, Clone, Default)]
+struct RunLog {
+    on: bool,
+    /// Last time the load changed state, `None` until it first does
+    since: Option<DateTime<Utc>>,
+    last_reading: Option<DateTime<Utc>>,
+    /// Window the runtime below belongs to
+    window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    runtime_seconds: f64,
+}
+
+/// One switchable load of the site
+pub struct ManagedLoad {
+    pub id: String,
+    pub load: Arc<dyn ControllableLoad>,
+    pub constraints: LoadConstraints,
+    schedule: RwLock<Option<LoadSchedule>>,
+    override_: RwLock<Option<LoadOverride>>,
+    log: RwLock<RunLog>,
+}
+
+impl ManagedLoad {
+    pub fn new(id: String, load: Arc<dyn ControllableLoad>, constraints: LoadConstraints) -> Self {
+        Self {
+            id,
+            load,
+            constraints,
+            schedule: RwLock::new(None),
+            override_: RwLock::new(None),
+            log: RwLock::new(RunLog::default()),
+        }
+    }
+
+    pub async fn schedule(&self) -> Option<LoadSchedule> {
+        self.schedule.read().await.clone()
+    }
+
+    pub async fn set_schedule(&self, schedule: LoadSchedule) {
+        *self.schedule.write().await = Some(schedule);
+    }
+
+    /// Override in force at `now`
+    pub async fn override_at(&self, now: DateTime<Utc>) -> Option<LoadOverride> {
+        self.override_.read().await.filter(|o| o.until > now)
+    }
+
+    /// Replace the schedule until the override expires; `None` clears it
+    pub async fn set_override(&self, override_: Option<LoadOverride>) {
+        *self.override_.write().await = override_;
+    }
+
+    /// Runtime already reached in the window containing `now`
+    pub async fn runtime_done_minutes(&self, now: DateTime<Utc>) -> u32 {
+        let log = self.log.read().await;
+        match log.window {
+            Some((start, end)) if start <= now && now < end => {
+                (log.runtime_seconds / 60.0).round() as u32
+            }
+            _ => 0,
+        }
+    }
+
+    /// Whether the load is on, and for how many minutes it has been so
+    pub async fn current_run(&self, now: DateTime<Utc>) -> (bool, Option<u32>) {
+        let log = self.log.read().await;
+        let minutes = log
+            .since
+            .map(|since| ((now - since).num_seconds().max(0) / 60) as u32);
+        (log.on, minutes)
+    }
+
+    /// Fold a reading into the runtime of the current window
+    async fn record(&self, state: &LoadState, now: DateTime<Utc>, tz: chrono_tz::Tz) {
+        let mut log = self.log.write().await;
+        let window = self
+            .constraints
+            .windows(now, now + Duration::seconds(1), tz)
+            .into_iter()
+            .find(|(start, end)| *start <= now && now < *end);
+
+        if log.window != window {
+            log.window = window;
+            log.runtime_seconds = 0.0;
+        } else if let (true, Some(last)) = (log.on, log.last_reading) {
+            log.runtime_seconds += (now - last).num_milliseconds().max(0) as f64 / 1000.0;
+        }
+        // How long the load was in its first reported state is unknown
+        if log.last_reading.is_some() && log.on != state.on {
+            log.since = Some(now);
+        }
+        log.on = state.on;
+        log.last_reading = Some(now);
+    }
+
+    /// Whether the load should be on now
+    ///
+    /// An override wins; otherwise the schedule decides, but a run shorter
+    /// than `min_on_minutes` is not cut and a pause shorter than
+    /// `min_off_minutes` is not ended.
+    async fn wanted_on(&self, now: DateTime<Utc>) -> bool {
+        if let Some(override_) = self.override_at(now).await {
+            return override_.on;
+        }
+        let scheduled = self
+            .schedule
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|s| s.is_on_at(now));
+        let (on, minutes) = self.current_run(now).await;
+        let minimum = if on {
+            self.constraints.min_on_minutes
+        } else {
+            self.constraints.min_off_minutes
+        };
+        match minutes {
+            Some(minutes) if scheduled != on && minutes < minimum => on,
+            _ => scheduled,
+        }
+    }
+}
+
+/// The site's switchable loads
+pub struct ControllableLoadGroup {
+    loads: Vec<ManagedLoad>,
+    timezone: chrono_tz::Tz,
+}
+
+impl ControllableLoadGroup {
+    pub fn new(loads: Vec<ManagedLoad>, timezone: chrono_tz::Tz) -> Result<Self> {
+        if loads.is_empty() {
+            bail!("Controllable load group needs at least one load");
+        }
+        for (i, load) in loads.iter().enumerate() {
+            if loads[..i].iter().any(|l| l.id == load.id) {
+                bail!("Duplicate controllable load id '{}'", load.id);
+            }
+        }
+        Ok(Self { loads, timezone })
+    }
+
+    pub fn loads(&self) -> &[ManagedLoad] {
+        &self.loads
+    }
+
+    pub fn load(&self, id: &str) -> Option<&ManagedLoad> {
+        self.loads.iter().find(|l| l.id == id)
+    }
+
+    pub fn timezone(&self) -> chrono_tz::Tz {
+        self.timezone
+    }
+
+    /// Power flow inputs for every load that answered
+    ///
+    /// Readings also advance each load's runtime for the day.
+    pub async fn inputs(&self, now: DateTime<Utc>) -> Vec<ControllableLoadInput> {
+        let states = join_all(self.loads.iter().map(|l| l.load.read_state())).await;
+        let mut inputs = Vec::with_capacity(self.loads.len());
+        for (managed, state) in self.loads.iter().zip(states) {
+            match state {
+                Ok(state) => {
+                    managed.record(&state, now, self.timezone).await;
+                    // A metered switch reports the actual draw while on
+                    let power_kw = state
+                        .power_w
+                        .filter(|w| state.on && *w > 0.0)
+                        .map(|w| w / 1000.0)
+                        .unwrap_or(managed.constraints.power_kw);
+                    inputs.push(ControllableLoadInput {
+                        id: managed.id.clone(),
+                        power_kw,
+                        phase: managed.constraints.phase,
+                        running: state.on,
+                        requested: managed.wanted_on(now).await,
+                    });
+                }
+                Err(e) => warn!(load = %managed.id, error = %e, "Controllable load read failed"),
+            }
+        }
+        inputs
+    }
+
+    /// Switch every load to its decision; loads without one are left alone
+    pub async fn apply(&self, switches: &[LoadSwitch], now: DateTime<Utc>) {
+        let commands = switches.iter().filter_map(|switch| {
+            let managed = self.load(&switch.id)?;
+            Some(async move {
+                let (on, _) = managed.current_run(now).await;
+                if on == switch.on {
+                    return;
+                }
+                match managed.load.switch(switch.on).await {
+                    Ok(()) => {
+                        let mut log = managed.log.write().await;
+                        log.on = switch.on;
+                        log.since = Some(now);
+                        info!(load = %managed.id, on = switch.on, "Controllable load switched");
+                    }
+                    Err(e) => warn!(
+                        load = %managed.id,
+                        on = switch.on,
+                        error = %e,
+                        "Failed to switch controllable load"
+                    ),
+                }
+            })
+        });
+        join_all(commands).await;
+    }
+
+    /// Switch every load off
+    pub async fn stop_all(&self, now: DateTime<Utc>) {
+        let switches: Vec<LoadSwitch> = self
+            .loads
+            .iter()
+            .map(|l| LoadSwitch {
+                id: l.id.clone(),
+                on: false,
+            })
+            .collect();
+        debug!("Switching off all controllable loads");
+        self.apply(&switches, now).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn constraints(start: (u32, u32), finish: (u32, u32)) -> LoadConstraints {
+        LoadConstraints {
+            runtime_minutes_per_day: 120,
+            earliest_start: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+            latest_finish: NaiveTime::from_hms_opt(finish.0, finish.1, 0).unwrap(),
+            min_on_minutes: 30,
+            min_off_minutes: 30,
+            power_kw: 1.5,
+            phase: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_overnight_window_ends_next_day() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap();
+
+        let windows =
+            constraints((22, 0), (6, 0)).windows(now, now + Duration::hours(24), chrono_tz::UTC);
+
+        assert_eq!(
+            windows,
+            vec![(
+                Utc.with_ymd_and_hms(2024, 1, 10, 22, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 1, 11, 6, 0, 0).unwrap()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_minimum_run_not_cut_by_schedule() {
+        let managed = ManagedLoad::new(
+            "pump".to_string(),
+            Arc::new(SimulatedControllableLoad::new(1.5)),
+            constraints((0, 0), (0, 0)),
+        );
+        let group = ControllableLoadGroup::new(vec![managed], chrono_tz::UTC).unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap();
+        let load = group.load("pump").unwrap();
+        load.set_schedule(LoadSchedule {
+            generated_at: start,
+            runs: vec![LoadRun {
+                start,
+                end: start + Duration::minutes(15),
+            }],
+            cost_sek: 0.0,
+            shortfall_minutes: 0,
+        })
+        .await;
+
+        let inputs = group.inputs(start).await;
+        assert!(inputs[0].requested && !inputs[0].running);
+        group
+            .apply(
+                &[LoadSwitch {
+                    id: "pump".to_string(),
+                    on: true,
+                }],
+                start,
+            )
+            .await;
+
+        // The schedule ends after 15 minutes, the 30 minute run does not
+        let inputs = group.inputs(start + Duration::minutes(20)).await;
+        assert!(inputs[0].requested && inputs[0].running);
+        let inputs = group.inputs(start + Duration::minutes(30)).await;
+        assert!(!inputs[0].requested);
+        assert_eq!(
+            load.runtime_done_minutes(start + Duration::minutes(30))
+                .await,
+            30
+        );
+    }
+}
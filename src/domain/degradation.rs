@@ -0,0 +1,687 @@
+//! Battery degradation
+//!
+//! Capacity fade is split into cycle aging, driven by depth of discharge,
+//! C-rate and temperature, and calendar aging, driven by the time spent at
+//! a given SoC and temperature. Each chemistry has its own curve. The
+//! optimizers price wear with a linearized version of the model, and the
+//! controller counts the cycles actually run with rainflow counting to keep
+//! an estimate of the pack's health.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::BatteryChemistry;
+
+/// Temperature the curves are specified at (°C)
+const REFERENCE_TEMPERATURE_C: f64 = 25.0;
+/// Below this temperature charging plates lithium and cycling wears faster (°C)
+const COLD_CYCLING_THRESHOLD_C: f64 = 10.0;
+/// Universal gas constant (J/(mol·K))
+const GAS_CONSTANT: f64 = 8.314;
+/// Calendar aging is specified at this SoC (%)
+const REFERENCE_SOC_PERCENT: f64 = 50.0;
+/// Smallest power counted as the battery being in use, as a C-rate
+const ACTIVE_C_RATE: f64 = 0.01;
+
+/// Capacity fade in percentage points of health
+pub trait DegradationModel: Send + Sync {
+    /// Health lost over one full cycle of `depth` (0-1) at `c_rate` and
+    /// `temperature_c`
+    fn cycle_fade_percent(&self, depth: f64, c_rate: f64, temperature_c: f64) -> f64;
+
+    /// Health lost resting for `hours` at `soc_percent` and `temperature_c`
+    fn calendar_fade_percent(&self, hours: f64, soc_percent: f64, temperature_c: f64) -> f64;
+
+    /// C-rate up to which cycling wears at the rated pace
+    fn reference_c_rate(&self) -> f64 {
+        0.5
+    }
+
+    /// Health at which the pack has reached the end of its life
+    fn end_of_life_health_percent(&self) -> f64 {
+        80.0
+    }
+
+    /// Wear costs the optimizers can use, for a pack that costs
+    /// `replacement_cost_sek` when it reaches the end of its life
+    ///
+    /// Throughput is priced as full-depth cycles, which bounds the cost of
+    /// shallower ones from above. Power above the reference C-rate pays the
+    /// secant of the model's extra wear up to `max_power_kw`, and calendar
+    /// aging adds a cost per SoC percent held.
+    fn linearized(
+        &self,
+        capacity_kwh: f64,
+        max_power_kw: f64,
+        replacement_cost_sek: f64,
+        temperature_c: f64,
+    ) -> LinearizedWear {
+        let capacity_kwh = capacity_kwh.max(0.1);
+        let sek_per_percent =
+            replacement_cost_sek.max(0.0) / (100.0 - self.end_of_life_health_percent()).max(1.0);
+        // A full cycle moves the capacity in and out again
+        let sek_per_kwh_at = |c_rate: f64| {
+            self.cycle_fade_percent(1.0, c_rate, temperature_c) * sek_per_percent
+                / (2.0 * capacity_kwh)
+        };
+
+        let threshold_kw = self.reference_c_rate() * capacity_kwh;
+        let throughput_sek_per_kwh = sek_per_kwh_at(self.reference_c_rate());
+        let high_power_sek_per_kwh = if max_power_kw > threshold_kw {
+            let max_cost_per_h = max_power_kw * sek_per_kwh_at(max_power_kw / capacity_kwh);
+            let threshold_cost_per_h = threshold_kw * throughput_sek_per_kwh;
+            ((max_cost_per_h - threshold_cost_per_h) / (max_power_kw - threshold_kw)
+                - throughput_sek_per_kwh)
+                .max(0.0)
+        } else {
+            0.0
+        };
+
+        let soc_fade_per_percent_hour = (self.calendar_fade_percent(1.0, 100.0, temperature_c)
+            - self.calendar_fade_percent(1.0, 0.0, temperature_c))
+            / 100.0;
+
+        LinearizedWear {
+            throughput_sek_per_kwh,
+            high_power_threshold_kw: threshold_kw,
+            high_power_sek_per_kwh,
+            soc_sek_per_percent_hour: soc_fade_per_percent_hour * sek_per_percent,
+        }
+    }
+}
+
+/// Degradation model of a chemistry
+pub fn degradation_model(chemistry: BatteryChemistry) -> Arc<dyn DegradationModel> {
+    Arc::new(DegradationCurve::for_chemistry(chemistry))
+}
+
+/// Empirical aging curve of a cell chemistry
+///
+/// Cycle life follows a Wöhler curve `N(d) = N(1) * d^-k` in the depth of
+/// discharge, is shortened linearly above the reference C-rate and follows
+/// Arrhenius in temperature, with extra stress when cycling in the cold.
+/// Calendar aging is linear in time and in the SoC held.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DegradationCurve {
+    /// Full-depth cycles at the reference C-rate and 25 °C until end of life
+    pub cycle_life_full_dod: f64,
+    /// Wöhler exponent, above 1 when shallow cycles are gentler per kWh
+    pub dod_exponent: f64,
+    /// C-rate the cycle life is rated at
+    pub reference_c_rate: f64,
+    /// Extra cycle wear per C above the reference C-rate
+    pub c_rate_stress: f64,
+    /// Calendar fade at 25 °C and 50 % SoC (percentage points per year)
+    pub calendar_fade_percent_per_year: f64,
+    /// Change in calendar fade from 50 % to 100 % SoC, negative where low SoC ages faster
+    pub calendar_soc_stress: f64,
+    /// Arrhenius activation energy (J/mol)
+    pub activation_energy_j_per_mol: f64,
+    /// Extra cycle wear per 10 °C below 10 °C
+    pub cold_cycling_stress: f64,
+    /// Health at which the pack is replaced (%)
+    pub end_of_life_health_percent: f64,
+}
+
+impl DegradationCurve {
+    /// Typical curve of a home battery of `chemistry`
+    ///
+    /// Unknown chemistries get the NMC curve, which is on the cautious side
+    /// for most home batteries.
+    pub fn for_chemistry(chemistry: BatteryChemistry) -> Self {
+        let nmc = Self {
+            cycle_life_full_dod: 3000.0,
+            dod_exponent: 1.5,
+            reference_c_rate: 0.5,
+            c_rate_stress: 0.6,
+            calendar_fade_percent_per_year: 2.5,
+            calendar_soc_stress: 1.0,
+            activation_energy_j_per_mol: 30_000.0,
+            cold_cycling_stress: 2.0,
+            end_of_life_health_percent: 80.0,
+        };
+        match chemistry {
+            BatteryChemistry::LiFePO4 => Self {
+                cycle_life_full_dod: 6000.0,
+                dod_exponent: 1.2,
+                c_rate_stress: 0.3,
+                calendar_fade_percent_per_year: 1.5,
+                calendar_soc_stress: 0.5,
+                activation_energy_j_per_mol: 31_500.0,
+                cold_cycling_stress: 1.0,
+                ..nmc
+            },
+            BatteryChemistry::NMC | BatteryChemistry::Unknown => nmc,
+            BatteryChemistry::NCA => Self {
+                cycle_life_full_dod: 2500.0,
+                dod_exponent: 1.6,
+                c_rate_stress: 0.7,
+                calendar_fade_percent_per_year: 3.0,
+                calendar_soc_stress: 1.2,
+                ..nmc
+            },
+            BatteryChemistry::LTO => Self {
+                cycle_life_full_dod: 15_000.0,
+                dod_exponent: 1.0,
+                reference_c_rate: 2.0,
+                c_rate_stress: 0.1,
+                calendar_fade_percent_per_year: 1.0,
+                calendar_soc_stress: 0.2,
+                activation_energy_j_per_mol: 20_000.0,
+                cold_cycling_stress: 0.2,
+                ..nmc
+            },
+            BatteryChemistry::LeadAcid => Self {
+                cycle_life_full_dod: 500.0,
+                dod_exponent: 1.3,
+                reference_c_rate: 0.1,
+                c_rate_stress: 1.0,
+                calendar_fade_percent_per_year: 5.0,
+                // Sulfation: lead-acid ages faster when kept low
+                calendar_soc_stress: -0.5,
+                activation_energy_j_per_mol: 40_000.0,
+                cold_cycling_stress: 0.0,
+                ..nmc
+            },
+        }
+    }
+
+    /// Arrhenius acceleration relative to 25 °C
+    fn arrhenius(&self, temperature_c: f64) -> f64 {
+        let kelvin = |c: f64| c + 273.15;
+        (self.activation_energy_j_per_mol / GAS_CONSTANT
+            * (1.0 / kelvin(REFERENCE_TEMPERATURE_C) - 1.0 / kelvin(temperature_c)))
+        .exp()
+    }
+}
+
+impl DegradationModel for DegradationCurve {
+    fn cycle_fade_percent(&self, depth: f64, c_rate: f64, temperature_c: f64) -> f64 {
+        let depth = depth.clamp(0.0, 1.0);
+        if depth == 0.0 {
+            return 0.0;
+        }
+        let fade_budget = 100.0 - self.end_of_life_health_percent;
+        let dod = depth.powf(self.dod_exponent);
+        let c_rate = 1.0 + self.c_rate_stress * (c_rate.abs() - self.reference_c_rate).max(0.0);
+        // Heat speeds up cycle aging, cold cycling plates lithium
+        let temperature = self.arrhenius(temperature_c).max(1.0)
+            * (1.0
+                + self.cold_cycling_stress
+                    * ((COLD_CYCLING_THRESHOLD_C - temperature_c) / 10.0).max(0.0));
+        fade_budget / self.cycle_life_full_dod.max(1.0) * dod * c_rate * temperature
+    }
+
+    fn calendar_fade_percent(&self, hours: f64, soc_percent: f64, temperature_c: f64) -> f64 {
+        let soc = 1.0
+            + self.calendar_soc_stress * (soc_percent.clamp(0.0, 100.0) - REFERENCE_SOC_PERCENT)
+                / REFERENCE_SOC_PERCENT;
+        self.calendar_fade_percent_per_year / (365.0 * 24.0)
+            * hours.max(0.0)
+            * soc.max(0.0)
+            * self.arrhenius(temperature_c)
+    }
+
+    fn reference_c_rate(&self) -> f64 {
+        self.reference_c_rate
+    }
+
+    fn end_of_life_health_percent(&self) -> f64 {
+        self.end_of_life_health_percent
+    }
+}
+
+/// Wear cost linear in the optimizers' decision variables
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LinearizedWear {
+    /// Cost of each kWh charged or discharged (SEK/kWh)
+    pub throughput_sek_per_kwh: f64,
+    /// Battery power above which the high-power cost applies (kW)
+    pub high_power_threshold_kw: f64,
+    /// Extra cost of each kWh moved above the threshold (SEK/kWh)
+    pub high_power_sek_per_kwh: f64,
+    /// Calendar aging cost of each SoC percent held for an hour (SEK)
+    pub soc_sek_per_percent_hour: f64,
+}
+
+impl LinearizedWear {
+    /// A flat cost per kWh of throughput
+    pub fn flat(throughput_sek_per_kwh: f64) -> Self {
+        Self {
+            throughput_sek_per_kwh,
+            high_power_threshold_kw: f64::INFINITY,
+            high_power_sek_per_kwh: 0.0,
+            soc_sek_per_percent_hour: 0.0,
+        }
+    }
+
+    /// Wear cost of running the battery at `battery_kw` for `dt_h` hours
+    /// from `soc_percent` (SEK)
+    pub fn cost_sek(&self, battery_kw: f64, dt_h: f64, soc_percent: f64) -> f64 {
+        let kw = battery_kw.abs();
+        (self.throughput_sek_per_kwh * kw
+            + self.high_power_sek_per_kwh * (kw - self.high_power_threshold_kw).max(0.0)
+            + self.soc_sek_per_percent_hour * soc_percent)
+            * dt_h
+    }
+}
+
+/// A cycle found by rainflow counting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RainflowCycle {
+    /// Depth of discharge (0-1)
+    pub depth: f64,
+    /// SoC the cycle swings around (%)
+    pub mean_soc_percent: f64,
+    /// 1.0 for a full cycle, 0.5 for a half cycle
+    pub count: f64,
+}
+
+/// Cycles in a SoC series (%) by rainflow counting (ASTM E1049)
+pub fn rainflow(soc_percent: &[f64]) -> Vec<RainflowCycle> {
+    let cycle = |from: f64, to: f64, count: f64| RainflowCycle {
+        depth: (from - to).abs() / 100.0,
+        mean_soc_percent: (from + to) / 2.0,
+        count,
+    };
+
+    let mut cycles = Vec::new();
+    let mut stack: Vec<f64> = Vec::new();
+    for point in reversals(soc_percent) {
+        stack.push(point);
+        while stack.len() >= 3 {
+            let n = stack.len();
+            let x = (stack[n - 1] - stack[n - 2]).abs();
+            let y = (stack[n - 2] - stack[n - 3]).abs();
+            if x < y {
+                break;
+            }
+            if n == 3 {
+                // The range starts the series, so it only closes half a cycle
+                cycles.push(cycle(stack[0], stack[1], 0.5));
+                stack.remove(0);
+            } else {
+                cycles.push(cycle(stack[n - 3], stack[n - 2], 1.0));
+                stack.drain(n - 3..n - 1);
+            }
+        }
+    }
+    cycles.extend(stack.windows(2).map(|pair| cycle(pair[0], pair[1], 0.5)));
+    cycles
+}
+
+/// Turning points of a series, with its first and last value
+fn reversals(series: &[f64]) -> Vec<f64> {
+    let mut points: Vec<f64> = Vec::new();
+    for &value in series.iter().filter(|v| v.is_finite()) {
+        match points.as_slice() {
+            [.., last] if *last == value => {}
+            [.., before, last] if (last - before).signum() == (value - last).signum() => {
+                *points.last_mut().unwrap() = value;
+            }
+            _ => points.push(value),
+        }
+    }
+    points
+}
+
+/// A day of battery use, as stored in the `battery_cycles` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyCycles {
+    pub date: NaiveDate,
+    pub equivalent_full_cycles: f64,
+    /// Count-weighted mean depth of the day's cycles, `None` without any
+    pub avg_depth_of_discharge: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+    pub total_energy_throughput_kwh: f64,
+    /// Health lost over the day (percentage points)
+    pub fade_percent: f64,
+}
+
+impl DailyCycles {
+    /// A day read back from `battery_cycles`, its fade replayed with `model`
+    ///
+    /// The table keeps neither the fade nor the mean SoC and temperature, so
+    /// the equivalent full cycles count as cycles of the day's mean depth at
+    /// the reference C-rate and temperature, plus a day of calendar aging at
+    /// the reference SoC.
+    pub fn replayed(
+        date: NaiveDate,
+        equivalent_full_cycles: f64,
+        avg_depth_of_discharge: Option<f64>,
+        max_temperature_c: Option<f64>,
+        total_energy_throughput_kwh: f64,
+        model: &dyn DegradationModel,
+    ) -> Self {
+        let cycle_fade_percent = match avg_depth_of_discharge {
+            Some(depth) if depth > 0.0 => {
+                equivalent_full_cycles.max(0.0) / depth
+                    * model.cycle_fade_percent(
+                        depth,
+                        model.reference_c_rate(),
+                        REFERENCE_TEMPERATURE_C,
+                    )
+            }
+            _ => 0.0,
+        };
+        let calendar_fade_percent =
+            model.calendar_fade_percent(24.0, REFERENCE_SOC_PERCENT, REFERENCE_TEMPERATURE_C);
+
+        Self {
+            date,
+            equivalent_full_cycles,
+            avg_depth_of_discharge,
+            max_temperature_c,
+            total_energy_throughput_kwh,
+            fade_percent: cycle_fade_percent + calendar_fade_percent,
+        }
+    }
+}
+
+/// Counts the cycles the battery runs and estimates its health from them
+///
+/// Days run from midnight to midnight in `timezone`.
+pub struct CycleTracker {
+    model: Arc<dyn DegradationModel>,
+    capacity_kwh: f64,
+    timezone: Tz,
+    health_percent: Option<f64>,
+    day: Option<NaiveDate>,
+    soc_series: Vec<f64>,
+    throughput_kwh: f64,
+    active_hours: f64,
+    hours: f64,
+    soc_percent_hours: f64,
+    temperature_c_hours: f64,
+    max_temperature_c: Option<f64>,
+}
+
+impl CycleTracker {
+    pub fn new(model: Arc<dyn DegradationModel>, capacity_kwh: f64, timezone: Tz) -> Self {
+        Self {
+            model,
+            capacity_kwh: capacity_kwh.max(0.1),
+            timezone,
+            health_percent: None,
+            day: None,
+            soc_series: Vec::new(),
+            throughput_kwh: 0.0,
+            active_hours: 0.0,
+            hours: 0.0,
+            soc_percent_hours: 0.0,
+            temperature_c_hours: 0.0,
+            max_temperature_c: None,
+        }
+    }
+
+    /// Estimated health, starting from the first reported one, `None` before
+    /// any sample
+    pub fn health_percent(&self) -> Option<f64> {
+        self.health_percent
+    }
+
+    /// Continue from the days recorded before a restart
+    ///
+    /// The estimate starts from a new pack less their fade instead of from
+    /// the health the BMS reports.
+    pub fn resume(&mut self, days: &[DailyCycles]) {
+        if days.is_empty() {
+            return;
+        }
+        let fade_percent: f64 = days.iter().map(|day| day.fade_percent).sum();
+        self.health_percent = Some((100.0 - fade_percent).max(0.0));
+    }
+
+    /// Add a sample covering the `dt_h` hours up to `now`
+    ///
+    /// Returns the previous day once a sample falls on a new local day, and
+    /// takes its fade off the health estimate.
+    pub fn record(
+        &mut self,
+        now: DateTime<Utc>,
+        dt_h: f64,
+        state: &super::BatteryState,
+    ) -> Option<DailyCycles> {
+        let date = now.with_timezone(&self.timezone).date_naive();
+        self.health_percent.get_or_insert(state.health_percent);
+
+        let closed = match self.day {
+            Some(day) if day != date => {
+                let closed = self.close_day(day);
+                if let Some(health) = self.health_percent.as_mut() {
+                    *health = (*health - closed.fade_percent).max(0.0);
+                }
+                Some(closed)
+            }
+            _ => None,
+        };
+        self.day = Some(date);
+
+        if state.soc_percent.is_finite() {
+            self.soc_series.push(state.soc_percent);
+        }
+        let dt_h = dt_h.max(0.0);
+        let kw = state.power_w.abs() / 1000.0;
+        if kw.is_finite() {
+            self.throughput_kwh += kw * dt_h;
+            if kw / self.capacity_kwh >= ACTIVE_C_RATE {
+                self.active_hours += dt_h;
+            }
+        }
+        if state.soc_percent.is_finite() && state.temperature_c.is_finite() {
+            self.hours += dt_h;
+            self.soc_percent_hours += state.soc_percent * dt_h;
+            self.temperature_c_hours += state.temperature_c * dt_h;
+            self.max_temperature_c = Some(
+                self.max_temperature_c
+                    .map_or(state.temperature_c, |t| t.max(state.temperature_c)),
+            );
+        }
+        closed
+    }
+
+    fn close_day(&mut self, date: NaiveDate) -> DailyCycles {
+        let cycles = rainflow(&self.soc_series);
+        let (mean_soc, mean_temperature) = if self.hours > 0.0 {
+            (
+                self.soc_percent_hours / self.hours,
+                self.temperature_c_hours / self.hours,
+            )
+        } else {
+            (REFERENCE_SOC_PERCENT, REFERENCE_TEMPERATURE_C)
+        };
+        let c_rate = if self.active_hours > 0.0 {
+            self.throughput_kwh / self.active_hours / self.capacity_kwh
+        } else {
+            0.0
+        };
+
+        let count: f64 = cycles.iter().map(|c| c.count).sum();
+        let fade_percent = cycles
+            .iter()
+            .map(|c| {
+                c.count
+                    * self
+                        .model
+                        .cycle_fade_percent(c.depth, c_rate, mean_temperature)
+            })
+            .sum::<f64>()
+            + self
+                .model
+                .calendar_fade_percent(self.hours, mean_soc, mean_temperature);
+        let day = DailyCycles {
+            date,
+            equivalent_full_cycles: self.throughput_kwh / (2.0 * self.capacity_kwh),
+            avg_depth_of_discharge: (count > 0.0)
+                .then(|| cycles.iter().map(|c| c.count * c.depth).sum::<f64>() / count),
+            max_temperature_c: self.max_temperature_c,
+            total_energy_throughput_kwh: self.throughput_kwh,
+            fade_percent,
+        };
+
+        // The day's last SoC starts the next day's series
+        let last_soc = self.soc_series.last().copied();
+        self.soc_series.clear();
+        self.soc_series.extend(last_soc);
+        self.throughput_kwh = 0.0;
+        self.active_hours = 0.0;
+        self.hours = 0.0;
+        self.soc_percent_hours = 0.0;
+        self.temperature_c_hours = 0.0;
+        self.max_temperature_c = None;
+        day
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{BatteryState, BatteryStatus};
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_rainflow_counts_nested_cycle() {
+        // A 20 % swing inside a full 80 % one
+        let cycles = rainflow(&[10.0, 90.0, 50.0, 70.0, 10.0]);
+
+        let full: Vec<_> = cycles.iter().filter(|c| c.count == 1.0).collect();
+        assert_eq!(full.len(), 1);
+        assert!((full[0].depth - 0.2).abs() < 1e-9);
+        assert!((full[0].mean_soc_percent - 60.0).abs() < 1e-9);
+
+        let half_depths: Vec<f64> = cycles
+            .iter()
+            .filter(|c| c.count == 0.5)
+            .map(|c| c.depth)
+            .collect();
+        assert_eq!(half_depths.len(), 2);
+        assert!(half_depths.iter().all(|d| (d - 0.8).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_rainflow_ignores_monotonic_steps() {
+        let cycles = rainflow(&[20.0, 30.0, 40.0, 40.0, 60.0, 50.0, 30.0]);
+        assert_eq!(cycles.len(), 2);
+        assert!((cycles[0].depth - 0.4).abs() < 1e-9);
+        assert!((cycles[1].depth - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_curves_rank_chemistries_and_stresses() {
+        let lfp = DegradationCurve::for_chemistry(BatteryChemistry::LiFePO4);
+        let nmc = DegradationCurve::for_chemistry(BatteryChemistry::NMC);
+        let fade = |c: &DegradationCurve| c.cycle_fade_percent(1.0, 0.5, 25.0);
+        assert!(fade(&lfp) < fade(&nmc));
+
+        // Shallow cycles wear less per kWh moved
+        assert!(nmc.cycle_fade_percent(0.2, 0.5, 25.0) * 5.0 < fade(&nmc));
+        // High C-rates, heat and cold all add wear
+        assert!(nmc.cycle_fade_percent(1.0, 1.5, 25.0) > fade(&nmc));
+        assert!(nmc.cycle_fade_percent(1.0, 0.5, 40.0) > fade(&nmc));
+        assert!(nmc.cycle_fade_percent(1.0, 0.5, -5.0) > fade(&nmc));
+
+        // Lithium cells age faster full, lead-acid faster empty
+        assert!(
+            nmc.calendar_fade_percent(24.0, 90.0, 25.0)
+                > nmc.calendar_fade_percent(24.0, 30.0, 25.0)
+        );
+        let lead = DegradationCurve::for_chemistry(BatteryChemistry::LeadAcid);
+        assert!(
+            lead.calendar_fade_percent(24.0, 90.0, 25.0)
+                < lead.calendar_fade_percent(24.0, 30.0, 25.0)
+        );
+    }
+
+    #[test]
+    fn test_linearized_wear_bounds_the_model() {
+        let lfp = DegradationCurve::for_chemistry(BatteryChemistry::LiFePO4);
+        let wear = lfp.linearized(10.0, 10.0, 50_000.0, 25.0);
+
+        // 6000 full cycles of 20 kWh each wear out a 50 000 SEK pack
+        assert!((wear.throughput_sek_per_kwh - 50_000.0 / 6000.0 / 20.0).abs() < 1e-9);
+        assert_eq!(wear.high_power_threshold_kw, 5.0);
+        assert!(wear.high_power_sek_per_kwh > 0.0);
+        assert!(wear.soc_sek_per_percent_hour > 0.0);
+
+        // The secant matches the model at full power
+        let sek_per_percent = 50_000.0 / 20.0;
+        let model_at_max = lfp.cycle_fade_percent(1.0, 1.0, 25.0) * sek_per_percent / 20.0 * 10.0;
+        let linear_at_max = wear.cost_sek(10.0, 1.0, 0.0);
+        assert!((linear_at_max - model_at_max).abs() < 1e-9);
+        assert_eq!(wear.cost_sek(-4.0, 1.0, 0.0), wear.cost_sek(4.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_tracker_closes_day_and_lowers_health() {
+        let model = degradation_model(BatteryChemistry::LiFePO4);
+        let mut tracker = CycleTracker::new(model, 10.0, chrono_tz::UTC);
+        let start = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let state = |soc_percent: f64, power_w: f64| BatteryState {
+            soc_percent,
+            power_w,
+            voltage_v: 48.0,
+            temperature_c: 25.0,
+            health_percent: 98.0,
+            status: BatteryStatus::Idle,
+        };
+
+        // Charge 20 -> 90 % and back at 3.5 kW
+        let samples = [
+            (20.0, 3500.0),
+            (55.0, 3500.0),
+            (90.0, -3500.0),
+            (55.0, -3500.0),
+            (20.0, 0.0),
+        ];
+        for (hour, &(soc, power_w)) in samples.iter().enumerate() {
+            let now = start + Duration::hours(hour as i64);
+            assert!(tracker.record(now, 1.0, &state(soc, power_w)).is_none());
+        }
+
+        let day = tracker
+            .record(start + Duration::days(1), 1.0, &state(20.0, 0.0))
+            .expect("day closed");
+        assert_eq!(day.date, NaiveDate::from_ymd_opt(2025, 6, 1).unwrap());
+        assert!((day.total_energy_throughput_kwh - 14.0).abs() < 1e-9);
+        assert!((day.equivalent_full_cycles - 0.7).abs() < 1e-9);
+        assert!((day.avg_depth_of_discharge.unwrap() - 0.7).abs() < 1e-9);
+        assert_eq!(day.max_temperature_c, Some(25.0));
+        assert!(day.fade_percent > 0.0);
+        assert!((tracker.health_percent().unwrap() - (98.0 - day.fade_percent)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_tracker_resumes_from_stored_days() {
+        let model = degradation_model(BatteryChemistry::NMC);
+        let date = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        // 0.7 equivalent full cycles of depth 0.7 is one cycle
+        let cycled = DailyCycles::replayed(date, 0.7, Some(0.7), Some(30.0), 14.0, model.as_ref());
+        let next = date.succ_opt().unwrap();
+        let idle = DailyCycles::replayed(next, 0.0, None, None, 0.0, model.as_ref());
+        let calendar = model.calendar_fade_percent(24.0, 50.0, 25.0);
+        assert!((idle.fade_percent - calendar).abs() < 1e-12);
+        assert!(
+            (cycled.fade_percent - calendar - model.cycle_fade_percent(0.7, 0.5, 25.0)).abs()
+                < 1e-12
+        );
+
+        let mut tracker = CycleTracker::new(Arc::clone(&model), 10.0, chrono_tz::UTC);
+        tracker.resume(&[cycled.clone(), idle.clone()]);
+        let expected = 100.0 - cycled.fade_percent - idle.fade_percent;
+        assert!((tracker.health_percent().unwrap() - expected).abs() < 1e-12);
+
+        // The BMS reading does not reset the resumed estimate
+        let state = BatteryState {
+            soc_percent: 50.0,
+            power_w: 0.0,
+            voltage_v: 48.0,
+            temperature_c: 25.0,
+            health_percent: 100.0,
+            status: BatteryStatus::Idle,
+        };
+        tracker.record(Utc::now(), 1.0, &state);
+        assert!((tracker.health_percent().unwrap() - expected).abs() < 1e-12);
+    }
+}
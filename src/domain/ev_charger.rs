@@ -7,6 +7,8 @@ use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
 
+use super::schedule::ScheduleInterval;
+
 /// EV Charger-specific errors
 #[derive(Debug, Error)]
 pub enum ChargerError {
@@ -37,6 +39,15 @@ pub trait EvCharger: Send + Sync {
     async fn stop_charging(&self) -> Result<()>;
     fn capabilities(&self) -> ChargerCapabilities;
 
+    /// Hand the charger the optimizer's plan for the coming hours
+    ///
+    /// The controller still sets the current every tick; a charger that
+    /// keeps the plan can follow it when the controller is out of reach.
+    /// Discharge periods are sent as zero. Default: ignored.
+    async fn set_charging_plan(&self, _plan: &[ScheduleInterval]) -> Result<()> {
+        Ok(())
+    }
+
     // V2X (Vehicle-to-Grid/Home) capabilities
 
     /// Get V2X capabilities if supported
@@ -309,7 +320,7 @@ impl SimulatedEvCharger {
     }
 
     pub fn default_charger() -> Self {
-        let caps = ChargerCapabilities {
+        Self::with_capabilities(ChargerCapabilities {
             max_current_amps: 32.0,
             min_current_amps: 6.0,
             phases: 3,
@@ -317,7 +328,11 @@ impl SimulatedEvCharger {
             connector_type: ConnectorType::Type2,
             power_max_kw: 22.0, // 32A * 230V * 3 phases / 1000
             supports_v2g: false,
-        };
+        })
+    }
+
+    /// Idle charger with no vehicle plugged in
+    pub fn with_capabilities(caps: ChargerCapabilities) -> Self {
         let initial = ChargerState {
             status: ChargerStatus::Available,
             connected: false,
@@ -0,0 +1,368 @@
+#![allow(dead_code)]
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use super::ev_charger::{ChargerState, ChargerStatus, EvCharger};
+use super::schedule::Schedule;
+use crate::power_flow::{
+    inputs::{EvChargerInput, EvState},
+    EvAllocation, EvAllocationPolicy,
+};
+use crate::simulation::three_phase::{LoadDistribution, ThreePhasePower};
+
+/// What the controller knows about the vehicle behind a charger
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VehiclePlan {
+    pub capacity_kwh: f64,
+    pub target_soc_percent: f64,
+    /// One-off departure; overrides `daily_departure` until it has passed
+    pub departure_time: Option<DateTime<Utc>>,
+    /// Usual departure in the household's local time
+    #[cfg_attr(feature = "swagger", schema(value_type = Option<String>))]
+    pub daily_departure: Option<NaiveTime>,
+}
+
+impl VehiclePlan {
+    /// Next time the vehicle has to be ready
+    pub fn next_departure(&self, now: DateTime<Utc>, tz: chrono_tz::Tz) -> Option<DateTime<Utc>> {
+        if let Some(departure) = self.departure_time.filter(|d| *d > now) {
+            return Some(departure);
+        }
+
+        let daily = self.daily_departure?;
+        let local_now = now.with_timezone(&tz);
+        [0, 1].into_iter().find_map(|days| {
+            (local_now.date_naive() + Duration::days(days))
+                .and_time(daily)
+                .and_local_timezone(tz)
+                .earliest()
+                .map(|d| d.with_timezone(&Utc))
+                .filter(|d| *d > now)
+        })
+    }
+}
+
+/// One charger on a multi-charger site
+pub struct ChargePoint {
+    pub id: String,
+    pub charger: Arc<dyn EvCharger>,
+    pub priority: u8,
+    /// Site phase (1-3) a single-phase charger is wired to
+    pub phase: u8,
+    /// Rating of the charger's circuit, may be below the charger's own maximum
+    pub max_current_a: f64,
+    plan: RwLock<VehiclePlan>,
+    allocated_current_a: RwLock<f64>,
+}
+
+impl ChargePoint {
+    pub fn new(
+        id: String,
+        charger: Arc<dyn EvCharger>,
+        priority: u8,
+        phase: u8,
+        max_current_a: f64,
+        plan: VehiclePlan,
+    ) -> Self {
+        Self {
+            id,
+            charger,
+            priority,
+            phase,
+            max_current_a,
+            plan: RwLock::new(plan),
+            allocated_current_a: RwLock::new(0.0),
+        }
+    }
+
+    pub async fn plan(&self) -> VehiclePlan {
+        self.plan.read().await.clone()
+    }
+
+    pub async fn set_plan(&self, plan: VehiclePlan) {
+        *self.plan.write().await = plan;
+    }
+
+    /// Current granted by the last control tick
+    pub async fn allocated_current_a(&self) -> f64 {
+        *self.allocated_current_a.read().await
+    }
+
+    /// How the charger's power lands on the site's phases
+    pub fn load_distribution(&self) -> LoadDistribution {
+        if self.charger.capabilities().phases >= 3 {
+            LoadDistribution::Balanced
+        } else {
+            LoadDistribution::SinglePhase(self.phase as usize)
+        }
+    }
+
+    /// Power-flow view of this charger, `None` if it cannot take current
+    ///
+    /// Without a SoC report the vehicle is assumed empty, so it keeps
+    /// asking for energy until it stops drawing on its own.
+    fn input(
+        &self,
+        state: &ChargerState,
+        plan: &VehiclePlan,
+        departure: Option<DateTime<Utc>>,
+    ) -> Option<EvChargerInput> {
+        if matches!(
+            state.status,
+            ChargerStatus::Faulted | ChargerStatus::Unavailable
+        ) {
+            return None;
+        }
+
+        let caps = self.charger.capabilities();
+        let phases = if caps.phases >= 3 { 3 } else { 1 };
+        let max_discharge_kw = self
+            .charger
+            .v2x_capabilities()
+            .map(|v2x| v2x.max_discharge_power_w / 1000.0)
+            .unwrap_or(0.0);
+
+        Some(EvChargerInput {
+            id: self.id.clone(),
+            ev: EvState {
+                connected: state.connected,
+                soc_percent: state.vehicle_soc_percent.unwrap_or(0.0).clamp(0.0, 100.0),
+                capacity_kwh: plan.capacity_kwh,
+                max_charge_kw: caps.power_max_kw,
+                max_discharge_kw,
+                departure_time: departure,
+                target_soc_percent: plan.target_soc_percent,
+            },
+            phases,
+            phase: self.phase,
+            min_current_a: caps.min_current_amps,
+            max_current_a: caps.max_current_amps.min(self.max_current_a),
+            priority: self.priority,
+            scheduled_power_kw: None,
+        })
+    }
+}
+
+/// Chargers sharing the site's main fuse
+///
+/// The power flow model decides how much current each charger gets; the
+/// group turns its chargers' readings into model inputs and applies the
+/// resulting allocation.
+pub struct EvChargerGroup {
+    chargers: Vec<ChargePoint>,
+    policy: EvAllocationPolicy,
+    timezone: chrono_tz::Tz,
+}
+
+impl EvChargerGroup {
+    pub fn new(
+        chargers: Vec<ChargePoint>,
+        policy: EvAllocationPolicy,
+        timezone: chrono_tz::Tz,
+    ) -> Result<Self> {
+        if chargers.is_empty() {
+            bail!("EV charger group needs at least one charger");
+        }
+        for (i, charger) in chargers.iter().enumerate() {
+            if chargers[..i].iter().any(|c| c.id == charger.id) {
+                bail!("Duplicate EV charger id '{}'", charger.id);
+            }
+        }
+        Ok(Self {
+            chargers,
+            policy,
+            timezone,
+        })
+    }
+
+    pub fn chargers(&self) -> &[ChargePoint] {
+        &self.chargers
+    }
+
+    pub fn charger(&self, id: &str) -> Option<&ChargePoint> {
+        self.chargers.iter().find(|c| c.id == id)
+    }
+
+    pub fn policy(&self) -> EvAllocationPolicy {
+        self.policy
+    }
+
+    pub fn timezone(&self) -> chrono_tz::Tz {
+        self.timezone
+    }
+
+    /// Read every charger concurrently, in charger order
+    pub async fn read_states(&self) -> Vec<Result<ChargerState>> {
+        join_all(self.chargers.iter().map(|c| c.charger.read_state())).await
+    }
+
+    /// Power flow inputs for every charger that answered and can charge
+    pub async fn inputs(&self, now: DateTime<Utc>) -> Vec<EvChargerInput> {
+        let states = self.read_states().await;
+        let mut inputs = Vec::with_capacity(self.chargers.len());
+        for (charge_point, state) in self.chargers.iter().zip(states) {
+            match state {
+                Ok(state) => {
+                    let plan = charge_point.plan().await;
+                    let departure = plan.next_departure(now, self.timezone);
+                    inputs.extend(charge_point.input(&state, &plan, departure));
+                }
+                Err(e) => warn!(charger = %charge_point.id, error = %e, "EV charger read failed"),
+            }
+        }
+        inputs
+    }
+
+    /// Command each charger's allocated current; chargers without an
+    /// allocation are set to 0 A
+    pub async fn apply(&self, allocations: &[EvAllocation]) {
+        let commands = self.chargers.iter().map(|charge_point| async move {
+            let current_a = allocations
+                .iter()
+                .find(|a| a.id == charge_point.id)
+                .map(|a| a.current_a)
+                .unwrap_or(0.0);
+            match charge_point.charger.set_current(current_a).await {
+                Ok(()) => {
+                    *charge_point.allocated_current_a.write().await = current_a;
+                    debug!(charger = %charge_point.id, current_a, "EV charger current updated");
+                }
+                Err(e) => warn!(
+                    charger = %charge_point.id,
+                    current_a,
+                    error = %e,
+                    "Failed to set EV charger current"
+                ),
+            }
+        });
+        join_all(commands).await;
+    }
+
+    /// Send each charger its part of the schedule from `from` onwards
+    ///
+    /// Chargers the schedule has no plan for are left alone.
+    pub async fn push_plans(&self, schedule: &Schedule, from: DateTime<Utc>) {
+        let pushes = self.chargers.iter().map(|charge_point| async move {
+            let plan = schedule.ev_plan(&charge_point.id, from);
+            if plan.is_empty() {
+                return;
+            }
+            if let Err(e) = charge_point.charger.set_charging_plan(&plan).await {
+                warn!(charger = %charge_point.id, error = %e, "Failed to send EV charging plan");
+            }
+        });
+        join_all(pushes).await;
+    }
+
+    /// Pause every charger
+    pub async fn stop_all(&self) {
+        self.apply(&[]).await;
+    }
+
+    /// Combined charging power of all chargers per site phase (W)
+    pub async fn phase_power_w(&self) -> ThreePhasePower {
+        self.chargers
+            .iter()
+            .zip(self.read_states().await)
+            .filter_map(|(charge_point, state)| {
+                let state = state.ok()?;
+                Some(charge_point.load_distribution().distribute(state.power_w))
+            })
+            .fold(ThreePhasePower::default(), |sum, power| sum.add(&power))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::SimulatedEvCharger;
+    use chrono::TimeZone;
+
+    fn plan() -> VehiclePlan {
+        VehiclePlan {
+            capacity_kwh: 60.0,
+            target_soc_percent: 80.0,
+            departure_time: None,
+            daily_departure: None,
+        }
+    }
+
+    fn charge_point(id: &str) -> ChargePoint {
+        let mut charger = SimulatedEvCharger::default_charger();
+        charger.disable_watchdog();
+        ChargePoint::new(id.to_string(), Arc::new(charger), 0, 1, 16.0, plan())
+    }
+
+    #[test]
+    fn test_duplicate_charger_ids_rejected() {
+        let result = EvChargerGroup::new(
+            vec![charge_point("drive"), charge_point("drive")],
+            EvAllocationPolicy::FairShare,
+            chrono_tz::UTC,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_daily_departure_rolls_over_to_tomorrow() {
+        let mut plan = plan();
+        plan.daily_departure = NaiveTime::from_hms_opt(7, 30, 0);
+        let now = Utc.with_ymd_and_hms(2024, 1, 10, 20, 0, 0).unwrap();
+
+        let departure = plan
+            .next_departure(now, chrono_tz::Europe::Stockholm)
+            .unwrap();
+
+        // 07:30 CET is 06:30 UTC
+        assert_eq!(
+            departure,
+            Utc.with_ymd_and_hms(2024, 1, 11, 6, 30, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inputs_cap_current_at_circuit_rating() {
+        let group = EvChargerGroup::new(
+            vec![charge_point("drive")],
+            EvAllocationPolicy::FairShare,
+            chrono_tz::UTC,
+        )
+        .unwrap();
+
+        let inputs = group.inputs(Utc::now()).await;
+
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].phases, 3);
+        assert_eq!(inputs[0].max_current_a, 16.0);
+    }
+
+    #[tokio::test]
+    async fn test_apply_zeroes_chargers_without_allocation() {
+        let group = EvChargerGroup::new(
+            vec![charge_point("a"), charge_point("b")],
+            EvAllocationPolicy::FairShare,
+            chrono_tz::UTC,
+        )
+        .unwrap();
+
+        group
+            .apply(&[EvAllocation {
+                id: "a".to_string(),
+                current_a: 10.0,
+                power_kw: 6.9,
+            }])
+            .await;
+
+        assert_eq!(
+            group.charger("a").unwrap().allocated_current_a().await,
+            10.0
+        );
+        assert_eq!(group.charger("b").unwrap().allocated_current_a().await, 0.0);
+    }
+}
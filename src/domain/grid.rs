@@ -9,7 +9,8 @@ pub struct GridConnection {
     pub status: GridStatus,
     pub import_power_w: f64,
     pub export_power_w: f64,
-    pub frequency_hz: f64,
+    /// `None` when neither the grid meter nor the inverter measures it
+    pub frequency_hz: Option<f64>,
     pub voltage_v: f64,
     pub current_a: f64,
 }
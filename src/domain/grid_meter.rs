@@ -0,0 +1,251 @@
+#![allow(dead_code)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+use super::{GridConnection, GridStatus, HealthStatus};
+use crate::simulation::environment::Environment;
+use crate::simulation::three_phase::ThreePhasePower;
+
+/// Below this voltage on every phase the grid is considered gone
+const BLACKOUT_VOLTAGE_V: f64 = 50.0;
+
+/// Utility meter at the grid connection point
+#[async_trait]
+pub trait GridMeter: Send + Sync {
+    async fn read(&self) -> Result<GridMeterReading>;
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        Ok(match self.read().await {
+            Ok(_) => HealthStatus::Healthy,
+            Err(_) => HealthStatus::Offline,
+        })
+    }
+}
+
+/// Measurements on one phase
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseReading {
+    /// Phase-to-neutral voltage
+    pub voltage_v: f64,
+    /// Current magnitude
+    pub current_a: f64,
+    /// Active power, positive = import
+    pub power_w: f64,
+}
+
+/// One snapshot of the grid meter
+///
+/// Single-phase meters report L2/L3 as zero.
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridMeterReading {
+    /// L1, L2, L3
+    pub phases: [PhaseReading; 3],
+    /// Total active power, positive = import from grid
+    pub power_w: f64,
+    /// `None` when the meter does not measure frequency, as on HAN/P1 ports
+    pub frequency_hz: Option<f64>,
+    /// Lifetime import counter
+    pub import_energy_kwh: f64,
+    /// Lifetime export counter
+    pub export_energy_kwh: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl GridMeterReading {
+    pub fn import_power_w(&self) -> f64 {
+        self.power_w.max(0.0)
+    }
+
+    pub fn export_power_w(&self) -> f64 {
+        (-self.power_w).max(0.0)
+    }
+
+    /// Highest phase current, the one that trips the main fuse first
+    pub fn max_phase_current_a(&self) -> f64 {
+        self.phases.iter().map(|p| p.current_a).fold(0.0, f64::max)
+    }
+
+    pub fn phase_currents_a(&self) -> [f64; 3] {
+        self.phases.map(|p| p.current_a)
+    }
+
+    /// Active power per phase, positive = import
+    pub fn phase_power(&self) -> ThreePhasePower {
+        let [l1, l2, l3] = self.phases.map(|p| p.power_w);
+        ThreePhasePower::new(l1, l2, l3)
+    }
+
+    /// Mean voltage over the phases that are live
+    pub fn average_voltage_v(&self) -> f64 {
+        let live: Vec<f64> = self
+            .phases
+            .iter()
+            .map(|p| p.voltage_v)
+            .filter(|&v| v > BLACKOUT_VOLTAGE_V)
+            .collect();
+        if live.is_empty() {
+            0.0
+        } else {
+            live.iter().sum::<f64>() / live.len() as f64
+        }
+    }
+
+    /// Phase voltage furthest from `nominal_v`, for over/under-voltage checks
+    pub fn worst_voltage_v(&self, nominal_v: f64) -> f64 {
+        self.phases
+            .iter()
+            .map(|p| p.voltage_v)
+            .filter(|&v| v > BLACKOUT_VOLTAGE_V)
+            .max_by(|a, b| (a - nominal_v).abs().total_cmp(&(b - nominal_v).abs()))
+            .unwrap_or(0.0)
+    }
+
+    pub fn to_connection(&self) -> GridConnection {
+        let voltage_v = self.average_voltage_v();
+        GridConnection {
+            status: if voltage_v > 0.0 {
+                GridStatus::Normal
+            } else {
+                GridStatus::Blackout
+            },
+            import_power_w: self.import_power_w(),
+            export_power_w: self.export_power_w(),
+            frequency_hz: self.frequency_hz,
+            voltage_v,
+            current_a: self.max_phase_current_a(),
+        }
+    }
+}
+
+/// Grid meter reading the simulated grid connection of an `Environment`
+///
+/// Power is spread evenly over the three phases; energy counters integrate
+/// the simulated import/export over simulation time.
+pub struct SimulatedGridMeter {
+    environment: Arc<RwLock<Environment>>,
+    counters: Mutex<EnergyCounters>,
+}
+
+#[derive(Debug, Default)]
+struct EnergyCounters {
+    import_kwh: f64,
+    export_kwh: f64,
+    last_update: Option<NaiveDateTime>,
+}
+
+impl SimulatedGridMeter {
+    pub fn new(environment: Arc<RwLock<Environment>>) -> Self {
+        Self {
+            environment,
+            counters: Mutex::new(EnergyCounters::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl GridMeter for SimulatedGridMeter {
+    async fn read(&self) -> Result<GridMeterReading> {
+        let grid = self.environment.read().await.grid_state().clone();
+
+        let mut counters = self.counters.lock().await;
+        if let Some(last) = counters.last_update {
+            let hours = (grid.timestamp - last).num_milliseconds().max(0) as f64 / 3_600_000.0;
+            counters.import_kwh += grid.import_kw * hours;
+            counters.export_kwh += grid.export_kw * hours;
+        }
+        counters.last_update = Some(grid.timestamp);
+
+        let (voltage_v, frequency_hz) = if grid.is_available {
+            (grid.voltage_v, grid.frequency_hz)
+        } else {
+            (0.0, 0.0)
+        };
+        let power_w = if grid.is_available {
+            (grid.import_kw - grid.export_kw) * 1000.0
+        } else {
+            0.0
+        };
+        let phase_power_w = power_w / 3.0;
+        let phase = PhaseReading {
+            voltage_v,
+            current_a: if voltage_v > 0.0 {
+                phase_power_w.abs() / voltage_v
+            } else {
+                0.0
+            },
+            power_w: phase_power_w,
+        };
+
+        Ok(GridMeterReading {
+            phases: [phase; 3],
+            power_w,
+            frequency_hz: Some(frequency_hz),
+            import_energy_kwh: counters.import_kwh,
+            export_energy_kwh: counters.export_kwh,
+            timestamp: Utc::now(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::environment::EnvironmentConfig;
+
+    fn reading(phases: [(f64, f64); 3]) -> GridMeterReading {
+        GridMeterReading {
+            phases: phases.map(|(voltage_v, current_a)| PhaseReading {
+                voltage_v,
+                current_a,
+                power_w: voltage_v * current_a,
+            }),
+            power_w: phases.iter().map(|(v, a)| v * a).sum(),
+            frequency_hz: Some(50.0),
+            import_energy_kwh: 0.0,
+            export_energy_kwh: 0.0,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_unbalanced_phases_expose_worst_case() {
+        // 4.6 kW total looks harmless, but L1 alone carries 20 A
+        let r = reading([(228.0, 20.0), (236.0, 0.0), (231.0, 0.0)]);
+        assert_eq!(r.max_phase_current_a(), 20.0);
+        assert_eq!(r.worst_voltage_v(230.0), 236.0);
+
+        let connection = r.to_connection();
+        assert_eq!(connection.status, GridStatus::Normal);
+        assert_eq!(connection.current_a, 20.0);
+        assert_eq!(connection.export_power_w, 0.0);
+    }
+
+    #[test]
+    fn test_dead_phases_read_as_blackout() {
+        let r = reading([(0.0, 0.0), (0.0, 0.0), (0.0, 0.0)]);
+        assert_eq!(r.to_connection().status, GridStatus::Blackout);
+    }
+
+    #[tokio::test]
+    async fn test_simulated_meter_integrates_energy() {
+        let mut config = EnvironmentConfig::default().with_random_seed(7);
+        config.grid.enable_faults = false;
+        let env = Arc::new(RwLock::new(Environment::new(config)));
+        let meter = SimulatedGridMeter::new(Arc::clone(&env));
+
+        meter.read().await.unwrap();
+        env.write().await.tick(chrono::Duration::minutes(30), 2.0, 0.0);
+        let r = meter.read().await.unwrap();
+
+        assert!((r.power_w - 2000.0).abs() < 1e-9);
+        assert!((r.import_energy_kwh - 1.0).abs() < 1e-9);
+        assert_eq!(r.export_energy_kwh, 0.0);
+        assert!((r.phases[0].power_w - 2000.0 / 3.0).abs() < 1e-9);
+    }
+}
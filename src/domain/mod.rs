@@ -1,14 +1,32 @@
+pub mod aggregate_battery;
 pub mod battery;
+pub mod controllable_load;
+pub mod degradation;
 pub mod ev_charger;
+pub mod ev_charger_group;
 pub mod forecast;
 pub mod grid;
+pub mod grid_meter;
 pub mod inverter;
+pub mod peak_ledger;
+pub mod reserve;
+pub mod resolution;
 pub mod schedule;
+pub mod thermal_load;
 pub mod types;
 
+pub use aggregate_battery::*;
 pub use battery::*;
+pub use controllable_load::*;
+pub use degradation::*;
 pub use ev_charger::*;
+pub use ev_charger_group::*;
 pub use grid::*;
+pub use grid_meter::*;
 pub use inverter::*;
+pub use peak_ledger::*;
+pub use reserve::*;
+pub use resolution::*;
 pub use schedule::*;
+pub use thermal_load::*;
 pub use types::*;
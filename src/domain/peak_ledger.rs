@@ -0,0 +1,390 @@
+#![allow(dead_code)]
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveDate, Timelike, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Rules deciding which hours count towards a peak-power tariff ("effekttariff")
+///
+/// The defaults bill the single highest hour of the month, around the
+/// clock. Ellevio-style tariffs average the top three hours, at most one per
+/// day, and may count night hours at a reduced weight.
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PeakTariffRules {
+    /// Number of monthly peaks averaged into the billed peak
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+    /// Count at most one peak per calendar day
+    #[serde(default)]
+    pub one_peak_per_day: bool,
+    /// Only weekday hours count at full weight
+    #[serde(default)]
+    pub weekdays_only: bool,
+    /// Local hour the full-weight window opens, `None` for all day
+    #[serde(default)]
+    pub window_start_hour: Option<u32>,
+    /// Local hour the full-weight window closes (exclusive, may wrap midnight)
+    #[serde(default)]
+    pub window_end_hour: Option<u32>,
+    /// Weight of hours outside the window, e.g. 0.5 for half-rate nights
+    #[serde(default)]
+    pub off_window_weight: f64,
+    /// IANA timezone the window and billing month are defined in
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+fn default_top_n() -> usize {
+    1
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+impl Default for PeakTariffRules {
+    fn default() -> Self {
+        Self {
+            top_n: default_top_n(),
+            one_peak_per_day: false,
+            weekdays_only: false,
+            window_start_hour: None,
+            window_end_hour: None,
+            off_window_weight: 0.0,
+            timezone: default_timezone(),
+        }
+    }
+}
+
+impl PeakTariffRules {
+    pub fn validate(&self) -> Result<()> {
+        if self.top_n == 0 {
+            bail!("Peak tariff must count at least one peak");
+        }
+        for hour in [self.window_start_hour, self.window_end_hour]
+            .into_iter()
+            .flatten()
+        {
+            if hour > 23 {
+                bail!("Peak tariff window hour {} is not in 0-23", hour);
+            }
+        }
+        if !(0.0..=1.0).contains(&self.off_window_weight) {
+            bail!(
+                "Off-window weight {} must be between 0 and 1",
+                self.off_window_weight
+            );
+        }
+        if self.timezone.parse::<chrono_tz::Tz>().is_err() {
+            bail!("Unknown peak tariff timezone '{}'", self.timezone);
+        }
+        Ok(())
+    }
+
+    fn tz(&self) -> chrono_tz::Tz {
+        self.timezone.parse().unwrap_or(chrono_tz::UTC)
+    }
+
+    /// Share of an hour's average import that counts towards the peak
+    pub fn weight(&self, hour_start: DateTime<Utc>) -> f64 {
+        let local = hour_start.with_timezone(&self.tz());
+        let weekday_ok =
+            !self.weekdays_only || !matches!(local.weekday(), Weekday::Sat | Weekday::Sun);
+        let hour = local.hour();
+        let in_window = match (self.window_start_hour, self.window_end_hour) {
+            (Some(start), Some(end)) if start <= end => hour >= start && hour < end,
+            (Some(start), Some(end)) => hour >= start || hour < end,
+            _ => true,
+        };
+
+        if weekday_ok && in_window {
+            1.0
+        } else {
+            self.off_window_weight
+        }
+    }
+
+    /// Billing month (year, month) containing `timestamp`
+    pub fn billing_month(&self, timestamp: DateTime<Utc>) -> (i32, u32) {
+        let local = timestamp.with_timezone(&self.tz());
+        (local.year(), local.month())
+    }
+
+    fn local_date(&self, timestamp: DateTime<Utc>) -> NaiveDate {
+        timestamp.with_timezone(&self.tz()).date_naive()
+    }
+}
+
+/// Average grid import over one clock hour
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct HourlyImport {
+    pub hour_start: DateTime<Utc>,
+    pub import_kw: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HourAccumulator {
+    hour_start: DateTime<Utc>,
+    energy_kwh: f64,
+    measured_hours: f64,
+}
+
+impl HourAccumulator {
+    fn close(&self) -> HourlyImport {
+        let import_kw = if self.measured_hours > 0.0 {
+            self.energy_kwh / self.measured_hours
+        } else {
+            0.0
+        };
+        HourlyImport {
+            hour_start: self.hour_start,
+            import_kw,
+        }
+    }
+}
+
+/// Hourly grid imports of the current billing month
+///
+/// Built from measured import power and persisted between runs, so that
+/// each re-optimization knows which peak has already been paid for.
+/// Hours are stored unweighted; the tariff rules are applied when reading,
+/// so changing them takes effect for the whole month.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeakLedger {
+    month: Option<(i32, u32)>,
+    hours: Vec<HourlyImport>,
+    current: Option<HourAccumulator>,
+}
+
+impl PeakLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a grid import measurement covering `dt` before `timestamp`
+    ///
+    /// Returns the completed hour when `timestamp` falls in a new clock hour.
+    /// An hour with gaps in the measurements is averaged over the time that
+    /// was measured.
+    pub fn record(
+        &mut self,
+        rules: &PeakTariffRules,
+        timestamp: DateTime<Utc>,
+        import_kw: f64,
+        dt: Duration,
+    ) -> Option<HourlyImport> {
+        let hour_start = timestamp.duration_trunc(Duration::hours(1)).ok()?;
+        let closed = match &self.current {
+            Some(acc) if acc.hour_start != hour_start => self.close_hour(rules),
+            _ => None,
+        };
+
+        let dt_hours = (dt.num_milliseconds() as f64 / 3_600_000.0).max(0.0);
+        if import_kw.is_finite() && dt_hours > 0.0 {
+            let acc = self.current.get_or_insert(HourAccumulator {
+                hour_start,
+                energy_kwh: 0.0,
+                measured_hours: 0.0,
+            });
+            acc.energy_kwh += import_kw.max(0.0) * dt_hours;
+            acc.measured_hours += dt_hours;
+        }
+
+        closed
+    }
+
+    fn close_hour(&mut self, rules: &PeakTariffRules) -> Option<HourlyImport> {
+        let hour = self.current.take()?.close();
+        let month = rules.billing_month(hour.hour_start);
+        if self.month != Some(month) {
+            self.hours.clear();
+            self.month = Some(month);
+        }
+        self.hours.push(hour);
+        Some(hour)
+    }
+
+    /// Weighted peaks currently on the bill for the month containing `now`, highest first
+    pub fn counted_peaks(&self, rules: &PeakTariffRules, now: DateTime<Utc>) -> Vec<f64> {
+        if self.month != Some(rules.billing_month(now)) {
+            return Vec::new();
+        }
+
+        let weighted = self
+            .hours
+            .iter()
+            .map(|h| (h.hour_start, h.import_kw * rules.weight(h.hour_start)))
+            .filter(|(_, kw)| *kw > 0.0);
+
+        let mut peaks: Vec<f64> = if rules.one_peak_per_day {
+            let mut per_day: HashMap<NaiveDate, f64> = HashMap::new();
+            for (hour_start, kw) in weighted {
+                let day = per_day.entry(rules.local_date(hour_start)).or_insert(0.0);
+                *day = day.max(kw);
+            }
+            per_day.into_values().collect()
+        } else {
+            weighted.map(|(_, kw)| kw).collect()
+        };
+
+        peaks.sort_by(|a, b| b.total_cmp(a));
+        peaks.truncate(rules.top_n);
+        peaks
+    }
+
+    /// Weighted hourly import a new peak must exceed before the bill goes up
+    ///
+    /// Zero until `top_n` peaks have been counted this month.
+    pub fn incurred_peak_kw(&self, rules: &PeakTariffRules, now: DateTime<Utc>) -> f64 {
+        let peaks = self.counted_peaks(rules, now);
+        if peaks.len() < rules.top_n {
+            0.0
+        } else {
+            peaks.last().copied().unwrap_or(0.0)
+        }
+    }
+
+    /// Peak the month is billed at so far (mean of the top-N, missing peaks as zero)
+    pub fn billed_peak_kw(&self, rules: &PeakTariffRules, now: DateTime<Utc>) -> f64 {
+        self.counted_peaks(rules, now).iter().sum::<f64>() / rules.top_n.max(1) as f64
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        let json = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read peak ledger {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse peak ledger {}", path.display()))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        tokio::fs::write(path, json)
+            .await
+            .with_context(|| format!("Failed to write peak ledger {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, day, hour, 0, 0).unwrap()
+    }
+
+    /// Record a full hour at constant import, closing it with the next hour's first sample
+    fn record_hour(
+        ledger: &mut PeakLedger,
+        rules: &PeakTariffRules,
+        start: DateTime<Utc>,
+        kw: f64,
+    ) {
+        for minute in (15..=60).step_by(15) {
+            ledger.record(
+                rules,
+                start + Duration::minutes(minute - 1),
+                kw,
+                Duration::minutes(15),
+            );
+        }
+        ledger.record(rules, start + Duration::hours(1), 0.0, Duration::zero());
+    }
+
+    #[test]
+    fn test_hourly_average_from_samples() {
+        let rules = PeakTariffRules::default();
+        let mut ledger = PeakLedger::new();
+        ledger.record(
+            &rules,
+            at(6, 10) + Duration::minutes(30),
+            8.0,
+            Duration::minutes(30),
+        );
+        ledger.record(
+            &rules,
+            at(6, 10) + Duration::minutes(59),
+            2.0,
+            Duration::minutes(30),
+        );
+        let closed = ledger
+            .record(&rules, at(6, 11), 0.0, Duration::zero())
+            .unwrap();
+
+        assert_eq!(closed.hour_start, at(6, 10));
+        assert!((closed.import_kw - 5.0).abs() < 1e-9);
+        assert!((ledger.incurred_peak_kw(&rules, at(6, 12)) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_top_three_one_per_day() {
+        let rules = PeakTariffRules {
+            top_n: 3,
+            one_peak_per_day: true,
+            ..Default::default()
+        };
+        let mut ledger = PeakLedger::new();
+        record_hour(&mut ledger, &rules, at(6, 8), 9.0);
+        record_hour(&mut ledger, &rules, at(6, 18), 8.0); // same day, not counted
+        record_hour(&mut ledger, &rules, at(7, 18), 6.0);
+        assert_eq!(ledger.incurred_peak_kw(&rules, at(8, 0)), 0.0);
+
+        record_hour(&mut ledger, &rules, at(8, 18), 4.0);
+        assert_eq!(ledger.counted_peaks(&rules, at(9, 0)), vec![9.0, 6.0, 4.0]);
+        assert!((ledger.incurred_peak_kw(&rules, at(9, 0)) - 4.0).abs() < 1e-9);
+        assert!((ledger.billed_peak_kw(&rules, at(9, 0)) - 19.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_window_weighting() {
+        let rules = PeakTariffRules {
+            weekdays_only: true,
+            window_start_hour: Some(7),
+            window_end_hour: Some(21),
+            off_window_weight: 0.5,
+            ..Default::default()
+        };
+        // 2025-01-06 is a Monday, 2025-01-11 a Saturday
+        assert_eq!(rules.weight(at(6, 12)), 1.0);
+        assert_eq!(rules.weight(at(6, 22)), 0.5);
+        assert_eq!(rules.weight(at(11, 12)), 0.5);
+
+        let mut ledger = PeakLedger::new();
+        record_hour(&mut ledger, &rules, at(6, 2), 10.0);
+        record_hour(&mut ledger, &rules, at(6, 12), 4.0);
+        assert!((ledger.incurred_peak_kw(&rules, at(7, 0)) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_new_month_starts_empty() {
+        let rules = PeakTariffRules::default();
+        let mut ledger = PeakLedger::new();
+        record_hour(&mut ledger, &rules, at(31, 10), 7.0);
+        let february = Utc.with_ymd_and_hms(2025, 2, 1, 10, 0, 0).unwrap();
+        assert_eq!(ledger.incurred_peak_kw(&rules, february), 0.0);
+
+        record_hour(&mut ledger, &rules, february, 3.0);
+        assert_eq!(ledger.counted_peaks(&rules, february), vec![3.0]);
+    }
+
+    #[tokio::test]
+    async fn test_ledger_survives_restart() {
+        let rules = PeakTariffRules::default();
+        let mut ledger = PeakLedger::new();
+        record_hour(&mut ledger, &rules, at(6, 10), 6.5);
+
+        let path = std::env::temp_dir().join(format!("peak_ledger_{}.json", uuid::Uuid::new_v4()));
+        ledger.save(&path).await.unwrap();
+        let restored = PeakLedger::load(&path).await.unwrap();
+        let _ = tokio::fs::remove_file(&path).await;
+
+        assert_eq!(restored.incurred_peak_kw(&rules, at(6, 12)), 6.5);
+    }
+}
@@ -0,0 +1,157 @@
+//! Frequency reserves
+//!
+//! Through an aggregator, a household battery can be sold on Svenska
+//! kraftnät's FCR-D up/down and FFR markets hour by hour. In a committed
+//! hour the battery keeps power and energy headroom for the sold capacity
+//! and responds to the grid frequency:
+//!
+//! - FCR-D up: linear from 0 at 49.9 Hz to full at 49.5 Hz (discharge)
+//! - FCR-D down: linear from 0 at 50.1 Hz to full at 50.5 Hz (charge)
+//! - FFR: full discharge once the frequency falls below the trigger level,
+//!   held for the support duration
+//!
+//! As a limited energy reservoir, the battery must be able to sustain full
+//! FCR-D activation for 20 minutes in either direction.
+
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// FCR-D up starts responding below this frequency (Hz)
+pub const FCR_D_UP_START_HZ: f64 = 49.9;
+/// FCR-D up is fully activated at this frequency (Hz)
+pub const FCR_D_UP_FULL_HZ: f64 = 49.5;
+/// FCR-D down starts responding above this frequency (Hz)
+pub const FCR_D_DOWN_START_HZ: f64 = 50.1;
+/// FCR-D down is fully activated at this frequency (Hz)
+pub const FCR_D_DOWN_FULL_HZ: f64 = 50.5;
+/// Full activation a limited energy reservoir must sustain (hours)
+pub const FCR_D_ENDURANCE_HOURS: f64 = 20.0 / 60.0;
+
+/// Share of FCR-D up capacity to activate at `frequency_hz` (0-1)
+pub fn fcr_d_up_share(frequency_hz: f64) -> f64 {
+    ((FCR_D_UP_START_HZ - frequency_hz) / (FCR_D_UP_START_HZ - FCR_D_UP_FULL_HZ)).clamp(0.0, 1.0)
+}
+
+/// Share of FCR-D down capacity to activate at `frequency_hz` (0-1)
+pub fn fcr_d_down_share(frequency_hz: f64) -> f64 {
+    ((frequency_hz - FCR_D_DOWN_START_HZ) / (FCR_D_DOWN_FULL_HZ - FCR_D_DOWN_START_HZ))
+        .clamp(0.0, 1.0)
+}
+
+/// Reserve capacity sold for every day's committed hours
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ReserveOffer {
+    /// Local hours (0-23) the capacity is sold for
+    #[serde(default)]
+    pub hours: Vec<u32>,
+    /// FCR-D up capacity (kW)
+    #[serde(default)]
+    pub fcr_d_up_kw: f64,
+    /// FCR-D down capacity (kW)
+    #[serde(default)]
+    pub fcr_d_down_kw: f64,
+    /// FFR capacity (kW)
+    #[serde(default)]
+    pub ffr_kw: f64,
+    /// FFR support duration (seconds)
+    #[serde(default)]
+    pub ffr_support_seconds: f64,
+    /// IANA timezone the hours are defined in
+    #[serde(default)]
+    pub timezone: String,
+}
+
+/// Capacity committed for one hour
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ReserveCommitment {
+    pub fcr_d_up_kw: f64,
+    pub fcr_d_down_kw: f64,
+    pub ffr_kw: f64,
+    pub ffr_support_seconds: f64,
+}
+
+impl ReserveOffer {
+    /// Capacity committed at `timestamp`, `None` outside the committed hours
+    pub fn commitment_at(&self, timestamp: DateTime<Utc>) -> Option<ReserveCommitment> {
+        let tz: chrono_tz::Tz = self.timezone.parse().unwrap_or(chrono_tz::UTC);
+        let hour = timestamp.with_timezone(&tz).hour();
+        let commitment = ReserveCommitment {
+            fcr_d_up_kw: self.fcr_d_up_kw.max(0.0),
+            fcr_d_down_kw: self.fcr_d_down_kw.max(0.0),
+            ffr_kw: self.ffr_kw.max(0.0),
+            ffr_support_seconds: self.ffr_support_seconds.max(0.0),
+        };
+        (self.hours.contains(&hour) && !commitment.is_empty()).then_some(commitment)
+    }
+}
+
+impl ReserveCommitment {
+    pub fn is_empty(&self) -> bool {
+        self.fcr_d_up_kw <= 0.0 && self.fcr_d_down_kw <= 0.0 && self.ffr_kw <= 0.0
+    }
+
+    /// Discharge power kept free for upward activation (kW)
+    ///
+    /// FCR-D up and FFR may be activated at the same time.
+    pub fn up_kw(&self) -> f64 {
+        self.fcr_d_up_kw + self.ffr_kw
+    }
+
+    /// Energy kept in the battery for upward activation (kWh, AC side)
+    pub fn up_energy_kwh(&self) -> f64 {
+        self.fcr_d_up_kw * FCR_D_ENDURANCE_HOURS + self.ffr_kw * self.ffr_support_seconds / 3600.0
+    }
+
+    /// Room kept in the battery for downward activation (kWh, AC side)
+    pub fn down_energy_kwh(&self) -> f64 {
+        self.fcr_d_down_kw * FCR_D_ENDURANCE_HOURS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_fcr_d_droop() {
+        assert_eq!(fcr_d_up_share(50.0), 0.0);
+        assert_eq!(fcr_d_up_share(49.9), 0.0);
+        assert!((fcr_d_up_share(49.7) - 0.5).abs() < 1e-9);
+        assert_eq!(fcr_d_up_share(49.2), 1.0);
+
+        assert_eq!(fcr_d_down_share(50.05), 0.0);
+        assert!((fcr_d_down_share(50.2) - 0.25).abs() < 1e-9);
+        assert_eq!(fcr_d_down_share(50.6), 1.0);
+        assert_eq!(fcr_d_down_share(49.5), 0.0);
+    }
+
+    #[test]
+    fn test_commitment_follows_local_hours() {
+        let offer = ReserveOffer {
+            hours: vec![7, 8],
+            fcr_d_up_kw: 3.0,
+            fcr_d_down_kw: 2.0,
+            ffr_kw: 0.0,
+            ffr_support_seconds: 30.0,
+            timezone: "Europe/Stockholm".to_string(),
+        };
+        // 06:30 UTC is 08:30 in Stockholm summer time
+        let committed = Utc.with_ymd_and_hms(2025, 6, 2, 6, 30, 0).unwrap();
+        let commitment = offer.commitment_at(committed).unwrap();
+        assert_eq!(commitment.up_kw(), 3.0);
+        assert!((commitment.up_energy_kwh() - 1.0).abs() < 1e-9);
+        assert!(offer
+            .commitment_at(committed + chrono::Duration::hours(1))
+            .is_none());
+
+        let nothing_sold = ReserveOffer {
+            fcr_d_up_kw: 0.0,
+            fcr_d_down_kw: 0.0,
+            ..offer
+        };
+        assert!(nothing_sold.commitment_at(committed).is_none());
+    }
+}
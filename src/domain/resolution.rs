@@ -0,0 +1,333 @@
+//! Time resolution of forecasts
+//!
+//! Day-ahead prices come in 15- or 60-minute periods depending on the
+//! market and provider, and load and PV forecasts have a step of their own.
+//! The strategies plan one schedule entry per price period, so the forecast
+//! engine first puts all three series on a common grid.
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::{ConsumptionPoint, Forecast24h, PricePoint, ProductionPoint};
+
+/// Longest horizon the optimizer plans over
+pub const MAX_HORIZON_HOURS: i64 = 48;
+
+/// Longest horizon the optimizer plans over
+pub fn max_horizon() -> Duration {
+    Duration::hours(MAX_HORIZON_HOURS)
+}
+
+impl Forecast24h {
+    /// Shortest price period, `None` without prices
+    pub fn resolution(&self) -> Option<Duration> {
+        self.prices.iter().map(|p| p.time_end - p.time_start).min()
+    }
+
+    /// Number of price periods starting within `horizon` of the first one
+    ///
+    /// The forecast engine starts the grid at the current period, so this is
+    /// the lookahead from now.
+    pub fn periods_within(&self, horizon: Duration) -> usize {
+        let Some(first) = self.prices.first() else {
+            return 0;
+        };
+        let end = first.time_start + horizon;
+        self.prices
+            .iter()
+            .take_while(|p| p.time_start < end)
+            .count()
+    }
+
+    /// Mean forecast house load over `[start, end)`, `None` if no point overlaps
+    pub fn load_kw_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Option<f64> {
+        time_weighted_mean(
+            self.consumption
+                .iter()
+                .map(|c| (c.time_start, c.time_end, c.load_kw)),
+            start,
+            end,
+        )
+        .map(|(kw, _)| kw)
+    }
+
+    /// Mean forecast PV power over `[start, end)`, `None` if no point overlaps
+    pub fn pv_kw_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Option<f64> {
+        time_weighted_mean(
+            self.production
+                .iter()
+                .map(|p| (p.time_start, p.time_end, p.pv_kw.max(0.0))),
+            start,
+            end,
+        )
+        .map(|(kw, _)| kw)
+    }
+
+    /// The forecast on a grid of `step` periods, from the one `now` falls in
+    /// up to `horizon` ahead
+    ///
+    /// The grid is aligned to the first price period, and periods that are
+    /// already over are dropped. Prices are averaged over or split into the new periods, and the grid
+    /// ends at the first period prices do not fully cover. Load and PV are
+    /// averaged over each period and hold their nearest value where their
+    /// forecast does not reach.
+    pub fn resampled(&self, now: DateTime<Utc>, step: Duration, horizon: Duration) -> Forecast24h {
+        let mut prices = self.prices.clone();
+        prices.sort_by_key(|p| p.time_start);
+        let Some(first) = prices.first() else {
+            return self.clone();
+        };
+        if step <= Duration::zero() {
+            return self.clone();
+        }
+
+        let mut start = first.time_start;
+        if now > start {
+            let elapsed = (now - start).num_seconds() / step.num_seconds().max(1);
+            start += step * elapsed as i32;
+        }
+        let horizon_end = start + horizon.min(max_horizon());
+        let mut resampled = Forecast24h {
+            area: self.area,
+            generated_at: self.generated_at,
+            prices: Vec::new(),
+            consumption: Vec::new(),
+            production: Vec::new(),
+        };
+
+        while start + step <= horizon_end {
+            let end = start + step;
+            let Some((price, covered)) = time_weighted_mean(
+                prices
+                    .iter()
+                    .map(|p| (p.time_start, p.time_end, p.price_sek_per_kwh)),
+                start,
+                end,
+            ) else {
+                break;
+            };
+            if covered < step {
+                break;
+            }
+            // Without any explicit export price the default rule still applies
+            let export_price = prices
+                .iter()
+                .any(|p| overlaps(p, start, end) && p.export_price_sek_per_kwh.is_some())
+                .then(|| {
+                    time_weighted_mean(
+                        prices
+                            .iter()
+                            .map(|p| (p.time_start, p.time_end, p.export_price())),
+                        start,
+                        end,
+                    )
+                    .map(|(price, _)| price)
+                })
+                .flatten();
+            resampled.prices.push(PricePoint {
+                time_start: start,
+                time_end: end,
+                price_sek_per_kwh: price,
+                export_price_sek_per_kwh: export_price,
+            });
+
+            if !self.consumption.is_empty() {
+                resampled.consumption.push(ConsumptionPoint {
+                    time_start: start,
+                    time_end: end,
+                    load_kw: self.load_kw_between(start, end).unwrap_or_else(|| {
+                        nearest(
+                            self.consumption
+                                .iter()
+                                .map(|c| (c.time_start, c.time_end, c.load_kw)),
+                            start,
+                        )
+                    }),
+                });
+            }
+            if !self.production.is_empty() {
+                resampled.production.push(ProductionPoint {
+                    time_start: start,
+                    time_end: end,
+                    pv_kw: self.pv_kw_between(start, end).unwrap_or_else(|| {
+                        nearest(
+                            self.production
+                                .iter()
+                                .map(|p| (p.time_start, p.time_end, p.pv_kw.max(0.0))),
+                            start,
+                        )
+                    }),
+                });
+            }
+            start = end;
+        }
+        resampled
+    }
+}
+
+fn overlaps(price: &PricePoint, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    price.time_start < end && start < price.time_end
+}
+
+/// Mean of piecewise-constant `points` over `[start, end)` and the time
+/// they cover, `None` if none overlaps
+fn time_weighted_mean(
+    points: impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>, f64)>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<(f64, Duration)> {
+    let mut covered = Duration::zero();
+    let mut weighted = 0.0;
+    for (point_start, point_end, value) in points {
+        let overlap = point_end.min(end) - point_start.max(start);
+        if overlap > Duration::zero() {
+            covered += overlap;
+            weighted += value * overlap.num_seconds() as f64;
+        }
+    }
+    (covered > Duration::zero()).then(|| (weighted / covered.num_seconds() as f64, covered))
+}
+
+/// Value of the point closest in time to `t`
+fn nearest(
+    points: impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>, f64)>,
+    t: DateTime<Utc>,
+) -> f64 {
+    points
+        .min_by_key(|&(start, end, _)| {
+            if t < start {
+                start - t
+            } else {
+                (t - end).max(Duration::zero())
+            }
+        })
+        .map_or(0.0, |(_, _, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::PriceArea;
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 0).unwrap()
+    }
+
+    fn forecast(price_step_min: i64, prices: &[f64], load_kw: &[f64]) -> Forecast24h {
+        let at = |i: usize, step_min: i64| start() + Duration::minutes(i as i64 * step_min);
+        Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start(),
+            prices: prices
+                .iter()
+                .enumerate()
+                .map(|(i, &price)| PricePoint {
+                    time_start: at(i, price_step_min),
+                    time_end: at(i + 1, price_step_min),
+                    price_sek_per_kwh: price,
+                    export_price_sek_per_kwh: None,
+                })
+                .collect(),
+            consumption: load_kw
+                .iter()
+                .enumerate()
+                .map(|(i, &load_kw)| ConsumptionPoint {
+                    time_start: at(i, 60),
+                    time_end: at(i + 1, 60),
+                    load_kw,
+                })
+                .collect(),
+            production: vec![],
+        }
+    }
+
+    #[test]
+    fn test_hourly_inputs_split_into_quarter_hours() {
+        let resampled = forecast(60, &[1.0, 2.0], &[0.5, 1.5]).resampled(
+            start(),
+            Duration::minutes(15),
+            max_horizon(),
+        );
+
+        assert_eq!(resampled.prices.len(), 8);
+        assert_eq!(resampled.consumption.len(), 8);
+        assert_eq!(resampled.prices[3].price_sek_per_kwh, 1.0);
+        assert_eq!(resampled.prices[4].price_sek_per_kwh, 2.0);
+        assert_eq!(resampled.consumption[5].load_kw, 1.5);
+        assert_eq!(resampled.resolution(), Some(Duration::minutes(15)));
+    }
+
+    #[test]
+    fn test_quarter_hour_prices_averaged_to_hours() {
+        let resampled = forecast(15, &[1.0, 2.0, 3.0, 6.0, 1.0, 1.0, 1.0, 1.0], &[2.0]).resampled(
+            start(),
+            Duration::hours(1),
+            max_horizon(),
+        );
+
+        assert_eq!(resampled.prices.len(), 2);
+        assert_eq!(resampled.prices[0].price_sek_per_kwh, 3.0);
+        assert_eq!(resampled.prices[1].price_sek_per_kwh, 1.0);
+        // The load forecast ends after one hour and holds its last value
+        assert_eq!(resampled.consumption[1].load_kw, 2.0);
+    }
+
+    #[test]
+    fn test_grid_ends_at_horizon_and_at_partial_periods() {
+        let hourly = forecast(60, &[1.0; 60], &[]);
+        let resampled = hourly.resampled(start(), Duration::minutes(30), Duration::hours(72));
+        assert_eq!(resampled.prices.len(), 96, "capped at 48 h");
+        assert!(resampled.consumption.is_empty());
+
+        // 45 minutes of prices only fill one 30-minute period
+        let short = forecast(15, &[1.0, 1.0, 1.0], &[]);
+        assert_eq!(
+            short
+                .resampled(start(), Duration::minutes(30), max_horizon())
+                .prices
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_grid_starts_at_the_current_period() {
+        // Today from midnight and tomorrow, resampled at 14:20 for 24 h
+        let mut two_days = forecast(60, &[1.0; 48], &[]);
+        two_days.prices[30].price_sek_per_kwh = 0.1;
+        let now = start() + Duration::minutes(14 * 60 + 20);
+
+        let hourly = two_days.resampled(now, Duration::hours(1), Duration::hours(24));
+        assert_eq!(hourly.prices.len(), 24);
+        assert_eq!(hourly.prices[0].time_start, start() + Duration::hours(14));
+        // Tomorrow's prices are kept up to the horizon
+        assert_eq!(hourly.prices[16].price_sek_per_kwh, 0.1);
+        assert_eq!(hourly.prices[23].time_end, start() + Duration::hours(38));
+
+        let quarter_hours = two_days.resampled(now, Duration::minutes(15), max_horizon());
+        assert_eq!(
+            quarter_hours.prices[0].time_start,
+            start() + Duration::minutes(14 * 60 + 15)
+        );
+        // Up to the end of tomorrow's prices
+        assert_eq!(quarter_hours.prices.len(), 4 * 34 - 1);
+    }
+
+    #[test]
+    fn test_explicit_export_prices_are_averaged() {
+        let mut quarter_hours = forecast(15, &[1.0, 1.0, 1.0, 1.0], &[]);
+        quarter_hours.prices[0].export_price_sek_per_kwh = Some(0.8);
+        let resampled = quarter_hours.resampled(start(), Duration::hours(1), max_horizon());
+
+        // One explicit 0.8 and three default 0.4
+        let export = resampled.prices[0].export_price_sek_per_kwh.unwrap();
+        assert!((export - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_periods_within_horizon() {
+        let quarter_hours = forecast(15, &[1.0; 300], &[]);
+        assert_eq!(quarter_hours.periods_within(max_horizon()), 192);
+        assert_eq!(quarter_hours.periods_within(Duration::hours(1)), 4);
+    }
+}
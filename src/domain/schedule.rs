@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
+use super::{max_horizon, MAX_HORIZON_HOURS};
+
 #[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Schedule {
@@ -13,6 +15,25 @@ pub struct Schedule {
     pub valid_until: DateTime<Utc>,
     pub entries: Vec<ScheduleEntry>,
     pub optimizer_version: String,
+    /// Spread of the schedule's cost over forecast scenarios, when the
+    /// optimizer evaluated any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_distribution: Option<CostDistribution>,
+}
+
+/// Cost of following a schedule across forecast scenarios (SEK)
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostDistribution {
+    pub expected_sek: f64,
+    /// Mean cost of the worst `1 - cvar_alpha` share of scenarios
+    pub cvar_sek: f64,
+    pub cvar_alpha: f64,
+    pub p10_sek: f64,
+    pub p50_sek: f64,
+    pub p90_sek: f64,
+    /// Cost in each scenario, in scenario order
+    pub scenario_costs_sek: Vec<f64>,
 }
 
 #[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
@@ -24,6 +45,18 @@ pub struct ScheduleEntry {
     /// Import price in SEK/kWh for this interval.
     pub price_sek_per_kwh: f64,
     pub reason: String,
+    /// Planned charging power per EV charger, empty when no vehicle is planned
+    #[serde(default)]
+    pub ev_targets: Vec<EvTarget>,
+}
+
+/// Planned power of one EV charger in a schedule interval
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EvTarget {
+    pub charger_id: String,
+    /// Positive = charging the vehicle, negative = V2G discharge
+    pub power_w: f64,
 }
 
 /// A simplified schedule interval with power target only.
@@ -44,6 +77,14 @@ impl ScheduleEntry {
             target_power_w: self.target_power_w,
         }
     }
+
+    /// Planned power of one EV charger, `None` if the entry has no plan for it
+    pub fn ev_power_w(&self, charger_id: &str) -> Option<f64> {
+        self.ev_targets
+            .iter()
+            .find(|t| t.charger_id == charger_id)
+            .map(|t| t.power_w)
+    }
 }
 
 /// Errors returned when validating a schedule.
@@ -69,6 +110,8 @@ pub enum ScheduleValidationError {
     },
     #[error("schedule window does not align with entries")]
     WindowMismatch,
+    #[error("schedule window of {hours:.1} h is longer than the {max_hours} h horizon")]
+    HorizonTooLong { hours: f64, max_hours: i64 },
 }
 
 impl Schedule {
@@ -109,7 +152,33 @@ impl Schedule {
         None
     }
 
+    /// Planned power of one EV charger at a specific timestamp
+    pub fn ev_power_at(&self, charger_id: &str, t: DateTime<Utc>) -> Option<f64> {
+        self.entries
+            .iter()
+            .find(|e| t >= e.time_start && t < e.time_end)
+            .and_then(|e| e.ev_power_w(charger_id))
+    }
+
+    /// The plan of one EV charger from `from` onwards, empty if it has none
+    pub fn ev_plan(&self, charger_id: &str, from: DateTime<Utc>) -> Vec<ScheduleInterval> {
+        self.entries
+            .iter()
+            .filter(|e| e.time_end > from)
+            .filter_map(|e| {
+                e.ev_power_w(charger_id).map(|power_w| ScheduleInterval {
+                    time_start: e.time_start.max(from),
+                    time_end: e.time_end,
+                    target_power_w: power_w,
+                })
+            })
+            .collect()
+    }
+
     /// Validate that entries cover the full schedule window without gaps or overlaps.
+    ///
+    /// Entries may be of any length (15, 30 or 60 minutes) and mixed, but
+    /// the window may not exceed the optimizer's horizon.
     pub fn validate(&self) -> Result<(), ScheduleValidationError> {
         if self.entries.is_empty() {
             return Err(ScheduleValidationError::EmptySchedule);
@@ -117,6 +186,12 @@ impl Schedule {
         if self.valid_from >= self.valid_until {
             return Err(ScheduleValidationError::InvalidWindow);
         }
+        if self.valid_until - self.valid_from > max_horizon() {
+            return Err(ScheduleValidationError::HorizonTooLong {
+                hours: (self.valid_until - self.valid_from).num_seconds() as f64 / 3600.0,
+                max_hours: MAX_HORIZON_HOURS,
+            });
+        }
 
         let mut previous_end: Option<DateTime<Utc>> = None;
         for (index, entry) in self.entries.iter().enumerate() {
@@ -127,6 +202,9 @@ impl Schedule {
             if !entry.price_sek_per_kwh.is_finite() {
                 return Err(ScheduleValidationError::InvalidEntryRange { index });
             }
+            if entry.ev_targets.iter().any(|t| !t.power_w.is_finite()) {
+                return Err(ScheduleValidationError::InvalidEntryRange { index });
+            }
 
             let interval = entry.interval();
             if interval.time_start >= interval.time_end {
@@ -192,6 +270,7 @@ mod tests {
             valid_until,
             entries,
             optimizer_version: "test".to_string(),
+            cost_distribution: None,
         }
     }
 
@@ -208,6 +287,7 @@ mod tests {
                 target_power_w: 100.0,
                 price_sek_per_kwh: 1.0,
                 reason: "slot-1".to_string(),
+                ev_targets: Vec::new(),
             },
             ScheduleEntry {
                 time_start: t1,
@@ -215,12 +295,57 @@ mod tests {
                 target_power_w: 200.0,
                 price_sek_per_kwh: 1.2,
                 reason: "slot-2".to_string(),
+                ev_targets: Vec::new(),
             },
         ]);
 
         assert_eq!(schedule.validate(), Ok(()));
     }
 
+    #[test]
+    fn validate_accepts_mixed_resolutions() {
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let quarter = |i: i64| t0 + chrono::Duration::minutes(15 * i);
+        let mut entries: Vec<ScheduleEntry> = (0..4)
+            .map(|i| ScheduleEntry {
+                time_start: quarter(i),
+                time_end: quarter(i + 1),
+                target_power_w: 100.0,
+                price_sek_per_kwh: 1.0,
+                reason: "quarter".to_string(),
+                ev_targets: Vec::new(),
+            })
+            .collect();
+        entries.push(ScheduleEntry {
+            time_start: quarter(4),
+            time_end: quarter(8),
+            target_power_w: 0.0,
+            price_sek_per_kwh: 1.0,
+            reason: "hour".to_string(),
+            ev_targets: Vec::new(),
+        });
+
+        assert_eq!(make_schedule(entries).validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_window_beyond_horizon() {
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let schedule = make_schedule(vec![ScheduleEntry {
+            time_start: t0,
+            time_end: t0 + chrono::Duration::hours(72),
+            target_power_w: 0.0,
+            price_sek_per_kwh: 1.0,
+            reason: "long".to_string(),
+            ev_targets: Vec::new(),
+        }]);
+
+        assert!(matches!(
+            schedule.validate(),
+            Err(ScheduleValidationError::HorizonTooLong { max_hours: 48, .. })
+        ));
+    }
+
     #[test]
     fn validate_rejects_gap() {
         let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
@@ -235,6 +360,7 @@ mod tests {
                 target_power_w: 100.0,
                 price_sek_per_kwh: 1.0,
                 reason: "slot-1".to_string(),
+                ev_targets: Vec::new(),
             },
             ScheduleEntry {
                 time_start: t2,
@@ -242,6 +368,7 @@ mod tests {
                 target_power_w: 200.0,
                 price_sek_per_kwh: 1.2,
                 reason: "slot-2".to_string(),
+                ev_targets: Vec::new(),
             },
         ]);
 
@@ -267,6 +394,7 @@ mod tests {
                 target_power_w: 100.0,
                 price_sek_per_kwh: 1.0,
                 reason: "slot-1".to_string(),
+                ev_targets: Vec::new(),
             },
             ScheduleEntry {
                 time_start: t1,
@@ -274,6 +402,7 @@ mod tests {
                 target_power_w: 200.0,
                 price_sek_per_kwh: 1.2,
                 reason: "slot-2".to_string(),
+                ev_targets: Vec::new(),
             },
         ]);
 
@@ -303,8 +432,10 @@ mod tests {
                 target_power_w: 100.0,
                 price_sek_per_kwh: 1.0,
                 reason: "slot-1".to_string(),
+                ev_targets: Vec::new(),
             }],
             optimizer_version: "test".to_string(),
+            cost_distribution: None,
         };
 
         assert_eq!(
@@ -312,4 +443,45 @@ mod tests {
             Err(ScheduleValidationError::EntryOutOfBounds { index: 0 })
         );
     }
+
+    #[test]
+    fn ev_plan_starts_at_the_requested_time() {
+        let t0 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let t1 = t0 + chrono::Duration::hours(1);
+        let t2 = t1 + chrono::Duration::hours(1);
+        let target = |power_w| {
+            vec![EvTarget {
+                charger_id: "drive".to_string(),
+                power_w,
+            }]
+        };
+
+        let schedule = make_schedule(vec![
+            ScheduleEntry {
+                time_start: t0,
+                time_end: t1,
+                target_power_w: 0.0,
+                price_sek_per_kwh: 1.0,
+                reason: "slot-1".to_string(),
+                ev_targets: target(7400.0),
+            },
+            ScheduleEntry {
+                time_start: t1,
+                time_end: t2,
+                target_power_w: 0.0,
+                price_sek_per_kwh: 2.0,
+                reason: "slot-2".to_string(),
+                ev_targets: target(0.0),
+            },
+        ]);
+        let half_past = t0 + chrono::Duration::minutes(30);
+
+        assert_eq!(schedule.ev_power_at("drive", half_past), Some(7400.0));
+        assert_eq!(schedule.ev_power_at("garage", half_past), None);
+
+        let plan = schedule.ev_plan("drive", half_past);
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].time_start, half_past);
+        assert_eq!(plan[1].target_power_w, 0.0);
+    }
 }
@@ -0,0 +1,265 @@
+//! Heat pumps and hot-water tanks as flexible loads
+//!
+//! Heating is the largest flexible load in a Nordic home. The house and the
+//! hot-water tank store heat for hours, so the heat pump can run ahead of
+//! cheap periods and coast through expensive ones. The controller does not
+//! run the compressor itself; it sends one of the four SG-Ready operating
+//! states and leaves the heat pump's own controls in charge of comfort and
+//! protection.
+#![allow(dead_code)]
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::HealthStatus;
+use crate::simulation::hvac::HvacSystem;
+use crate::simulation::thermal::ThermalZone;
+
+/// Operating state requested from the heat pump
+///
+/// Follows the SG-Ready label's relay states (relay 1 : relay 2).
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ThermalLoadMode {
+    /// 1:0, compressor held off (utility block, at most 2 h at a time)
+    Blocked,
+    /// 0:0, the heat pump's own schedule
+    #[default]
+    Normal,
+    /// 0:1, raised setpoints for house and hot water
+    Boost,
+    /// 1:1, run now at full output up to the heat pump's limits
+    ForcedOn,
+}
+
+impl ThermalLoadMode {
+    /// Relay outputs (relay 1, relay 2) for this state
+    pub fn sg_ready_relays(self) -> (bool, bool) {
+        match self {
+            Self::Blocked => (true, false),
+            Self::Normal => (false, false),
+            Self::Boost => (false, true),
+            Self::ForcedOn => (true, true),
+        }
+    }
+}
+
+/// One reading of the heat pump
+///
+/// Values the device does not report are `None`.
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalLoadState {
+    pub mode: ThermalLoadMode,
+    pub indoor_temp_c: Option<f64>,
+    /// Top of the hot-water tank
+    pub dhw_temp_c: Option<f64>,
+    pub outdoor_temp_c: Option<f64>,
+    /// Electrical input power
+    pub power_w: Option<f64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalLoadCapabilities {
+    /// Electrical input at full output
+    pub rated_power_kw: f64,
+    /// Whether the heat pump also charges a hot-water tank
+    pub has_dhw_tank: bool,
+    /// Whether `Blocked` is honoured; some units ignore it
+    pub supports_blocking: bool,
+}
+
+/// Heat pump the controller can steer between operating states
+#[async_trait]
+pub trait FlexibleThermalLoad: Send + Sync {
+    async fn read_state(&self) -> Result<ThermalLoadState>;
+    async fn set_mode(&self, mode: ThermalLoadMode) -> Result<()>;
+    fn capabilities(&self) -> ThermalLoadCapabilities;
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        Ok(match self.read_state().await {
+            Ok(_) => HealthStatus::Healthy,
+            Err(_) => HealthStatus::Offline,
+        })
+    }
+}
+
+/// How far the simulated heat pump's thermostat is fooled in `Boost` (K)
+const SIM_BOOST_OFFSET_K: f64 = 2.0;
+/// Offset that keeps the simulated compressor running in `ForcedOn` (K)
+const SIM_FORCED_OFFSET_K: f64 = 10.0;
+/// Longest stretch one read advances the simulation by
+const SIM_MAX_STEP_SECONDS: f64 = 3600.0;
+
+/// Heat pump from `simulation::hvac` heating a `ThermalZone`
+///
+/// Time advances with the wall clock on every read. The operating state
+/// shifts the room temperature the heat pump's thermostat sees, which is
+/// how SG-Ready units implement `Boost` as well.
+pub struct SimulatedThermalLoad {
+    inner: Mutex<SimulatedInner>,
+    caps: ThermalLoadCapabilities,
+    nominal_voltage_v: f64,
+}
+
+struct SimulatedInner {
+    hvac: Box<dyn HvacSystem>,
+    zone: ThermalZone,
+    mode: ThermalLoadMode,
+    outdoor_temp_c: f64,
+    /// Hot water drawn per day, spread evenly
+    dhw_draw_liters_per_day: f64,
+    power_w: f64,
+    last_update: std::time::Instant,
+}
+
+impl SimulatedThermalLoad {
+    pub fn new(
+        hvac: Box<dyn HvacSystem>,
+        zone: ThermalZone,
+        rated_power_kw: f64,
+        outdoor_temp_c: f64,
+        dhw_draw_liters_per_day: f64,
+    ) -> Self {
+        let has_dhw_tank = hvac.dhw_tank_state().is_some();
+        Self {
+            inner: Mutex::new(SimulatedInner {
+                hvac,
+                zone,
+                mode: ThermalLoadMode::Normal,
+                outdoor_temp_c,
+                dhw_draw_liters_per_day,
+                power_w: 0.0,
+                last_update: std::time::Instant::now(),
+            }),
+            caps: ThermalLoadCapabilities {
+                rated_power_kw,
+                has_dhw_tank,
+                supports_blocking: true,
+            },
+            nominal_voltage_v: 230.0,
+        }
+    }
+
+    pub async fn set_outdoor_temp_c(&self, temp_c: f64) {
+        self.inner.lock().await.outdoor_temp_c = temp_c;
+    }
+
+    /// Advance the house and heat pump by `dt_seconds`
+    pub async fn step(&self, dt_seconds: f64) {
+        let mut inner = self.inner.lock().await;
+        inner.step(dt_seconds, self.nominal_voltage_v);
+    }
+}
+
+impl SimulatedInner {
+    fn step(&mut self, dt_seconds: f64, nominal_voltage_v: f64) {
+        let indoor = self.zone.indoor_temp_c();
+        let draw_liters = self.dhw_draw_liters_per_day * dt_seconds / 86_400.0;
+        let (power_w, heat_w) = match self.mode {
+            ThermalLoadMode::Blocked => (0.0, 0.0),
+            mode => {
+                let offset = match mode {
+                    ThermalLoadMode::Boost => SIM_BOOST_OFFSET_K,
+                    ThermalLoadMode::ForcedOn => SIM_FORCED_OFFSET_K,
+                    _ => 0.0,
+                };
+                let result = self.hvac.step_extended(
+                    dt_seconds,
+                    indoor - offset,
+                    self.outdoor_temp_c,
+                    draw_liters,
+                );
+                (
+                    result.load.total_power_kw(nominal_voltage_v) * 1000.0,
+                    result.house_heat_output_kw * 1000.0,
+                )
+            }
+        };
+        self.zone.step(dt_seconds, self.outdoor_temp_c, heat_w, 0.0);
+        self.power_w = power_w;
+    }
+}
+
+#[async_trait]
+impl FlexibleThermalLoad for SimulatedThermalLoad {
+    async fn read_state(&self) -> Result<ThermalLoadState> {
+        let mut inner = self.inner.lock().await;
+        let elapsed = inner.last_update.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            inner.step(elapsed.min(SIM_MAX_STEP_SECONDS), self.nominal_voltage_v);
+            inner.last_update = std::time::Instant::now();
+        }
+        Ok(ThermalLoadState {
+            mode: inner.mode,
+            indoor_temp_c: Some(inner.zone.indoor_temp_c()),
+            dhw_temp_c: inner.hvac.dhw_tank_state().map(|tank| tank.temp_c),
+            outdoor_temp_c: Some(inner.outdoor_temp_c),
+            power_w: Some(inner.power_w),
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn set_mode(&self, mode: ThermalLoadMode) -> Result<()> {
+        self.inner.lock().await.mode = mode;
+        Ok(())
+    }
+
+    fn capabilities(&self) -> ThermalLoadCapabilities {
+        self.caps.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::hvac::{DhwTankState, GeothermalHeatPump, GeothermalHeatPumpConfig};
+    use crate::simulation::thermal::ThermalZoneConfig;
+
+    fn heat_pump() -> SimulatedThermalLoad {
+        let hvac = GeothermalHeatPump::with_dhw_tank(
+            GeothermalHeatPumpConfig::default(),
+            DhwTankState::default(),
+        );
+        SimulatedThermalLoad::new(
+            Box::new(hvac),
+            ThermalZone::new(ThermalZoneConfig::default(), 21.0),
+            3.15,
+            -5.0,
+            0.0,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_modes_shift_indoor_temperature() {
+        let blocked = heat_pump();
+        blocked.set_mode(ThermalLoadMode::Blocked).await.unwrap();
+        let boosted = heat_pump();
+        boosted.set_mode(ThermalLoadMode::Boost).await.unwrap();
+
+        for _ in 0..(3 * 60) {
+            blocked.step(60.0).await;
+            boosted.step(60.0).await;
+        }
+        let blocked = blocked.read_state().await.unwrap();
+        let boosted = boosted.read_state().await.unwrap();
+
+        assert_eq!(blocked.power_w, Some(0.0));
+        assert!(blocked.indoor_temp_c.unwrap() < 20.8);
+        assert!(boosted.indoor_temp_c.unwrap() > 21.5);
+        assert!(boosted.dhw_temp_c.is_some());
+    }
+
+    #[test]
+    fn test_sg_ready_relay_states() {
+        assert_eq!(ThermalLoadMode::Blocked.sg_ready_relays(), (true, false));
+        assert_eq!(ThermalLoadMode::Normal.sg_ready_relays(), (false, false));
+        assert_eq!(ThermalLoadMode::Boost.sg_ready_relays(), (false, true));
+        assert_eq!(ThermalLoadMode::ForcedOn.sg_ready_relays(), (true, true));
+    }
+}
@@ -4,7 +4,8 @@ use async_trait::async_trait;
 use chrono::{Utc, TimeZone, Timelike};
 use uuid::Uuid;
 
-use crate::domain::ConsumptionPoint;
+use super::BASELINE_STEP_MINUTES;
+use crate::domain::{ConsumptionPoint, MAX_HORIZON_HOURS};
 
 #[cfg(feature = "ml")]
 use crate::forecast::features::{normalize_features_cyclical, FeatureExtractor};
@@ -38,11 +39,13 @@ impl ConsumptionForecaster for SimpleConsumptionForecaster {
             .with_ymd_and_hms(now.year(), now.month(), now.day(), 0, 0, 0)
             .unwrap();
 
-        let mut out = Vec::with_capacity(24);
-        for h in 0..24 {
-            let t0 = start + chrono::Duration::hours(h);
-            let t1 = start + chrono::Duration::hours(h + 1);
-            let hh = t0.hour() as f64;
+        // Quarter-hour points over the longest horizon; coarser grids average them
+        let periods = MAX_HORIZON_HOURS * 60 / BASELINE_STEP_MINUTES;
+        let mut out = Vec::with_capacity(periods as usize);
+        for i in 0..periods {
+            let t0 = start + chrono::Duration::minutes(i * BASELINE_STEP_MINUTES);
+            let t1 = t0 + chrono::Duration::minutes(BASELINE_STEP_MINUTES);
+            let hh = t0.hour() as f64 + t0.minute() as f64 / 60.0;
 
             let base = 0.6;
             let morning = bump(hh, 7.5, 1.5) * 1.0;
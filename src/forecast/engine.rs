@@ -1,15 +1,19 @@
 #![allow(dead_code)]
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use uuid::Uuid;
 
 use super::{ConsumptionForecaster, PriceForecaster, ProductionForecaster};
-use crate::domain::{Forecast24h, PriceArea};
+use crate::domain::{max_horizon, Forecast24h, PriceArea};
 
 pub struct ForecastEngine {
     pub price_forecaster: Box<dyn PriceForecaster>,
     pub consumption_forecaster: Box<dyn ConsumptionForecaster>,
     pub production_forecaster: Box<dyn ProductionForecaster>,
+    /// Period length all series are resampled to
+    step: Duration,
+    /// How far ahead the forecast reaches from the current period
+    horizon: Duration,
 }
 
 impl ForecastEngine {
@@ -22,9 +26,19 @@ impl ForecastEngine {
             price_forecaster: price,
             consumption_forecaster: cons,
             production_forecaster: prod,
+            step: Duration::hours(1),
+            horizon: Duration::hours(24),
         }
     }
 
+    /// Resample forecasts to `step_minutes` periods over `horizon_hours`
+    /// (at most 48 h)
+    pub fn with_resolution(mut self, step_minutes: u32, horizon_hours: u32) -> Self {
+        self.step = Duration::minutes(step_minutes.max(1) as i64);
+        self.horizon = Duration::hours(horizon_hours.max(1) as i64).min(max_horizon());
+        self
+    }
+
     pub async fn get_forecast_24h(
         &self,
         area: PriceArea,
@@ -53,17 +67,12 @@ impl ForecastEngine {
                     error=%e,
                     "Consumption forecast failed, using fallback (flat 2kW load)"
                 );
-                // Fallback: assume constant 2kW load for next 24 hours
-                let mut fallback = Vec::new();
-                let now = Utc::now();
-                for i in 0..24 {
-                    fallback.push(crate::domain::ConsumptionPoint {
-                        time_start: now + chrono::Duration::hours(i),
-                        time_end: now + chrono::Duration::hours(i + 1),
-                        load_kw: 2.0,
-                    });
-                }
-                fallback
+                // Fallback: assume constant 2kW load over the horizon
+                vec![crate::domain::ConsumptionPoint {
+                    time_start: generated_at,
+                    time_end: generated_at + self.horizon,
+                    load_kw: 2.0,
+                }]
             }
         };
 
@@ -76,25 +85,22 @@ impl ForecastEngine {
                     "Production forecast failed, using fallback (zero production)"
                 );
                 // Fallback: assume no solar production (conservative)
-                let mut fallback = Vec::new();
-                let now = Utc::now();
-                for i in 0..24 {
-                    fallback.push(crate::domain::ProductionPoint {
-                        time_start: now + chrono::Duration::hours(i),
-                        time_end: now + chrono::Duration::hours(i + 1),
-                        pv_kw: 0.0,
-                    });
-                }
-                fallback
+                vec![crate::domain::ProductionPoint {
+                    time_start: generated_at,
+                    time_end: generated_at + self.horizon,
+                    pv_kw: 0.0,
+                }]
             }
         };
 
-        Ok(Forecast24h {
+        // Providers differ in resolution, so all series go on one grid
+        let forecast = Forecast24h {
             area,
             generated_at,
             prices,
             consumption,
             production,
-        })
+        };
+        Ok(forecast.resampled(generated_at, self.step, self.horizon))
     }
 }
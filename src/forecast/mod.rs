@@ -12,3 +12,6 @@ pub use metrics::*;
 pub use prices::*;
 pub use production::*;
 pub use weather::*;
+
+/// Period length of the built-in load and PV baselines
+pub const BASELINE_STEP_MINUTES: i64 = 15;
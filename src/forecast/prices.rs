@@ -70,9 +70,7 @@ impl ElprisetJustNuPriceForecaster {
         })
     }
 
-    fn url_for_today(&self, area: PriceArea) -> String {
-        let now = Utc::now();
-        let date = now.date_naive();
+    fn url_for_date(&self, area: PriceArea, date: chrono::NaiveDate) -> String {
         format!(
             "{}/api/v1/prices/{:04}/{:02}-{:02}_{}.json",
             self.base_url.trim_end_matches('/'),
@@ -82,6 +80,33 @@ impl ElprisetJustNuPriceForecaster {
             area
         )
     }
+
+    /// Prices of one day, in whatever resolution the API publishes
+    async fn fetch_day(&self, area: PriceArea, date: chrono::NaiveDate) -> Result<Vec<PricePoint>> {
+        let url = self.url_for_date(area, date);
+        let resp = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context("price GET failed")?;
+        let status = resp.status();
+        let body = resp.text().await.context("price read failed")?;
+        if !status.is_success() {
+            anyhow::bail!("price API error: HTTP {status}: {body}");
+        }
+
+        let raw: Vec<RawPrice> = serde_json::from_str(&body).context("price JSON parse failed")?;
+        Ok(raw
+            .into_iter()
+            .map(|r| PricePoint {
+                time_start: r.time_start,
+                time_end: r.time_end,
+                price_sek_per_kwh: r.sek_per_kwh,
+                export_price_sek_per_kwh: None, // Use default (40% of import price)
+            })
+            .collect())
+    }
 }
 
 #[async_trait]
@@ -102,30 +127,16 @@ impl PriceForecaster for ElprisetJustNuPriceForecaster {
         }
 
         // CRITICAL FIX: Try API first, fallback to database on failure
-        let url = self.url_for_today(area);
+        let today = Utc::now().date_naive();
         let api_result = async {
-            let resp = self
-                .client
-                .get(&url)
-                .send()
-                .await
-                .context("price GET failed")?;
-            let status = resp.status();
-            let body = resp.text().await.context("price read failed")?;
-            if !status.is_success() {
-                anyhow::bail!("price API error: HTTP {status}: {body}");
+            let mut points = self.fetch_day(area, today).await?;
+            // Tomorrow's prices are published around 13:00, before that the API has none
+            if let Some(tomorrow) = today.succ_opt() {
+                match self.fetch_day(area, tomorrow).await {
+                    Ok(next_day) => points.extend(next_day),
+                    Err(e) => tracing::debug!(error=%e, "No prices for tomorrow yet"),
+                }
             }
-
-            let raw: Vec<RawPrice> = serde_json::from_str(&body).context("price JSON parse failed")?;
-            let points = raw
-                .into_iter()
-                .map(|r| PricePoint {
-                    time_start: r.time_start,
-                    time_end: r.time_end,
-                    price_sek_per_kwh: r.sek_per_kwh,
-                    export_price_sek_per_kwh: None, // Use default (40% of import price)
-                })
-                .collect::<Vec<_>>();
             Ok::<Vec<PricePoint>, anyhow::Error>(points)
         }.await;
 
@@ -153,7 +164,7 @@ impl PriceForecaster for ElprisetJustNuPriceForecaster {
                 if let Some(ref db_repo) = self.db_repo {
                     let now = Utc::now();
                     let start = now.with_timezone(&chrono::FixedOffset::east_opt(0).unwrap());
-                    let end = (now + chrono::Duration::hours(24)).with_timezone(&chrono::FixedOffset::east_opt(0).unwrap());
+                    let end = (now + crate::domain::max_horizon()).with_timezone(&chrono::FixedOffset::east_opt(0).unwrap());
 
                     match db_repo.find_range(start, end, area).await {
                         Ok(db_points) if !db_points.is_empty() => {
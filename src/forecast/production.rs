@@ -4,7 +4,8 @@ use async_trait::async_trait;
 use chrono::{Datelike, Utc, TimeZone, Timelike};
 use uuid::Uuid;
 
-use crate::domain::ProductionPoint;
+use super::BASELINE_STEP_MINUTES;
+use crate::domain::{ProductionPoint, MAX_HORIZON_HOURS};
 
 #[async_trait]
 pub trait ProductionForecaster: Send + Sync {
@@ -37,11 +38,13 @@ impl ProductionForecaster for SimpleProductionForecaster {
             .with_ymd_and_hms(now.year(), now.month(), now.day(), 0, 0, 0)
             .unwrap();
 
-        let mut out = Vec::with_capacity(24);
-        for h in 0..24 {
-            let t0 = start + chrono::Duration::hours(h);
-            let t1 = start + chrono::Duration::hours(h + 1);
-            let hh = t0.hour() as f64;
+        // Quarter-hour points over the longest horizon; coarser grids average them
+        let periods = MAX_HORIZON_HOURS * 60 / BASELINE_STEP_MINUTES;
+        let mut out = Vec::with_capacity(periods as usize);
+        for i in 0..periods {
+            let t0 = start + chrono::Duration::minutes(i * BASELINE_STEP_MINUTES);
+            let t1 = t0 + chrono::Duration::minutes(BASELINE_STEP_MINUTES);
+            let hh = t0.hour() as f64 + t0.minute() as f64 / 60.0;
 
             let pv = if hh < self.sunrise || hh > self.sunset {
                 0.0
@@ -1,11 +1,15 @@
 #![allow(dead_code)]
 use std::sync::Arc;
 
+use tokio::sync::RwLock;
+
 use crate::domain::{
-    Battery, BatteryCapabilities, BatteryState, BatteryStatus, EvCharger,
-    Inverter, InverterCapabilities, InverterMode, InverterState, InverterStatus,
-    SimulatedBattery, SimulatedEvCharger, SimulatedInverter,
+    Battery, BatteryCapabilities, BatteryState, BatteryStatus, ControllableLoad, EvCharger,
+    FlexibleThermalLoad, GridMeter, Inverter, InverterCapabilities, InverterMode, InverterState,
+    InverterStatus, SimulatedBattery, SimulatedControllableLoad, SimulatedEvCharger,
+    SimulatedGridMeter, SimulatedInverter, SimulatedThermalLoad,
 };
+use crate::simulation::environment::{Environment, EnvironmentConfig};
 
 /// Hardware mode configuration
 ///
@@ -47,6 +51,40 @@ impl DeviceFactory {
         initial_soc: f64,
         ambient_temp_c: f64,
     ) -> Arc<dyn Battery> {
+        self.create_battery_device(caps, initial_soc, ambient_temp_c, None, None)
+            .await
+    }
+
+    /// Create one pack of a multi-battery site
+    ///
+    /// Modbus packs use their own unit id and device profile when set.
+    pub async fn create_battery_pack(
+        &self,
+        caps: BatteryCapabilities,
+        pack: &crate::config::BatteryPackConfig,
+    ) -> Arc<dyn Battery> {
+        tracing::info!("Creating battery pack '{}'", pack.id);
+        self.create_battery_device(
+            caps,
+            pack.battery.initial_soc_percent,
+            pack.battery.ambient_temp_c,
+            pack.unit_id,
+            pack.profile.as_deref(),
+        )
+        .await
+    }
+
+    async fn create_battery_device(
+        &self,
+        caps: BatteryCapabilities,
+        initial_soc: f64,
+        ambient_temp_c: f64,
+        unit_id: Option<u8>,
+        profile: Option<&str>,
+    ) -> Arc<dyn Battery> {
+        #[cfg(not(feature = "modbus"))]
+        let _ = (unit_id, profile);
+
         match self.mode {
             HardwareMode::Simulated => {
                 let initial = BatteryState {
@@ -66,14 +104,50 @@ impl DeviceFactory {
                         // For now, use localhost with configured port
                         // TODO: Add device discovery or explicit host configuration
                         let addr = format!("127.0.0.1:{}", modbus_config.default_port);
-                        match crate::hardware::modbus::ModbusBattery::new(
-                            &addr,
-                            modbus_config.default_unit_id,
-                        )
-                        .await
-                        {
+                        let unit_id = unit_id.unwrap_or(modbus_config.default_unit_id);
+                        let battery = async {
+                            use crate::modbus::client::{ModbusClient, Transport};
+                            use crate::modbus::register_map::{GenericBatteryRegisterMap, RegisterMap};
+
+                            let transport = Transport::from_config(modbus_config, &addr)?;
+                            let profile = match profile.or(modbus_config.battery_profile.as_deref()) {
+                                Some(name) => {
+                                    let profile = crate::hardware::modbus::DeviceProfile::find(
+                                        &modbus_config.profiles_dir,
+                                        name,
+                                    )?;
+                                    tracing::info!(
+                                        "Using device profile {} ({} {})",
+                                        profile.name,
+                                        profile.device.manufacturer,
+                                        profile.device.model
+                                    );
+                                    Some(profile)
+                                }
+                                None => None,
+                            };
+                            let timeout_ms = profile
+                                .as_ref()
+                                .map_or(modbus_config.timeout_ms, |p| p.modbus.timeout_ms);
+                            let client = ModbusClient::connect_transport(
+                                transport,
+                                unit_id,
+                                std::time::Duration::from_millis(timeout_ms),
+                            )
+                            .await?;
+                            let register_map: Box<dyn RegisterMap> = match profile {
+                                Some(profile) => Box::new(profile.register_map),
+                                None => Box::new(GenericBatteryRegisterMap),
+                            };
+                            crate::hardware::modbus::ModbusBattery::with_client(client, register_map).await
+                        }
+                        .await;
+                        match battery {
                             Ok(battery) => {
-                                tracing::info!("Successfully connected to Modbus battery at {}", addr);
+                                tracing::info!(
+                                    "Successfully connected to Modbus battery via {:?}",
+                                    modbus_config.transport
+                                );
                                 return Arc::new(battery);
                             }
                             Err(e) => {
@@ -130,66 +204,392 @@ impl DeviceFactory {
         }
     }
 
-    /// Create an inverter instance based on hardware mode
-    pub fn create_inverter(&self, caps: InverterCapabilities) -> Arc<dyn Inverter> {
-        match self.mode {
-            HardwareMode::Simulated => {
-                let initial = InverterState {
-                    mode: InverterMode::GridTied,
-                    pv_power_w: 0.0,
-                    ac_output_power_w: 0.0,
-                    dc_input_power_w: 0.0,
-                    grid_frequency_hz: 50.0,
-                    ac_voltage_v: 230.0,
-                    dc_voltage_v: 400.0,
-                    temperature_c: 35.0,
-                    efficiency_percent: 97.0,
-                    status: InverterStatus::Normal,
-                    daily_energy_kwh: 0.0,
-                    total_energy_kwh: 0.0,
+    /// Create an OCPP charger that dials into the given central system
+    ///
+    /// The charger is registered with the central system and stays offline
+    /// until the wallbox with the configured charge point ID connects.
+    pub async fn create_ocpp_ev_charger(
+        &self,
+        central_system: &crate::ocpp::central_system::CentralSystem,
+    ) -> Arc<dyn EvCharger> {
+        use crate::hardware::ocpp::OcppEvChargerConfig;
+
+        let mut charger_config = OcppEvChargerConfig::default();
+        if let Some(ocpp) = self.config.as_ref().and_then(|c| c.hardware.ocpp.as_ref()) {
+            charger_config.charge_point_id = ocpp.charge_point_id.clone();
+        }
+
+        Self::register_ocpp_charger(central_system, charger_config).await
+    }
+
+    /// Create one charger of a multi-charger site
+    ///
+    /// With a central system the charger is an OCPP wallbox identified by
+    /// `charge_point_id`; otherwise it is simulated.
+    pub async fn create_site_ev_charger(
+        &self,
+        charger: &crate::config::EvChargerConfig,
+        central_system: Option<&crate::ocpp::central_system::CentralSystem>,
+    ) -> Arc<dyn EvCharger> {
+        use crate::domain::{ChargerCapabilities, ConnectorType};
+        use crate::hardware::ocpp::OcppEvChargerConfig;
+
+        let caps = ChargerCapabilities {
+            max_current_amps: charger.max_current_a,
+            min_current_amps: 6.0,
+            phases: charger.phases,
+            voltage_v: 230.0,
+            connector_type: ConnectorType::Type2,
+            power_max_kw: charger.max_current_a * 230.0 * charger.phases as f64 / 1000.0,
+            supports_v2g: false,
+        };
+
+        match central_system {
+            Some(central_system) => {
+                let charger_config = OcppEvChargerConfig {
+                    charge_point_id: charger
+                        .charge_point_id
+                        .clone()
+                        .unwrap_or_else(|| charger.id.clone()),
+                    capabilities: caps,
+                    ..OcppEvChargerConfig::default()
                 };
-                Arc::new(SimulatedInverter::new(initial, caps))
+                Self::register_ocpp_charger(central_system, charger_config).await
             }
+            None => {
+                tracing::info!("Creating simulated EV charger '{}'", charger.id);
+                Arc::new(SimulatedEvCharger::with_capabilities(caps))
+            }
+        }
+    }
+
+    async fn register_ocpp_charger(
+        central_system: &crate::ocpp::central_system::CentralSystem,
+        charger_config: crate::hardware::ocpp::OcppEvChargerConfig,
+    ) -> Arc<dyn EvCharger> {
+        use crate::hardware::ocpp::OcppEvCharger;
+
+        tracing::info!(
+            "Waiting for OCPP charge point {} to connect",
+            charger_config.charge_point_id
+        );
+        let charger = Arc::new(OcppEvCharger::new(charger_config));
+        central_system.register_charger(Arc::clone(&charger)).await;
+        charger
+    }
+
+    /// Create an inverter instance based on hardware mode
+    ///
+    /// `caps` only applies to simulated inverters; real ones report their own.
+    pub async fn create_inverter(&self, caps: InverterCapabilities) -> Arc<dyn Inverter> {
+        let initial = InverterState {
+            mode: InverterMode::GridTied,
+            pv_power_w: 0.0,
+            ac_output_power_w: 0.0,
+            dc_input_power_w: 0.0,
+            grid_frequency_hz: 50.0,
+            ac_voltage_v: 230.0,
+            dc_voltage_v: 400.0,
+            temperature_c: 35.0,
+            efficiency_percent: 97.0,
+            status: InverterStatus::Normal,
+            daily_energy_kwh: 0.0,
+            total_energy_kwh: 0.0,
+        };
+        match self.mode {
+            HardwareMode::Simulated => Arc::new(SimulatedInverter::new(initial, caps)),
             #[cfg(feature = "modbus")]
             HardwareMode::Modbus => {
-                // TODO: Implement Modbus inverter
-                tracing::warn!("Modbus inverter not yet implemented, falling back to simulated");
-                let initial = InverterState {
-                    mode: InverterMode::GridTied,
-                    pv_power_w: 0.0,
-                    ac_output_power_w: 0.0,
-                    dc_input_power_w: 0.0,
-                    grid_frequency_hz: 50.0,
-                    ac_voltage_v: 230.0,
-                    dc_voltage_v: 400.0,
-                    temperature_c: 35.0,
-                    efficiency_percent: 97.0,
-                    status: InverterStatus::Normal,
-                    daily_energy_kwh: 0.0,
-                    total_energy_kwh: 0.0,
-                };
+                if let Some(modbus_config) = self.config.as_ref().and_then(|c| c.hardware.modbus.as_ref()) {
+                    match Self::create_modbus_inverter(modbus_config).await {
+                        Ok(inverter) => return inverter,
+                        Err(e) => {
+                            tracing::error!(
+                                error = %e,
+                                "Failed to connect to Modbus inverter, falling back to simulated"
+                            );
+                        }
+                    }
+                } else {
+                    tracing::warn!("Modbus inverter not configured, falling back to simulated");
+                }
                 Arc::new(SimulatedInverter::new(initial, caps))
             }
             HardwareMode::Mock => {
                 tracing::warn!("Mock inverter not yet implemented, falling back to simulated");
-                let initial = InverterState {
-                    mode: InverterMode::GridTied,
-                    pv_power_w: 0.0,
-                    ac_output_power_w: 0.0,
-                    dc_input_power_w: 0.0,
-                    grid_frequency_hz: 50.0,
-                    ac_voltage_v: 230.0,
-                    dc_voltage_v: 400.0,
-                    temperature_c: 35.0,
-                    efficiency_percent: 97.0,
-                    status: InverterStatus::Normal,
-                    daily_energy_kwh: 0.0,
-                    total_energy_kwh: 0.0,
-                };
                 Arc::new(SimulatedInverter::new(initial, caps))
             }
         }
     }
+
+    #[cfg(feature = "modbus")]
+    async fn create_modbus_inverter(
+        modbus_config: &crate::config::ModbusConfig,
+    ) -> anyhow::Result<Arc<dyn Inverter>> {
+        use crate::config::InverterRegisterLayout;
+        use crate::hardware::modbus::{ModbusInverter, SunSpecInverter};
+        use crate::modbus::client::{ModbusClient, Transport};
+        use crate::modbus::register_map::{GenericInverterRegisterMap, HuaweiSun2000RegisterMap};
+        use crate::modbus::sunspec::SunSpecReader;
+
+        // Same host as the battery; hybrid inverters usually front both
+        let addr = format!("127.0.0.1:{}", modbus_config.default_port);
+        let unit_id = modbus_config.inverter_unit_id.unwrap_or(modbus_config.default_unit_id);
+        let client = ModbusClient::connect_transport(
+            Transport::from_config(modbus_config, &addr)?,
+            unit_id,
+            std::time::Duration::from_millis(modbus_config.timeout_ms),
+        )
+        .await?;
+
+        let inverter: Arc<dyn Inverter> = match modbus_config.inverter_registers {
+            InverterRegisterLayout::Sunspec => {
+                Arc::new(SunSpecInverter::new(SunSpecReader::new(client).await?).await?)
+            }
+            InverterRegisterLayout::Auto => match SunSpecReader::new(client.clone()).await {
+                Ok(reader) if reader.map().is_inverter() => {
+                    tracing::info!("Inverter speaks SunSpec, using its model map");
+                    Arc::new(SunSpecInverter::new(reader).await?)
+                }
+                _ => {
+                    tracing::info!("No SunSpec inverter found, using the generic register map");
+                    Arc::new(ModbusInverter::with_client(client, Box::new(GenericInverterRegisterMap)).await?)
+                }
+            },
+            InverterRegisterLayout::Generic => {
+                Arc::new(ModbusInverter::with_client(client, Box::new(GenericInverterRegisterMap)).await?)
+            }
+            InverterRegisterLayout::HuaweiSun2000 => {
+                Arc::new(ModbusInverter::with_client(client, Box::new(HuaweiSun2000RegisterMap)).await?)
+            }
+        };
+        tracing::info!(
+            "Successfully connected to Modbus inverter via {:?}",
+            modbus_config.transport
+        );
+        Ok(inverter)
+    }
+
+    /// Create the grid meter, if one is available in this hardware mode
+    ///
+    /// Simulated meters read `environment`'s grid; without one they see an
+    /// idle default grid. On real hardware a configured HAN/P1 port wins over
+    /// a Modbus meter; with neither, None is returned so callers fall back to
+    /// their own estimates rather than acting on made-up measurements.
+    pub async fn create_grid_meter(
+        &self,
+        environment: Option<Arc<RwLock<Environment>>>,
+    ) -> Option<Arc<dyn GridMeter>> {
+        let han_config = self
+            .config
+            .as_ref()
+            .and_then(|c| c.hardware.han.as_ref())
+            .filter(|_| self.mode != HardwareMode::Simulated);
+        if let Some(han_config) = han_config {
+            match crate::hardware::han::HanGridMeter::from_config(han_config) {
+                Ok(meter) => return Some(Arc::new(meter)),
+                Err(e) => tracing::error!(error = %e, "Invalid HAN port configuration"),
+            }
+        }
+
+        let simulated = || -> Arc<dyn GridMeter> {
+            let environment = environment.unwrap_or_else(|| {
+                Arc::new(RwLock::new(Environment::new(EnvironmentConfig::default())))
+            });
+            Arc::new(SimulatedGridMeter::new(environment))
+        };
+        match self.mode {
+            HardwareMode::Simulated => Some(simulated()),
+            #[cfg(feature = "modbus")]
+            HardwareMode::Modbus => {
+                let modbus_config = self.config.as_ref().and_then(|c| c.hardware.modbus.as_ref())?;
+                let model = modbus_config.grid_meter?;
+                match Self::create_modbus_grid_meter(modbus_config, model).await {
+                    Ok(meter) => {
+                        tracing::info!("Successfully connected to {:?} grid meter", model);
+                        Some(meter)
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            error = %e,
+                            "Failed to connect to grid meter, falling back to estimated grid flows"
+                        );
+                        None
+                    }
+                }
+            }
+            HardwareMode::Mock => {
+                tracing::warn!("Mock grid meter not yet implemented, falling back to simulated");
+                Some(simulated())
+            }
+        }
+    }
+
+    #[cfg(feature = "modbus")]
+    async fn create_modbus_grid_meter(
+        modbus_config: &crate::config::ModbusConfig,
+        model: crate::config::GridMeterModel,
+    ) -> anyhow::Result<Arc<dyn GridMeter>> {
+        use crate::config::GridMeterModel;
+        use crate::hardware::modbus::{ModbusGridMeter, SunSpecMeter};
+        use crate::modbus::client::{ModbusClient, Transport};
+        use crate::modbus::register_map::{Em24RegisterMap, Sdm630RegisterMap};
+        use crate::modbus::sunspec::SunSpecReader;
+
+        let addr = format!("127.0.0.1:{}", modbus_config.default_port);
+        let unit_id = modbus_config.grid_meter_unit_id.unwrap_or(modbus_config.default_unit_id);
+        let client = ModbusClient::connect_transport(
+            Transport::from_config(modbus_config, &addr)?,
+            unit_id,
+            std::time::Duration::from_millis(modbus_config.timeout_ms),
+        )
+        .await?;
+
+        Ok(match model {
+            GridMeterModel::Sdm630 => Arc::new(ModbusGridMeter::with_client(client, Box::new(Sdm630RegisterMap))?),
+            GridMeterModel::Em24 => Arc::new(ModbusGridMeter::with_client(client, Box::new(Em24RegisterMap))?),
+            GridMeterModel::Sunspec => Arc::new(SunSpecMeter::new(SunSpecReader::new(client).await?)?),
+        })
+    }
+
+    /// Create the heat pump steered as a flexible load, if enabled
+    ///
+    /// Simulation runs a ground source heat pump in a simulated house. Real
+    /// units need Modbus mode; when they cannot be reached None is returned
+    /// and heating is left to the heat pump alone.
+    pub async fn create_thermal_load(&self) -> Option<Arc<dyn FlexibleThermalLoad>> {
+        let config = self.config.as_ref().map(|c| &c.heat_pump).filter(|hp| hp.enabled)?;
+        match self.mode {
+            #[cfg(feature = "modbus")]
+            HardwareMode::Modbus if config.kind != crate::config::HeatPumpKind::Simulated => {
+                let modbus_config = self.config.as_ref().and_then(|c| c.hardware.modbus.as_ref())?;
+                match Self::create_modbus_thermal_load(modbus_config, config).await {
+                    Ok(heat_pump) => {
+                        tracing::info!("Successfully connected to {:?} heat pump", config.kind);
+                        Some(heat_pump)
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to connect to heat pump, pre-heating disabled");
+                        None
+                    }
+                }
+            }
+            _ => {
+                if config.kind != crate::config::HeatPumpKind::Simulated {
+                    tracing::warn!("{:?} heat pump needs Modbus hardware mode, simulating it", config.kind);
+                }
+                Some(Arc::new(Self::create_simulated_thermal_load(config)))
+            }
+        }
+    }
+
+    /// Create a switchable load
+    ///
+    /// Shelly switches are reached over HTTP in any mode, like OCPP
+    /// wallboxes. Relay coils need Modbus mode; an unreachable relay module
+    /// gives None and the load is left out.
+    pub async fn create_controllable_load(
+        &self,
+        config: &crate::config::ControllableLoadConfig,
+    ) -> Option<Arc<dyn ControllableLoad>> {
+        use crate::config::ControllableLoadKind;
+
+        match (config.kind, self.mode) {
+            (ControllableLoadKind::Shelly, _) => {
+                let address = config.address.as_deref()?;
+                tracing::info!("Using Shelly switch at {} for load '{}'", address, config.id);
+                Some(Arc::new(crate::hardware::shelly::ShellySwitch::new(address, config.switch_id)))
+            }
+            #[cfg(feature = "modbus")]
+            (ControllableLoadKind::ModbusCoil, HardwareMode::Modbus) => {
+                let modbus_config = self.config.as_ref().and_then(|c| c.hardware.modbus.as_ref())?;
+                match Self::create_modbus_relay_load(modbus_config, config).await {
+                    Ok(load) => Some(load),
+                    Err(e) => {
+                        tracing::error!(load = %config.id, error = %e, "Failed to connect to relay module, load left out");
+                        None
+                    }
+                }
+            }
+            (kind, _) => {
+                if kind != ControllableLoadKind::Simulated {
+                    tracing::warn!("Load '{}' needs Modbus hardware mode, simulating it", config.id);
+                }
+                Some(Arc::new(SimulatedControllableLoad::new(config.power_kw)))
+            }
+        }
+    }
+
+    #[cfg(feature = "modbus")]
+    async fn create_modbus_relay_load(
+        modbus_config: &crate::config::ModbusConfig,
+        config: &crate::config::ControllableLoadConfig,
+    ) -> anyhow::Result<Arc<dyn ControllableLoad>> {
+        use crate::hardware::modbus::ModbusRelayLoad;
+        use crate::modbus::client::{ModbusClient, Transport};
+
+        let addr = format!("127.0.0.1:{}", modbus_config.default_port);
+        let unit_id = config.unit_id.unwrap_or(modbus_config.default_unit_id);
+        let client = ModbusClient::connect_transport(
+            Transport::from_config(modbus_config, &addr)?,
+            unit_id,
+            std::time::Duration::from_millis(modbus_config.timeout_ms),
+        )
+        .await?;
+        Ok(Arc::new(ModbusRelayLoad::with_client(client, config.coil)))
+    }
+
+    fn create_simulated_thermal_load(config: &crate::config::HeatPumpConfig) -> SimulatedThermalLoad {
+        use crate::simulation::hvac::{DhwTankState, GeothermalHeatPump, GeothermalHeatPumpConfig};
+        use crate::simulation::thermal::{ThermalZone, ThermalZoneConfig};
+
+        let hvac_config = GeothermalHeatPumpConfig {
+            compressor_power_kw: config.rated_power_kw,
+            cop_at_nominal: config.cop,
+            target_temp_c: config.setpoint_c,
+            ..Default::default()
+        };
+        let tank = DhwTankState {
+            temp_c: config.dhw_min_c + 5.0,
+            capacity_liters: config.dhw_tank_liters,
+            min_temp_c: config.dhw_min_c,
+            ..Default::default()
+        };
+        SimulatedThermalLoad::new(
+            Box::new(GeothermalHeatPump::with_dhw_tank(hvac_config, tank)),
+            ThermalZone::new(ThermalZoneConfig::default(), config.setpoint_c),
+            config.rated_power_kw,
+            config.fallback_outdoor_temp_c,
+            config.dhw_draw_liters_per_day,
+        )
+    }
+
+    #[cfg(feature = "modbus")]
+    async fn create_modbus_thermal_load(
+        modbus_config: &crate::config::ModbusConfig,
+        config: &crate::config::HeatPumpConfig,
+    ) -> anyhow::Result<Arc<dyn FlexibleThermalLoad>> {
+        use crate::config::HeatPumpKind;
+        use crate::hardware::modbus::{ModbusHeatPump, SgReadyHeatPump};
+        use crate::modbus::client::{ModbusClient, Transport};
+        use crate::modbus::register_map::{NibeRegisterMap, ThermiaRegisterMap};
+
+        let addr = format!("127.0.0.1:{}", modbus_config.default_port);
+        let unit_id = config.unit_id.unwrap_or(modbus_config.default_unit_id);
+        let client = ModbusClient::connect_transport(
+            Transport::from_config(modbus_config, &addr)?,
+            unit_id,
+            std::time::Duration::from_millis(modbus_config.timeout_ms),
+        )
+        .await?;
+
+        Ok(match config.kind {
+            HeatPumpKind::SgReady => Arc::new(SgReadyHeatPump::with_client(client, config.sg_ready_coils, config.rated_power_kw)),
+            HeatPumpKind::Nibe => Arc::new(ModbusHeatPump::with_client(client, Box::new(NibeRegisterMap), config.rated_power_kw)),
+            HeatPumpKind::Thermia => Arc::new(ModbusHeatPump::with_client(client, Box::new(ThermiaRegisterMap), config.rated_power_kw)),
+            HeatPumpKind::Simulated => anyhow::bail!("simulated heat pump is not a Modbus device"),
+        })
+    }
 }
 
 impl Default for DeviceFactory {
@@ -245,7 +645,7 @@ mod tests {
             supports_frequency_regulation: true,
         };
 
-        let inverter = factory.create_inverter(caps);
+        let inverter = factory.create_inverter(caps).await;
         let state = inverter.read_state().await.unwrap();
 
         assert_eq!(state.mode, InverterMode::GridTied);
@@ -0,0 +1,230 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use super::telegram::TelegramBuffer;
+use crate::config::HanConfig;
+use crate::domain::grid_meter::{GridMeter, GridMeterReading};
+
+/// Reconnect backoff bounds
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Where telegrams come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum HanSource {
+    /// P1 cable on a local serial port (DSMR 5 and Swedish HAN run 115200 8N1)
+    Serial { path: String, baud_rate: u32 },
+    /// P1-to-TCP bridge (ser2net, ESP dongles) forwarding the raw telegrams
+    Tcp { addr: String },
+}
+
+impl HanSource {
+    pub fn from_config(config: &HanConfig) -> Result<Self> {
+        match (&config.serial_port, &config.tcp_address) {
+            (Some(path), None) => Ok(Self::Serial {
+                path: path.clone(),
+                baud_rate: config.baud_rate,
+            }),
+            (None, Some(addr)) => Ok(Self::Tcp { addr: addr.clone() }),
+            _ => bail!("HAN port needs exactly one of serial_port or tcp_address"),
+        }
+    }
+
+    async fn open(&self) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        match self {
+            Self::Tcp { addr } => {
+                let stream = tokio::net::TcpStream::connect(addr)
+                    .await
+                    .with_context(|| format!("Failed to connect to HAN bridge {}", addr))?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(feature = "han")]
+            Self::Serial { path, baud_rate } => {
+                let port = tokio_serial::SerialStream::open(&tokio_serial::new(path.as_str(), *baud_rate))
+                    .with_context(|| format!("Failed to open HAN serial port {}", path))?;
+                Ok(Box::new(port))
+            }
+            #[cfg(not(feature = "han"))]
+            Self::Serial { path, .. } => {
+                bail!("Reading HAN port {} requires the 'han' feature", path)
+            }
+        }
+    }
+}
+
+impl fmt::Display for HanSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serial { path, baud_rate } => write!(f, "{}@{}", path, baud_rate),
+            Self::Tcp { addr } => write!(f, "tcp://{}", addr),
+        }
+    }
+}
+
+type Latest = Arc<RwLock<Option<(GridMeterReading, Instant)>>>;
+
+/// Grid meter fed by the HAN/P1 port of a DSMR 5 or Swedish HAN smart meter
+///
+/// The meter pushes a telegram every 1-10 s; a background task keeps the
+/// latest one and reconnects when the port goes quiet or drops.
+pub struct HanGridMeter {
+    source: HanSource,
+    latest: Latest,
+    max_age: Duration,
+    task: JoinHandle<()>,
+}
+
+impl HanGridMeter {
+    /// Start reading telegrams; readings older than `max_age` are refused
+    pub fn spawn(source: HanSource, max_age: Duration) -> Self {
+        let latest: Latest = Arc::new(RwLock::new(None));
+        let task = tokio::spawn(run(source.clone(), Arc::clone(&latest), max_age));
+        info!("Reading HAN telegrams from {}", source);
+        Self {
+            source,
+            latest,
+            max_age,
+            task,
+        }
+    }
+
+    pub fn from_config(config: &HanConfig) -> Result<Self> {
+        Ok(Self::spawn(
+            HanSource::from_config(config)?,
+            Duration::from_secs(config.max_age_secs),
+        ))
+    }
+}
+
+impl Drop for HanGridMeter {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[async_trait]
+impl GridMeter for HanGridMeter {
+    async fn read(&self) -> Result<GridMeterReading> {
+        match &*self.latest.read().await {
+            Some((reading, received)) if received.elapsed() <= self.max_age => Ok(reading.clone()),
+            Some((_, received)) => bail!(
+                "Last HAN telegram from {} is {:.0}s old",
+                self.source,
+                received.elapsed().as_secs_f64()
+            ),
+            None => bail!("No HAN telegram received from {} yet", self.source),
+        }
+    }
+}
+
+async fn run(source: HanSource, latest: Latest, max_age: Duration) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match source.open().await {
+            Ok(stream) => {
+                backoff = MIN_BACKOFF;
+                if let Err(e) = read_telegrams(stream, &latest, max_age).await {
+                    warn!(error = %e, "HAN port {} lost, reconnecting", source);
+                }
+            }
+            Err(e) => warn!(error = %e, "Failed to open HAN port"),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn read_telegrams(
+    mut stream: Box<dyn AsyncRead + Send + Unpin>,
+    latest: &Latest,
+    max_age: Duration,
+) -> Result<()> {
+    let mut buffer = TelegramBuffer::new();
+    let mut chunk = [0u8; 1024];
+    loop {
+        // A half-open TCP bridge never errors; silence longer than max_age means reconnect
+        let n = tokio::time::timeout(max_age, stream.read(&mut chunk))
+            .await
+            .context("No data on HAN port")??;
+        if n == 0 {
+            bail!("HAN stream closed");
+        }
+        buffer.extend(&chunk[..n]);
+
+        while let Some(telegram) = buffer.next_telegram() {
+            match telegram.and_then(|t| t.to_reading()) {
+                Ok(reading) => {
+                    debug!(power_w = reading.power_w, "HAN telegram");
+                    *latest.write().await = Some((reading, Instant::now()));
+                }
+                Err(e) => warn!(error = %e, "Dropping HAN telegram"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    const DSMR5: &[u8] = include_bytes!("../../../tests/fixtures/p1/dsmr5_three_phase.txt");
+    const DSMR5_BAD_CRC: &[u8] = include_bytes!("../../../tests/fixtures/p1/dsmr5_bad_crc.txt");
+
+    #[tokio::test]
+    async fn test_reads_telegrams_from_tcp_bridge() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(DSMR5_BAD_CRC).await.unwrap();
+            for chunk in DSMR5.chunks(100) {
+                socket.write_all(chunk).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let meter = HanGridMeter::spawn(HanSource::Tcp { addr }, Duration::from_secs(10));
+        let mut reading = meter.read().await;
+        for _ in 0..50 {
+            if reading.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            reading = meter.read().await;
+        }
+
+        let reading = reading.unwrap();
+        assert!((reading.power_w - 3482.0).abs() < 1e-9);
+        assert_eq!(reading.max_phase_current_a(), 11.0);
+    }
+
+    #[test]
+    fn test_source_requires_one_endpoint() {
+        let mut config = HanConfig {
+            serial_port: Some("/dev/ttyUSB0".into()),
+            tcp_address: None,
+            baud_rate: 115_200,
+            max_age_secs: 30,
+        };
+        assert_eq!(
+            HanSource::from_config(&config).unwrap(),
+            HanSource::Serial {
+                path: "/dev/ttyUSB0".into(),
+                baud_rate: 115_200
+            }
+        );
+
+        config.tcp_address = Some("192.168.1.40:8088".into());
+        assert!(HanSource::from_config(&config).is_err());
+    }
+}
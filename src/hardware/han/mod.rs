@@ -0,0 +1,10 @@
+//! # HAN/P1 Smart Meter Port
+//!
+//! Reads DSMR 5 (Netherlands, Belgium) and Swedish HAN telegrams from the
+//! customer port of the utility meter, over a serial cable or a TCP bridge.
+
+pub mod meter;
+pub mod telegram;
+
+pub use meter::{HanGridMeter, HanSource};
+pub use telegram::{Telegram, TelegramBuffer, TelegramError};
@@ -0,0 +1,331 @@
+//! DSMR 5 / Swedish HAN telegram parsing
+//!
+//! A telegram is a block of OBIS-tagged lines framed by a `/` header and a
+//! `!` footer carrying a CRC16 of everything in between:
+//!
+//! ```text
+//! /ISK5\2M550T-1012
+//!
+//! 1-0:1.7.0(03.482*kW)
+//! 1-0:32.7.0(229.4*V)
+//! !3C4A
+//! ```
+
+use chrono::Utc;
+use thiserror::Error;
+
+use crate::domain::grid_meter::{GridMeterReading, PhaseReading};
+
+/// Telegrams larger than this are garbage; DSMR 5 tops out around 1-2 kB
+const MAX_TELEGRAM_BYTES: usize = 8 * 1024;
+
+/// OBIS codes used for grid readings
+mod obis {
+    pub const POWER_IMPORT: &str = "1-0:1.7.0";
+    pub const POWER_EXPORT: &str = "1-0:2.7.0";
+    /// Swedish HAN reports totals; DSMR splits them per tariff
+    pub const ENERGY_IMPORT_TOTAL: &str = "1-0:1.8.0";
+    pub const ENERGY_EXPORT_TOTAL: &str = "1-0:2.8.0";
+    pub const ENERGY_IMPORT_TARIFFS: [&str; 2] = ["1-0:1.8.1", "1-0:1.8.2"];
+    pub const ENERGY_EXPORT_TARIFFS: [&str; 2] = ["1-0:2.8.1", "1-0:2.8.2"];
+    pub const PHASE_VOLTAGE: [&str; 3] = ["1-0:32.7.0", "1-0:52.7.0", "1-0:72.7.0"];
+    pub const PHASE_CURRENT: [&str; 3] = ["1-0:31.7.0", "1-0:51.7.0", "1-0:71.7.0"];
+    pub const PHASE_IMPORT: [&str; 3] = ["1-0:21.7.0", "1-0:41.7.0", "1-0:61.7.0"];
+    pub const PHASE_EXPORT: [&str; 3] = ["1-0:22.7.0", "1-0:42.7.0", "1-0:62.7.0"];
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TelegramError {
+    #[error("Telegram does not start with '/'")]
+    MissingHeader,
+    #[error("Telegram has no '!' footer with CRC")]
+    MissingFooter,
+    #[error("Telegram CRC mismatch: footer says {expected:04X}, data gives {actual:04X}")]
+    CrcMismatch { expected: u16, actual: u16 },
+    #[error("Malformed telegram line: {0}")]
+    InvalidLine(String),
+    #[error("Telegram lacks required object {0}")]
+    MissingObject(&'static str),
+    #[error("Invalid value '{value}' for {obis}")]
+    InvalidValue { obis: String, value: String },
+}
+
+/// CRC-16/ARC (poly 0xA001 reflected, init 0) as used by DSMR 5 and HAN
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// One parsed telegram
+#[derive(Debug, Clone)]
+pub struct Telegram {
+    /// Meter identification from the header line, without the leading '/'
+    pub identification: String,
+    /// OBIS reference and the raw contents of each `(...)` group, in order
+    objects: Vec<(String, Vec<String>)>,
+}
+
+impl Telegram {
+    /// Parse a complete telegram, checking its CRC
+    pub fn parse(raw: &[u8]) -> Result<Self, TelegramError> {
+        if raw.first() != Some(&b'/') {
+            return Err(TelegramError::MissingHeader);
+        }
+        let bang = raw
+            .iter()
+            .position(|&b| b == b'!')
+            .ok_or(TelegramError::MissingFooter)?;
+        let footer = raw
+            .get(bang + 1..bang + 5)
+            .and_then(|crc| std::str::from_utf8(crc).ok())
+            .and_then(|crc| u16::from_str_radix(crc, 16).ok())
+            .ok_or(TelegramError::MissingFooter)?;
+        let actual = crc16(&raw[..=bang]);
+        if footer != actual {
+            return Err(TelegramError::CrcMismatch {
+                expected: footer,
+                actual,
+            });
+        }
+
+        // CRC passed, so anything non-ASCII here is a meter bug, not line noise
+        let text = String::from_utf8_lossy(&raw[1..bang]);
+        let mut lines = text.lines();
+        let identification = lines.next().unwrap_or_default().trim().to_string();
+
+        let mut objects = Vec::new();
+        for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+            objects.push(Self::parse_line(line)?);
+        }
+
+        Ok(Self {
+            identification,
+            objects,
+        })
+    }
+
+    /// Split `1-0:99.97.0(2)(0-0:96.7.19)(...)` into reference and groups
+    fn parse_line(line: &str) -> Result<(String, Vec<String>), TelegramError> {
+        let invalid = || TelegramError::InvalidLine(line.to_string());
+        let open = line.find('(').ok_or_else(invalid)?;
+        let obis = line[..open].to_string();
+        if obis.is_empty() || !line.ends_with(')') {
+            return Err(invalid());
+        }
+
+        let groups = line[open + 1..line.len() - 1]
+            .split(")(")
+            .map(str::to_string)
+            .collect();
+        Ok((obis, groups))
+    }
+
+    /// Raw value groups of an object
+    pub fn get(&self, obis: &str) -> Option<&[String]> {
+        self.objects
+            .iter()
+            .find(|(reference, _)| reference == obis)
+            .map(|(_, groups)| groups.as_slice())
+    }
+
+    /// Numeric value of a `value*unit` object, scaled to base units (W, Wh, V, A)
+    pub fn quantity(&self, obis: &str) -> Result<Option<f64>, TelegramError> {
+        let Some(raw) = self.get(obis).and_then(|groups| groups.last()) else {
+            return Ok(None);
+        };
+        let invalid = || TelegramError::InvalidValue {
+            obis: obis.to_string(),
+            value: raw.clone(),
+        };
+
+        let (number, unit) = raw.split_once('*').unwrap_or((raw, ""));
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        let scale = match unit {
+            "kW" | "kWh" | "kvar" | "kvarh" => 1000.0,
+            "" | "W" | "Wh" | "V" | "A" | "var" | "varh" | "m3" | "s" => 1.0,
+            _ => return Err(invalid()),
+        };
+        Ok(Some(value * scale))
+    }
+
+    fn required(&self, obis: &'static str) -> Result<f64, TelegramError> {
+        self.quantity(obis)?.ok_or(TelegramError::MissingObject(obis))
+    }
+
+    fn optional(&self, obis: &str) -> Result<f64, TelegramError> {
+        Ok(self.quantity(obis)?.unwrap_or(0.0))
+    }
+
+    /// Energy counter in kWh, from the total or the sum of the tariff registers
+    fn energy_kwh(&self, total: &'static str, tariffs: [&str; 2]) -> Result<f64, TelegramError> {
+        let wh = match self.quantity(total)? {
+            Some(wh) => wh,
+            None => self.optional(tariffs[0])? + self.optional(tariffs[1])?,
+        };
+        Ok(wh / 1000.0)
+    }
+
+    /// Convert to a grid meter reading
+    ///
+    /// Total power is required; per-phase objects missing on single-phase
+    /// meters read as zero.
+    pub fn to_reading(&self) -> Result<GridMeterReading, TelegramError> {
+        let power_w = self.required(obis::POWER_IMPORT)? - self.required(obis::POWER_EXPORT)?;
+
+        let mut phases = [PhaseReading::default(); 3];
+        for (i, phase) in phases.iter_mut().enumerate() {
+            *phase = PhaseReading {
+                voltage_v: self.optional(obis::PHASE_VOLTAGE[i])?,
+                current_a: self.optional(obis::PHASE_CURRENT[i])?,
+                power_w: self.optional(obis::PHASE_IMPORT[i])?
+                    - self.optional(obis::PHASE_EXPORT[i])?,
+            };
+        }
+
+        Ok(GridMeterReading {
+            phases,
+            power_w,
+            // P1 ports do not report frequency
+            frequency_hz: None,
+            import_energy_kwh: self
+                .energy_kwh(obis::ENERGY_IMPORT_TOTAL, obis::ENERGY_IMPORT_TARIFFS)?,
+            export_energy_kwh: self
+                .energy_kwh(obis::ENERGY_EXPORT_TOTAL, obis::ENERGY_EXPORT_TARIFFS)?,
+            timestamp: Utc::now(),
+        })
+    }
+}
+
+/// Reassembles telegrams from a byte stream
+///
+/// Serial reads and TCP segments split telegrams arbitrarily, and a reader
+/// that connects mid-telegram sees a partial one first; bytes before the next
+/// `/` are discarded.
+#[derive(Debug, Default)]
+pub struct TelegramBuffer {
+    buf: Vec<u8>,
+}
+
+impl TelegramBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Next complete telegram, if one has been received
+    pub fn next_telegram(&mut self) -> Option<Result<Telegram, TelegramError>> {
+        loop {
+            match self.buf.iter().position(|&b| b == b'/') {
+                Some(start) => {
+                    self.buf.drain(..start);
+                }
+                None => {
+                    self.buf.clear();
+                    return None;
+                }
+            }
+
+            // Footer is '!' + 4 hex digits; a header before it means the
+            // previous telegram was truncated
+            let end = self.buf.iter().position(|&b| b == b'!');
+            let restart = self.buf[1..].iter().position(|&b| b == b'/').map(|p| p + 1);
+            match (end, restart) {
+                (_, Some(restart)) if end.is_none_or(|end| restart < end) => {
+                    self.buf.drain(..restart);
+                    continue;
+                }
+                (Some(end), _) if self.buf.len() >= end + 5 => {
+                    let telegram: Vec<u8> = self.buf.drain(..end + 5).collect();
+                    return Some(Telegram::parse(&telegram));
+                }
+                _ => {
+                    if self.buf.len() > MAX_TELEGRAM_BYTES {
+                        self.buf.clear();
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DSMR5: &[u8] = include_bytes!("../../../tests/fixtures/p1/dsmr5_three_phase.txt");
+    const DSMR5_BAD_CRC: &[u8] = include_bytes!("../../../tests/fixtures/p1/dsmr5_bad_crc.txt");
+    const HAN_SE: &[u8] = include_bytes!("../../../tests/fixtures/p1/han_se_exporting.txt");
+
+    #[test]
+    fn test_crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0xBB3D);
+    }
+
+    #[test]
+    fn test_dsmr5_three_phase() {
+        let telegram = Telegram::parse(DSMR5).unwrap();
+        assert_eq!(telegram.identification, "ISK5\\2M550T-1012");
+        assert_eq!(telegram.get("1-0:99.97.0").unwrap().len(), 6);
+        assert_eq!(telegram.get("0-0:96.13.0").unwrap(), [""]);
+        assert_eq!(telegram.quantity("0-1:24.2.1").unwrap(), Some(1234.567));
+
+        let reading = telegram.to_reading().unwrap();
+        assert!((reading.power_w - 3482.0).abs() < 1e-9);
+        assert!((reading.import_energy_kwh - 8499.565).abs() < 1e-9);
+        assert!((reading.export_energy_kwh - 2716.8).abs() < 1e-9);
+        assert_eq!(reading.phase_currents_a(), [11.0, 3.0, 1.0]);
+        assert_eq!(reading.phases[1].voltage_v, 231.0);
+        assert!((reading.phases[0].power_w - 2514.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_swedish_han_exporting() {
+        let reading = Telegram::parse(HAN_SE).unwrap().to_reading().unwrap();
+        assert!((reading.power_w + 4215.0).abs() < 1e-9);
+        assert!((reading.import_energy_kwh - 16432.118).abs() < 1e-9);
+        assert!((reading.export_energy_kwh - 4377.902).abs() < 1e-9);
+        assert!((reading.phases[2].power_w + 1196.0).abs() < 1e-9);
+        assert_eq!(reading.phase_currents_a(), [6.7, 6.0, 5.1]);
+        assert_eq!(reading.import_power_w(), 0.0);
+    }
+
+    #[test]
+    fn test_crc_mismatch_rejected() {
+        assert!(matches!(
+            Telegram::parse(DSMR5_BAD_CRC),
+            Err(TelegramError::CrcMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_buffer_reassembles_split_stream() {
+        let mut buffer = TelegramBuffer::new();
+
+        // Connected mid-telegram: the tail of a previous one comes first
+        buffer.extend(&HAN_SE[HAN_SE.len() / 2..]);
+        assert!(buffer.next_telegram().is_none());
+
+        for chunk in DSMR5.chunks(64) {
+            buffer.extend(chunk);
+        }
+        buffer.extend(&HAN_SE[..40]);
+        let telegram = buffer.next_telegram().unwrap().unwrap();
+        assert_eq!(telegram.identification, "ISK5\\2M550T-1012");
+        assert!(buffer.next_telegram().is_none());
+
+        // A fresh header before the footer drops the truncated telegram
+        buffer.extend(HAN_SE);
+        let telegram = buffer.next_telegram().unwrap().unwrap();
+        assert_eq!(telegram.identification, "ELL5\\253833635_A");
+    }
+}
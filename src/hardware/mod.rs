@@ -1,4 +1,6 @@
 pub mod factory;
+pub mod han;
 pub mod modbus;
 pub mod ocpp;
+pub mod shelly;
 
@@ -1,9 +1,11 @@
-use crate::domain::battery::{Battery, BatteryState, BatteryCapabilities};
+use super::profile::DeviceProfile;
+use super::{read_register, write_register};
+use crate::domain::battery::{Battery, BatteryState, BatteryCapabilities, BatteryChemistry};
 use crate::modbus::client::ModbusClient;
-use crate::modbus::register_map::{RegisterMap, GenericBatteryRegisterMap, HuaweiLuna2000RegisterMap, SolarEdgeRegisterMap};
-use crate::modbus::parser;
+use crate::modbus::register_map::{RegisterMap, GenericBatteryRegisterMap};
 use anyhow::{Result, Context};
 use async_trait::async_trait;
+use std::time::Duration;
 use tracing::debug;
 
 /// Modbus-based battery implementation
@@ -33,51 +35,46 @@ impl ModbusBattery {
             .await
             .context("Failed to connect to Modbus battery")?;
 
-        let register_map = Box::new(GenericBatteryRegisterMap);
-
-        // Read capabilities from device
-        let capabilities = Self::read_capabilities(&client, &*register_map).await?;
-
-        let battery = Self {
-            client,
-            register_map,
-            capabilities,
-        };
+        Self::with_client(client, Box::new(GenericBatteryRegisterMap)).await
+    }
 
-        // Enable remote control mode if supported by the device
-        battery.enable_remote_control().await?;
+    /// Create a new ModbusBattery from a device profile
+    ///
+    /// Uses the profile's register layout and timeout; `addr` and `unit_id`
+    /// come from the site configuration since they differ per installation.
+    pub async fn with_profile(addr: &str, unit_id: u8, profile: &DeviceProfile) -> Result<Self> {
+        let client = ModbusClient::connect_with_timeout(
+            addr,
+            unit_id,
+            Duration::from_millis(profile.modbus.timeout_ms),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to connect to {} {} battery",
+                profile.device.manufacturer, profile.device.model
+            )
+        })?;
 
-        Ok(battery)
+        Self::with_client(client, Box::new(profile.register_map.clone())).await
     }
 
-    /// Create a new ModbusBattery with Huawei Luna2000 register map
+    /// Create a new ModbusBattery with the Huawei Luna2000 profile
     pub async fn new_huawei(addr: &str, unit_id: u8) -> Result<Self> {
-        let client = ModbusClient::connect(addr, unit_id)
-            .await
-            .context("Failed to connect to Huawei battery")?;
-
-        let register_map = Box::new(HuaweiLuna2000RegisterMap);
-        let capabilities = Self::read_capabilities(&client, &*register_map).await?;
-
-        let battery = Self {
-            client,
-            register_map,
-            capabilities,
-        };
-
-        // Enable remote control mode (required for Huawei inverters)
-        battery.enable_remote_control().await?;
-
-        Ok(battery)
+        Self::with_profile(addr, unit_id, &DeviceProfile::builtin("huawei_luna2000")?).await
     }
 
-    /// Create a new ModbusBattery with SolarEdge register map
+    /// Create a new ModbusBattery with the SolarEdge StorEdge profile
     pub async fn new_solaredge(addr: &str, unit_id: u8) -> Result<Self> {
-        let client = ModbusClient::connect(addr, unit_id)
-            .await
-            .context("Failed to connect to SolarEdge battery")?;
+        Self::with_profile(addr, unit_id, &DeviceProfile::builtin("solaredge_storedge")?).await
+    }
 
-        let register_map = Box::new(SolarEdgeRegisterMap);
+    /// Create a ModbusBattery on an already connected client
+    ///
+    /// Use this for RTU and RTU-over-TCP links, where the caller builds the
+    /// client from a `Transport`.
+    pub async fn with_client(client: ModbusClient, register_map: Box<dyn RegisterMap>) -> Result<Self> {
+        // Read capabilities from device
         let capabilities = Self::read_capabilities(&client, &*register_map).await?;
 
         let battery = Self {
@@ -86,13 +83,16 @@ impl ModbusBattery {
             capabilities,
         };
 
-        // Enable remote control mode (required for SolarEdge inverters)
+        // Enable remote control mode if supported by the device
         battery.enable_remote_control().await?;
 
         Ok(battery)
     }
 
     /// Read battery capabilities from device
+    ///
+    /// Values the register map does not expose come from its rated
+    /// capabilities (the profile's `[capabilities]` section).
     async fn read_capabilities(
         client: &ModbusClient,
         register_map: &dyn RegisterMap,
@@ -100,81 +100,63 @@ impl ModbusBattery {
         // CRITICAL FIX: Fail early if capabilities cannot be read
         // Using wrong defaults (e.g., 10kWh for a 30kWh battery) causes incorrect
         // SoC calculations, which can lead to deep-discharge or overcharge damage.
+        let rated = register_map.rated_capabilities();
 
-        // Read max charge power with retry
-        let max_charge_regs = client
-            .read_holding_registers(register_map.max_charge_power_register(), 1)
-            .await
-            .context("Failed to read max_charge_power - battery not responding correctly")?;
-
-        // Read max discharge power with retry
-        let max_discharge_regs = client
-            .read_holding_registers(register_map.max_discharge_power_register(), 1)
-            .await
-            .context("Failed to read max_discharge_power - battery not responding correctly")?;
+        let max_charge_kw = match register_map.max_charge_power() {
+            Some(spec) => read_register(client, spec)
+                .await
+                .context("Failed to read max_charge_power - battery not responding correctly")?
+                / 1000.0,
+            None => rated
+                .as_ref()
+                .map(|r| r.max_charge_kw)
+                .context("Register map has neither a max_charge_power register nor rated capabilities")?,
+        };
 
-        // Read capacity with retry (MOST CRITICAL - wrong capacity = wrong SoC)
-        let capacity_regs = client
-            .read_holding_registers(register_map.capacity_register(), 1)
-            .await
-            .context("Failed to read battery capacity - cannot proceed without accurate specs")?;
+        let max_discharge_kw = match register_map.max_discharge_power() {
+            Some(spec) => read_register(client, spec)
+                .await
+                .context("Failed to read max_discharge_power - battery not responding correctly")?
+                / 1000.0,
+            None => rated
+                .as_ref()
+                .map(|r| r.max_discharge_kw)
+                .context("Register map has neither a max_discharge_power register nor rated capabilities")?,
+        };
 
-        let max_charge_kw = parser::parse_u16(&max_charge_regs) as f64 / 1000.0;
-        let max_discharge_kw = parser::parse_u16(&max_discharge_regs) as f64 / 1000.0;
-        let capacity_kwh = parser::parse_u16(&capacity_regs) as f64 / 1000.0;
+        // Capacity is the MOST CRITICAL value - wrong capacity = wrong SoC
+        let capacity_kwh = match register_map.capacity() {
+            Some(spec) => read_register(client, spec)
+                .await
+                .context("Failed to read battery capacity - cannot proceed without accurate specs")?
+                / 1000.0,
+            None => rated
+                .as_ref()
+                .map(|r| r.capacity_kwh)
+                .context("Register map has neither a capacity register nor rated capabilities")?,
+        };
 
         Ok(BatteryCapabilities {
             capacity_kwh,
             max_charge_kw,
             max_discharge_kw,
-            efficiency: 0.95, // 95% typical round-trip efficiency
-            degradation_per_cycle: 0.0001, // 0.01% per cycle
+            efficiency: rated.as_ref().map(|r| r.efficiency).unwrap_or(0.95), // 95% typical round-trip efficiency
+            degradation_per_cycle: rated.as_ref().map(|r| r.degradation_per_cycle).unwrap_or(0.0001), // 0.01% per cycle
+            chemistry: rated.map(|r| r.chemistry).unwrap_or(BatteryChemistry::Unknown),
         })
     }
 
-    /// Read a single register with scaling
-    async fn read_scaled_register(&self, register: u16, scale: f64) -> Result<f64> {
-        let regs = self.client.read_holding_registers(register, 1).await?;
-        Ok(parser::parse_scaled_u16(&regs, scale))
-    }
-
     /// Write power command to battery (watts, positive=charge, negative=discharge)
     async fn write_power_command(&self, watts: f64) -> Result<()> {
-        let scale = self.register_map.power_command_scale();
-        let scaled_value = watts / scale;
+        let spec = self.register_map.power_command();
 
         // CRITICAL FIX: Prevent integer overflow that would reverse control polarity
-        // i16 range is -32,768 to 32,767. If scaled_value exceeds this, it wraps around.
-        // Example: 5000W / 0.1 = 50,000 -> wraps to -15,536 (DISCHARGE instead of CHARGE!)
-        const I16_MAX: f64 = 32767.0;
-        const I16_MIN: f64 = -32768.0;
-
-        if scaled_value > I16_MAX || scaled_value < I16_MIN {
-            return Err(anyhow::anyhow!(
-                "Power command {} W (scaled: {}) exceeds i16 range [{}, {}]. \
-                 This would cause integer overflow and reverse polarity. \
-                 Check your register map scale factor ({}) or reduce power.",
-                watts,
-                scaled_value,
-                I16_MIN,
-                I16_MAX,
-                scale
-            ));
-        }
-
-        let register_value = scaled_value as i16;
-
-        // Convert i16 to u16 for Modbus
-        let value = register_value as u16;
-
-        debug!("Writing power command: {} W (register value: {})", watts, register_value);
-
-        self.client
-            .write_single_register(
-                self.register_map.power_command_register(),
-                value,
-            )
+        // Example: 5000W / 0.1 = 50,000 -> wraps to -15,536 as i16 (DISCHARGE instead of CHARGE!)
+        // RegisterSpec::encode rejects values outside the register's type instead of wrapping.
+        debug!("Writing power command: {} W to register {}", watts, spec.address);
+        write_register(&self.client, spec, watts)
             .await
+            .context("Failed to write power command")
     }
 
     /// Health check the Modbus connection
@@ -224,38 +206,13 @@ impl Battery for ModbusBattery {
         debug!("Reading battery state via Modbus");
 
         // Read all registers in parallel for efficiency
-        let soc_task = self.read_scaled_register(
-            self.register_map.soc_register(),
-            self.register_map.soc_scale(),
-        );
-
-        let power_task = self.read_scaled_register(
-            self.register_map.power_register(),
-            self.register_map.power_scale(),
-        );
-
-        let voltage_task = self.read_scaled_register(
-            self.register_map.voltage_register(),
-            self.register_map.voltage_scale(),
-        );
-
-        let temp_task = self.read_scaled_register(
-            self.register_map.temperature_register(),
-            self.register_map.temperature_scale(),
-        );
-
-        let health_task = self.read_scaled_register(
-            self.register_map.health_register(),
-            self.register_map.health_scale(),
-        );
-
-        // Wait for all reads to complete
+        let map = &self.register_map;
         let (soc, power, voltage, temperature, health) = tokio::try_join!(
-            soc_task,
-            power_task,
-            voltage_task,
-            temp_task,
-            health_task,
+            read_register(&self.client, map.soc()),
+            read_register(&self.client, map.power()),
+            read_register(&self.client, map.voltage()),
+            read_register(&self.client, map.temperature()),
+            read_register(&self.client, map.health()),
         )?;
 
         // Determine status based on power
@@ -317,6 +274,11 @@ impl Battery for ModbusBattery {
             Err(_) => Ok(crate::domain::HealthStatus::Offline),
         }
     }
+
+    async fn emergency_shutdown(&self) -> Result<()> {
+        tracing::warn!("Emergency shutdown: commanding Modbus battery to 0 W");
+        self.write_power_command(0.0).await
+    }
 }
 
 #[cfg(test)]
@@ -326,22 +288,25 @@ mod tests {
     #[test]
     fn test_generic_register_map() {
         let map = GenericBatteryRegisterMap;
-        assert_eq!(map.soc_register(), 37000);
-        assert_eq!(map.soc_scale(), 0.1);
+        assert_eq!(map.soc().address, 37000);
+        assert_eq!(map.soc().scale, 0.1);
     }
 
     #[test]
-    fn test_huawei_register_map() {
-        let map = HuaweiLuna2000RegisterMap;
-        assert_eq!(map.soc_register(), 37760);
-        assert_eq!(map.soc_scale(), 0.1);
+    fn test_power_register_is_signed() {
+        // -1500 W discharge must not read back as 64036 W charge
+        let map = GenericBatteryRegisterMap;
+        assert_eq!(map.power().decode(&[(-1500i16) as u16]).unwrap(), -1500.0);
     }
 
     #[test]
-    fn test_solaredge_register_map() {
-        let map = SolarEdgeRegisterMap;
-        assert_eq!(map.soc_register(), 62852);
-        assert_eq!(map.soc_scale(), 1.0);
+    fn test_builtin_profile_register_maps() {
+        let huawei = DeviceProfile::builtin("huawei_luna2000").unwrap();
+        assert_eq!(huawei.register_map.soc().address, 37760);
+        assert_eq!(huawei.register_map.soc().scale, 0.1);
+
+        let solaredge = DeviceProfile::builtin("solaredge_storedge").unwrap();
+        assert_eq!(solaredge.register_map.soc().address, 62852);
+        assert_eq!(solaredge.register_map.soc().scale, 1.0);
     }
 }
-
@@ -0,0 +1,192 @@
+use crate::domain::thermal_load::{
+    FlexibleThermalLoad, ThermalLoadCapabilities, ThermalLoadMode, ThermalLoadState,
+};
+use crate::modbus::client::ModbusClient;
+use crate::modbus::register_map::{HeatPumpRegisterMap, RegisterSpec};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+
+use super::write_register;
+
+/// Heating curve offset per operating state (K)
+///
+/// Lowering the curve far enough stops the compressor until the house has
+/// cooled noticeably, which is as close to `Blocked` as the register allows.
+fn heating_offset_k(mode: ThermalLoadMode) -> f64 {
+    match mode {
+        ThermalLoadMode::Blocked => -5.0,
+        ThermalLoadMode::Normal => 0.0,
+        ThermalLoadMode::Boost => 2.0,
+        ThermalLoadMode::ForcedOn => 5.0,
+    }
+}
+
+/// Heat pump with a Modbus interface (NIBE, Thermia)
+///
+/// Operating states are mapped onto the heating curve offset and the hot
+/// water boost, restored to zero and off in `Normal`.
+pub struct ModbusHeatPump {
+    client: ModbusClient,
+    register_map: Box<dyn HeatPumpRegisterMap>,
+    caps: ThermalLoadCapabilities,
+    mode: RwLock<ThermalLoadMode>,
+}
+
+impl ModbusHeatPump {
+    pub fn with_client(
+        client: ModbusClient,
+        register_map: Box<dyn HeatPumpRegisterMap>,
+        rated_power_kw: f64,
+    ) -> Self {
+        let caps = ThermalLoadCapabilities {
+            rated_power_kw,
+            has_dhw_tank: register_map.dhw_temp().is_some(),
+            supports_blocking: true,
+        };
+        Self {
+            client,
+            register_map,
+            caps,
+            mode: RwLock::new(ThermalLoadMode::Normal),
+        }
+    }
+
+    async fn read_value(&self, spec: RegisterSpec) -> Result<f64> {
+        let regs = if self.register_map.uses_input_registers() {
+            self.client
+                .read_input_registers(spec.address, spec.word_count())
+                .await
+        } else {
+            self.client
+                .read_holding_registers(spec.address, spec.word_count())
+                .await
+        }?;
+        spec.decode(&regs)
+    }
+
+    async fn read_optional(&self, spec: Option<RegisterSpec>) -> Result<Option<f64>> {
+        match spec {
+            Some(spec) => self.read_value(spec).await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl FlexibleThermalLoad for ModbusHeatPump {
+    async fn read_state(&self) -> Result<ThermalLoadState> {
+        let map = &self.register_map;
+        let state = ThermalLoadState {
+            mode: *self.mode.read().await,
+            indoor_temp_c: self.read_optional(map.indoor_temp()).await?,
+            dhw_temp_c: self.read_optional(map.dhw_temp()).await?,
+            outdoor_temp_c: Some(
+                self.read_value(map.outdoor_temp())
+                    .await
+                    .context("Failed to read heat pump outdoor temperature")?,
+            ),
+            power_w: self.read_optional(map.power()).await?,
+            timestamp: Utc::now(),
+        };
+        debug!(indoor_temp_c = ?state.indoor_temp_c, dhw_temp_c = ?state.dhw_temp_c, "Heat pump reading");
+        Ok(state)
+    }
+
+    async fn set_mode(&self, mode: ThermalLoadMode) -> Result<()> {
+        write_register(
+            &self.client,
+            self.register_map.heating_offset(),
+            heating_offset_k(mode),
+        )
+        .await
+        .context("Failed to write heating curve offset")?;
+        if let Some((spec, off, on)) = self.register_map.dhw_boost() {
+            let boost = matches!(mode, ThermalLoadMode::Boost | ThermalLoadMode::ForcedOn);
+            write_register(&self.client, spec, if boost { on } else { off })
+                .await
+                .context("Failed to write hot water boost")?;
+        }
+        *self.mode.write().await = mode;
+        info!(?mode, "Heat pump operating state set");
+        Ok(())
+    }
+
+    fn capabilities(&self) -> ThermalLoadCapabilities {
+        self.caps.clone()
+    }
+}
+
+/// SG-Ready heat pump switched by two relay outputs
+///
+/// The relays are coils of a Modbus relay module wired to the heat pump's
+/// SG-Ready inputs. The interface carries no measurements.
+pub struct SgReadyHeatPump {
+    client: ModbusClient,
+    /// Coil addresses of relay 1 and relay 2
+    coils: [u16; 2],
+    caps: ThermalLoadCapabilities,
+    mode: RwLock<ThermalLoadMode>,
+}
+
+impl SgReadyHeatPump {
+    pub fn with_client(client: ModbusClient, coils: [u16; 2], rated_power_kw: f64) -> Self {
+        Self {
+            client,
+            coils,
+            caps: ThermalLoadCapabilities {
+                rated_power_kw,
+                has_dhw_tank: true,
+                supports_blocking: true,
+            },
+            mode: RwLock::new(ThermalLoadMode::Normal),
+        }
+    }
+}
+
+#[async_trait]
+impl FlexibleThermalLoad for SgReadyHeatPump {
+    async fn read_state(&self) -> Result<ThermalLoadState> {
+        Ok(ThermalLoadState {
+            mode: *self.mode.read().await,
+            indoor_temp_c: None,
+            dhw_temp_c: None,
+            outdoor_temp_c: None,
+            power_w: None,
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn set_mode(&self, mode: ThermalLoadMode) -> Result<()> {
+        let (relay1, relay2) = mode.sg_ready_relays();
+        // Release before engaging, so the unit never sees 1:1 on the way
+        // between `Blocked` and `Boost`
+        let order = if relay1 {
+            [(1, relay2), (0, relay1)]
+        } else {
+            [(0, relay1), (1, relay2)]
+        };
+        for (index, on) in order {
+            self.client
+                .write_single_coil(self.coils[index], on)
+                .await
+                .with_context(|| format!("Failed to switch SG-Ready relay {}", index + 1))?;
+        }
+        *self.mode.write().await = mode;
+        info!(?mode, "SG-Ready state set");
+        Ok(())
+    }
+
+    fn capabilities(&self) -> ThermalLoadCapabilities {
+        self.caps.clone()
+    }
+
+    async fn health_check(&self) -> Result<crate::domain::HealthStatus> {
+        Ok(match self.client.health_check().await {
+            Ok(_) => crate::domain::HealthStatus::Healthy,
+            Err(_) => crate::domain::HealthStatus::Offline,
+        })
+    }
+}
@@ -0,0 +1,243 @@
+use super::{read_register, write_register};
+use crate::domain::inverter::{
+    Inverter, InverterCapabilities, InverterError, InverterMode, InverterState, InverterStatus,
+};
+use crate::modbus::client::ModbusClient;
+use crate::modbus::register_map::{GenericInverterRegisterMap, InverterRegisterMap};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// Modbus-based PV/hybrid inverter
+///
+/// Like `ModbusBattery`, create ONCE at startup and reuse: the client keeps a
+/// single persistent connection.
+pub struct ModbusInverter {
+    client: ModbusClient,
+    register_map: Box<dyn InverterRegisterMap>,
+    capabilities: InverterCapabilities,
+    /// Last mode commanded, for maps without a readable mode register
+    commanded_mode: Mutex<InverterMode>,
+}
+
+impl ModbusInverter {
+    /// Create a new ModbusInverter with the generic register map
+    pub async fn new(addr: &str, unit_id: u8) -> Result<Self> {
+        let client = ModbusClient::connect(addr, unit_id)
+            .await
+            .context("Failed to connect to Modbus inverter")?;
+
+        Self::with_client(client, Box::new(GenericInverterRegisterMap)).await
+    }
+
+    /// Create a ModbusInverter on an already connected client
+    pub async fn with_client(
+        client: ModbusClient,
+        register_map: Box<dyn InverterRegisterMap>,
+    ) -> Result<Self> {
+        let capabilities = Self::read_capabilities(&client, &*register_map).await?;
+        info!(
+            "Modbus inverter rated {} W, {} MPPT channel(s)",
+            capabilities.rated_power_w, capabilities.mppt_channels
+        );
+
+        Ok(Self {
+            client,
+            register_map,
+            capabilities,
+            commanded_mode: Mutex::new(InverterMode::GridTied),
+        })
+    }
+
+    async fn read_capabilities(
+        client: &ModbusClient,
+        register_map: &dyn InverterRegisterMap,
+    ) -> Result<InverterCapabilities> {
+        // Rated power bounds every export limit we write, so fail rather than guess
+        let rated_power_w = read_register(client, register_map.rated_power())
+            .await
+            .context("Failed to read inverter rated power")?;
+        if !rated_power_w.is_finite() || rated_power_w <= 0.0 {
+            anyhow::bail!("Inverter reports invalid rated power {} W", rated_power_w);
+        }
+
+        let mppt_channels = match register_map.mppt_count() {
+            Some(spec) => read_register(client, spec)
+                .await
+                .context("Failed to read MPPT count")?
+                .clamp(1.0, u8::MAX as f64) as u8,
+            None => 1,
+        };
+
+        Ok(InverterCapabilities {
+            rated_power_w,
+            max_dc_input_w: rated_power_w,
+            max_ac_output_w: rated_power_w,
+            max_efficiency_percent: 98.0, // Typical for modern string inverters
+            mppt_channels,
+            supports_export_limit: register_map.export_limit().is_some(),
+            supports_frequency_regulation: false,
+        })
+    }
+
+    async fn write_command(&self, (address, value): (u16, u16)) -> Result<()> {
+        debug!("Writing inverter command: {} to register {}", value, address);
+        self.client.write_single_register(address, value).await
+    }
+
+    async fn read_mode(&self, status: InverterStatus) -> Result<InverterMode> {
+        if let Some(address) = self.register_map.mode_register() {
+            let raw = self.client.read_holding_registers(address, 1).await?[0];
+            if let Some(mode) = self.register_map.decode_mode(raw) {
+                return Ok(mode);
+            }
+            warn!("Unknown inverter mode value {} in register {}", raw, address);
+        }
+
+        Ok(match status {
+            InverterStatus::Standby | InverterStatus::Shutdown => InverterMode::Standby,
+            _ => *self.commanded_mode.lock().unwrap_or_else(|e| e.into_inner()),
+        })
+    }
+
+    /// Health check the Modbus connection
+    pub async fn health_check(&self) -> Result<()> {
+        read_register(&self.client, self.register_map.status())
+            .await
+            .map(|_| ())
+            .context("Inverter health check failed")
+    }
+
+    /// Reconnect to the Modbus device if connection is lost
+    pub async fn reconnect(&self) -> Result<()> {
+        self.client.reconnect().await
+    }
+}
+
+#[async_trait]
+impl Inverter for ModbusInverter {
+    async fn read_state(&self) -> Result<InverterState> {
+        debug!("Reading inverter state via Modbus");
+
+        let map = &self.register_map;
+        let (pv_power, ac_power, dc_voltage, ac_voltage, frequency, temperature, daily, total, status) =
+            tokio::try_join!(
+                read_register(&self.client, map.pv_power()),
+                read_register(&self.client, map.ac_power()),
+                read_register(&self.client, map.dc_voltage()),
+                read_register(&self.client, map.ac_voltage()),
+                read_register(&self.client, map.grid_frequency()),
+                read_register(&self.client, map.temperature()),
+                read_register(&self.client, map.daily_energy()),
+                read_register(&self.client, map.total_energy()),
+                read_register(&self.client, map.status()),
+            )?;
+
+        let status = map.decode_status(status as u16);
+        let mode = self.read_mode(status).await?;
+        let efficiency_percent = if pv_power > 0.0 {
+            (ac_power / pv_power * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        };
+
+        Ok(InverterState {
+            mode,
+            pv_power_w: pv_power,
+            ac_output_power_w: ac_power,
+            dc_input_power_w: pv_power,
+            grid_frequency_hz: frequency,
+            ac_voltage_v: ac_voltage,
+            dc_voltage_v: dc_voltage,
+            temperature_c: temperature,
+            efficiency_percent,
+            status,
+            daily_energy_kwh: daily,
+            total_energy_kwh: total,
+        })
+    }
+
+    async fn set_mode(&self, mode: InverterMode) -> Result<()> {
+        let command = self.register_map.mode_command(mode).ok_or_else(|| {
+            let current = *self.commanded_mode.lock().unwrap_or_else(|e| e.into_inner());
+            InverterError::InvalidModeTransition(format!("{:?}", current), format!("{:?}", mode))
+        })?;
+
+        self.write_command(command)
+            .await
+            .with_context(|| format!("Failed to set inverter mode {:?}", mode))?;
+        *self.commanded_mode.lock().unwrap_or_else(|e| e.into_inner()) = mode;
+        Ok(())
+    }
+
+    async fn set_export_limit(&self, watts: f64) -> Result<()> {
+        let spec = self
+            .register_map
+            .export_limit()
+            .ok_or(InverterError::ExportLimitNotSupported)?;
+        if !watts.is_finite() || watts < 0.0 || watts > self.capabilities.max_ac_output_w {
+            return Err(InverterError::InvalidExportLimit(watts).into());
+        }
+
+        if let Some(enable) = self.register_map.export_limit_enable() {
+            self.write_command(enable)
+                .await
+                .context("Failed to enable export limitation")?;
+        }
+        write_register(&self.client, spec, watts)
+            .await
+            .context("Failed to write export limit")
+    }
+
+    fn capabilities(&self) -> InverterCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn emergency_shutdown(&self) -> Result<()> {
+        match self.register_map.mode_command(InverterMode::Standby) {
+            Some(command) => {
+                warn!("Emergency shutdown: commanding Modbus inverter to standby");
+                self.write_command(command).await?;
+                *self.commanded_mode.lock().unwrap_or_else(|e| e.into_inner()) =
+                    InverterMode::Standby;
+                Ok(())
+            }
+            None => {
+                warn!("Emergency shutdown: inverter has no standby command, limiting export to 0 W");
+                self.set_export_limit(0.0).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::inverter::{InverterMode, InverterStatus};
+    use crate::modbus::register_map::{
+        GenericInverterRegisterMap, HuaweiSun2000RegisterMap, InverterRegisterMap,
+    };
+
+    #[test]
+    fn test_generic_mode_round_trip() {
+        let map = GenericInverterRegisterMap;
+        for mode in [InverterMode::GridTied, InverterMode::Backup, InverterMode::Standby] {
+            let (address, value) = map.mode_command(mode).unwrap();
+            assert_eq!(Some(address), map.mode_register());
+            assert_eq!(map.decode_mode(value), Some(mode));
+        }
+    }
+
+    #[test]
+    fn test_huawei_status_and_power_decoding() {
+        let map = HuaweiSun2000RegisterMap;
+        assert_eq!(map.decode_status(0x0200), InverterStatus::Normal);
+        assert_eq!(map.decode_status(0xA000), InverterStatus::Standby);
+        assert_eq!(map.decode_status(0x0300), InverterStatus::Fault);
+        assert!(map.mode_command(InverterMode::OffGrid).is_none());
+
+        // 5.2 kW export (gain 1000) must stay positive across both words
+        assert_eq!(map.ac_power().decode(&[0, 5200]).unwrap(), 5200.0);
+        assert_eq!(map.ac_power().decode(&[0xFFFF, (-300i16) as u16]).unwrap(), -300.0);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::domain::grid_meter::{GridMeter, GridMeterReading, PhaseReading};
+use crate::modbus::client::ModbusClient;
+use crate::modbus::register_map::{MeterRegisterMap, RegisterSpec};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use tracing::debug;
+
+/// Largest block a single Modbus read may return
+const MAX_BLOCK_REGISTERS: u16 = 125;
+
+/// Grid meter on Modbus (Eastron SDM630, Carlo Gavazzi EM24, ...)
+///
+/// All values are fetched with one block read so the per-phase figures come
+/// from the same measurement cycle.
+pub struct ModbusGridMeter {
+    client: ModbusClient,
+    register_map: Box<dyn MeterRegisterMap>,
+    /// First register and length of the block covering every value
+    block: (u16, u16),
+}
+
+impl ModbusGridMeter {
+    pub fn with_client(client: ModbusClient, register_map: Box<dyn MeterRegisterMap>) -> Result<Self> {
+        let block = Self::block_span(&*register_map)?;
+        Ok(Self {
+            client,
+            register_map,
+            block,
+        })
+    }
+
+    fn specs(map: &dyn MeterRegisterMap) -> Vec<RegisterSpec> {
+        let mut specs = Vec::with_capacity(13);
+        specs.extend(map.phase_voltage());
+        specs.extend(map.phase_current());
+        specs.extend(map.phase_power());
+        specs.extend([
+            map.total_power(),
+            map.frequency(),
+            map.import_energy(),
+            map.export_energy(),
+        ]);
+        specs
+    }
+
+    fn block_span(map: &dyn MeterRegisterMap) -> Result<(u16, u16)> {
+        let specs = Self::specs(map);
+        let start = specs.iter().map(|s| s.address).min().unwrap_or(0);
+        let end = specs
+            .iter()
+            .map(|s| u32::from(s.address) + u32::from(s.word_count()))
+            .max()
+            .unwrap_or(0);
+        let len = end - u32::from(start);
+        if len > u32::from(MAX_BLOCK_REGISTERS) {
+            bail!("Meter registers span {} registers, more than one read allows", len);
+        }
+        Ok((start, len as u16))
+    }
+
+    pub async fn health_check(&self) -> Result<()> {
+        self.read_block().await.map(|_| ())
+    }
+
+    async fn read_block(&self) -> Result<Vec<u16>> {
+        let (start, len) = self.block;
+        let regs = if self.register_map.uses_input_registers() {
+            self.client.read_input_registers(start, len).await
+        } else {
+            self.client.read_holding_registers(start, len).await
+        }
+        .context("Failed to read grid meter")?;
+        if regs.len() < len as usize {
+            bail!("Grid meter returned {} of {} registers", regs.len(), len);
+        }
+        Ok(regs)
+    }
+}
+
+#[async_trait]
+impl GridMeter for ModbusGridMeter {
+    async fn read(&self) -> Result<GridMeterReading> {
+        let regs = self.read_block().await?;
+        let start = self.block.0;
+        let value = |spec: RegisterSpec| spec.decode(&regs[(spec.address - start) as usize..]);
+
+        let map = &self.register_map;
+        let (voltage, current, power) = (map.phase_voltage(), map.phase_current(), map.phase_power());
+        let mut phases = [PhaseReading::default(); 3];
+        for (i, phase) in phases.iter_mut().enumerate() {
+            *phase = PhaseReading {
+                voltage_v: value(voltage[i])?,
+                current_a: value(current[i])?.abs(),
+                power_w: value(power[i])?,
+            };
+        }
+
+        let reading = GridMeterReading {
+            phases,
+            power_w: value(map.total_power())?,
+            frequency_hz: Some(value(map.frequency())?),
+            import_energy_kwh: value(map.import_energy())?,
+            export_energy_kwh: value(map.export_energy())?,
+            timestamp: Utc::now(),
+        };
+        debug!(power_w = reading.power_w, "Grid meter reading");
+        Ok(reading)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modbus::register_map::{Em24RegisterMap, Sdm630RegisterMap};
+
+    #[test]
+    fn test_meter_maps_fit_one_read() {
+        assert_eq!(ModbusGridMeter::block_span(&Sdm630RegisterMap).unwrap(), (0, 0x4C));
+        assert_eq!(ModbusGridMeter::block_span(&Em24RegisterMap).unwrap(), (0, 0x50));
+    }
+
+    #[test]
+    fn test_em24_low_word_first() {
+        // 2301 (230.1 V) as INT32 LSW first
+        let v = Em24RegisterMap.phase_voltage()[0].decode(&[2301, 0]).unwrap();
+        assert!((v - 230.1).abs() < 1e-9);
+        // -1234.5 W export
+        let raw = (-12345i32) as u32;
+        let p = Em24RegisterMap
+            .total_power()
+            .decode(&[raw as u16, (raw >> 16) as u16])
+            .unwrap();
+        assert!((p + 1234.5).abs() < 1e-9);
+    }
+}
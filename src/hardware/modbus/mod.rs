@@ -1,5 +1,66 @@
 #[cfg(feature = "modbus")]
 pub mod battery;
 
+#[cfg(feature = "modbus")]
+pub mod profile;
+
 #[cfg(feature = "modbus")]
 pub use battery::ModbusBattery;
+
+#[cfg(feature = "modbus")]
+pub use profile::DeviceProfile;
+
+#[cfg(feature = "modbus")]
+pub mod inverter;
+
+#[cfg(feature = "modbus")]
+pub use inverter::ModbusInverter;
+
+#[cfg(feature = "modbus")]
+pub mod meter;
+
+#[cfg(feature = "modbus")]
+pub use meter::ModbusGridMeter;
+
+#[cfg(feature = "modbus")]
+pub mod heat_pump;
+
+#[cfg(feature = "modbus")]
+pub use heat_pump::{ModbusHeatPump, SgReadyHeatPump};
+
+#[cfg(feature = "modbus")]
+pub mod relay;
+
+#[cfg(feature = "modbus")]
+pub use relay::ModbusRelayLoad;
+
+#[cfg(feature = "modbus")]
+pub mod sunspec;
+
+#[cfg(feature = "modbus")]
+pub use sunspec::{SunSpecBattery, SunSpecInverter, SunSpecMeter};
+
+/// Read and decode a single value described by `spec`
+#[cfg(feature = "modbus")]
+async fn read_register(
+    client: &crate::modbus::client::ModbusClient,
+    spec: crate::modbus::register_map::RegisterSpec,
+) -> anyhow::Result<f64> {
+    let regs = client.read_holding_registers(spec.address, spec.word_count()).await?;
+    spec.decode(&regs)
+}
+
+/// Encode `value` per `spec` and write it with a single or multiple register write
+#[cfg(feature = "modbus")]
+async fn write_register(
+    client: &crate::modbus::client::ModbusClient,
+    spec: crate::modbus::register_map::RegisterSpec,
+    value: f64,
+) -> anyhow::Result<()> {
+    let words = spec.encode(value)?;
+    if let [word] = words[..] {
+        client.write_single_register(spec.address, word).await
+    } else {
+        client.write_multiple_registers(spec.address, &words).await
+    }
+}
@@ -0,0 +1,658 @@
+//! Battery device profiles
+//!
+//! A profile is a TOML file in `config/device_profiles/` describing where a
+//! battery keeps its values and how they are encoded, so new hardware can be
+//! added without recompiling. See `generic_battery.toml` for the annotated
+//! template.
+//!
+//! Registers are given as flat `[registers]` keys, `<name>_<field>`:
+//!
+//! | field         | meaning                                                        |
+//! |---------------|----------------------------------------------------------------|
+//! | `address`     | start register (required)                                      |
+//! | `scale`       | engineering value = raw * scale (default 1.0)                  |
+//! | `type`        | `u16`, `i16`, `u32`, `i32` or `f32`                            |
+//! | `signed`      | shorthand for `i16` when `type` is omitted                     |
+//! | `word_order`  | `big_endian` or `little_endian`, defaults to `[modbus]`'s      |
+//! | `unit`        | informational                                                  |
+//! | `writable`    | `power` only: `false` if it reports measurements only          |
+//!
+//! `soc`, `power`, `voltage`, `temperature` and `health` are required.
+//! `power_command` defaults to the `power` register unless that is marked
+//! `power_writable = false`, in which case it is required. The optional
+//! `max_charge_power`, `max_discharge_power` and `capacity` registers fall
+//! back to `[capabilities]` when absent.
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+
+use crate::domain::battery::{BatteryCapabilities, BatteryChemistry};
+use crate::modbus::register_map::{DataType, RegisterMap, RegisterSpec, WordOrder};
+
+/// Where profiles are looked up when the config does not say otherwise
+pub const DEFAULT_PROFILES_DIR: &str = "config/device_profiles";
+
+/// Profiles shipped with the source tree, available even without the files on disk
+const BUILTIN_PROFILES: &[(&str, &str)] = &[
+    (
+        "generic_battery",
+        include_str!("../../../config/device_profiles/generic_battery.toml"),
+    ),
+    (
+        "huawei_luna2000",
+        include_str!("../../../config/device_profiles/huawei_luna2000.toml"),
+    ),
+    (
+        "solaredge_storedge",
+        include_str!("../../../config/device_profiles/solaredge_storedge.toml"),
+    ),
+];
+
+const REGISTER_NAMES: &[&str] = &[
+    "soc",
+    "power",
+    "voltage",
+    "temperature",
+    "health",
+    "power_command",
+    "max_charge_power",
+    "max_discharge_power",
+    "capacity",
+];
+
+const REGISTER_FIELDS: &[&str] = &["address", "scale", "type", "signed", "word_order", "unit", "writable"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceInfo {
+    pub manufacturer: String,
+    pub model: String,
+    pub device_type: String,
+    pub protocol: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileModbusSettings {
+    pub default_port: u16,
+    pub unit_id: u8,
+    pub timeout_ms: u64,
+    pub retry_count: u32,
+    /// Word order for multi-register values without their own `_word_order`
+    #[serde(default)]
+    pub word_order: WordOrder,
+}
+
+/// Register that switches a hybrid inverter into external (EMS) control
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ControlModeSettings {
+    pub mode_address: u16,
+    pub remote_value: u16,
+}
+
+/// Nameplate data, used for anything the device does not report itself
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RatedCapabilities {
+    pub capacity_kwh: f64,
+    pub max_charge_kw: f64,
+    pub max_discharge_kw: f64,
+    pub efficiency: f64,
+    pub chemistry: BatteryChemistry,
+    pub nominal_voltage: f64,
+    pub degradation_per_cycle: f64,
+}
+
+impl From<&RatedCapabilities> for BatteryCapabilities {
+    fn from(rated: &RatedCapabilities) -> Self {
+        Self {
+            capacity_kwh: rated.capacity_kwh,
+            max_charge_kw: rated.max_charge_kw,
+            max_discharge_kw: rated.max_discharge_kw,
+            efficiency: rated.efficiency,
+            degradation_per_cycle: rated.degradation_per_cycle,
+            chemistry: rated.chemistry,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileLimits {
+    pub min_soc: f64,
+    pub max_soc: f64,
+    pub min_temperature: f64,
+    pub max_temperature: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProfile {
+    device: DeviceInfo,
+    modbus: ProfileModbusSettings,
+    registers: toml::Table,
+    control: Option<ControlModeSettings>,
+    capabilities: Option<RatedCapabilities>,
+    limits: Option<ProfileLimits>,
+}
+
+/// A parsed and validated device profile
+#[derive(Debug, Clone)]
+pub struct DeviceProfile {
+    /// File stem, e.g. `huawei_luna2000`
+    pub name: String,
+    pub device: DeviceInfo,
+    pub modbus: ProfileModbusSettings,
+    pub register_map: ProfileRegisterMap,
+    pub capabilities: Option<RatedCapabilities>,
+    pub limits: Option<ProfileLimits>,
+}
+
+impl DeviceProfile {
+    /// Parse and validate a profile from TOML source
+    pub fn from_toml_str(name: &str, source: &str) -> Result<Self> {
+        let raw: RawProfile = toml::from_str(source)
+            .with_context(|| format!("Device profile '{}' is not valid TOML", name))?;
+        Self::from_raw(name, raw).with_context(|| format!("Invalid device profile '{}'", name))
+    }
+
+    /// Load a profile file; the profile is named after the file stem
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| anyhow!("Invalid device profile path {}", path.display()))?;
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read device profile {}", path.display()))?;
+        Self::from_toml_str(name, &source)
+    }
+
+    /// Load every `*.toml` profile in a directory, keyed by name
+    ///
+    /// Fails on the first invalid file so a typo is reported at startup
+    /// rather than silently dropping the device.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<BTreeMap<String, Self>> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read device profile directory {}", dir.display()))?;
+
+        let mut profiles = BTreeMap::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+                continue;
+            }
+            let profile = Self::load(&path)?;
+            profiles.insert(profile.name.clone(), profile);
+        }
+        Ok(profiles)
+    }
+
+    /// Resolve a profile by name: `<dir>/<name>.toml` first, then the built-ins
+    pub fn find(dir: impl AsRef<Path>, name: &str) -> Result<Self> {
+        let path = dir.as_ref().join(format!("{}.toml", name));
+        if path.exists() {
+            return Self::load(path);
+        }
+        Self::builtin(name)
+    }
+
+    /// One of the profiles shipped in `config/device_profiles`
+    pub fn builtin(name: &str) -> Result<Self> {
+        let (_, source) = BUILTIN_PROFILES
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown device profile '{}' (built-in: {})",
+                    name,
+                    BUILTIN_PROFILES.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", ")
+                )
+            })?;
+        Self::from_toml_str(name, source)
+    }
+
+    fn from_raw(name: &str, raw: RawProfile) -> Result<Self> {
+        if raw.device.device_type != "Battery" {
+            bail!(
+                "device_type '{}' is not supported, expected 'Battery'",
+                raw.device.device_type
+            );
+        }
+        if raw.modbus.unit_id == 0 || raw.modbus.unit_id > 247 {
+            bail!("unit_id {} is outside 1-247", raw.modbus.unit_id);
+        }
+        if let Some(ref caps) = raw.capabilities {
+            validate_capabilities(caps)?;
+        }
+        if let Some(ref limits) = raw.limits {
+            validate_limits(limits)?;
+        }
+
+        let specs = parse_registers(&raw.registers, raw.modbus.word_order)?;
+        let required = |name: &str| {
+            specs
+                .get(name)
+                .copied()
+                .ok_or_else(|| anyhow!("missing required register '{}_address'", name))
+        };
+
+        let power = required("power")?;
+        // Writing commands to a measurement register would be ignored, or
+        // rejected, by the device
+        let power_command = match specs.get("power_command") {
+            Some(spec) => *spec,
+            None if power_writable(&raw.registers)? => power,
+            None => bail!("register 'power' is read-only, 'power_command_address' is required"),
+        };
+        let register_map = ProfileRegisterMap {
+            soc: required("soc")?,
+            power,
+            voltage: required("voltage")?,
+            temperature: required("temperature")?,
+            health: required("health")?,
+            power_command,
+            max_charge_power: specs.get("max_charge_power").copied(),
+            max_discharge_power: specs.get("max_discharge_power").copied(),
+            capacity: specs.get("capacity").copied(),
+            control: raw.control,
+            rated: raw.capabilities.as_ref().map(BatteryCapabilities::from),
+        };
+        register_map.validate()?;
+
+        if register_map.capacity.is_none() && raw.capabilities.is_none() {
+            bail!("either a 'capacity' register or a [capabilities] section is required");
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            device: raw.device,
+            modbus: raw.modbus,
+            register_map,
+            capabilities: raw.capabilities,
+            limits: raw.limits,
+        })
+    }
+}
+
+/// `RegisterMap` built from a device profile
+#[derive(Debug, Clone)]
+pub struct ProfileRegisterMap {
+    soc: RegisterSpec,
+    power: RegisterSpec,
+    voltage: RegisterSpec,
+    temperature: RegisterSpec,
+    health: RegisterSpec,
+    power_command: RegisterSpec,
+    max_charge_power: Option<RegisterSpec>,
+    max_discharge_power: Option<RegisterSpec>,
+    capacity: Option<RegisterSpec>,
+    control: Option<ControlModeSettings>,
+    rated: Option<BatteryCapabilities>,
+}
+
+impl ProfileRegisterMap {
+    /// Reject layouts where values overlap, or where the control-mode write
+    /// would land on a data register
+    fn validate(&self) -> Result<()> {
+        let reads = [
+            ("soc", Some(self.soc)),
+            ("power", Some(self.power)),
+            ("voltage", Some(self.voltage)),
+            ("temperature", Some(self.temperature)),
+            ("health", Some(self.health)),
+            ("max_charge_power", self.max_charge_power),
+            ("max_discharge_power", self.max_discharge_power),
+            ("capacity", self.capacity),
+        ];
+        let reads: Vec<_> = reads
+            .into_iter()
+            .filter_map(|(name, spec)| spec.map(|spec| (name, spec)))
+            .collect();
+
+        for (i, (name_a, a)) in reads.iter().enumerate() {
+            for (name_b, b) in &reads[i + 1..] {
+                if overlaps(a, b) {
+                    bail!(
+                        "registers '{}' ({} x{}) and '{}' ({} x{}) overlap",
+                        name_a,
+                        a.address,
+                        a.word_count(),
+                        name_b,
+                        b.address,
+                        b.word_count()
+                    );
+                }
+            }
+        }
+
+        if self.power_command.data_type == DataType::U16 || self.power_command.data_type == DataType::U32 {
+            bail!("power_command must be a signed type (positive = charge, negative = discharge)");
+        }
+
+        if let Some(control) = self.control {
+            let mode = RegisterSpec::new(control.mode_address, DataType::U16, 1.0);
+            for (name, spec) in reads.iter().chain(std::iter::once(&("power_command", self.power_command))) {
+                if overlaps(&mode, spec) {
+                    bail!(
+                        "control mode_address {} overlaps register '{}'",
+                        control.mode_address,
+                        name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RegisterMap for ProfileRegisterMap {
+    fn soc(&self) -> RegisterSpec { self.soc }
+    fn power(&self) -> RegisterSpec { self.power }
+    fn voltage(&self) -> RegisterSpec { self.voltage }
+    fn temperature(&self) -> RegisterSpec { self.temperature }
+    fn health(&self) -> RegisterSpec { self.health }
+    fn power_command(&self) -> RegisterSpec { self.power_command }
+
+    fn max_charge_power(&self) -> Option<RegisterSpec> { self.max_charge_power }
+    fn max_discharge_power(&self) -> Option<RegisterSpec> { self.max_discharge_power }
+    fn capacity(&self) -> Option<RegisterSpec> { self.capacity }
+
+    fn control_mode_register(&self) -> Option<u16> {
+        self.control.map(|c| c.mode_address)
+    }
+
+    fn remote_control_value(&self) -> u16 {
+        self.control.map(|c| c.remote_value).unwrap_or(1)
+    }
+
+    fn rated_capabilities(&self) -> Option<BatteryCapabilities> {
+        self.rated.clone()
+    }
+}
+
+fn overlaps(a: &RegisterSpec, b: &RegisterSpec) -> bool {
+    let a_end = a.address as u32 + a.word_count() as u32;
+    let b_end = b.address as u32 + b.word_count() as u32;
+    (a.address as u32) < b_end && (b.address as u32) < a_end
+}
+
+/// Split `<register>_<field>` keys and build a spec per register
+fn parse_registers(table: &toml::Table, default_order: WordOrder) -> Result<BTreeMap<String, RegisterSpec>> {
+    let mut fields: BTreeMap<&str, BTreeMap<&str, &toml::Value>> = BTreeMap::new();
+    for (key, value) in table {
+        let (register, field) = REGISTER_FIELDS
+            .iter()
+            .find_map(|field| {
+                key.strip_suffix(field)
+                    .and_then(|rest| rest.strip_suffix('_'))
+                    .and_then(|register| REGISTER_NAMES.iter().find(|name| **name == register))
+                    .map(|register| (*register, *field))
+            })
+            .ok_or_else(|| anyhow!("unknown register key '{}'", key))?;
+        if field == "writable" && register != "power" {
+            bail!("'{}' is not supported, only 'power_writable' is", key);
+        }
+        fields.entry(register).or_default().insert(field, value);
+    }
+
+    let mut specs = BTreeMap::new();
+    for (register, fields) in fields {
+        let spec = parse_register(&fields, default_order)
+            .with_context(|| format!("register '{}'", register))?;
+        specs.insert(register.to_string(), spec);
+    }
+    Ok(specs)
+}
+
+fn parse_register(fields: &BTreeMap<&str, &toml::Value>, default_order: WordOrder) -> Result<RegisterSpec> {
+    let address = fields
+        .get("address")
+        .ok_or_else(|| anyhow!("missing '_address'"))?
+        .as_integer()
+        .and_then(|a| u16::try_from(a).ok())
+        .ok_or_else(|| anyhow!("'_address' must be an integer in 0-65535"))?;
+
+    let scale = match fields.get("scale") {
+        None => 1.0,
+        Some(v) => v
+            .as_float()
+            .or_else(|| v.as_integer().map(|i| i as f64))
+            .ok_or_else(|| anyhow!("'_scale' must be a number"))?,
+    };
+    if !scale.is_finite() || scale == 0.0 {
+        bail!("'_scale' must be finite and non-zero, got {}", scale);
+    }
+
+    let signed = match fields.get("signed") {
+        None => None,
+        Some(v) => Some(v.as_bool().ok_or_else(|| anyhow!("'_signed' must be true or false"))?),
+    };
+    let data_type = match fields.get("type") {
+        Some(v) => {
+            let data_type: DataType = (*v)
+                .clone()
+                .try_into()
+                .map_err(|_| anyhow!("'_type' must be one of u16, i16, u32, i32, f32, got {}", v))?;
+            if let Some(signed) = signed {
+                if signed != data_type.is_signed() {
+                    bail!("'_signed = {}' contradicts '_type = {:?}'", signed, data_type);
+                }
+            }
+            data_type
+        }
+        None if signed == Some(true) => DataType::I16,
+        None => DataType::U16,
+    };
+
+    let word_order = match fields.get("word_order") {
+        None => default_order,
+        Some(v) => (*v)
+            .clone()
+            .try_into()
+            .map_err(|_| anyhow!("'_word_order' must be big_endian or little_endian, got {}", v))?,
+    };
+
+    if fields.get("unit").is_some_and(|v| !v.is_str()) {
+        bail!("'_unit' must be a string");
+    }
+
+    if address as u32 + data_type.word_count() as u32 > 0x1_0000 {
+        bail!("{:?} at {} runs past the end of the register space", data_type, address);
+    }
+
+    Ok(RegisterSpec::new(address, data_type, scale).with_word_order(word_order))
+}
+
+/// `power_writable`, true when absent
+fn power_writable(table: &toml::Table) -> Result<bool> {
+    match table.get("power_writable") {
+        None => Ok(true),
+        Some(v) => v.as_bool().ok_or_else(|| anyhow!("'power_writable' must be true or false")),
+    }
+}
+
+fn validate_capabilities(caps: &RatedCapabilities) -> Result<()> {
+    for (name, value) in [
+        ("capacity_kwh", caps.capacity_kwh),
+        ("max_charge_kw", caps.max_charge_kw),
+        ("max_discharge_kw", caps.max_discharge_kw),
+        ("nominal_voltage", caps.nominal_voltage),
+    ] {
+        if !value.is_finite() || value <= 0.0 {
+            bail!("capabilities.{} must be positive, got {}", name, value);
+        }
+    }
+    if !(0.5..=1.0).contains(&caps.efficiency) {
+        bail!("capabilities.efficiency must be within 0.5-1.0, got {}", caps.efficiency);
+    }
+    if !(0.0..=1.0).contains(&caps.degradation_per_cycle) {
+        bail!(
+            "capabilities.degradation_per_cycle must be within 0.0-1.0, got {}",
+            caps.degradation_per_cycle
+        );
+    }
+    Ok(())
+}
+
+fn validate_limits(limits: &ProfileLimits) -> Result<()> {
+    if !(0.0..=100.0).contains(&limits.min_soc)
+        || !(0.0..=100.0).contains(&limits.max_soc)
+        || limits.min_soc >= limits.max_soc
+    {
+        bail!(
+            "limits.min_soc ({}) and max_soc ({}) must satisfy 0 <= min < max <= 100",
+            limits.min_soc,
+            limits.max_soc
+        );
+    }
+    if limits.min_temperature >= limits.max_temperature {
+        bail!(
+            "limits.min_temperature ({}) must be below max_temperature ({})",
+            limits.min_temperature,
+            limits.max_temperature
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL: &str = r#"
+        [device]
+        manufacturer = "Acme"
+        model = "Box"
+        device_type = "Battery"
+        protocol = "ModbusTCP"
+
+        [modbus]
+        default_port = 502
+        unit_id = 3
+        timeout_ms = 2000
+        retry_count = 1
+        word_order = "little_endian"
+
+        [registers]
+        soc_address = 100
+        power_address = 101
+        power_type = "i32"
+        power_scale = 0.1
+        voltage_address = 103
+        voltage_type = "f32"
+        voltage_word_order = "big_endian"
+        temperature_address = 105
+        temperature_signed = true
+        health_address = 106
+        capacity_address = 107
+        capacity_type = "u32"
+
+        [control]
+        mode_address = 200
+        remote_value = 4
+    "#;
+
+    #[test]
+    fn test_builtin_profiles_are_valid() {
+        for (name, _) in BUILTIN_PROFILES {
+            DeviceProfile::builtin(name).unwrap();
+        }
+        assert!(DeviceProfile::builtin("nope").is_err());
+    }
+
+    #[test]
+    fn test_profiles_on_disk_match_builtins() {
+        let profiles = DeviceProfile::load_dir(DEFAULT_PROFILES_DIR).unwrap();
+        for (name, _) in BUILTIN_PROFILES {
+            assert!(profiles.contains_key(*name), "missing {}", name);
+        }
+    }
+
+    #[test]
+    fn test_huawei_profile_layout() {
+        let profile = DeviceProfile::builtin("huawei_luna2000").unwrap();
+        let map = &profile.register_map;
+
+        assert_eq!(map.soc(), RegisterSpec::new(37760, DataType::U16, 0.1));
+        assert_eq!(map.voltage().address, 37766);
+        assert_eq!(map.power().data_type, DataType::I16);
+        // Commands go to the forcible charge/discharge register, 37765 only
+        // reports the measured power
+        assert_eq!(map.power().address, 37765);
+        assert_eq!(map.power_command(), RegisterSpec::new(47100, DataType::I16, 1.0));
+        assert_eq!(map.control_mode_register(), Some(47000));
+        assert_eq!(map.remote_control_value(), 2);
+
+        let rated = map.rated_capabilities().unwrap();
+        assert_eq!(rated.capacity_kwh, 15.0);
+        assert_eq!(rated.chemistry, BatteryChemistry::LiFePO4);
+    }
+
+    #[test]
+    fn test_parse_types_and_word_order() {
+        let profile = DeviceProfile::from_toml_str("acme", MINIMAL).unwrap();
+        let map = &profile.register_map;
+
+        assert_eq!(profile.modbus.unit_id, 3);
+        assert_eq!(map.soc().data_type, DataType::U16);
+        assert_eq!(map.power().data_type, DataType::I32);
+        assert_eq!(map.power().word_order, WordOrder::LittleEndian);
+        assert_eq!(map.voltage().data_type, DataType::F32);
+        assert_eq!(map.voltage().word_order, WordOrder::BigEndian);
+        assert_eq!(map.temperature().data_type, DataType::I16);
+        assert_eq!(map.capacity().unwrap().data_type, DataType::U32);
+        assert_eq!(map.max_charge_power(), None);
+        assert_eq!(map.remote_control_value(), 4);
+        assert!(map.rated_capabilities().is_none());
+
+        // -1000.0 W as little-endian i32 at 0.1 W/LSB
+        let words = map.power().encode(-1000.0).unwrap();
+        assert_eq!(words, vec![0xD8F0, 0xFFFF]);
+        assert_eq!(map.power().decode(&words).unwrap(), -1000.0);
+    }
+
+    #[test]
+    fn test_rejects_invalid_profiles() {
+        let cases = [
+            ("soc_adress = 1", "unknown register key"),
+            ("power_type = \"u16\"", "signed type"),
+            ("power_type = \"i64\"", "_type"),
+            ("temperature_signed = false\ntemperature_type = \"i16\"", "contradicts"),
+            ("health_scale = 0.0", "non-zero"),
+            ("soc_address = 102", "overlap"),
+            ("power_writable = false", "'power_command_address' is required"),
+            ("power_writable = \"no\"", "true or false"),
+            ("soc_writable = false", "only 'power_writable'"),
+        ];
+        for (patch, expected) in cases {
+            let source = patch_registers(patch);
+            let err = format!("{:#}", DeviceProfile::from_toml_str("bad", &source).unwrap_err());
+            assert!(err.contains(expected), "{}: {}", patch, err);
+        }
+
+        let control_clash = MINIMAL.replace("mode_address = 200", "mode_address = 102");
+        assert!(DeviceProfile::from_toml_str("bad", &control_clash).is_err());
+
+        let no_capacity = MINIMAL.replace("capacity_address = 107", "").replace("capacity_type = \"u32\"", "");
+        assert!(DeviceProfile::from_toml_str("bad", &no_capacity).is_err());
+    }
+
+    /// Override keys in MINIMAL's [registers] section
+    fn patch_registers(patch: &str) -> String {
+        let mut lines: Vec<String> = MINIMAL.lines().map(str::to_string).collect();
+        for entry in patch.lines() {
+            let key = entry.split('=').next().unwrap().trim();
+            lines.retain(|l| l.split('=').next().unwrap().trim() != key);
+        }
+        let at = lines.iter().position(|l| l.trim() == "[registers]").unwrap();
+        lines.insert(at + 1, patch.to_string());
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,44 @@
+use crate::domain::{ControllableLoad, LoadState};
+use crate::modbus::client::ModbusClient;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use tracing::debug;
+
+/// Load switched by one coil of a Modbus relay module
+///
+/// Relay modules carry no meter, so no power is reported.
+pub struct ModbusRelayLoad {
+    client: ModbusClient,
+    coil: u16,
+}
+
+impl ModbusRelayLoad {
+    pub fn with_client(client: ModbusClient, coil: u16) -> Self {
+        Self { client, coil }
+    }
+}
+
+#[async_trait]
+impl ControllableLoad for ModbusRelayLoad {
+    async fn read_state(&self) -> Result<LoadState> {
+        let coils = self.client.read_coils(self.coil, 1).await?;
+        let on = *coils
+            .first()
+            .with_context(|| format!("Empty coil read at {}", self.coil))?;
+        Ok(LoadState {
+            on,
+            power_w: None,
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn switch(&self, on: bool) -> Result<()> {
+        self.client
+            .write_single_coil(self.coil, on)
+            .await
+            .with_context(|| format!("Failed to switch relay coil {}", self.coil))?;
+        debug!(coil = self.coil, on, "Relay switched");
+        Ok(())
+    }
+}
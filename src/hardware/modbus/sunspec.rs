@@ -0,0 +1,432 @@
+//! Inverter, grid meter and battery backed by SunSpec models
+//!
+//! All three share one `SunSpecReader`, so a hybrid inverter exposing
+//! models 103, 124 and 203 on the same unit ID needs a single connection.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{Local, NaiveDate, Utc};
+use tracing::{debug, warn};
+
+use crate::domain::battery::{
+    Battery, BatteryCapabilities, BatteryChemistry, BatteryState, BatteryStatus,
+};
+use crate::domain::inverter::{
+    Inverter, InverterCapabilities, InverterError, InverterMode, InverterState, InverterStatus,
+};
+use crate::domain::grid_meter::{GridMeter, GridMeterReading, PhaseReading};
+use crate::domain::HealthStatus;
+use crate::modbus::sunspec::{
+    model_id, BatteryBaseModel, MeterModel, OperatingState, SunSpecReader,
+};
+
+/// Used when neither the battery nor the inverter reports a temperature
+const NOMINAL_TEMPERATURE_C: f64 = 25.0;
+
+/// Inverter exposing SunSpec models 101-103 (plus 120/121/123/160 if present)
+pub struct SunSpecInverter {
+    reader: SunSpecReader,
+    caps: InverterCapabilities,
+    /// Lifetime energy at the start of the local day, for `daily_energy_kwh`
+    day_start: Mutex<Option<(NaiveDate, f64)>>,
+}
+
+impl SunSpecInverter {
+    pub async fn connect(addr: &str, unit_id: u8) -> Result<Self> {
+        Self::new(SunSpecReader::connect(addr, unit_id).await?).await
+    }
+
+    pub async fn new(reader: SunSpecReader) -> Result<Self> {
+        if !reader.map().is_inverter() {
+            bail!("SunSpec device has no inverter model (101-103)");
+        }
+        let caps = Self::read_capabilities(&reader).await?;
+        Ok(Self {
+            reader,
+            caps,
+            day_start: Mutex::new(None),
+        })
+    }
+
+    async fn read_capabilities(reader: &SunSpecReader) -> Result<InverterCapabilities> {
+        let map = reader.map();
+        let nameplate = match map.has(model_id::NAMEPLATE) {
+            true => Some(reader.nameplate().await?),
+            false => None,
+        };
+        let settings = match map.has(model_id::BASIC_SETTINGS) {
+            true => Some(reader.settings().await?),
+            false => None,
+        };
+        let mppt_channels = match map.has(model_id::MPPT) {
+            true => reader.mppt().await?.modules.len().max(1),
+            false => 1,
+        };
+
+        let rated_power_w = nameplate
+            .as_ref()
+            .and_then(|n| n.max_power_w)
+            .or_else(|| settings.as_ref().and_then(|s| s.max_power_w))
+            .context("SunSpec inverter reports no rated power (WRtg in model 120 or WMax in 121)")?;
+        let max_ac_output_w = settings
+            .as_ref()
+            .and_then(|s| s.max_power_w)
+            .unwrap_or(rated_power_w);
+
+        Ok(InverterCapabilities {
+            rated_power_w,
+            // SunSpec has no DC input rating; the AC rating is the safe lower bound
+            max_dc_input_w: rated_power_w,
+            max_ac_output_w,
+            max_efficiency_percent: 97.0,
+            mppt_channels: mppt_channels.min(u8::MAX as usize) as u8,
+            supports_export_limit: map.has(model_id::IMMEDIATE_CONTROLS),
+            supports_frequency_regulation: false,
+        })
+    }
+
+    /// Energy since local midnight, tracked from the lifetime counter
+    fn daily_energy_kwh(&self, total_kwh: f64) -> f64 {
+        let today = Local::now().date_naive();
+        let mut day_start = self.day_start.lock().unwrap_or_else(|e| e.into_inner());
+        match *day_start {
+            Some((date, start)) if date == today && total_kwh >= start => total_kwh - start,
+            _ => {
+                *day_start = Some((today, total_kwh));
+                0.0
+            }
+        }
+    }
+}
+
+fn inverter_status(state: Option<OperatingState>) -> InverterStatus {
+    match state {
+        Some(OperatingState::Mppt) | Some(OperatingState::Throttled) => InverterStatus::Normal,
+        Some(OperatingState::Starting) => InverterStatus::InitialStandby,
+        Some(OperatingState::Sleeping) | Some(OperatingState::Standby) => InverterStatus::Standby,
+        Some(OperatingState::Off) | Some(OperatingState::ShuttingDown) => InverterStatus::Shutdown,
+        Some(OperatingState::Fault) => InverterStatus::Fault,
+        Some(OperatingState::Unknown(_)) | None => InverterStatus::Warning,
+    }
+}
+
+#[async_trait]
+impl Inverter for SunSpecInverter {
+    async fn read_state(&self) -> Result<InverterState> {
+        let inverter = self.reader.inverter().await?;
+        let mppt_power = match self.reader.map().has(model_id::MPPT) {
+            true => self.reader.mppt().await?.total_power_w(),
+            false => None,
+        };
+
+        let ac_power = inverter.ac_power_w.unwrap_or(0.0);
+        let dc_power = inverter.dc_power_w.or(mppt_power).unwrap_or(0.0);
+        let efficiency_percent = if dc_power > 0.0 {
+            (ac_power / dc_power * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        };
+        let total_energy_kwh = inverter.ac_energy_wh.unwrap_or(0.0) / 1000.0;
+        let status = inverter_status(inverter.state);
+
+        Ok(InverterState {
+            mode: match status {
+                InverterStatus::Standby | InverterStatus::Shutdown => InverterMode::Standby,
+                _ => InverterMode::GridTied,
+            },
+            pv_power_w: mppt_power.unwrap_or(dc_power),
+            ac_output_power_w: ac_power,
+            dc_input_power_w: dc_power,
+            grid_frequency_hz: inverter.frequency_hz.unwrap_or(0.0),
+            ac_voltage_v: inverter.phase_voltage_v[0].unwrap_or(0.0),
+            dc_voltage_v: inverter.dc_voltage_v.unwrap_or(0.0),
+            temperature_c: inverter.cabinet_temperature_c.unwrap_or(0.0),
+            efficiency_percent,
+            status,
+            daily_energy_kwh: self.daily_energy_kwh(total_energy_kwh),
+            total_energy_kwh,
+        })
+    }
+
+    async fn set_mode(&self, mode: InverterMode) -> Result<()> {
+        match mode {
+            InverterMode::GridTied | InverterMode::Hybrid => self.reader.set_connected(true).await,
+            InverterMode::Standby => self.reader.set_connected(false).await,
+            InverterMode::OffGrid | InverterMode::Backup => Err(InverterError::InvalidModeTransition(
+                "SunSpec".to_string(),
+                format!("{:?}", mode),
+            )
+            .into()),
+        }
+    }
+
+    /// Limits AC output through model 123 `WMaxLimPct`
+    async fn set_export_limit(&self, watts: f64) -> Result<()> {
+        if !self.caps.supports_export_limit {
+            return Err(InverterError::ExportLimitNotSupported.into());
+        }
+        if !watts.is_finite() || watts < 0.0 {
+            return Err(InverterError::InvalidExportLimit(watts).into());
+        }
+
+        let max_w = self.caps.max_ac_output_w;
+        if watts >= max_w {
+            debug!("Export limit {} W at or above WMax {} W, lifting limit", watts, max_w);
+            self.reader.set_power_limit_pct(None).await
+        } else {
+            self.reader.set_power_limit_pct(Some(watts / max_w * 100.0)).await
+        }
+    }
+
+    fn capabilities(&self) -> InverterCapabilities {
+        self.caps.clone()
+    }
+
+    async fn emergency_shutdown(&self) -> Result<()> {
+        warn!("Emergency shutdown: disconnecting SunSpec inverter from the grid");
+        self.reader.set_connected(false).await
+    }
+}
+
+/// Grid meter exposing SunSpec models 201-204
+pub struct SunSpecMeter {
+    reader: SunSpecReader,
+}
+
+impl SunSpecMeter {
+    pub async fn connect(addr: &str, unit_id: u8) -> Result<Self> {
+        Self::new(SunSpecReader::connect(addr, unit_id).await?)
+    }
+
+    pub fn new(reader: SunSpecReader) -> Result<Self> {
+        if !reader.map().is_meter() {
+            bail!("SunSpec device has no meter model (201-204)");
+        }
+        Ok(Self { reader })
+    }
+
+    /// Raw model 201-204 readings; positive power is grid import
+    pub async fn read_model(&self) -> Result<MeterModel> {
+        self.reader.meter().await
+    }
+}
+
+#[async_trait]
+impl GridMeter for SunSpecMeter {
+    async fn read(&self) -> Result<GridMeterReading> {
+        let meter = self.reader.meter().await?;
+        let power_w = meter.power_w.context("SunSpec meter does not report total power")?;
+
+        let mut phases = [PhaseReading::default(); 3];
+        for (i, phase) in phases.iter_mut().take(meter.phases as usize).enumerate() {
+            *phase = PhaseReading {
+                voltage_v: meter.phase_voltage_v[i].unwrap_or(0.0),
+                current_a: meter.phase_current_a[i].unwrap_or(0.0).abs(),
+                power_w: meter.phase_power_w[i].unwrap_or(0.0),
+            };
+        }
+        // Single-phase meters may only fill in the totals
+        if meter.phases == 1 {
+            phases[0].current_a = meter.phase_current_a[0].or(meter.current_a).unwrap_or(0.0).abs();
+            phases[0].power_w = meter.phase_power_w[0].unwrap_or(power_w);
+        }
+
+        Ok(GridMeterReading {
+            phases,
+            power_w,
+            frequency_hz: meter.frequency_hz,
+            import_energy_kwh: meter.imported_wh.unwrap_or(0.0) / 1000.0,
+            export_energy_kwh: meter.exported_wh.unwrap_or(0.0) / 1000.0,
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        Ok(match self.reader.health_check().await {
+            Ok(()) => HealthStatus::Healthy,
+            Err(_) => HealthStatus::Offline,
+        })
+    }
+}
+
+/// Battery controlled through SunSpec model 124, measured through 802 if present
+///
+/// Without model 802 the device does not report battery power, so the
+/// last commanded setpoint is reported instead.
+pub struct SunSpecBattery {
+    reader: SunSpecReader,
+    capabilities: BatteryCapabilities,
+    /// Last commanded power as `f64` bits
+    last_command_w: AtomicU64,
+}
+
+impl SunSpecBattery {
+    pub async fn connect(addr: &str, unit_id: u8) -> Result<Self> {
+        Self::new(SunSpecReader::connect(addr, unit_id).await?).await
+    }
+
+    pub async fn new(reader: SunSpecReader) -> Result<Self> {
+        let map = reader.map();
+        if !map.has(model_id::STORAGE) {
+            bail!("SunSpec device has no storage control model (124)");
+        }
+        if !map.is_inverter() {
+            warn!("SunSpec storage reports no temperature; battery temperature limits cannot be enforced");
+        }
+
+        let capabilities = Self::read_capabilities(&reader).await?;
+        Ok(Self {
+            reader,
+            capabilities,
+            last_command_w: AtomicU64::new(0f64.to_bits()),
+        })
+    }
+
+    async fn read_capabilities(reader: &SunSpecReader) -> Result<BatteryCapabilities> {
+        // CRITICAL FIX: Fail early if capabilities cannot be read
+        // Wrong capacity = wrong SoC bookkeeping in the optimizer.
+        let map = reader.map();
+        let storage = reader.storage().await?;
+        let battery = match map.has(model_id::BATTERY_BASE) {
+            true => Some(reader.battery().await?),
+            false => None,
+        };
+        let nameplate = match map.has(model_id::NAMEPLATE) {
+            true => Some(reader.nameplate().await?),
+            false => None,
+        };
+
+        let capacity_wh = battery
+            .as_ref()
+            .and_then(|b| b.capacity_wh)
+            .or_else(|| nameplate.as_ref().and_then(|n| n.energy_rating_wh))
+            .ok_or_else(|| anyhow!("SunSpec device reports no battery capacity (802 WHRtg or 120 WHRtg)"))?;
+        let max_charge_w = battery
+            .as_ref()
+            .and_then(|b| b.max_charge_w)
+            .or(storage.max_charge_w)
+            .or_else(|| nameplate.as_ref().and_then(|n| n.max_charge_rate_w))
+            .ok_or_else(|| anyhow!("SunSpec device reports no maximum charge power"))?;
+        let max_discharge_w = battery
+            .as_ref()
+            .and_then(|b| b.max_discharge_w)
+            .or_else(|| nameplate.as_ref().and_then(|n| n.max_discharge_rate_w))
+            .unwrap_or(max_charge_w);
+
+        let chemistry = match battery.as_ref().and_then(|b| b.battery_type) {
+            Some(BatteryBaseModel::TYPE_LEAD_ACID) => BatteryChemistry::LeadAcid,
+            _ => BatteryChemistry::Unknown,
+        };
+
+        Ok(BatteryCapabilities {
+            capacity_kwh: capacity_wh / 1000.0,
+            max_charge_kw: max_charge_w / 1000.0,
+            max_discharge_kw: max_discharge_w / 1000.0,
+            efficiency: 0.95, // 95% typical round-trip efficiency
+            degradation_per_cycle: 0.0001, // 0.01% per cycle
+            chemistry,
+        })
+    }
+
+    fn last_command_w(&self) -> f64 {
+        f64::from_bits(self.last_command_w.load(Ordering::Relaxed))
+    }
+}
+
+#[async_trait]
+impl Battery for SunSpecBattery {
+    async fn read_state(&self) -> Result<BatteryState> {
+        let map = self.reader.map();
+        let battery = match map.has(model_id::BATTERY_BASE) {
+            true => Some(self.reader.battery().await?),
+            false => None,
+        };
+        let storage = match battery {
+            Some(_) => None,
+            None => Some(self.reader.storage().await?),
+        };
+        let temperature_c = match map.is_inverter() {
+            true => self.reader.inverter().await?.cabinet_temperature_c,
+            false => None,
+        };
+
+        let soc_percent = battery
+            .as_ref()
+            .and_then(|b| b.soc_pct)
+            .or_else(|| storage.as_ref().and_then(|s| s.state_of_charge_pct))
+            .context("SunSpec device reports no state of charge")?;
+        let power_w = battery
+            .as_ref()
+            .and_then(|b| b.power_w)
+            .unwrap_or_else(|| self.last_command_w());
+
+        Ok(BatteryState {
+            soc_percent,
+            power_w,
+            voltage_v: battery
+                .as_ref()
+                .and_then(|b| b.voltage_v)
+                .or_else(|| storage.as_ref().and_then(|s| s.battery_voltage_v))
+                .unwrap_or(0.0),
+            temperature_c: temperature_c.unwrap_or(NOMINAL_TEMPERATURE_C),
+            health_percent: battery.as_ref().and_then(|b| b.soh_pct).unwrap_or(100.0),
+            status: if power_w > 10.0 {
+                BatteryStatus::Charging
+            } else if power_w < -10.0 {
+                BatteryStatus::Discharging
+            } else {
+                BatteryStatus::Idle
+            },
+        })
+    }
+
+    async fn set_power(&self, watts: f64) -> Result<()> {
+        let max_charge_w = self.capabilities.max_charge_kw * 1000.0;
+        let max_discharge_w = self.capabilities.max_discharge_kw * 1000.0;
+        if watts > max_charge_w {
+            bail!("Charge power {} W exceeds maximum {} W", watts, max_charge_w);
+        }
+        if watts < -max_discharge_w {
+            bail!("Discharge power {} W exceeds maximum {} W", -watts, max_discharge_w);
+        }
+
+        self.reader.set_storage_power(watts).await?;
+        self.last_command_w.store(watts.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn capabilities(&self) -> BatteryCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn reconnect(&self) -> Result<()> {
+        self.reader.client().reconnect().await
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        match self.reader.health_check().await {
+            Ok(_) => Ok(HealthStatus::Healthy),
+            Err(_) => Ok(HealthStatus::Offline),
+        }
+    }
+
+    async fn emergency_shutdown(&self) -> Result<()> {
+        warn!("Emergency shutdown: holding SunSpec battery at 0 W");
+        self.set_power(0.0).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operating_state_mapping() {
+        assert_eq!(inverter_status(Some(OperatingState::Mppt)), InverterStatus::Normal);
+        assert_eq!(inverter_status(Some(OperatingState::Fault)), InverterStatus::Fault);
+        assert_eq!(inverter_status(Some(OperatingState::Sleeping)), InverterStatus::Standby);
+        assert_eq!(inverter_status(None), InverterStatus::Warning);
+    }
+}
@@ -1,16 +1,23 @@
 //! # OCPP EV Charger Implementation
 //!
 //! Implements the EvCharger domain trait using OCPP 1.6 protocol for real EV chargers.
+//!
+//! The charger dials into the controller's OCPP central system. While it is
+//! connected the central system attaches a [`ChargePointSession`], which carries
+//! SetChargingProfile and RemoteStart/Stop requests back to the charger.
 
 use crate::domain::ev_charger::{
     ChargerCapabilities, ChargerError, ChargerState, ChargerStatus, ConnectorType, EvCharger,
 };
+use crate::domain::schedule::ScheduleInterval;
+use crate::ocpp::central_system::ChargePointSession;
 use crate::ocpp::messages::{
     ChargePointStatus, ChargingProfile, ChargingProfileKind, ChargingProfilePurpose,
-    ChargingRateUnit, ChargingSchedule, ChargingSchedulePeriod, RemoteStartStopStatus,
-    RemoteStartTransactionRequest, RemoteStopTransactionRequest,
+    ChargingProfileStatus, ChargingRateUnit, ChargingSchedule, ChargingSchedulePeriod,
+    RemoteStartStopStatus, RemoteStartTransactionRequest, RemoteStartTransactionResponse,
+    RemoteStopTransactionRequest, RemoteStopTransactionResponse, SetChargingProfileRequest,
+    SetChargingProfileResponse,
 };
-use crate::ocpp::{ConnectionState, OcppClient};
 use anyhow::Result;
 use async_trait::async_trait;
 use chrono::Utc;
@@ -21,9 +28,7 @@ use tracing::{debug, info, warn};
 /// Configuration for OCPP EV Charger
 #[derive(Debug, Clone)]
 pub struct OcppEvChargerConfig {
-    /// OCPP central system URL (WebSocket endpoint)
-    pub central_system_url: String,
-    /// Charge point identifier
+    /// Charge point identifier the charger uses when connecting
     pub charge_point_id: String,
     /// Connector ID (typically 1 for single-connector chargers)
     pub connector_id: i32,
@@ -36,7 +41,6 @@ pub struct OcppEvChargerConfig {
 impl Default for OcppEvChargerConfig {
     fn default() -> Self {
         Self {
-            central_system_url: "ws://localhost:8080/ocpp".to_string(),
             charge_point_id: "CP001".to_string(),
             connector_id: 1,
             capabilities: ChargerCapabilities {
@@ -60,6 +64,10 @@ struct OcppChargerInternalState {
     pub charger_state: ChargerState,
     /// Current transaction ID (if charging session active)
     pub transaction_id: Option<i32>,
+    /// Energy register at transaction start (Wh)
+    pub meter_start_wh: Option<i32>,
+    /// Transaction start time
+    pub transaction_started_at: Option<chrono::DateTime<Utc>>,
     /// Last known OCPP status
     pub last_ocpp_status: ChargePointStatus,
     /// Last status update timestamp
@@ -82,6 +90,8 @@ impl Default for OcppChargerInternalState {
                 energy_discharged_kwh: 0.0,
             },
             transaction_id: None,
+            meter_start_wh: None,
+            transaction_started_at: None,
             last_ocpp_status: ChargePointStatus::Available,
             last_update: Utc::now(),
         }
@@ -91,45 +101,56 @@ impl Default for OcppChargerInternalState {
 /// OCPP-based EV Charger implementation
 pub struct OcppEvCharger {
     config: OcppEvChargerConfig,
-    ocpp_client: Arc<OcppClient>,
+    session: RwLock<Option<Arc<ChargePointSession>>>,
     internal_state: Arc<RwLock<OcppChargerInternalState>>,
 }
 
 impl OcppEvCharger {
     /// Create a new OCPP EV Charger
     pub fn new(config: OcppEvChargerConfig) -> Self {
-        let ocpp_client = Arc::new(OcppClient::new(
-            config.central_system_url.clone(),
-            config.charge_point_id.clone(),
-        ));
-
         Self {
             config,
-            ocpp_client,
+            session: RwLock::new(None),
             internal_state: Arc::new(RwLock::new(OcppChargerInternalState::default())),
         }
     }
 
-    /// Connect to OCPP central system
-    pub async fn connect(&self) -> Result<()> {
-        info!("Connecting OCPP charger {}", self.config.charge_point_id);
-        self.ocpp_client.connect().await?;
-        Ok(())
+    pub fn charge_point_id(&self) -> &str {
+        &self.config.charge_point_id
     }
 
-    /// Disconnect from OCPP central system
-    pub async fn disconnect(&self) -> Result<()> {
-        info!(
-            "Disconnecting OCPP charger {}",
-            self.config.charge_point_id
-        );
-        self.ocpp_client.disconnect().await?;
-        Ok(())
+    pub fn connector_id(&self) -> i32 {
+        self.config.connector_id
+    }
+
+    /// Attach the session of the charger's WebSocket connection
+    pub async fn attach_session(&self, session: Arc<ChargePointSession>) {
+        info!("OCPP charger {} attached", self.config.charge_point_id);
+        *self.session.write().await = Some(session);
+    }
+
+    /// Detach the session once the charger disconnects
+    pub async fn detach_session(&self) {
+        info!("OCPP charger {} detached", self.config.charge_point_id);
+        *self.session.write().await = None;
     }
 
     /// Check connection status
     pub async fn is_connected(&self) -> bool {
-        self.ocpp_client.get_state().await == ConnectionState::Connected
+        self.session.read().await.is_some()
+    }
+
+    /// Transaction currently running on the connector, if any
+    pub async fn transaction_id(&self) -> Option<i32> {
+        self.internal_state.read().await.transaction_id
+    }
+
+    async fn session(&self) -> Result<Arc<ChargePointSession>> {
+        self.session
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| ChargerError::Offline.into())
     }
 
     /// Update charger state from OCPP status notification
@@ -182,7 +203,9 @@ impl OcppEvCharger {
 
     /// Update charger state from meter values
     ///
-    /// This should be called when receiving MeterValues messages from the charger
+    /// This should be called when receiving MeterValues messages from the charger.
+    /// `energy_kwh` is the charger's energy register; during a transaction the
+    /// delivered energy is reported relative to the register at transaction start.
     pub async fn handle_meter_values(
         &self,
         energy_kwh: Option<f64>,
@@ -196,7 +219,10 @@ impl OcppEvCharger {
             energy_kwh, power_w, current_a, soc_percent);
 
         if let Some(energy) = energy_kwh {
-            state.charger_state.energy_delivered_kwh = energy;
+            state.charger_state.energy_delivered_kwh = match state.meter_start_wh {
+                Some(start_wh) => (energy - start_wh as f64 / 1000.0).max(0.0),
+                None => energy,
+            };
         }
 
         if let Some(power) = power_w {
@@ -216,6 +242,51 @@ impl OcppEvCharger {
         Ok(())
     }
 
+    /// Record a transaction started by the charger (StartTransaction)
+    pub async fn handle_transaction_started(
+        &self,
+        transaction_id: i32,
+        meter_start_wh: i32,
+    ) -> Result<()> {
+        let mut state = self.internal_state.write().await;
+        state.transaction_id = Some(transaction_id);
+        state.meter_start_wh = Some(meter_start_wh);
+        state.transaction_started_at = Some(Utc::now());
+        state.charger_state.energy_delivered_kwh = 0.0;
+        state.charger_state.connected = true;
+        state.last_update = Utc::now();
+        Ok(())
+    }
+
+    /// Record a transaction ended by the charger (StopTransaction)
+    pub async fn handle_transaction_stopped(
+        &self,
+        transaction_id: i32,
+        meter_stop_wh: i32,
+    ) -> Result<()> {
+        let mut state = self.internal_state.write().await;
+        if state.transaction_id != Some(transaction_id) {
+            debug!(
+                "Ignoring StopTransaction {} for charger {}: not the active transaction",
+                transaction_id, self.config.charge_point_id
+            );
+            return Ok(());
+        }
+
+        if let Some(start_wh) = state.meter_start_wh {
+            state.charger_state.energy_delivered_kwh =
+                (meter_stop_wh - start_wh).max(0) as f64 / 1000.0;
+        }
+        state.transaction_id = None;
+        state.meter_start_wh = None;
+        state.transaction_started_at = None;
+        state.charger_state.charging = false;
+        state.charger_state.current_amps = 0.0;
+        state.charger_state.power_w = 0.0;
+        state.last_update = Utc::now();
+        Ok(())
+    }
+
     /// Send a charging profile to the charger using SetChargingProfile
     ///
     /// This is the primary way to control charging current in OCPP
@@ -226,7 +297,7 @@ impl OcppEvCharger {
         );
 
         // Create a charging profile with a single period
-        let _profile = ChargingProfile {
+        let profile = ChargingProfile {
             charging_profile_id: 1,
             stack_level: 0,
             charging_profile_purpose: ChargingProfilePurpose::TxProfile,
@@ -247,12 +318,24 @@ impl OcppEvCharger {
             },
         };
 
-        // TODO: Actually send SetChargingProfile OCPP message via WebSocket
-        // For now, just log it
-        info!(
-            "Would send SetChargingProfile to connector {} with limit {} A",
-            self.config.connector_id, max_current_amps
-        );
+        let request = SetChargingProfileRequest {
+            connector_id: self.config.connector_id,
+            cs_charging_profiles: profile,
+        };
+        let response: SetChargingProfileResponse = self
+            .session()
+            .await?
+            .call_typed("SetChargingProfile", &request)
+            .await
+            .map_err(|e| ChargerError::Communication(format!("{:#}", e)))?;
+
+        if response.status != ChargingProfileStatus::Accepted {
+            return Err(ChargerError::Communication(format!(
+                "SetChargingProfile {:?} by charger",
+                response.status
+            ))
+            .into());
+        }
 
         // Update internal state to reflect the new current limit
         let mut state = self.internal_state.write().await;
@@ -266,30 +349,96 @@ impl OcppEvCharger {
         Ok(())
     }
 
+    /// Install the optimizer's plan as the charger's default profile
+    ///
+    /// Sent as a TxDefaultProfile so it also covers sessions that start
+    /// later. The per-tick TxProfile from `set_current` takes precedence
+    /// while the controller is in contact.
+    async fn send_charging_plan(&self, plan: &[ScheduleInterval]) -> Result<()> {
+        let Some(start) = plan.first().map(|p| p.time_start) else {
+            return Ok(());
+        };
+        let caps = &self.config.capabilities;
+        let watts_per_amp = caps.voltage_v * caps.phases as f64;
+        let periods = plan
+            .iter()
+            .map(|interval| {
+                let amps =
+                    (interval.target_power_w.max(0.0) / watts_per_amp).min(caps.max_current_amps);
+                ChargingSchedulePeriod {
+                    start_period: (interval.time_start - start).num_seconds() as i32,
+                    // Below the minimum current the charger cannot charge at all
+                    limit: if amps < caps.min_current_amps {
+                        0.0
+                    } else {
+                        amps
+                    },
+                    number_phases: Some(caps.phases as i32),
+                }
+            })
+            .collect();
+        let end = plan.last().map(|p| p.time_end).unwrap_or(start);
+
+        let profile = ChargingProfile {
+            charging_profile_id: 2,
+            stack_level: 0,
+            charging_profile_purpose: ChargingProfilePurpose::TxDefaultProfile,
+            charging_profile_kind: ChargingProfileKind::Absolute,
+            recurrency_kind: None,
+            valid_from: Some(start),
+            valid_to: Some(end),
+            charging_schedule: ChargingSchedule {
+                charging_rate_unit: ChargingRateUnit::A,
+                charging_schedule_period: periods,
+                duration: Some((end - start).num_seconds() as i32),
+                start_schedule: Some(start),
+                min_charging_rate: Some(caps.min_current_amps),
+            },
+        };
+
+        let request = SetChargingProfileRequest {
+            connector_id: self.config.connector_id,
+            cs_charging_profiles: profile,
+        };
+        let response: SetChargingProfileResponse = self
+            .session()
+            .await?
+            .call_typed("SetChargingProfile", &request)
+            .await
+            .map_err(|e| ChargerError::Communication(format!("{:#}", e)))?;
+
+        if response.status != ChargingProfileStatus::Accepted {
+            return Err(ChargerError::Communication(format!(
+                "SetChargingProfile {:?} by charger",
+                response.status
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
     /// Send RemoteStartTransaction command
     async fn send_remote_start(&self) -> Result<()> {
         debug!("Sending RemoteStartTransaction");
 
-        let _request = RemoteStartTransactionRequest {
+        let request = RemoteStartTransactionRequest {
             id_tag: self.config.default_id_tag.clone(),
             connector_id: Some(self.config.connector_id),
             charging_profile: None, // Can set initial profile here if needed
         };
 
-        // TODO: Actually send OCPP message via WebSocket
-        info!(
-            "Would send RemoteStartTransaction to connector {}",
-            self.config.connector_id
-        );
-
-        // For now, simulate success
-        let status = RemoteStartStopStatus::Accepted;
+        let response: RemoteStartTransactionResponse = self
+            .session()
+            .await?
+            .call_typed("RemoteStartTransaction", &request)
+            .await
+            .map_err(|e| ChargerError::Communication(format!("{:#}", e)))?;
 
-        if status == RemoteStartStopStatus::Accepted {
+        if response.status == RemoteStartStopStatus::Accepted {
+            // The charger confirms with StartTransaction, which assigns the transaction ID
             let mut state = self.internal_state.write().await;
             state.charger_state.charging = true;
             state.charger_state.status = ChargerStatus::Charging;
-            state.transaction_id = Some(1); // Simulated transaction ID
             Ok(())
         } else {
             Err(ChargerError::SessionError(
@@ -310,21 +459,22 @@ impl OcppEvCharger {
 
         drop(state); // Release read lock
 
-        let _request = RemoteStopTransactionRequest { transaction_id };
-
-        // TODO: Actually send OCPP message via WebSocket
-        info!("Would send RemoteStopTransaction for transaction {}", transaction_id);
+        let request = RemoteStopTransactionRequest { transaction_id };
 
-        // For now, simulate success
-        let status = RemoteStartStopStatus::Accepted;
+        let response: RemoteStopTransactionResponse = self
+            .session()
+            .await?
+            .call_typed("RemoteStopTransaction", &request)
+            .await
+            .map_err(|e| ChargerError::Communication(format!("{:#}", e)))?;
 
-        if status == RemoteStartStopStatus::Accepted {
+        if response.status == RemoteStartStopStatus::Accepted {
+            // The transaction itself ends when the charger sends StopTransaction
             let mut state = self.internal_state.write().await;
             state.charger_state.charging = false;
             state.charger_state.status = ChargerStatus::SuspendedEVSE;
             state.charger_state.current_amps = 0.0;
             state.charger_state.power_w = 0.0;
-            state.transaction_id = None;
             Ok(())
         } else {
             Err(ChargerError::SessionError("Remote stop rejected by charger".to_string()).into())
@@ -357,7 +507,12 @@ impl EvCharger for OcppEvCharger {
         }
 
         let state = self.internal_state.read().await;
-        Ok(state.charger_state.clone())
+        let mut charger_state = state.charger_state.clone();
+        if let Some(started) = state.transaction_started_at {
+            charger_state.session_duration_seconds =
+                (Utc::now() - started).num_seconds().max(0) as u64;
+        }
+        Ok(charger_state)
     }
 
     async fn set_current(&self, amps: f64) -> Result<()> {
@@ -448,11 +603,36 @@ impl EvCharger for OcppEvCharger {
     fn capabilities(&self) -> ChargerCapabilities {
         self.config.capabilities.clone()
     }
+
+    async fn set_charging_plan(&self, plan: &[ScheduleInterval]) -> Result<()> {
+        if !self.is_connected().await {
+            return Err(ChargerError::Offline.into());
+        }
+        self.send_charging_plan(plan).await?;
+        debug!(
+            "Sent {}-period charging plan to OCPP charger {}",
+            plan.len(),
+            self.config.charge_point_id
+        );
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ocpp::central_system::scripted_session;
+
+    /// Charger attached to a charge point that accepts every request
+    async fn connected_charger() -> OcppEvCharger {
+        let charger = OcppEvCharger::new(OcppEvChargerConfig::default());
+        charger
+            .attach_session(scripted_session("CP001", |_| {
+                serde_json::json!({ "status": "Accepted" })
+            }))
+            .await;
+        charger
+    }
 
     #[tokio::test]
     async fn test_ocpp_charger_creation() {
@@ -466,8 +646,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_ocpp_status_notification_mapping() {
-        let config = OcppEvChargerConfig::default();
-        let charger = OcppEvCharger::new(config);
+        let charger = connected_charger().await;
 
         // Test various status mappings
         charger
@@ -497,8 +676,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_ocpp_meter_values() {
-        let config = OcppEvChargerConfig::default();
-        let charger = OcppEvCharger::new(config);
+        let charger = connected_charger().await;
 
         // Simulate meter values update
         charger
@@ -515,15 +693,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_ocpp_set_current_validation() {
-        let config = OcppEvChargerConfig::default();
-        let charger = OcppEvCharger::new(config);
+        let charger = connected_charger().await;
 
-        // Connect first
-        charger.connect().await.unwrap();
-
-        // Valid current should succeed (in simulation mode)
+        // Valid current is accepted by the charge point
         let result = charger.set_current(16.0).await;
-        // Will succeed in simulation
         assert!(result.is_ok());
 
         // Invalid current (too high) should fail
@@ -537,11 +710,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_ocpp_start_stop_charging() {
-        let config = OcppEvChargerConfig::default();
-        let charger = OcppEvCharger::new(config);
-
-        // Connect first
-        charger.connect().await.unwrap();
+        let charger = connected_charger().await;
 
         // Can't start without vehicle connected
         let result = charger.start_charging().await;
@@ -553,13 +722,16 @@ mod tests {
             .await
             .unwrap();
 
-        // Now can start (in simulation mode)
+        // Now can start
         let result = charger.start_charging().await;
         assert!(result.is_ok());
 
         let state = charger.read_state().await.unwrap();
         assert!(state.charging);
 
+        // Charger confirms the remote start with StartTransaction
+        charger.handle_transaction_started(7, 1000).await.unwrap();
+
         // Stop charging
         let result = charger.stop_charging().await;
         assert!(result.is_ok());
@@ -586,4 +758,90 @@ mod tests {
         let result = charger.start_charging().await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_ocpp_sends_charging_profile_limit() {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&sent);
+        let charger = OcppEvCharger::new(OcppEvChargerConfig::default());
+        charger
+            .attach_session(scripted_session("CP001", move |call| {
+                log.lock().unwrap().push((call.action.clone(), call.payload.clone()));
+                serde_json::json!({ "status": "Accepted" })
+            }))
+            .await;
+
+        charger.set_current(4.0).await.unwrap();
+
+        let sent = sent.lock().unwrap();
+        let (action, payload) = &sent[0];
+        assert_eq!(action, "SetChargingProfile");
+        assert_eq!(payload["connectorId"], 1);
+        let period = &payload["csChargingProfiles"]["chargingSchedule"]["chargingSchedulePeriod"][0];
+        // Non-zero requests are clamped up to the minimum charging current
+        assert_eq!(period["limit"], 6.0);
+    }
+
+    #[tokio::test]
+    async fn test_ocpp_sends_charging_plan_as_default_profile() {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&sent);
+        let charger = OcppEvCharger::new(OcppEvChargerConfig::default());
+        charger
+            .attach_session(scripted_session("CP001", move |call| {
+                log.lock().unwrap().push(call.payload.clone());
+                serde_json::json!({ "status": "Accepted" })
+            }))
+            .await;
+
+        let start = Utc::now();
+        let hour = chrono::Duration::hours(1);
+        let plan: Vec<ScheduleInterval> = [11_040.0, 2_000.0, -3_000.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &power_w)| ScheduleInterval {
+                time_start: start + hour * i as i32,
+                time_end: start + hour * (i as i32 + 1),
+                target_power_w: power_w,
+            })
+            .collect();
+        charger.set_charging_plan(&plan).await.unwrap();
+
+        let sent = sent.lock().unwrap();
+        let profile = &sent[0]["csChargingProfiles"];
+        assert_eq!(profile["chargingProfilePurpose"], "TxDefaultProfile");
+        assert_eq!(profile["chargingSchedule"]["duration"], 3 * 3600);
+        let periods = &profile["chargingSchedule"]["chargingSchedulePeriod"];
+        assert_eq!(periods[0]["startPeriod"], 0);
+        assert_eq!(periods[0]["limit"], 16.0);
+        assert_eq!(periods[1]["startPeriod"], 3600);
+        // Below the minimum current and discharging both pause charging
+        assert_eq!(periods[1]["limit"], 0.0);
+        assert_eq!(periods[2]["limit"], 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_ocpp_rejected_profile_is_an_error() {
+        let charger = OcppEvCharger::new(OcppEvChargerConfig::default());
+        charger
+            .attach_session(scripted_session("CP001", |_| {
+                serde_json::json!({ "status": "Rejected" })
+            }))
+            .await;
+
+        assert!(charger.set_current(16.0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ocpp_energy_is_relative_to_transaction_start() {
+        let charger = connected_charger().await;
+        charger.handle_transaction_started(3, 12_000).await.unwrap();
+        charger
+            .handle_meter_values(Some(14.5), None, None, None)
+            .await
+            .unwrap();
+
+        let state = charger.read_state().await.unwrap();
+        assert_eq!(state.energy_delivered_kwh, 2.5);
+    }
 }
@@ -0,0 +1,112 @@
+//! Shelly Gen2+ switches over their local HTTP RPC API
+//!
+//! `Switch.Set` turns an output on or off and `Switch.GetStatus` reports it
+//! together with the metered power on models that have a meter.
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::domain::{ControllableLoad, LoadState};
+
+/// Reply of `Switch.GetStatus`; only the fields the controller uses
+#[derive(Debug, Deserialize)]
+struct SwitchStatus {
+    output: bool,
+    /// Active power (W), absent on models without metering
+    #[serde(default)]
+    apower: Option<f64>,
+}
+
+/// One output of a Shelly switch
+pub struct ShellySwitch {
+    client: Client,
+    base_url: String,
+    switch_id: u8,
+}
+
+impl ShellySwitch {
+    /// `address` is the switch's host name or IP address
+    pub fn new(address: &str, switch_id: u8) -> Self {
+        let base_url = if address.starts_with("http://") || address.starts_with("https://") {
+            address.trim_end_matches('/').to_string()
+        } else {
+            format!("http://{}", address.trim_end_matches('/'))
+        };
+        Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            base_url,
+            switch_id,
+        }
+    }
+
+    fn rpc_url(&self, method: &str) -> String {
+        format!("{}/rpc/{}", self.base_url, method)
+    }
+}
+
+#[async_trait]
+impl ControllableLoad for ShellySwitch {
+    async fn read_state(&self) -> Result<LoadState> {
+        let status: SwitchStatus = self
+            .client
+            .get(self.rpc_url("Switch.GetStatus"))
+            .query(&[("id", self.switch_id)])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Failed to reach Shelly switch at {}", self.base_url))?
+            .json()
+            .await
+            .context("Invalid Shelly Switch.GetStatus reply")?;
+        Ok(LoadState {
+            on: status.output,
+            power_w: status.apower,
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn switch(&self, on: bool) -> Result<()> {
+        self.client
+            .get(self.rpc_url("Switch.Set"))
+            .query(&[("id", self.switch_id.to_string()), ("on", on.to_string())])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Failed to switch Shelly at {}", self.base_url))?;
+        debug!(url = %self.base_url, switch_id = self.switch_id, on, "Shelly switched");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switch_status_parsing() {
+        let json = r#"{"id":0,"source":"http","output":true,"apower":1187.4,
+            "voltage":231.2,"current":5.14,"temperature":{"tC":41.2}}"#;
+        let status: SwitchStatus = serde_json::from_str(json).unwrap();
+        assert!(status.output);
+        assert_eq!(status.apower, Some(1187.4));
+
+        let status: SwitchStatus = serde_json::from_str(r#"{"id":0,"output":false}"#).unwrap();
+        assert_eq!(status.apower, None);
+    }
+
+    #[test]
+    fn test_rpc_url_from_address() {
+        let shelly = ShellySwitch::new("192.168.1.40", 1);
+        assert_eq!(
+            shelly.rpc_url("Switch.Set"),
+            "http://192.168.1.40/rpc/Switch.Set"
+        );
+    }
+}
@@ -1,21 +1,181 @@
 #[cfg(feature = "modbus")]
 pub mod client {
-    use anyhow::{Context as AnyhowContext, Result};
+    use crate::config::{ModbusConfig, ModbusTransport, SerialParity};
+    use anyhow::{bail, Context as AnyhowContext, Result};
+    use futures::future::BoxFuture;
+    use std::fmt;
     use std::sync::Arc;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
+    use tokio::net::TcpStream;
     use tokio::sync::Mutex;
     use tokio::time::timeout;
-    use tokio_modbus::client::tcp;
+    use tokio_modbus::client::{rtu, tcp};
     use tokio_modbus::prelude::*;
-    use tracing::{debug, warn, error};
+    use tracing::{debug, error, info, warn};
 
     const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
     const MAX_RETRIES: u32 = 3;
 
+    /// Above 19200 baud the Modbus serial line spec fixes the inter-frame
+    /// silence at 1.75 ms instead of scaling it with the character time
+    const FAST_LINE_FRAME_GAP: Duration = Duration::from_micros(1750);
+
+    /// Character framing and timing of an RTU line
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct SerialLine {
+        pub baud_rate: u32,
+        pub parity: SerialParity,
+        pub stop_bits: u8,
+        /// Overrides the 3.5 character silence derived from the baud rate
+        pub inter_frame_delay: Option<Duration>,
+    }
+
+    impl Default for SerialLine {
+        fn default() -> Self {
+            Self {
+                baud_rate: 9600,
+                parity: SerialParity::None,
+                stop_bits: 1,
+                inter_frame_delay: None,
+            }
+        }
+    }
+
+    impl SerialLine {
+        /// Time to send one character: start bit, 8 data bits, parity, stop bits
+        pub fn char_time(&self) -> Duration {
+            let parity_bits = if self.parity == SerialParity::None { 0 } else { 1 };
+            let bits = 1 + 8 + parity_bits + u32::from(self.stop_bits);
+            Duration::from_secs_f64(f64::from(bits) / f64::from(self.baud_rate.max(1)))
+        }
+
+        /// Minimum bus silence between two frames (t3.5)
+        pub fn frame_gap(&self) -> Duration {
+            if let Some(delay) = self.inter_frame_delay {
+                return delay;
+            }
+            if self.baud_rate > 19_200 {
+                FAST_LINE_FRAME_GAP
+            } else {
+                self.char_time().mul_f64(3.5)
+            }
+        }
+    }
+
+    /// Where and how Modbus frames are sent
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Transport {
+        /// Modbus TCP (MBAP header, no CRC)
+        Tcp { addr: String },
+        /// RTU framing on a local serial port
+        Rtu { path: String, line: SerialLine },
+        /// RTU frames tunnelled unchanged through a TCP serial gateway
+        RtuOverTcp { addr: String, line: SerialLine },
+    }
+
+    impl Transport {
+        /// Build the transport selected in `[hardware.modbus]`
+        ///
+        /// `addr` is the device's TCP address; it is ignored for `rtu`, which
+        /// uses `serial_port` instead.
+        pub fn from_config(config: &ModbusConfig, addr: &str) -> Result<Self> {
+            let line = SerialLine {
+                baud_rate: config.baud_rate,
+                parity: config.parity,
+                stop_bits: config.stop_bits,
+                inter_frame_delay: config.inter_frame_delay_ms.map(Duration::from_millis),
+            };
+            Ok(match config.transport {
+                ModbusTransport::Tcp => Transport::Tcp { addr: addr.to_string() },
+                ModbusTransport::RtuOverTcp => Transport::RtuOverTcp {
+                    addr: addr.to_string(),
+                    line,
+                },
+                ModbusTransport::Rtu => {
+                    let Some(path) = config.serial_port.clone().filter(|p| !p.is_empty()) else {
+                        bail!("Modbus transport \"rtu\" requires serial_port to be set");
+                    };
+                    Transport::Rtu { path, line }
+                }
+            })
+        }
+
+        fn serial_line(&self) -> Option<&SerialLine> {
+            match self {
+                Transport::Tcp { .. } => None,
+                Transport::Rtu { line, .. } | Transport::RtuOverTcp { line, .. } => Some(line),
+            }
+        }
+
+        /// Open a client context for `unit_id`
+        async fn open(
+            &self,
+            unit_id: u8,
+            timeout_duration: Duration,
+        ) -> Result<tokio_modbus::client::Context> {
+            match self {
+                Transport::Tcp { addr } => {
+                    let socket_addr = addr.parse().context("Invalid address format")?;
+                    timeout(timeout_duration, tcp::connect(socket_addr))
+                        .await
+                        .context("Connection timeout")?
+                        .context("Failed to connect")
+                }
+                Transport::RtuOverTcp { addr, .. } => {
+                    let stream = timeout(timeout_duration, TcpStream::connect(addr.as_str()))
+                        .await
+                        .context("Connection timeout")?
+                        .context("Failed to connect to serial gateway")?;
+                    // Gateways forward whatever arrives, so don't let Nagle
+                    // split a frame across segments
+                    stream.set_nodelay(true)?;
+                    Ok(rtu::attach_slave(stream, Slave(unit_id)))
+                }
+                Transport::Rtu { path, line } => {
+                    let parity = match line.parity {
+                        SerialParity::None => tokio_serial::Parity::None,
+                        SerialParity::Even => tokio_serial::Parity::Even,
+                        SerialParity::Odd => tokio_serial::Parity::Odd,
+                    };
+                    let stop_bits = if line.stop_bits >= 2 {
+                        tokio_serial::StopBits::Two
+                    } else {
+                        tokio_serial::StopBits::One
+                    };
+                    let builder = tokio_serial::new(path.as_str(), line.baud_rate)
+                        .data_bits(tokio_serial::DataBits::Eight)
+                        .parity(parity)
+                        .stop_bits(stop_bits)
+                        .timeout(timeout_duration);
+                    let port = tokio_serial::SerialStream::open(&builder)
+                        .with_context(|| format!("Failed to open serial port {}", path))?;
+                    Ok(rtu::attach_slave(port, Slave(unit_id)))
+                }
+            }
+        }
+    }
+
+    impl fmt::Display for Transport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Transport::Tcp { addr } => write!(f, "tcp://{}", addr),
+                Transport::Rtu { path, line } => write!(f, "rtu://{}@{}", path, line.baud_rate),
+                Transport::RtuOverTcp { addr, .. } => write!(f, "rtu+tcp://{}", addr),
+            }
+        }
+    }
+
+    /// Open context plus the bookkeeping needed for RTU frame spacing
+    struct Link {
+        ctx: tokio_modbus::client::Context,
+        /// When the bus last went quiet, i.e. the end of the last transaction
+        idle_since: Option<Instant>,
+    }
+
     pub struct ModbusClient {
-        context: Arc<Mutex<tokio_modbus::client::Context>>,
+        link: Arc<Mutex<Link>>,
         unit_id: u8,
-        addr: String,
+        transport: Transport,
         timeout_duration: Duration,
     }
 
@@ -31,28 +191,44 @@ pub mod client {
             unit_id: u8,
             timeout_duration: Duration,
         ) -> Result<Self> {
-            let socket_addr = addr.parse().context("Invalid address format")?;
+            Self::connect_transport(
+                Transport::Tcp { addr: addr.to_string() },
+                unit_id,
+                timeout_duration,
+            )
+            .await
+        }
 
-            debug!("Connecting to Modbus device at {} (unit {})", addr, unit_id);
+        /// Connect over any transport (TCP, serial RTU or RTU-over-TCP)
+        pub async fn connect_transport(
+            transport: Transport,
+            unit_id: u8,
+            timeout_duration: Duration,
+        ) -> Result<Self> {
+            debug!("Connecting to Modbus device at {} (unit {})", transport, unit_id);
 
-            let ctx = timeout(timeout_duration, tcp::connect(socket_addr))
-                .await
-                .context("Connection timeout")?
-                .context("Failed to connect")?;
+            let ctx = transport.open(unit_id, timeout_duration).await?;
 
             Ok(Self {
-                context: Arc::new(Mutex::new(ctx)),
+                link: Arc::new(Mutex::new(Link { ctx, idle_since: None })),
                 unit_id,
-                addr: addr.to_string(),
+                transport,
                 timeout_duration,
             })
         }
 
+        pub fn transport(&self) -> &Transport {
+            &self.transport
+        }
+
         /// Read holding registers with automatic retry
         pub async fn read_holding_registers(&self, start: u16, count: u16) -> Result<Vec<u16>> {
-            self.retry_operation(|ctx| async move {
-                ctx.set_slave(Slave(self.unit_id));
-                ctx.read_holding_registers(start, count).await
+            let unit_id = self.unit_id;
+            self.retry_operation(move |ctx| {
+                Box::pin(async move {
+                    ctx.set_slave(Slave(unit_id));
+                    ctx.read_holding_registers(start, count).await
+                })
             })
             .await
             .context(format!("Failed to read holding registers at {}", start))
@@ -60,9 +236,12 @@ pub mod client {
 
         /// Read input registers with automatic retry
         pub async fn read_input_registers(&self, start: u16, count: u16) -> Result<Vec<u16>> {
-            self.retry_operation(|ctx| async move {
-                ctx.set_slave(Slave(self.unit_id));
-                ctx.read_input_registers(start, count).await
+            let unit_id = self.unit_id;
+            self.retry_operation(move |ctx| {
+                Box::pin(async move {
+                    ctx.set_slave(Slave(unit_id));
+                    ctx.read_input_registers(start, count).await
+                })
             })
             .await
             .context(format!("Failed to read input registers at {}", start))
@@ -70,10 +249,13 @@ pub mod client {
 
         /// Write a single register
         pub async fn write_single_register(&self, addr: u16, value: u16) -> Result<()> {
-            self.retry_operation(|ctx| async move {
-                ctx.set_slave(Slave(self.unit_id));
-                ctx.write_single_register(addr, value).await?;
-                Ok(())
+            let unit_id = self.unit_id;
+            self.retry_operation(move |ctx| {
+                Box::pin(async move {
+                    ctx.set_slave(Slave(unit_id));
+                    ctx.write_single_register(addr, value).await?;
+                    Ok(())
+                })
             })
             .await
             .context(format!("Failed to write register at {}", addr))
@@ -82,21 +264,49 @@ pub mod client {
         /// Write multiple registers with automatic retry
         pub async fn write_multiple_registers(&self, start: u16, values: &[u16]) -> Result<()> {
             let values = values.to_vec(); // Clone for move into closure
-            self.retry_operation(|ctx| {
+            let unit_id = self.unit_id;
+            self.retry_operation(move |ctx| {
                 let values = values.clone();
-                async move {
-                    ctx.set_slave(Slave(self.unit_id));
+                Box::pin(async move {
+                    ctx.set_slave(Slave(unit_id));
                     ctx.write_multiple_registers(start, &values).await?;
                     Ok(())
-                }
+                })
             })
             .await
             .context(format!("Failed to write multiple registers at {}", start))
         }
 
+        /// Read coils, e.g. the outputs of a relay module
+        pub async fn read_coils(&self, start: u16, count: u16) -> Result<Vec<bool>> {
+            let unit_id = self.unit_id;
+            self.retry_operation(move |ctx| {
+                Box::pin(async move {
+                    ctx.set_slave(Slave(unit_id));
+                    ctx.read_coils(start, count).await
+                })
+            })
+            .await
+            .context(format!("Failed to read coils at {}", start))
+        }
+
+        /// Switch a single coil, e.g. a relay output
+        pub async fn write_single_coil(&self, addr: u16, on: bool) -> Result<()> {
+            let unit_id = self.unit_id;
+            self.retry_operation(move |ctx| {
+                Box::pin(async move {
+                    ctx.set_slave(Slave(unit_id));
+                    ctx.write_single_coil(addr, on).await?;
+                    Ok(())
+                })
+            })
+            .await
+            .context(format!("Failed to write coil at {}", addr))
+        }
+
         /// Health check - attempts to read a single register
         pub async fn health_check(&self) -> Result<()> {
-            debug!("Performing health check on {}", self.addr);
+            debug!("Performing health check on {}", self.transport);
             // Try to read register 0 (most devices support this)
             self.read_holding_registers(0, 1)
                 .await
@@ -118,15 +328,16 @@ pub mod client {
         ///
         /// See `reconnect_with_backoff()` for safe reconnection with retry logic.
         pub async fn reconnect(&self) -> Result<()> {
-            warn!("Reconnecting to Modbus device at {}", self.addr);
-            let socket_addr = self.addr.parse()?;
-            let new_ctx = timeout(self.timeout_duration, tcp::connect(socket_addr))
+            warn!("Reconnecting to Modbus device at {}", self.transport);
+            let new_ctx = self
+                .transport
+                .open(self.unit_id, self.timeout_duration)
                 .await
-                .context("Reconnection timeout")?
                 .context("Failed to reconnect")?;
 
-            let mut ctx = self.context.lock().await;
-            *ctx = new_ctx;
+            let mut link = self.link.lock().await;
+            link.ctx = new_ctx;
+            link.idle_since = Some(Instant::now());
             Ok(())
         }
 
@@ -157,7 +368,7 @@ pub mod client {
             for attempt in 1..=max_retries {
                 match self.reconnect().await {
                     Ok(()) => {
-                        info!("Successfully reconnected to {} on attempt {}", self.addr, attempt);
+                        info!("Successfully reconnected to {} on attempt {}", self.transport, attempt);
                         return Ok(());
                     }
                     Err(e) => {
@@ -179,18 +390,34 @@ pub mod client {
         }
 
         /// Execute an operation with retry logic
-        async fn retry_operation<F, Fut, T>(&self, operation: F) -> Result<T>
+        ///
+        /// The operation borrows the locked context, so it returns a boxed
+        /// future tied to that borrow rather than an unconstrained `Fut`.
+        ///
+        /// On RTU links the bus must stay silent for t3.5 between frames, or
+        /// the device merges two requests into one garbled frame; the lock
+        /// is held across the wait so concurrent callers queue behind it.
+        async fn retry_operation<F, T>(&self, operation: F) -> Result<T>
         where
-            F: Fn(&mut tokio_modbus::client::Context) -> Fut,
-            Fut: std::future::Future<Output = std::result::Result<T, std::io::Error>>,
+            F: for<'a> Fn(
+                &'a mut tokio_modbus::client::Context,
+            ) -> BoxFuture<'a, std::result::Result<T, std::io::Error>>,
         {
             // Ensure we always attempt at least once, even if MAX_RETRIES is 0
             let max_attempts = MAX_RETRIES.max(1);
+            let serial_line = self.transport.serial_line().copied();
 
             for attempt in 1..=max_attempts {
-                let mut ctx = self.context.lock().await;
+                let mut link = self.link.lock().await;
+
+                if let (Some(line), Some(idle_since)) = (serial_line, link.idle_since) {
+                    tokio::time::sleep_until((idle_since + line.frame_gap()).into()).await;
+                }
 
-                match timeout(self.timeout_duration, operation(&mut *ctx)).await {
+                let outcome = timeout(self.timeout_duration, operation(&mut link.ctx)).await;
+                link.idle_since = Some(Instant::now());
+
+                match outcome {
                     Ok(Ok(result)) => {
                         if attempt > 1 {
                             debug!("Operation succeeded on attempt {}", attempt);
@@ -211,8 +438,18 @@ pub mod client {
                     }
                 }
 
+                // RTU has no transaction id, so a late or corrupted reply left
+                // in the read buffer would be taken as the answer to the retry.
+                // Reopening the port/socket discards it.
+                if serial_line.is_some() {
+                    match self.transport.open(self.unit_id, self.timeout_duration).await {
+                        Ok(ctx) => link.ctx = ctx,
+                        Err(e) => warn!("Failed to reopen {} before retry: {}", self.transport, e),
+                    }
+                }
+
                 // Small delay between retries
-                drop(ctx); // Release lock before sleeping
+                drop(link); // Release lock before sleeping
                 tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
             }
 
@@ -224,42 +461,242 @@ pub mod client {
     impl Clone for ModbusClient {
         fn clone(&self) -> Self {
             Self {
-                context: Arc::clone(&self.context),
+                link: Arc::clone(&self.link),
                 unit_id: self.unit_id,
-                addr: self.addr.clone(),
+                transport: self.transport.clone(),
                 timeout_duration: self.timeout_duration,
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_frame_gap_follows_baud_rate() {
+            // 9600 8N1: 10 bits per char, t3.5 = 3.5 * 1.0417 ms
+            let line = SerialLine::default();
+            let gap = line.frame_gap().as_secs_f64();
+            assert!((gap - 3.5 * 10.0 / 9600.0).abs() < 1e-6);
+
+            // 9600 8E1 carries a parity bit
+            let even = SerialLine { parity: SerialParity::Even, ..line };
+            assert!(even.frame_gap() > line.frame_gap());
+
+            // Fixed 1.75 ms above 19200 baud
+            let fast = SerialLine { baud_rate: 115_200, ..line };
+            assert_eq!(fast.frame_gap(), Duration::from_micros(1750));
+
+            let manual = SerialLine {
+                inter_frame_delay: Some(Duration::from_millis(20)),
+                ..line
+            };
+            assert_eq!(manual.frame_gap(), Duration::from_millis(20));
+        }
+
+        #[test]
+        fn test_transport_from_config() {
+            let mut config: ModbusConfig = serde_json::from_value(serde_json::json!({
+                "default_port": 502,
+                "default_unit_id": 1,
+                "timeout_ms": 1000,
+                "max_retries": 3,
+                "transport": "rtu_over_tcp",
+                "parity": "even",
+                "baud_rate": 19200,
+            }))
+            .unwrap();
+
+            let transport = Transport::from_config(&config, "10.0.0.7:8899").unwrap();
+            match transport {
+                Transport::RtuOverTcp { addr, line } => {
+                    assert_eq!(addr, "10.0.0.7:8899");
+                    assert_eq!(line.baud_rate, 19200);
+                    assert_eq!(line.parity, SerialParity::Even);
+                    assert_eq!(line.stop_bits, 1);
+                }
+                other => panic!("unexpected transport {:?}", other),
+            }
+
+            config.transport = ModbusTransport::Rtu;
+            assert!(Transport::from_config(&config, "10.0.0.7:8899").is_err());
+
+            config.serial_port = Some("/dev/ttyUSB0".to_string());
+            assert!(matches!(
+                Transport::from_config(&config, "10.0.0.7:8899").unwrap(),
+                Transport::Rtu { ref path, .. } if path == "/dev/ttyUSB0"
+            ));
+        }
+    }
 }
 /// Register mapping for Modbus devices
 #[cfg(feature = "modbus")]
 pub mod register_map {
-    use anyhow::{Result, Context};
+    use crate::domain::battery::BatteryCapabilities;
+    use crate::domain::inverter::{InverterMode, InverterStatus};
+    use crate::modbus::parser;
+    use anyhow::{bail, Result};
+    use serde::{Deserialize, Serialize};
+
+    /// Wire type of a register value
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum DataType {
+        U16,
+        I16,
+        U32,
+        I32,
+        F32,
+    }
 
-    /// Register map trait for different device vendors
-    pub trait RegisterMap: Send + Sync {
-        fn soc_register(&self) -> u16;
-        fn soc_scale(&self) -> f64;
+    impl DataType {
+        /// Number of 16-bit registers the value occupies
+        pub fn word_count(self) -> u16 {
+            match self {
+                DataType::U16 | DataType::I16 => 1,
+                DataType::U32 | DataType::I32 | DataType::F32 => 2,
+            }
+        }
+
+        pub fn is_signed(self) -> bool {
+            matches!(self, DataType::I16 | DataType::I32 | DataType::F32)
+        }
+    }
+
+    /// Order of the 16-bit words in a multi-register value
+    ///
+    /// Bytes within each register are always big-endian per the Modbus spec,
+    /// but vendors disagree on which word comes first.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum WordOrder {
+        /// High word at the lower address (Huawei, SunSpec)
+        #[default]
+        BigEndian,
+        /// Low word at the lower address ("word swapped", e.g. SolarEdge floats)
+        LittleEndian,
+    }
+
+    /// Location and encoding of a single logical value
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct RegisterSpec {
+        pub address: u16,
+        pub data_type: DataType,
+        /// Engineering value = raw value * scale
+        pub scale: f64,
+        pub word_order: WordOrder,
+    }
+
+    impl RegisterSpec {
+        pub const fn new(address: u16, data_type: DataType, scale: f64) -> Self {
+            Self {
+                address,
+                data_type,
+                scale,
+                word_order: WordOrder::BigEndian,
+            }
+        }
 
-        fn power_register(&self) -> u16;
-        fn power_scale(&self) -> f64;
+        pub const fn with_word_order(mut self, word_order: WordOrder) -> Self {
+            self.word_order = word_order;
+            self
+        }
 
-        fn voltage_register(&self) -> u16;
-        fn voltage_scale(&self) -> f64;
+        pub fn word_count(&self) -> u16 {
+            self.data_type.word_count()
+        }
 
-        fn temperature_register(&self) -> u16;
-        fn temperature_scale(&self) -> f64;
+        /// Decode raw registers (as read from `address`) into a scaled value
+        pub fn decode(&self, registers: &[u16]) -> Result<f64> {
+            let words = self.word_count() as usize;
+            if registers.len() < words {
+                bail!(
+                    "Register {} needs {} words for {:?}, got {}",
+                    self.address,
+                    words,
+                    self.data_type,
+                    registers.len()
+                );
+            }
 
-        fn health_register(&self) -> u16;
-        fn health_scale(&self) -> f64;
+            let mut words = [registers[0], registers.get(1).copied().unwrap_or(0)];
+            if self.word_order == WordOrder::LittleEndian {
+                words.swap(0, 1);
+            }
 
-        fn power_command_register(&self) -> u16;
-        fn power_command_scale(&self) -> f64;
+            let raw = match self.data_type {
+                DataType::U16 => parser::parse_u16(&words) as f64,
+                DataType::I16 => parser::parse_i16(&words) as f64,
+                DataType::U32 => parser::parse_u32(&words) as f64,
+                DataType::I32 => parser::parse_i32(&words) as f64,
+                DataType::F32 => parser::parse_f32(&words) as f64,
+            };
+            Ok(raw * self.scale)
+        }
 
-        fn max_charge_power_register(&self) -> u16;
-        fn max_discharge_power_register(&self) -> u16;
-        fn capacity_register(&self) -> u16;
+        /// Encode a scaled value into the registers to write at `address`
+        ///
+        /// Values that do not fit the wire type are rejected instead of
+        /// wrapping around, since a wrapped power command reverses polarity.
+        pub fn encode(&self, value: f64) -> Result<Vec<u16>> {
+            let raw = value / self.scale;
+            if !raw.is_finite() {
+                bail!("Cannot encode non-finite value {} for register {}", value, self.address);
+            }
+
+            let (min, max) = match self.data_type {
+                DataType::U16 => (0.0, u16::MAX as f64),
+                DataType::I16 => (i16::MIN as f64, i16::MAX as f64),
+                DataType::U32 => (0.0, u32::MAX as f64),
+                DataType::I32 => (i32::MIN as f64, i32::MAX as f64),
+                DataType::F32 => (f32::MIN as f64, f32::MAX as f64),
+            };
+            let rounded = if self.data_type == DataType::F32 { raw } else { raw.round() };
+            if rounded < min || rounded > max {
+                bail!(
+                    "Value {} (raw {}) exceeds {:?} range [{}, {}] for register {}. \
+                     Check the scale factor ({}) in the register map.",
+                    value,
+                    rounded,
+                    self.data_type,
+                    min,
+                    max,
+                    self.address,
+                    self.scale
+                );
+            }
+
+            let bits: u32 = match self.data_type {
+                DataType::U16 => return Ok(vec![rounded as u16]),
+                DataType::I16 => return Ok(vec![rounded as i16 as u16]),
+                DataType::U32 => rounded as u32,
+                DataType::I32 => rounded as i32 as u32,
+                DataType::F32 => (rounded as f32).to_bits(),
+            };
+            let (high, low) = ((bits >> 16) as u16, bits as u16);
+            Ok(match self.word_order {
+                WordOrder::BigEndian => vec![high, low],
+                WordOrder::LittleEndian => vec![low, high],
+            })
+        }
+    }
+
+    /// Register map trait for different device vendors
+    ///
+    /// Powers are in watts (positive = charge) and capacity in Wh.
+    pub trait RegisterMap: Send + Sync {
+        fn soc(&self) -> RegisterSpec;
+        fn power(&self) -> RegisterSpec;
+        fn voltage(&self) -> RegisterSpec;
+        fn temperature(&self) -> RegisterSpec;
+        fn health(&self) -> RegisterSpec;
+        fn power_command(&self) -> RegisterSpec;
+
+        /// Registers reporting the device's own limits, if it exposes them
+        fn max_charge_power(&self) -> Option<RegisterSpec>;
+        fn max_discharge_power(&self) -> Option<RegisterSpec>;
+        fn capacity(&self) -> Option<RegisterSpec>;
 
         /// Control mode register (optional, returns None if not supported)
         /// Used to enable remote control mode on hybrid inverters
@@ -271,102 +708,351 @@ pub mod register_map {
         fn remote_control_value(&self) -> u16 {
             1 // Default: 1 = Remote Control
         }
+
+        /// Rated capabilities used when the device does not report them
+        fn rated_capabilities(&self) -> Option<BatteryCapabilities> {
+            None
+        }
     }
 
     /// Generic battery register map (common Modbus addresses)
+    ///
+    /// Vendor-specific layouts live in `config/device_profiles/*.toml`, see
+    /// `hardware::modbus::profile`.
     pub struct GenericBatteryRegisterMap;
 
     impl RegisterMap for GenericBatteryRegisterMap {
-        fn soc_register(&self) -> u16 { 37000 }
-        fn soc_scale(&self) -> f64 { 0.1 }
+        fn soc(&self) -> RegisterSpec { RegisterSpec::new(37000, DataType::U16, 0.1) }
+        fn power(&self) -> RegisterSpec { RegisterSpec::new(37001, DataType::I16, 1.0) }
+        fn voltage(&self) -> RegisterSpec { RegisterSpec::new(37002, DataType::U16, 0.1) }
+        fn temperature(&self) -> RegisterSpec { RegisterSpec::new(37003, DataType::I16, 0.1) }
+        fn health(&self) -> RegisterSpec { RegisterSpec::new(37004, DataType::U16, 0.1) }
+        fn power_command(&self) -> RegisterSpec { RegisterSpec::new(47000, DataType::I16, 1.0) }
+
+        fn max_charge_power(&self) -> Option<RegisterSpec> {
+            Some(RegisterSpec::new(37010, DataType::U16, 1.0))
+        }
+        fn max_discharge_power(&self) -> Option<RegisterSpec> {
+            Some(RegisterSpec::new(37011, DataType::U16, 1.0))
+        }
+        fn capacity(&self) -> Option<RegisterSpec> {
+            Some(RegisterSpec::new(37012, DataType::U16, 1.0))
+        }
+    }
 
-        fn power_register(&self) -> u16 { 37001 }
-        fn power_scale(&self) -> f64 { 1.0 }
+    /// Register layout of a PV or hybrid inverter
+    ///
+    /// Power values decode to watts, energy to kWh.
+    pub trait InverterRegisterMap: Send + Sync {
+        /// DC power drawn from the PV strings
+        fn pv_power(&self) -> RegisterSpec;
+        fn ac_power(&self) -> RegisterSpec;
+        fn dc_voltage(&self) -> RegisterSpec;
+        fn ac_voltage(&self) -> RegisterSpec;
+        fn grid_frequency(&self) -> RegisterSpec;
+        fn temperature(&self) -> RegisterSpec;
+        fn daily_energy(&self) -> RegisterSpec;
+        fn total_energy(&self) -> RegisterSpec;
+
+        fn status(&self) -> RegisterSpec;
+        fn decode_status(&self, raw: u16) -> InverterStatus;
+
+        fn rated_power(&self) -> RegisterSpec;
+        fn mppt_count(&self) -> Option<RegisterSpec> {
+            None
+        }
 
-        fn voltage_register(&self) -> u16 { 37002 }
-        fn voltage_scale(&self) -> f64 { 0.1 }
+        /// Register holding the working mode, if the device reports one
+        fn mode_register(&self) -> Option<u16> {
+            None
+        }
 
-        fn temperature_register(&self) -> u16 { 37003 }
-        fn temperature_scale(&self) -> f64 { 0.1 }
+        fn decode_mode(&self, _raw: u16) -> Option<InverterMode> {
+            None
+        }
+
+        /// Register write (address, value) that switches to `mode`,
+        /// or None if the device cannot be put in that mode
+        fn mode_command(&self, mode: InverterMode) -> Option<(u16, u16)>;
 
-        fn health_register(&self) -> u16 { 37004 }
-        fn health_scale(&self) -> f64 { 0.1 }
+        /// Register taking the grid feed-in limit in watts
+        fn export_limit(&self) -> Option<RegisterSpec>;
 
-        fn power_command_register(&self) -> u16 { 47000 }
-        fn power_command_scale(&self) -> f64 { 1.0 }
+        /// Write (address, value) that makes the device honour `export_limit`
+        fn export_limit_enable(&self) -> Option<(u16, u16)> {
+            None
+        }
+    }
 
-        fn max_charge_power_register(&self) -> u16 { 37010 }
-        fn max_discharge_power_register(&self) -> u16 { 37011 }
-        fn capacity_register(&self) -> u16 { 37012 }
+    /// Generic inverter register map (common Modbus addresses)
+    pub struct GenericInverterRegisterMap;
+
+    impl GenericInverterRegisterMap {
+        const MODE_REGISTER: u16 = 35100;
+        const MODES: [InverterMode; 5] = [
+            InverterMode::GridTied,
+            InverterMode::OffGrid,
+            InverterMode::Hybrid,
+            InverterMode::Backup,
+            InverterMode::Standby,
+        ];
     }
 
-    /// Huawei Luna2000 register map
-    pub struct HuaweiLuna2000RegisterMap;
+    impl InverterRegisterMap for GenericInverterRegisterMap {
+        fn pv_power(&self) -> RegisterSpec { RegisterSpec::new(35010, DataType::U32, 1.0) }
+        fn dc_voltage(&self) -> RegisterSpec { RegisterSpec::new(35012, DataType::U16, 0.1) }
+        fn ac_power(&self) -> RegisterSpec { RegisterSpec::new(35014, DataType::I32, 1.0) }
+        fn ac_voltage(&self) -> RegisterSpec { RegisterSpec::new(35016, DataType::U16, 0.1) }
+        fn grid_frequency(&self) -> RegisterSpec { RegisterSpec::new(35017, DataType::U16, 0.01) }
+        fn temperature(&self) -> RegisterSpec { RegisterSpec::new(35018, DataType::I16, 0.1) }
+        fn daily_energy(&self) -> RegisterSpec { RegisterSpec::new(35020, DataType::U32, 0.01) }
+        fn total_energy(&self) -> RegisterSpec { RegisterSpec::new(35022, DataType::U32, 0.01) }
+        fn status(&self) -> RegisterSpec { RegisterSpec::new(35024, DataType::U16, 1.0) }
+        fn rated_power(&self) -> RegisterSpec { RegisterSpec::new(35000, DataType::U32, 1.0) }
+
+        fn mppt_count(&self) -> Option<RegisterSpec> {
+            Some(RegisterSpec::new(35002, DataType::U16, 1.0))
+        }
+
+        fn decode_status(&self, raw: u16) -> InverterStatus {
+            match raw {
+                0 => InverterStatus::Normal,
+                1 => InverterStatus::Standby,
+                2 => InverterStatus::Fault,
+                3 => InverterStatus::Shutdown,
+                5 => InverterStatus::InitialStandby,
+                _ => InverterStatus::Warning,
+            }
+        }
+
+        fn mode_register(&self) -> Option<u16> {
+            Some(Self::MODE_REGISTER)
+        }
 
-    impl RegisterMap for HuaweiLuna2000RegisterMap {
-        fn soc_register(&self) -> u16 { 37760 }
-        fn soc_scale(&self) -> f64 { 0.1 }
+        fn decode_mode(&self, raw: u16) -> Option<InverterMode> {
+            Self::MODES.get(raw as usize).copied()
+        }
 
-        fn power_register(&self) -> u16 { 37765 }
-        fn power_scale(&self) -> f64 { 1.0 }
+        fn mode_command(&self, mode: InverterMode) -> Option<(u16, u16)> {
+            let value = Self::MODES.iter().position(|&m| m == mode)?;
+            Some((Self::MODE_REGISTER, value as u16))
+        }
 
-        fn voltage_register(&self) -> u16 { 37762 }
-        fn voltage_scale(&self) -> f64 { 0.1 }
+        fn export_limit(&self) -> Option<RegisterSpec> {
+            Some(RegisterSpec::new(35102, DataType::U32, 1.0))
+        }
 
-        fn temperature_register(&self) -> u16 { 37761 }
-        fn temperature_scale(&self) -> f64 { 0.1 }
+        fn export_limit_enable(&self) -> Option<(u16, u16)> {
+            Some((35104, 1))
+        }
+    }
 
-        fn health_register(&self) -> u16 { 37763 }
-        fn health_scale(&self) -> f64 { 0.1 }
+    /// Huawei SUN2000 string/hybrid inverters (Modbus interface definitions V3)
+    pub struct HuaweiSun2000RegisterMap;
+
+    impl InverterRegisterMap for HuaweiSun2000RegisterMap {
+        // Power registers are kW with gain 1000, i.e. raw watts
+        fn pv_power(&self) -> RegisterSpec { RegisterSpec::new(32064, DataType::I32, 1.0) }
+        fn ac_power(&self) -> RegisterSpec { RegisterSpec::new(32080, DataType::I32, 1.0) }
+        /// PV1 string voltage
+        fn dc_voltage(&self) -> RegisterSpec { RegisterSpec::new(32016, DataType::I16, 0.1) }
+        /// Phase A voltage
+        fn ac_voltage(&self) -> RegisterSpec { RegisterSpec::new(32069, DataType::U16, 0.1) }
+        fn grid_frequency(&self) -> RegisterSpec { RegisterSpec::new(32085, DataType::U16, 0.01) }
+        fn temperature(&self) -> RegisterSpec { RegisterSpec::new(32087, DataType::I16, 0.1) }
+        fn daily_energy(&self) -> RegisterSpec { RegisterSpec::new(32114, DataType::U32, 0.01) }
+        fn total_energy(&self) -> RegisterSpec { RegisterSpec::new(32106, DataType::U32, 0.01) }
+        fn status(&self) -> RegisterSpec { RegisterSpec::new(32089, DataType::U16, 1.0) }
+        fn rated_power(&self) -> RegisterSpec { RegisterSpec::new(30073, DataType::U32, 1.0) }
+
+        fn mppt_count(&self) -> Option<RegisterSpec> {
+            Some(RegisterSpec::new(30072, DataType::U16, 1.0))
+        }
 
-        fn power_command_register(&self) -> u16 { 47100 }
-        fn power_command_scale(&self) -> f64 { 1.0 }
+        fn decode_status(&self, raw: u16) -> InverterStatus {
+            match raw {
+                0x0000..=0x00FF => InverterStatus::InitialStandby,
+                0x0100..=0x01FF | 0xA000..=0xA002 => InverterStatus::Standby,
+                0x0200 => InverterStatus::Normal,
+                // Power limited / self derating
+                0x0201 | 0x0202 => InverterStatus::Warning,
+                0x0300 => InverterStatus::Fault,
+                0x0301..=0x03FF => InverterStatus::Shutdown,
+                _ => InverterStatus::Warning,
+            }
+        }
 
-        fn max_charge_power_register(&self) -> u16 { 37764 }
-        fn max_discharge_power_register(&self) -> u16 { 37764 }
-        fn capacity_register(&self) -> u16 { 37758 }
+        /// Start-up (40200) and shutdown (40201) commands; SUN2000 has no
+        /// off-grid or backup switch over Modbus
+        fn mode_command(&self, mode: InverterMode) -> Option<(u16, u16)> {
+            match mode {
+                InverterMode::GridTied | InverterMode::Hybrid => Some((40200, 0)),
+                InverterMode::Standby => Some((40201, 0)),
+                InverterMode::OffGrid | InverterMode::Backup => None,
+            }
+        }
 
-        fn control_mode_register(&self) -> Option<u16> {
-            Some(47000) // Huawei control mode register
+        /// Maximum grid feed-in power (kW, gain 1000)
+        fn export_limit(&self) -> Option<RegisterSpec> {
+            Some(RegisterSpec::new(47416, DataType::I32, 1.0))
         }
 
-        fn remote_control_value(&self) -> u16 {
-            2 // Huawei: 2 = Remote EMS Control
+        /// Active power control mode 5: "grid connection with limited power (kW)"
+        fn export_limit_enable(&self) -> Option<(u16, u16)> {
+            Some((47415, 5))
+        }
+    }
+
+    /// Register layout of a grid (utility) meter
+    ///
+    /// Power decodes to watts with positive = import, energy to kWh.
+    pub trait MeterRegisterMap: Send + Sync {
+        /// L1, L2, L3 phase-to-neutral voltage
+        fn phase_voltage(&self) -> [RegisterSpec; 3];
+        fn phase_current(&self) -> [RegisterSpec; 3];
+        fn phase_power(&self) -> [RegisterSpec; 3];
+        fn total_power(&self) -> RegisterSpec;
+        fn frequency(&self) -> RegisterSpec;
+        fn import_energy(&self) -> RegisterSpec;
+        fn export_energy(&self) -> RegisterSpec;
+
+        /// Measurements are input registers (FC 04) on most meters
+        fn uses_input_registers(&self) -> bool {
+            true
+        }
+    }
+
+    /// Register layout of a heat pump
+    ///
+    /// Temperatures decode to °C and power to watts. The controller steers
+    /// the unit by shifting its heating curve and requesting extra hot water.
+    pub trait HeatPumpRegisterMap: Send + Sync {
+        fn outdoor_temp(&self) -> RegisterSpec;
+        /// Room sensor, if one is fitted and exposed
+        fn indoor_temp(&self) -> Option<RegisterSpec>;
+        /// Top of the hot-water tank
+        fn dhw_temp(&self) -> Option<RegisterSpec>;
+        /// Electrical input of the compressor
+        fn power(&self) -> Option<RegisterSpec>;
+        /// Parallel shift of the heating curve (K, holding register)
+        fn heating_offset(&self) -> RegisterSpec;
+        /// Holding register and its (off, on) values for a one-time hot
+        /// water increase, `None` if the unit has no such command
+        fn dhw_boost(&self) -> Option<(RegisterSpec, f64, f64)>;
+
+        /// Measurements are input registers (FC 04)
+        fn uses_input_registers(&self) -> bool {
+            true
+        }
+    }
+
+    /// NIBE F-series through the MODBUS 40 accessory, and S-series
+    ///
+    /// Register numbers as listed by NIBE ModbusManager, climate system 1.
+    pub struct NibeRegisterMap;
+
+    impl HeatPumpRegisterMap for NibeRegisterMap {
+        fn outdoor_temp(&self) -> RegisterSpec { RegisterSpec::new(40004, DataType::I16, 0.1) }
+        fn indoor_temp(&self) -> Option<RegisterSpec> { Some(RegisterSpec::new(40033, DataType::I16, 0.1)) }
+        fn dhw_temp(&self) -> Option<RegisterSpec> { Some(RegisterSpec::new(40013, DataType::I16, 0.1)) }
+        fn power(&self) -> Option<RegisterSpec> { Some(RegisterSpec::new(43141, DataType::U16, 1.0)) }
+        fn heating_offset(&self) -> RegisterSpec { RegisterSpec::new(47011, DataType::I16, 1.0) }
+        /// "Temporary lux": 0 = off, 4 = one-time increase
+        fn dhw_boost(&self) -> Option<(RegisterSpec, f64, f64)> {
+            Some((RegisterSpec::new(48132, DataType::U16, 1.0), 0.0, 4.0))
+        }
+    }
+
+    /// Thermia Genesis (Atlas, Calibra, iTec): hundredths of a degree
+    ///
+    /// Genesis has no hot water boost register, so the tank is only
+    /// pre-heated through SG-Ready on these units.
+    pub struct ThermiaRegisterMap;
+
+    impl HeatPumpRegisterMap for ThermiaRegisterMap {
+        fn outdoor_temp(&self) -> RegisterSpec { RegisterSpec::new(13, DataType::I16, 0.01) }
+        fn indoor_temp(&self) -> Option<RegisterSpec> { Some(RegisterSpec::new(121, DataType::I16, 0.01)) }
+        fn dhw_temp(&self) -> Option<RegisterSpec> { Some(RegisterSpec::new(15, DataType::I16, 0.01)) }
+        fn power(&self) -> Option<RegisterSpec> { None }
+        /// "Comfort wheel setting"
+        fn heating_offset(&self) -> RegisterSpec { RegisterSpec::new(5, DataType::I16, 0.01) }
+        fn dhw_boost(&self) -> Option<(RegisterSpec, f64, f64)> { None }
+    }
+
+    /// Eastron SDM630: IEEE 754 floats, input registers
+    pub struct Sdm630RegisterMap;
+
+    impl Sdm630RegisterMap {
+        const fn float(address: u16) -> RegisterSpec {
+            RegisterSpec::new(address, DataType::F32, 1.0)
         }
     }
 
-    /// SolarEdge StorEdge register map
-    pub struct SolarEdgeRegisterMap;
+    impl MeterRegisterMap for Sdm630RegisterMap {
+        fn phase_voltage(&self) -> [RegisterSpec; 3] { [Self::float(0x00), Self::float(0x02), Self::float(0x04)] }
+        fn phase_current(&self) -> [RegisterSpec; 3] { [Self::float(0x06), Self::float(0x08), Self::float(0x0A)] }
+        fn phase_power(&self) -> [RegisterSpec; 3] { [Self::float(0x0C), Self::float(0x0E), Self::float(0x10)] }
+        fn total_power(&self) -> RegisterSpec { Self::float(0x34) }
+        fn frequency(&self) -> RegisterSpec { Self::float(0x46) }
+        fn import_energy(&self) -> RegisterSpec { Self::float(0x48) }
+        fn export_energy(&self) -> RegisterSpec { Self::float(0x4A) }
+    }
 
-    impl RegisterMap for SolarEdgeRegisterMap {
-        fn soc_register(&self) -> u16 { 62852 }
-        fn soc_scale(&self) -> f64 { 1.0 }
+    /// Carlo Gavazzi EM24: INT32 with the low word first
+    pub struct Em24RegisterMap;
 
-        fn power_register(&self) -> u16 { 62853 }
-        fn power_scale(&self) -> f64 { 1.0 }
+    impl Em24RegisterMap {
+        const fn int32(address: u16, scale: f64) -> RegisterSpec {
+            RegisterSpec::new(address, DataType::I32, scale).with_word_order(WordOrder::LittleEndian)
+        }
+    }
 
-        fn voltage_register(&self) -> u16 { 62854 }
-        fn voltage_scale(&self) -> f64 { 0.01 }
+    impl MeterRegisterMap for Em24RegisterMap {
+        fn phase_voltage(&self) -> [RegisterSpec; 3] {
+            [Self::int32(0x00, 0.1), Self::int32(0x02, 0.1), Self::int32(0x04, 0.1)]
+        }
+        fn phase_current(&self) -> [RegisterSpec; 3] {
+            [Self::int32(0x0C, 0.001), Self::int32(0x0E, 0.001), Self::int32(0x10, 0.001)]
+        }
+        fn phase_power(&self) -> [RegisterSpec; 3] {
+            [Self::int32(0x12, 0.1), Self::int32(0x14, 0.1), Self::int32(0x16, 0.1)]
+        }
+        fn total_power(&self) -> RegisterSpec { Self::int32(0x28, 0.1) }
+        fn frequency(&self) -> RegisterSpec { RegisterSpec::new(0x33, DataType::I16, 0.1) }
+        fn import_energy(&self) -> RegisterSpec { Self::int32(0x34, 0.1) }
+        fn export_energy(&self) -> RegisterSpec { Self::int32(0x4E, 0.1) }
+    }
 
-        fn temperature_register(&self) -> u16 { 62855 }
-        fn temperature_scale(&self) -> f64 { 0.1 }
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-        fn health_register(&self) -> u16 { 62856 }
-        fn health_scale(&self) -> f64 { 1.0 }
+        #[test]
+        fn test_decode_signed_and_word_order() {
+            let power = RegisterSpec::new(100, DataType::I16, 1.0);
+            assert_eq!(power.decode(&[0xFC18]).unwrap(), -1000.0);
 
-        fn power_command_register(&self) -> u16 { 57348 }
-        fn power_command_scale(&self) -> f64 { 1.0 }
+            let energy = RegisterSpec::new(100, DataType::U32, 0.01);
+            assert_eq!(energy.decode(&[0x0001, 0x0000]).unwrap(), 655.36);
+            let swapped = energy.with_word_order(WordOrder::LittleEndian);
+            assert_eq!(swapped.decode(&[0x0000, 0x0001]).unwrap(), 655.36);
 
-        fn max_charge_power_register(&self) -> u16 { 62857 }
-        fn max_discharge_power_register(&self) -> u16 { 62858 }
-        fn capacity_register(&self) -> u16 { 62859 }
+            let float = RegisterSpec::new(100, DataType::F32, 1.0).with_word_order(WordOrder::LittleEndian);
+            assert_eq!(float.decode(&[0x0000, 0x4248]).unwrap(), 50.0);
 
-        fn control_mode_register(&self) -> Option<u16> {
-            Some(0xE004) // SolarEdge control mode register
+            assert!(energy.decode(&[1]).is_err());
         }
 
-        fn remote_control_value(&self) -> u16 {
-            3 // SolarEdge: 3 = Remote Control via Modbus
+        #[test]
+        fn test_encode_round_trips_and_rejects_overflow() {
+            let command = RegisterSpec::new(100, DataType::I32, 0.1).with_word_order(WordOrder::LittleEndian);
+            let words = command.encode(-5000.0).unwrap();
+            assert_eq!(words.len(), 2);
+            assert_eq!(command.decode(&words).unwrap(), -5000.0);
+
+            // 5000 W at 0.1 W/LSB does not fit an i16 and must not wrap to discharge
+            assert!(RegisterSpec::new(100, DataType::I16, 0.1).encode(5000.0).is_err());
+            assert!(RegisterSpec::new(100, DataType::U16, 1.0).encode(-1.0).is_err());
         }
     }
 }
@@ -453,6 +1139,10 @@ pub mod parser {
     }
 }
 
+/// SunSpec model discovery and readers on top of the client
+#[cfg(feature = "modbus")]
+pub mod sunspec;
+
 #[cfg(not(feature = "modbus"))]
 pub mod client {}
 
@@ -461,3 +1151,6 @@ pub mod register_map {}
 
 #[cfg(not(feature = "modbus"))]
 pub mod parser {}
+
+#[cfg(not(feature = "modbus"))]
+pub mod sunspec {}
@@ -0,0 +1,875 @@
+//! SunSpec model discovery and typed readers
+//!
+//! A SunSpec device exposes a chain of self-describing models right after a
+//! "SunS" marker at one of a few well-known base addresses. Every model is
+//! `[id, length, data...]` and the chain ends with id 0xFFFF. Values are
+//! plain integers with a separate scale-factor register (value * 10^sf), and
+//! each type reserves a sentinel for "not implemented".
+#![allow(dead_code)]
+
+use anyhow::{anyhow, bail, Context, Result};
+use tracing::debug;
+
+use super::client::ModbusClient;
+
+/// "SunS" as two registers
+pub const SUNS_MARKER: [u16; 2] = [0x5375, 0x6E53];
+
+/// Base addresses where the marker may live, in probe order
+pub const BASE_ADDRESSES: [u16; 3] = [40000, 0, 50000];
+
+const END_MODEL_ID: u16 = 0xFFFF;
+const MAX_MODELS: usize = 64;
+/// Modbus limit for a single read request
+const MAX_READ_REGISTERS: u16 = 125;
+
+/// SunSpec model IDs this crate understands
+pub mod model_id {
+    pub const COMMON: u16 = 1;
+    pub const INVERTER_SINGLE_PHASE: u16 = 101;
+    pub const INVERTER_SPLIT_PHASE: u16 = 102;
+    pub const INVERTER_THREE_PHASE: u16 = 103;
+    pub const NAMEPLATE: u16 = 120;
+    pub const BASIC_SETTINGS: u16 = 121;
+    pub const MEASUREMENTS_STATUS: u16 = 122;
+    pub const IMMEDIATE_CONTROLS: u16 = 123;
+    pub const STORAGE: u16 = 124;
+    pub const PRICING: u16 = 125;
+    pub const VOLT_VAR: u16 = 126;
+    pub const MPPT: u16 = 160;
+    pub const METER_SINGLE_PHASE: u16 = 201;
+    pub const METER_SPLIT_PHASE: u16 = 202;
+    pub const METER_WYE: u16 = 203;
+    pub const METER_DELTA: u16 = 204;
+    pub const BATTERY_BASE: u16 = 802;
+
+    pub const INVERTERS: [u16; 3] = [INVERTER_SINGLE_PHASE, INVERTER_SPLIT_PHASE, INVERTER_THREE_PHASE];
+    pub const METERS: [u16; 4] = [METER_SINGLE_PHASE, METER_SPLIT_PHASE, METER_WYE, METER_DELTA];
+}
+
+/// Location of one model in the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelHeader {
+    pub id: u16,
+    /// First data register, i.e. just after the `[id, length]` header
+    pub address: u16,
+    pub length: u16,
+}
+
+/// The model chain of one device
+#[derive(Debug, Clone)]
+pub struct SunSpecMap {
+    pub base_address: u16,
+    pub models: Vec<ModelHeader>,
+}
+
+impl SunSpecMap {
+    /// Probe the well-known base addresses for the marker and walk the chain
+    pub async fn discover(client: &ModbusClient) -> Result<Self> {
+        for base in BASE_ADDRESSES {
+            match client.read_holding_registers(base, 2).await {
+                Ok(regs) if regs[..] == SUNS_MARKER => return Self::walk(client, base).await,
+                Ok(regs) => debug!("No SunSpec marker at {} (read {:04X?})", base, regs),
+                Err(e) => debug!("No SunSpec marker at {}: {}", base, e),
+            }
+        }
+        bail!("No SunSpec marker found at any of {:?}", BASE_ADDRESSES)
+    }
+
+    async fn walk(client: &ModbusClient, base: u16) -> Result<Self> {
+        let mut models = Vec::new();
+        let mut next = base as u32 + SUNS_MARKER.len() as u32;
+
+        loop {
+            if models.len() >= MAX_MODELS {
+                bail!("SunSpec chain at {} has more than {} models", base, MAX_MODELS);
+            }
+            let header_address = u16::try_from(next)
+                .map_err(|_| anyhow!("SunSpec chain at {} runs past register 65535", base))?;
+            let header = client
+                .read_holding_registers(header_address, 2)
+                .await
+                .with_context(|| format!("Failed to read SunSpec model header at {}", header_address))?;
+            let (id, length) = (header[0], header[1]);
+
+            // Some firmwares leave the end marker out and return zeros instead
+            if id == END_MODEL_ID || id == 0 {
+                break;
+            }
+
+            let address = header_address
+                .checked_add(2)
+                .ok_or_else(|| anyhow!("SunSpec model {} header at end of register space", id))?;
+            debug!("SunSpec model {} at {} ({} registers)", id, address, length);
+            models.push(ModelHeader { id, address, length });
+            next = address as u32 + length as u32;
+        }
+
+        Self::from_models(base, models)
+    }
+
+    /// Build a map from an already known chain
+    pub fn from_models(base_address: u16, models: Vec<ModelHeader>) -> Result<Self> {
+        if models.first().map(|m| m.id) != Some(model_id::COMMON) {
+            bail!("SunSpec chain at {} does not start with the common model", base_address);
+        }
+        Ok(Self { base_address, models })
+    }
+
+    pub fn find(&self, id: u16) -> Option<ModelHeader> {
+        self.models.iter().copied().find(|m| m.id == id)
+    }
+
+    /// First model matching any of `ids`
+    pub fn find_any(&self, ids: &[u16]) -> Option<ModelHeader> {
+        self.models.iter().copied().find(|m| ids.contains(&m.id))
+    }
+
+    pub fn has(&self, id: u16) -> bool {
+        self.find(id).is_some()
+    }
+
+    pub fn is_inverter(&self) -> bool {
+        self.find_any(&model_id::INVERTERS).is_some()
+    }
+
+    pub fn is_meter(&self) -> bool {
+        self.find_any(&model_id::METERS).is_some()
+    }
+
+    pub fn is_storage(&self) -> bool {
+        self.has(model_id::BATTERY_BASE) || self.has(model_id::STORAGE)
+    }
+}
+
+/// Raw registers of one model with SunSpec-aware accessors
+///
+/// Offsets are relative to the first data register. Accessors return `None`
+/// for the type's "not implemented" sentinel.
+#[derive(Debug, Clone)]
+pub struct ModelBlock {
+    pub header: ModelHeader,
+    regs: Vec<u16>,
+}
+
+impl ModelBlock {
+    pub fn new(header: ModelHeader, regs: Vec<u16>) -> Result<Self> {
+        if regs.len() < header.length as usize {
+            bail!(
+                "SunSpec model {} needs {} registers, got {}",
+                header.id,
+                header.length,
+                regs.len()
+            );
+        }
+        Ok(Self { header, regs })
+    }
+
+    /// Read a whole model, split into requests the protocol allows
+    pub async fn read(client: &ModbusClient, header: ModelHeader) -> Result<Self> {
+        let mut regs = Vec::with_capacity(header.length as usize);
+        while regs.len() < header.length as usize {
+            let offset = regs.len() as u16;
+            let count = (header.length - offset).min(MAX_READ_REGISTERS);
+            let chunk = client
+                .read_holding_registers(header.address + offset, count)
+                .await
+                .with_context(|| format!("Failed to read SunSpec model {}", header.id))?;
+            if chunk.is_empty() {
+                bail!("Device returned no registers for SunSpec model {}", header.id);
+            }
+            regs.extend(chunk);
+        }
+        Self::new(header, regs)
+    }
+
+    fn expect(&self, ids: &[u16]) -> Result<()> {
+        if !ids.contains(&self.header.id) {
+            bail!("Expected SunSpec model {:?}, got {}", ids, self.header.id);
+        }
+        Ok(())
+    }
+
+    fn reg(&self, offset: u16) -> Option<u16> {
+        if offset < self.header.length {
+            self.regs.get(offset as usize).copied()
+        } else {
+            None
+        }
+    }
+
+    pub fn uint16(&self, offset: u16) -> Option<u16> {
+        self.reg(offset).filter(|v| *v != 0xFFFF)
+    }
+
+    pub fn int16(&self, offset: u16) -> Option<i16> {
+        self.reg(offset).map(|v| v as i16).filter(|v| *v != i16::MIN)
+    }
+
+    pub fn uint32(&self, offset: u16) -> Option<u32> {
+        let value = ((self.reg(offset)? as u32) << 16) | self.reg(offset + 1)? as u32;
+        (value != u32::MAX).then_some(value)
+    }
+
+    /// Accumulators have no sentinel; 0 means "not accumulated yet"
+    pub fn acc32(&self, offset: u16) -> Option<u32> {
+        Some(((self.reg(offset)? as u32) << 16) | self.reg(offset + 1)? as u32)
+    }
+
+    /// Scale factor exponent; the spec limits it to -10..=10
+    pub fn sunssf(&self, offset: u16) -> Option<i32> {
+        self.int16(offset).map(i32::from).filter(|sf| (-10..=10).contains(sf))
+    }
+
+    pub fn scaled_u16(&self, offset: u16, sf_offset: u16) -> Option<f64> {
+        Some(self.uint16(offset)? as f64 * 10f64.powi(self.sunssf(sf_offset)?))
+    }
+
+    pub fn scaled_i16(&self, offset: u16, sf_offset: u16) -> Option<f64> {
+        Some(self.int16(offset)? as f64 * 10f64.powi(self.sunssf(sf_offset)?))
+    }
+
+    pub fn scaled_acc32(&self, offset: u16, sf_offset: u16) -> Option<f64> {
+        Some(self.acc32(offset)? as f64 * 10f64.powi(self.sunssf(sf_offset)?))
+    }
+
+    /// NUL-padded ASCII string spanning `len` registers
+    pub fn string(&self, offset: u16, len: u16) -> String {
+        let bytes: Vec<u8> = (offset..offset + len)
+            .filter_map(|o| self.reg(o))
+            .flat_map(u16::to_be_bytes)
+            .take_while(|b| *b != 0)
+            .collect();
+        String::from_utf8_lossy(&bytes).trim().to_string()
+    }
+
+    /// Encode an engineering value for an int16 register scaled by `sf_offset`
+    pub fn encode_i16(&self, value: f64, sf_offset: u16) -> Result<u16> {
+        let sf = self
+            .sunssf(sf_offset)
+            .ok_or_else(|| anyhow!("SunSpec model {} has no scale factor at {}", self.header.id, sf_offset))?;
+        let raw = (value / 10f64.powi(sf)).round();
+        if !raw.is_finite() || raw <= i16::MIN as f64 || raw > i16::MAX as f64 {
+            bail!("Value {} does not fit SunSpec int16 with scale factor {}", value, sf);
+        }
+        Ok(raw as i16 as u16)
+    }
+}
+
+/// Model 1: device identity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommonModel {
+    pub manufacturer: String,
+    pub model: String,
+    pub options: String,
+    pub version: String,
+    pub serial_number: String,
+}
+
+impl CommonModel {
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&[model_id::COMMON])?;
+        Ok(Self {
+            manufacturer: block.string(0, 16),
+            model: block.string(16, 16),
+            options: block.string(32, 8),
+            version: block.string(40, 8),
+            serial_number: block.string(48, 16),
+        })
+    }
+}
+
+/// Inverter operating state (`St` in models 101-103)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatingState {
+    Off,
+    Sleeping,
+    Starting,
+    Mppt,
+    Throttled,
+    ShuttingDown,
+    Fault,
+    Standby,
+    Unknown(u16),
+}
+
+impl From<u16> for OperatingState {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::Off,
+            2 => Self::Sleeping,
+            3 => Self::Starting,
+            4 => Self::Mppt,
+            5 => Self::Throttled,
+            6 => Self::ShuttingDown,
+            7 => Self::Fault,
+            8 => Self::Standby,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// Models 101-103: inverter AC and DC measurements
+#[derive(Debug, Clone)]
+pub struct InverterModel {
+    pub phases: u8,
+    pub ac_current_a: Option<f64>,
+    pub phase_current_a: [Option<f64>; 3],
+    pub phase_voltage_v: [Option<f64>; 3],
+    pub ac_power_w: Option<f64>,
+    pub frequency_hz: Option<f64>,
+    pub ac_energy_wh: Option<f64>,
+    pub dc_current_a: Option<f64>,
+    pub dc_voltage_v: Option<f64>,
+    pub dc_power_w: Option<f64>,
+    pub cabinet_temperature_c: Option<f64>,
+    pub state: Option<OperatingState>,
+}
+
+impl InverterModel {
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&model_id::INVERTERS)?;
+        Ok(Self {
+            phases: (block.header.id - 100) as u8,
+            ac_current_a: block.scaled_u16(0, 4),
+            phase_current_a: [block.scaled_u16(1, 4), block.scaled_u16(2, 4), block.scaled_u16(3, 4)],
+            phase_voltage_v: [block.scaled_u16(8, 11), block.scaled_u16(9, 11), block.scaled_u16(10, 11)],
+            ac_power_w: block.scaled_i16(12, 13),
+            frequency_hz: block.scaled_u16(14, 15),
+            ac_energy_wh: block.scaled_acc32(22, 24),
+            dc_current_a: block.scaled_u16(25, 26),
+            dc_voltage_v: block.scaled_u16(27, 28),
+            dc_power_w: block.scaled_i16(29, 30),
+            cabinet_temperature_c: block.scaled_i16(31, 35),
+            state: block.uint16(36).map(OperatingState::from),
+        })
+    }
+}
+
+/// Model 120: ratings
+#[derive(Debug, Clone)]
+pub struct NameplateModel {
+    pub max_power_w: Option<f64>,
+    pub max_apparent_power_va: Option<f64>,
+    pub max_current_a: Option<f64>,
+    pub energy_rating_wh: Option<f64>,
+    pub max_charge_rate_w: Option<f64>,
+    pub max_discharge_rate_w: Option<f64>,
+}
+
+impl NameplateModel {
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&[model_id::NAMEPLATE])?;
+        Ok(Self {
+            max_power_w: block.scaled_u16(1, 2),
+            max_apparent_power_va: block.scaled_u16(3, 4),
+            max_current_a: block.scaled_u16(10, 11),
+            energy_rating_wh: block.scaled_u16(17, 18),
+            max_charge_rate_w: block.scaled_u16(21, 22),
+            max_discharge_rate_w: block.scaled_u16(23, 24),
+        })
+    }
+}
+
+/// Model 121: configured limits
+#[derive(Debug, Clone)]
+pub struct SettingsModel {
+    pub max_power_w: Option<f64>,
+    pub max_apparent_power_va: Option<f64>,
+}
+
+impl SettingsModel {
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&[model_id::BASIC_SETTINGS])?;
+        Ok(Self {
+            max_power_w: block.scaled_u16(0, 20),
+            max_apparent_power_va: block.scaled_u16(5, 24),
+        })
+    }
+}
+
+/// Model 123: immediate controls
+#[derive(Debug, Clone)]
+pub struct ImmediateControlsModel {
+    pub connected: Option<bool>,
+    /// Output limit as percent of `WMax`
+    pub power_limit_pct: Option<f64>,
+    pub power_limit_enabled: Option<bool>,
+}
+
+impl ImmediateControlsModel {
+    pub const CONN: u16 = 2;
+    pub const WMAX_LIM_PCT: u16 = 3;
+    pub const WMAX_LIM_ENA: u16 = 7;
+    pub const WMAX_LIM_PCT_SF: u16 = 21;
+
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&[model_id::IMMEDIATE_CONTROLS])?;
+        Ok(Self {
+            connected: block.uint16(Self::CONN).map(|v| v == 1),
+            power_limit_pct: block.scaled_u16(Self::WMAX_LIM_PCT, Self::WMAX_LIM_PCT_SF),
+            power_limit_enabled: block.uint16(Self::WMAX_LIM_ENA).map(|v| v == 1),
+        })
+    }
+}
+
+/// Model 124: storage control
+#[derive(Debug, Clone)]
+pub struct StorageModel {
+    pub max_charge_w: Option<f64>,
+    pub min_reserve_pct: Option<f64>,
+    pub state_of_charge_pct: Option<f64>,
+    pub battery_voltage_v: Option<f64>,
+    pub charge_status: Option<u16>,
+    pub charge_rate_pct: Option<f64>,
+    pub discharge_rate_pct: Option<f64>,
+}
+
+impl StorageModel {
+    pub const STOR_CTL_MOD: u16 = 3;
+    pub const OUT_W_RTE: u16 = 10;
+    pub const IN_W_RTE: u16 = 11;
+    pub const IN_OUT_W_RTE_SF: u16 = 23;
+
+    /// `StorCtl_Mod` bits
+    pub const CHARGE: u16 = 0b01;
+    pub const DISCHARGE: u16 = 0b10;
+
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&[model_id::STORAGE])?;
+        Ok(Self {
+            max_charge_w: block.scaled_u16(0, 16),
+            min_reserve_pct: block.scaled_u16(5, 19),
+            state_of_charge_pct: block.scaled_u16(6, 20),
+            battery_voltage_v: block.scaled_u16(8, 22),
+            charge_status: block.uint16(9),
+            discharge_rate_pct: block.scaled_i16(Self::OUT_W_RTE, Self::IN_OUT_W_RTE_SF),
+            charge_rate_pct: block.scaled_i16(Self::IN_W_RTE, Self::IN_OUT_W_RTE_SF),
+        })
+    }
+}
+
+/// Model 160: per-tracker DC measurements
+#[derive(Debug, Clone)]
+pub struct MpptModel {
+    pub modules: Vec<MpptModule>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MpptModule {
+    pub id: Option<u16>,
+    pub label: String,
+    pub dc_current_a: Option<f64>,
+    pub dc_voltage_v: Option<f64>,
+    pub dc_power_w: Option<f64>,
+    pub energy_wh: Option<f64>,
+}
+
+impl MpptModel {
+    const FIXED_LEN: u16 = 8;
+    const MODULE_LEN: u16 = 20;
+
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&[model_id::MPPT])?;
+        let declared = block.uint16(6).unwrap_or(0);
+        let available = block.header.length.saturating_sub(Self::FIXED_LEN) / Self::MODULE_LEN;
+        let count = declared.min(available);
+
+        // Module values share the scale factors in the fixed block
+        let scaled = |raw: Option<f64>, sf_offset: u16| Some(raw? * 10f64.powi(block.sunssf(sf_offset)?));
+        let modules = (0..count)
+            .map(|i| {
+                let base = Self::FIXED_LEN + i * Self::MODULE_LEN;
+                MpptModule {
+                    id: block.uint16(base),
+                    label: block.string(base + 1, 8),
+                    dc_current_a: scaled(block.uint16(base + 9).map(f64::from), 0),
+                    dc_voltage_v: scaled(block.uint16(base + 10).map(f64::from), 1),
+                    dc_power_w: scaled(block.uint16(base + 11).map(f64::from), 2),
+                    energy_wh: scaled(block.acc32(base + 12).map(f64::from), 3),
+                }
+            })
+            .collect();
+        Ok(Self { modules })
+    }
+
+    pub fn total_power_w(&self) -> Option<f64> {
+        self.modules.iter().map(|m| m.dc_power_w).sum()
+    }
+}
+
+/// Models 201-204: grid meter
+///
+/// Power follows the SunSpec meter convention: positive is imported from
+/// the grid, negative is exported.
+#[derive(Debug, Clone)]
+pub struct MeterModel {
+    pub phases: u8,
+    pub current_a: Option<f64>,
+    pub phase_current_a: [Option<f64>; 3],
+    pub phase_voltage_v: [Option<f64>; 3],
+    pub frequency_hz: Option<f64>,
+    pub power_w: Option<f64>,
+    pub phase_power_w: [Option<f64>; 3],
+    pub exported_wh: Option<f64>,
+    pub imported_wh: Option<f64>,
+}
+
+impl MeterModel {
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&model_id::METERS)?;
+        let phases = match block.header.id {
+            model_id::METER_SINGLE_PHASE => 1,
+            model_id::METER_SPLIT_PHASE => 2,
+            _ => 3,
+        };
+        Ok(Self {
+            phases,
+            current_a: block.scaled_i16(0, 4),
+            phase_current_a: [block.scaled_i16(1, 4), block.scaled_i16(2, 4), block.scaled_i16(3, 4)],
+            phase_voltage_v: [block.scaled_i16(6, 13), block.scaled_i16(7, 13), block.scaled_i16(8, 13)],
+            frequency_hz: block.scaled_i16(14, 15),
+            power_w: block.scaled_i16(16, 20),
+            phase_power_w: [block.scaled_i16(17, 20), block.scaled_i16(18, 20), block.scaled_i16(19, 20)],
+            exported_wh: block.scaled_acc32(36, 52),
+            imported_wh: block.scaled_acc32(44, 52),
+        })
+    }
+}
+
+/// Model 802: battery bank
+#[derive(Debug, Clone)]
+pub struct BatteryBaseModel {
+    pub capacity_wh: Option<f64>,
+    pub max_charge_w: Option<f64>,
+    pub max_discharge_w: Option<f64>,
+    pub soc_max_pct: Option<f64>,
+    pub soc_min_pct: Option<f64>,
+    pub soc_pct: Option<f64>,
+    pub soh_pct: Option<f64>,
+    pub cycle_count: Option<u32>,
+    pub charge_status: Option<u16>,
+    pub battery_type: Option<u16>,
+    pub voltage_v: Option<f64>,
+    pub current_a: Option<f64>,
+    /// Positive = charging (SunSpec reports discharge as positive; flipped here)
+    pub power_w: Option<f64>,
+}
+
+impl BatteryBaseModel {
+    /// `Typ` value for lead-acid banks
+    pub const TYPE_LEAD_ACID: u16 = 2;
+
+    pub fn parse(block: &ModelBlock) -> Result<Self> {
+        block.expect(&[model_id::BATTERY_BASE])?;
+        Ok(Self {
+            capacity_wh: block.scaled_u16(1, 51),
+            max_charge_w: block.scaled_u16(2, 52),
+            max_discharge_w: block.scaled_u16(3, 52),
+            soc_max_pct: block.scaled_u16(5, 54),
+            soc_min_pct: block.scaled_u16(6, 54),
+            soc_pct: block.scaled_u16(9, 54),
+            soh_pct: block.scaled_u16(11, 56),
+            cycle_count: block.uint32(12),
+            charge_status: block.uint16(14),
+            battery_type: block.uint16(19),
+            voltage_v: block.scaled_u16(32, 57),
+            current_a: block.scaled_i16(42, 59),
+            power_w: block.scaled_i16(45, 61).map(|w| -w),
+        })
+    }
+}
+
+/// Typed access to a discovered SunSpec device
+#[derive(Clone)]
+pub struct SunSpecReader {
+    client: ModbusClient,
+    map: SunSpecMap,
+}
+
+impl SunSpecReader {
+    /// Connect and walk the model chain
+    pub async fn connect(addr: &str, unit_id: u8) -> Result<Self> {
+        let client = ModbusClient::connect(addr, unit_id)
+            .await
+            .context("Failed to connect to SunSpec device")?;
+        Self::new(client).await
+    }
+
+    pub async fn new(client: ModbusClient) -> Result<Self> {
+        let map = SunSpecMap::discover(&client).await?;
+        Ok(Self { client, map })
+    }
+
+    pub fn map(&self) -> &SunSpecMap {
+        &self.map
+    }
+
+    pub fn client(&self) -> &ModbusClient {
+        &self.client
+    }
+
+    /// Re-read the marker; cheaper than a model and valid on every SunSpec device
+    pub async fn health_check(&self) -> Result<()> {
+        let regs = self
+            .client
+            .read_holding_registers(self.map.base_address, 2)
+            .await
+            .context("SunSpec health check failed")?;
+        if regs[..] != SUNS_MARKER {
+            bail!("SunSpec marker at {} disappeared", self.map.base_address);
+        }
+        Ok(())
+    }
+
+    pub async fn read_model(&self, ids: &[u16]) -> Result<ModelBlock> {
+        let header = self
+            .map
+            .find_any(ids)
+            .ok_or_else(|| anyhow!("Device does not implement SunSpec model {:?}", ids))?;
+        ModelBlock::read(&self.client, header).await
+    }
+
+    pub async fn common(&self) -> Result<CommonModel> {
+        CommonModel::parse(&self.read_model(&[model_id::COMMON]).await?)
+    }
+
+    pub async fn inverter(&self) -> Result<InverterModel> {
+        InverterModel::parse(&self.read_model(&model_id::INVERTERS).await?)
+    }
+
+    pub async fn nameplate(&self) -> Result<NameplateModel> {
+        NameplateModel::parse(&self.read_model(&[model_id::NAMEPLATE]).await?)
+    }
+
+    pub async fn settings(&self) -> Result<SettingsModel> {
+        SettingsModel::parse(&self.read_model(&[model_id::BASIC_SETTINGS]).await?)
+    }
+
+    pub async fn immediate_controls(&self) -> Result<ImmediateControlsModel> {
+        ImmediateControlsModel::parse(&self.read_model(&[model_id::IMMEDIATE_CONTROLS]).await?)
+    }
+
+    pub async fn storage(&self) -> Result<StorageModel> {
+        StorageModel::parse(&self.read_model(&[model_id::STORAGE]).await?)
+    }
+
+    pub async fn mppt(&self) -> Result<MpptModel> {
+        MpptModel::parse(&self.read_model(&[model_id::MPPT]).await?)
+    }
+
+    pub async fn meter(&self) -> Result<MeterModel> {
+        MeterModel::parse(&self.read_model(&model_id::METERS).await?)
+    }
+
+    pub async fn battery(&self) -> Result<BatteryBaseModel> {
+        BatteryBaseModel::parse(&self.read_model(&[model_id::BATTERY_BASE]).await?)
+    }
+
+    /// Connect or disconnect the inverter from the grid (model 123 `Conn`)
+    pub async fn set_connected(&self, connected: bool) -> Result<()> {
+        let header = self.require(model_id::IMMEDIATE_CONTROLS)?;
+        self.client
+            .write_single_register(header.address + ImmediateControlsModel::CONN, connected as u16)
+            .await
+    }
+
+    /// Limit AC output to a percentage of `WMax`, or lift the limit with `None`
+    pub async fn set_power_limit_pct(&self, pct: Option<f64>) -> Result<()> {
+        let block = self.read_model(&[model_id::IMMEDIATE_CONTROLS]).await?;
+        let address = block.header.address;
+        match pct {
+            Some(pct) => {
+                let raw = block.encode_i16(pct.clamp(0.0, 100.0), ImmediateControlsModel::WMAX_LIM_PCT_SF)?;
+                self.client
+                    .write_single_register(address + ImmediateControlsModel::WMAX_LIM_PCT, raw)
+                    .await?;
+                self.client
+                    .write_single_register(address + ImmediateControlsModel::WMAX_LIM_ENA, 1)
+                    .await
+            }
+            None => {
+                self.client
+                    .write_single_register(address + ImmediateControlsModel::WMAX_LIM_ENA, 0)
+                    .await
+            }
+        }
+    }
+
+    /// Command storage power via model 124 (positive = charge)
+    ///
+    /// The rate registers are percentages of `WChaMax`; both directions are
+    /// enabled with a zero rate to hold the battery idle.
+    pub async fn set_storage_power(&self, watts: f64) -> Result<()> {
+        let block = self.read_model(&[model_id::STORAGE]).await?;
+        let storage = StorageModel::parse(&block)?;
+        let max_w = storage
+            .max_charge_w
+            .filter(|w| *w > 0.0)
+            .ok_or_else(|| anyhow!("SunSpec storage model does not report WChaMax"))?;
+
+        let pct = (watts.abs() / max_w * 100.0).min(100.0);
+        let (mode, charge_pct, discharge_pct) = if watts > 0.0 {
+            (StorageModel::CHARGE, pct, 0.0)
+        } else if watts < 0.0 {
+            (StorageModel::DISCHARGE, 0.0, pct)
+        } else {
+            (StorageModel::CHARGE | StorageModel::DISCHARGE, 0.0, 0.0)
+        };
+
+        let address = block.header.address;
+        let out_rate = block.encode_i16(discharge_pct, StorageModel::IN_OUT_W_RTE_SF)?;
+        let in_rate = block.encode_i16(charge_pct, StorageModel::IN_OUT_W_RTE_SF)?;
+        // OutWRte and InWRte are adjacent, write the rates before the mode
+        self.client
+            .write_multiple_registers(address + StorageModel::OUT_W_RTE, &[out_rate, in_rate])
+            .await?;
+        self.client
+            .write_single_register(address + StorageModel::STOR_CTL_MOD, mode)
+            .await
+    }
+
+    fn require(&self, id: u16) -> Result<ModelHeader> {
+        self.map
+            .find(id)
+            .ok_or_else(|| anyhow!("Device does not implement SunSpec model {}", id))
+    }
+}
+
+/// Helpers for building register images in tests
+#[cfg(test)]
+pub(crate) mod image {
+    use super::*;
+
+    /// Registers for a model with `values` placed at the given offsets
+    pub fn model(length: u16, values: &[(u16, u16)]) -> Vec<u16> {
+        let mut regs = vec![0u16; length as usize];
+        for &(offset, value) in values {
+            regs[offset as usize] = value;
+        }
+        regs
+    }
+
+    pub fn block(id: u16, regs: Vec<u16>) -> ModelBlock {
+        let header = ModelHeader {
+            id,
+            address: 40004,
+            length: regs.len() as u16,
+        };
+        ModelBlock::new(header, regs).unwrap()
+    }
+
+    pub fn sf(exp: i16) -> u16 {
+        exp as u16
+    }
+
+    pub fn ascii(s: &str, len: u16) -> Vec<(u16, u16)> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(len as usize * 2, 0);
+        bytes
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| (i as u16, u16::from_be_bytes([pair[0], pair[1]])))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::image::*;
+    use super::*;
+
+    #[test]
+    fn test_common_model_strings() {
+        let mut values = ascii("SolarEdge", 16);
+        values.extend(ascii("SE10K", 16).into_iter().map(|(o, v)| (o + 16, v)));
+        values.extend(ascii("7E0412AB", 16).into_iter().map(|(o, v)| (o + 48, v)));
+        let common = CommonModel::parse(&block(1, model(66, &values))).unwrap();
+
+        assert_eq!(common.manufacturer, "SolarEdge");
+        assert_eq!(common.model, "SE10K");
+        assert_eq!(common.serial_number, "7E0412AB");
+    }
+
+    #[test]
+    fn test_inverter_scale_factors_and_sentinels() {
+        let regs = model(
+            50,
+            &[
+                (8, 2305),       // PhVphA
+                (11, sf(-1)),    // V_SF
+                (12, 4250),      // W
+                (13, sf(0)),     // W_SF
+                (14, 5002),      // Hz
+                (15, sf(-2)),    // Hz_SF
+                (22, 0x0001),    // WH high word
+                (23, 0x86A0),    // WH low word (100000)
+                (24, sf(1)),     // WH_SF
+                (29, 0x8000),    // DCW not implemented
+                (30, sf(0)),
+                (36, 4),         // MPPT
+            ],
+        );
+        let inverter = InverterModel::parse(&block(103, regs)).unwrap();
+
+        assert_eq!(inverter.phases, 3);
+        assert_eq!(inverter.phase_voltage_v[0], Some(230.5));
+        assert_eq!(inverter.ac_power_w, Some(4250.0));
+        assert_eq!(inverter.frequency_hz, Some(50.02));
+        assert_eq!(inverter.ac_energy_wh, Some(1_000_000.0));
+        assert_eq!(inverter.dc_power_w, None);
+        assert_eq!(inverter.state, Some(OperatingState::Mppt));
+    }
+
+    #[test]
+    fn test_meter_per_phase_export() {
+        let regs = model(
+            105,
+            &[
+                (16, (-1500i16) as u16), // W: exporting
+                (17, (-600i16) as u16),
+                (18, (-500i16) as u16),
+                (19, (-400i16) as u16),
+                (20, sf(0)),
+                (44, 0),
+                (45, 12345), // TotWhImp
+                (52, sf(0)),
+            ],
+        );
+        let meter = MeterModel::parse(&block(203, regs)).unwrap();
+
+        assert_eq!(meter.phases, 3);
+        assert_eq!(meter.power_w, Some(-1500.0));
+        assert_eq!(meter.phase_power_w, [Some(-600.0), Some(-500.0), Some(-400.0)]);
+        assert_eq!(meter.imported_wh, Some(12345.0));
+        // Current scale factor left at its sentinel
+        assert_eq!(
+            MeterModel::parse(&block(203, model(105, &[(0, 10), (4, 0x8000)]))).unwrap().current_a,
+            None
+        );
+    }
+
+    #[test]
+    fn test_battery_power_sign_is_flipped() {
+        let regs = model(62, &[(9, 875), (54, sf(-1)), (45, 2000), (61, sf(0))]);
+        let battery = BatteryBaseModel::parse(&block(802, regs)).unwrap();
+
+        assert_eq!(battery.soc_pct, Some(87.5));
+        // SunSpec: positive = discharging; domain: positive = charging
+        assert_eq!(battery.power_w, Some(-2000.0));
+    }
+
+    #[test]
+    fn test_wrong_model_and_short_block_rejected() {
+        assert!(MeterModel::parse(&block(103, model(50, &[]))).is_err());
+        let header = ModelHeader { id: 1, address: 0, length: 66 };
+        assert!(ModelBlock::new(header, vec![0; 10]).is_err());
+    }
+
+    #[test]
+    fn test_encode_uses_scale_factor() {
+        let controls = block(123, model(24, &[(21, sf(-1))]));
+        assert_eq!(controls.encode_i16(55.5, 21).unwrap(), 555);
+        assert!(controls.encode_i16(5000.0, 21).is_err());
+    }
+}
@@ -0,0 +1,1062 @@
+#![allow(dead_code)]
+//! OCPP Central System
+//!
+//! Hosts the OCPP 1.6-J central-system (CSMS) side so that wallboxes can dial
+//! into the controller instead of an external backend. Chargers connect to
+//! `{path}/{charge_point_id}` on the API server and the central system:
+//! - Answers BootNotification, Heartbeat, StatusNotification, MeterValues,
+//!   StartTransaction and StopTransaction
+//! - Routes status and metering into the registered [`OcppEvCharger`]
+//! - Lets the charger send SetChargingProfile and RemoteStart/Stop back over
+//!   the same socket via its [`ChargePointSession`]
+//!
+//! Charge points authenticate with HTTP Basic auth on the upgrade request
+//! (OCPP 1.6 security profile 1): the username is the charge point ID and the
+//! password the one configured for it. Unknown charge points are refused.
+
+use super::messages::*;
+use super::{Call, CallError, CallResult, ErrorCode, OcppError, OcppMessage};
+use crate::hardware::ocpp::OcppEvCharger;
+use anyhow::{Context, Result};
+use axum::{
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use base64::Engine;
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex, Notify, RwLock};
+use tracing::{debug, info, warn};
+
+/// Capacity of the per-connection outbound frame queue
+const OUTBOUND_QUEUE_SIZE: usize = 32;
+
+type PendingResponse = std::result::Result<serde_json::Value, CallError>;
+
+/// Central system settings
+#[derive(Debug, Clone)]
+pub struct CentralSystemConfig {
+    /// Heartbeat interval handed to chargers in the BootNotification response
+    pub heartbeat_interval_secs: u64,
+    /// How long to wait for a charger to answer a CALL
+    pub request_timeout: Duration,
+    /// HTTP Basic auth password per charge point ID
+    pub charge_point_passwords: HashMap<String, String>,
+}
+
+impl Default for CentralSystemConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval_secs: 300,
+            request_timeout: Duration::from_secs(30),
+            charge_point_passwords: HashMap::new(),
+        }
+    }
+}
+
+impl From<&crate::config::OcppConfig> for CentralSystemConfig {
+    fn from(config: &crate::config::OcppConfig) -> Self {
+        Self {
+            heartbeat_interval_secs: config.heartbeat_interval_secs,
+            charge_point_passwords: config.charge_point_passwords.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+/// One connected charge point as seen from the central system
+///
+/// Outgoing CALLs are correlated with the charger's CALLRESULT/CALLERROR by
+/// unique message ID. Frames are handed to the socket task through a queue so
+/// the session can be used from anywhere in the controller.
+pub struct ChargePointSession {
+    charge_point_id: String,
+    outbound: mpsc::Sender<String>,
+    pending: Mutex<HashMap<String, oneshot::Sender<PendingResponse>>>,
+    /// OCPP-J allows a single outstanding CALL per direction
+    call_lock: Mutex<()>,
+    request_timeout: Duration,
+    shutdown: Notify,
+}
+
+impl ChargePointSession {
+    /// Create a session and the receiver its socket task drains
+    pub fn new(
+        charge_point_id: impl Into<String>,
+        request_timeout: Duration,
+    ) -> (Arc<Self>, mpsc::Receiver<String>) {
+        let (outbound, outbound_rx) = mpsc::channel(OUTBOUND_QUEUE_SIZE);
+        let session = Arc::new(Self {
+            charge_point_id: charge_point_id.into(),
+            outbound,
+            pending: Mutex::new(HashMap::new()),
+            call_lock: Mutex::new(()),
+            request_timeout,
+            shutdown: Notify::new(),
+        });
+        (session, outbound_rx)
+    }
+
+    pub fn charge_point_id(&self) -> &str {
+        &self.charge_point_id
+    }
+
+    /// Send a CALL to the charger and wait for the matching CALLRESULT payload
+    pub async fn call(
+        &self,
+        action: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, OcppError> {
+        let _in_flight = self.call_lock.lock().await;
+
+        let call = Call::new(action, payload);
+        let message_id = call.message_id.clone();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(message_id.clone(), tx);
+
+        if self
+            .outbound
+            .send(OcppMessage::Call(call).to_json())
+            .await
+            .is_err()
+        {
+            self.pending.lock().await.remove(&message_id);
+            return Err(OcppError::NotConnected);
+        }
+
+        match tokio::time::timeout(self.request_timeout, rx).await {
+            Ok(Ok(Ok(payload))) => Ok(payload),
+            Ok(Ok(Err(error))) => Err(OcppError::CallError {
+                action: action.to_string(),
+                code: error.error_code,
+                description: error.error_description,
+            }),
+            Ok(Err(_)) => Err(OcppError::ConnectionClosed(action.to_string())),
+            Err(_) => {
+                self.pending.lock().await.remove(&message_id);
+                Err(OcppError::Timeout {
+                    action: action.to_string(),
+                    timeout_ms: self.request_timeout.as_millis() as u64,
+                })
+            }
+        }
+    }
+
+    /// Send a typed CALL and decode the typed response
+    pub async fn call_typed<Req, Resp>(&self, action: &str, request: &Req) -> Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let payload = serde_json::to_value(request)
+            .with_context(|| format!("Failed to encode {} request", action))?;
+        let response = self.call(action, payload).await?;
+        serde_json::from_value(response)
+            .with_context(|| format!("Failed to decode {} response", action))
+    }
+
+    /// Complete the pending CALL a CALLRESULT/CALLERROR answers
+    ///
+    /// Returns false if no CALL with that message ID is waiting.
+    pub async fn resolve(&self, frame: OcppMessage) -> bool {
+        let (message_id, response) = match frame {
+            OcppMessage::CallResult(result) => (result.message_id, Ok(result.payload)),
+            OcppMessage::CallError(error) => (error.message_id.clone(), Err(error)),
+            OcppMessage::Call(_) => return false,
+        };
+
+        match self.pending.lock().await.remove(&message_id) {
+            Some(tx) => {
+                let _ = tx.send(response);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ask the socket task serving this session to hang up
+    pub fn close(&self) {
+        self.shutdown.notify_one();
+    }
+}
+
+/// OCPP 1.6-J central system
+#[derive(Clone)]
+pub struct CentralSystem {
+    inner: Arc<CentralSystemInner>,
+}
+
+struct CentralSystemInner {
+    config: CentralSystemConfig,
+    sessions: RwLock<HashMap<String, Arc<ChargePointSession>>>,
+    chargers: RwLock<HashMap<String, Arc<OcppEvCharger>>>,
+    next_transaction_id: AtomicI32,
+}
+
+impl CentralSystem {
+    pub fn new(config: CentralSystemConfig) -> Self {
+        Self {
+            inner: Arc::new(CentralSystemInner {
+                config,
+                sessions: RwLock::new(HashMap::new()),
+                chargers: RwLock::new(HashMap::new()),
+                next_transaction_id: AtomicI32::new(1),
+            }),
+        }
+    }
+
+    /// Register a charger so notifications from its charge point reach it
+    pub async fn register_charger(&self, charger: Arc<OcppEvCharger>) {
+        let id = charger.charge_point_id().to_string();
+        if let Some(session) = self.inner.sessions.read().await.get(&id) {
+            charger.attach_session(Arc::clone(session)).await;
+        }
+        self.inner.chargers.write().await.insert(id, charger);
+    }
+
+    /// Session of a connected charge point
+    pub async fn session(&self, charge_point_id: &str) -> Option<Arc<ChargePointSession>> {
+        self.inner
+            .sessions
+            .read()
+            .await
+            .get(charge_point_id)
+            .cloned()
+    }
+
+    /// IDs of all currently connected charge points
+    pub async fn connected_charge_points(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.inner.sessions.read().await.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Axum routes serving the WebSocket endpoint at `{path}/:charge_point_id`
+    ///
+    /// Chargers authenticate with HTTP Basic auth instead of the API bearer
+    /// token, so this router is mounted outside the bearer-token protected API.
+    pub fn router(&self, path: &str) -> Router {
+        let route = format!("{}/:charge_point_id", path.trim_end_matches('/'));
+        Router::new()
+            .route(&route, get(ws_handler))
+            .with_state(self.clone())
+    }
+
+    /// Check the credentials a charge point presents on its upgrade request
+    ///
+    /// Only registered charge points with a configured password are let in,
+    /// and the Basic auth username must match the ID in the URL.
+    pub async fn authorize(
+        &self,
+        charge_point_id: &str,
+        headers: &HeaderMap,
+    ) -> std::result::Result<(), StatusCode> {
+        if !self
+            .inner
+            .chargers
+            .read()
+            .await
+            .contains_key(charge_point_id)
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let Some(expected) = self
+            .inner
+            .config
+            .charge_point_passwords
+            .get(charge_point_id)
+        else {
+            warn!(
+                "No password configured for charge point {}",
+                charge_point_id
+            );
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+
+        match basic_credentials(headers) {
+            Some((username, password))
+                if username == charge_point_id
+                    && crate::auth::constant_time_compare(
+                        password.as_bytes(),
+                        expected.as_bytes(),
+                    ) =>
+            {
+                Ok(())
+            }
+            _ => Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+
+    /// Start a session for a newly connected charge point
+    ///
+    /// A reconnecting charger replaces its previous session, which is closed.
+    /// Callers must have passed [`CentralSystem::authorize`] first.
+    pub async fn open_session(
+        &self,
+        charge_point_id: &str,
+    ) -> (Arc<ChargePointSession>, mpsc::Receiver<String>) {
+        let (session, outbound_rx) =
+            ChargePointSession::new(charge_point_id, self.inner.config.request_timeout);
+
+        if let Some(previous) = self
+            .inner
+            .sessions
+            .write()
+            .await
+            .insert(charge_point_id.to_string(), Arc::clone(&session))
+        {
+            info!(
+                "Charge point {} reconnected, closing previous session",
+                charge_point_id
+            );
+            previous.close();
+        }
+
+        if let Some(charger) = self.charger(charge_point_id).await {
+            charger.attach_session(Arc::clone(&session)).await;
+        }
+
+        info!("Charge point {} connected", charge_point_id);
+        (session, outbound_rx)
+    }
+
+    /// Drop a session once its socket is gone
+    pub async fn close_session(&self, session: &Arc<ChargePointSession>) {
+        let id = session.charge_point_id();
+        let mut sessions = self.inner.sessions.write().await;
+        // Only remove the entry if it has not been replaced by a newer connection
+        if sessions.get(id).is_some_and(|s| Arc::ptr_eq(s, session)) {
+            sessions.remove(id);
+            drop(sessions);
+            if let Some(charger) = self.charger(id).await {
+                charger.detach_session().await;
+            }
+            info!("Charge point {} disconnected", id);
+        }
+    }
+
+    /// Process one text frame from a charge point, returning the reply to send
+    pub async fn handle_frame(&self, session: &ChargePointSession, text: &str) -> Option<String> {
+        match OcppMessage::parse(text) {
+            Ok(OcppMessage::Call(call)) => Some(
+                self.handle_call(session.charge_point_id(), call)
+                    .await
+                    .to_json(),
+            ),
+            Ok(response) => {
+                if !session.resolve(response).await {
+                    debug!(
+                        "Ignoring response from {} with no pending CALL",
+                        session.charge_point_id()
+                    );
+                }
+                None
+            }
+            Err(e) => {
+                warn!("Dropping frame from {}: {}", session.charge_point_id(), e);
+                None
+            }
+        }
+    }
+
+    async fn charger(&self, charge_point_id: &str) -> Option<Arc<OcppEvCharger>> {
+        self.inner
+            .chargers
+            .read()
+            .await
+            .get(charge_point_id)
+            .cloned()
+    }
+
+    async fn handle_call(&self, charge_point_id: &str, call: Call) -> OcppMessage {
+        let message_id = call.message_id.clone();
+        let result = match call.action.as_str() {
+            "BootNotification" => {
+                self.on_boot_notification(charge_point_id, call.payload)
+                    .await
+            }
+            "Heartbeat" => reply(HeartbeatResponse {
+                current_time: Utc::now(),
+            }),
+            "StatusNotification" => {
+                self.on_status_notification(charge_point_id, call.payload)
+                    .await
+            }
+            "MeterValues" => self.on_meter_values(charge_point_id, call.payload).await,
+            "StartTransaction" => {
+                self.on_start_transaction(charge_point_id, call.payload)
+                    .await
+            }
+            "StopTransaction" => {
+                self.on_stop_transaction(charge_point_id, call.payload)
+                    .await
+            }
+            other => {
+                debug!("Unsupported action {} from {}", other, charge_point_id);
+                return OcppMessage::CallError(CallError::new(
+                    message_id,
+                    ErrorCode::NotImplemented,
+                    format!("{} is not implemented", other),
+                ));
+            }
+        };
+
+        match result {
+            Ok(payload) => OcppMessage::CallResult(CallResult::new(message_id, payload)),
+            Err(e) => {
+                warn!(
+                    "Rejecting {} from {}: {:#}",
+                    call.action, charge_point_id, e
+                );
+                OcppMessage::CallError(CallError::new(
+                    message_id,
+                    ErrorCode::FormationViolation,
+                    e.to_string(),
+                ))
+            }
+        }
+    }
+
+    async fn on_boot_notification(
+        &self,
+        charge_point_id: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let request: BootNotificationRequest = decode(payload)?;
+        info!(
+            "BootNotification from {}: {} {} (firmware {:?})",
+            charge_point_id,
+            request.charge_point_vendor,
+            request.charge_point_model,
+            request.firmware_version
+        );
+
+        reply(BootNotificationResponse {
+            status: RegistrationStatus::Accepted,
+            current_time: Utc::now(),
+            interval: self.inner.config.heartbeat_interval_secs as i32,
+        })
+    }
+
+    async fn on_status_notification(
+        &self,
+        charge_point_id: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let request: StatusNotificationRequest = decode(payload)?;
+        debug!(
+            "StatusNotification from {} connector {}: {:?} ({:?})",
+            charge_point_id, request.connector_id, request.status, request.error_code
+        );
+
+        if let Some(charger) = self.charger(charge_point_id).await {
+            if request.connector_id == charger.connector_id() {
+                charger.handle_status_notification(request.status).await?;
+            }
+        }
+
+        reply(StatusNotificationResponse {})
+    }
+
+    async fn on_meter_values(
+        &self,
+        charge_point_id: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let request: MeterValuesRequest = decode(payload)?;
+
+        if let Some(charger) = self.charger(charge_point_id).await {
+            if request.connector_id == charger.connector_id() {
+                let summary = MeterSummary::from_meter_values(&request.meter_value);
+                charger
+                    .handle_meter_values(
+                        summary.energy_kwh,
+                        summary.power_w,
+                        summary.current_a,
+                        summary.soc_percent,
+                    )
+                    .await?;
+            }
+        }
+
+        reply(MeterValuesResponse {})
+    }
+
+    async fn on_start_transaction(
+        &self,
+        charge_point_id: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let request: StartTransactionRequest = decode(payload)?;
+        let transaction_id = self
+            .inner
+            .next_transaction_id
+            .fetch_add(1, Ordering::SeqCst);
+        info!(
+            "StartTransaction {} on {} connector {} for {}",
+            transaction_id, charge_point_id, request.connector_id, request.id_tag
+        );
+
+        if let Some(charger) = self.charger(charge_point_id).await {
+            if request.connector_id == charger.connector_id() {
+                charger
+                    .handle_transaction_started(transaction_id, request.meter_start)
+                    .await?;
+            }
+        }
+
+        reply(StartTransactionResponse {
+            id_tag_info: IdTagInfo {
+                expiry_date: None,
+                parent_id_tag: None,
+                status: AuthorizationStatus::Accepted,
+            },
+            transaction_id,
+        })
+    }
+
+    async fn on_stop_transaction(
+        &self,
+        charge_point_id: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let request: StopTransactionRequest = decode(payload)?;
+        info!(
+            "StopTransaction {} on {} ({:?})",
+            request.transaction_id, charge_point_id, request.reason
+        );
+
+        if let Some(charger) = self.charger(charge_point_id).await {
+            charger
+                .handle_transaction_stopped(request.transaction_id, request.meter_stop)
+                .await?;
+        }
+
+        reply(StopTransactionResponse {
+            id_tag_info: Some(IdTagInfo {
+                expiry_date: None,
+                parent_id_tag: None,
+                status: AuthorizationStatus::Accepted,
+            }),
+        })
+    }
+
+    /// Pump frames between a charger's WebSocket and its session
+    async fn serve_socket(self, charge_point_id: String, socket: WebSocket) {
+        let (session, mut outbound_rx) = self.open_session(&charge_point_id).await;
+        let (mut sink, mut stream) = socket.split();
+
+        loop {
+            tokio::select! {
+                _ = session.shutdown.notified() => break,
+                frame = outbound_rx.recv() => match frame {
+                    Some(text) => {
+                        if sink.send(WsMessage::Text(text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+                msg = stream.next() => match msg {
+                    Some(Ok(WsMessage::Text(text))) => {
+                        if let Some(reply) = self.handle_frame(&session, &text).await {
+                            if sink.send(WsMessage::Text(reply)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(WsMessage::Close(_))) | None => break,
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        warn!("WebSocket error from {}: {}", charge_point_id, e);
+                        break;
+                    }
+                },
+            }
+        }
+
+        let _ = sink.close().await;
+        self.close_session(&session).await;
+    }
+}
+
+async fn ws_handler(
+    State(central_system): State<CentralSystem>,
+    Path(charge_point_id): Path<String>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if let Err(status) = central_system.authorize(&charge_point_id, &headers).await {
+        warn!(
+            "Refused connection from charge point {}: {}",
+            charge_point_id, status
+        );
+        return match status {
+            StatusCode::UNAUTHORIZED => {
+                (status, [(header::WWW_AUTHENTICATE, "Basic realm=\"OCPP\"")]).into_response()
+            }
+            _ => status.into_response(),
+        };
+    }
+
+    let offers_ocpp16 = headers
+        .get_all("Sec-WebSocket-Protocol")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|p| p.trim() == super::OCPP16_SUBPROTOCOL);
+    if !offers_ocpp16 {
+        warn!(
+            "Charge point {} did not offer the {} subprotocol",
+            charge_point_id,
+            super::OCPP16_SUBPROTOCOL
+        );
+    }
+
+    ws.protocols([super::OCPP16_SUBPROTOCOL])
+        .on_upgrade(move |socket| central_system.serve_socket(charge_point_id, socket))
+}
+
+/// Username and password of an `Authorization: Basic` header
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+fn decode<T: DeserializeOwned>(payload: serde_json::Value) -> Result<T> {
+    serde_json::from_value(payload).context("Invalid payload")
+}
+
+fn reply<T: Serialize>(response: T) -> Result<serde_json::Value> {
+    Ok(serde_json::to_value(response)?)
+}
+
+/// Latest readings extracted from a MeterValues payload
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeterSummary {
+    /// Energy import register (kWh)
+    pub energy_kwh: Option<f64>,
+    /// Active charging power, summed over phases if no total is reported (W)
+    pub power_w: Option<f64>,
+    /// Charging current; the highest phase current if reported per phase (A)
+    pub current_a: Option<f64>,
+    /// Vehicle state of charge (%)
+    pub soc_percent: Option<f64>,
+}
+
+impl MeterSummary {
+    /// Summarize the most recent meter value in the batch
+    pub fn from_meter_values(meter_values: &[MeterValue]) -> Self {
+        let Some(latest) = meter_values.iter().max_by_key(|m| m.timestamp) else {
+            return Self::default();
+        };
+
+        let mut summary = Self::default();
+        let mut phase_power_w: Option<f64> = None;
+        let mut phase_current_a: Option<f64> = None;
+
+        for sample in &latest.sampled_value {
+            let Ok(value) = sample.value.trim().parse::<f64>() else {
+                continue;
+            };
+            let per_phase = sample.phase.is_some();
+
+            match sample
+                .measurand
+                .unwrap_or(Measurand::EnergyActiveImportRegister)
+            {
+                Measurand::EnergyActiveImportRegister if !per_phase => {
+                    summary.energy_kwh = Some(match sample.unit {
+                        Some(UnitOfMeasure::KWh) => value,
+                        _ => value / 1000.0,
+                    });
+                }
+                Measurand::PowerActiveImport => {
+                    let watts = match sample.unit {
+                        Some(UnitOfMeasure::KW) => value * 1000.0,
+                        _ => value,
+                    };
+                    if per_phase {
+                        phase_power_w = Some(phase_power_w.unwrap_or(0.0) + watts);
+                    } else {
+                        summary.power_w = Some(watts);
+                    }
+                }
+                Measurand::CurrentImport => {
+                    if per_phase {
+                        phase_current_a = Some(phase_current_a.map_or(value, |c| c.max(value)));
+                    } else {
+                        summary.current_a = Some(value);
+                    }
+                }
+                Measurand::SoC => summary.soc_percent = Some(value),
+                _ => {}
+            }
+        }
+
+        summary.power_w = summary.power_w.or(phase_power_w);
+        summary.current_a = summary.current_a.or(phase_current_a);
+        summary
+    }
+}
+
+/// Session whose charger side is played by `respond`
+#[cfg(test)]
+pub(crate) fn scripted_session<F>(charge_point_id: &str, respond: F) -> Arc<ChargePointSession>
+where
+    F: Fn(&Call) -> serde_json::Value + Send + 'static,
+{
+    let (session, mut outbound_rx) =
+        ChargePointSession::new(charge_point_id, Duration::from_secs(2));
+    let peer = Arc::clone(&session);
+    tokio::spawn(async move {
+        while let Some(text) = outbound_rx.recv().await {
+            if let Ok(OcppMessage::Call(call)) = OcppMessage::parse(&text) {
+                let result = CallResult::new(call.message_id.clone(), respond(&call));
+                peer.resolve(OcppMessage::CallResult(result)).await;
+            }
+        }
+    });
+    session
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ev_charger::{ChargerStatus, EvCharger};
+    use crate::hardware::ocpp::OcppEvChargerConfig;
+
+    fn call_frame(action: &str, payload: serde_json::Value) -> String {
+        OcppMessage::Call(Call::new(action, payload)).to_json()
+    }
+
+    const PASSWORD: &str = "correct-horse-battery";
+
+    async fn central_system_with_charger() -> (CentralSystem, Arc<OcppEvCharger>) {
+        let central_system = CentralSystem::new(CentralSystemConfig {
+            charge_point_passwords: HashMap::from([("CP001".to_string(), PASSWORD.to_string())]),
+            ..CentralSystemConfig::default()
+        });
+        let charger = Arc::new(OcppEvCharger::new(OcppEvChargerConfig::default()));
+        central_system.register_charger(Arc::clone(&charger)).await;
+        (central_system, charger)
+    }
+
+    fn expect_result(reply: Option<String>) -> serde_json::Value {
+        match OcppMessage::parse(&reply.expect("no reply")).unwrap() {
+            OcppMessage::CallResult(result) => result.payload,
+            other => panic!("expected CALLRESULT, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_boot_notification_returns_heartbeat_interval() {
+        let (central_system, charger) = central_system_with_charger().await;
+        let (session, _rx) = central_system.open_session("CP001").await;
+        assert!(charger.is_connected().await);
+
+        let reply = central_system
+            .handle_frame(
+                &session,
+                &call_frame(
+                    "BootNotification",
+                    serde_json::json!({
+                        "chargePointVendor": "Easee",
+                        "chargePointModel": "Home"
+                    }),
+                ),
+            )
+            .await;
+        let payload = expect_result(reply);
+        assert_eq!(payload["status"], "Accepted");
+        assert_eq!(payload["interval"], 300);
+    }
+
+    #[tokio::test]
+    async fn test_status_and_meter_values_reach_charger() {
+        let (central_system, charger) = central_system_with_charger().await;
+        let (session, _rx) = central_system.open_session("CP001").await;
+
+        central_system
+            .handle_frame(
+                &session,
+                &call_frame(
+                    "StatusNotification",
+                    serde_json::json!({
+                        "connectorId": 1,
+                        "errorCode": "NoError",
+                        "status": "Charging"
+                    }),
+                ),
+            )
+            .await;
+        central_system
+            .handle_frame(
+                &session,
+                &call_frame(
+                    "MeterValues",
+                    serde_json::json!({
+                        "connectorId": 1,
+                        "meterValue": [{
+                            "timestamp": "2024-06-01T12:00:00Z",
+                            "sampledValue": [
+                                {"value": "3200", "unit": "Wh"},
+                                {"value": "7400", "measurand": "Power.Active.Import", "unit": "W"},
+                                {"value": "10.5", "measurand": "Current.Import", "phase": "L1", "unit": "A"},
+                                {"value": "10.7", "measurand": "Current.Import", "phase": "L2", "unit": "A"}
+                            ]
+                        }]
+                    }),
+                ),
+            )
+            .await;
+
+        let state = charger.read_state().await.unwrap();
+        assert_eq!(state.status, ChargerStatus::Charging);
+        assert!(state.charging);
+        assert_eq!(state.energy_delivered_kwh, 3.2);
+        assert_eq!(state.power_w, 7400.0);
+        assert_eq!(state.current_amps, 10.7);
+    }
+
+    #[tokio::test]
+    async fn test_transactions_are_tracked() {
+        let (central_system, charger) = central_system_with_charger().await;
+        let (session, _rx) = central_system.open_session("CP001").await;
+
+        let reply = central_system
+            .handle_frame(
+                &session,
+                &call_frame(
+                    "StartTransaction",
+                    serde_json::json!({
+                        "connectorId": 1,
+                        "idTag": "TAG1",
+                        "meterStart": 1000,
+                        "timestamp": "2024-06-01T12:00:00Z"
+                    }),
+                ),
+            )
+            .await;
+        let payload = expect_result(reply);
+        assert_eq!(payload["idTagInfo"]["status"], "Accepted");
+        let transaction_id = payload["transactionId"].as_i64().unwrap() as i32;
+        assert_eq!(charger.transaction_id().await, Some(transaction_id));
+
+        let reply = central_system
+            .handle_frame(
+                &session,
+                &call_frame(
+                    "StopTransaction",
+                    serde_json::json!({
+                        "meterStop": 4500,
+                        "timestamp": "2024-06-01T13:00:00Z",
+                        "transactionId": transaction_id
+                    }),
+                ),
+            )
+            .await;
+        expect_result(reply);
+        assert_eq!(charger.transaction_id().await, None);
+        assert_eq!(
+            charger.read_state().await.unwrap().energy_delivered_kwh,
+            3.5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_action_and_bad_payload_are_rejected() {
+        let (central_system, _charger) = central_system_with_charger().await;
+        let (session, _rx) = central_system.open_session("CP001").await;
+
+        let reply = central_system
+            .handle_frame(&session, &call_frame("DataTransfer", serde_json::json!({})))
+            .await
+            .unwrap();
+        assert!(
+            matches!(OcppMessage::parse(&reply).unwrap(), OcppMessage::CallError(e) if e.error_code == "NotImplemented")
+        );
+
+        let reply = central_system
+            .handle_frame(
+                &session,
+                &call_frame("StartTransaction", serde_json::json!({})),
+            )
+            .await
+            .unwrap();
+        assert!(
+            matches!(OcppMessage::parse(&reply).unwrap(), OcppMessage::CallError(e) if e.error_code == "FormationViolation")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_replaces_session() {
+        let (central_system, charger) = central_system_with_charger().await;
+        let (first, _rx1) = central_system.open_session("CP001").await;
+        let (second, _rx2) = central_system.open_session("CP001").await;
+
+        // The stale socket closing must not detach the new session
+        central_system.close_session(&first).await;
+        assert!(charger.is_connected().await);
+        assert_eq!(
+            central_system.connected_charge_points().await,
+            vec!["CP001"]
+        );
+
+        central_system.close_session(&second).await;
+        assert!(!charger.is_connected().await);
+        assert!(central_system.connected_charge_points().await.is_empty());
+    }
+
+    fn basic_auth(username: &str, password: &str) -> HeaderMap {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Basic {}", credentials).parse().unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_charge_points_must_authenticate() {
+        let (central_system, _charger) = central_system_with_charger().await;
+
+        assert_eq!(
+            central_system
+                .authorize("CP001", &basic_auth("CP001", PASSWORD))
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            central_system.authorize("CP001", &HeaderMap::new()).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            central_system
+                .authorize("CP001", &basic_auth("CP001", "wrong-password-here"))
+                .await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        // Credentials of one charge point do not open another's endpoint
+        assert_eq!(
+            central_system
+                .authorize("CP001", &basic_auth("CP002", PASSWORD))
+                .await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            central_system
+                .authorize("CP002", &basic_auth("CP002", PASSWORD))
+                .await,
+            Err(StatusCode::NOT_FOUND)
+        );
+    }
+
+    #[test]
+    fn test_meter_summary_sums_phase_power() {
+        let meter_values: Vec<MeterValue> = serde_json::from_value(serde_json::json!([
+            {
+                "timestamp": "2024-06-01T11:59:00Z",
+                "sampledValue": [{"value": "1.0", "unit": "kWh"}]
+            },
+            {
+                "timestamp": "2024-06-01T12:00:00Z",
+                "sampledValue": [
+                    {"value": "2.5", "unit": "kWh"},
+                    {"value": "2.3", "measurand": "Power.Active.Import", "phase": "L1", "unit": "kW"},
+                    {"value": "2.4", "measurand": "Power.Active.Import", "phase": "L2", "unit": "kW"},
+                    {"value": "64", "measurand": "SoC", "unit": "Percent"}
+                ]
+            }
+        ]))
+        .unwrap();
+
+        let summary = MeterSummary::from_meter_values(&meter_values);
+        assert_eq!(summary.energy_kwh, Some(2.5));
+        assert_eq!(summary.power_w.map(|p| p.round()), Some(4700.0));
+        assert_eq!(summary.current_a, None);
+        assert_eq!(summary.soc_percent, Some(64.0));
+    }
+
+    #[cfg(feature = "ocpp")]
+    #[tokio::test]
+    async fn test_charger_connects_over_websocket() {
+        use crate::ocpp::transport::{OcppTransport, TransportConfig};
+
+        let (central_system, charger) = central_system_with_charger().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = central_system.router("/ocpp");
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let url = format!("ws://{}/ocpp/CP001", addr);
+        assert!(OcppTransport::connect(&url, TransportConfig::default())
+            .await
+            .is_err());
+
+        // The charge point side of the connection
+        let (transport, mut incoming) = OcppTransport::connect(
+            &url,
+            TransportConfig {
+                basic_auth: Some(("CP001".to_string(), PASSWORD.to_string())),
+                ..TransportConfig::default()
+            },
+        )
+        .await
+        .unwrap();
+        tokio::spawn({
+            let transport = transport.clone();
+            async move {
+                while let Some(call) = incoming.recv().await {
+                    let _ = transport
+                        .respond(
+                            &call.message_id,
+                            serde_json::json!({ "status": "Accepted" }),
+                        )
+                        .await;
+                }
+            }
+        });
+
+        let boot: BootNotificationResponse = transport
+            .call_typed(
+                "BootNotification",
+                &serde_json::json!({ "chargePointVendor": "ABB", "chargePointModel": "Terra AC" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(boot.status, RegistrationStatus::Accepted);
+        assert!(charger.is_connected().await);
+
+        transport
+            .call(
+                "StatusNotification",
+                serde_json::json!({ "connectorId": 1, "errorCode": "NoError", "status": "Charging" }),
+            )
+            .await
+            .unwrap();
+
+        // Controller -> charger: SetChargingProfile travels back over the same socket
+        charger.set_current(10.0).await.unwrap();
+        assert_eq!(charger.read_state().await.unwrap().current_amps, 10.0);
+    }
+}
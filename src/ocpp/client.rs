@@ -1,17 +1,24 @@
 #![allow(dead_code)]
-#![allow(dead_code)]
 //! OCPP Client Implementation
 //!
 //! This module provides a WebSocket-based client for communicating with
 //! EV chargers using the OCPP 1.6 protocol.
+//!
+//! The WebSocket transport requires the `ocpp` feature. Without it the client
+//! can still be constructed and its handlers exercised, but every outgoing
+//! request fails.
 
 use super::messages::*;
+#[cfg(feature = "ocpp")]
+use super::transport::{OcppTransport, TransportConfig};
 use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 /// OCPP WebSocket Client
+#[derive(Clone)]
 pub struct OcppWebSocketClient {
     endpoint_url: String,
     charge_point_id: String,
@@ -19,6 +26,8 @@ pub struct OcppWebSocketClient {
     /// Track active transaction IDs to properly validate RemoteStopTransaction
     /// A real charger rejects stop requests if no transaction is active
     active_transactions: Arc<RwLock<HashSet<i32>>>,
+    #[cfg(feature = "ocpp")]
+    transport: Arc<RwLock<Option<OcppTransport>>>,
 }
 
 impl OcppWebSocketClient {
@@ -29,33 +38,152 @@ impl OcppWebSocketClient {
             charge_point_id,
             heartbeat_interval: Arc::new(RwLock::new(None)),
             active_transactions: Arc::new(RwLock::new(HashSet::new())),
+            #[cfg(feature = "ocpp")]
+            transport: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Full WebSocket URL; OCPP-J identifies the charge point by the last path segment
+    pub fn connection_url(&self) -> String {
+        format!(
+            "{}/{}",
+            self.endpoint_url.trim_end_matches('/'),
+            self.charge_point_id
+        )
+    }
+
+    /// Connect to the central system with default transport timing
+    pub async fn connect(&self) -> Result<()> {
+        #[cfg(feature = "ocpp")]
+        {
+            self.connect_with(TransportConfig::default()).await
+        }
+        #[cfg(not(feature = "ocpp"))]
+        {
+            anyhow::bail!("OCPP WebSocket transport requires 'ocpp' feature to be enabled")
+        }
+    }
+
+    /// Connect to the central system and start dispatching its requests
+    #[cfg(feature = "ocpp")]
+    pub async fn connect_with(&self, config: TransportConfig) -> Result<()> {
+        let (transport, mut incoming) =
+            OcppTransport::connect(&self.connection_url(), config).await?;
+        *self.transport.write().await = Some(transport.clone());
+
+        let client = self.clone();
+        tokio::spawn(async move {
+            while let Some(call) = incoming.recv().await {
+                client.handle_incoming_call(&transport, call).await;
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Close the connection to the central system
+    pub async fn disconnect(&self) {
+        #[cfg(feature = "ocpp")]
+        if let Some(transport) = self.transport.write().await.take() {
+            transport.close().await;
+        }
+    }
+
+    /// Answer a CALL initiated by the central system
+    #[cfg(feature = "ocpp")]
+    async fn handle_incoming_call(&self, transport: &OcppTransport, call: super::Call) {
+        let reply = match call.action.as_str() {
+            "RemoteStartTransaction" => match serde_json::from_value(call.payload) {
+                Ok(request) => self
+                    .handle_remote_start(request)
+                    .await
+                    .and_then(|r| Ok(serde_json::to_value(r)?)),
+                Err(e) => Err(e.into()),
+            },
+            "RemoteStopTransaction" => match serde_json::from_value(call.payload) {
+                Ok(request) => self
+                    .handle_remote_stop(request)
+                    .await
+                    .and_then(|r| Ok(serde_json::to_value(r)?)),
+                Err(e) => Err(e.into()),
+            },
+            other => {
+                tracing::debug!("Rejecting unsupported OCPP action {}", other);
+                let _ = transport
+                    .respond_error(
+                        &call.message_id,
+                        super::ErrorCode::NotImplemented,
+                        &format!("{} is not implemented", other),
+                    )
+                    .await;
+                return;
+            }
+        };
+
+        let sent = match reply {
+            Ok(payload) => transport.respond(&call.message_id, payload).await,
+            Err(e) => {
+                transport
+                    .respond_error(
+                        &call.message_id,
+                        super::ErrorCode::FormationViolation,
+                        &e.to_string(),
+                    )
+                    .await
+            }
+        };
+        if let Err(e) = sent {
+            tracing::warn!("Failed to answer {}: {}", call.action, e);
+        }
+    }
+
+    /// Send a CALL over the active transport
+    async fn call<Req, Resp>(&self, action: &str, request: &Req) -> Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        #[cfg(feature = "ocpp")]
+        {
+            let transport = self
+                .transport
+                .read()
+                .await
+                .clone()
+                .ok_or(super::OcppError::NotConnected)?;
+            transport.call_typed(action, request).await
+        }
+        #[cfg(not(feature = "ocpp"))]
+        {
+            let _ = request;
+            anyhow::bail!(
+                "Cannot send {}: OCPP WebSocket transport requires 'ocpp' feature to be enabled",
+                action
+            )
         }
     }
 
     /// Send a boot notification
+    ///
+    /// On acceptance the heartbeat interval returned by the central system is stored.
     pub async fn send_boot_notification(
         &self,
-        _request: BootNotificationRequest,
+        request: BootNotificationRequest,
     ) -> Result<BootNotificationResponse> {
-        // TODO: Implement actual WebSocket communication
-        // For now, return a mock response
         tracing::debug!("Sending boot notification for charge point {}", self.charge_point_id);
 
-        Ok(BootNotificationResponse {
-            status: RegistrationStatus::Accepted,
-            current_time: chrono::Utc::now(),
-            interval: 300, // 5 minutes
-        })
+        let response: BootNotificationResponse = self.call("BootNotification", &request).await?;
+        if response.status == RegistrationStatus::Accepted {
+            self.set_heartbeat_interval(response.interval).await;
+        }
+
+        Ok(response)
     }
 
     /// Send a heartbeat
     pub async fn send_heartbeat(&self) -> Result<HeartbeatResponse> {
-        // TODO: Implement actual WebSocket communication
         tracing::debug!("Sending heartbeat");
-
-        Ok(HeartbeatResponse {
-            current_time: chrono::Utc::now(),
-        })
+        self.call("Heartbeat", &HeartbeatRequest {}).await
     }
 
     /// Send a status notification
@@ -63,14 +191,13 @@ impl OcppWebSocketClient {
         &self,
         request: StatusNotificationRequest,
     ) -> Result<StatusNotificationResponse> {
-        // TODO: Implement actual WebSocket communication
         tracing::debug!(
             "Sending status notification for connector {}: {:?}",
             request.connector_id,
             request.status
         );
 
-        Ok(StatusNotificationResponse {})
+        self.call("StatusNotification", &request).await
     }
 
     /// Handle remote start transaction
@@ -152,14 +279,8 @@ impl OcppWebSocketClient {
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_boot_notification() {
-        let client = OcppWebSocketClient::new(
-            "ws://localhost:8080/ocpp".to_string(),
-            "CP001".to_string(),
-        );
-
-        let request = BootNotificationRequest {
+    fn boot_request() -> BootNotificationRequest {
+        BootNotificationRequest {
             charge_point_vendor: "OpenEnergyController".to_string(),
             charge_point_model: "OEC-CP-001".to_string(),
             charge_point_serial_number: Some("SN123456".to_string()),
@@ -169,21 +290,72 @@ mod tests {
             imsi: None,
             meter_type: None,
             meter_serial_number: None,
-        };
+        }
+    }
 
-        let response = client.send_boot_notification(request).await.unwrap();
+    #[cfg(feature = "ocpp")]
+    async fn connected_client() -> OcppWebSocketClient {
+        use crate::ocpp::transport::stand_in;
+        use crate::ocpp::{CallResult, OcppMessage};
+
+        let url = stand_in::spawn(|ws| {
+            stand_in::serve_calls(ws, |call| {
+                let payload = match call.action.as_str() {
+                    "BootNotification" => serde_json::json!({
+                        "status": "Accepted",
+                        "currentTime": chrono::Utc::now(),
+                        "interval": 300
+                    }),
+                    "Heartbeat" => serde_json::json!({ "currentTime": chrono::Utc::now() }),
+                    _ => serde_json::json!({}),
+                };
+                OcppMessage::CallResult(CallResult::new(call.message_id.clone(), payload))
+            })
+        })
+        .await;
+
+        let client = OcppWebSocketClient::new(format!("{}/ocpp", url), "CP001".to_string());
+        client.connect().await.unwrap();
+        client
+    }
+
+    #[cfg(feature = "ocpp")]
+    #[tokio::test]
+    async fn test_boot_notification() {
+        let client = connected_client().await;
+
+        let response = client.send_boot_notification(boot_request()).await.unwrap();
         assert_eq!(response.status, RegistrationStatus::Accepted);
+        assert_eq!(client.get_heartbeat_interval().await, Some(300));
     }
 
+    #[cfg(feature = "ocpp")]
     #[tokio::test]
     async fn test_heartbeat() {
+        let client = connected_client().await;
+
+        let response = client.send_heartbeat().await.unwrap();
+        assert!(response.current_time.timestamp() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_send_without_connection_fails() {
         let client = OcppWebSocketClient::new(
             "ws://localhost:8080/ocpp".to_string(),
             "CP001".to_string(),
         );
 
-        let response = client.send_heartbeat().await.unwrap();
-        assert!(response.current_time.timestamp() > 0);
+        assert!(client.send_boot_notification(boot_request()).await.is_err());
+        assert_eq!(client.get_heartbeat_interval().await, None);
+    }
+
+    #[test]
+    fn test_connection_url_appends_charge_point_id() {
+        let client = OcppWebSocketClient::new(
+            "ws://localhost:8080/ocpp/".to_string(),
+            "CP001".to_string(),
+        );
+        assert_eq!(client.connection_url(), "ws://localhost:8080/ocpp/CP001");
     }
 
     #[tokio::test]
@@ -202,4 +374,44 @@ mod tests {
         let response = client.handle_remote_start(request).await.unwrap();
         assert_eq!(response.status, RemoteStartStopStatus::Accepted);
     }
+
+    #[cfg(feature = "ocpp")]
+    #[tokio::test]
+    async fn test_remote_stop_from_central_system() {
+        use crate::ocpp::transport::stand_in;
+        use crate::ocpp::{Call, OcppMessage};
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let (answer_tx, answer_rx) = tokio::sync::oneshot::channel::<String>();
+        let answer_tx = Arc::new(tokio::sync::Mutex::new(Some(answer_tx)));
+        let url = stand_in::spawn(move |mut ws| {
+            let answer_tx = answer_tx.clone();
+            async move {
+                let call = Call::new(
+                    "RemoteStopTransaction",
+                    serde_json::json!({ "transactionId": 42 }),
+                );
+                ws.send(Message::Text(OcppMessage::Call(call).to_json()))
+                    .await
+                    .unwrap();
+                if let Some(Ok(Message::Text(text))) = ws.next().await {
+                    if let Some(tx) = answer_tx.lock().await.take() {
+                        let _ = tx.send(text);
+                    }
+                }
+            }
+        })
+        .await;
+
+        let client = OcppWebSocketClient::new(url, "CP001".to_string());
+        client.connect().await.unwrap();
+
+        // No transaction 42 is active, so the charger must reject the stop
+        let answer = answer_rx.await.unwrap();
+        match OcppMessage::parse(&answer).unwrap() {
+            OcppMessage::CallResult(result) => assert_eq!(result.payload["status"], "Rejected"),
+            other => panic!("expected CALLRESULT, got {:?}", other),
+        }
+    }
 }
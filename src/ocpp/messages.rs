@@ -1,5 +1,4 @@
 #![allow(dead_code)]
-#![allow(dead_code)]
 //! OCPP 1.6 Message Definitions
 //!
 //! This module defines the message payloads for various OCPP operations.
@@ -223,6 +222,209 @@ pub struct ChargingSchedulePeriod {
     pub number_phases: Option<i32>,
 }
 
+/// Set Charging Profile Request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetChargingProfileRequest {
+    pub connector_id: i32,
+    pub cs_charging_profiles: ChargingProfile,
+}
+
+/// Set Charging Profile Response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetChargingProfileResponse {
+    pub status: ChargingProfileStatus,
+}
+
+/// Charging Profile Status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ChargingProfileStatus {
+    Accepted,
+    Rejected,
+    NotSupported,
+}
+
+/// Start Transaction Request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartTransactionRequest {
+    pub connector_id: i32,
+    pub id_tag: String,
+    pub meter_start: i32, // Wh
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reservation_id: Option<i32>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Start Transaction Response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartTransactionResponse {
+    pub id_tag_info: IdTagInfo,
+    pub transaction_id: i32,
+}
+
+/// Stop Transaction Request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopTransactionRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_tag: Option<String>,
+    pub meter_stop: i32, // Wh
+    pub timestamp: DateTime<Utc>,
+    pub transaction_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<StopReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_data: Option<Vec<MeterValue>>,
+}
+
+/// Stop Transaction Response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopTransactionResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_tag_info: Option<IdTagInfo>,
+}
+
+/// Reason a transaction was stopped
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StopReason {
+    EmergencyStop,
+    EVDisconnected,
+    HardReset,
+    Local,
+    Other,
+    PowerLoss,
+    Reboot,
+    Remote,
+    SoftReset,
+    UnlockCommand,
+    DeAuthorized,
+}
+
+/// Authorization result for an ID tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdTagInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_date: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id_tag: Option<String>,
+    pub status: AuthorizationStatus,
+}
+
+/// Authorization Status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AuthorizationStatus {
+    Accepted,
+    Blocked,
+    Expired,
+    Invalid,
+    ConcurrentTx,
+}
+
+/// Meter Values Request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeterValuesRequest {
+    pub connector_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_id: Option<i32>,
+    pub meter_value: Vec<MeterValue>,
+}
+
+/// Meter Values Response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeterValuesResponse {}
+
+/// A set of sampled values taken at the same point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeterValue {
+    pub timestamp: DateTime<Utc>,
+    pub sampled_value: Vec<SampledValue>,
+}
+
+/// Single sampled meter value
+///
+/// The value is transmitted as a string; `measurand` defaults to
+/// `Energy.Active.Import.Register` and `unit` to `Wh` when omitted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SampledValue {
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub measurand: Option<Measurand>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<UnitOfMeasure>,
+}
+
+/// Measurand of a sampled value (subset used by the controller)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Measurand {
+    #[serde(rename = "Energy.Active.Import.Register")]
+    EnergyActiveImportRegister,
+    #[serde(rename = "Energy.Active.Export.Register")]
+    EnergyActiveExportRegister,
+    #[serde(rename = "Power.Active.Import")]
+    PowerActiveImport,
+    #[serde(rename = "Power.Active.Export")]
+    PowerActiveExport,
+    #[serde(rename = "Power.Offered")]
+    PowerOffered,
+    #[serde(rename = "Current.Import")]
+    CurrentImport,
+    #[serde(rename = "Current.Export")]
+    CurrentExport,
+    #[serde(rename = "Current.Offered")]
+    CurrentOffered,
+    Voltage,
+    Frequency,
+    Temperature,
+    SoC,
+    /// Any measurand the controller does not interpret
+    #[serde(other)]
+    Other,
+}
+
+/// Unit of a sampled value
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UnitOfMeasure {
+    Wh,
+    #[serde(rename = "kWh")]
+    KWh,
+    #[serde(rename = "varh")]
+    Varh,
+    #[serde(rename = "kvarh")]
+    Kvarh,
+    W,
+    #[serde(rename = "kW")]
+    KW,
+    VA,
+    #[serde(rename = "kVA")]
+    KVA,
+    #[serde(rename = "var")]
+    Var,
+    #[serde(rename = "kvar")]
+    Kvar,
+    A,
+    V,
+    Celsius,
+    Fahrenheit,
+    K,
+    Percent,
+}
+
 /// Change Configuration Request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -296,4 +498,29 @@ mod tests {
         let json = serde_json::to_string(&profile).unwrap();
         assert!(json.contains("\"limit\":32"));
     }
+
+    #[test]
+    fn test_meter_values_deserialization() {
+        let json = r#"{
+            "connectorId": 1,
+            "transactionId": 12,
+            "meterValue": [{
+                "timestamp": "2024-06-01T12:00:00Z",
+                "sampledValue": [
+                    {"value": "5500"},
+                    {"value": "7.4", "measurand": "Power.Active.Import", "unit": "kW"},
+                    {"value": "230.1", "measurand": "Voltage", "phase": "L1-N", "unit": "V"},
+                    {"value": "1", "measurand": "Power.Factor"}
+                ]
+            }]
+        }"#;
+
+        let request: MeterValuesRequest = serde_json::from_str(json).unwrap();
+        let samples = &request.meter_value[0].sampled_value;
+        assert_eq!(request.transaction_id, Some(12));
+        assert_eq!(samples[0].measurand, None);
+        assert_eq!(samples[1].measurand, Some(Measurand::PowerActiveImport));
+        assert_eq!(samples[1].unit, Some(UnitOfMeasure::KW));
+        assert_eq!(samples[3].measurand, Some(Measurand::Other));
+    }
 }
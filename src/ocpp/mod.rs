@@ -13,10 +13,17 @@
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use thiserror::Error;
 use tokio::sync::RwLock;
 
 pub mod messages;
 pub mod client;
+pub mod central_system;
+#[cfg(feature = "ocpp")]
+pub mod transport;
+
+/// WebSocket subprotocol negotiated for OCPP 1.6 JSON
+pub const OCPP16_SUBPROTOCOL: &str = "ocpp1.6";
 
 /// OCPP Message Type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -53,6 +60,168 @@ pub struct CallError {
     pub error_details: serde_json::Value,
 }
 
+impl Call {
+    /// Create a new CALL with a fresh unique message ID
+    pub fn new(action: impl Into<String>, payload: serde_json::Value) -> Self {
+        Self {
+            message_type_id: MessageType::Call as u8,
+            message_id: uuid::Uuid::new_v4().to_string(),
+            action: action.into(),
+            payload,
+        }
+    }
+}
+
+impl CallResult {
+    /// Create a CALLRESULT answering the CALL with the given message ID
+    pub fn new(message_id: impl Into<String>, payload: serde_json::Value) -> Self {
+        Self {
+            message_type_id: MessageType::CallResult as u8,
+            message_id: message_id.into(),
+            payload,
+        }
+    }
+}
+
+impl CallError {
+    /// Create a CALLERROR answering the CALL with the given message ID
+    pub fn new(
+        message_id: impl Into<String>,
+        error_code: ErrorCode,
+        error_description: impl Into<String>,
+    ) -> Self {
+        Self {
+            message_type_id: MessageType::CallError as u8,
+            message_id: message_id.into(),
+            error_code: error_code.to_string(),
+            error_description: error_description.into(),
+            error_details: serde_json::json!({}),
+        }
+    }
+}
+
+/// A single OCPP-J frame as carried in a WebSocket text message
+///
+/// On the wire each frame is a JSON array:
+/// - CALL: `[2, "<id>", "<action>", {payload}]`
+/// - CALLRESULT: `[3, "<id>", {payload}]`
+/// - CALLERROR: `[4, "<id>", "<code>", "<description>", {details}]`
+#[derive(Debug, Clone)]
+pub enum OcppMessage {
+    Call(Call),
+    CallResult(CallResult),
+    CallError(CallError),
+}
+
+impl OcppMessage {
+    /// Unique message ID used to correlate CALLs with their responses
+    pub fn message_id(&self) -> &str {
+        match self {
+            Self::Call(c) => &c.message_id,
+            Self::CallResult(r) => &r.message_id,
+            Self::CallError(e) => &e.message_id,
+        }
+    }
+
+    /// Encode the frame as an OCPP-J JSON array
+    pub fn to_json(&self) -> String {
+        let value = match self {
+            Self::Call(c) => serde_json::json!([
+                MessageType::Call as u8,
+                c.message_id,
+                c.action,
+                c.payload
+            ]),
+            Self::CallResult(r) => {
+                serde_json::json!([MessageType::CallResult as u8, r.message_id, r.payload])
+            }
+            Self::CallError(e) => serde_json::json!([
+                MessageType::CallError as u8,
+                e.message_id,
+                e.error_code,
+                e.error_description,
+                e.error_details
+            ]),
+        };
+        value.to_string()
+    }
+
+    /// Decode an OCPP-J JSON array into a frame
+    pub fn parse(text: &str) -> Result<Self, OcppError> {
+        let value: serde_json::Value = serde_json::from_str(text)
+            .map_err(|e| OcppError::Malformed(format!("invalid JSON: {}", e)))?;
+        let items = value
+            .as_array()
+            .ok_or_else(|| OcppError::Malformed("frame is not a JSON array".to_string()))?;
+
+        let type_id = items
+            .first()
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| OcppError::Malformed("missing message type id".to_string()))?;
+        let message_id = items
+            .get(1)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| OcppError::Malformed("missing unique message id".to_string()))?
+            .to_string();
+        let str_at = |index: usize, field: &str| {
+            items
+                .get(index)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| OcppError::Malformed(format!("missing {}", field)))
+        };
+
+        match type_id {
+            2 if items.len() == 4 => Ok(Self::Call(Call {
+                message_type_id: MessageType::Call as u8,
+                message_id,
+                action: str_at(2, "action")?,
+                payload: items[3].clone(),
+            })),
+            3 if items.len() == 3 => Ok(Self::CallResult(CallResult {
+                message_type_id: MessageType::CallResult as u8,
+                message_id,
+                payload: items[2].clone(),
+            })),
+            4 if items.len() == 5 => Ok(Self::CallError(CallError {
+                message_type_id: MessageType::CallError as u8,
+                message_id,
+                error_code: str_at(2, "error code")?,
+                error_description: str_at(3, "error description")?,
+                error_details: items[4].clone(),
+            })),
+            2..=4 => Err(OcppError::Malformed(format!(
+                "unexpected element count {} for message type {}",
+                items.len(),
+                type_id
+            ))),
+            other => Err(OcppError::Malformed(format!(
+                "unknown message type id {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Errors surfaced by the OCPP-J transport
+#[derive(Debug, Error)]
+pub enum OcppError {
+    #[error("Not connected to OCPP peer")]
+    NotConnected,
+    #[error("{action} timed out after {timeout_ms} ms")]
+    Timeout { action: String, timeout_ms: u64 },
+    #[error("{action} rejected by peer with {code}: {description}")]
+    CallError {
+        action: String,
+        code: String,
+        description: String,
+    },
+    #[error("Connection closed before a response to {0} arrived")]
+    ConnectionClosed(String),
+    #[error("Malformed OCPP-J frame: {0}")]
+    Malformed(String),
+}
+
 /// OCPP Error Codes
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ErrorCode {
@@ -210,4 +379,46 @@ mod tests {
         let state = client.state.read().await;
         assert!(state.last_heartbeat.is_some());
     }
+
+    #[test]
+    fn test_call_frame_round_trip() {
+        let call = Call::new("Heartbeat", serde_json::json!({}));
+        let text = OcppMessage::Call(call.clone()).to_json();
+        assert!(text.starts_with("[2,"));
+
+        match OcppMessage::parse(&text).unwrap() {
+            OcppMessage::Call(parsed) => {
+                assert_eq!(parsed.message_id, call.message_id);
+                assert_eq!(parsed.action, "Heartbeat");
+            }
+            other => panic!("expected CALL, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_call_result_and_error_parsing() {
+        let result = OcppMessage::parse(r#"[3,"abc",{"currentTime":"2024-01-01T00:00:00Z"}]"#)
+            .unwrap();
+        assert!(matches!(result, OcppMessage::CallResult(ref r) if r.message_id == "abc"));
+
+        let error = OcppMessage::CallError(CallError::new(
+            "abc",
+            ErrorCode::NotImplemented,
+            "unknown action",
+        ));
+        match OcppMessage::parse(&error.to_json()).unwrap() {
+            OcppMessage::CallError(e) => {
+                assert_eq!(e.error_code, "NotImplemented");
+                assert_eq!(e.error_description, "unknown action");
+            }
+            other => panic!("expected CALLERROR, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_malformed_frames_rejected() {
+        assert!(OcppMessage::parse("{}").is_err());
+        assert!(OcppMessage::parse(r#"[2,"id","Heartbeat"]"#).is_err());
+        assert!(OcppMessage::parse(r#"[9,"id",{}]"#).is_err());
+    }
 }
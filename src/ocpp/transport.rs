@@ -0,0 +1,672 @@
+#![allow(dead_code)]
+//! OCPP-J WebSocket Transport
+//!
+//! Carries OCPP 1.6 JSON frames over a WebSocket connection negotiated with the
+//! `ocpp1.6` subprotocol. The transport owns the socket in a background task and
+//! exposes a request/response API on top of it:
+//! - Outgoing CALLs are correlated with their CALLRESULT/CALLERROR by unique message ID
+//! - Every CALL is bounded by a request timeout
+//! - Only one CALL is in flight at a time, as required by OCPP-J
+//! - Incoming CALLs from the peer are forwarded on a channel for dispatch
+//! - Lost connections are re-established with exponential backoff
+
+use super::{Call, CallError, CallResult, ConnectionState, ErrorCode, OcppError, OcppMessage};
+use anyhow::{Context, Result};
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type PendingResponse = std::result::Result<serde_json::Value, CallError>;
+type PendingMap = Arc<Mutex<HashMap<String, oneshot::Sender<PendingResponse>>>>;
+
+/// Base delay for reconnect backoff
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound for reconnect backoff
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(16);
+/// Capacity of the queue for incoming CALLs awaiting dispatch
+const INCOMING_QUEUE_SIZE: usize = 32;
+
+/// Timing settings for the OCPP-J transport
+#[derive(Debug, Clone)]
+pub struct TransportConfig {
+    /// How long to wait for a CALLRESULT/CALLERROR before giving up
+    pub request_timeout: Duration,
+    /// Timeout for the WebSocket handshake
+    pub connect_timeout: Duration,
+    /// Re-establish the connection when it drops
+    pub reconnect: bool,
+    /// Username and password for HTTP Basic auth (OCPP security profile 1)
+    pub basic_auth: Option<(String, String)>,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            reconnect: true,
+            basic_auth: None,
+        }
+    }
+}
+
+enum Outbound {
+    Frame(String),
+    Close,
+}
+
+/// Handle to an OCPP-J WebSocket connection
+///
+/// Cloning the handle shares the underlying connection. The connection task
+/// shuts down once [`OcppTransport::close`] is called or every handle is dropped.
+#[derive(Clone)]
+pub struct OcppTransport {
+    url: String,
+    config: TransportConfig,
+    outbound: mpsc::Sender<Outbound>,
+    pending: PendingMap,
+    state: Arc<RwLock<ConnectionState>>,
+    /// OCPP-J allows a single outstanding CALL per direction
+    call_lock: Arc<Mutex<()>>,
+}
+
+impl OcppTransport {
+    /// Connect to `url` and start the connection task
+    ///
+    /// Returns the transport handle together with a receiver for CALLs
+    /// initiated by the peer. Every received CALL must be answered with
+    /// [`OcppTransport::respond`] or [`OcppTransport::respond_error`].
+    pub async fn connect(
+        url: &str,
+        config: TransportConfig,
+    ) -> Result<(Self, mpsc::Receiver<Call>)> {
+        let ws = open(url, &config).await?;
+
+        let (outbound_tx, outbound_rx) = mpsc::channel(INCOMING_QUEUE_SIZE);
+        let (incoming_tx, incoming_rx) = mpsc::channel(INCOMING_QUEUE_SIZE);
+
+        let transport = Self {
+            url: url.to_string(),
+            config,
+            outbound: outbound_tx,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            state: Arc::new(RwLock::new(ConnectionState::Connected)),
+            call_lock: Arc::new(Mutex::new(())),
+        };
+
+        tokio::spawn(connection_task(
+            transport.url.clone(),
+            transport.config.clone(),
+            ws,
+            outbound_rx,
+            incoming_tx,
+            transport.pending.clone(),
+            transport.state.clone(),
+        ));
+
+        tracing::info!("OCPP-J transport connected to {}", url);
+        Ok((transport, incoming_rx))
+    }
+
+    /// Current connection state
+    pub async fn state(&self) -> ConnectionState {
+        *self.state.read().await
+    }
+
+    /// Send a CALL and wait for the matching CALLRESULT payload
+    pub async fn call(
+        &self,
+        action: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, OcppError> {
+        let _in_flight = self.call_lock.lock().await;
+
+        if *self.state.read().await != ConnectionState::Connected {
+            return Err(OcppError::NotConnected);
+        }
+
+        let call = Call::new(action, payload);
+        let message_id = call.message_id.clone();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(message_id.clone(), tx);
+
+        if self
+            .outbound
+            .send(Outbound::Frame(OcppMessage::Call(call).to_json()))
+            .await
+            .is_err()
+        {
+            self.pending.lock().await.remove(&message_id);
+            return Err(OcppError::NotConnected);
+        }
+
+        match tokio::time::timeout(self.config.request_timeout, rx).await {
+            Ok(Ok(Ok(payload))) => Ok(payload),
+            Ok(Ok(Err(error))) => Err(OcppError::CallError {
+                action: action.to_string(),
+                code: error.error_code,
+                description: error.error_description,
+            }),
+            Ok(Err(_)) => Err(OcppError::ConnectionClosed(action.to_string())),
+            Err(_) => {
+                // Late responses for this ID are dropped by the connection task
+                self.pending.lock().await.remove(&message_id);
+                Err(OcppError::Timeout {
+                    action: action.to_string(),
+                    timeout_ms: self.config.request_timeout.as_millis() as u64,
+                })
+            }
+        }
+    }
+
+    /// Send a typed CALL and decode the typed response
+    pub async fn call_typed<Req, Resp>(&self, action: &str, request: &Req) -> Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let payload = serde_json::to_value(request)
+            .with_context(|| format!("Failed to encode {} request", action))?;
+        let response = self.call(action, payload).await?;
+        serde_json::from_value(response)
+            .with_context(|| format!("Failed to decode {} response", action))
+    }
+
+    /// Answer an incoming CALL with a CALLRESULT
+    pub async fn respond(&self, message_id: &str, payload: serde_json::Value) -> Result<()> {
+        let frame = OcppMessage::CallResult(CallResult::new(message_id, payload));
+        self.send_frame(frame).await
+    }
+
+    /// Answer an incoming CALL with a CALLERROR
+    pub async fn respond_error(
+        &self,
+        message_id: &str,
+        code: ErrorCode,
+        description: &str,
+    ) -> Result<()> {
+        let frame = OcppMessage::CallError(CallError::new(message_id, code, description));
+        self.send_frame(frame).await
+    }
+
+    /// Close the connection and stop reconnecting
+    pub async fn close(&self) {
+        let _ = self.outbound.send(Outbound::Close).await;
+    }
+
+    async fn send_frame(&self, frame: OcppMessage) -> Result<()> {
+        self.outbound
+            .send(Outbound::Frame(frame.to_json()))
+            .await
+            .map_err(|_| OcppError::NotConnected.into())
+    }
+}
+
+/// Open a WebSocket connection requesting the OCPP 1.6 subprotocol
+async fn open(url: &str, config: &TransportConfig) -> Result<WsStream> {
+    let mut request = url
+        .into_client_request()
+        .with_context(|| format!("Invalid OCPP endpoint URL: {}", url))?;
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static(super::OCPP16_SUBPROTOCOL),
+    );
+    if let Some((username, password)) = &config.basic_auth {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        request.headers_mut().insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Basic {}", credentials))
+                .context("Invalid OCPP credentials")?,
+        );
+    }
+
+    let (ws, response) = tokio::time::timeout(
+        config.connect_timeout,
+        tokio_tungstenite::connect_async(request),
+    )
+    .await
+    .with_context(|| format!("Timed out connecting to {}", url))?
+    .with_context(|| format!("WebSocket handshake with {} failed", url))?;
+
+    let accepted = response
+        .headers()
+        .get("Sec-WebSocket-Protocol")
+        .and_then(|v| v.to_str().ok());
+    if accepted != Some(super::OCPP16_SUBPROTOCOL) {
+        tracing::warn!(
+            "OCPP peer at {} did not confirm the {} subprotocol (got {:?})",
+            url,
+            super::OCPP16_SUBPROTOCOL,
+            accepted
+        );
+    }
+
+    Ok(ws)
+}
+
+enum PumpExit {
+    Shutdown,
+    Disconnected,
+}
+
+async fn connection_task(
+    url: String,
+    config: TransportConfig,
+    mut ws: WsStream,
+    mut outbound_rx: mpsc::Receiver<Outbound>,
+    incoming_tx: mpsc::Sender<Call>,
+    pending: PendingMap,
+    state: Arc<RwLock<ConnectionState>>,
+) {
+    loop {
+        let exit = pump(&mut ws, &mut outbound_rx, &incoming_tx, &pending).await;
+
+        // Fail every CALL still waiting on this connection
+        pending.lock().await.clear();
+
+        if matches!(exit, PumpExit::Shutdown) || !config.reconnect {
+            *state.write().await = ConnectionState::Disconnected;
+            tracing::info!("OCPP-J transport to {} closed", url);
+            return;
+        }
+
+        *state.write().await = ConnectionState::Connecting;
+        tracing::warn!("OCPP-J connection to {} lost, reconnecting", url);
+
+        let mut attempt: u32 = 0;
+        ws = loop {
+            // Exponential backoff: 1s, 2s, 4s, 8s, 16s (capped)
+            let delay = RECONNECT_BASE_DELAY
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(RECONNECT_MAX_DELAY);
+
+            let sleep = tokio::time::sleep(delay);
+            tokio::pin!(sleep);
+            loop {
+                tokio::select! {
+                    _ = &mut sleep => break,
+                    cmd = outbound_rx.recv() => match cmd {
+                        // Frames queued while offline are stale once we reconnect
+                        Some(Outbound::Frame(_)) => {}
+                        Some(Outbound::Close) | None => {
+                            *state.write().await = ConnectionState::Disconnected;
+                            return;
+                        }
+                    },
+                }
+            }
+
+            match open(&url, &config).await {
+                Ok(ws) => break ws,
+                Err(e) => {
+                    attempt = attempt.saturating_add(1);
+                    tracing::warn!("OCPP-J reconnect attempt {} failed: {:#}", attempt, e);
+                }
+            }
+        };
+
+        *state.write().await = ConnectionState::Connected;
+        tracing::info!("OCPP-J transport reconnected to {}", url);
+    }
+}
+
+/// Shuttle frames between the socket and the transport handle until the
+/// connection drops or the transport is closed
+async fn pump(
+    ws: &mut WsStream,
+    outbound_rx: &mut mpsc::Receiver<Outbound>,
+    incoming_tx: &mpsc::Sender<Call>,
+    pending: &PendingMap,
+) -> PumpExit {
+    loop {
+        tokio::select! {
+            cmd = outbound_rx.recv() => match cmd {
+                Some(Outbound::Frame(text)) => {
+                    if let Err(e) = ws.send(Message::Text(text)).await {
+                        tracing::warn!("OCPP-J send failed: {}", e);
+                        return PumpExit::Disconnected;
+                    }
+                }
+                Some(Outbound::Close) | None => {
+                    let _ = ws.close(None).await;
+                    return PumpExit::Shutdown;
+                }
+            },
+            msg = ws.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    if let Some(reply) = dispatch(&text, incoming_tx, pending).await {
+                        if let Err(e) = ws.send(Message::Text(reply.to_json())).await {
+                            tracing::warn!("OCPP-J send failed: {}", e);
+                            return PumpExit::Disconnected;
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => return PumpExit::Disconnected,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    tracing::warn!("OCPP-J receive failed: {}", e);
+                    return PumpExit::Disconnected;
+                }
+            },
+        }
+    }
+}
+
+/// Route an incoming frame, returning an immediate reply if one is required
+async fn dispatch(
+    text: &str,
+    incoming_tx: &mpsc::Sender<Call>,
+    pending: &PendingMap,
+) -> Option<OcppMessage> {
+    let frame = match OcppMessage::parse(text) {
+        Ok(frame) => frame,
+        Err(e) => {
+            tracing::warn!("Dropping OCPP-J frame: {}", e);
+            return None;
+        }
+    };
+
+    match frame {
+        OcppMessage::CallResult(result) => {
+            match pending.lock().await.remove(&result.message_id) {
+                Some(tx) => {
+                    let _ = tx.send(Ok(result.payload));
+                }
+                None => tracing::debug!("Ignoring CALLRESULT for unknown id {}", result.message_id),
+            }
+            None
+        }
+        OcppMessage::CallError(error) => {
+            match pending.lock().await.remove(&error.message_id) {
+                Some(tx) => {
+                    let _ = tx.send(Err(error));
+                }
+                None => tracing::debug!("Ignoring CALLERROR for unknown id {}", error.message_id),
+            }
+            None
+        }
+        OcppMessage::Call(call) => {
+            let message_id = call.message_id.clone();
+            let action = call.action.clone();
+            match incoming_tx.try_send(call) {
+                Ok(()) => None,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    Some(OcppMessage::CallError(CallError::new(
+                        message_id,
+                        ErrorCode::InternalError,
+                        "Too many pending requests",
+                    )))
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    Some(OcppMessage::CallError(CallError::new(
+                        message_id,
+                        ErrorCode::NotImplemented,
+                        format!("No handler for {}", action),
+                    )))
+                }
+            }
+        }
+    }
+}
+
+/// Local OCPP-J peer used to exercise the transport in tests
+#[cfg(test)]
+pub(crate) mod stand_in {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+    /// Accepted connection to the stand-in peer
+    pub(crate) type PeerSocket = WebSocketStream<TcpStream>;
+
+    /// Listen on an ephemeral port and hand each accepted socket to `handler`
+    ///
+    /// Returns the `ws://` base URL of the listener.
+    pub(crate) async fn spawn<F, Fut>(handler: F) -> String
+    where
+        F: Fn(PeerSocket) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handler = Arc::new(handler);
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let negotiate = |_: &Request, mut response: Response| {
+                        response.headers_mut().insert(
+                            "Sec-WebSocket-Protocol",
+                            HeaderValue::from_static(crate::ocpp::OCPP16_SUBPROTOCOL),
+                        );
+                        Ok(response)
+                    };
+                    if let Ok(ws) = tokio_tungstenite::accept_hdr_async(stream, negotiate).await {
+                        handler(ws).await;
+                    }
+                });
+            }
+        });
+
+        format!("ws://{}", addr)
+    }
+
+    /// Answer every CALL on the socket using `respond`
+    pub(crate) async fn serve_calls<F>(mut ws: PeerSocket, respond: F)
+    where
+        F: Fn(&Call) -> OcppMessage,
+    {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Text(text) = msg {
+                if let Ok(OcppMessage::Call(call)) = OcppMessage::parse(&text) {
+                    let reply = respond(&call);
+                    if ws.send(Message::Text(reply.to_json())).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn fast_config() -> TransportConfig {
+        TransportConfig {
+            request_timeout: Duration::from_millis(300),
+            connect_timeout: Duration::from_secs(2),
+            reconnect: true,
+            basic_auth: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_correlates_result() {
+        let url = stand_in::spawn(|ws| {
+            stand_in::serve_calls(ws, |call| {
+                OcppMessage::CallResult(CallResult::new(
+                    call.message_id.clone(),
+                    serde_json::json!({ "echo": call.action }),
+                ))
+            })
+        })
+        .await;
+
+        let (transport, _incoming) = OcppTransport::connect(&url, fast_config()).await.unwrap();
+        let response = transport
+            .call("Heartbeat", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(response["echo"], "Heartbeat");
+    }
+
+    #[tokio::test]
+    async fn test_call_error_is_surfaced() {
+        let url = stand_in::spawn(|ws| {
+            stand_in::serve_calls(ws, |call| {
+                OcppMessage::CallError(CallError::new(
+                    call.message_id.clone(),
+                    ErrorCode::NotSupported,
+                    "nope",
+                ))
+            })
+        })
+        .await;
+
+        let (transport, _incoming) = OcppTransport::connect(&url, fast_config()).await.unwrap();
+        let err = transport
+            .call("DataTransfer", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OcppError::CallError { ref code, .. } if code == "NotSupported"));
+    }
+
+    #[tokio::test]
+    async fn test_call_times_out_without_response() {
+        let url = stand_in::spawn(|mut ws| async move {
+            // Swallow every frame without answering
+            while let Some(Ok(_)) = ws.next().await {}
+        })
+        .await;
+
+        let (transport, _incoming) = OcppTransport::connect(&url, fast_config()).await.unwrap();
+        let err = transport
+            .call("Heartbeat", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OcppError::Timeout { .. }));
+        assert!(transport.pending.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_incoming_call_is_forwarded_and_answered() {
+        let (answer_tx, answer_rx) = oneshot::channel::<String>();
+        let answer_tx = Arc::new(Mutex::new(Some(answer_tx)));
+
+        let url = stand_in::spawn(move |mut ws| {
+            let answer_tx = answer_tx.clone();
+            async move {
+                let call = Call::new(
+                    "RemoteStopTransaction",
+                    serde_json::json!({ "transactionId": 7 }),
+                );
+                ws.send(Message::Text(OcppMessage::Call(call).to_json()))
+                    .await
+                    .unwrap();
+                if let Some(Ok(Message::Text(text))) = ws.next().await {
+                    if let Some(tx) = answer_tx.lock().await.take() {
+                        let _ = tx.send(text);
+                    }
+                }
+            }
+        })
+        .await;
+
+        let (transport, mut incoming) = OcppTransport::connect(&url, fast_config()).await.unwrap();
+        let call = incoming.recv().await.unwrap();
+        assert_eq!(call.action, "RemoteStopTransaction");
+        transport
+            .respond(
+                &call.message_id,
+                serde_json::json!({ "status": "Accepted" }),
+            )
+            .await
+            .unwrap();
+
+        let answer = answer_rx.await.unwrap();
+        match OcppMessage::parse(&answer).unwrap() {
+            OcppMessage::CallResult(result) => {
+                assert_eq!(result.message_id, call.message_id);
+                assert_eq!(result.payload["status"], "Accepted");
+            }
+            other => panic!("expected CALLRESULT, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_after_peer_drops_connection() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+
+        let url = stand_in::spawn(move |ws| {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt == 0 {
+                    // Drop the first connection immediately
+                    drop(ws);
+                    return;
+                }
+                stand_in::serve_calls(ws, |call| {
+                    OcppMessage::CallResult(CallResult::new(
+                        call.message_id.clone(),
+                        serde_json::json!({}),
+                    ))
+                })
+                .await;
+            }
+        })
+        .await;
+
+        let (transport, _incoming) = OcppTransport::connect(&url, fast_config()).await.unwrap();
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            if connections.load(Ordering::SeqCst) >= 2
+                && transport.state().await == ConnectionState::Connected
+            {
+                break;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "transport never reconnected"
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        transport
+            .call("Heartbeat", serde_json::json!({}))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_call_after_close_fails() {
+        let url = stand_in::spawn(|ws| {
+            stand_in::serve_calls(ws, |call| {
+                OcppMessage::CallResult(CallResult::new(
+                    call.message_id.clone(),
+                    serde_json::json!({}),
+                ))
+            })
+        })
+        .await;
+
+        let (transport, _incoming) = OcppTransport::connect(&url, fast_config()).await.unwrap();
+        transport.close().await;
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+        while transport.state().await != ConnectionState::Disconnected {
+            assert!(tokio::time::Instant::now() < deadline);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(matches!(
+            transport.call("Heartbeat", serde_json::json!({})).await,
+            Err(OcppError::NotConnected)
+        ));
+    }
+}
@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 use serde::{Deserialize, Serialize};
 
+use chrono::{DateTime, Utc};
+
+use crate::domain::{LinearizedWear, PeakTariffRules, ReserveOffer};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Constraints {
     pub min_soc_percent: f64,
@@ -33,6 +37,13 @@ pub struct Constraints {
     pub battery_degradation_per_cycle: f64,
     pub battery_replacement_cost_sek: f64,
 
+    /// Wear costs from the battery's degradation model
+    ///
+    /// When unset, throughput pays a flat
+    /// `battery_degradation_per_cycle * battery_replacement_cost_sek / battery_capacity_kwh`.
+    #[serde(default)]
+    pub battery_wear: Option<LinearizedWear>,
+
     /// CRITICAL: Swedish "Effekttariff" (Peak Power Tariff)
     /// Grid operators (Ellevio, Vattenfall, E.ON) charge based on monthly peak hourly average power
     /// Typical: 50-120 SEK/kW/month
@@ -41,6 +52,81 @@ pub struct Constraints {
     /// If disabled (0.0), optimizer ignores peak power and may create expensive spikes.
     /// If enabled (typical 100 SEK/kW), optimizer will flatten load profile to avoid peaks.
     pub peak_power_tariff_sek_per_kw: f64,
+
+    /// Weighted hourly peak already on this month's bill (kW)
+    ///
+    /// Filled from the peak ledger before each run; only peaks above this
+    /// level add to the tariff cost.
+    #[serde(default)]
+    pub peak_power_incurred_kw: f64,
+
+    /// Which hours count towards the peak and how many peaks are averaged
+    #[serde(default)]
+    pub peak_tariff: PeakTariffRules,
+
+    /// Maximum power the site may feed into the grid (kW)
+    #[serde(default = "default_max_export_grid_kw")]
+    pub max_export_grid_kw: f64,
+
+    /// Fee the grid operator deducts from the spot price for exported energy
+    ///
+    /// Used for periods without an explicit export price. When unset, the
+    /// price point's default export price applies.
+    #[serde(default)]
+    pub export_fee_sek_per_kwh: Option<f64>,
+
+    /// Let the optimizer plan PV curtailment, e.g. when export prices go negative
+    ///
+    /// Only enable this when the inverter accepts a production limit. Without
+    /// it PV is curtailed only when nothing else can absorb it.
+    #[serde(default)]
+    pub allow_pv_curtailment: bool,
+
+    /// Store surplus PV rather than export it when the two are worth the same
+    #[serde(default = "default_prefer_self_consumption")]
+    pub prefer_self_consumption: bool,
+
+    /// Frequency reserve capacity sold for committed hours, which the
+    /// schedule keeps power and energy headroom for
+    #[serde(default)]
+    pub reserve: ReserveOffer,
+
+    /// SoC kept in the battery for a grid outage (storm reserve)
+    ///
+    /// Raises the floor of every period's SoC range; falling short of it
+    /// costs the reserve shortfall penalty, so a battery below the reserve
+    /// is charged up rather than making the plan infeasible.
+    #[serde(default)]
+    pub backup_reserve_soc_percent: f64,
+}
+
+/// Cost of each SoC percentage point outside a committed hour's range
+///
+/// High enough that no price spread makes breaking a reserve commitment pay.
+pub const RESERVE_SHORTFALL_PENALTY_SEK_PER_PERCENT: f64 = 100.0;
+
+/// Battery power and SoC range a schedule may use in a period
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryLimits {
+    pub max_charge_kw: f64,
+    pub max_discharge_kw: f64,
+    pub min_soc_percent: f64,
+    pub max_soc_percent: f64,
+}
+
+impl BatteryLimits {
+    /// Percentage points `soc_percent` lies outside the SoC range
+    pub fn soc_shortfall_percent(&self, soc_percent: f64) -> f64 {
+        (self.min_soc_percent - soc_percent).max(0.0) + (soc_percent - self.max_soc_percent).max(0.0)
+    }
+}
+
+fn default_max_export_grid_kw() -> f64 {
+    11.0
+}
+
+fn default_prefer_self_consumption() -> bool {
+    true
 }
 
 impl Default for Constraints {
@@ -57,9 +143,81 @@ impl Default for Constraints {
             battery_efficiency: 0.95,
             battery_degradation_per_cycle: 0.0001,
             battery_replacement_cost_sek: 50000.0,
+            battery_wear: None,
             // Enable peak power tariff by default (100 SEK/kW is typical for Swedish grid)
             // Set to 0.0 to disable if not applicable
             peak_power_tariff_sek_per_kw: 100.0,
+            peak_power_incurred_kw: 0.0,
+            peak_tariff: PeakTariffRules::default(),
+            max_export_grid_kw: default_max_export_grid_kw(),
+            export_fee_sek_per_kwh: None,
+            allow_pv_curtailment: false,
+            prefer_self_consumption: default_prefer_self_consumption(),
+            reserve: ReserveOffer::default(),
+            backup_reserve_soc_percent: 0.0,
+        }
+    }
+}
+
+impl Constraints {
+    /// Battery limits for a period starting at `time_start`
+    ///
+    /// In hours with a reserve commitment the sold power is kept free in both
+    /// directions, and the SoC range narrows so that full activation can be
+    /// sustained for the required endurance. The outage reserve raises the
+    /// floor in every period.
+    pub fn battery_limits_at(&self, time_start: DateTime<Utc>) -> BatteryLimits {
+        let limits = BatteryLimits {
+            max_charge_kw: self.battery_max_charge_kw,
+            max_discharge_kw: self.battery_max_discharge_kw,
+            min_soc_percent: self
+                .min_soc_percent
+                .max(self.backup_reserve_soc_percent)
+                .min(self.max_soc_percent),
+            max_soc_percent: self.max_soc_percent,
+        };
+        let Some(commitment) = self.reserve.commitment_at(time_start) else {
+            return limits;
+        };
+
+        let capacity_kwh = self.battery_capacity_kwh.max(0.1);
+        let efficiency = self.battery_efficiency.clamp(0.5, 1.0);
+        let min_soc_percent = (limits.min_soc_percent
+            + commitment.up_energy_kwh() / efficiency / capacity_kwh * 100.0)
+            .min(limits.max_soc_percent);
+        let max_soc_percent = (limits.max_soc_percent
+            - commitment.down_energy_kwh() * efficiency / capacity_kwh * 100.0)
+            .max(min_soc_percent);
+        BatteryLimits {
+            max_charge_kw: (limits.max_charge_kw - commitment.fcr_d_down_kw).max(0.0),
+            max_discharge_kw: (limits.max_discharge_kw - commitment.up_kw()).max(0.0),
+            min_soc_percent,
+            max_soc_percent,
         }
     }
+
+    /// Whether a period's SoC range is narrower than the plain SoC window,
+    /// from a reserve commitment or the outage reserve
+    pub fn has_soc_band_at(&self, time_start: DateTime<Utc>) -> bool {
+        self.backup_reserve_soc_percent > self.min_soc_percent
+            || self.reserve.commitment_at(time_start).is_some()
+    }
+
+    /// Wear costs the strategies charge for running the battery
+    pub fn wear(&self) -> LinearizedWear {
+        self.battery_wear.unwrap_or_else(|| {
+            LinearizedWear::flat(
+                self.battery_degradation_per_cycle * self.battery_replacement_cost_sek
+                    / self.battery_capacity_kwh.max(0.1),
+            )
+        })
+    }
+
+    /// Bill increase per kW a new hourly peak rises above `peak_power_incurred_kw`
+    ///
+    /// A new peak replaces the lowest of the top-N, so the averaged billed
+    /// peak moves by a 1/N share of the increase.
+    pub fn marginal_peak_cost_sek_per_kw(&self) -> f64 {
+        self.peak_power_tariff_sek_per_kw / self.peak_tariff.top_n.max(1) as f64
+    }
 }
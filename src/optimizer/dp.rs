@@ -1,11 +1,16 @@
 #![allow(dead_code)]
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DurationRound, Utc};
+use std::ops::Range;
 use uuid::Uuid;
 
-use super::{Action, Constraints, OptimizationStrategy, SystemState};
-use crate::domain::{Forecast24h, Schedule, ScheduleEntry};
+use super::ev::{cheapest_charging_plan, ev_targets};
+use super::{
+    Action, Constraints, OptimizationStrategy, SystemState,
+    RESERVE_SHORTFALL_PENALTY_SEK_PER_PERCENT,
+};
+use crate::domain::{max_horizon, Forecast24h, Schedule, ScheduleEntry};
 
 pub struct DynamicProgrammingOptimizer;
 
@@ -18,7 +23,8 @@ impl OptimizationStrategy for DynamicProgrammingOptimizer {
         constraints: &Constraints,
     ) -> Result<Schedule> {
         let now = Utc::now();
-        let n = forecast.prices.len().min(24);
+        // One step per price period, whatever its length, up to 48 h
+        let n = forecast.periods_within(max_horizon());
         if n == 0 {
             anyhow::bail!("no price points available");
         }
@@ -34,54 +40,170 @@ impl OptimizationStrategy for DynamicProgrammingOptimizer {
             }
         }
 
+        // Vehicles are planned first, the battery then works around their load
+        let ev_plans: Vec<Vec<f64>> = state
+            .evs
+            .iter()
+            .map(|request| cheapest_charging_plan(forecast, n, request))
+            .collect();
+
         let soc0 = bucket(state.battery.soc_percent);
         // Use 51 states (0-100% in 2% increments) for better granularity
         const NUM_SOC_STATES: usize = 51;
-        let mut dp = vec![vec![f64::INFINITY; NUM_SOC_STATES]; n + 1];
-        let mut prev = vec![vec![None; NUM_SOC_STATES]; n + 1];
-        dp[0][soc0] = 0.0;
-
-        for t in 0..n {
-            for soc in 0..NUM_SOC_STATES {
-                let cur = dp[t][soc];
-                if !cur.is_finite() {
-                    continue;
+        // The horizon's highest weighted hourly mean import is part of the
+        // state, since a peak tariff charges it once, not once per interval
+        let tariff = constraints.peak_power_tariff_sek_per_kw > 0.0;
+        let peak_levels = if tariff { NUM_PEAK_LEVELS } else { 1 };
+        // So is the mean the current clock hour has reached, which shorter
+        // periods add to one after another
+        let mean_levels = if tariff {
+            (constraints.peak_power_incurred_kw.max(0.0) / PEAK_STEP_KW).ceil() as usize
+                + NUM_PEAK_LEVELS
+        } else {
+            1
+        };
+        let peak_cost_per_kw = constraints.marginal_peak_cost_sek_per_kw();
+        let hours = clock_hours(forecast, n);
+        let boundary_states = NUM_SOC_STATES * peak_levels;
+
+        // Cost of each (SoC, peak) state at an hour boundary, and per hour how
+        // each state at its end was reached
+        let mut at_boundary = vec![f64::INFINITY; boundary_states];
+        at_boundary[soc0 * peak_levels] = 0.0;
+        let mut came_from: Vec<Vec<Option<(usize, u128)>>> = Vec::with_capacity(hours.len());
+
+        for hour in &hours {
+            if hour.len() > MAX_PERIODS_PER_HOUR {
+                anyhow::bail!(
+                    "{} price periods start at {}, at most {} per hour are supported",
+                    hour.len(),
+                    forecast.prices[hour.start].time_start,
+                    MAX_PERIODS_PER_HOUR
+                );
+            }
+            let hour_h: f64 = hour.clone().map(|t| period_hours(forecast, t)).sum();
+
+            // Paths through the hour by (SoC, peak, hourly mean so far)
+            let mut paths = vec![HourPath::UNREACHED; boundary_states * mean_levels];
+            for (start, &cost) in at_boundary.iter().enumerate() {
+                if cost.is_finite() {
+                    paths[start * mean_levels] = HourPath {
+                        cost,
+                        start,
+                        ..HourPath::UNREACHED
+                    };
                 }
-                for action in [Action::Charge, Action::Discharge, Action::Idle] {
-                    let (next_soc, cost, target_power_w) =
-                        simulate_action(soc, action, forecast, t, constraints)?;
-                    let new_cost = cur + cost;
-                    if new_cost < dp[t + 1][next_soc] {
-                        dp[t + 1][next_soc] = new_cost;
-                        prev[t + 1][next_soc] = Some((soc, action, target_power_w));
+            }
+
+            for (step, t) in hour.clone().enumerate() {
+                let net_load_kw =
+                    net_load_kw(forecast, t) + ev_plans.iter().map(|plan| plan[t]).sum::<f64>();
+                // Share of the hourly mean this period's import makes up, as
+                // in the MILP
+                let peak_weight = constraints
+                    .peak_tariff
+                    .weight(forecast.prices[t].time_start);
+                let mean_share = if hour_h > 0.0 {
+                    peak_weight * period_hours(forecast, t) / hour_h
+                } else {
+                    0.0
+                };
+
+                let mut next = vec![HourPath::UNREACHED; paths.len()];
+                let per_soc = peak_levels * mean_levels;
+                for soc in 0..NUM_SOC_STATES {
+                    let states = soc * per_soc..(soc + 1) * per_soc;
+                    if paths[states.clone()].iter().all(|p| !p.cost.is_finite()) {
+                        continue;
                     }
+                    let mut outcomes = Vec::with_capacity(ACTIONS.len());
+                    for action in ACTIONS {
+                        outcomes.push(simulate_action(soc, action, forecast, t, constraints)?);
+                    }
+                    for index in states {
+                        let path = paths[index];
+                        if !path.cost.is_finite() {
+                            continue;
+                        }
+                        let peak = index / mean_levels % peak_levels;
+                        for (code, &(next_soc, cost, target_power_w)) in outcomes.iter().enumerate()
+                        {
+                            let import_kw = (net_load_kw + target_power_w / 1000.0).max(0.0);
+                            let mean_kw = path.mean_kw + mean_share * import_kw;
+                            let mean = mean_level(mean_kw, mean_levels);
+                            let slot =
+                                &mut next[(next_soc * peak_levels + peak) * mean_levels + mean];
+                            let new_cost = path.cost + cost;
+                            if new_cost < slot.cost {
+                                *slot = HourPath {
+                                    cost: new_cost,
+                                    mean_kw,
+                                    start: path.start,
+                                    actions: path.actions | (code as u128) << (2 * step),
+                                };
+                            }
+                        }
+                    }
+                }
+                paths = next;
+            }
+
+            // The hour's mean is final now. Only its rise above the peak
+            // already paid for costs extra
+            at_boundary = vec![f64::INFINITY; boundary_states];
+            let mut reached = vec![None; boundary_states];
+            for (index, path) in paths.iter().enumerate() {
+                if !path.cost.is_finite() {
+                    continue;
+                }
+                let soc = index / mean_levels / peak_levels;
+                let peak = index / mean_levels % peak_levels;
+                let next_peak = peak_level(path.mean_kw, constraints.peak_power_incurred_kw)
+                    .clamp(peak, peak_levels - 1);
+                let peak_cost = (next_peak - peak) as f64 * PEAK_STEP_KW * peak_cost_per_kw;
+                let end = soc * peak_levels + next_peak;
+                if path.cost + peak_cost < at_boundary[end] {
+                    at_boundary[end] = path.cost + peak_cost;
+                    reached[end] = Some((path.start, path.actions));
                 }
             }
+            came_from.push(reached);
         }
 
-        let (mut best_soc, _) = dp[n]
+        let (mut best, _) = at_boundary
             .iter()
             .enumerate()
             .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
-            .map(|(i, v)| (i, *v))
             .unwrap();
 
+        let mut hour_plans = Vec::with_capacity(hours.len());
+        for reached in came_from.iter().rev() {
+            let (start, actions) =
+                reached[best].ok_or_else(|| anyhow::anyhow!("backtrack failed"))?;
+            hour_plans.push((start / peak_levels, actions));
+            best = start;
+        }
+        hour_plans.reverse();
+
+        // Replay each hour's actions from the SoC it started at
         let mut entries = Vec::with_capacity(n);
-        for t in (1..=n).rev() {
-            let (psoc, action, target_power_w) = prev[t][best_soc]
-                .clone()
-                .ok_or_else(|| anyhow::anyhow!("backtrack failed"))?;
-            let p = &forecast.prices[t - 1];
-            entries.push(ScheduleEntry {
-                time_start: p.time_start,
-                time_end: p.time_end,
-                target_power_w,
-                price_sek_per_kwh: p.price_sek_per_kwh,
-                reason: format!("dp:{:?}", action),
-            });
-            best_soc = psoc;
+        for (hour, (mut soc, actions)) in hours.iter().zip(hour_plans) {
+            for (step, t) in hour.clone().enumerate() {
+                let action = ACTIONS[(actions >> (2 * step) & 0b11) as usize];
+                let (next_soc, _, target_power_w) =
+                    simulate_action(soc, action, forecast, t, constraints)?;
+                let p = &forecast.prices[t];
+                entries.push(ScheduleEntry {
+                    time_start: p.time_start,
+                    time_end: p.time_end,
+                    target_power_w,
+                    price_sek_per_kwh: p.price_sek_per_kwh,
+                    reason: format!("dp:{:?}", action),
+                    ev_targets: ev_targets(&state.evs, &ev_plans, t),
+                });
+                soc = next_soc;
+            }
         }
-        entries.reverse();
 
         let valid_from = entries.first().map(|e| e.time_start).unwrap_or(now);
         let valid_until = entries.last().map(|e| e.time_end).unwrap_or(now);
@@ -93,6 +215,7 @@ impl OptimizationStrategy for DynamicProgrammingOptimizer {
             valid_until,
             entries,
             optimizer_version: "dp-skeleton-0.1".to_string(),
+            cost_distribution: None,
         })
     }
 }
@@ -105,6 +228,87 @@ fn bucket(soc_percent: f64) -> usize {
     b.clamp(0, 50) as usize
 }
 
+/// Actions the DP chooses between, indexed by the two bits per period
+/// `HourPath::actions` keeps
+const ACTIONS: [Action; 3] = [Action::Charge, Action::Discharge, Action::Idle];
+/// Periods one clock hour can hold before its actions no longer fit a `u128`
+const MAX_PERIODS_PER_HOUR: usize = 64;
+
+/// Cheapest path found through the current clock hour to one DP state
+#[derive(Clone, Copy)]
+struct HourPath {
+    cost: f64,
+    /// Weighted mean import the hour has reached so far (kW)
+    mean_kw: f64,
+    /// (SoC, peak) state the hour started in
+    start: usize,
+    /// Action per period of the hour, two bits each
+    actions: u128,
+}
+
+impl HourPath {
+    const UNREACHED: Self = Self {
+        cost: f64::INFINITY,
+        mean_kw: 0.0,
+        start: 0,
+        actions: 0,
+    };
+}
+
+/// Resolution of the tracked peak above the incurred one (kW)
+const PEAK_STEP_KW: f64 = 0.5;
+/// Peak levels tracked, covering up to 20 kW above the incurred peak
+const NUM_PEAK_LEVELS: usize = 41;
+
+/// Peak level (steps above `incurred_kw`) a weighted import reaches, rounded up
+fn peak_level(weighted_import_kw: f64, incurred_kw: f64) -> usize {
+    ((weighted_import_kw - incurred_kw.max(0.0)) / PEAK_STEP_KW)
+        .ceil()
+        .clamp(0.0, (NUM_PEAK_LEVELS - 1) as f64) as usize
+}
+
+/// Hourly mean level (steps from zero) of a weighted import, rounded up
+fn mean_level(mean_kw: f64, levels: usize) -> usize {
+    ((mean_kw / PEAK_STEP_KW).ceil().max(0.0) as usize).min(levels - 1)
+}
+
+/// Consecutive price periods grouped by the clock hour they start in, since
+/// the tariff counts the mean power of each hour
+fn clock_hours(forecast: &Forecast24h, n: usize) -> Vec<Range<usize>> {
+    let hour_of = |t: usize| {
+        forecast.prices[t]
+            .time_start
+            .duration_trunc(chrono::Duration::hours(1))
+            .ok()
+    };
+    let mut hours: Vec<Range<usize>> = Vec::new();
+    for t in 0..n {
+        match hours.last_mut() {
+            Some(hour) if hour_of(t) == hour_of(t - 1) => hour.end = t + 1,
+            _ => hours.push(t..t + 1),
+        }
+    }
+    hours
+}
+
+/// Length of price period `t` in hours
+fn period_hours(forecast: &Forecast24h, t: usize) -> f64 {
+    let period = &forecast.prices[t];
+    (period.time_end - period.time_start).num_seconds() as f64 / 3600.0
+}
+
+/// House load minus PV over price period `t`, zero when either forecast is missing
+fn net_load_kw(forecast: &Forecast24h, t: usize) -> f64 {
+    let period = &forecast.prices[t];
+    let load = forecast
+        .load_kw_between(period.time_start, period.time_end)
+        .unwrap_or(0.0);
+    let pv = forecast
+        .pv_kw_between(period.time_start, period.time_end)
+        .unwrap_or(0.0);
+    load - pv
+}
+
 fn simulate_action(
     soc_bucket: usize,
     action: Action,
@@ -116,18 +320,12 @@ fn simulate_action(
     let import_price = price_point.price_sek_per_kwh;
     let export_price = price_point.export_price();
 
-    // Calculate cycle penalty from battery degradation and replacement cost
-    // cycle_penalty = degradation_per_cycle * replacement_cost (SEK)
-    // This represents the economic cost of wearing out the battery
-    // Example: LiFePO4 with 0.0001 degradation/cycle and 50,000 SEK cost
-    // = 0.0001 * 50000 = 5 SEK per full cycle
-    let cycle_penalty_per_full_cycle =
-        constraints.battery_degradation_per_cycle * constraints.battery_replacement_cost_sek;
-
     // Use battery physical limits (NOT grid limits!)
     // The constraint is min(grid_limit, battery_limit)
-    let battery_max_charge_w = constraints.battery_max_charge_kw.max(0.1) * 1000.0;
-    let battery_max_discharge_w = constraints.battery_max_discharge_kw.max(0.1) * 1000.0;
+    // Power sold as frequency reserve is not available to the schedule
+    let limits = constraints.battery_limits_at(price_point.time_start);
+    let battery_max_charge_w = limits.max_charge_kw * 1000.0;
+    let battery_max_discharge_w = limits.max_discharge_kw * 1000.0;
     let grid_max_w = constraints.max_power_grid_kw.max(0.1) * 1000.0;
 
     let max_charge_w = battery_max_charge_w.min(grid_max_w);
@@ -145,7 +343,7 @@ fn simulate_action(
 
     // CRITICAL FIX: Calculate actual time step duration from forecast (don't assume 1 hour!)
     // Forecast resolution varies: 15min, 30min, or 60min depending on provider
-    let dt_hours = (price_point.time_end - price_point.time_start).num_seconds() as f64 / 3600.0;
+    let dt_hours = period_hours(forecast, t);
     // Validate time step is reasonable (between 1 minute and 4 hours)
     if dt_hours <= 0.0 || dt_hours > 4.0 {
         anyhow::bail!(
@@ -188,10 +386,17 @@ fn simulate_action(
     let max_b = bucket(constraints.max_soc_percent);
     next = next.clamp(min_b as i64, max_b as i64);
 
-    // Cost calculation: energy cost + cycle degradation penalty (proportional to throughput)
-    // CRITICAL FIX: Cycle penalty must be proportional to energy moved
-    // A full 0-100% cycle costs cycle_penalty_per_full_cycle
-    // A partial cycle costs: (energy_throughput / capacity) * cycle_penalty_per_full_cycle
+    // A move cut short by the SoC limits only transfers part of the energy,
+    // otherwise an empty battery could keep "discharging" for free
+    let share = if buckets_to_move != 0 {
+        ((next - soc_bucket as i64) as f64 / buckets_to_move as f64).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    let energy_kwh = energy_kwh * share;
+    let target_power_w = target_power_w * share;
+
+    // Cost calculation: energy cost + battery wear
     //
     // CRITICAL FIX: Use different prices for import (charging) vs export (discharging)
     // When charging (positive energy): we pay import_price
@@ -205,11 +410,19 @@ fn simulate_action(
         energy_kwh * export_price
     };
 
-    if matches!(action, Action::Charge | Action::Discharge) {
-        // Calculate proportional cycle cost based on energy throughput
-        let cycle_fraction = energy_kwh.abs() / battery_capacity;
-        cost += cycle_fraction * cycle_penalty_per_full_cycle;
-    }
+    // A committed reserve hour must start and end with enough energy and
+    // room for full activation
+    let shortfall_percent = limits.soc_shortfall_percent(soc_bucket as f64 * 2.0)
+        + limits.soc_shortfall_percent(next as f64 * 2.0);
+    cost += shortfall_percent * RESERVE_SHORTFALL_PENALTY_SEK_PER_PERCENT;
+
+    // Wear from the degradation model: throughput, high power and the SoC
+    // the period starts at
+    cost += constraints.wear().cost_sek(
+        target_power_w / 1000.0,
+        dt_hours,
+        soc_bucket as f64 * 2.0,
+    );
 
     // Validate cost is finite to prevent undefined behavior in min_by comparisons
     if !cost.is_finite() {
@@ -224,3 +437,212 @@ fn simulate_action(
 
     Ok((next as usize, cost, target_power_w))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        BatteryState, BatteryStatus, ConsumptionPoint, PriceArea, PricePoint, ReserveOffer,
+    };
+    use chrono::{Duration, TimeZone};
+
+    /// 2 SEK/kWh day with one 0.1 SEK/kWh hour at index 3 and a 2 kW house load
+    fn one_cheap_hour_forecast() -> Forecast24h {
+        let start = Utc.with_ymd_and_hms(2025, 1, 6, 0, 0, 0).unwrap();
+        let mut forecast = Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start,
+            prices: vec![],
+            consumption: vec![],
+            production: vec![],
+        };
+        for i in 0..24 {
+            let time_start = start + Duration::hours(i);
+            let time_end = time_start + Duration::hours(1);
+            forecast.prices.push(PricePoint {
+                time_start,
+                time_end,
+                price_sek_per_kwh: if i == 3 { 0.1 } else { 2.0 },
+                export_price_sek_per_kwh: None,
+            });
+            forecast.consumption.push(ConsumptionPoint {
+                time_start,
+                time_end,
+                load_kw: 2.0,
+            });
+        }
+        forecast
+    }
+
+    fn battery_state(soc_percent: f64) -> SystemState {
+        SystemState {
+            battery: BatteryState {
+                soc_percent,
+                power_w: 0.0,
+                voltage_v: 48.0,
+                temperature_c: 25.0,
+                health_percent: 100.0,
+                status: BatteryStatus::Idle,
+            },
+            evs: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dp_only_pays_for_peak_above_incurred() {
+        let mut constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 1000.0,
+            battery_degradation_per_cycle: 0.00001,
+            ..Constraints::default()
+        };
+        let forecast = one_cheap_hour_forecast();
+
+        let fresh = DynamicProgrammingOptimizer
+            .optimize(&battery_state(20.0), &forecast, &constraints)
+            .await
+            .unwrap();
+        assert!(
+            fresh.entries[3].target_power_w <= 0.0,
+            "charging would set a new 7 kW peak"
+        );
+
+        // A 7 kW peak is already on the bill, so the cheap hour can be used
+        constraints.peak_power_incurred_kw = 7.0;
+        let incurred = DynamicProgrammingOptimizer
+            .optimize(&battery_state(20.0), &forecast, &constraints)
+            .await
+            .unwrap();
+        assert!(incurred.entries[3].target_power_w > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_dp_plans_quarter_hours_up_to_48_hours() {
+        // Quarter-hour prices for 50 h with one cheap hour, hourly load
+        let mut forecast = one_cheap_hour_forecast();
+        let start = forecast.prices[0].time_start;
+        forecast.prices = (0..200)
+            .map(|i| PricePoint {
+                time_start: start + Duration::minutes(15 * i),
+                time_end: start + Duration::minutes(15 * (i + 1)),
+                price_sek_per_kwh: if (8..12).contains(&i) { 0.1 } else { 2.0 },
+                export_price_sek_per_kwh: None,
+            })
+            .collect();
+        let constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 0.0,
+            battery_degradation_per_cycle: 0.00001,
+            ..Constraints::default()
+        };
+
+        let schedule = DynamicProgrammingOptimizer
+            .optimize(&battery_state(20.0), &forecast, &constraints)
+            .await
+            .unwrap();
+
+        assert_eq!(schedule.entries.len(), 192);
+        assert!(schedule.validate().is_ok());
+        assert_eq!(
+            schedule.entries[10].time_end - schedule.entries[10].time_start,
+            Duration::minutes(15)
+        );
+        assert!(schedule.entries[8..12].iter().all(|e| e.target_power_w > 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_dp_prices_peak_on_hourly_mean_import() {
+        // One cheap quarter-hour: charging at 5 kW in it imports 7 kW, but
+        // the hour's mean stays at 3.25 kW, below the 4 kW already incurred
+        let mut forecast = one_cheap_hour_forecast();
+        let start = forecast.prices[0].time_start;
+        forecast.prices = (0..96)
+            .map(|i| PricePoint {
+                time_start: start + Duration::minutes(15 * i),
+                time_end: start + Duration::minutes(15 * (i + 1)),
+                price_sek_per_kwh: if i == 12 { 0.1 } else { 2.0 },
+                export_price_sek_per_kwh: None,
+            })
+            .collect();
+        let constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 1000.0,
+            peak_power_incurred_kw: 4.0,
+            battery_degradation_per_cycle: 0.00001,
+            ..Constraints::default()
+        };
+
+        let schedule = DynamicProgrammingOptimizer
+            .optimize(&battery_state(20.0), &forecast, &constraints)
+            .await
+            .unwrap();
+
+        assert!(schedule.entries[12].target_power_w > 0.0);
+        for hour in schedule.entries.chunks(4) {
+            let mean_kw = hour
+                .iter()
+                .map(|e| (2.0 + e.target_power_w / 1000.0).max(0.0))
+                .sum::<f64>()
+                / 4.0;
+            assert!(mean_kw <= 4.0, "{mean_kw} kW at {}", hour[0].time_start);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dp_keeps_headroom_for_reserve_hours() {
+        // The cheap hour is sold as FCR-D down and the evening as FCR-D up
+        let constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 0.0,
+            battery_degradation_per_cycle: 0.00001,
+            reserve: ReserveOffer {
+                hours: vec![3, 5, 6],
+                fcr_d_up_kw: 3.0,
+                fcr_d_down_kw: 5.0,
+                ffr_kw: 0.0,
+                ffr_support_seconds: 0.0,
+                timezone: "UTC".to_string(),
+            },
+            ..Constraints::default()
+        };
+
+        let schedule = DynamicProgrammingOptimizer
+            .optimize(&battery_state(20.0), &one_cheap_hour_forecast(), &constraints)
+            .await
+            .unwrap();
+
+        assert_eq!(schedule.entries[3].target_power_w, 0.0);
+        assert!(schedule.entries[5..7]
+            .iter()
+            .all(|e| e.target_power_w >= -2000.0));
+    }
+
+    #[tokio::test]
+    async fn test_dp_restores_backup_reserve() {
+        // Starting below the reserve, the battery is charged back up at
+        // once and never planned below it afterwards
+        let constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 0.0,
+            battery_degradation_per_cycle: 0.00001,
+            backup_reserve_soc_percent: 60.0,
+            ..Constraints::default()
+        };
+
+        let schedule = DynamicProgrammingOptimizer
+            .optimize(&battery_state(40.0), &one_cheap_hour_forecast(), &constraints)
+            .await
+            .unwrap();
+
+        let mut soc = 40.0;
+        for entry in &schedule.entries {
+            let kw = entry.target_power_w / 1000.0;
+            let dc_kwh = if kw > 0.0 { kw * 0.95 } else { kw / 0.95 };
+            soc += dc_kwh / constraints.battery_capacity_kwh * 100.0;
+            assert!(soc >= 58.0, "SoC {soc:.1}% at {}", entry.time_start);
+        }
+    }
+
+    #[test]
+    fn test_peak_level_rounds_up_above_incurred() {
+        assert_eq!(peak_level(3.0, 5.0), 0);
+        assert_eq!(peak_level(5.2, 5.0), 1);
+        assert_eq!(peak_level(7.0, 5.0), 4);
+        assert_eq!(peak_level(100.0, 0.0), NUM_PEAK_LEVELS - 1);
+    }
+}
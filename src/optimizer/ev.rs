@@ -0,0 +1,148 @@
+//! EV charging plans for strategies that cannot co-optimize the vehicle
+//!
+//! The DP and greedy strategies plan each vehicle on its own first and then
+//! schedule the battery around the resulting load. The MILP strategy plans
+//! vehicles and battery jointly instead.
+
+use super::EvChargeRequest;
+use crate::domain::{EvTarget, Forecast24h};
+
+/// Charging power per period (kW) that covers the vehicle's energy need
+/// before departure in the cheapest periods
+///
+/// Periods are filled at full power, cheapest first, and the last one only
+/// as far as needed but never below the charger's minimum. Without a
+/// departure the whole horizon is available.
+pub fn cheapest_charging_plan(
+    forecast: &Forecast24h,
+    n: usize,
+    request: &EvChargeRequest,
+) -> Vec<f64> {
+    let mut plan = vec![0.0; n];
+    let max_kw = request.max_charge_kw();
+    let mut needed_kwh = request.ev.energy_needed_kwh();
+    if !request.ev.connected || max_kw <= 0.0 || needed_kwh <= 0.0 {
+        return plan;
+    }
+
+    let available_h = available_hours(forecast, n, request);
+
+    // Stable sort, so equally priced periods are used earliest first
+    let mut order: Vec<usize> = (0..n).filter(|&t| available_h[t] > 0.0).collect();
+    order.sort_by(|&a, &b| {
+        forecast.prices[a]
+            .price_sek_per_kwh
+            .total_cmp(&forecast.prices[b].price_sek_per_kwh)
+    });
+
+    for t in order {
+        if needed_kwh <= 1e-6 {
+            break;
+        }
+        let kw = (needed_kwh / available_h[t])
+            .min(max_kw)
+            .max(request.min_charge_kw());
+        plan[t] = kw;
+        needed_kwh -= kw * available_h[t];
+    }
+    plan
+}
+
+/// Hours of each period the vehicle is still plugged in
+pub fn available_hours(forecast: &Forecast24h, n: usize, request: &EvChargeRequest) -> Vec<f64> {
+    forecast.prices[..n]
+        .iter()
+        .map(|p| {
+            let end = match request.ev.departure_time {
+                Some(departure) => p.time_end.min(departure),
+                None => p.time_end,
+            };
+            ((end - p.time_start).num_seconds() as f64 / 3600.0).max(0.0)
+        })
+        .collect()
+}
+
+/// Schedule entry targets for period `t` of each vehicle's plan (plans in kW)
+pub fn ev_targets(requests: &[EvChargeRequest], plans: &[Vec<f64>], t: usize) -> Vec<EvTarget> {
+    requests
+        .iter()
+        .zip(plans)
+        .map(|(request, plan)| EvTarget {
+            charger_id: request.charger_id.clone(),
+            power_w: plan[t] * 1000.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{PriceArea, PricePoint};
+    use crate::power_flow::inputs::EvState;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn forecast(prices: &[f64]) -> Forecast24h {
+        let start = Utc.with_ymd_and_hms(2025, 1, 6, 18, 0, 0).unwrap();
+        Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start,
+            prices: prices
+                .iter()
+                .enumerate()
+                .map(|(i, &price)| PricePoint {
+                    time_start: start + Duration::hours(i as i64),
+                    time_end: start + Duration::hours(i as i64 + 1),
+                    price_sek_per_kwh: price,
+                    export_price_sek_per_kwh: None,
+                })
+                .collect(),
+            consumption: vec![],
+            production: vec![],
+        }
+    }
+
+    fn request(energy_needed_kwh: f64, departure_h: i64) -> EvChargeRequest {
+        EvChargeRequest {
+            charger_id: "drive".to_string(),
+            ev: EvState {
+                connected: true,
+                soc_percent: 50.0,
+                capacity_kwh: 100.0,
+                max_charge_kw: 11.0,
+                max_discharge_kw: 0.0,
+                departure_time: Some(
+                    Utc.with_ymd_and_hms(2025, 1, 6, 18, 0, 0).unwrap()
+                        + Duration::hours(departure_h),
+                ),
+                target_soc_percent: 50.0 + energy_needed_kwh,
+            },
+            min_current_a: 6.0,
+            max_current_a: 16.0,
+            phases: 3,
+            voltage_v: 230.0,
+            bidirectional: false,
+        }
+    }
+
+    #[test]
+    fn test_cheapest_periods_before_departure() {
+        // The cheapest hour (index 4) is after departure
+        let forecast = forecast(&[2.0, 1.0, 0.5, 1.5, 0.1]);
+        let plan = cheapest_charging_plan(&forecast, 5, &request(16.0, 4));
+
+        // The vehicle's 11 kW limit is below the charger's 11.04 kW
+        assert!((plan[2] - 11.0).abs() < 1e-9);
+        assert!((plan[1] - 5.0).abs() < 1e-9);
+        assert_eq!(plan[0], 0.0);
+        assert_eq!(plan[4], 0.0);
+    }
+
+    #[test]
+    fn test_small_remainder_charges_at_minimum_current() {
+        let forecast = forecast(&[1.0, 2.0]);
+        let plan = cheapest_charging_plan(&forecast, 2, &request(1.0, 2));
+
+        assert!((plan[0] - 6.0 * 3.0 * 0.23).abs() < 1e-9);
+        assert_eq!(plan[1], 0.0);
+    }
+}
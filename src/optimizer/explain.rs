@@ -0,0 +1,403 @@
+//! # Schedule Explanations
+//!
+//! Prices a schedule interval by interval with the same cost terms the
+//! strategies optimize (energy, export, wear, peak tariff) and compares it
+//! with two baselines over the same forecast:
+//!
+//! - **No battery**: the house runs straight off the grid
+//! - **Self-consumption**: the battery only stores PV surplus and covers
+//!   the house load, ignoring prices
+//!
+//! The difference to a baseline is what the plan is expected to save, and
+//! the per-interval terms answer why a given hour charges or discharges.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::strategies::milp::export_price;
+use super::Constraints;
+use crate::domain::{Forecast24h, PricePoint, Schedule};
+
+/// Cost terms of an interval or a whole plan (SEK)
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostBreakdown {
+    /// Paid for imported energy
+    pub energy_cost_sek: f64,
+    /// Earned for exported energy
+    pub export_revenue_sek: f64,
+    /// Battery wear from the degradation model
+    pub wear_cost_sek: f64,
+    /// Peak tariff increase from imports above the month's peak so far
+    pub peak_penalty_sek: f64,
+    /// Energy cost minus export revenue plus wear and peak penalty
+    pub total_sek: f64,
+}
+
+impl CostBreakdown {
+    fn add(&mut self, other: &CostBreakdown) {
+        self.energy_cost_sek += other.energy_cost_sek;
+        self.export_revenue_sek += other.export_revenue_sek;
+        self.wear_cost_sek += other.wear_cost_sek;
+        self.peak_penalty_sek += other.peak_penalty_sek;
+        self.total_sek += other.total_sek;
+    }
+}
+
+/// One schedule interval, priced
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntervalExplanation {
+    pub time_start: DateTime<Utc>,
+    pub time_end: DateTime<Utc>,
+    /// The strategy's own label, e.g. `dp:Charge`
+    pub reason: String,
+    pub import_price_sek_per_kwh: f64,
+    pub export_price_sek_per_kwh: f64,
+    /// Forecast house load minus PV plus planned EV charging (kW)
+    pub net_load_kw: f64,
+    /// Planned battery power (kW, positive = charge)
+    pub battery_kw: f64,
+    /// Resulting grid flow (kW, positive = import)
+    pub grid_kw: f64,
+    pub soc_start_percent: f64,
+    pub soc_end_percent: f64,
+    pub cost: CostBreakdown,
+}
+
+/// Reference operation the plan is compared against
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Baseline {
+    NoBattery,
+    SelfConsumption,
+}
+
+/// Cost of a baseline over the schedule's intervals
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineComparison {
+    pub baseline: Baseline,
+    pub cost: CostBreakdown,
+    /// Baseline cost minus plan cost; positive when the plan is cheaper
+    pub savings_sek: f64,
+}
+
+/// Cost breakdown of a schedule and its baselines
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleExplanation {
+    pub schedule_id: Uuid,
+    pub optimizer_version: String,
+    pub initial_soc_percent: f64,
+    pub intervals: Vec<IntervalExplanation>,
+    pub total: CostBreakdown,
+    pub baselines: Vec<BaselineComparison>,
+}
+
+impl ScheduleExplanation {
+    /// Savings against `baseline` (SEK)
+    pub fn savings_sek(&self, baseline: Baseline) -> Option<f64> {
+        self.baselines
+            .iter()
+            .find(|b| b.baseline == baseline)
+            .map(|b| b.savings_sek)
+    }
+}
+
+/// Price `schedule` against `forecast`, starting from `soc_percent`
+pub fn explain_schedule(
+    schedule: &Schedule,
+    forecast: &Forecast24h,
+    constraints: &Constraints,
+    soc_percent: f64,
+) -> ScheduleExplanation {
+    let intervals: Vec<Interval> = schedule
+        .entries
+        .iter()
+        .map(|entry| {
+            let price = forecast
+                .prices
+                .iter()
+                .find(|p| p.time_start == entry.time_start)
+                .cloned()
+                .unwrap_or(PricePoint {
+                    time_start: entry.time_start,
+                    time_end: entry.time_end,
+                    price_sek_per_kwh: entry.price_sek_per_kwh,
+                    export_price_sek_per_kwh: None,
+                });
+            let load_kw = forecast
+                .load_kw_between(entry.time_start, entry.time_end)
+                .unwrap_or(0.0);
+            let pv_kw = forecast
+                .pv_kw_between(entry.time_start, entry.time_end)
+                .unwrap_or(0.0);
+            let ev_kw: f64 = entry.ev_targets.iter().map(|e| e.power_w / 1000.0).sum();
+            Interval {
+                dt_h: (entry.time_end - entry.time_start).num_seconds().max(0) as f64 / 3600.0,
+                price,
+                net_load_kw: load_kw - pv_kw + ev_kw,
+                planned_kw: entry.target_power_w / 1000.0,
+                reason: entry.reason.clone(),
+            }
+        })
+        .collect();
+
+    let plan = simulate(&intervals, constraints, soc_percent, |i, _| i.planned_kw);
+    let total = sum(&plan);
+    let baselines = [Baseline::NoBattery, Baseline::SelfConsumption]
+        .into_iter()
+        .map(|baseline| {
+            let cost = sum(&simulate(
+                &intervals,
+                constraints,
+                soc_percent,
+                |i, soc| match baseline {
+                    Baseline::NoBattery => 0.0,
+                    Baseline::SelfConsumption => self_consumption_kw(i, soc, constraints),
+                },
+            ));
+            BaselineComparison {
+                baseline,
+                cost,
+                savings_sek: cost.total_sek - total.total_sek,
+            }
+        })
+        .collect();
+
+    ScheduleExplanation {
+        schedule_id: schedule.id,
+        optimizer_version: schedule.optimizer_version.clone(),
+        initial_soc_percent: soc_percent,
+        intervals: plan,
+        total,
+        baselines,
+    }
+}
+
+struct Interval {
+    dt_h: f64,
+    price: PricePoint,
+    net_load_kw: f64,
+    planned_kw: f64,
+    reason: String,
+}
+
+/// Run the battery at `battery_kw(interval, soc)` through the intervals
+fn simulate(
+    intervals: &[Interval],
+    constraints: &Constraints,
+    soc_percent: f64,
+    battery_kw: impl Fn(&Interval, f64) -> f64,
+) -> Vec<IntervalExplanation> {
+    let efficiency = constraints.battery_efficiency.clamp(0.5, 1.0);
+    let capacity_kwh = constraints.battery_capacity_kwh.max(0.1);
+    let peak_cost_per_kw = constraints.marginal_peak_cost_sek_per_kw();
+    let wear = constraints.wear();
+    let mut peak_kw = constraints.peak_power_incurred_kw.max(0.0);
+    let mut soc = soc_percent;
+
+    intervals
+        .iter()
+        .map(|interval| {
+            let battery_kw = battery_kw(interval, soc);
+            let grid_kw = interval.net_load_kw + battery_kw;
+            let import_price = interval.price.price_sek_per_kwh;
+            let export_price = export_price(&interval.price, constraints);
+
+            let energy_cost_sek = grid_kw.max(0.0) * import_price * interval.dt_h;
+            let export_revenue_sek = (-grid_kw).clamp(0.0, constraints.max_export_grid_kw.max(0.0))
+                * export_price
+                * interval.dt_h;
+            let wear_cost_sek = wear.cost_sek(battery_kw, interval.dt_h, soc);
+            // Only the rise above the highest peak so far is billed
+            let weighted_kw = constraints.peak_tariff.weight(interval.price.time_start) * grid_kw;
+            let peak_penalty_sek = (weighted_kw - peak_kw).max(0.0) * peak_cost_per_kw;
+            peak_kw = peak_kw.max(weighted_kw);
+
+            let stored_kw = if battery_kw >= 0.0 {
+                battery_kw * efficiency
+            } else {
+                battery_kw / efficiency
+            };
+            let soc_start_percent = soc;
+            soc = (soc + stored_kw * interval.dt_h / capacity_kwh * 100.0).clamp(0.0, 100.0);
+
+            IntervalExplanation {
+                time_start: interval.price.time_start,
+                time_end: interval.price.time_end,
+                reason: interval.reason.clone(),
+                import_price_sek_per_kwh: import_price,
+                export_price_sek_per_kwh: export_price,
+                net_load_kw: interval.net_load_kw,
+                battery_kw,
+                grid_kw,
+                soc_start_percent,
+                soc_end_percent: soc,
+                cost: CostBreakdown {
+                    energy_cost_sek,
+                    export_revenue_sek,
+                    wear_cost_sek,
+                    peak_penalty_sek,
+                    total_sek: energy_cost_sek - export_revenue_sek
+                        + wear_cost_sek
+                        + peak_penalty_sek,
+                },
+            }
+        })
+        .collect()
+}
+
+/// Battery power that stores PV surplus and covers the load, within the
+/// power and SoC limits (kW)
+fn self_consumption_kw(interval: &Interval, soc: f64, constraints: &Constraints) -> f64 {
+    if interval.dt_h <= 0.0 {
+        return 0.0;
+    }
+    let efficiency = constraints.battery_efficiency.clamp(0.5, 1.0);
+    let capacity_kwh = constraints.battery_capacity_kwh.max(0.1);
+    let room_kw = (constraints.max_soc_percent - soc).max(0.0) / 100.0 * capacity_kwh
+        / efficiency
+        / interval.dt_h;
+    let available_kw =
+        (soc - constraints.min_soc_percent).max(0.0) / 100.0 * capacity_kwh * efficiency
+            / interval.dt_h;
+    (-interval.net_load_kw).clamp(
+        -constraints
+            .battery_max_discharge_kw
+            .max(0.0)
+            .min(available_kw),
+        constraints.battery_max_charge_kw.max(0.0).min(room_kw),
+    )
+}
+
+fn sum(intervals: &[IntervalExplanation]) -> CostBreakdown {
+    let mut total = CostBreakdown::default();
+    for interval in intervals {
+        total.add(&interval.cost);
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ConsumptionPoint, PriceArea, ProductionPoint, ScheduleEntry};
+    use chrono::{Duration, TimeZone};
+
+    /// Four hours: cheap night, two expensive evening hours and a sunny
+    /// noon, with a 2 kW house load throughout
+    fn forecast() -> Forecast24h {
+        let start = Utc.with_ymd_and_hms(2025, 5, 12, 0, 0, 0).unwrap();
+        let prices = [0.2, 2.0, 2.0, 1.0];
+        let pv = [0.0, 0.0, 0.0, 5.0];
+        let hour = |h: usize| start + Duration::hours(h as i64);
+        Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start,
+            prices: prices
+                .iter()
+                .enumerate()
+                .map(|(h, p)| PricePoint {
+                    time_start: hour(h),
+                    time_end: hour(h + 1),
+                    price_sek_per_kwh: *p,
+                    export_price_sek_per_kwh: Some(0.5),
+                })
+                .collect(),
+            consumption: (0..4)
+                .map(|h| ConsumptionPoint {
+                    time_start: hour(h),
+                    time_end: hour(h + 1),
+                    load_kw: 2.0,
+                })
+                .collect(),
+            production: pv
+                .iter()
+                .enumerate()
+                .map(|(h, kw)| ProductionPoint {
+                    time_start: hour(h),
+                    time_end: hour(h + 1),
+                    pv_kw: *kw,
+                })
+                .collect(),
+        }
+    }
+
+    fn schedule(forecast: &Forecast24h, battery_kw: [f64; 4]) -> Schedule {
+        let entries: Vec<ScheduleEntry> = forecast
+            .prices
+            .iter()
+            .zip(battery_kw)
+            .map(|(p, kw)| ScheduleEntry {
+                time_start: p.time_start,
+                time_end: p.time_end,
+                target_power_w: kw * 1000.0,
+                price_sek_per_kwh: p.price_sek_per_kwh,
+                reason: "test".to_string(),
+                ev_targets: Vec::new(),
+            })
+            .collect();
+        Schedule {
+            id: Uuid::new_v4(),
+            created_at: forecast.generated_at,
+            valid_from: entries[0].time_start,
+            valid_until: entries[3].time_end,
+            entries,
+            optimizer_version: "test".to_string(),
+            cost_distribution: None,
+        }
+    }
+
+    #[test]
+    fn test_idle_plan_matches_no_battery_baseline() {
+        let forecast = forecast();
+        let explanation = explain_schedule(
+            &schedule(&forecast, [0.0; 4]),
+            &forecast,
+            &Constraints::default(),
+            50.0,
+        );
+
+        // 2 kW at 0.2 + 2.0 + 2.0 SEK, then 3 kW exported at 0.5 SEK
+        let total = explanation.total;
+        assert!((total.energy_cost_sek - 8.4).abs() < 1e-9);
+        assert!((total.export_revenue_sek - 1.5).abs() < 1e-9);
+        assert_eq!(total.wear_cost_sek, 0.0);
+        assert!(explanation.savings_sek(Baseline::NoBattery).unwrap().abs() < 1e-9);
+        assert!(explanation
+            .intervals
+            .iter()
+            .all(|i| i.soc_start_percent == 50.0 && i.soc_end_percent == 50.0));
+    }
+
+    #[test]
+    fn test_arbitrage_beats_both_baselines() {
+        let forecast = forecast();
+        let constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 0.0,
+            battery_degradation_per_cycle: 0.00001,
+            ..Constraints::default()
+        };
+        let explanation = explain_schedule(
+            &schedule(&forecast, [5.0, -2.0, -2.0, 3.0]),
+            &forecast,
+            &constraints,
+            20.0,
+        );
+
+        let first = &explanation.intervals[0];
+        assert!((first.grid_kw - 7.0).abs() < 1e-9);
+        assert!(first.soc_end_percent > first.soc_start_percent);
+        assert!(explanation.intervals[1].cost.energy_cost_sek.abs() < 1e-9);
+        assert!(explanation.total.wear_cost_sek > 0.0);
+
+        let sum: f64 = explanation.intervals.iter().map(|i| i.cost.total_sek).sum();
+        assert!((sum - explanation.total.total_sek).abs() < 1e-9);
+        assert!(explanation.savings_sek(Baseline::NoBattery).unwrap() > 0.0);
+        assert!(explanation.savings_sek(Baseline::SelfConsumption).unwrap() > 0.0);
+    }
+}
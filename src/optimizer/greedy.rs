@@ -4,8 +4,9 @@ use async_trait::async_trait;
 use chrono::Utc;
 use uuid::Uuid;
 
-use super::{Constraints, OptimizationStrategy, SystemState};
-use crate::domain::{Forecast24h, Schedule, ScheduleEntry};
+use super::ev::{cheapest_charging_plan, ev_targets};
+use super::{BatteryLimits, Constraints, OptimizationStrategy, SystemState};
+use crate::domain::{max_horizon, Forecast24h, Schedule, ScheduleEntry};
 
 /// Simple greedy optimizer that follows basic rules:
 /// - Charge when prices are below average
@@ -36,46 +37,51 @@ impl GreedyOptimizer {
         }
     }
 
-    /// Calculate average price from forecast
+    /// Calculate average price over the planning horizon
     fn average_price(forecast: &Forecast24h) -> f64 {
-        if forecast.prices.is_empty() {
+        let prices = &forecast.prices[..forecast.periods_within(max_horizon())];
+        if prices.is_empty() {
             return 0.0;
         }
 
-        let sum: f64 = forecast.prices.iter().map(|p| p.price_sek_per_kwh).sum();
-        sum / forecast.prices.len() as f64
+        let sum: f64 = prices.iter().map(|p| p.price_sek_per_kwh).sum();
+        sum / prices.len() as f64
     }
 
     /// Determine target power based on price and SoC
+    ///
+    /// `limits` are the period's battery limits, which leave out any power
+    /// and energy sold as frequency reserve.
     fn determine_power(
         &self,
         price: f64,
         avg_price: f64,
         current_soc: f64,
         constraints: &Constraints,
+        limits: &BatteryLimits,
     ) -> (f64, &str) {
         // Use battery physical limits (NOT grid limits!)
         // The constraint is min(grid_limit, battery_limit)
-        let battery_max_charge_w = constraints.battery_max_charge_kw * 1000.0;
-        let battery_max_discharge_w = constraints.battery_max_discharge_kw * 1000.0;
+        let battery_max_charge_w = limits.max_charge_kw * 1000.0;
+        let battery_max_discharge_w = limits.max_discharge_kw * 1000.0;
         let grid_max_w = constraints.max_power_grid_kw * 1000.0;
 
         let max_charge_w = battery_max_charge_w.min(grid_max_w);
         let max_discharge_w = battery_max_discharge_w.min(grid_max_w);
 
         // Don't charge if already at max SoC
-        if current_soc >= constraints.max_soc_percent {
+        if current_soc >= limits.max_soc_percent {
             return (0.0, "at_max_soc");
         }
 
         // Don't discharge if at min SoC
-        if current_soc <= constraints.min_soc_percent {
+        if current_soc <= limits.min_soc_percent {
             return (0.0, "at_min_soc");
         }
 
         // Charge decision
         if price < avg_price * self.charge_threshold {
-            let power = if current_soc < constraints.min_soc_percent + 10.0 {
+            let power = if current_soc < limits.min_soc_percent + 10.0 {
                 // Charge faster if SoC is low
                 max_charge_w
             } else {
@@ -87,9 +93,9 @@ impl GreedyOptimizer {
 
         // Discharge decision
         if price > avg_price * self.discharge_threshold
-            && current_soc > constraints.min_soc_percent + 20.0
+            && current_soc > limits.min_soc_percent + 20.0
         {
-            let power = if current_soc > constraints.max_soc_percent - 10.0 {
+            let power = if current_soc > limits.max_soc_percent - 10.0 {
                 // Discharge faster if SoC is high
                 -max_discharge_w
             } else {
@@ -144,6 +150,7 @@ impl OptimizationStrategy for GreedyOptimizer {
             anyhow::bail!("No price points available for optimization");
         }
 
+        let n = forecast.periods_within(max_horizon());
         let avg_price = Self::average_price(forecast);
         let mut current_soc = state.battery.soc_percent;
 
@@ -151,17 +158,24 @@ impl OptimizationStrategy for GreedyOptimizer {
         let capacity_kwh = constraints.battery_capacity_kwh.max(0.1); // Prevent division by zero
         let efficiency = constraints.battery_efficiency.clamp(0.5, 1.0);
 
+        let ev_plans: Vec<Vec<f64>> = state
+            .evs
+            .iter()
+            .map(|request| cheapest_charging_plan(forecast, n, request))
+            .collect();
+
         let mut entries = Vec::new();
 
-        for price_point in &forecast.prices {
+        for (t, price_point) in forecast.prices.iter().take(n).enumerate() {
             let (target_power_w, reason) = self.determine_power(
                 price_point.price_sek_per_kwh,
                 avg_price,
                 current_soc,
                 constraints,
+                &constraints.battery_limits_at(price_point.time_start),
             );
 
-            // Calculate duration for this interval (typically 1 hour)
+            // Calculate duration for this interval (15, 30 or 60 minutes)
             let duration = price_point
                 .time_end
                 .signed_duration_since(price_point.time_start);
@@ -173,6 +187,7 @@ impl OptimizationStrategy for GreedyOptimizer {
                 target_power_w,
                 price_sek_per_kwh: price_point.price_sek_per_kwh,
                 reason: format!("greedy:{}", reason),
+                ev_targets: ev_targets(&state.evs, &ev_plans, t),
             });
 
             // Update simulated SoC for next iteration
@@ -195,6 +210,7 @@ impl OptimizationStrategy for GreedyOptimizer {
             valid_until,
             entries,
             optimizer_version: "greedy-v1.0".to_string(),
+            cost_distribution: None,
         })
     }
 }
@@ -254,19 +270,20 @@ mod tests {
     fn test_greedy_threshold_logic() {
         let optimizer = GreedyOptimizer::default();
         let constraints = Constraints::default();
+        let limits = constraints.battery_limits_at(Utc::now());
 
         // Low price should trigger charging
-        let (power, reason) = optimizer.determine_power(0.5, 1.0, 50.0, &constraints);
+        let (power, reason) = optimizer.determine_power(0.5, 1.0, 50.0, &constraints, &limits);
         assert!(power > 0.0);
         assert_eq!(reason, "cheap_price_charge");
 
         // High price should trigger discharging
-        let (power, reason) = optimizer.determine_power(2.0, 1.0, 50.0, &constraints);
+        let (power, reason) = optimizer.determine_power(2.0, 1.0, 50.0, &constraints, &limits);
         assert!(power < 0.0);
         assert_eq!(reason, "high_price_discharge");
 
         // Average price should stay idle
-        let (power, reason) = optimizer.determine_power(1.0, 1.0, 50.0, &constraints);
+        let (power, reason) = optimizer.determine_power(1.0, 1.0, 50.0, &constraints, &limits);
         assert_eq!(power, 0.0);
         assert_eq!(reason, "idle");
     }
@@ -0,0 +1,400 @@
+//! # Switchable Load Scheduling
+//!
+//! Places the daily runtime of on/off loads (pool pump, dryer, immersion
+//! heater) into the cheapest price periods of each load's window. Energy
+//! a run can take from forecast PV surplus is valued at the export price,
+//! the rest at the import price. Periods where the forecast house load
+//! plus the load would exceed the import limit are left out.
+//!
+//! Each window is solved exactly over (periods run, on/off, time in that
+//! state), so minimum run and pause lengths hold across the plan. Loads are
+//! planned one after another, each seeing the runs of the loads before it.
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::{max_horizon, Forecast24h, LoadConstraints, LoadRun, LoadSchedule};
+
+/// Cost of every kWh of runtime the plan leaves out (SEK)
+///
+/// Far above any spot price, so runtime is only dropped when no feasible
+/// placement exists.
+pub const SHORTFALL_PENALTY_SEK_PER_KWH: f64 = 100.0;
+
+/// Where a load stands when planning starts
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadStart {
+    pub on: bool,
+    /// Time in the current state, `None` when unknown
+    pub minutes_in_state: Option<u32>,
+    /// Runtime already reached in the window under way
+    pub runtime_done_minutes: u32,
+}
+
+/// Price period of the horizon as seen by one load
+struct Slot {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    hours: f64,
+    /// Energy cost of running through the slot (SEK)
+    cost_sek: f64,
+    /// Whether the import limit has room for the load
+    allowed: bool,
+}
+
+/// Plans the runs of the site's switchable loads
+pub struct LoadScheduler {
+    max_import_kw: f64,
+}
+
+impl LoadScheduler {
+    pub fn new(max_import_kw: f64) -> Self {
+        Self { max_import_kw }
+    }
+
+    /// Plan every load in turn; later loads see the runs of earlier ones
+    /// as extra house load
+    pub fn plan_group(
+        &self,
+        loads: &[(&LoadConstraints, LoadStart)],
+        forecast: &Forecast24h,
+        now: DateTime<Utc>,
+        tz: chrono_tz::Tz,
+    ) -> Vec<LoadSchedule> {
+        let n = forecast.periods_within(max_horizon());
+        let mut committed_kw = vec![0.0; n];
+        loads
+            .iter()
+            .map(|(constraints, start)| {
+                let schedule = self.plan(constraints, *start, forecast, &committed_kw, now, tz);
+                for (kw, price) in committed_kw.iter_mut().zip(&forecast.prices[..n]) {
+                    if schedule
+                        .runs
+                        .iter()
+                        .any(|run| run.start < price.time_end && price.time_start < run.end)
+                    {
+                        *kw += constraints.power_kw;
+                    }
+                }
+                schedule
+            })
+            .collect()
+    }
+
+    /// Plan one load's runs over the forecast horizon
+    ///
+    /// `committed_kw` is load already planned per price period on top of
+    /// the forecast. Windows that are not under way and end past the
+    /// horizon are left for a replan with more prices.
+    pub fn plan(
+        &self,
+        constraints: &LoadConstraints,
+        start: LoadStart,
+        forecast: &Forecast24h,
+        committed_kw: &[f64],
+        now: DateTime<Utc>,
+        tz: chrono_tz::Tz,
+    ) -> LoadSchedule {
+        let n = forecast.periods_within(max_horizon());
+        let mut schedule = LoadSchedule {
+            generated_at: now,
+            runs: Vec::new(),
+            cost_sek: 0.0,
+            shortfall_minutes: 0,
+        };
+        let (Some(resolution), Some(last)) = (forecast.resolution(), forecast.prices[..n].last())
+        else {
+            return schedule;
+        };
+        let period_minutes = resolution.num_minutes().max(1) as u32;
+        let horizon_end = last.time_end;
+        let periods = |minutes: u32| minutes.div_ceil(period_minutes) as usize;
+
+        let mut state = start;
+        for (window_start, window_end) in constraints.windows(now, horizon_end, tz) {
+            let under_way = window_start <= now;
+            if !under_way && window_end > horizon_end {
+                continue;
+            }
+            let slots: Vec<Slot> = forecast.prices[..n]
+                .iter()
+                .enumerate()
+                .filter_map(|(i, price)| {
+                    let slot_start = price.time_start.max(now);
+                    if slot_start < window_start || price.time_end > window_end {
+                        return None;
+                    }
+                    let hours = (price.time_end - slot_start).num_seconds() as f64 / 3600.0;
+                    if hours <= 0.0 {
+                        return None;
+                    }
+                    let load_kw = forecast
+                        .load_kw_between(price.time_start, price.time_end)
+                        .unwrap_or(0.0)
+                        + committed_kw.get(i).copied().unwrap_or(0.0);
+                    let pv_kw = forecast
+                        .pv_kw_between(price.time_start, price.time_end)
+                        .unwrap_or(0.0);
+                    let from_surplus_kw = (pv_kw - load_kw).clamp(0.0, constraints.power_kw);
+                    let from_grid_kw = constraints.power_kw - from_surplus_kw;
+                    Some(Slot {
+                        start: slot_start,
+                        end: price.time_end,
+                        hours,
+                        cost_sek: (from_grid_kw * price.price_sek_per_kwh
+                            + from_surplus_kw * price.export_price())
+                            * hours,
+                        allowed: load_kw - pv_kw + constraints.power_kw <= self.max_import_kw,
+                    })
+                })
+                .collect();
+
+            let done = if under_way {
+                state.runtime_done_minutes
+            } else {
+                0
+            };
+            let needed_minutes = constraints.runtime_minutes_per_day.saturating_sub(done);
+            let initial = if under_way {
+                (state.on, state.minutes_in_state.map(periods))
+            } else {
+                (false, None)
+            };
+            let plan = solve_window(
+                &slots,
+                periods(needed_minutes),
+                periods(constraints.min_on_minutes),
+                periods(constraints.min_off_minutes),
+                initial,
+                constraints.power_kw,
+            );
+
+            let mut run_minutes = 0.0;
+            for (slot, on) in slots.iter().zip(&plan) {
+                if !on {
+                    continue;
+                }
+                run_minutes += slot.hours * 60.0;
+                schedule.cost_sek += slot.cost_sek;
+                match schedule.runs.last_mut() {
+                    Some(run) if run.end == slot.start => run.end = slot.end,
+                    _ => schedule.runs.push(LoadRun {
+                        start: slot.start,
+                        end: slot.end,
+                    }),
+                }
+            }
+            schedule.shortfall_minutes += (needed_minutes as f64 - run_minutes).max(0.0) as u32;
+            state = LoadStart::default();
+        }
+        schedule
+    }
+}
+
+/// Cheapest on/off pattern over one window's slots
+///
+/// States are (periods run, capped at `needed`; on/off; periods in that
+/// state, capped at the longest minimum). A state change is allowed once
+/// the current state has lasted its minimum, and the window may not end in
+/// a run shorter than `min_on`. The load can always be switched off where
+/// the import limit forbids running. Without a feasible pattern the load
+/// stays off.
+fn solve_window(
+    slots: &[Slot],
+    needed: usize,
+    min_on: usize,
+    min_off: usize,
+    (on0, len0): (bool, Option<usize>),
+    power_kw: f64,
+) -> Vec<bool> {
+    let cap = min_on.max(min_off).max(1);
+    let len0 = len0.unwrap_or(cap).clamp(1, cap);
+    let states = (needed + 1) * 2 * cap;
+    let index = |k: usize, on: bool, len: usize| (k * 2 + on as usize) * cap + (len - 1);
+
+    // cost[t][s] is the cheapest cost of reaching state s after t slots
+    let mut cost = vec![vec![f64::INFINITY; states]; slots.len() + 1];
+    let mut parent = vec![vec![(usize::MAX, false); states]; slots.len() + 1];
+    cost[0][index(0, on0, len0)] = 0.0;
+
+    for (t, slot) in slots.iter().enumerate() {
+        for k in 0..=needed {
+            for on in [false, true] {
+                for len in 1..=cap {
+                    let from = index(k, on, len);
+                    let base = cost[t][from];
+                    if !base.is_finite() {
+                        continue;
+                    }
+                    for next_on in [false, true] {
+                        if next_on && !slot.allowed {
+                            continue;
+                        }
+                        let next_len = if next_on == on {
+                            (len + 1).min(cap)
+                        } else {
+                            let minimum = if on { min_on } else { min_off };
+                            if len < minimum && slot.allowed {
+                                continue;
+                            }
+                            1
+                        };
+                        let (next_k, step) = if next_on {
+                            ((k + 1).min(needed), slot.cost_sek)
+                        } else {
+                            (k, 0.0)
+                        };
+                        let to = index(next_k, next_on, next_len);
+                        if base + step < cost[t + 1][to] {
+                            cost[t + 1][to] = base + step;
+                            parent[t + 1][to] = (from, next_on);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Shortfall is valued per missing period at the mean slot length
+    let period_hours = slots.iter().map(|s| s.hours).sum::<f64>() / slots.len().max(1) as f64;
+    let mut best = (f64::INFINITY, usize::MAX);
+    for k in 0..=needed {
+        for on in [false, true] {
+            for len in 1..=cap {
+                if on && len < min_on {
+                    continue;
+                }
+                let s = index(k, on, len);
+                let total = cost[slots.len()][s]
+                    + (needed - k) as f64 * period_hours * power_kw * SHORTFALL_PENALTY_SEK_PER_KWH;
+                if total < best.0 {
+                    best = (total, s);
+                }
+            }
+        }
+    }
+
+    let mut plan = vec![false; slots.len()];
+    if best.1 == usize::MAX {
+        return plan;
+    }
+    let mut s = best.1;
+    for t in (1..=slots.len()).rev() {
+        let (from, on) = parent[t][s];
+        plan[t - 1] = on;
+        s = from;
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ConsumptionPoint, PriceArea, PricePoint};
+    use chrono::{Duration, NaiveTime, TimeZone};
+
+    fn forecast(prices: &[f64], load_kw: f64) -> Forecast24h {
+        let start = Utc.with_ymd_and_hms(2025, 6, 2, 0, 0, 0).unwrap();
+        let hour = |i: usize| start + Duration::hours(i as i64);
+        Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start,
+            prices: prices
+                .iter()
+                .enumerate()
+                .map(|(i, &price)| PricePoint {
+                    time_start: hour(i),
+                    time_end: hour(i + 1),
+                    price_sek_per_kwh: price,
+                    export_price_sek_per_kwh: None,
+                })
+                .collect(),
+            consumption: (0..prices.len())
+                .map(|i| ConsumptionPoint {
+                    time_start: hour(i),
+                    time_end: hour(i + 1),
+                    load_kw,
+                })
+                .collect(),
+            production: vec![],
+        }
+    }
+
+    fn pool_pump(runtime_minutes: u32, min_on_minutes: u32) -> LoadConstraints {
+        LoadConstraints {
+            runtime_minutes_per_day: runtime_minutes,
+            earliest_start: NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+            latest_finish: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            min_on_minutes,
+            min_off_minutes: 60,
+            power_kw: 1.0,
+            phase: None,
+        }
+    }
+
+    fn hours_on(schedule: &LoadSchedule, now: DateTime<Utc>) -> Vec<i64> {
+        (0..12)
+            .filter(|h| schedule.is_on_at(now + Duration::hours(*h) + Duration::minutes(30)))
+            .collect()
+    }
+
+    #[test]
+    fn test_runs_in_cheapest_hours_of_window() {
+        let prices = [
+            2.0, 2.0, 0.5, 2.0, 0.4, 2.0, 2.0, 0.6, 2.0, 2.0, 2.0, 2.0, 0.1, 0.1,
+        ];
+        let forecast = forecast(&prices, 1.0);
+        let now = forecast.prices[0].time_start;
+
+        let schedule = LoadScheduler::new(10.0).plan(
+            &pool_pump(180, 60),
+            LoadStart::default(),
+            &forecast,
+            &[],
+            now,
+            chrono_tz::UTC,
+        );
+
+        // The 0.1 hours lie outside the 00:00-12:00 window
+        assert_eq!(hours_on(&schedule, now), vec![2, 4, 7]);
+        assert!((schedule.cost_sek - 1.5).abs() < 1e-9);
+        assert_eq!(schedule.shortfall_minutes, 0);
+    }
+
+    #[test]
+    fn test_minimum_run_keeps_hours_together() {
+        let prices = [2.0, 2.0, 0.5, 2.0, 0.4, 2.0, 2.0, 0.6, 2.0, 2.0, 2.0, 2.0];
+        let forecast = forecast(&prices, 1.0);
+        let now = forecast.prices[0].time_start;
+
+        let schedule = LoadScheduler::new(10.0).plan(
+            &pool_pump(180, 180),
+            LoadStart::default(),
+            &forecast,
+            &[],
+            now,
+            chrono_tz::UTC,
+        );
+
+        assert_eq!(hours_on(&schedule, now), vec![2, 3, 4]);
+        assert_eq!(schedule.runs.len(), 1);
+    }
+
+    #[test]
+    fn test_group_respects_import_limit() {
+        let prices = [2.0, 2.0, 0.5, 2.0, 0.4, 2.0, 2.0, 0.6, 2.0, 2.0, 2.0, 2.0];
+        let forecast = forecast(&prices, 1.0);
+        let now = forecast.prices[0].time_start;
+        let pump = pool_pump(60, 60);
+
+        // 1 kW house + one 1 kW load fits under 2.5 kW, two do not
+        let schedules = LoadScheduler::new(2.5).plan_group(
+            &[(&pump, LoadStart::default()), (&pump, LoadStart::default())],
+            &forecast,
+            now,
+            chrono_tz::UTC,
+        );
+
+        assert_eq!(hours_on(&schedules[0], now), vec![4]);
+        assert_eq!(hours_on(&schedules[1], now), vec![2]);
+    }
+}
@@ -1,7 +1,12 @@
 pub mod constraints;
 pub mod dp;
+pub mod ev;
+pub mod explain;
 pub mod greedy;
+pub mod loads;
+pub mod mpc;
 pub mod strategies;
+pub mod thermal;
 pub mod types;
 
 pub use constraints::*;
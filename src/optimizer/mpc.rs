@@ -0,0 +1,487 @@
+//! Receding-horizon (MPC) battery control
+//!
+//! The schedule is planned once per re-optimization from forecasts; by the
+//! next tick the measured SoC, PV and load have moved on. In MPC mode the
+//! controller re-solves the next few schedule periods every tick from the
+//! measurements and applies only the first step.
+//!
+//! The short problem is warm-started from the current schedule: it takes
+//! its periods and prices, the plan's own power is always one of the
+//! candidate actions, and energy left at the end of the horizon is valued
+//! at the plan's later prices. The gap between measured and forecast net
+//! load is carried forward and fades out over the horizon.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use super::strategies::milp::export_price;
+use super::{Constraints, RESERVE_SHORTFALL_PENALTY_SEK_PER_PERCENT};
+use crate::domain::{Forecast24h, PricePoint, Schedule};
+
+/// Share of the measured forecast error still present one period later
+const ERROR_DECAY_PER_PERIOD: f64 = 0.5;
+
+/// Horizon and compute budget of the per-tick re-solve
+#[derive(Debug, Clone)]
+pub struct MpcSettings {
+    /// Schedule periods the re-solve looks ahead, the current one included
+    pub horizon_periods: usize,
+    /// SoC grid resolution (percentage points)
+    pub soc_step_percent: f64,
+    /// Evenly spaced battery power levels between full discharge and full charge
+    pub power_levels: usize,
+    /// Wall-clock time the solve may take before the schedule is followed instead
+    pub budget: Duration,
+}
+
+impl Default for MpcSettings {
+    fn default() -> Self {
+        Self {
+            horizon_periods: 6,
+            soc_step_percent: 0.5,
+            power_levels: 11,
+            budget: Duration::from_millis(200),
+        }
+    }
+}
+
+/// What the controller measured this tick
+#[derive(Debug, Clone, Copy)]
+pub struct MpcMeasurement {
+    pub timestamp: DateTime<Utc>,
+    pub soc_percent: f64,
+    pub pv_kw: f64,
+    pub house_load_kw: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MpcSolution {
+    /// Battery power to apply now (W, positive = charge)
+    pub target_power_w: f64,
+    /// Planned battery power for each period of the horizon (W)
+    pub planned_power_w: Vec<f64>,
+    /// Cost of the horizon including the value of the final SoC (SEK)
+    pub expected_cost_sek: f64,
+}
+
+/// One period of the short horizon
+struct Period {
+    dt_h: f64,
+    price: PricePoint,
+    net_load_kw: f64,
+    peak_weight: f64,
+    planned_kw: f64,
+}
+
+pub struct MpcPlanner {
+    settings: MpcSettings,
+}
+
+impl MpcPlanner {
+    pub fn new(settings: MpcSettings) -> Self {
+        Self { settings }
+    }
+
+    pub fn settings(&self) -> &MpcSettings {
+        &self.settings
+    }
+
+    /// Re-solve on a blocking thread; `None` when it fails or runs over
+    /// the budget, so the caller can follow the schedule instead
+    pub async fn solve_within_budget(
+        self: &std::sync::Arc<Self>,
+        measurement: MpcMeasurement,
+        schedule: Schedule,
+        forecast: Option<Forecast24h>,
+        constraints: Constraints,
+    ) -> Option<MpcSolution> {
+        let planner = std::sync::Arc::clone(self);
+        let solve = tokio::task::spawn_blocking(move || {
+            planner.solve(&measurement, &schedule, forecast.as_ref(), &constraints)
+        });
+        match tokio::time::timeout(self.settings.budget, solve).await {
+            Ok(Ok(Ok(solution))) => Some(solution),
+            Ok(Ok(Err(e))) => {
+                tracing::warn!(error = %e, "MPC re-solve failed, following the schedule");
+                None
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(error = %e, "MPC re-solve panicked, following the schedule");
+                None
+            }
+            Err(_) => {
+                tracing::warn!(
+                    budget_ms = self.settings.budget.as_millis() as u64,
+                    "MPC re-solve exceeded its budget, following the schedule"
+                );
+                None
+            }
+        }
+    }
+
+    /// Solve the short horizon by backward dynamic programming over SoC
+    pub fn solve(
+        &self,
+        measurement: &MpcMeasurement,
+        schedule: &Schedule,
+        forecast: Option<&Forecast24h>,
+        constraints: &Constraints,
+    ) -> Result<MpcSolution> {
+        let periods = self.periods(measurement, schedule, forecast, constraints);
+        if periods.is_empty() {
+            bail!("Schedule has no periods left to re-solve");
+        }
+
+        let min_soc = constraints.min_soc_percent;
+        let max_soc = constraints.max_soc_percent.max(min_soc);
+        let step = self.settings.soc_step_percent.max(0.01);
+        let grid: Vec<f64> = (0..=((max_soc - min_soc) / step).ceil() as usize)
+            .map(|i| (min_soc + i as f64 * step).min(max_soc))
+            .collect();
+
+        // Energy left at the end is worth what the plan would pay for it later
+        let terminal_price = terminal_price(schedule, &periods, measurement.timestamp);
+        let capacity_kwh = constraints.battery_capacity_kwh.max(0.1);
+        let terminal: Vec<f64> = grid
+            .iter()
+            .map(|soc| -terminal_price * soc / 100.0 * capacity_kwh)
+            .collect();
+
+        // Backward pass: values[t] is the cost to go from the start of period t
+        let mut values = vec![terminal];
+        for period in periods.iter().rev() {
+            let next = values.last().expect("terminal value");
+            let value = grid
+                .iter()
+                .map(|&soc| {
+                    self.best_action(period, soc, &grid, next, constraints)
+                        .map_or(0.0, |(_, cost)| cost)
+                })
+                .collect();
+            values.push(value);
+        }
+        values.reverse();
+
+        // Forward pass from the measured SoC
+        let mut soc = measurement.soc_percent.clamp(min_soc, max_soc);
+        let mut planned_power_w = Vec::with_capacity(periods.len());
+        for (t, period) in periods.iter().enumerate() {
+            let kw = self
+                .best_action(period, soc, &grid, &values[t + 1], constraints)
+                .map_or(0.0, |(kw, _)| kw);
+            planned_power_w.push(kw * 1000.0);
+            soc = next_soc(soc, kw, period.dt_h, constraints).clamp(min_soc, max_soc);
+        }
+
+        Ok(MpcSolution {
+            target_power_w: planned_power_w[0],
+            planned_power_w,
+            expected_cost_sek: interpolate(&grid, &values[0], measurement.soc_percent),
+        })
+    }
+
+    /// Cheapest feasible battery power at `soc` and its cost to go; the
+    /// value between grid points is interpolated so short periods with
+    /// small SoC moves are not rounded away
+    fn best_action(
+        &self,
+        period: &Period,
+        soc: f64,
+        grid: &[f64],
+        next_value: &[f64],
+        constraints: &Constraints,
+    ) -> Option<(f64, f64)> {
+        let (min_soc, max_soc) = (grid[0], grid[grid.len() - 1]);
+        self.actions(period, constraints)
+            .filter_map(|kw| {
+                let soc_next = next_soc(soc, kw, period.dt_h, constraints);
+                (soc_next >= min_soc - 1e-9 && soc_next <= max_soc + 1e-9).then(|| {
+                    let cost = period_cost(period, soc, kw, constraints)
+                        + interpolate(grid, next_value, soc_next);
+                    (kw, cost)
+                })
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Battery power levels to try in a period (kW): an even grid plus
+    /// the plan's own power, within the headroom left by reserve sales
+    fn actions<'a>(
+        &'a self,
+        period: &'a Period,
+        constraints: &'a Constraints,
+    ) -> impl Iterator<Item = f64> + 'a {
+        let limits = constraints.battery_limits_at(period.price.time_start);
+        let max_charge = limits.max_charge_kw.max(0.0);
+        let max_discharge = limits.max_discharge_kw.max(0.0);
+        let levels = self.settings.power_levels.max(2);
+        (0..levels)
+            .map(move |i| {
+                -max_discharge + (max_charge + max_discharge) * i as f64 / (levels - 1) as f64
+            })
+            .chain([0.0, period.planned_kw.clamp(-max_discharge, max_charge)])
+    }
+
+    /// The schedule periods ahead of `measurement`, the current one cut at now
+    fn periods(
+        &self,
+        measurement: &MpcMeasurement,
+        schedule: &Schedule,
+        forecast: Option<&Forecast24h>,
+        constraints: &Constraints,
+    ) -> Vec<Period> {
+        let now = measurement.timestamp;
+        let measured_net_kw = measurement.house_load_kw - measurement.pv_kw;
+        let error_kw =
+            measured_net_kw - forecast_net_load_kw(forecast, now).unwrap_or(measured_net_kw);
+
+        schedule
+            .entries
+            .iter()
+            .filter(|e| e.time_end > now)
+            .take(self.settings.horizon_periods.max(1))
+            .enumerate()
+            .map(|(k, entry)| {
+                let start = entry.time_start.max(now);
+                let ev_kw: f64 = entry.ev_targets.iter().map(|e| e.power_w / 1000.0).sum();
+                let net_load_kw = if k == 0 {
+                    measured_net_kw
+                } else {
+                    // Without a forecast the measurement persists
+                    forecast_net_load_kw(forecast, entry.time_start)
+                        .map(|kw| kw + error_kw * ERROR_DECAY_PER_PERIOD.powi(k as i32))
+                        .unwrap_or(measured_net_kw)
+                };
+                Period {
+                    dt_h: (entry.time_end - start).num_seconds() as f64 / 3600.0,
+                    price: PricePoint {
+                        time_start: entry.time_start,
+                        time_end: entry.time_end,
+                        price_sek_per_kwh: entry.price_sek_per_kwh,
+                        export_price_sek_per_kwh: None,
+                    },
+                    net_load_kw: net_load_kw + ev_kw,
+                    peak_weight: constraints.peak_tariff.weight(entry.time_start),
+                    planned_kw: entry.target_power_w / 1000.0,
+                }
+            })
+            .filter(|p| p.dt_h > 0.0)
+            .collect()
+    }
+}
+
+/// SoC after running the battery at `kw` for `dt_h` hours
+fn next_soc(soc: f64, kw: f64, dt_h: f64, constraints: &Constraints) -> f64 {
+    let efficiency = constraints.battery_efficiency.clamp(0.5, 1.0);
+    let stored_kw = if kw >= 0.0 {
+        kw * efficiency
+    } else {
+        kw / efficiency
+    };
+    soc + stored_kw * dt_h / constraints.battery_capacity_kwh.max(0.1) * 100.0
+}
+
+/// Cost of running the battery at `battery_kw` through a period from
+/// `soc_percent` (SEK)
+///
+/// Import above the month's incurred peak pays the marginal peak cost in
+/// every period that would set it, which keeps the short horizon from
+/// creating new peaks without tracking the peak as state. Starting a
+/// reserve hour outside its SoC band is penalised.
+fn period_cost(
+    period: &Period,
+    soc_percent: f64,
+    battery_kw: f64,
+    constraints: &Constraints,
+) -> f64 {
+    let grid_kw = period.net_load_kw + battery_kw;
+    let energy = if grid_kw >= 0.0 {
+        grid_kw * period.price.price_sek_per_kwh
+    } else {
+        grid_kw.max(-constraints.max_export_grid_kw.max(0.0))
+            * export_price(&period.price, constraints)
+    } * period.dt_h;
+    let new_peak_kw =
+        (period.peak_weight * grid_kw - constraints.peak_power_incurred_kw.max(0.0)).max(0.0);
+    energy
+        + constraints
+            .wear()
+            .cost_sek(battery_kw, period.dt_h, soc_percent)
+        + new_peak_kw * constraints.marginal_peak_cost_sek_per_kw()
+        + constraints
+            .battery_limits_at(period.price.time_start)
+            .soc_shortfall_percent(soc_percent)
+            * RESERVE_SHORTFALL_PENALTY_SEK_PER_PERCENT
+}
+
+/// Mean price of the plan after the horizon, or of the horizon itself
+fn terminal_price(schedule: &Schedule, periods: &[Period], now: DateTime<Utc>) -> f64 {
+    let horizon_end = periods.last().map(|p| p.price.time_end).unwrap_or(now);
+    let later: Vec<f64> = schedule
+        .entries
+        .iter()
+        .filter(|e| e.time_start >= horizon_end)
+        .map(|e| e.price_sek_per_kwh)
+        .collect();
+    let prices: Vec<f64> = if later.is_empty() {
+        periods.iter().map(|p| p.price.price_sek_per_kwh).collect()
+    } else {
+        later
+    };
+    prices.iter().sum::<f64>() / prices.len().max(1) as f64
+}
+
+/// Forecast house load minus PV at `t`, `None` without a forecast for it
+fn forecast_net_load_kw(forecast: Option<&Forecast24h>, t: DateTime<Utc>) -> Option<f64> {
+    let forecast = forecast?;
+    let load = forecast
+        .consumption
+        .iter()
+        .find(|c| c.time_start <= t && t < c.time_end)?
+        .load_kw;
+    let pv = forecast
+        .production
+        .iter()
+        .find(|p| p.time_start <= t && t < p.time_end)
+        .map_or(0.0, |p| p.pv_kw.max(0.0));
+    Some(load - pv)
+}
+
+/// Linear interpolation of `values` over the ascending `grid`
+fn interpolate(grid: &[f64], values: &[f64], x: f64) -> f64 {
+    let last = grid.len() - 1;
+    if x <= grid[0] {
+        return values[0];
+    }
+    if x >= grid[last] {
+        return values[last];
+    }
+    let i = grid.partition_point(|&g| g <= x).min(last) - 1;
+    let span = grid[i + 1] - grid[i];
+    if span <= 0.0 {
+        return values[i];
+    }
+    let w = (x - grid[i]) / span;
+    values[i] * (1.0 - w) + values[i + 1] * w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ScheduleEntry;
+    use chrono::{Duration as ChronoDuration, TimeZone};
+
+    fn schedule(prices: &[f64], target_power_w: f64) -> Schedule {
+        let start = Utc.with_ymd_and_hms(2025, 3, 3, 12, 0, 0).unwrap();
+        let entries: Vec<ScheduleEntry> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, &price)| ScheduleEntry {
+                time_start: start + ChronoDuration::hours(i as i64),
+                time_end: start + ChronoDuration::hours(i as i64 + 1),
+                target_power_w,
+                price_sek_per_kwh: price,
+                reason: "test".to_string(),
+                ev_targets: Vec::new(),
+            })
+            .collect();
+        Schedule {
+            id: uuid::Uuid::new_v4(),
+            created_at: start,
+            valid_from: start,
+            valid_until: entries.last().unwrap().time_end,
+            entries,
+            optimizer_version: "test".to_string(),
+            cost_distribution: None,
+        }
+    }
+
+    fn measurement(soc_percent: f64, pv_kw: f64, house_load_kw: f64) -> MpcMeasurement {
+        MpcMeasurement {
+            timestamp: Utc.with_ymd_and_hms(2025, 3, 3, 12, 30, 0).unwrap(),
+            soc_percent,
+            pv_kw,
+            house_load_kw,
+        }
+    }
+
+    #[test]
+    fn test_stores_unexpected_pv_surplus() {
+        // The plan idles, but PV turned out 4 kW above the house load and
+        // the battery has room for all of it
+        let schedule = schedule(&[1.0, 1.0, 2.0, 2.0], 0.0);
+        let solution = MpcPlanner::new(MpcSettings::default())
+            .solve(
+                &measurement(30.0, 5.0, 1.0),
+                &schedule,
+                None,
+                &Constraints::default(),
+            )
+            .unwrap();
+
+        assert!(solution.target_power_w > 3000.0, "{:?}", solution);
+        assert_eq!(solution.planned_power_w.len(), 4);
+    }
+
+    #[test]
+    fn test_respects_measured_soc() {
+        // The plan wants to discharge, but the battery is already at its minimum
+        let constraints = Constraints::default();
+        let schedule = schedule(&[3.0, 3.0], -5000.0);
+        let solution = MpcPlanner::new(MpcSettings::default())
+            .solve(
+                &measurement(constraints.min_soc_percent, 0.0, 2.0),
+                &schedule,
+                None,
+                &constraints,
+            )
+            .unwrap();
+
+        assert!(solution.target_power_w >= 0.0, "{:?}", solution);
+    }
+
+    #[test]
+    fn test_nothing_left_to_solve_is_an_error() {
+        let schedule = schedule(&[1.0], 0.0);
+        let mut late = measurement(50.0, 0.0, 1.0);
+        late.timestamp = schedule.valid_until;
+
+        let result = MpcPlanner::new(MpcSettings::default()).solve(
+            &late,
+            &schedule,
+            None,
+            &Constraints::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_zero_budget_falls_back() {
+        // A fine SoC grid makes the solve slow enough to always miss the budget
+        let planner = std::sync::Arc::new(MpcPlanner::new(MpcSettings {
+            budget: Duration::ZERO,
+            soc_step_percent: 0.001,
+            ..MpcSettings::default()
+        }));
+
+        let solution = planner
+            .solve_within_budget(
+                measurement(50.0, 0.0, 1.0),
+                schedule(&[1.0; 6], 0.0),
+                None,
+                Constraints::default(),
+            )
+            .await;
+        assert!(solution.is_none());
+    }
+
+    #[test]
+    fn test_interpolate_between_grid_points() {
+        let grid = [0.0, 1.0, 2.0];
+        let values = [0.0, 10.0, 30.0];
+        assert_eq!(interpolate(&grid, &values, 1.5), 20.0);
+        assert_eq!(interpolate(&grid, &values, -1.0), 0.0);
+        assert_eq!(interpolate(&grid, &values, 5.0), 30.0);
+    }
+}
@@ -2,16 +2,22 @@
 //! MILP (Mixed-Integer Linear Programming) Optimizer
 //!
 //! This module implements an exact optimization strategy using linear programming
-//! to solve the 24-hour battery scheduling problem. MILP is the industry standard
+//! to solve the battery scheduling problem. MILP is the industry standard
 //! for optimal battery scheduling with complex constraints.
 //!
 //! The formulation considers:
-//! - Energy prices over 24h horizon
+//! - Energy prices over up to 48h, one period per price period (15-60 min)
+//! - PV production, with grid import and export as separate flows
+//! - Export revenue at the export price and the grid export limit
+//! - Optional PV curtailment
 //! - Battery SoC bounds (min/max)
-//! - Charge/discharge power limits
+//! - Charge/discharge power limits, less frequency reserve capacity sold
+//!   for committed hours, and the SoC range full activation needs
 //! - Grid power limits (fuse limits)
 //! - Battery efficiency losses
 //! - Cycle count constraints
+//! - Connected EVs: energy needed by departure, charger min/max current and
+//!   optional V2G, scheduled jointly with the battery
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -21,9 +27,37 @@ use uuid::Uuid;
 #[cfg(feature = "optimization")]
 use good_lp::*;
 
-use crate::domain::{Forecast24h, Schedule, ScheduleEntry};
+use crate::domain::{Forecast24h, PricePoint, Schedule, ScheduleEntry};
+use crate::optimizer::ev::ev_targets;
 use crate::optimizer::{Constraints, OptimizationStrategy, SystemState};
 
+/// Cost per exported kWh that tips ties towards storing PV (SEK/kWh)
+const SELF_CONSUMPTION_TIE_BREAK_SEK_PER_KWH: f64 = 0.01;
+
+/// Cost per curtailed kWh when curtailment is not allowed (SEK/kWh)
+///
+/// High enough that PV is only curtailed when the battery, the house and
+/// the export limit cannot absorb it; the inverter clips it then anyway.
+const FORCED_CURTAILMENT_PENALTY_SEK_PER_KWH: f64 = 100.0;
+
+/// Cost per kWh an EV is short of its target at departure (SEK/kWh)
+///
+/// Keeps the problem feasible when the need cannot be met in time while
+/// making sure the vehicle is charged whenever it can be.
+const EV_SHORTFALL_PENALTY_SEK_PER_KWH: f64 = 50.0;
+
+/// Periods above which the solve may be too slow for a Raspberry Pi
+/// (24 h at 15-minute resolution)
+const LARGE_PROBLEM_PERIODS: usize = 96;
+
+/// Solved power plans (kW)
+struct MilpSolution {
+    /// Battery net power per period (positive = charge)
+    battery_kw: Vec<f64>,
+    /// Net power per period of each requested EV (positive = charge)
+    ev_kw: Vec<Vec<f64>>,
+}
+
 /// MILP Optimizer using linear programming for exact solutions
 pub struct MilpOptimizer {
     /// Solver to use (CBC, HiGHS, etc.)
@@ -64,10 +98,17 @@ impl MilpOptimizer {
         state: &SystemState,
         forecast: &Forecast24h,
         constraints: &Constraints,
-    ) -> Result<Vec<f64>> {
+    ) -> Result<MilpSolution> {
+        use crate::domain::max_horizon;
+        use crate::optimizer::ev::available_hours;
+        use crate::optimizer::{
+            BatteryLimits, EvChargeRequest, RESERVE_SHORTFALL_PENALTY_SEK_PER_PERCENT,
+        };
+        use chrono::DurationRound;
         use good_lp::*;
 
-        let n_periods = forecast.prices.len();
+        let n_periods = forecast.periods_within(max_horizon());
+        let periods = &forecast.prices[..n_periods];
         if n_periods == 0 {
             anyhow::bail!("No price periods available");
         }
@@ -77,7 +118,7 @@ impl MilpOptimizer {
         // and ThermalZone constraints, complexity can explode on a Raspberry Pi.
         //
         // Mitigation strategies:
-        // 1. Limit problem size to 24 periods (hourly resolution for 24h)
+        // 1. Limit problem size to 96 periods (24h at 15-minute resolution)
         // 2. Use continuous relaxation (Linear Programming) instead of true MILP
         //    for faster solve times. This is acceptable for battery scheduling
         //    where power can be continuous.
@@ -87,10 +128,10 @@ impl MilpOptimizer {
         //
         // WARNING: If solver hangs on Raspberry Pi, reduce n_periods or switch to
         // greedy/DP strategy for real-time control.
-        if n_periods > 48 {
+        if n_periods > LARGE_PROBLEM_PERIODS {
             tracing::warn!(
                 "MILP solver received {} periods. This may be too large for Raspberry Pi. \
-                 Consider reducing to 24 periods (hourly) for faster solve times.",
+                 Consider a longer time_step_minutes or a shorter horizon_hours.",
                 n_periods
             );
         }
@@ -105,15 +146,88 @@ impl MilpOptimizer {
         // charge[t] = charging power at time t (kW)
         // discharge[t] = discharging power at time t (kW)
         // soc[t] = state of charge at time t (%)
-        // peak_power = maximum grid import power across all periods (kW) - for Effekttariff
+        // grid_import[t] / grid_export[t] = power bought from / sold to the grid (kW)
+        // curtail[t] = PV production not taken from the panels (kW)
+        // peak_power = highest weighted grid import across all periods (kW) - for Effekttariff
         let charge = problem.add_vector(variable().min(0.0), n_periods);
         let discharge = problem.add_vector(variable().min(0.0), n_periods);
         let soc = problem.add_vector(variable().min(0.0).max(100.0), n_periods + 1);
-        let peak_power = problem.add(variable().min(0.0));
+        let grid_import = problem.add_vector(
+            variable().min(0.0).max(constraints.max_power_grid_kw),
+            n_periods,
+        );
+        let grid_export = problem.add_vector(
+            variable().min(0.0).max(constraints.max_export_grid_kw.max(0.0)),
+            n_periods,
+        );
+        let curtail = problem.add_vector(variable().min(0.0), n_periods);
+        // Battery power above the wear model's high-power threshold (kW)
+        let high_power = problem.add_vector(variable().min(0.0), n_periods);
+        // SoC outside a reserve hour's range at the end of each period (%)
+        let reserve_shortfall = problem.add_vector(variable().min(0.0), n_periods);
+        // Peaks already on this month's bill are paid for either way
+        let peak_power =
+            problem.add(variable().min(constraints.peak_power_incurred_kw.max(0.0)));
+
+        // Per connected EV:
+        // charge[t] / discharge[t] = power into / out of the vehicle (kW),
+        // zero once it has left
+        // on[t] = charger active, so charging stays at or above its minimum current
+        // shortfall = energy still missing at departure (kWh)
+        struct EvVariables<'a> {
+            request: &'a EvChargeRequest,
+            available_h: Vec<f64>,
+            max_charge_kw: f64,
+            charge: Vec<Variable>,
+            discharge: Vec<Variable>,
+            on: Vec<Variable>,
+            shortfall: Variable,
+        }
+        let evs: Vec<EvVariables> = state
+            .evs
+            .iter()
+            .map(|request| {
+                let available_h = available_hours(forecast, n_periods, request);
+                let max_charge_kw = if request.ev.connected {
+                    request.max_charge_kw()
+                } else {
+                    0.0
+                };
+                let max_discharge_kw = if request.ev.connected {
+                    request.max_discharge_kw(constraints)
+                } else {
+                    0.0
+                };
+                let charge = available_h
+                    .iter()
+                    .map(|&h| {
+                        let max = if h > 0.0 { max_charge_kw } else { 0.0 };
+                        problem.add(variable().min(0.0).max(max))
+                    })
+                    .collect();
+                let discharge = available_h
+                    .iter()
+                    .map(|&h| {
+                        let max = if h > 0.0 { max_discharge_kw } else { 0.0 };
+                        problem.add(variable().min(0.0).max(max))
+                    })
+                    .collect();
+                let on = problem.add_vector(variable().binary(), n_periods);
+                let shortfall = problem.add(variable().min(0.0));
+                EvVariables {
+                    request,
+                    available_h,
+                    max_charge_kw,
+                    charge,
+                    discharge,
+                    on,
+                    shortfall,
+                }
+            })
+            .collect();
 
         // Calculate time step durations (in hours)
-        let durations: Vec<f64> = forecast
-            .prices
+        let durations: Vec<f64> = periods
             .iter()
             .map(|p| {
                 let duration = p.time_end.signed_duration_since(p.time_start);
@@ -122,49 +236,126 @@ impl MilpOptimizer {
             .collect();
 
         // Extract prices
-        let prices: Vec<f64> = forecast
-            .prices
+        let prices: Vec<f64> = periods
             .iter()
             .map(|p| p.price_sek_per_kwh)
             .collect();
 
+        let export_prices: Vec<f64> = periods
+            .iter()
+            .map(|p| export_price(p, constraints))
+            .collect();
+
+        // Power and SoC range left after any reserve commitment
+        let limits: Vec<BatteryLimits> = periods
+            .iter()
+            .map(|p| constraints.battery_limits_at(p.time_start))
+            .collect();
+
+        // Share of each period's import that counts towards the tariff peak
+        let peak_weights: Vec<f64> = periods
+            .iter()
+            .map(|p| constraints.peak_tariff.weight(p.time_start))
+            .collect();
+
         // AUDIT FIX #4: Extract consumption forecast to account for house load in constraints
-        // If consumption data is available, use it; otherwise assume conservative 2kW baseline
-        let consumption: Vec<f64> = if forecast.consumption.len() == n_periods {
-            forecast.consumption.iter().map(|c| c.load_kw).collect()
-        } else {
-            vec![2.0; n_periods] // Conservative 2kW baseline house load
+        // Averaged over each price period; where there is none, assume a conservative 2kW baseline
+        let consumption: Vec<f64> = periods
+            .iter()
+            .map(|p| forecast.load_kw_between(p.time_start, p.time_end).unwrap_or(2.0))
+            .collect();
+
+        // PV forecast; without one the panels are assumed to produce nothing
+        let production: Vec<f64> = periods
+            .iter()
+            .map(|p| forecast.pv_kw_between(p.time_start, p.time_end).unwrap_or(0.0))
+            .collect();
+
+        // The tariff counts the mean power of each clock hour, so shorter
+        // periods are grouped by the hour they fall in
+        let hour_of = |t: usize| {
+            periods[t]
+                .time_start
+                .duration_trunc(chrono::Duration::hours(1))
+                .ok()
         };
+        let mut peak_hours: Vec<Vec<usize>> = Vec::new();
+        for t in 0..n_periods {
+            let same_hour = t > 0 && hour_of(t) == hour_of(t - 1);
+            match peak_hours.last_mut() {
+                Some(hour) if same_hour => hour.push(t),
+                _ => peak_hours.push(vec![t]),
+            }
+        }
 
         // Build the optimization problem
-        // Objective: Minimize energy cost + peak power penalty (Effekttariff) + battery wear cost
+        // Objective: Minimize import cost - export revenue + peak power penalty (Effekttariff)
+        // + battery wear cost
         let energy_cost = (0..n_periods)
-            .map(|t| prices[t] * durations[t] * (charge[t] - discharge[t]))
+            .map(|t| {
+                durations[t] * (prices[t] * grid_import[t] - export_prices[t] * grid_export[t])
+            })
+            .sum::<Expression>();
+
+        // Storing PV is preferred over exporting it at the same value, and
+        // curtailment is a last resort unless the schedule may plan it
+        let self_consumption_weight = if constraints.prefer_self_consumption {
+            SELF_CONSUMPTION_TIE_BREAK_SEK_PER_KWH
+        } else {
+            0.0
+        };
+        let curtailment_weight = if constraints.allow_pv_curtailment {
+            0.0
+        } else {
+            FORCED_CURTAILMENT_PENALTY_SEK_PER_KWH
+        };
+        let pv_preference_cost = (0..n_periods)
+            .map(|t| {
+                durations[t]
+                    * (self_consumption_weight * grid_export[t] + curtailment_weight * curtail[t])
+            })
             .sum::<Expression>();
 
         // Peak power tariff penalty (Swedish "Effekttariff")
-        // This is charged monthly based on the maximum hourly average power
+        // This is charged monthly based on the top-N hourly average powers
         // Typical: 50-120 SEK/kW/month
-        // We amortize the monthly charge over the 24h optimization period
-        let peak_power_penalty = peak_power * constraints.peak_power_tariff_sek_per_kw;
+        // peak_power is bounded below by the peak already incurred, so only the
+        // increase above it changes the objective
+        let peak_power_penalty = peak_power * constraints.marginal_peak_cost_sek_per_kw();
 
         // CRITICAL FIX #3: Battery degradation cost
         // Real LiFePO4 batteries cost ~0.50 - 1.00 SEK per cycled kWh in wear
         // Without this, optimizer will cycle battery to save 0.01 SEK, destroying a 50,000 SEK battery
-        // Wear cost = (charge + discharge) * degradation_per_cycle * replacement_cost / capacity
-        let wear_cost_per_kwh = (constraints.battery_degradation_per_cycle
-            * constraints.battery_replacement_cost_sek)
-            / constraints.battery_capacity_kwh;
-
+        // The linearized degradation model prices throughput, power above the
+        // high-power threshold and the SoC held through each period
+        let wear = constraints.wear();
         let battery_wear_cost = (0..n_periods)
             .map(|t| {
                 // Both charging and discharging cause wear (one full cycle = charge + discharge)
                 // Multiply by duration to convert power (kW) to energy (kWh)
-                wear_cost_per_kwh * durations[t] * (charge[t] + discharge[t])
+                durations[t]
+                    * (wear.throughput_sek_per_kwh * (charge[t] + discharge[t])
+                        + wear.high_power_sek_per_kwh * high_power[t]
+                        + wear.soc_sek_per_percent_hour * soc[t])
             })
             .sum::<Expression>();
 
-        let objective = energy_cost + peak_power_penalty + battery_wear_cost;
+        let ev_shortfall_cost = evs
+            .iter()
+            .map(|ev| EV_SHORTFALL_PENALTY_SEK_PER_KWH * ev.shortfall)
+            .sum::<Expression>();
+
+        let reserve_shortfall_cost = reserve_shortfall
+            .iter()
+            .map(|&shortfall| RESERVE_SHORTFALL_PENALTY_SEK_PER_PERCENT * shortfall)
+            .sum::<Expression>();
+
+        let objective = energy_cost
+            + peak_power_penalty
+            + battery_wear_cost
+            + pv_preference_cost
+            + ev_shortfall_cost
+            + reserve_shortfall_cost;
 
         let mut problem_builder = problem.minimise(objective).using(default_solver);
 
@@ -185,28 +376,42 @@ impl MilpOptimizer {
             problem_builder = problem_builder.with(constraint!(soc[t + 1] == soc[t] + soc_delta));
 
             // Power limits
-            // Battery charge rate limit
+            // Battery charge rate limit, less any power sold as FCR-D down
             problem_builder =
-                problem_builder.with(constraint!(charge[t] <= constraints.battery_max_charge_kw));
+                problem_builder.with(constraint!(charge[t] <= limits[t].max_charge_kw));
 
-            // Battery discharge rate limit
+            // Battery discharge rate limit, less any power sold as FCR-D up and FFR
+            problem_builder =
+                problem_builder.with(constraint!(discharge[t] <= limits[t].max_discharge_kw));
+
+            // Power above the threshold pays the high-power wear cost
+            if wear.high_power_sek_per_kwh > 0.0 {
+                problem_builder = problem_builder
+                    .with(constraint!(
+                        high_power[t] >= charge[t] - wear.high_power_threshold_kw
+                    ))
+                    .with(constraint!(
+                        high_power[t] >= discharge[t] - wear.high_power_threshold_kw
+                    ));
+            }
+
+            // Power balance: PV + import + discharge = house + charge + export
+            // AUDIT FIX #4: The fuse limit (bound on grid_import) thereby covers the
+            // house load too, so charging cannot push the total draw past the fuse
+            let ev_charging = evs.iter().map(|ev| ev.charge[t]).sum::<Expression>();
+            let ev_discharging = evs.iter().map(|ev| ev.discharge[t]).sum::<Expression>();
             problem_builder = problem_builder.with(constraint!(
-                discharge[t] <= constraints.battery_max_discharge_kw
+                grid_import[t] + discharge[t] + ev_discharging.clone() - curtail[t] + production[t]
+                    == charge[t] + ev_charging + grid_export[t] + consumption[t]
             ));
 
-            // AUDIT FIX #4: Fuse limit must account for total grid import (house + battery)
-            // Without this, the optimizer could command 11kW charging while house draws 6kW,
-            // causing a 17kW total draw that blows the 11kW fuse.
-            // The fuse limit is reduced by the house consumption to get max battery charge
-            let max_charge_with_house = constraints.max_power_grid_kw - consumption[t];
-            problem_builder = problem_builder.with(constraint!(charge[t] <= max_charge_with_house));
+            // Only PV and the batteries can be exported, not energy bought in the same period
+            problem_builder = problem_builder.with(constraint!(
+                grid_export[t] + curtail[t] <= discharge[t] + ev_discharging + production[t]
+            ));
 
-            // CRITICAL FIX #2: Peak power tracking for Effekttariff
-            // peak_power must be >= total grid import at every time period
-            // Total grid import = house consumption + battery charging (discharge reduces grid import)
-            // This forces the optimizer to minimize the maximum grid power across all periods
-            let total_grid_import = consumption[t] + charge[t];
-            problem_builder = problem_builder.with(constraint!(peak_power >= total_grid_import));
+            // Curtailment cannot exceed production
+            problem_builder = problem_builder.with(constraint!(curtail[t] <= production[t]));
 
             // SoC bounds
             problem_builder =
@@ -214,6 +419,71 @@ impl MilpOptimizer {
 
             problem_builder =
                 problem_builder.with(constraint!(soc[t + 1] <= constraints.max_soc_percent));
+
+            // A reserve hour must keep energy and room for full activation,
+            // from its start (the end of the period before) to its end;
+            // the outage reserve holds the same way in every period
+            if constraints.has_soc_band_at(periods[t].time_start) {
+                let band = limits[t];
+                problem_builder = problem_builder
+                    .with(constraint!(
+                        soc[t + 1] + reserve_shortfall[t] >= band.min_soc_percent
+                    ))
+                    .with(constraint!(
+                        soc[t + 1] - reserve_shortfall[t] <= band.max_soc_percent
+                    ));
+                if t > 0 {
+                    problem_builder = problem_builder
+                        .with(constraint!(
+                            soc[t] + reserve_shortfall[t - 1] >= band.min_soc_percent
+                        ))
+                        .with(constraint!(
+                            soc[t] - reserve_shortfall[t - 1] <= band.max_soc_percent
+                        ));
+                }
+            }
+        }
+
+        // CRITICAL FIX #2: Peak power tracking for Effekttariff
+        // peak_power must be >= weighted mean grid import of every counted hour
+        // This forces the optimizer to minimize the maximum grid power across all hours
+        for hour in &peak_hours {
+            let hour_h: f64 = hour.iter().map(|&t| durations[t]).sum();
+            if hour_h <= 0.0 || hour.iter().all(|&t| peak_weights[t] <= 0.0) {
+                continue;
+            }
+            let mean_import = hour
+                .iter()
+                .map(|&t| peak_weights[t] * durations[t] / hour_h * grid_import[t])
+                .sum::<Expression>();
+            problem_builder = problem_builder.with(constraint!(peak_power >= mean_import));
+        }
+
+        // EV constraints
+        for ev in &evs {
+            let request = ev.request;
+            let min_charge_kw = request.min_charge_kw();
+            let stored_kwh = request.ev.capacity_kwh * request.ev.soc_percent / 100.0;
+            let mut delivered = Expression::from(0.0);
+            for t in 0..n_periods {
+                // Below the minimum current the charger cannot charge at all
+                problem_builder = problem_builder
+                    .with(constraint!(ev.charge[t] <= ev.max_charge_kw * ev.on[t]))
+                    .with(constraint!(ev.charge[t] >= min_charge_kw * ev.on[t]));
+
+                // The vehicle's battery stays between empty and full
+                delivered += ev.available_h[t] * (ev.charge[t] - ev.discharge[t]);
+                problem_builder = problem_builder
+                    .with(constraint!(
+                        delivered.clone() <= request.ev.capacity_kwh - stored_kwh
+                    ))
+                    .with(constraint!(delivered.clone() >= -stored_kwh));
+            }
+
+            // The energy needed by departure, or whatever is missing is penalised
+            problem_builder = problem_builder.with(constraint!(
+                delivered + ev.shortfall >= request.ev.energy_needed_kwh()
+            ));
         }
 
         // Solve the optimization problem
@@ -230,7 +500,19 @@ impl MilpOptimizer {
             })
             .collect();
 
-        Ok(power_schedule)
+        let ev_kw = evs
+            .iter()
+            .map(|ev| {
+                (0..n_periods)
+                    .map(|t| solution.value(ev.charge[t]) - solution.value(ev.discharge[t]))
+                    .collect()
+            })
+            .collect();
+
+        Ok(MilpSolution {
+            battery_kw: power_schedule,
+            ev_kw,
+        })
     }
 
     #[cfg(not(feature = "optimization"))]
@@ -239,11 +521,23 @@ impl MilpOptimizer {
         _state: &SystemState,
         _forecast: &Forecast24h,
         _constraints: &Constraints,
-    ) -> Result<Vec<f64>> {
+    ) -> Result<MilpSolution> {
         anyhow::bail!("MILP optimization requires 'optimization' feature to be enabled");
     }
 }
 
+/// Price earned per exported kWh in a period
+///
+/// An explicit export price wins; otherwise the spot price less the
+/// configured export fee, falling back to the price point's default.
+pub(crate) fn export_price(price: &PricePoint, constraints: &Constraints) -> f64 {
+    match (price.export_price_sek_per_kwh, constraints.export_fee_sek_per_kwh) {
+        (Some(export_price), _) => export_price,
+        (None, Some(fee)) => price.price_sek_per_kwh - fee,
+        (None, None) => price.export_price(),
+    }
+}
+
 #[async_trait]
 impl OptimizationStrategy for MilpOptimizer {
     async fn optimize(
@@ -259,14 +553,15 @@ impl OptimizationStrategy for MilpOptimizer {
         }
 
         // Solve the LP problem
-        let power_schedule = self
+        let solution = self
             .solve_lp(state, forecast, constraints)
             .context("MILP solver failed")?;
 
         // Convert solution to schedule entries
         let mut entries = Vec::new();
-        for (i, price_point) in forecast.prices.iter().enumerate() {
-            let target_power_kw = power_schedule[i];
+        let n_periods = solution.battery_kw.len();
+        for (i, price_point) in forecast.prices.iter().take(n_periods).enumerate() {
+            let target_power_kw = solution.battery_kw[i];
             let target_power_w = target_power_kw * 1000.0;
 
             let reason = if target_power_w > 100.0 {
@@ -283,6 +578,7 @@ impl OptimizationStrategy for MilpOptimizer {
                 target_power_w,
                 price_sek_per_kwh: price_point.price_sek_per_kwh,
                 reason: reason.to_string(),
+                ev_targets: ev_targets(&state.evs, &solution.ev_kw, i),
             });
         }
 
@@ -296,6 +592,7 @@ impl OptimizationStrategy for MilpOptimizer {
             valid_until,
             entries,
             optimizer_version: "milp-v1.0".to_string(),
+            cost_distribution: None,
         })
     }
 }
@@ -303,8 +600,45 @@ impl OptimizationStrategy for MilpOptimizer {
 #[cfg(all(test, feature = "optimization"))]
 mod tests {
     use super::*;
-    use crate::domain::{BatteryState, PriceArea, PricePoint};
-    use chrono::Duration;
+    use crate::domain::{BatteryState, ConsumptionPoint, PriceArea, PricePoint, ProductionPoint};
+    use crate::optimizer::EvChargeRequest;
+    use crate::power_flow::inputs::EvState;
+    use chrono::{Duration, DurationRound};
+
+    fn battery_state(soc_percent: f64) -> SystemState {
+        SystemState {
+            battery: BatteryState {
+                soc_percent,
+                power_w: 0.0,
+                voltage_v: 48.0,
+                temperature_c: 25.0,
+                status: crate::domain::BatteryStatus::Idle,
+                health_percent: 100.0,
+            },
+            evs: Vec::new(),
+        }
+    }
+
+    /// Flat 1 SEK/kWh day with 1 kW house load and `pv_peak_kw` of PV
+    /// between 10:00 and 15:00
+    fn create_sunny_forecast(pv_peak_kw: f64) -> Forecast24h {
+        let mut forecast = create_test_forecast();
+        forecast.consumption.clear();
+        for (i, price) in forecast.prices.iter_mut().enumerate() {
+            price.price_sek_per_kwh = 1.0;
+            forecast.consumption.push(ConsumptionPoint {
+                time_start: price.time_start,
+                time_end: price.time_end,
+                load_kw: 1.0,
+            });
+            forecast.production.push(ProductionPoint {
+                time_start: price.time_start,
+                time_end: price.time_end,
+                pv_kw: if (10..15).contains(&i) { pv_peak_kw } else { 0.0 },
+            });
+        }
+        forecast
+    }
 
     fn create_test_forecast() -> Forecast24h {
         let now = Utc::now();
@@ -354,6 +688,7 @@ mod tests {
                 status: crate::domain::BatteryStatus::Idle,
                 health_percent: 100.0,
             },
+            evs: Vec::new(),
         };
         let constraints = Constraints::default();
         let forecast = create_test_forecast();
@@ -398,6 +733,7 @@ mod tests {
                 status: crate::domain::BatteryStatus::Idle,
                 health_percent: 100.0,
             },
+            evs: Vec::new(),
         };
         let constraints = Constraints::default();
         let forecast = create_test_forecast();
@@ -432,4 +768,235 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_milp_stores_surplus_pv_instead_of_buying() {
+        let optimizer = MilpOptimizer::default();
+        let constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 0.0,
+            battery_degradation_per_cycle: 0.00001,
+            ..Constraints::default()
+        };
+        let forecast = create_sunny_forecast(5.0);
+
+        let schedule = optimizer
+            .optimize(&battery_state(30.0), &forecast, &constraints)
+            .await
+            .unwrap();
+
+        // Exporting earns 0.4 SEK/kWh, while stored PV saves 1 SEK/kWh later
+        let sunny_charge_w: f64 = schedule.entries[10..15]
+            .iter()
+            .map(|e| e.target_power_w.max(0.0))
+            .sum();
+        assert!(sunny_charge_w > 1000.0, "surplus PV should be stored");
+
+        // The price is flat, so buying energy to store it only adds losses
+        for entry in schedule.entries[..10].iter().chain(&schedule.entries[15..]) {
+            assert!(entry.target_power_w <= 1.0, "charged from grid: {:?}", entry);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_milp_feasible_when_pv_exceeds_export_limit() {
+        let optimizer = MilpOptimizer::default();
+        let constraints = Constraints {
+            max_export_grid_kw: 3.0,
+            ..Constraints::default()
+        };
+        // 10 kW of PV against 1 kW of house load, 3 kW of export and 5 kW
+        // of charging leaves 1 kW that only curtailment can absorb
+        let forecast = create_sunny_forecast(10.0);
+
+        let schedule = optimizer
+            .optimize(&battery_state(90.0), &forecast, &constraints)
+            .await
+            .unwrap();
+
+        assert_eq!(schedule.entries.len(), 24);
+        for entry in &schedule.entries[10..15] {
+            assert!(entry.target_power_w <= constraints.battery_max_charge_kw * 1000.0 + 1.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_milp_charges_freely_below_incurred_peak() {
+        let optimizer = MilpOptimizer::default();
+        let mut constraints = Constraints {
+            peak_power_tariff_sek_per_kw: 1000.0,
+            ..Constraints::default()
+        };
+        // One cheap hour against the assumed 2 kW house load
+        let mut forecast = create_test_forecast();
+        for (i, price) in forecast.prices.iter_mut().enumerate() {
+            price.price_sek_per_kwh = if i == 3 { 0.1 } else { 2.0 };
+        }
+        let charge_w = |schedule: &Schedule| schedule.entries[3].target_power_w;
+
+        let fresh = optimizer
+            .optimize(&battery_state(20.0), &forecast, &constraints)
+            .await
+            .unwrap();
+        assert!(charge_w(&fresh) < 1000.0, "new peak should be avoided");
+
+        // A 7 kW peak is already on the bill, so charging at 5 kW costs nothing extra
+        constraints.peak_power_incurred_kw = 7.0;
+        let incurred = optimizer
+            .optimize(&battery_state(20.0), &forecast, &constraints)
+            .await
+            .unwrap();
+        assert!(charge_w(&incurred) > 4900.0);
+    }
+
+    #[tokio::test]
+    async fn test_milp_plans_quarter_hour_periods() {
+        // A cheap hour then an expensive one, in quarter-hour prices, with an hourly load
+        let start = Utc::now().duration_trunc(Duration::hours(1)).unwrap();
+        let forecast = Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start,
+            prices: (0..8)
+                .map(|i| PricePoint {
+                    time_start: start + Duration::minutes(15 * i),
+                    time_end: start + Duration::minutes(15 * (i + 1)),
+                    price_sek_per_kwh: if i < 4 { 0.1 } else { 3.0 },
+                    export_price_sek_per_kwh: None,
+                })
+                .collect(),
+            consumption: (0..2)
+                .map(|i| ConsumptionPoint {
+                    time_start: start + Duration::hours(i),
+                    time_end: start + Duration::hours(i + 1),
+                    load_kw: 1.0,
+                })
+                .collect(),
+            production: vec![],
+        };
+
+        let schedule = MilpOptimizer::default()
+            .optimize(&battery_state(20.0), &forecast, &Constraints::default())
+            .await
+            .unwrap();
+
+        assert_eq!(schedule.entries.len(), 8);
+        assert!(schedule.validate().is_ok());
+        let first_hour_w: f64 = schedule.entries[..4].iter().map(|e| e.target_power_w).sum();
+        assert!(first_hour_w > 0.0, "charges in the cheap hour");
+    }
+
+    #[tokio::test]
+    async fn test_milp_charges_ev_in_cheap_hours_before_departure() {
+        let optimizer = MilpOptimizer::default();
+        let forecast = create_test_forecast();
+        let mut state = battery_state(20.0);
+        state.evs.push(EvChargeRequest {
+            charger_id: "drive".to_string(),
+            ev: EvState {
+                connected: true,
+                soc_percent: 40.0,
+                capacity_kwh: 60.0,
+                max_charge_kw: 11.0,
+                max_discharge_kw: 0.0,
+                departure_time: Some(forecast.prices[8].time_start),
+                target_soc_percent: 80.0,
+            },
+            min_current_a: 6.0,
+            max_current_a: 16.0,
+            phases: 3,
+            voltage_v: 230.0,
+            bidirectional: false,
+        });
+
+        let schedule = optimizer
+            .optimize(&state, &forecast, &Constraints::default())
+            .await
+            .unwrap();
+
+        let ev_kw: Vec<f64> = schedule
+            .entries
+            .iter()
+            .map(|e| e.ev_power_w("drive").unwrap_or(0.0) / 1000.0)
+            .collect();
+        // 24 kWh needed, all of it in the cheap night hours before departure
+        let night_kwh: f64 = ev_kw[..6].iter().sum();
+        assert!((night_kwh - 24.0).abs() < 0.1, "night: {night_kwh}");
+        assert!(ev_kw[6..].iter().all(|&kw| kw.abs() < 1e-6));
+        assert!(ev_kw
+            .iter()
+            .all(|&kw| kw < 1e-6 || (4.1..=11.0 + 1e-6).contains(&kw)));
+    }
+
+    #[tokio::test]
+    async fn test_milp_discharges_only_the_v2x_charger() {
+        use crate::power_flow::inputs::EvChargerInput;
+
+        let optimizer = MilpOptimizer::default();
+        let forecast = create_test_forecast();
+        let mut state = battery_state(50.0);
+        // Two V2X-capable chargers with full vehicles; V2X drives the first
+        for (id, v2x) in [("driveway", true), ("garage", false)] {
+            let input = EvChargerInput {
+                id: id.to_string(),
+                ev: EvState {
+                    connected: true,
+                    soc_percent: 90.0,
+                    capacity_kwh: 60.0,
+                    max_charge_kw: 11.0,
+                    max_discharge_kw: 11.0,
+                    departure_time: None,
+                    target_soc_percent: 50.0,
+                },
+                phases: 3,
+                phase: 1,
+                min_current_a: 6.0,
+                max_current_a: 16.0,
+                priority: 0,
+                scheduled_power_kw: None,
+            };
+            let request = EvChargeRequest::from_input(&input, 230.0, v2x);
+            state.evs.push(request);
+        }
+        let discharged_w = |schedule: &Schedule, id: &str| -> f64 {
+            schedule
+                .entries
+                .iter()
+                .map(|e| (-e.ev_power_w(id).unwrap_or(0.0)).max(0.0))
+                .sum()
+        };
+
+        // V2G off by default: neither vehicle is discharged
+        let schedule = optimizer
+            .optimize(&state, &forecast, &Constraints::default())
+            .await
+            .unwrap();
+        assert!(discharged_w(&schedule, "driveway") < 1e-3);
+        assert!(discharged_w(&schedule, "garage") < 1e-3);
+
+        // With V2G the driven vehicle covers house load, the other one never does
+        let constraints = Constraints {
+            v2g_enabled: true,
+            ..Constraints::default()
+        };
+        let schedule = optimizer
+            .optimize(&state, &forecast, &constraints)
+            .await
+            .unwrap();
+        assert!(discharged_w(&schedule, "driveway") > 0.0);
+        assert!(discharged_w(&schedule, "garage") < 1e-3);
+    }
+
+    #[test]
+    fn test_milp_export_price_uses_fee() {
+        let forecast = create_sunny_forecast(5.0);
+        let mut price = forecast.prices[0].clone();
+        let mut constraints = Constraints::default();
+
+        assert!((export_price(&price, &constraints) - 0.4).abs() < 1e-9);
+
+        constraints.export_fee_sek_per_kwh = Some(0.1);
+        assert!((export_price(&price, &constraints) - 0.9).abs() < 1e-9);
+
+        price.export_price_sek_per_kwh = Some(0.7);
+        assert!((export_price(&price, &constraints) - 0.7).abs() < 1e-9);
+    }
 }
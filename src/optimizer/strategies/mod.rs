@@ -4,7 +4,10 @@
 //! - Greedy: Simple rule-based optimizer
 //! - DP: Dynamic programming optimizer
 //! - MILP: Mixed-integer linear programming optimizer (exact solution)
+//! - Scenario: Picks the schedule that holds up best under forecast errors
 
 pub mod milp;
+pub mod scenario;
 
 pub use milp::*;
+pub use scenario::*;
@@ -0,0 +1,531 @@
+//! Scenario-based (stochastic) optimizer
+//!
+//! The other strategies treat the forecast as exact, so a cloudy day can
+//! leave the battery full at noon or empty in the evening peak. This
+//! strategy samples forecast errors into scenarios:
+//! - PV and house load get a day-wide error plus hourly noise, since a
+//!   cloudy or busy day shifts every hour the same way
+//! - Prices are only perturbed after the day-ahead auction horizon; the
+//!   published hours are known
+//!
+//! The inner strategy plans a candidate schedule for the expected forecast
+//! and for scenarios spread over the range of outcomes. Every candidate is
+//! then replayed against every scenario, with the battery clamped at its
+//! SoC limits and the grid covering the difference. The candidate with the
+//! lowest expected cost plus a CVaR risk term wins and carries its cost
+//! distribution. Only its first hours are acted on before the next
+//! re-optimization, so this is the first-stage decision.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, NaiveTime, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::StandardNormal;
+
+use super::milp::export_price;
+use crate::domain::{CostDistribution, Forecast24h, Schedule};
+use crate::optimizer::{Constraints, OptimizationStrategy, SystemState};
+
+/// Share of the forecast error that is common to the whole day
+const DAY_ERROR_WEIGHT: f64 = 0.8;
+
+/// Nord Pool publishes the next day's prices at about 13:00 CET
+const DAY_AHEAD_PUBLICATION_HOUR: u32 = 13;
+
+/// How forecast uncertainty is sampled and how risk is weighed
+#[derive(Debug, Clone)]
+pub struct ScenarioSettings {
+    /// Number of sampled scenarios the candidates are scored against
+    pub num_scenarios: usize,
+    /// Number of candidate schedules planned by the inner strategy
+    pub num_candidates: usize,
+    /// Relative standard deviation of the PV forecast
+    pub pv_spread: f64,
+    /// Relative standard deviation of the house load forecast
+    pub load_spread: f64,
+    /// Relative standard deviation of prices not yet published day-ahead
+    pub price_spread: f64,
+    /// CVaR confidence level; the worst `1 - cvar_alpha` share is the tail
+    pub cvar_alpha: f64,
+    /// Weight of the CVaR term against the expected cost (0 = risk neutral)
+    pub risk_weight: f64,
+    /// Seed for the scenario sampler, so schedules are reproducible
+    pub seed: u64,
+}
+
+impl Default for ScenarioSettings {
+    fn default() -> Self {
+        Self {
+            num_scenarios: 30,
+            num_candidates: 5,
+            pv_spread: 0.35,
+            load_spread: 0.15,
+            price_spread: 0.25,
+            cvar_alpha: 0.9,
+            risk_weight: 0.5,
+            seed: 42,
+        }
+    }
+}
+
+/// Picks the inner strategy's schedule that holds up best across scenarios
+pub struct ScenarioOptimizer {
+    inner: Box<dyn OptimizationStrategy>,
+    settings: ScenarioSettings,
+}
+
+impl ScenarioOptimizer {
+    pub fn new(inner: Box<dyn OptimizationStrategy>, settings: ScenarioSettings) -> Self {
+        Self { inner, settings }
+    }
+
+    /// Forecasts with sampled errors, deterministic for a given seed
+    pub fn sample_scenarios(&self, forecast: &Forecast24h) -> Vec<Forecast24h> {
+        let mut rng = StdRng::seed_from_u64(self.settings.seed);
+        let known_until = day_ahead_known_until(forecast.generated_at);
+        (0..self.settings.num_scenarios)
+            .map(|_| {
+                let mut scenario = forecast.clone();
+                let pv_day: f64 = rng.sample(StandardNormal);
+                for point in &mut scenario.production {
+                    let factor = error_factor(&mut rng, pv_day, self.settings.pv_spread);
+                    point.pv_kw = (point.pv_kw * factor).max(0.0);
+                }
+                let load_day: f64 = rng.sample(StandardNormal);
+                for point in &mut scenario.consumption {
+                    let factor = error_factor(&mut rng, load_day, self.settings.load_spread);
+                    point.load_kw = (point.load_kw * factor).max(0.0);
+                }
+                let price_day: f64 = rng.sample(StandardNormal);
+                for point in scenario
+                    .prices
+                    .iter_mut()
+                    .filter(|p| p.time_start >= known_until)
+                {
+                    let factor = error_factor(&mut rng, price_day, self.settings.price_spread);
+                    point.price_sek_per_kwh *= factor;
+                    if let Some(export) = point.export_price_sek_per_kwh.as_mut() {
+                        *export *= factor;
+                    }
+                }
+                scenario
+            })
+            .collect()
+    }
+
+    /// Scenarios the candidates are planned for: evenly spread over the
+    /// range of net load, from the sunniest and quietest to the darkest
+    fn candidate_scenarios<'a>(&self, scenarios: &'a [Forecast24h]) -> Vec<&'a Forecast24h> {
+        let mut by_net_load: Vec<(f64, &Forecast24h)> = scenarios
+            .iter()
+            .map(|s| {
+                let load: f64 = s.consumption.iter().map(|c| c.load_kw).sum();
+                let pv: f64 = s.production.iter().map(|p| p.pv_kw).sum();
+                (load - pv, s)
+            })
+            .collect();
+        by_net_load.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // The expected forecast is always a candidate, the rest sit at
+        // evenly spaced ranks
+        let picks = self.settings.num_candidates.saturating_sub(1);
+        (0..picks)
+            .filter_map(|i| {
+                let rank = (i as f64 + 0.5) / picks as f64 * by_net_load.len() as f64;
+                by_net_load.get(rank as usize).map(|(_, s)| *s)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl OptimizationStrategy for ScenarioOptimizer {
+    async fn optimize(
+        &self,
+        state: &SystemState,
+        forecast: &Forecast24h,
+        constraints: &Constraints,
+    ) -> Result<Schedule> {
+        if forecast.prices.is_empty() {
+            anyhow::bail!("No price points available for optimization");
+        }
+
+        let scenarios = self.sample_scenarios(forecast);
+
+        let mut candidates = vec![self
+            .inner
+            .optimize(state, forecast, constraints)
+            .await
+            .context("Planning for the expected forecast failed")?];
+        for scenario in self.candidate_scenarios(&scenarios) {
+            match self.inner.optimize(state, scenario, constraints).await {
+                Ok(schedule) => candidates.push(schedule),
+                Err(e) => tracing::warn!(error = %e, "Scenario candidate failed, skipping it"),
+            }
+        }
+
+        let mut best: Option<(f64, Schedule, CostDistribution)> = None;
+        for candidate in candidates {
+            let costs: Vec<f64> = scenarios
+                .iter()
+                .map(|scenario| scenario_cost(&candidate, scenario, state, constraints))
+                .collect();
+            let Some(distribution) = cost_distribution(costs, self.settings.cvar_alpha) else {
+                // Without scenarios the expected forecast's plan stands
+                return Ok(candidate);
+            };
+            let score =
+                distribution.expected_sek + self.settings.risk_weight * distribution.cvar_sek;
+            if best
+                .as_ref()
+                .is_none_or(|(best_score, _, _)| score < *best_score)
+            {
+                best = Some((score, candidate, distribution));
+            }
+        }
+
+        let (_, mut schedule, distribution) =
+            best.context("Inner strategy produced no candidate schedule")?;
+        tracing::debug!(
+            expected_sek = distribution.expected_sek,
+            cvar_sek = distribution.cvar_sek,
+            "Scenario optimizer picked schedule"
+        );
+        schedule.optimizer_version = format!("scenario-v1.0/{}", schedule.optimizer_version);
+        schedule.cost_distribution = Some(distribution);
+        Ok(schedule)
+    }
+}
+
+/// Multiplicative forecast error with a day-wide and an hourly part
+fn error_factor(rng: &mut StdRng, day_error: f64, spread: f64) -> f64 {
+    let hour_error: f64 = rng.sample(StandardNormal);
+    let hour_weight = (1.0 - DAY_ERROR_WEIGHT * DAY_ERROR_WEIGHT).sqrt();
+    1.0 + spread * (DAY_ERROR_WEIGHT * day_error + hour_weight * hour_error)
+}
+
+/// End of the last delivery day whose day-ahead prices are published
+fn day_ahead_known_until(now: DateTime<Utc>) -> DateTime<Utc> {
+    let tz = chrono_tz::Europe::Stockholm;
+    let local = now.with_timezone(&tz);
+    let days = if local.time() >= NaiveTime::from_hms_opt(DAY_AHEAD_PUBLICATION_HOUR, 0, 0).unwrap()
+    {
+        2
+    } else {
+        1
+    };
+    (local.date_naive() + Duration::days(days))
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(tz)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or(now)
+}
+
+/// Cost of following `schedule` when `scenario` comes true (SEK)
+///
+/// The battery follows the plan until it hits its SoC limits and the grid
+/// covers the rest. Import, export, battery wear and any new tariff peak
+/// are counted as in the MILP objective.
+pub fn scenario_cost(
+    schedule: &Schedule,
+    scenario: &Forecast24h,
+    state: &SystemState,
+    constraints: &Constraints,
+) -> f64 {
+    let capacity_kwh = constraints.battery_capacity_kwh.max(0.1);
+    let efficiency = constraints.battery_efficiency.clamp(0.5, 1.0);
+    let wear = constraints.wear();
+
+    let mut soc = state.battery.soc_percent;
+    let mut cost = 0.0;
+    let mut peak = HourlyPeak::new(constraints.peak_power_incurred_kw);
+    for price in &scenario.prices {
+        let dt_h = (price.time_end - price.time_start).num_seconds() as f64 / 3600.0;
+        let Some(entry) = schedule
+            .entries
+            .iter()
+            .find(|e| e.time_start <= price.time_start && price.time_start < e.time_end)
+        else {
+            continue;
+        };
+
+        let planned_kw = entry.target_power_w / 1000.0;
+        let battery_kw = if planned_kw >= 0.0 {
+            let room_kwh = (constraints.max_soc_percent - soc).max(0.0) / 100.0 * capacity_kwh;
+            planned_kw.min(room_kwh / efficiency / dt_h)
+        } else {
+            let stored_kwh = (soc - constraints.min_soc_percent).max(0.0) / 100.0 * capacity_kwh;
+            planned_kw.max(-stored_kwh * efficiency / dt_h)
+        };
+        cost += wear.cost_sek(battery_kw, dt_h, soc);
+        soc += if battery_kw >= 0.0 {
+            battery_kw * efficiency * dt_h / capacity_kwh * 100.0
+        } else {
+            battery_kw / efficiency * dt_h / capacity_kwh * 100.0
+        };
+
+        let load_kw = scenario
+            .load_kw_between(price.time_start, price.time_end)
+            .unwrap_or(0.0);
+        let pv_kw = scenario
+            .pv_kw_between(price.time_start, price.time_end)
+            .unwrap_or(0.0);
+        let ev_kw: f64 = entry.ev_targets.iter().map(|e| e.power_w / 1000.0).sum();
+        let grid_kw = load_kw + ev_kw + battery_kw - pv_kw;
+
+        cost += if grid_kw >= 0.0 {
+            grid_kw * price.price_sek_per_kwh * dt_h
+        } else {
+            grid_kw.max(-constraints.max_export_grid_kw.max(0.0))
+                * export_price(price, constraints)
+                * dt_h
+        };
+        peak.add(
+            price.time_start,
+            dt_h,
+            constraints.peak_tariff.weight(price.time_start) * grid_kw.max(0.0),
+        );
+    }
+
+    let new_peak_kw = peak.peak_kw() - constraints.peak_power_incurred_kw.max(0.0);
+    cost + new_peak_kw * constraints.marginal_peak_cost_sek_per_kw()
+}
+
+/// Highest mean weighted import of a clock hour, as the peak tariff
+/// measures it, whatever the length of the periods adding to it
+struct HourlyPeak {
+    peak_kw: f64,
+    hour: Option<DateTime<Utc>>,
+    hour_kwh: f64,
+    hour_h: f64,
+}
+
+impl HourlyPeak {
+    fn new(incurred_kw: f64) -> Self {
+        Self {
+            peak_kw: incurred_kw.max(0.0),
+            hour: None,
+            hour_kwh: 0.0,
+            hour_h: 0.0,
+        }
+    }
+
+    fn add(&mut self, start: DateTime<Utc>, dt_h: f64, weighted_kw: f64) {
+        let hour = start.duration_trunc(Duration::hours(1)).ok();
+        if hour != self.hour {
+            self.close_hour();
+            self.hour = hour;
+        }
+        self.hour_kwh += weighted_kw * dt_h;
+        self.hour_h += dt_h;
+    }
+
+    fn close_hour(&mut self) {
+        if self.hour_h > 0.0 {
+            self.peak_kw = self.peak_kw.max(self.hour_kwh / self.hour_h);
+        }
+        self.hour_kwh = 0.0;
+        self.hour_h = 0.0;
+    }
+
+    fn peak_kw(mut self) -> f64 {
+        self.close_hour();
+        self.peak_kw
+    }
+}
+
+/// Summary of scenario costs, `None` without any scenario
+pub fn cost_distribution(mut costs: Vec<f64>, cvar_alpha: f64) -> Option<CostDistribution> {
+    if costs.is_empty() {
+        return None;
+    }
+    let scenario_costs_sek = costs.clone();
+    costs.sort_by(f64::total_cmp);
+
+    let n = costs.len();
+    let quantile = |q: f64| costs[((q * (n - 1) as f64).round() as usize).min(n - 1)];
+    let tail = (((1.0 - cvar_alpha) * n as f64).ceil() as usize).clamp(1, n);
+
+    Some(CostDistribution {
+        expected_sek: costs.iter().sum::<f64>() / n as f64,
+        cvar_sek: costs[n - tail..].iter().sum::<f64>() / tail as f64,
+        cvar_alpha,
+        p10_sek: quantile(0.1),
+        p50_sek: quantile(0.5),
+        p90_sek: quantile(0.9),
+        scenario_costs_sek,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        BatteryState, BatteryStatus, ConsumptionPoint, PriceArea, PricePoint, ProductionPoint,
+        ScheduleEntry,
+    };
+    use crate::optimizer::DynamicProgrammingOptimizer;
+    use chrono::TimeZone;
+
+    fn state(soc_percent: f64) -> SystemState {
+        SystemState {
+            battery: BatteryState {
+                soc_percent,
+                power_w: 0.0,
+                voltage_v: 48.0,
+                temperature_c: 25.0,
+                status: BatteryStatus::Idle,
+                health_percent: 100.0,
+            },
+            evs: Vec::new(),
+        }
+    }
+
+    /// Sunny midday and an expensive evening, generated at 06:00 UTC
+    fn forecast() -> Forecast24h {
+        let start = Utc.with_ymd_and_hms(2025, 6, 2, 6, 0, 0).unwrap();
+        let hour = |i: i64| start + Duration::hours(i);
+        Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start,
+            prices: (0..24)
+                .map(|i| PricePoint {
+                    time_start: hour(i),
+                    time_end: hour(i + 1),
+                    price_sek_per_kwh: if (11..15).contains(&i) { 3.0 } else { 1.0 },
+                    export_price_sek_per_kwh: None,
+                })
+                .collect(),
+            consumption: (0..24)
+                .map(|i| ConsumptionPoint {
+                    time_start: hour(i),
+                    time_end: hour(i + 1),
+                    load_kw: 1.0,
+                })
+                .collect(),
+            production: (0..24)
+                .map(|i| ProductionPoint {
+                    time_start: hour(i),
+                    time_end: hour(i + 1),
+                    pv_kw: if (4..9).contains(&i) { 4.0 } else { 0.0 },
+                })
+                .collect(),
+        }
+    }
+
+    fn optimizer() -> ScenarioOptimizer {
+        ScenarioOptimizer::new(
+            Box::new(DynamicProgrammingOptimizer),
+            ScenarioSettings::default(),
+        )
+    }
+
+    #[test]
+    fn test_published_prices_are_not_perturbed() {
+        let forecast = forecast();
+        let scenarios = optimizer().sample_scenarios(&forecast);
+
+        // Generated at 08:00 CEST, so only today's prices are published
+        let known_until = day_ahead_known_until(forecast.generated_at);
+        assert_eq!(
+            known_until,
+            Utc.with_ymd_and_hms(2025, 6, 2, 22, 0, 0).unwrap()
+        );
+
+        assert_eq!(scenarios.len(), 30);
+        for scenario in &scenarios {
+            for (sampled, expected) in scenario.prices.iter().zip(&forecast.prices) {
+                if sampled.time_start < known_until {
+                    assert_eq!(sampled.price_sek_per_kwh, expected.price_sek_per_kwh);
+                }
+            }
+            assert!(scenario.production.iter().all(|p| p.pv_kw >= 0.0));
+        }
+        let pv_totals: Vec<f64> = scenarios
+            .iter()
+            .map(|s| s.production.iter().map(|p| p.pv_kw).sum())
+            .collect();
+        assert!(pv_totals.iter().any(|&kwh| kwh < 20.0));
+        assert!(pv_totals.iter().any(|&kwh| kwh > 20.0));
+    }
+
+    #[test]
+    fn test_empty_battery_cannot_deliver_planned_discharge() {
+        let forecast = forecast();
+        let entries = forecast
+            .prices
+            .iter()
+            .map(|p| ScheduleEntry {
+                time_start: p.time_start,
+                time_end: p.time_end,
+                target_power_w: -5000.0,
+                price_sek_per_kwh: p.price_sek_per_kwh,
+                reason: "test".to_string(),
+                ev_targets: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let schedule = Schedule {
+            id: uuid::Uuid::new_v4(),
+            created_at: forecast.generated_at,
+            valid_from: entries[0].time_start,
+            valid_until: entries[23].time_end,
+            entries,
+            optimizer_version: "test".to_string(),
+            cost_distribution: None,
+        };
+        let constraints = Constraints::default();
+
+        let empty = scenario_cost(
+            &schedule,
+            &forecast,
+            &state(constraints.min_soc_percent),
+            &constraints,
+        );
+        let full = scenario_cost(
+            &schedule,
+            &forecast,
+            &state(constraints.max_soc_percent),
+            &constraints,
+        );
+
+        assert!(full < empty);
+    }
+
+    #[test]
+    fn test_peak_is_mean_of_clock_hour() {
+        let start = Utc.with_ymd_and_hms(2025, 6, 2, 6, 0, 0).unwrap();
+        let mut peak = HourlyPeak::new(0.0);
+        // A 4 kW quarter-hour in an otherwise idle hour, then a steady 2 kW hour
+        for (i, kw) in [4.0, 0.0, 0.0, 0.0, 2.0, 2.0, 2.0, 2.0].into_iter().enumerate() {
+            peak.add(start + Duration::minutes(15 * i as i64), 0.25, kw);
+        }
+        assert_eq!(peak.peak_kw(), 2.0);
+    }
+
+    #[test]
+    fn test_cvar_is_mean_of_worst_tail() {
+        let costs = (1..=10).map(f64::from).collect();
+        let distribution = cost_distribution(costs, 0.8).unwrap();
+
+        assert!((distribution.expected_sek - 5.5).abs() < 1e-9);
+        assert!((distribution.cvar_sek - 9.5).abs() < 1e-9);
+        assert_eq!(distribution.p50_sek, 6.0);
+        assert!(cost_distribution(Vec::new(), 0.9).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_carries_cost_distribution() {
+        let forecast = forecast();
+        let schedule = optimizer()
+            .optimize(&state(50.0), &forecast, &Constraints::default())
+            .await
+            .unwrap();
+
+        let distribution = schedule.cost_distribution.as_ref().unwrap();
+        assert_eq!(distribution.scenario_costs_sek.len(), 30);
+        assert!(distribution.cvar_sek >= distribution.expected_sek);
+        assert!(schedule.optimizer_version.starts_with("scenario-v1.0/"));
+        schedule.validate().unwrap();
+    }
+}
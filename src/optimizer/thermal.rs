@@ -0,0 +1,523 @@
+//! # Heat Pump Pre-Heating
+//!
+//! Plans the heat pump's SG-Ready operating state per price period. The
+//! house and the hot-water tank are each modelled as one thermal mass
+//! losing heat to its surroundings, so the planner can charge them in
+//! cheap periods (`Boost`), let them coast through expensive ones
+//! (`Blocked`) and otherwise leave the heat pump to its own schedule
+//! (`Normal`), without letting the room fall below the comfort band.
+//!
+//! The value of every (room, tank) temperature pair is computed backwards
+//! over the horizon on a grid and interpolated between grid points, since a
+//! well insulated house moves only a tenth of a kelvin in an idle quarter
+//! hour. The plan itself is then rolled forward from the measured
+//! temperatures.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{max_horizon, Forecast24h, ThermalLoadMode};
+use crate::simulation::hvac::DhwTankState;
+use crate::simulation::thermal::{HydronicZoneConfig, ThermalZoneConfig};
+
+/// Cost of every kelvin-hour spent below a comfort limit (SEK)
+pub const COMFORT_PENALTY_SEK_PER_KELVIN_HOUR: f64 = 100.0;
+/// Grid spacing of the room temperature (K)
+const ROOM_STEP_K: f64 = 0.05;
+/// Grid spacing of the hot-water temperature (K)
+const TANK_STEP_K: f64 = 0.5;
+const WATER_HEAT_CAPACITY_J_PER_LITER_K: f64 = 4186.0;
+const COLD_WATER_TEMP_C: f64 = 10.0;
+
+/// Modes the planner chooses from; `ForcedOn` is left to manual control
+const PLANNED_MODES: [ThermalLoadMode; 3] = [
+    ThermalLoadMode::Blocked,
+    ThermalLoadMode::Normal,
+    ThermalLoadMode::Boost,
+];
+
+/// Heat balance of one thermal mass: capacity, loss to a fixed surrounding
+/// temperature and constant gains
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LumpedThermalModel {
+    pub heat_loss_w_per_k: f64,
+    pub thermal_mass_j_per_k: f64,
+    /// Gains other than the heat pump, e.g. occupants and appliances (W)
+    pub internal_gains_w: f64,
+}
+
+impl From<&ThermalZoneConfig> for LumpedThermalModel {
+    fn from(config: &ThermalZoneConfig) -> Self {
+        Self {
+            heat_loss_w_per_k: config.heat_loss_coefficient_w_per_k(),
+            thermal_mass_j_per_k: config.thermal_mass_j_per_k(),
+            internal_gains_w: config.internal_gains_w,
+        }
+    }
+}
+
+impl From<&HydronicZoneConfig> for LumpedThermalModel {
+    /// Slab and room air as one mass; the slab's lag is ignored, which is
+    /// fine for plans made in whole price periods
+    fn from(config: &HydronicZoneConfig) -> Self {
+        Self {
+            heat_loss_w_per_k: 1000.0 / config.r_air_to_out_k_per_kw
+                + 1000.0 / config.r_slab_to_ground_k_per_kw,
+            thermal_mass_j_per_k: (config.slab_thermal_mass_kwh_k + config.air_thermal_mass_kwh_k)
+                * 3.6e6,
+            internal_gains_w: config.internal_gains_kw * 1000.0,
+        }
+    }
+}
+
+impl LumpedThermalModel {
+    /// Hot-water tank with draws spread evenly over the day
+    ///
+    /// Drawn water is replaced by cold water, which cools the tank like a
+    /// loss to a 10 °C surrounding.
+    pub fn dhw_tank(tank: &DhwTankState, draw_liters_per_day: f64) -> (Self, f64) {
+        let draw_w_per_k = draw_liters_per_day / 86_400.0 * WATER_HEAT_CAPACITY_J_PER_LITER_K;
+        let heat_loss_w_per_k = tank.heat_loss_rate_w_per_k + draw_w_per_k;
+        let surrounding_c = if heat_loss_w_per_k > 0.0 {
+            (tank.heat_loss_rate_w_per_k * tank.ambient_temp_c + draw_w_per_k * COLD_WATER_TEMP_C)
+                / heat_loss_w_per_k
+        } else {
+            tank.ambient_temp_c
+        };
+        let model = Self {
+            heat_loss_w_per_k,
+            thermal_mass_j_per_k: tank.capacity_liters * WATER_HEAT_CAPACITY_J_PER_LITER_K,
+            internal_gains_w: 0.0,
+        };
+        (model, surrounding_c)
+    }
+
+    /// Temperature after `dt_s` seconds of constant `heat_w`
+    pub fn temp_after(&self, temp_c: f64, surrounding_c: f64, heat_w: f64, dt_s: f64) -> f64 {
+        let gains_w = heat_w + self.internal_gains_w;
+        if self.heat_loss_w_per_k <= 0.0 {
+            return temp_c + gains_w * dt_s / self.thermal_mass_j_per_k;
+        }
+        let equilibrium_c = surrounding_c + gains_w / self.heat_loss_w_per_k;
+        let decay = (-dt_s * self.heat_loss_w_per_k / self.thermal_mass_j_per_k).exp();
+        equilibrium_c + (temp_c - equilibrium_c) * decay
+    }
+
+    /// Constant heat that brings `temp_c` to `target_c` in `dt_s` seconds (W, never negative)
+    pub fn heat_to_reach(&self, temp_c: f64, target_c: f64, surrounding_c: f64, dt_s: f64) -> f64 {
+        let heat_w = if self.heat_loss_w_per_k <= 0.0 {
+            (target_c - temp_c) * self.thermal_mass_j_per_k / dt_s
+        } else {
+            let decay = (-dt_s * self.heat_loss_w_per_k / self.thermal_mass_j_per_k).exp();
+            let equilibrium_c = (target_c - temp_c * decay) / (1.0 - decay);
+            (equilibrium_c - surrounding_c) * self.heat_loss_w_per_k
+        };
+        (heat_w - self.internal_gains_w).max(0.0)
+    }
+}
+
+/// Comfort limits and heat pump ratings the planner works with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThermalPlanSettings {
+    /// Room temperature never planned below this (°C)
+    pub comfort_min_c: f64,
+    /// Room temperature `Boost` heats up to (°C)
+    pub comfort_max_c: f64,
+    /// Room temperature the heat pump holds in `Normal` (°C)
+    pub setpoint_c: f64,
+    /// Hot water never planned below this; `Normal` holds it here (°C)
+    pub dhw_min_c: f64,
+    /// Hot water temperature `Boost` heats up to (°C)
+    pub dhw_max_c: f64,
+    /// Electrical input at full output (kW)
+    pub rated_power_kw: f64,
+    /// Coefficient of performance for space heating
+    pub cop: f64,
+    /// Coefficient of performance for hot water, lower for the higher flow temperature
+    pub dhw_cop: f64,
+}
+
+/// One period of a heating plan
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalPlanEntry {
+    pub time_start: DateTime<Utc>,
+    pub time_end: DateTime<Utc>,
+    pub mode: ThermalLoadMode,
+    pub price_sek_per_kwh: f64,
+    /// Expected average electrical input (kW)
+    pub power_kw: f64,
+    /// Expected room temperature at the end of the period (°C)
+    pub indoor_temp_c: f64,
+    /// Expected hot water temperature at the end of the period (°C)
+    pub dhw_temp_c: Option<f64>,
+}
+
+/// Operating state per price period
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalPlan {
+    pub generated_at: DateTime<Utc>,
+    pub entries: Vec<ThermalPlanEntry>,
+    /// Electricity cost of the plan (SEK)
+    pub cost_sek: f64,
+    /// Electricity cost of staying in `Normal` throughout (SEK)
+    pub baseline_cost_sek: f64,
+}
+
+impl ThermalPlan {
+    /// Planned state at `timestamp`, `None` outside the plan
+    pub fn mode_at(&self, timestamp: DateTime<Utc>) -> Option<ThermalLoadMode> {
+        self.entries
+            .iter()
+            .find(|e| e.time_start <= timestamp && timestamp < e.time_end)
+            .map(|e| e.mode)
+    }
+
+    pub fn savings_sek(&self) -> f64 {
+        self.baseline_cost_sek - self.cost_sek
+    }
+}
+
+/// Result of holding one mode for a period
+#[derive(Debug, Clone, Copy)]
+struct Outcome {
+    indoor_c: f64,
+    dhw_c: f64,
+    energy_kwh: f64,
+    /// Kelvin-hours below the comfort limits
+    shortfall_kh: f64,
+}
+
+pub struct ThermalPlanner {
+    settings: ThermalPlanSettings,
+    house: LumpedThermalModel,
+    /// Tank model and its surrounding temperature, `None` without a tank
+    tank: Option<(LumpedThermalModel, f64)>,
+}
+
+impl ThermalPlanner {
+    pub fn new(
+        settings: ThermalPlanSettings,
+        house: LumpedThermalModel,
+        tank: Option<(LumpedThermalModel, f64)>,
+    ) -> Self {
+        Self {
+            settings,
+            house,
+            tank,
+        }
+    }
+
+    pub fn settings(&self) -> &ThermalPlanSettings {
+        &self.settings
+    }
+
+    /// Plan the horizon from the measured temperatures
+    ///
+    /// Without an outdoor forecast the current outdoor temperature is
+    /// assumed to hold over the horizon.
+    pub fn plan(
+        &self,
+        forecast: &Forecast24h,
+        indoor_temp_c: f64,
+        dhw_temp_c: Option<f64>,
+        outdoor_temp_c: f64,
+    ) -> ThermalPlan {
+        let n = forecast.periods_within(max_horizon());
+        let s = &self.settings;
+        let rooms = grid(s.comfort_min_c, s.comfort_max_c, ROOM_STEP_K);
+        let tanks = match self.tank {
+            Some(_) => grid(s.dhw_min_c, s.dhw_max_c, TANK_STEP_K),
+            None => vec![s.dhw_min_c],
+        };
+
+        // value[t] is the cheapest cost from the start of period t, by (room, tank) grid point
+        let mut value = vec![vec![0.0; rooms.len() * tanks.len()]; n + 1];
+        for t in (0..n).rev() {
+            let price = &forecast.prices[t];
+            let dt_s = (price.time_end - price.time_start).num_seconds() as f64;
+            for (i, &room) in rooms.iter().enumerate() {
+                for (j, &tank) in tanks.iter().enumerate() {
+                    value[t][i * tanks.len() + j] = PLANNED_MODES
+                        .iter()
+                        .map(|&mode| {
+                            let outcome = self.outcome(mode, room, tank, outdoor_temp_c, dt_s);
+                            self.stage_cost(&outcome, price.price_sek_per_kwh)
+                                + interpolate(
+                                    &value[t + 1],
+                                    &rooms,
+                                    &tanks,
+                                    outcome.indoor_c,
+                                    outcome.dhw_c,
+                                )
+                        })
+                        .fold(f64::INFINITY, f64::min);
+                }
+            }
+        }
+
+        let mut entries = Vec::with_capacity(n);
+        let (mut cost_sek, mut baseline_cost_sek) = (0.0, 0.0);
+        let mut state = (indoor_temp_c, dhw_temp_c.unwrap_or(s.dhw_min_c));
+        let mut baseline = state;
+        for t in 0..n {
+            let price = &forecast.prices[t];
+            let dt_s = (price.time_end - price.time_start).num_seconds() as f64;
+            // Ties go to the mode listed first, so `Boost` needs a strict gain
+            let (mode, outcome) = PLANNED_MODES
+                .iter()
+                .map(|&mode| {
+                    (
+                        mode,
+                        self.outcome(mode, state.0, state.1, outdoor_temp_c, dt_s),
+                    )
+                })
+                .min_by(|a, b| {
+                    let total = |o: &Outcome| {
+                        self.stage_cost(o, price.price_sek_per_kwh)
+                            + interpolate(&value[t + 1], &rooms, &tanks, o.indoor_c, o.dhw_c)
+                    };
+                    total(&a.1).total_cmp(&total(&b.1))
+                })
+                .expect("at least one mode");
+            cost_sek += outcome.energy_kwh * price.price_sek_per_kwh;
+            state = (outcome.indoor_c, outcome.dhw_c);
+            entries.push(ThermalPlanEntry {
+                time_start: price.time_start,
+                time_end: price.time_end,
+                mode,
+                price_sek_per_kwh: price.price_sek_per_kwh,
+                power_kw: outcome.energy_kwh * 3600.0 / dt_s.max(1.0),
+                indoor_temp_c: outcome.indoor_c,
+                dhw_temp_c: self.tank.map(|_| outcome.dhw_c),
+            });
+
+            let normal = self.outcome(
+                ThermalLoadMode::Normal,
+                baseline.0,
+                baseline.1,
+                outdoor_temp_c,
+                dt_s,
+            );
+            baseline_cost_sek += normal.energy_kwh * price.price_sek_per_kwh;
+            baseline = (normal.indoor_c, normal.dhw_c);
+        }
+
+        ThermalPlan {
+            generated_at: Utc::now(),
+            entries,
+            cost_sek,
+            baseline_cost_sek,
+        }
+    }
+
+    fn stage_cost(&self, outcome: &Outcome, price_sek_per_kwh: f64) -> f64 {
+        outcome.energy_kwh * price_sek_per_kwh
+            + outcome.shortfall_kh * COMFORT_PENALTY_SEK_PER_KELVIN_HOUR
+    }
+
+    /// Hold `mode` for `dt_s` seconds
+    ///
+    /// The compressor serves the tank first, as Nordic heat pumps do, and
+    /// heats the house with the time left.
+    fn outcome(
+        &self,
+        mode: ThermalLoadMode,
+        indoor_c: f64,
+        dhw_c: f64,
+        outdoor_c: f64,
+        dt_s: f64,
+    ) -> Outcome {
+        let s = &self.settings;
+        let (room_target, tank_target) = match mode {
+            ThermalLoadMode::Blocked => (None, None),
+            ThermalLoadMode::Normal => (Some(s.setpoint_c), Some(s.dhw_min_c)),
+            ThermalLoadMode::Boost | ThermalLoadMode::ForcedOn => {
+                (Some(s.comfort_max_c), Some(s.dhw_max_c))
+            }
+        };
+        let rated_w = s.rated_power_kw * 1000.0;
+
+        let (dhw_end, tank_heat_w, tank_share) = match (self.tank, tank_target) {
+            (Some((tank, surrounding)), Some(target)) => {
+                let max_w = rated_w * s.dhw_cop;
+                let heat_w = tank
+                    .heat_to_reach(dhw_c, target, surrounding, dt_s)
+                    .min(max_w);
+                let share = if max_w > 0.0 { heat_w / max_w } else { 0.0 };
+                (
+                    tank.temp_after(dhw_c, surrounding, heat_w, dt_s),
+                    heat_w,
+                    share,
+                )
+            }
+            (Some((tank, surrounding)), None) => {
+                (tank.temp_after(dhw_c, surrounding, 0.0, dt_s), 0.0, 0.0)
+            }
+            (None, _) => (dhw_c, 0.0, 0.0),
+        };
+
+        let room_heat_w = match room_target {
+            Some(target) => self
+                .house
+                .heat_to_reach(indoor_c, target, outdoor_c, dt_s)
+                .min(rated_w * s.cop * (1.0 - tank_share)),
+            None => 0.0,
+        };
+        let indoor_end = self
+            .house
+            .temp_after(indoor_c, outdoor_c, room_heat_w, dt_s);
+
+        let input_w = room_heat_w / s.cop
+            + if tank_heat_w > 0.0 {
+                tank_heat_w / s.dhw_cop
+            } else {
+                0.0
+            };
+        let hours = dt_s / 3600.0;
+        let tank_shortfall = if self.tank.is_some() {
+            (s.dhw_min_c - dhw_end).max(0.0)
+        } else {
+            0.0
+        };
+        Outcome {
+            indoor_c: indoor_end,
+            dhw_c: dhw_end,
+            energy_kwh: input_w / 1000.0 * hours,
+            shortfall_kh: ((s.comfort_min_c - indoor_end).max(0.0) + tank_shortfall) * hours,
+        }
+    }
+}
+
+/// Evenly spaced points from `min` to `max`, both included
+fn grid(min: f64, max: f64, step: f64) -> Vec<f64> {
+    let count = ((max - min) / step).round().max(0.0) as usize + 1;
+    (0..count)
+        .map(|i| {
+            if count > 1 {
+                min + (max - min) * i as f64 / (count - 1) as f64
+            } else {
+                min
+            }
+        })
+        .collect()
+}
+
+/// Bilinear interpolation of `values` (room-major), clamped to the grid
+fn interpolate(values: &[f64], rooms: &[f64], tanks: &[f64], room: f64, tank: f64) -> f64 {
+    let (i, fi) = locate(rooms, room);
+    let (j, fj) = locate(tanks, tank);
+    let at = |i: usize, j: usize| values[i * tanks.len() + j];
+    let i1 = (i + 1).min(rooms.len() - 1);
+    let j1 = (j + 1).min(tanks.len() - 1);
+    let low = at(i, j) * (1.0 - fj) + at(i, j1) * fj;
+    let high = at(i1, j) * (1.0 - fj) + at(i1, j1) * fj;
+    low * (1.0 - fi) + high * fi
+}
+
+/// Index of the grid cell holding `x` and the position within it (0-1)
+fn locate(points: &[f64], x: f64) -> (usize, f64) {
+    let last = points.len() - 1;
+    if last == 0 || x <= points[0] {
+        return (0, 0.0);
+    }
+    if x >= points[last] {
+        return (last, 0.0);
+    }
+    let step = (points[last] - points[0]) / last as f64;
+    let i = (((x - points[0]) / step) as usize).min(last - 1);
+    (i, ((x - points[i]) / step).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{PriceArea, PricePoint};
+    use chrono::{Duration, TimeZone};
+
+    fn forecast(prices: &[f64]) -> Forecast24h {
+        let start = Utc.with_ymd_and_hms(2025, 1, 6, 0, 0, 0).unwrap();
+        Forecast24h {
+            area: PriceArea::SE3,
+            generated_at: start,
+            prices: prices
+                .iter()
+                .enumerate()
+                .map(|(i, &price)| PricePoint {
+                    time_start: start + Duration::hours(i as i64),
+                    time_end: start + Duration::hours(i as i64 + 1),
+                    price_sek_per_kwh: price,
+                    export_price_sek_per_kwh: None,
+                })
+                .collect(),
+            consumption: vec![],
+            production: vec![],
+        }
+    }
+
+    fn planner() -> ThermalPlanner {
+        ThermalPlanner::new(
+            ThermalPlanSettings {
+                comfort_min_c: 20.0,
+                comfort_max_c: 23.0,
+                setpoint_c: 21.0,
+                dhw_min_c: 45.0,
+                dhw_max_c: 60.0,
+                rated_power_kw: 3.0,
+                cop: 3.5,
+                dhw_cop: 2.5,
+            },
+            LumpedThermalModel::from(&ThermalZoneConfig::default()),
+            Some(LumpedThermalModel::dhw_tank(
+                &DhwTankState::default(),
+                150.0,
+            )),
+        )
+    }
+
+    #[test]
+    fn test_pre_heats_before_expensive_hours() {
+        let prices = [0.2, 0.2, 0.2, 0.2, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 1.0, 1.0];
+        let plan = planner().plan(&forecast(&prices), 21.0, Some(50.0), -5.0);
+
+        assert_eq!(plan.entries.len(), prices.len());
+        assert!(plan.entries[..4]
+            .iter()
+            .any(|e| e.mode == ThermalLoadMode::Boost));
+        let blocked = plan.entries[4..10]
+            .iter()
+            .filter(|e| e.mode == ThermalLoadMode::Blocked)
+            .count();
+        assert!(blocked >= 4);
+        // Heat is stored in the house and the tank, within the comfort band
+        assert!(plan.entries[3].indoor_temp_c > 21.5);
+        assert!(plan.entries[3].dhw_temp_c.unwrap() > 50.0);
+        for entry in &plan.entries {
+            assert!(entry.indoor_temp_c >= 20.0 - 0.05, "{:?}", entry);
+            assert!(entry.indoor_temp_c <= 23.0 + 0.05, "{:?}", entry);
+            assert!(entry.dhw_temp_c.unwrap() >= 45.0 - 0.5, "{:?}", entry);
+        }
+        assert!(plan.savings_sek() > 0.0);
+        assert_eq!(
+            plan.mode_at(plan.entries[0].time_start),
+            Some(plan.entries[0].mode)
+        );
+    }
+
+    #[test]
+    fn test_flat_prices_do_not_pre_heat_the_house() {
+        let plan = planner().plan(&forecast(&[1.0; 12]), 21.0, Some(50.0), 0.0);
+
+        assert!(plan.entries.iter().all(|e| e.indoor_temp_c <= 21.0 + 0.05));
+        assert!(plan.savings_sek() >= -1e-6);
+    }
+
+    #[test]
+    fn test_lumped_model_reaches_target() {
+        let house = LumpedThermalModel::from(&HydronicZoneConfig::default());
+        let heat_w = house.heat_to_reach(20.0, 21.0, -5.0, 3600.0);
+        assert!((house.temp_after(20.0, -5.0, heat_w, 3600.0) - 21.0).abs() < 1e-6);
+        assert_eq!(house.heat_to_reach(22.0, 21.0, 20.0, 3600.0), 0.0);
+    }
+}
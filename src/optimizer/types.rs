@@ -5,10 +5,75 @@ use serde::{Deserialize, Serialize};
 
 use super::Constraints;
 use crate::domain::{BatteryState, Forecast24h, Schedule};
+use crate::power_flow::inputs::{EvChargerInput, EvState};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemState {
     pub battery: BatteryState,
+    /// Plugged-in vehicles to schedule alongside the battery
+    #[serde(default)]
+    pub evs: Vec<EvChargeRequest>,
+}
+
+/// A plugged-in vehicle and the limits of the charger it is on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvChargeRequest {
+    /// Charger the plan is for
+    pub charger_id: String,
+    pub ev: EvState,
+    /// Lowest current the charger delivers once it charges (A)
+    pub min_current_a: f64,
+    /// Highest current of the charger or its circuit (A)
+    pub max_current_a: f64,
+    pub phases: u8,
+    pub voltage_v: f64,
+    /// Charger can discharge the vehicle (V2G)
+    pub bidirectional: bool,
+}
+
+impl EvChargeRequest {
+    /// Request for a charger as the power flow model sees it
+    ///
+    /// Only the charger the V2X controller drives, flagged by `v2x`, can
+    /// discharge the vehicle, and only when the vehicle supports it.
+    pub fn from_input(input: &EvChargerInput, voltage_v: f64, v2x: bool) -> Self {
+        Self {
+            charger_id: input.id.clone(),
+            ev: input.ev.clone(),
+            min_current_a: input.min_current_a,
+            max_current_a: input.max_current_a,
+            phases: input.phases,
+            voltage_v,
+            bidirectional: v2x && input.ev.max_discharge_kw > 0.0,
+        }
+    }
+
+    fn kw_per_amp(&self) -> f64 {
+        self.voltage_v * self.phases.max(1) as f64 / 1000.0
+    }
+
+    /// Lowest non-zero charging power (kW)
+    pub fn min_charge_kw(&self) -> f64 {
+        (self.min_current_a * self.kw_per_amp()).min(self.max_charge_kw())
+    }
+
+    /// Highest charging power allowed by the charger and the vehicle (kW)
+    pub fn max_charge_kw(&self) -> f64 {
+        (self.max_current_a * self.kw_per_amp())
+            .min(self.ev.max_charge_kw)
+            .max(0.0)
+    }
+
+    /// Highest discharge power, zero unless V2G is enabled and supported (kW)
+    pub fn max_discharge_kw(&self, constraints: &Constraints) -> f64 {
+        if constraints.v2g_enabled && self.bidirectional {
+            (self.max_current_a * self.kw_per_amp())
+                .min(self.ev.max_discharge_kw)
+                .max(0.0)
+        } else {
+            0.0
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -2,6 +2,9 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
+use super::ev_allocation::EvAllocationPolicy;
+use crate::simulation::three_phase::LoadDistribution;
+
 /// Physical constraints (hard limits that CANNOT be violated)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhysicalConstraints {
@@ -31,6 +34,37 @@ pub struct PhysicalConstraints {
 
     /// Grid voltage (V, typically 230V single-phase or 400V three-phase)
     pub grid_voltage_v: f64,
+
+    /// Phase (1-3) a single-phase PV inverter feeds, `None` if it feeds all phases
+    #[serde(default)]
+    pub pv_phase: Option<u8>,
+
+    /// Phase (1-3) a single-phase battery inverter is on, `None` if three-phase
+    #[serde(default)]
+    pub battery_phase: Option<u8>,
+}
+
+impl PhysicalConstraints {
+    /// Highest current a single phase may carry in either direction (A)
+    ///
+    /// Without an explicit per-phase rating, the import limit spread
+    /// evenly over the phases is used.
+    pub fn phase_limit_a(&self) -> f64 {
+        self.max_current_per_phase_a.unwrap_or_else(|| {
+            self.max_grid_import_kw * 1000.0 / (self.grid_voltage_v * self.phases.max(1) as f64)
+        })
+    }
+
+    /// How a device wired to `phase` (`None` = all phases) loads the site
+    ///
+    /// A single-phase site carries everything on L1.
+    pub fn distribution(&self, phase: Option<u8>) -> LoadDistribution {
+        match phase {
+            _ if self.phases < 3 => LoadDistribution::SinglePhase(1),
+            Some(phase) => LoadDistribution::SinglePhase(phase as usize),
+            None => LoadDistribution::Balanced,
+        }
+    }
 }
 
 impl Default for PhysicalConstraints {
@@ -49,6 +83,8 @@ impl Default for PhysicalConstraints {
             phases: 1,
             max_current_per_phase_a: Some(0.0), // SAFE MODE
             grid_voltage_v: 230.0,         // Standard European voltage (informational)
+            pv_phase: None,
+            battery_phase: None,
         }
     }
 }
@@ -109,6 +145,10 @@ pub struct EconomicObjectives {
     /// Used when grid price is cheap but battery isn't full
     /// Default: 0.5 (50% of max charge rate)
     pub low_price_charge_rate: f64,
+
+    /// How fuse headroom is shared on sites with several EV chargers
+    #[serde(default)]
+    pub ev_allocation: EvAllocationPolicy,
 }
 
 impl Default for EconomicObjectives {
@@ -121,6 +161,7 @@ impl Default for EconomicObjectives {
             ev_departure_time: None,
             ev_target_soc_percent: None,
             low_price_charge_rate: 0.5,
+            ev_allocation: EvAllocationPolicy::default(),
         }
     }
 }
@@ -238,6 +279,22 @@ impl AllConstraints {
             return Err("evse_max_current_a must be >= evse_min_current_a".to_string());
         }
 
+        if self.physical.phases != 1 && self.physical.phases != 3 {
+            return Err(format!("phases must be 1 or 3, got {}", self.physical.phases));
+        }
+
+        if let Some(limit_a) = self.physical.max_current_per_phase_a {
+            if !limit_a.is_finite() || limit_a <= 0.0 {
+                return Err("max_current_per_phase_a must be positive (0.0 = safe mode, explicit config required)".to_string());
+            }
+        }
+
+        for (name, phase) in [("pv_phase", self.physical.pv_phase), ("battery_phase", self.physical.battery_phase)] {
+            if phase.is_some_and(|p| !(1..=3).contains(&p)) {
+                return Err(format!("{} must be between 1 and 3", name));
+            }
+        }
+
         // Check safety constraints ranges
         if self.safety.battery_min_soc_percent < 0.0 || self.safety.battery_min_soc_percent > 100.0 {
             return Err("battery_min_soc_percent must be between 0 and 100".to_string());
@@ -307,6 +364,21 @@ mod tests {
         assert!(constraints.validate().is_err());
     }
 
+    #[test]
+    fn test_validation_invalid_device_phase() {
+        let mut constraints = AllConstraints::default();
+        constraints.physical.max_grid_import_kw = 17.0;
+        constraints.physical.max_battery_charge_kw = 5.0;
+        constraints.physical.max_battery_discharge_kw = 5.0;
+        constraints.physical.evse_max_current_a = 16.0;
+        constraints.physical.phases = 3;
+        constraints.physical.max_current_per_phase_a = Some(25.0);
+        assert!(constraints.validate().is_ok());
+
+        constraints.physical.pv_phase = Some(4);
+        assert!(constraints.validate().is_err());
+    }
+
     #[test]
     fn test_validation_invalid_price() {
         let mut constraints = AllConstraints::default();
@@ -0,0 +1,357 @@
+#![allow(dead_code)]
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Slack for floating point comparisons of currents (A)
+const CURRENT_EPSILON_A: f64 = 1e-6;
+
+/// How fuse headroom is shared between chargers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvAllocationPolicy {
+    /// Equal current to every charging vehicle
+    #[default]
+    FairShare,
+    /// Satisfy chargers in priority order (lowest value first)
+    Priority,
+    /// Earliest departure first; vehicles without a deadline go last
+    Deadline,
+}
+
+/// Current and power granted to one charger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvAllocation {
+    pub id: String,
+    pub current_a: f64,
+    pub power_kw: f64,
+}
+
+/// What one charger asks for this tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvDemand {
+    pub id: String,
+    /// Phases the charger draws from (1 or 3)
+    pub phases: u8,
+    /// Site phase (1-3) a single-phase charger is wired to
+    pub phase: u8,
+    /// Below this the charger cannot run (IEC 61851: 6 A)
+    pub min_current_a: f64,
+    /// Current the vehicle wants, already capped by charger and vehicle limits
+    pub requested_current_a: f64,
+    pub priority: u8,
+    pub departure_time: Option<DateTime<Utc>>,
+}
+
+impl EvDemand {
+    /// Amps drawn on each of `site_phases` phases per amp of charger current
+    ///
+    /// A single-phase site model carries the whole connection on one
+    /// phase, so a three-phase charger loads it three times over.
+    pub fn phase_load(&self, site_phases: usize) -> Vec<f64> {
+        let mut load = vec![0.0; site_phases];
+        if site_phases == 0 {
+            return load;
+        }
+        if site_phases == 1 {
+            load[0] = self.phases.max(1) as f64;
+        } else if self.phases >= 3 {
+            load.iter_mut().for_each(|l| *l = 1.0);
+        } else {
+            let phase = (self.phase.max(1) as usize - 1) % site_phases;
+            load[phase] = 1.0;
+        }
+        load
+    }
+}
+
+/// Split per-phase fuse headroom between chargers
+///
+/// Chargers are admitted in policy order as long as their minimum current
+/// fits on every phase they draw from; a charger that does not fit is paused
+/// rather than run below its floor. What is left is then shared out: equally
+/// under `FairShare`, in order under `Priority` and `Deadline`. Returns one
+/// current per demand, in demand order.
+///
+/// `total_headroom_a` caps the sum of all phase currents, which is how an
+/// import limit below the fuse rating (a power subscription) shows up.
+pub fn allocate(
+    policy: EvAllocationPolicy,
+    demands: &[EvDemand],
+    headroom_a: &[f64],
+    total_headroom_a: f64,
+) -> Vec<f64> {
+    let site_phases = headroom_a.len();
+    // The total budget is one more constraint every charger draws from
+    let loads: Vec<Vec<f64>> = demands
+        .iter()
+        .map(|d| {
+            let mut load = d.phase_load(site_phases);
+            load.push(load.iter().sum());
+            load
+        })
+        .collect();
+    let mut remaining: Vec<f64> = headroom_a
+        .iter()
+        .chain(std::iter::once(&total_headroom_a))
+        .map(|h| h.max(0.0))
+        .collect();
+    let mut currents = vec![0.0; demands.len()];
+
+    let order = admission_order(policy, demands);
+
+    // Admit at the floor first so no charger starves another below 6 A
+    let mut admitted = Vec::with_capacity(order.len());
+    for &i in &order {
+        let demand = &demands[i];
+        if demand.requested_current_a + CURRENT_EPSILON_A < demand.min_current_a {
+            continue;
+        }
+        let fits = loads[i]
+            .iter()
+            .zip(&remaining)
+            .all(|(load, room)| load * demand.min_current_a <= room + CURRENT_EPSILON_A);
+        if !fits {
+            continue;
+        }
+        for (room, load) in remaining.iter_mut().zip(&loads[i]) {
+            *room -= load * demand.min_current_a;
+        }
+        currents[i] = demand.min_current_a;
+        admitted.push(i);
+    }
+
+    match policy {
+        EvAllocationPolicy::FairShare => {
+            fair_fill(demands, &loads, &admitted, &mut remaining, &mut currents)
+        }
+        EvAllocationPolicy::Priority | EvAllocationPolicy::Deadline => {
+            for &i in &admitted {
+                let extra = max_extra(&loads[i], &remaining)
+                    .min(demands[i].requested_current_a - currents[i])
+                    .max(0.0);
+                for (room, load) in remaining.iter_mut().zip(&loads[i]) {
+                    *room -= load * extra;
+                }
+                currents[i] += extra;
+            }
+        }
+    }
+
+    currents
+}
+
+fn admission_order(policy: EvAllocationPolicy, demands: &[EvDemand]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..demands.len()).collect();
+    match policy {
+        EvAllocationPolicy::FairShare | EvAllocationPolicy::Priority => {
+            order.sort_by_key(|&i| demands[i].priority);
+        }
+        EvAllocationPolicy::Deadline => {
+            order.sort_by_key(|&i| {
+                (
+                    demands[i].departure_time.is_none(),
+                    demands[i].departure_time,
+                    demands[i].priority,
+                )
+            });
+        }
+    }
+    order
+}
+
+/// Largest current increase that still fits on every loaded phase
+fn max_extra(load: &[f64], remaining: &[f64]) -> f64 {
+    load.iter()
+        .zip(remaining)
+        .filter(|(l, _)| **l > 0.0)
+        .map(|(l, room)| room.max(0.0) / l)
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Raise every admitted charger by the same amount until it is satisfied
+/// or one of its phases is full
+fn fair_fill(
+    demands: &[EvDemand],
+    loads: &[Vec<f64>],
+    admitted: &[usize],
+    remaining: &mut [f64],
+    currents: &mut [f64],
+) {
+    let mut active: Vec<usize> = admitted
+        .iter()
+        .copied()
+        .filter(|&i| demands[i].requested_current_a - currents[i] > CURRENT_EPSILON_A)
+        .collect();
+
+    while !active.is_empty() {
+        let step_by_demand = active
+            .iter()
+            .map(|&i| demands[i].requested_current_a - currents[i])
+            .fold(f64::INFINITY, f64::min);
+        let step_by_phase = (0..remaining.len())
+            .filter_map(|p| {
+                let load: f64 = active.iter().map(|&i| loads[i][p]).sum();
+                (load > 0.0).then(|| remaining[p].max(0.0) / load)
+            })
+            .fold(f64::INFINITY, f64::min);
+        let step = step_by_demand.min(step_by_phase);
+
+        for &i in &active {
+            currents[i] += step;
+            for (room, load) in remaining.iter_mut().zip(&loads[i]) {
+                *room -= load * step;
+            }
+        }
+
+        active.retain(|&i| {
+            demands[i].requested_current_a - currents[i] > CURRENT_EPSILON_A
+                && loads[i]
+                    .iter()
+                    .zip(remaining.iter())
+                    .all(|(load, room)| *load == 0.0 || *room > CURRENT_EPSILON_A)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn demand(id: &str, phases: u8, phase: u8, requested_a: f64, priority: u8) -> EvDemand {
+        EvDemand {
+            id: id.to_string(),
+            phases,
+            phase,
+            min_current_a: 6.0,
+            requested_current_a: requested_a,
+            priority,
+            departure_time: None,
+        }
+    }
+
+    #[test]
+    fn test_fair_share_splits_headroom_equally() {
+        let demands = vec![demand("a", 3, 1, 32.0, 0), demand("b", 3, 1, 32.0, 1)];
+
+        let currents = allocate(
+            EvAllocationPolicy::FairShare,
+            &demands,
+            &[20.0, 20.0, 20.0],
+            f64::INFINITY,
+        );
+
+        assert!((currents[0] - 10.0).abs() < 1e-6);
+        assert!((currents[1] - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fair_share_hands_unused_current_to_others() {
+        let demands = vec![demand("a", 3, 1, 7.0, 0), demand("b", 3, 1, 32.0, 0)];
+
+        let currents = allocate(
+            EvAllocationPolicy::FairShare,
+            &demands,
+            &[20.0, 20.0, 20.0],
+            f64::INFINITY,
+        );
+
+        assert!((currents[0] - 7.0).abs() < 1e-6);
+        assert!((currents[1] - 13.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_charger_paused_when_floor_does_not_fit() {
+        let demands = vec![demand("a", 3, 1, 32.0, 0), demand("b", 3, 1, 32.0, 1)];
+
+        let currents = allocate(
+            EvAllocationPolicy::FairShare,
+            &demands,
+            &[10.0, 10.0, 10.0],
+            f64::INFINITY,
+        );
+
+        assert!((currents[0] - 10.0).abs() < 1e-6);
+        assert_eq!(currents[1], 0.0, "second car cannot get 6 A and must pause");
+    }
+
+    #[test]
+    fn test_priority_fills_first_charger() {
+        let demands = vec![demand("low", 3, 1, 32.0, 5), demand("high", 3, 1, 16.0, 0)];
+
+        let currents = allocate(
+            EvAllocationPolicy::Priority,
+            &demands,
+            &[25.0, 25.0, 25.0],
+            f64::INFINITY,
+        );
+
+        assert!((currents[1] - 16.0).abs() < 1e-6);
+        assert!((currents[0] - 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_deadline_favours_earliest_departure() {
+        let now = Utc::now();
+        let mut later = demand("later", 3, 1, 32.0, 0);
+        later.departure_time = Some(now + Duration::hours(10));
+        let mut sooner = demand("sooner", 3, 1, 32.0, 0);
+        sooner.departure_time = Some(now + Duration::hours(2));
+        let no_deadline = demand("whenever", 3, 1, 32.0, 0);
+
+        let currents = allocate(
+            EvAllocationPolicy::Deadline,
+            &[later, sooner, no_deadline],
+            &[30.0, 30.0, 30.0],
+            f64::INFINITY,
+        );
+
+        assert!((currents[1] - 18.0).abs() < 1e-6);
+        assert!((currents[0] - 6.0).abs() < 1e-6);
+        assert!((currents[2] - 6.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_single_phase_chargers_on_different_phases_do_not_compete() {
+        let demands = vec![demand("l1", 1, 1, 16.0, 0), demand("l2", 1, 2, 16.0, 0)];
+
+        let currents = allocate(
+            EvAllocationPolicy::FairShare,
+            &demands,
+            &[16.0, 16.0, 0.0],
+            f64::INFINITY,
+        );
+
+        assert!((currents[0] - 16.0).abs() < 1e-6);
+        assert!((currents[1] - 16.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_total_headroom_caps_chargers_on_separate_phases() {
+        let demands = vec![demand("l1", 1, 1, 16.0, 0), demand("l2", 1, 2, 16.0, 0)];
+
+        let currents = allocate(
+            EvAllocationPolicy::FairShare,
+            &demands,
+            &[16.0, 16.0, 16.0],
+            20.0,
+        );
+
+        assert!((currents[0] - 10.0).abs() < 1e-6);
+        assert!((currents[1] - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_three_phase_charger_limited_by_fullest_phase() {
+        let demands = vec![demand("a", 3, 1, 32.0, 0)];
+
+        let currents = allocate(
+            EvAllocationPolicy::FairShare,
+            &demands,
+            &[20.0, 8.0, 20.0],
+            f64::INFINITY,
+        );
+
+        assert!((currents[0] - 8.0).abs() < 1e-6);
+    }
+}
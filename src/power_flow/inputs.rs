@@ -2,6 +2,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::simulation::three_phase::ThreePhasePower;
+
 /// Input state for power flow computation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PowerFlowInputs {
@@ -11,6 +13,11 @@ pub struct PowerFlowInputs {
     /// Household load (kW)
     pub house_load_kw: f64,
 
+    /// Household load per phase (W), when a grid meter reports phases
+    /// Without it the house is assumed to load every phase evenly
+    #[serde(default)]
+    pub house_load_phases: Option<ThreePhasePower>,
+
     /// Battery state of charge (%)
     pub battery_soc_percent: f64,
 
@@ -20,6 +27,14 @@ pub struct PowerFlowInputs {
     /// EV state (if connected)
     pub ev_state: Option<EvState>,
 
+    /// Chargers sharing the fuse; when set, `ev_state` is ignored for charging
+    #[serde(default)]
+    pub ev_chargers: Vec<EvChargerInput>,
+
+    /// Switchable loads (pool pump, water heater) the controller may run
+    #[serde(default)]
+    pub controllable_loads: Vec<ControllableLoadInput>,
+
     /// Current grid electricity price (SEK/kWh)
     pub grid_price_sek_kwh: f64,
 
@@ -61,6 +76,54 @@ pub struct EvState {
     pub target_soc_percent: f64,
 }
 
+/// One charger of a multi-charger site and the vehicle plugged into it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvChargerInput {
+    pub id: String,
+
+    pub ev: EvState,
+
+    /// Phases the charger draws from (1 or 3)
+    pub phases: u8,
+
+    /// Site phase (1-3) a single-phase charger is wired to
+    pub phase: u8,
+
+    /// Minimum charging current (A)
+    pub min_current_a: f64,
+
+    /// Maximum charging current of the charger or its circuit (A)
+    pub max_current_a: f64,
+
+    /// Allocation priority, lowest first
+    pub priority: u8,
+
+    /// Charging power the schedule plans for this period (kW); replaces
+    /// the urgency heuristic, spare PV is still taken on top
+    #[serde(default)]
+    pub scheduled_power_kw: Option<f64>,
+}
+
+/// A switchable load and what its schedule asks of it
+///
+/// A running load is part of the measured `house_load_kw`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControllableLoadInput {
+    pub id: String,
+
+    /// Power drawn while on (kW)
+    pub power_kw: f64,
+
+    /// Site phase (1-3) a single-phase load is wired to; `None` for three-phase
+    pub phase: Option<u8>,
+
+    /// Whether the load is on now
+    pub running: bool,
+
+    /// Whether the schedule or an override wants it on
+    pub requested: bool,
+}
+
 impl EvState {
     /// Calculate how much energy is needed to reach target SoC
     pub fn energy_needed_kwh(&self) -> f64 {
@@ -128,9 +191,12 @@ impl PowerFlowInputs {
         Self {
             pv_production_kw,
             house_load_kw,
+            house_load_phases: None,
             battery_soc_percent,
             battery_temp_c,
             ev_state: None,
+            ev_chargers: Vec::new(),
+            controllable_loads: Vec::new(),
             grid_price_sek_kwh,
             target_power_w: None,
             ev_target_power_w: None,
@@ -177,6 +243,24 @@ impl PowerFlowInputs {
         self
     }
 
+    /// Set the measured household load per phase
+    pub fn with_house_load_phases(mut self, house_load_phases: ThreePhasePower) -> Self {
+        self.house_load_phases = Some(house_load_phases);
+        self
+    }
+
+    /// Add a charger of a multi-charger site
+    pub fn with_ev_charger(mut self, charger: EvChargerInput) -> Self {
+        self.ev_chargers.push(charger);
+        self
+    }
+
+    /// Add a switchable load
+    pub fn with_controllable_load(mut self, load: ControllableLoadInput) -> Self {
+        self.controllable_loads.push(load);
+        self
+    }
+
     /// Validate inputs for sanity
     pub fn validate(&self) -> Result<(), String> {
         // Check all values are finite (not NaN or Inf)
@@ -188,6 +272,20 @@ impl PowerFlowInputs {
             return Err(format!("house_load_kw is not finite: {}", self.house_load_kw));
         }
 
+        if let Some(phases) = self.house_load_phases {
+            if phases.as_array().iter().any(|w| !w.is_finite()) {
+                return Err(format!("house_load_phases is not finite: {:?}", phases));
+            }
+        }
+
+        if let Some(load) = self
+            .controllable_loads
+            .iter()
+            .find(|l| !l.power_kw.is_finite() || l.power_kw < 0.0)
+        {
+            return Err(format!("controllable load '{}' power is invalid: {}", load.id, load.power_kw));
+        }
+
         if !self.battery_soc_percent.is_finite() {
             return Err(format!("battery_soc_percent is not finite: {}", self.battery_soc_percent));
         }
@@ -219,32 +317,48 @@ impl PowerFlowInputs {
             return Err("battery_soc_percent must be between 0 and 100".to_string());
         }
 
-        if let Some(ref ev) = self.ev_state {
-            // Check EV values are finite
-            if !ev.soc_percent.is_finite() {
-                return Err(format!("EV soc_percent is not finite: {}", ev.soc_percent));
+        for charger in &self.ev_chargers {
+            if !charger.min_current_a.is_finite() || !charger.max_current_a.is_finite() {
+                return Err(format!("EV charger {} current limits are not finite", charger.id));
             }
-
-            if !ev.capacity_kwh.is_finite() {
-                return Err(format!("EV capacity_kwh is not finite: {}", ev.capacity_kwh));
+            if !matches!(charger.phases, 1 | 3) {
+                return Err(format!("EV charger {} must use 1 or 3 phases", charger.id));
             }
+            Self::validate_ev_state(&charger.ev)?;
+        }
 
-            if !ev.max_charge_kw.is_finite() {
-                return Err(format!("EV max_charge_kw is not finite: {}", ev.max_charge_kw));
-            }
+        if let Some(ref ev) = self.ev_state {
+            Self::validate_ev_state(ev)?;
+        }
 
-            if !ev.max_discharge_kw.is_finite() {
-                return Err(format!("EV max_discharge_kw is not finite: {}", ev.max_discharge_kw));
-            }
+        Ok(())
+    }
 
-            // Now check ranges
-            if ev.soc_percent < 0.0 || ev.soc_percent > 100.0 {
-                return Err("EV soc_percent must be between 0 and 100".to_string());
-            }
+    fn validate_ev_state(ev: &EvState) -> Result<(), String> {
+        // Check EV values are finite
+        if !ev.soc_percent.is_finite() {
+            return Err(format!("EV soc_percent is not finite: {}", ev.soc_percent));
+        }
 
-            if ev.capacity_kwh <= 0.0 {
-                return Err("EV capacity_kwh must be positive".to_string());
-            }
+        if !ev.capacity_kwh.is_finite() {
+            return Err(format!("EV capacity_kwh is not finite: {}", ev.capacity_kwh));
+        }
+
+        if !ev.max_charge_kw.is_finite() {
+            return Err(format!("EV max_charge_kw is not finite: {}", ev.max_charge_kw));
+        }
+
+        if !ev.max_discharge_kw.is_finite() {
+            return Err(format!("EV max_discharge_kw is not finite: {}", ev.max_discharge_kw));
+        }
+
+        // Now check ranges
+        if ev.soc_percent < 0.0 || ev.soc_percent > 100.0 {
+            return Err("EV soc_percent must be between 0 and 100".to_string());
+        }
+
+        if ev.capacity_kwh <= 0.0 {
+            return Err("EV capacity_kwh must be positive".to_string());
         }
 
         Ok(())
@@ -6,10 +6,12 @@
 
 pub mod snapshot;
 pub mod constraints;
+pub mod ev_allocation;
 pub mod inputs;
 pub mod model;
 
-pub use snapshot::PowerSnapshot;
+pub use snapshot::{LoadSwitch, PowerSnapshot};
 pub use constraints::AllConstraints;
+pub use ev_allocation::{EvAllocation, EvAllocationPolicy};
 pub use inputs::PowerFlowInputs;
 // PowerFlowModel is exported via model module, not re-exported here
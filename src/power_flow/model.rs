@@ -1,6 +1,9 @@
 #![allow(dead_code)]
 
-use super::{AllConstraints, PowerFlowInputs, PowerSnapshot};
+use super::ev_allocation::{self, EvAllocation, EvDemand};
+use super::inputs::{ControllableLoadInput, EvState};
+use super::{AllConstraints, LoadSwitch, PowerFlowInputs, PowerSnapshot};
+use crate::simulation::three_phase::ThreePhasePower;
 
 // EV Charging Urgency Thresholds
 const EV_HIGH_URGENCY_THRESHOLD: f64 = 0.8; // Above this: use max power even if importing
@@ -15,6 +18,9 @@ const BATTERY_HYSTERESIS_PERCENT: f64 = 2.0; // SoC buffer to prevent oscillatio
 // Grid Price Arbitrage Thresholds
 const CHEAP_GRID_PRICE_MULTIPLIER: f64 = 0.5; // Charge when price < threshold * 0.5
 
+// Slack on the per-phase current check for floating point error (A)
+const PHASE_CURRENT_TOLERANCE_A: f64 = 0.01;
+
 /// Power Flow Model - THE CORE ALGORITHM
 ///
 /// This model computes optimal power flows while respecting the constraint hierarchy:
@@ -36,30 +42,59 @@ impl PowerFlowModel {
     /// This is the CORE algorithm that orchestrates all energy flows.
     ///
     /// Algorithm steps:
-    /// 1. House load gets priority (always satisfied)
+    /// 1. House load gets priority (always satisfied), switchable loads
+    ///    are added while the fuse allows
     /// 2. Allocate PV to house first
     /// 3. Calculate EV charging urgency
     /// 4. Allocate power to EV with fuse protection
     /// 5. Charge battery from excess PV
     /// 6. Apply arbitrage logic (charge when cheap, discharge when expensive)
     /// 7. Export excess PV to grid (if beneficial)
-    /// 8. Verify power balance and fuse limits
+    /// 8. Verify power balance, fuse limits and per-phase currents
+    ///
+    /// Every phase is tracked separately: EV and battery power is limited
+    /// so that no phase exceeds its fuse, even when the total looks fine.
     pub fn compute_flows(&self, inputs: &PowerFlowInputs) -> Result<PowerSnapshot, String> {
-        // Step 1: House load always has priority
-        let house_kw = inputs.house_load_kw;
+        // Step 1: House load always has priority; switchable loads join it
+        // only where the fuse has room for them
+        let pv_phases = self
+            .phase_power(self.constraints.physical.pv_phase, inputs.pv_production_kw * 1000.0);
+        let (house_kw, house_phases, load_switches) =
+            self.switch_controllable_loads(inputs, &pv_phases);
 
         // Step 2: Allocate PV to house first
         let pv_to_house = inputs.pv_production_kw.min(house_kw);
         let remaining_pv = inputs.pv_production_kw - pv_to_house;
         let house_deficit = house_kw - pv_to_house;
 
+        // Net house load per phase after PV (W)
+        let base_phases = house_phases.sub(&pv_phases);
+
         // Step 3: Calculate EV charging urgency and allocate power
-        let (ev_kw, remaining_pv_after_ev) =
-            self.allocate_ev_power(&inputs, remaining_pv, house_deficit)?;
+        // A V2X discharge request is served by the single-EV path
+        let v2x_discharge = inputs.ev_target_power_w.is_some_and(|w| w < 0.0);
+        let (ev_kw, remaining_pv_after_ev, ev_allocations, ev_phases) =
+            if inputs.ev_chargers.is_empty() || v2x_discharge {
+                let (ev_kw, remaining_pv) =
+                    self.allocate_ev_power(inputs, remaining_pv, house_deficit, &base_phases)?;
+                let ev_phases = self.phase_power(None, ev_kw * 1000.0);
+                (ev_kw, remaining_pv, Vec::new(), ev_phases)
+            } else {
+                self.allocate_charger_power(inputs, remaining_pv, house_deficit, &base_phases)
+            };
+        let base_phases = base_phases.add(&ev_phases);
 
-        // Step 4: Battery power decision (charge, discharge, or idle)
+        // Step 4: Battery power decision (charge, discharge, or idle),
+        // kept within what the battery's phases can take
         let (battery_kw, _remaining_pv_after_battery) =
-            self.decide_battery_power(&inputs, remaining_pv_after_ev, house_deficit);
+            self.decide_battery_power(inputs, remaining_pv_after_ev, house_deficit);
+        let (min_battery_kw, max_battery_kw) =
+            self.phase_power_range_kw(&base_phases, self.constraints.physical.battery_phase);
+        let battery_kw = battery_kw
+            .min(max_battery_kw.max(0.0))
+            .max(min_battery_kw.min(0.0));
+        let battery_phases =
+            self.phase_power(self.constraints.physical.battery_phase, battery_kw * 1000.0);
 
         // Step 5: Grid power (import/export)
         let grid_kw =
@@ -72,6 +107,9 @@ impl PowerFlowModel {
             battery_kw,
             ev_kw,
             grid_kw,
+            grid_phases: Some(base_phases.add(&battery_phases)),
+            ev_allocations,
+            load_switches,
             timestamp: inputs.timestamp,
         };
 
@@ -87,6 +125,7 @@ impl PowerFlowModel {
         inputs: &PowerFlowInputs,
         available_pv_kw: f64,
         house_deficit_kw: f64,
+        base_phases: &ThreePhasePower,
     ) -> Result<(f64, f64), String> {
         if let Some(target_w) = inputs.ev_target_power_w {
             let target_kw = target_w / 1000.0;
@@ -128,31 +167,9 @@ impl PowerFlowModel {
             _ => return Ok((0.0, available_pv_kw)), // No EV or doesn't need charging
         };
 
-        // Calculate urgency (0-1)
-        let urgency = ev_state.urgency_factor(inputs.timestamp);
-
-        // Determine EV charge power based on urgency and available power
-        let computed_ev_kw = if urgency > EV_HIGH_URGENCY_THRESHOLD {
-            // High urgency: use max power even if it means importing from grid
-            ev_state
-                .max_charge_kw
-                .min(self.constraints.physical.max_grid_import_kw)
-        } else if urgency > EV_MEDIUM_URGENCY_THRESHOLD {
-            // Medium urgency: use available PV + some grid if needed
-            let min_kw = available_pv_kw;
-            let max_kw = ev_state.max_charge_kw;
-            // Interpolate based on urgency
-            let urgency_range = urgency - EV_MEDIUM_URGENCY_THRESHOLD;
-            let urgency_span = EV_HIGH_URGENCY_THRESHOLD - EV_MEDIUM_URGENCY_THRESHOLD;
-            min_kw + (max_kw - min_kw) * (urgency_range / urgency_span)
-        } else {
-            // Low urgency: only use excess PV (solar priority)
-            available_pv_kw
-        };
-
         let desired_ev_kw = match inputs.ev_target_power_w {
             Some(target_w) => (target_w / 1000.0).max(0.0),
-            None => computed_ev_kw,
+            None => self.desired_ev_kw(ev_state, inputs.timestamp, available_pv_kw),
         };
 
         // Apply EV charger min/max current limits
@@ -162,6 +179,10 @@ impl PowerFlowModel {
         let max_ev_kw_by_fuse = (self.constraints.physical.max_grid_import_kw - house_deficit_kw
             + available_pv_kw)
             .max(0.0);
+        // The charger draws evenly from the site's phases, so the fullest
+        // phase sets the limit
+        let (_, max_ev_kw_by_phase) = self.phase_power_range_kw(base_phases, None);
+        let max_ev_kw_by_fuse = max_ev_kw_by_fuse.min(max_ev_kw_by_phase.max(0.0));
 
         let ev_kw = if desired_ev_kw >= evse_min_kw {
             desired_ev_kw
@@ -179,6 +200,218 @@ impl PowerFlowModel {
         Ok((ev_kw, remaining_pv))
     }
 
+    /// Charge power a vehicle wants, from its urgency and the spare PV
+    fn desired_ev_kw(
+        &self,
+        ev: &EvState,
+        now: chrono::DateTime<chrono::Utc>,
+        available_pv_kw: f64,
+    ) -> f64 {
+        // Calculate urgency (0-1)
+        let urgency = ev.urgency_factor(now);
+
+        // Determine EV charge power based on urgency and available power
+        if urgency > EV_HIGH_URGENCY_THRESHOLD {
+            // High urgency: use max power even if it means importing from grid
+            ev.max_charge_kw
+                .min(self.constraints.physical.max_grid_import_kw)
+        } else if urgency > EV_MEDIUM_URGENCY_THRESHOLD {
+            // Medium urgency: use available PV + some grid if needed
+            let min_kw = available_pv_kw;
+            let max_kw = ev.max_charge_kw;
+            // Interpolate based on urgency
+            let urgency_range = urgency - EV_MEDIUM_URGENCY_THRESHOLD;
+            let urgency_span = EV_HIGH_URGENCY_THRESHOLD - EV_MEDIUM_URGENCY_THRESHOLD;
+            min_kw + (max_kw - min_kw) * (urgency_range / urgency_span)
+        } else {
+            // Low urgency: only use excess PV (solar priority)
+            available_pv_kw
+        }
+    }
+
+    /// Share fuse headroom between the chargers of a multi-charger site
+    ///
+    /// Each vehicle's request follows the single-EV urgency rules, with the
+    /// spare PV split evenly between the vehicles that want energy. The
+    /// requests are then fitted into the per-phase headroom left by the
+    /// house using the configured allocation policy, so a single-phase
+    /// charger can use a lightly loaded phase.
+    fn allocate_charger_power(
+        &self,
+        inputs: &PowerFlowInputs,
+        available_pv_kw: f64,
+        house_deficit_kw: f64,
+        base_phases: &ThreePhasePower,
+    ) -> (f64, f64, Vec<EvAllocation>, ThreePhasePower) {
+        let physical = &self.constraints.physical;
+        let voltage = physical.grid_voltage_v;
+        let site_phases = physical.phases.max(1) as usize;
+
+        let wanting = inputs
+            .ev_chargers
+            .iter()
+            .filter(|c| c.ev.needs_charging())
+            .count();
+        let pv_share_kw = available_pv_kw / wanting.max(1) as f64;
+
+        let demands: Vec<EvDemand> = inputs
+            .ev_chargers
+            .iter()
+            .map(|charger| {
+                let kw_per_amp = voltage * charger.phases as f64 / 1000.0;
+                let requested_current_a = if charger.ev.needs_charging() && kw_per_amp > 0.0 {
+                    let desired_kw = match charger.scheduled_power_kw {
+                        Some(scheduled_kw) => scheduled_kw.max(pv_share_kw),
+                        None => self.desired_ev_kw(&charger.ev, inputs.timestamp, pv_share_kw),
+                    };
+                    (desired_kw / kw_per_amp)
+                        .min(charger.ev.max_charge_kw / kw_per_amp)
+                        .min(charger.max_current_a)
+                        .min(physical.evse_max_current_a)
+                        .max(0.0)
+                } else {
+                    0.0
+                };
+                EvDemand {
+                    id: charger.id.clone(),
+                    phases: charger.phases,
+                    phase: charger.phase,
+                    min_current_a: charger.min_current_a.max(physical.evse_min_current_a),
+                    requested_current_a,
+                    priority: charger.priority,
+                    departure_time: charger.ev.departure_time,
+                }
+            })
+            .collect();
+
+        // Fuse headroom left on each phase by the house, and the import
+        // limit as a budget over all phases together
+        let phase_limit_a = physical.phase_limit_a();
+        let headroom_a: Vec<f64> = base_phases.as_array()[..site_phases]
+            .iter()
+            .map(|w| (phase_limit_a - w / voltage).max(0.0))
+            .collect();
+        let total_headroom_a = (physical.max_grid_import_kw - house_deficit_kw + available_pv_kw)
+            * 1000.0
+            / voltage;
+
+        let currents = ev_allocation::allocate(
+            self.constraints.economic.ev_allocation,
+            &demands,
+            &headroom_a,
+            total_headroom_a,
+        );
+
+        let mut ev_phases = [0.0; 3];
+        let allocations: Vec<EvAllocation> = demands
+            .iter()
+            .zip(currents)
+            .map(|(demand, current_a)| {
+                for (phase_w, load) in ev_phases.iter_mut().zip(demand.phase_load(site_phases)) {
+                    *phase_w += current_a * load * voltage;
+                }
+                EvAllocation {
+                    id: demand.id.clone(),
+                    current_a,
+                    power_kw: current_a * voltage * demand.phases as f64 / 1000.0,
+                }
+            })
+            .collect();
+
+        let ev_kw: f64 = allocations.iter().map(|a| a.power_kw).sum();
+        let remaining_pv = available_pv_kw - ev_kw.min(available_pv_kw);
+        let [l1_w, l2_w, l3_w] = ev_phases;
+        (ev_kw, remaining_pv, allocations, ThreePhasePower::new(l1_w, l2_w, l3_w))
+    }
+
+    /// Decide which switchable loads may be on, and the house load with them
+    ///
+    /// Running loads are taken out of the measured house load and added
+    /// back one by one, running loads before new starts, while every phase
+    /// and the import limit have room. A running load that no longer fits
+    /// is shed; a start that does not fit waits for a later tick.
+    fn switch_controllable_loads(
+        &self,
+        inputs: &PowerFlowInputs,
+        pv_phases: &ThreePhasePower,
+    ) -> (f64, ThreePhasePower, Vec<LoadSwitch>) {
+        let mut house_kw = inputs.house_load_kw;
+        let mut house_phases = self.house_phases(inputs);
+        if inputs.controllable_loads.is_empty() {
+            return (house_kw, house_phases, Vec::new());
+        }
+
+        for load in inputs.controllable_loads.iter().filter(|l| l.running) {
+            house_kw -= load.power_kw;
+            house_phases = house_phases.sub(&self.phase_power(load.phase, load.power_kw * 1000.0));
+        }
+        house_kw = house_kw.max(0.0);
+
+        let mut loads: Vec<&ControllableLoadInput> = inputs.controllable_loads.iter().collect();
+        loads.sort_by_key(|l| !l.running);
+
+        let max_import_kw = self.constraints.physical.max_grid_import_kw;
+        let switches = loads
+            .into_iter()
+            .map(|load| {
+                let (_, max_kw) =
+                    self.phase_power_range_kw(&house_phases.sub(pv_phases), load.phase);
+                let on = load.requested
+                    && load.power_kw <= max_kw
+                    && house_kw + load.power_kw - inputs.pv_production_kw <= max_import_kw;
+                if on {
+                    house_kw += load.power_kw;
+                    house_phases =
+                        house_phases.add(&self.phase_power(load.phase, load.power_kw * 1000.0));
+                }
+                LoadSwitch {
+                    id: load.id.clone(),
+                    on,
+                }
+            })
+            .collect();
+
+        (house_kw, house_phases, switches)
+    }
+
+    /// Household load per phase (W)
+    ///
+    /// Uses the measured split on three-phase sites and spreads the total
+    /// evenly otherwise.
+    fn house_phases(&self, inputs: &PowerFlowInputs) -> ThreePhasePower {
+        match inputs.house_load_phases {
+            Some(phases) if self.constraints.physical.phases >= 3 => phases,
+            _ => self.phase_power(None, inputs.house_load_kw * 1000.0),
+        }
+    }
+
+    /// Power (W) of a device wired to `phase` (`None` = all phases), per phase
+    fn phase_power(&self, phase: Option<u8>, power_w: f64) -> ThreePhasePower {
+        self.constraints.physical.distribution(phase).distribute(power_w)
+    }
+
+    /// Range of power (kW, positive = consumption) a device wired to `phase`
+    /// can take before any of its phases leaves the per-phase limit, given
+    /// the load already on each phase (W)
+    fn phase_power_range_kw(&self, base_phases: &ThreePhasePower, phase: Option<u8>) -> (f64, f64) {
+        let physical = &self.constraints.physical;
+        let limit_w = physical.phase_limit_a() * physical.grid_voltage_v;
+        let share = self.phase_power(phase, 1.0).as_array();
+
+        let (min_w, max_w) = base_phases
+            .as_array()
+            .iter()
+            .zip(share)
+            .filter(|(_, share)| *share > 0.0)
+            .fold((f64::NEG_INFINITY, f64::INFINITY), |(min_w, max_w), (base_w, share)| {
+                (
+                    min_w.max((-limit_w - base_w) / share),
+                    max_w.min((limit_w - base_w) / share),
+                )
+            });
+        (min_w / 1000.0, max_w / 1000.0)
+    }
+
     /// Decide battery power (charge/discharge/idle)
     ///
     /// Priority hierarchy:
@@ -293,6 +526,24 @@ impl PowerFlowModel {
             ));
         }
 
+        // Verify every phase stays within its fuse
+        let physical = &self.constraints.physical;
+        let phase_limit_a = physical.phase_limit_a();
+        if let Some(phase) =
+            snapshot.overloaded_phase(phase_limit_a + PHASE_CURRENT_TOLERANCE_A, physical.grid_voltage_v)
+        {
+            let current_a = snapshot
+                .grid_phases
+                .map(|p| p.as_array()[phase - 1] / physical.grid_voltage_v)
+                .unwrap_or_default();
+            return Err(format!(
+                "Phase L{} current {:.1}A exceeds {:.1}A limit",
+                phase,
+                current_a.abs(),
+                phase_limit_a
+            ));
+        }
+
         // Verify export limit
         if snapshot.exceeds_export_limit(self.constraints.physical.max_grid_export_kw) {
             return Err(format!(
@@ -327,7 +578,8 @@ impl PowerFlowModel {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::power_flow::inputs::EvState;
+    use crate::power_flow::inputs::{ControllableLoadInput, EvChargerInput, EvState};
+    use crate::simulation::three_phase::ThreePhasePower;
     use chrono::Utc;
 
     fn test_constraints() -> AllConstraints {
@@ -339,10 +591,18 @@ mod tests {
         constraints.physical.evse_min_current_a = 6.0;
         constraints.physical.evse_max_current_a = 32.0;
         constraints.physical.phases = 1;
+        constraints.physical.max_current_per_phase_a = None;
         constraints.physical.grid_voltage_v = 230.0;
         constraints
     }
 
+    fn three_phase_constraints(max_current_per_phase_a: f64) -> AllConstraints {
+        let mut constraints = test_constraints();
+        constraints.physical.phases = 3;
+        constraints.physical.max_current_per_phase_a = Some(max_current_per_phase_a);
+        constraints
+    }
+
     #[test]
     fn test_simple_pv_to_house() {
         let model = PowerFlowModel::new(test_constraints());
@@ -476,4 +736,188 @@ mod tests {
         );
         assert!(snapshot.verify_power_balance());
     }
+
+    fn ev_charger(id: &str, soc_percent: f64, departure_hours: i64, priority: u8) -> EvChargerInput {
+        EvChargerInput {
+            id: id.to_string(),
+            ev: EvState {
+                connected: true,
+                soc_percent,
+                capacity_kwh: 60.0,
+                max_charge_kw: 11.0,
+                max_discharge_kw: 0.0,
+                departure_time: Some(Utc::now() + chrono::Duration::hours(departure_hours)),
+                target_soc_percent: 80.0,
+            },
+            phases: 3,
+            phase: 1,
+            min_current_a: 6.0,
+            max_current_a: 16.0,
+            priority,
+            scheduled_power_kw: None,
+        }
+    }
+
+    #[test]
+    fn test_multiple_chargers_share_phase_headroom() {
+        let model = PowerFlowModel::new(three_phase_constraints(25.0));
+        // 2.07 kW house load = 3 A per phase, leaving 22 A for two urgent cars
+        let inputs = PowerFlowInputs::new_now(0.0, 2.07, 50.0, 25.0, 1.5)
+            .with_ev_charger(ev_charger("a", 10.0, 2, 0))
+            .with_ev_charger(ev_charger("b", 10.0, 2, 0));
+
+        let snapshot = model.compute_flows(&inputs).unwrap();
+
+        assert_eq!(snapshot.ev_allocations.len(), 2);
+        for allocation in &snapshot.ev_allocations {
+            assert!((allocation.current_a - 11.0).abs() < 0.01, "{:?}", allocation);
+        }
+        assert!((snapshot.ev_kw - 2.0 * 11.0 * 230.0 * 3.0 / 1000.0).abs() < 0.01);
+        assert!(snapshot.verify_power_balance());
+    }
+
+    #[test]
+    fn test_charger_below_six_amp_floor_is_paused() {
+        let mut constraints = three_phase_constraints(10.0);
+        constraints.economic.ev_allocation = crate::power_flow::EvAllocationPolicy::Priority;
+
+        let model = PowerFlowModel::new(constraints);
+        let inputs = PowerFlowInputs::new_now(0.0, 0.0, 50.0, 25.0, 1.5)
+            .with_ev_charger(ev_charger("first", 10.0, 2, 0))
+            .with_ev_charger(ev_charger("second", 10.0, 2, 1));
+
+        let snapshot = model.compute_flows(&inputs).unwrap();
+
+        // Only 4 A would be left for the second car, below the 6 A floor
+        assert!((snapshot.ev_allocations[0].current_a - 10.0).abs() < 0.01);
+        assert_eq!(snapshot.ev_allocations[1].current_a, 0.0);
+    }
+
+    #[test]
+    fn test_scheduled_charger_power_replaces_urgency() {
+        let model = PowerFlowModel::new(three_phase_constraints(25.0));
+        // Urgent, but the schedule moved its charging to a later period
+        let mut waiting = ev_charger("waiting", 10.0, 2, 0);
+        waiting.scheduled_power_kw = Some(0.0);
+        // Relaxed, but the schedule charges it now
+        let mut planned = ev_charger("planned", 10.0, 48, 0);
+        planned.scheduled_power_kw = Some(6.9);
+
+        let inputs = PowerFlowInputs::new_now(0.0, 0.0, 50.0, 25.0, 1.5)
+            .with_ev_charger(waiting)
+            .with_ev_charger(planned);
+
+        let snapshot = model.compute_flows(&inputs).unwrap();
+
+        assert_eq!(snapshot.ev_allocations[0].current_a, 0.0);
+        assert!((snapshot.ev_allocations[1].current_a - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_single_phase_chargers_follow_phase_load() {
+        let model = PowerFlowModel::new(three_phase_constraints(25.0));
+        let mut on_l1 = ev_charger("l1", 10.0, 2, 0);
+        on_l1.phases = 1;
+        let mut on_l2 = ev_charger("l2", 10.0, 2, 0);
+        on_l2.phases = 1;
+        on_l2.phase = 2;
+
+        // 20 A of house load on L1 leaves 5 A there, below the 6 A floor
+        let inputs = PowerFlowInputs::new_now(0.0, 4.6, 50.0, 25.0, 1.5)
+            .with_house_load_phases(ThreePhasePower::new(4600.0, 0.0, 0.0))
+            .with_ev_charger(on_l1)
+            .with_ev_charger(on_l2);
+
+        let snapshot = model.compute_flows(&inputs).unwrap();
+
+        assert_eq!(snapshot.ev_allocations[0].current_a, 0.0);
+        assert!((snapshot.ev_allocations[1].current_a - 16.0).abs() < 0.01);
+        let currents = snapshot.grid_phase_currents(230.0).unwrap();
+        assert!(currents.max_phase() <= 25.0 + 0.01);
+    }
+
+    #[test]
+    fn test_single_phase_battery_limited_by_its_phase() {
+        let mut constraints = three_phase_constraints(25.0);
+        constraints.physical.battery_phase = Some(1);
+        let model = PowerFlowModel::new(constraints);
+
+        // Cheap price and low SoC ask for 5 kW, but L1 already carries 20 A
+        let inputs = PowerFlowInputs::new_now(0.0, 4.6, 21.0, 25.0, 0.5)
+            .with_house_load_phases(ThreePhasePower::new(4600.0, 0.0, 0.0));
+
+        let snapshot = model.compute_flows(&inputs).unwrap();
+
+        assert!((snapshot.battery_kw - 1.15).abs() < 0.01, "{}", snapshot);
+        assert_eq!(snapshot.overloaded_phase(25.0 + 0.01, 230.0), None);
+    }
+
+    #[test]
+    fn test_overloaded_phase_rejected_when_total_is_within_limit() {
+        let model = PowerFlowModel::new(three_phase_constraints(25.0));
+
+        // 6.9 kW is far below the 20 kW import limit, but it is all on L3
+        let inputs = PowerFlowInputs::new_now(0.0, 6.9, 21.0, 25.0, 1.5)
+            .with_house_load_phases(ThreePhasePower::new(0.0, 0.0, 6900.0));
+
+        let err = model.compute_flows(&inputs).unwrap_err();
+
+        assert!(err.contains("Phase L3"), "{}", err);
+    }
+
+    fn controllable_load(id: &str, power_kw: f64, running: bool) -> ControllableLoadInput {
+        ControllableLoadInput {
+            id: id.to_string(),
+            power_kw,
+            phase: Some(1),
+            running,
+            requested: true,
+        }
+    }
+
+    #[test]
+    fn test_controllable_load_start_waits_for_phase_headroom() {
+        let model = PowerFlowModel::new(three_phase_constraints(25.0));
+
+        // L1 carries 20 A; a 2 kW heater on L1 would take it to 28.7 A
+        let inputs = PowerFlowInputs::new_now(0.0, 4.6, 50.0, 25.0, 1.5)
+            .with_house_load_phases(ThreePhasePower::new(4600.0, 0.0, 0.0))
+            .with_controllable_load(controllable_load("heater", 2.0, false))
+            .with_controllable_load(controllable_load("pump", 1.0, false));
+
+        let snapshot = model.compute_flows(&inputs).unwrap();
+
+        assert_eq!(
+            snapshot.load_switches,
+            vec![
+                LoadSwitch { id: "heater".to_string(), on: false },
+                LoadSwitch { id: "pump".to_string(), on: true },
+            ]
+        );
+        assert!((snapshot.house_kw - 5.6).abs() < 1e-9);
+        assert!(snapshot.verify_power_balance());
+    }
+
+    #[test]
+    fn test_running_controllable_load_keeps_its_place() {
+        let model = PowerFlowModel::new(three_phase_constraints(25.0));
+
+        // The running pump is part of the measured 24.3 A on L1, so it
+        // stays on while the heater has to wait
+        let inputs = PowerFlowInputs::new_now(0.0, 5.6, 50.0, 25.0, 1.5)
+            .with_house_load_phases(ThreePhasePower::new(5600.0, 0.0, 0.0))
+            .with_controllable_load(controllable_load("heater", 1.0, false))
+            .with_controllable_load(controllable_load("pump", 1.0, true));
+
+        let snapshot = model.compute_flows(&inputs).unwrap();
+
+        assert_eq!(
+            snapshot.load_switches,
+            vec![
+                LoadSwitch { id: "pump".to_string(), on: true },
+                LoadSwitch { id: "heater".to_string(), on: false },
+            ]
+        );
+        assert!((snapshot.house_kw - 5.6).abs() < 1e-9);
+    }
 }
@@ -2,6 +2,9 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use super::ev_allocation::EvAllocation;
+use crate::simulation::three_phase::{ThreePhaseCurrent, ThreePhasePower};
+
 /// Power snapshot representing all energy flows at a single point in time
 ///
 /// Power balance equation: PV + Battery + Grid = House + EV (EV may be negative when discharging)
@@ -22,10 +25,29 @@ pub struct PowerSnapshot {
     /// Grid power (positive = import, negative = export)
     pub grid_kw: f64,
 
+    /// Grid power per phase (W, positive = import), when known
+    #[serde(default)]
+    pub grid_phases: Option<ThreePhasePower>,
+
+    /// Per-charger share of `ev_kw` on multi-charger sites
+    #[serde(default)]
+    pub ev_allocations: Vec<EvAllocation>,
+
+    /// On/off decision for every switchable load, part of `house_kw`
+    #[serde(default)]
+    pub load_switches: Vec<LoadSwitch>,
+
     /// Timestamp of this snapshot
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+/// Whether a switchable load may be on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadSwitch {
+    pub id: String,
+    pub on: bool,
+}
+
 impl PowerSnapshot {
     /// Create a new power snapshot with explicit timestamp
     ///
@@ -47,6 +69,9 @@ impl PowerSnapshot {
             battery_kw,
             ev_kw,
             grid_kw,
+            grid_phases: None,
+            ev_allocations: Vec::new(),
+            load_switches: Vec::new(),
             timestamp,
         }
     }
@@ -99,6 +124,19 @@ impl PowerSnapshot {
         self.grid_import_kw() > fuse_limit_kw
     }
 
+    /// Grid current per phase, when the per-phase flows are known
+    pub fn grid_phase_currents(&self, voltage_v: f64) -> Option<ThreePhaseCurrent> {
+        self.grid_phases.map(|p| p.currents(voltage_v))
+    }
+
+    /// Phase (1-3) whose current exceeds `limit_a` in either direction
+    ///
+    /// The aggregate fuse check misses a single overloaded phase when
+    /// the load is unbalanced.
+    pub fn overloaded_phase(&self, limit_a: f64, voltage_v: f64) -> Option<usize> {
+        self.grid_phase_currents(voltage_v)?.overloaded_phase(limit_a)
+    }
+
     /// Check if grid export exceeds limit
     pub fn exceeds_export_limit(&self, export_limit_kw: f64) -> bool {
         self.grid_export_kw() > export_limit_kw
@@ -193,6 +231,17 @@ mod tests {
         assert!(!snapshot.exceeds_fuse_limit(20.0));
     }
 
+    #[test]
+    fn test_overloaded_phase_with_balanced_total() {
+        // 5.5 kW total is well below 3x25 A, but all of it is on L2
+        let mut snapshot = PowerSnapshot::new_now(0.0, 5.5, 0.0, 0.0, 5.5);
+        assert_eq!(snapshot.overloaded_phase(20.0, 230.0), None);
+
+        snapshot.grid_phases = Some(ThreePhasePower::new(0.0, 5500.0, 0.0));
+        assert_eq!(snapshot.overloaded_phase(20.0, 230.0), Some(2));
+        assert_eq!(snapshot.overloaded_phase(25.0, 230.0), None);
+    }
+
     #[test]
     fn test_self_consumption() {
         // PV 10kW, House 3kW, export 7kW -> self-consumption = 3kW
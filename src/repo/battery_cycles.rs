@@ -0,0 +1,79 @@
+#![cfg(feature = "db")]
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A day of battery cycling
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BatteryCycleRow {
+    pub device_id: Option<Uuid>,
+    pub cycle_date: NaiveDate,
+    pub equivalent_full_cycles: f64,
+    pub avg_depth_of_discharge: Option<f64>,
+    pub max_temperature_c: Option<f64>,
+    pub total_energy_throughput_kwh: f64,
+}
+
+pub struct BatteryCycleRepository<'a> {
+    pool: &'a PgPool,
+}
+
+impl<'a> BatteryCycleRepository<'a> {
+    pub fn new(pool: &'a PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Insert a day, replacing any row already stored for it
+    pub async fn upsert(&self, row: &BatteryCycleRow) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO battery_cycles (
+                device_id, cycle_date, equivalent_full_cycles, avg_depth_of_discharge,
+                max_temperature_c, total_energy_throughput_kwh
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (device_id, cycle_date) DO UPDATE SET
+                equivalent_full_cycles = EXCLUDED.equivalent_full_cycles,
+                avg_depth_of_discharge = EXCLUDED.avg_depth_of_discharge,
+                max_temperature_c = EXCLUDED.max_temperature_c,
+                total_energy_throughput_kwh = EXCLUDED.total_energy_throughput_kwh
+            "#,
+            row.device_id,
+            row.cycle_date,
+            row.equivalent_full_cycles,
+            row.avg_depth_of_discharge,
+            row.max_temperature_c,
+            row.total_energy_throughput_kwh,
+        )
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Days from `from` on, oldest first
+    pub async fn find_since(
+        &self,
+        device_id: Uuid,
+        from: NaiveDate,
+    ) -> Result<Vec<BatteryCycleRow>> {
+        let rows = sqlx::query_as!(
+            BatteryCycleRow,
+            r#"
+            SELECT device_id, cycle_date, equivalent_full_cycles, avg_depth_of_discharge,
+                   max_temperature_c, total_energy_throughput_kwh
+            FROM battery_cycles
+            WHERE device_id = $1 AND cycle_date >= $2
+            ORDER BY cycle_date ASC
+            "#,
+            device_id,
+            from,
+        )
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+}
@@ -26,11 +26,15 @@ impl Repositories {
 #[cfg(feature = "db")]
 pub mod pg;
 
+#[cfg(feature = "db")]
+pub mod battery_cycles;
 #[cfg(feature = "db")]
 pub mod battery_states;
 #[cfg(feature = "db")]
 pub mod devices;
 #[cfg(feature = "db")]
+pub mod optimization_runs;
+#[cfg(feature = "db")]
 pub mod schedules;
 #[cfg(feature = "db")]
 pub mod prices;
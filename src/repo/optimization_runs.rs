@@ -0,0 +1,95 @@
+#![cfg(feature = "db")]
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// One optimization run; `result_json` holds the schedule's explanation
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OptimizationRunRow {
+    pub id: Uuid,
+    pub household_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub objective: String,
+    pub optimizer_version: String,
+    pub constraints_json: serde_json::Value,
+    pub result_json: serde_json::Value,
+    pub cost_estimate_sek: Option<f64>,
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+pub struct OptimizationRunRepository<'a> {
+    pool: &'a PgPool,
+}
+
+impl<'a> OptimizationRunRepository<'a> {
+    pub fn new(pool: &'a PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, run: &OptimizationRunRow) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO optimization_runs (
+                id, household_id, created_at, duration_ms, objective, optimizer_version,
+                constraints_json, result_json, cost_estimate_sek, success, error_message
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            run.id,
+            run.household_id,
+            run.created_at,
+            run.duration_ms,
+            run.objective,
+            run.optimizer_version,
+            run.constraints_json,
+            run.result_json,
+            run.cost_estimate_sek,
+            run.success,
+            run.error_message,
+        )
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<OptimizationRunRow>> {
+        let run = sqlx::query_as!(
+            OptimizationRunRow,
+            r#"
+            SELECT id, household_id, created_at, duration_ms, objective, optimizer_version,
+                   constraints_json, result_json, cost_estimate_sek, success, error_message
+            FROM optimization_runs
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(run)
+    }
+
+    /// The latest `limit` runs, newest first
+    pub async fn find_recent(&self, limit: i64) -> Result<Vec<OptimizationRunRow>> {
+        let runs = sqlx::query_as!(
+            OptimizationRunRow,
+            r#"
+            SELECT id, household_id, created_at, duration_ms, objective, optimizer_version,
+                   constraints_json, result_json, cost_estimate_sek, success, error_message
+            FROM optimization_runs
+            ORDER BY created_at DESC
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(runs)
+    }
+}
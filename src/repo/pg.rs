@@ -11,7 +11,9 @@ pub use battery_states::BatteryStateRepository;
 pub use devices::DeviceRepository;
 pub use schedules::ScheduleRepository;
 
+use crate::repo::battery_cycles::BatteryCycleRepository;
 use crate::repo::consumption::ConsumptionRepository;
+use crate::repo::optimization_runs::OptimizationRunRepository;
 
 pub struct PgRepo {
     pub pool: PgPool,
@@ -42,11 +44,21 @@ impl PgRepo {
         BatteryStateRepository::new(&self.pool)
     }
 
+    /// Get a battery cycle repository
+    pub fn battery_cycles(&self) -> BatteryCycleRepository {
+        BatteryCycleRepository::new(&self.pool)
+    }
+
     /// Get a schedule repository
     pub fn schedules(&self) -> ScheduleRepository {
         ScheduleRepository::new(&self.pool)
     }
 
+    /// Get an optimization run repository
+    pub fn optimization_runs(&self) -> OptimizationRunRepository {
+        OptimizationRunRepository::new(&self.pool)
+    }
+
     /// Get a consumption history repository
     pub fn consumption(&self) -> ConsumptionRepository {
         ConsumptionRepository::new(self.pool.clone())
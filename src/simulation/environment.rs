@@ -157,6 +157,11 @@ impl Environment {
         self.grid_sim.is_available()
     }
 
+    /// Current grid simulator state (import/export, voltage, frequency)
+    pub fn grid_state(&self) -> &GridState {
+        self.grid_sim.state()
+    }
+
     /// Get net load (house - solar) in kW
     pub fn net_load_kw(&self) -> f64 {
         (self.house_load_kw() - self.solar_production_kw()).max(0.0)
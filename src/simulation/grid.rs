@@ -221,11 +221,7 @@ impl GridSimulator {
 
     /// Simulate random fault events
     fn simulate_faults(&mut self, minutes_elapsed: i64) {
-        if !self.config.enable_faults {
-            return;
-        }
-
-        // Check if currently in a fault
+        // Check if currently in a fault (random or injected)
         if self.fault_duration_remaining > 0 {
             self.fault_duration_remaining -= minutes_elapsed;
             if self.fault_duration_remaining <= 0 {
@@ -236,6 +232,10 @@ impl GridSimulator {
             return;
         }
 
+        if !self.config.enable_faults {
+            return;
+        }
+
         // Check for new fault
         let probability_per_minute = self.config.fault_probability_per_hour / 60.0;
         for _ in 0..minutes_elapsed {
@@ -272,6 +272,18 @@ impl GridSimulator {
         measured_voltage.clamp(180.0, 260.0)
     }
 
+    /// Inject a fault lasting `duration_minutes`, e.g. a frequency event to
+    /// test FCR-D/FFR response
+    ///
+    /// Frequency and voltage follow the fault from the next tick.
+    pub fn trigger_fault(&mut self, fault: GridFaultType, duration_minutes: i64) {
+        let affects_availability = matches!(fault, GridFaultType::FuseTripped | GridFaultType::Outage);
+        self.current_state.fault = fault;
+        self.fault_duration_remaining = duration_minutes.max(1);
+        self.current_state.fault_duration_minutes = self.fault_duration_remaining;
+        self.current_state.is_available = !affects_availability;
+    }
+
     /// Trigger a random fault
     fn trigger_random_fault(&mut self) {
         let fault_type = self.rng.gen_range(0..100);
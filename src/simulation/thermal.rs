@@ -49,8 +49,29 @@ impl ThermalZoneConfig {
             ..Default::default()
         }
     }
+
+    /// Heat lost through the envelope and ventilation per kelvin indoor-outdoor (W/K)
+    pub fn heat_loss_coefficient_w_per_k(&self) -> f64 {
+        let wall_area_m2 = (self.floor_area_m2.sqrt() * 4.0) * CEILING_HEIGHT_M;
+        let volume_m3 = self.floor_area_m2 * CEILING_HEIGHT_M;
+
+        wall_area_m2 * self.wall_u_value
+            + self.window_area_m2 * self.window_u_value
+            + self.floor_area_m2 * self.ceiling_u_value
+            + self.floor_area_m2 * self.floor_u_value
+            + (volume_m3 * self.air_changes_per_hour / 3600.0) * AIR_DENSITY * AIR_SPECIFIC_HEAT
+    }
+
+    /// Effective heat capacity of the building, air plus furnishings and structure (J/K)
+    pub fn thermal_mass_j_per_k(&self) -> f64 {
+        self.floor_area_m2 * CEILING_HEIGHT_M * AIR_DENSITY * AIR_SPECIFIC_HEAT * 50.0
+    }
 }
 
+const CEILING_HEIGHT_M: f64 = 2.5;
+const AIR_DENSITY: f64 = 1.2;
+const AIR_SPECIFIC_HEAT: f64 = 1005.0;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThermalZoneState {
     pub indoor_temp_c: f64,
@@ -100,34 +121,14 @@ impl ThermalZone {
     }
 
     fn step_internal(&mut self, dt_seconds: f64, outdoor_temp_c: f64, hvac_heat_w: f64, solar_gain_w: f64) {
-        const CEILING_HEIGHT_M: f64 = 2.5;
-        const AIR_DENSITY: f64 = 1.2;
-        const AIR_SPECIFIC_HEAT: f64 = 1005.0;
-
-        let wall_area_m2 = (self.config.floor_area_m2.sqrt() * 4.0) * CEILING_HEIGHT_M;
-        let ceiling_area_m2 = self.config.floor_area_m2;
-
         let temp_delta = self.state.indoor_temp_c - outdoor_temp_c;
-
-        let wall_loss = wall_area_m2 * self.config.wall_u_value * temp_delta;
-        let window_loss = self.config.window_area_m2 * self.config.window_u_value * temp_delta;
-        let ceiling_loss = ceiling_area_m2 * self.config.ceiling_u_value * temp_delta;
-        let floor_loss = ceiling_area_m2 * self.config.floor_u_value * temp_delta;
-
-        let volume_m3 = self.config.floor_area_m2 * CEILING_HEIGHT_M;
-        let ventilation_loss = (volume_m3 * self.config.air_changes_per_hour / 3600.0)
-            * AIR_DENSITY
-            * AIR_SPECIFIC_HEAT
-            * temp_delta;
-
-        let total_heat_loss = wall_loss + window_loss + ceiling_loss + floor_loss + ventilation_loss;
+        let total_heat_loss = self.config.heat_loss_coefficient_w_per_k() * temp_delta;
 
         let total_heat_gain = hvac_heat_w + self.config.internal_gains_w + solar_gain_w;
 
         let net_heat_flow = total_heat_gain - total_heat_loss;
 
-        let thermal_mass_j_per_k = volume_m3 * AIR_DENSITY * AIR_SPECIFIC_HEAT * 50.0;
-        let temp_change = (net_heat_flow * dt_seconds) / thermal_mass_j_per_k;
+        let temp_change = (net_heat_flow * dt_seconds) / self.config.thermal_mass_j_per_k();
 
         self.state.indoor_temp_c = (self.state.indoor_temp_c + temp_change).clamp(-20.0, 40.0);
         self.state.heat_loss_w = total_heat_loss;
@@ -54,6 +54,11 @@ impl ThreePhasePower {
         self.l1_w + self.l2_w + self.l3_w
     }
 
+    /// Per-phase values as `[L1, L2, L3]`
+    pub fn as_array(&self) -> [f64; 3] {
+        [self.l1_w, self.l2_w, self.l3_w]
+    }
+
     /// Maximum single-phase power
     pub fn max_phase(&self) -> f64 {
         self.l1_w.max(self.l2_w).max(self.l3_w)
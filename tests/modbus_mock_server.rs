@@ -2,8 +2,8 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
 use tokio::sync::RwLock;
 use tokio::time::{sleep, Duration};
 
@@ -41,6 +41,10 @@ pub struct MockModbusServer {
     simulate_connection_error: Arc<RwLock<bool>>,
     /// Simulate timeout
     simulate_timeout: Arc<RwLock<bool>>,
+    /// Speak RTU framing (unit id + PDU + CRC) instead of Modbus TCP
+    rtu_framing: bool,
+    /// Send RTU responses with a broken CRC
+    corrupt_crc: Arc<RwLock<bool>>,
 }
 
 impl MockModbusServer {
@@ -53,9 +57,21 @@ impl MockModbusServer {
             response_delay_ms: 0,
             simulate_connection_error: Arc::new(RwLock::new(false)),
             simulate_timeout: Arc::new(RwLock::new(false)),
+            rtu_framing: false,
+            corrupt_crc: Arc::new(RwLock::new(false)),
         }
     }
 
+    /// Speak RTU framing, as a serial gateway or RS-485 device would
+    pub fn set_rtu_framing(&mut self, enable: bool) {
+        self.rtu_framing = enable;
+    }
+
+    /// Corrupt the CRC of every RTU response
+    pub async fn set_corrupt_crc(&self, enable: bool) {
+        *self.corrupt_crc.write().await = enable;
+    }
+
     /// Set response delay for realistic simulation
     pub fn set_response_delay(&mut self, delay_ms: u64) {
         self.response_delay_ms = delay_ms;
@@ -97,6 +113,12 @@ impl MockModbusServer {
         registers.insert(address, value);
     }
 
+    /// Load a SunSpec register image into the holding registers
+    pub async fn load_sunspec(&self, image: SunSpecImage) {
+        let base = image.base;
+        self.set_holding_registers(base, &image.finish()).await;
+    }
+
     /// Get a holding register value
     pub async fn get_holding_register(&self, address: u16) -> Option<u16> {
         let registers = self.holding_registers.read().await;
@@ -119,8 +141,19 @@ impl MockModbusServer {
         }
     }
 
+    /// Serve a single already-open stream, e.g. one end of a pty pair
+    pub async fn serve<S>(self: Arc<Self>, stream: S) -> std::io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.handle_connection(stream).await
+    }
+
     /// Handle a client connection
-    async fn handle_connection(&self, mut stream: TcpStream) -> std::io::Result<()> {
+    async fn handle_connection<S>(&self, mut stream: S) -> std::io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         let mut buffer = vec![0u8; 256];
 
         loop {
@@ -153,7 +186,15 @@ impl MockModbusServer {
             }
 
             // Process request and generate response
-            let response = self.process_request(request).await;
+            let response = if self.rtu_framing {
+                match self.process_rtu_request(request).await {
+                    Some(response) => response,
+                    // Devices stay silent on a bad frame
+                    None => continue,
+                }
+            } else {
+                self.process_request(request).await
+            };
 
             // Send response
             stream.write_all(&response).await?;
@@ -173,13 +214,41 @@ impl MockModbusServer {
         let unit_id = request[6];
         let function_code = request[7];
 
-        match function_code {
+        let mut response = match function_code {
             0x03 => self.read_holding_registers(unit_id, &request[8..]).await,
             0x04 => self.read_input_registers(unit_id, &request[8..]).await,
             0x06 => self.write_single_register(unit_id, &request[8..]).await,
             0x10 => self.write_multiple_registers(unit_id, &request[8..]).await,
             _ => self.error_response(unit_id, function_code, ExceptionCode::IllegalFunction),
+        };
+
+        // Echo the transaction ID, clients reject responses that don't match
+        response[0..2].copy_from_slice(&request[0..2]);
+        response
+    }
+
+    /// Process an RTU frame by rewrapping its PDU in an MBAP header
+    async fn process_rtu_request(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        if frame.len() < 4 {
+            return None;
         }
+        let (body, crc) = frame.split_at(frame.len() - 2);
+        if crc16(body).to_le_bytes() != [crc[0], crc[1]] {
+            return None;
+        }
+
+        let mut request = vec![0, 0, 0, 0];
+        request.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        request.extend_from_slice(body);
+        let response = self.process_request(&request).await;
+
+        let mut reply = response[6..].to_vec();
+        let mut crc = crc16(&reply);
+        if *self.corrupt_crc.read().await {
+            crc ^= 0xFFFF;
+        }
+        reply.extend_from_slice(&crc.to_le_bytes());
+        Some(reply)
     }
 
     /// Read holding registers (function code 0x03)
@@ -341,6 +410,66 @@ impl MockModbusServer {
     }
 }
 
+/// Modbus RTU CRC16 (polynomial 0xA001 reflected, initial value 0xFFFF)
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// Builder for a SunSpec register image: "SunS" marker, model chain, end model
+pub struct SunSpecImage {
+    base: u16,
+    registers: Vec<u16>,
+}
+
+impl SunSpecImage {
+    pub fn new(base: u16) -> Self {
+        Self {
+            base,
+            registers: vec![0x5375, 0x6E53],
+        }
+    }
+
+    /// Append a model of `length` registers with `values` at the given offsets
+    pub fn model(mut self, id: u16, length: u16, values: &[(u16, u16)]) -> Self {
+        let mut data = vec![0u16; length as usize];
+        for &(offset, value) in values {
+            data[offset as usize] = value;
+        }
+        self.registers.push(id);
+        self.registers.push(length);
+        self.registers.extend(data);
+        self
+    }
+
+    /// Address of the first data register of the next model appended
+    pub fn next_model_address(&self) -> u16 {
+        self.base + self.registers.len() as u16 + 2
+    }
+
+    fn finish(mut self) -> Vec<u16> {
+        self.registers.extend([0xFFFF, 0]);
+        self.registers
+    }
+}
+
+/// Encode `s` as SunSpec string registers starting at `offset`
+pub fn sunspec_string(offset: u16, s: &str, len: u16) -> Vec<(u16, u16)> {
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.resize(len as usize * 2, 0);
+    bytes
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| (offset + i as u16, u16::from_be_bytes([pair[0], pair[1]])))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -387,4 +516,407 @@ mod tests {
         server.set_response_delay(50);
         assert_eq!(server.response_delay_ms, 50);
     }
+
+    #[test]
+    fn test_crc16_reference_frame() {
+        // Read 10 holding registers from unit 1: 01 03 00 00 00 0A C5 CD
+        let crc = crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]);
+        assert_eq!(crc.to_le_bytes(), [0xC5, 0xCD]);
+    }
+}
+
+#[cfg(feature = "modbus")]
+mod sunspec_tests {
+    use super::*;
+    use open_energy_controller::domain::{Battery, Inverter, InverterStatus};
+    use open_energy_controller::hardware::modbus::{SunSpecBattery, SunSpecInverter, SunSpecMeter};
+    use open_energy_controller::modbus::sunspec::{model_id, SunSpecReader};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    /// Scale factor exponent as stored in a register
+    fn sf(exp: i16) -> u16 {
+        exp as u16
+    }
+
+    async fn start(server: MockModbusServer) -> (Arc<MockModbusServer>, String) {
+        let addr = server.addr.to_string();
+        let server = Arc::new(server);
+        tokio::spawn(Arc::clone(&server).start());
+        sleep(Duration::from_millis(50)).await;
+        (server, addr)
+    }
+
+    fn server(port: u16) -> MockModbusServer {
+        MockModbusServer::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port))
+    }
+
+    /// Hybrid inverter: 3-phase inverter, storage, MPPT, grid meter and battery bank
+    fn hybrid_image() -> (SunSpecImage, u16, u16) {
+        let mut common = sunspec_string(0, "Fronius", 16);
+        common.extend(sunspec_string(16, "Symo GEN24 10.0", 16));
+        common.extend(sunspec_string(48, "31234567", 16));
+
+        let image = SunSpecImage::new(40000)
+            .model(1, 66, &common)
+            .model(
+                103,
+                50,
+                &[
+                    (8, 2310),
+                    (11, sf(-1)),
+                    (12, 4200),
+                    (13, sf(0)),
+                    (14, 5001),
+                    (15, sf(-2)),
+                    (22, 0),
+                    (23, 50000),
+                    (24, sf(0)),
+                    (29, 4400),
+                    (30, sf(0)),
+                    (31, 412),
+                    (35, sf(-1)),
+                    (36, 4),
+                ],
+            )
+            .model(120, 26, &[(1, 10000), (2, sf(0)), (17, 10240), (18, sf(0))])
+            .model(121, 30, &[(0, 1000), (20, sf(1))]);
+        let controls = image.next_model_address();
+        let image = image.model(123, 24, &[(2, 1), (21, sf(-1))]);
+        let storage = image.next_model_address();
+        let image = image
+            .model(124, 24, &[(0, 5000), (16, sf(0)), (6, 80), (20, sf(0)), (23, sf(0))])
+            .model(
+                160,
+                48,
+                &[(2, sf(0)), (6, 2), (8 + 11, 2500), (28 + 11, 1900)],
+            )
+            .model(
+                203,
+                105,
+                &[(16, (-800i16) as u16), (17, (-300i16) as u16), (18, (-250i16) as u16), (19, (-250i16) as u16), (20, sf(0))],
+            )
+            .model(
+                802,
+                62,
+                &[(1, 1024), (51, sf(1)), (2, 5000), (3, 5000), (52, sf(0)), (9, 812), (54, sf(-1)), (11, 97), (56, sf(0)), (45, 1500), (61, sf(0))],
+            );
+        (image, controls, storage)
+    }
+
+    #[tokio::test]
+    async fn test_sunspec_discovery_walks_model_chain() {
+        let mock = server(15510);
+        let (image, _, _) = hybrid_image();
+        mock.load_sunspec(image).await;
+        let (_mock, addr) = start(mock).await;
+
+        let reader = SunSpecReader::connect(&addr, 1).await.unwrap();
+        let ids: Vec<u16> = reader.map().models.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![1, 103, 120, 121, 123, 124, 160, 203, 802]);
+        assert_eq!(reader.map().base_address, 40000);
+
+        let common = reader.common().await.unwrap();
+        assert_eq!(common.manufacturer, "Fronius");
+        assert_eq!(common.serial_number, "31234567");
+        assert!(reader.map().has(model_id::BATTERY_BASE));
+    }
+
+    #[tokio::test]
+    async fn test_sunspec_inverter_state_and_export_limit() {
+        let mock = server(15511);
+        let (image, controls, _) = hybrid_image();
+        mock.load_sunspec(image).await;
+        let (mock, addr) = start(mock).await;
+
+        let inverter = SunSpecInverter::new(SunSpecReader::connect(&addr, 1).await.unwrap())
+            .await
+            .unwrap();
+        let caps = inverter.capabilities();
+        assert_eq!(caps.rated_power_w, 10000.0);
+        assert_eq!(caps.mppt_channels, 2);
+        assert!(caps.supports_export_limit);
+
+        let state = inverter.read_state().await.unwrap();
+        assert_eq!(state.ac_output_power_w, 4200.0);
+        assert_eq!(state.pv_power_w, 4400.0);
+        assert!((state.ac_voltage_v - 231.0).abs() < 1e-9);
+        assert!((state.grid_frequency_hz - 50.01).abs() < 1e-9);
+        assert_eq!(state.total_energy_kwh, 50.0);
+        assert_eq!(state.status, InverterStatus::Normal);
+
+        // 2.5 kW of a 10 kW WMax = 25.0% at scale factor -1
+        inverter.set_export_limit(2500.0).await.unwrap();
+        assert_eq!(mock.get_holding_register(controls + 3).await, Some(250));
+        assert_eq!(mock.get_holding_register(controls + 7).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_sunspec_battery_and_meter() {
+        let mock = server(15512);
+        let (image, _, storage) = hybrid_image();
+        mock.load_sunspec(image).await;
+        let (mock, addr) = start(mock).await;
+        let reader = SunSpecReader::connect(&addr, 1).await.unwrap();
+
+        let battery = SunSpecBattery::new(reader.clone()).await.unwrap();
+        assert_eq!(battery.capabilities().capacity_kwh, 10.24);
+        let state = battery.read_state().await.unwrap();
+        assert_eq!(state.soc_percent, 81.2);
+        assert_eq!(state.power_w, -1500.0);
+        assert!((state.temperature_c - 41.2).abs() < 1e-9);
+
+        // Discharge 2.5 kW of a 5 kW WChaMax: OutWRte = 50%, StorCtl_Mod = discharge
+        battery.set_power(-2500.0).await.unwrap();
+        assert_eq!(mock.get_holding_register(storage + 10).await, Some(50));
+        assert_eq!(mock.get_holding_register(storage + 11).await, Some(0));
+        assert_eq!(mock.get_holding_register(storage + 3).await, Some(0b10));
+
+        let meter = SunSpecMeter::new(reader).unwrap().read_model().await.unwrap();
+        assert_eq!(meter.power_w, Some(-800.0));
+        assert_eq!(meter.phase_power_w, [Some(-300.0), Some(-250.0), Some(-250.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_sunspec_marker_at_alternate_base() {
+        let mock = server(15513);
+        mock.load_sunspec(SunSpecImage::new(50000).model(1, 66, &[]).model(201, 105, &[])).await;
+        let (_mock, addr) = start(mock).await;
+
+        let reader = SunSpecReader::connect(&addr, 1).await.unwrap();
+        assert_eq!(reader.map().base_address, 50000);
+        assert!(reader.map().is_meter());
+        assert!(SunSpecBattery::new(reader).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_no_sunspec_marker() {
+        let mock = server(15514);
+        mock.set_holding_registers(40000, &[1, 2]).await;
+        let (_mock, addr) = start(mock).await;
+
+        assert!(SunSpecReader::connect(&addr, 1).await.is_err());
+    }
+}
+
+#[cfg(feature = "modbus")]
+mod rtu_tests {
+    use super::*;
+    use open_energy_controller::config::SerialParity;
+    use open_energy_controller::modbus::client::{ModbusClient, SerialLine, Transport};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Instant;
+
+    async fn start_rtu_gateway(port: u16) -> (Arc<MockModbusServer>, Transport) {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+        let mut server = MockModbusServer::new(addr);
+        server.set_rtu_framing(true);
+        let server = Arc::new(server);
+        tokio::spawn(Arc::clone(&server).start());
+        sleep(Duration::from_millis(50)).await;
+
+        let transport = Transport::RtuOverTcp {
+            addr: addr.to_string(),
+            line: SerialLine::default(),
+        };
+        (server, transport)
+    }
+
+    #[tokio::test]
+    async fn test_rtu_over_tcp_read_write() {
+        let (mock, transport) = start_rtu_gateway(15520).await;
+        mock.set_holding_registers(100, &[11, 22, 33]).await;
+        mock.set_input_register(7, 0xBEEF).await;
+
+        let client = ModbusClient::connect_transport(transport, 3, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(client.read_holding_registers(100, 3).await.unwrap(), vec![11, 22, 33]);
+        assert_eq!(client.read_input_registers(7, 1).await.unwrap(), vec![0xBEEF]);
+
+        client.write_single_register(200, 42).await.unwrap();
+        client.write_multiple_registers(300, &[1, 2]).await.unwrap();
+        assert_eq!(mock.get_holding_register(200).await, Some(42));
+        assert_eq!(mock.get_holding_register(301).await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_rtu_bad_crc_rejected_then_recovers() {
+        let (mock, transport) = start_rtu_gateway(15521).await;
+        mock.set_holding_register(10, 1234).await;
+
+        let client = ModbusClient::connect_transport(transport, 1, Duration::from_millis(200))
+            .await
+            .unwrap();
+
+        mock.set_corrupt_crc(true).await;
+        assert!(client.read_holding_registers(10, 1).await.is_err());
+
+        mock.set_corrupt_crc(false).await;
+        assert_eq!(client.read_holding_registers(10, 1).await.unwrap(), vec![1234]);
+    }
+
+    #[tokio::test]
+    async fn test_rtu_inter_frame_delay() {
+        let (mock, _) = start_rtu_gateway(15522).await;
+        mock.set_holding_register(0, 1).await;
+
+        let transport = Transport::RtuOverTcp {
+            addr: "127.0.0.1:15522".to_string(),
+            line: SerialLine {
+                inter_frame_delay: Some(Duration::from_millis(80)),
+                ..SerialLine::default()
+            },
+        };
+        let client = ModbusClient::connect_transport(transport, 1, Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let started = Instant::now();
+        for _ in 0..3 {
+            client.read_holding_registers(0, 1).await.unwrap();
+        }
+        // The first request goes out immediately, the next two wait out the gap
+        assert!(started.elapsed() >= Duration::from_millis(160));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rtu_over_pty() {
+        use tokio_serial::SerialPort;
+
+        let (master, slave) = tokio_serial::SerialStream::pair().unwrap();
+        let path = slave.name().unwrap();
+
+        let mut server = MockModbusServer::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0));
+        server.set_rtu_framing(true);
+        let server = Arc::new(server);
+        server.set_holding_registers(37760, &[955]).await;
+        tokio::spawn(Arc::clone(&server).serve(master));
+
+        let transport = Transport::Rtu {
+            path,
+            line: SerialLine {
+                baud_rate: 19200,
+                parity: SerialParity::Even,
+                ..SerialLine::default()
+            },
+        };
+        let client = ModbusClient::connect_transport(transport, 1, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(client.read_holding_registers(37760, 1).await.unwrap(), vec![955]);
+
+        client.write_single_register(47075, 500).await.unwrap();
+        assert_eq!(server.get_holding_register(47075).await, Some(500));
+        drop(slave);
+    }
+}
+
+#[cfg(feature = "modbus")]
+mod inverter_tests {
+    use super::*;
+    use open_energy_controller::domain::{Inverter, InverterMode, InverterStatus};
+    use open_energy_controller::hardware::modbus::ModbusInverter;
+    use open_energy_controller::modbus::client::ModbusClient;
+    use open_energy_controller::modbus::register_map::HuaweiSun2000RegisterMap;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[tokio::test]
+    async fn test_huawei_inverter_state_and_controls() {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 15530);
+        let mock = Arc::new(MockModbusServer::new(addr));
+        mock.set_holding_registers(30072, &[2, 0, 10000]).await; // 2 MPPT, 10 kW rated
+        mock.set_holding_register(32016, 4125).await; // PV1 412.5 V
+        mock.set_holding_registers(32064, &[0, 6200]).await; // 6.2 kW DC
+        mock.set_holding_register(32069, 2318).await; // 231.8 V
+        mock.set_holding_registers(32080, &[0, 6000]).await; // 6.0 kW AC
+        mock.set_holding_registers(32085, &[5002, 0, 452, 0, 0x0200]).await; // Hz, -, 45.2 °C, -, on-grid
+        mock.set_holding_registers(32106, &[1, 0]).await; // 655.36 kWh lifetime
+        mock.set_holding_registers(32114, &[0, 2150]).await; // 21.5 kWh today
+        tokio::spawn(Arc::clone(&mock).start());
+        sleep(Duration::from_millis(50)).await;
+
+        let client = ModbusClient::connect(&addr.to_string(), 1).await.unwrap();
+        let inverter = ModbusInverter::with_client(client, Box::new(HuaweiSun2000RegisterMap))
+            .await
+            .unwrap();
+        let caps = inverter.capabilities();
+        assert_eq!(caps.rated_power_w, 10000.0);
+        assert_eq!(caps.mppt_channels, 2);
+
+        let state = inverter.read_state().await.unwrap();
+        assert_eq!(state.pv_power_w, 6200.0);
+        assert_eq!(state.ac_output_power_w, 6000.0);
+        assert!((state.dc_voltage_v - 412.5).abs() < 1e-9);
+        assert!((state.grid_frequency_hz - 50.02).abs() < 1e-9);
+        assert!((state.temperature_c - 45.2).abs() < 1e-9);
+        assert!((state.daily_energy_kwh - 21.5).abs() < 1e-9);
+        assert!((state.total_energy_kwh - 655.36).abs() < 1e-9);
+        assert_eq!(state.status, InverterStatus::Normal);
+        assert_eq!(state.mode, InverterMode::GridTied);
+
+        inverter.set_export_limit(3500.0).await.unwrap();
+        assert_eq!(mock.get_holding_register(47415).await, Some(5));
+        assert_eq!(mock.get_holding_register(47417).await, Some(3500));
+        assert!(inverter.set_export_limit(12000.0).await.is_err());
+
+        assert!(inverter.set_mode(InverterMode::OffGrid).await.is_err());
+        mock.set_holding_register(40201, 1).await;
+        inverter.set_mode(InverterMode::Standby).await.unwrap();
+        assert_eq!(mock.get_holding_register(40201).await, Some(0));
+    }
+}
+
+#[cfg(feature = "modbus")]
+mod meter_tests {
+    use super::*;
+    use open_energy_controller::domain::{GridMeter, GridStatus};
+    use open_energy_controller::hardware::modbus::ModbusGridMeter;
+    use open_energy_controller::modbus::client::ModbusClient;
+    use open_energy_controller::modbus::register_map::Sdm630RegisterMap;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    async fn set_f32(mock: &MockModbusServer, address: u16, value: f32) {
+        let bits = value.to_bits();
+        mock.set_input_register(address, (bits >> 16) as u16).await;
+        mock.set_input_register(address + 1, bits as u16).await;
+    }
+
+    #[tokio::test]
+    async fn test_sdm630_per_phase_reading() {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 15540);
+        let mock = Arc::new(MockModbusServer::new(addr));
+        for (i, (volts, amps, watts)) in [(231.5, 18.0, 4100.0), (229.0, 2.0, 450.0), (233.0, -1.5, -340.0)]
+            .into_iter()
+            .enumerate()
+        {
+            let offset = 2 * i as u16;
+            set_f32(&mock, offset, volts).await;
+            set_f32(&mock, 0x06 + offset, amps).await;
+            set_f32(&mock, 0x0C + offset, watts).await;
+        }
+        set_f32(&mock, 0x34, 4210.0).await;
+        set_f32(&mock, 0x46, 49.98).await;
+        set_f32(&mock, 0x48, 1234.5).await;
+        set_f32(&mock, 0x4A, 321.0).await;
+        tokio::spawn(Arc::clone(&mock).start());
+        sleep(Duration::from_millis(50)).await;
+
+        let client = ModbusClient::connect(&addr.to_string(), 1).await.unwrap();
+        let meter = ModbusGridMeter::with_client(client, Box::new(Sdm630RegisterMap)).unwrap();
+        let reading = meter.read().await.unwrap();
+
+        assert_eq!(reading.phases[0].voltage_v, 231.5);
+        assert_eq!(reading.phase_currents_a(), [18.0, 2.0, 1.5]);
+        assert_eq!(reading.phases[2].power_w, -340.0);
+        assert_eq!(reading.power_w, 4210.0);
+        assert!((reading.frequency_hz.unwrap() - 49.98).abs() < 1e-4);
+        assert_eq!(reading.import_energy_kwh, 1234.5);
+        assert_eq!(reading.export_energy_kwh, 321.0);
+
+        let connection = reading.to_connection();
+        assert_eq!(connection.status, GridStatus::Normal);
+        assert_eq!(connection.current_a, 18.0);
+        assert_eq!(connection.import_power_w, 4210.0);
+    }
 }